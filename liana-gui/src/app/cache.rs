@@ -11,7 +11,7 @@ use crate::{
     },
 };
 use liana::miniscript::bitcoin::Network;
-use lianad::commands::CoinStatus;
+use lianad::commands::{AddressDiscoveryProgress, CoinStatus};
 use std::sync::Arc;
 use std::time::Instant;
 
@@ -51,6 +51,10 @@ impl Cache {
         self.daemon_cache.rescan_progress
     }
 
+    pub fn address_discovery(&self) -> Option<AddressDiscoveryProgress> {
+        self.daemon_cache.address_discovery
+    }
+
     pub fn sync_progress(&self) -> f64 {
         self.daemon_cache.sync_progress
     }
@@ -66,6 +70,8 @@ pub struct DaemonCache {
     pub blockheight: i32,
     pub coins: Vec<Coin>,
     pub rescan_progress: Option<f64>,
+    /// The indexes reached by an ongoing address discovery, if any.
+    pub address_discovery: Option<AddressDiscoveryProgress>,
     pub sync_progress: f64,
     /// The most recent `last_poll_timestamp`.
     pub last_poll_timestamp: Option<u32>,
@@ -79,6 +85,7 @@ impl std::default::Default for DaemonCache {
             blockheight: 0,
             coins: Vec::new(),
             rescan_progress: None,
+            address_discovery: None,
             sync_progress: 1.0,
             last_poll_timestamp: None,
             last_tick: Instant::now(),
@@ -4,7 +4,7 @@ use std::sync::Arc;
 use liana::miniscript::bitcoin::{
     bip32::{ChildNumber, Fingerprint},
     psbt::Psbt,
-    Address, Txid,
+    Address, OutPoint, Txid,
 };
 use lianad::config::Config as DaemonConfig;
 
@@ -46,10 +46,14 @@ pub enum Message {
     Coins(Result<Vec<Coin>, Error>),
     /// When we want both coins and tip height together.
     CoinsTipHeight(Result<Vec<Coin>, Error>, Result<i32, Error>),
+    /// A coin was frozen (`true`) or unfrozen (`false`).
+    CoinFrozen(Result<(OutPoint, bool), Error>),
     Labels(Result<HashMap<String, String>, Error>),
     SpendTxs(Result<Vec<SpendTx>, Error>),
     Psbt(Result<(Psbt, Vec<String>), Error>),
+    ImportedPayments(Result<Vec<Destination>, Error>),
     RbfPsbt(Result<Txid, Error>),
+    CpfpPsbt(Result<Txid, Error>),
     Recovery(Result<SpendTx, Error>),
     Signed(Fingerprint, Result<Psbt, Error>),
     WalletUpdated(Result<Arc<Wallet>, Error>),
@@ -66,6 +70,8 @@ pub enum Message {
     LabelsUpdated(Result<HashMap<String, Option<String>>, Error>),
     BroadcastModal(Result<HashSet<Txid>, Error>),
     RbfModal(Box<HistoryTransaction>, bool, Result<HashSet<Txid>, Error>),
+    /// The unconfirmed coins of the transaction that can be spent by a CPFP child.
+    CpfpModal(Box<HistoryTransaction>, Result<Vec<OutPoint>, Error>),
     Export(ImportExportMessage),
 }
 
@@ -42,7 +42,10 @@ use crate::{
         settings::{LianaSettings, SettingsTrait, SettingsUI, WalletId},
         wallet::Wallet,
     },
-    daemon::{embedded::EmbeddedDaemon, Daemon, DaemonBackend, DaemonError},
+    daemon::{
+        embedded::{self, EmbeddedDaemon},
+        Daemon, DaemonBackend, DaemonError,
+    },
     dir::LianaDirectory,
     node::{bitcoind::Bitcoind, NodeType},
 };
@@ -392,6 +395,7 @@ impl<S: SettingsTrait> App<S> {
                         blockheight: info.block_height,
                         coins: coins.coins,
                         rescan_progress: info.rescan_progress,
+                        address_discovery: info.address_discovery,
                         sync_progress: info.sync,
                         last_poll_timestamp: info.last_poll_timestamp,
                         last_tick: tick,
@@ -494,6 +498,7 @@ impl<S: SettingsTrait> App<S> {
     ) -> Result<(), Error> {
         Handle::current().block_on(async { self.daemon.stop().await })?;
         let network = cfg.bitcoin_config.network;
+        embedded::update_host_backend(&cfg)?;
         let daemon = EmbeddedDaemon::start(cfg)?;
         self.daemon = Arc::new(daemon);
         let mut daemon_config_path = datadir_path
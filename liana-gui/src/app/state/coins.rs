@@ -148,6 +148,32 @@ impl State for CoinsPanel {
                     self.selected.push(i);
                 }
             }
+            Message::View(view::Message::ToggleFreezeCoin(i)) => {
+                if let Some(coin) = self.coins.list.get(i) {
+                    let (outpoint, freeze) = (coin.outpoint, !coin.is_frozen);
+                    return Task::perform(
+                        async move {
+                            if freeze {
+                                daemon.freeze_coins(&[outpoint]).await?;
+                            } else {
+                                daemon.unfreeze_coins(&[outpoint]).await?;
+                            }
+                            Ok((outpoint, freeze))
+                        },
+                        Message::CoinFrozen,
+                    );
+                }
+            }
+            Message::CoinFrozen(res) => match res {
+                Err(e) => self.warning = Some(e),
+                Ok((outpoint, is_frozen)) => {
+                    self.warning = None;
+                    if let Some(coin) = self.coins.list.iter_mut().find(|c| c.outpoint == outpoint)
+                    {
+                        coin.is_frozen = is_frozen;
+                    }
+                }
+            },
             _ => {}
         };
         Task::none()
@@ -228,6 +254,8 @@ mod tests {
                 derivation_index: 0.into(),
                 is_change: false,
                 is_from_self: false,
+                is_frozen: false,
+                time_since_confirmation: None,
             },
             Coin {
                 outpoint: bitcoin::OutPoint { txid, vout: 3 },
@@ -239,6 +267,8 @@ mod tests {
                 derivation_index: 1.into(),
                 is_change: false,
                 is_from_self: false,
+                is_frozen: false,
+                time_since_confirmation: None,
             },
             Coin {
                 outpoint: bitcoin::OutPoint { txid, vout: 0 },
@@ -250,6 +280,8 @@ mod tests {
                 derivation_index: 2.into(),
                 is_change: false,
                 is_from_self: false,
+                is_frozen: false,
+                time_since_confirmation: None,
             },
             Coin {
                 outpoint: bitcoin::OutPoint { txid, vout: 1 },
@@ -261,6 +293,8 @@ mod tests {
                 derivation_index: 3.into(),
                 is_change: false,
                 is_from_self: false,
+                is_frozen: false,
+                time_since_confirmation: None,
             },
         ]);
 
@@ -4,6 +4,7 @@ use std::{
 };
 
 use iced::{Subscription, Task};
+use liana::descriptors::ExportFormat;
 use liana_ui::{widget::modal::Modal, widget::Element};
 use tokio::task::JoinHandle;
 
@@ -73,17 +74,27 @@ impl ExportModal {
     pub fn modal_title(&self) -> &'static str {
         match self.import_export_type {
             ImportExportType::Transactions => "Export Transactions",
+            ImportExportType::AccountingReport { .. } => "Export Accounting Report",
             ImportExportType::ExportPsbt(_) => "Export PSBT",
             ImportExportType::ExportXpub(_) => "Export Xpub",
             ImportExportType::ImportXpub(_) => "Import Xpub",
             ImportExportType::ExportProcessBackup(..) => "Export Backup",
             ImportExportType::ExportEncryptedDescriptor(_) => "Export Encrypted Descriptor",
+            ImportExportType::ExportBsmsRecord(..) => "Export BSMS Descriptor Record",
             ImportExportType::Descriptor(_) => "Export Descriptor",
+            ImportExportType::SignerDescriptor { format, .. } => match format {
+                ExportFormat::Coldcard => "Export Coldcard Descriptor",
+                ExportFormat::Bip388 => "Export Wallet Policy",
+                ExportFormat::Specter => "Export Specter Wallet",
+                ExportFormat::Bsms => "Export BSMS Record",
+            },
             ImportExportType::ExportLabels => "Export Labels",
             ImportExportType::ImportPsbt(_) => "Import PSBT",
             ImportExportType::ImportDescriptor => "Import Descriptor",
             ImportExportType::ImportBackup { .. } => "Restore Backup",
             ImportExportType::FromBackup => "Import existing wallet from backup",
+            ImportExportType::ExportProofOfReserves(_) => "Export Proof of Reserves",
+            ImportExportType::VerifyProofOfReserves(_) => "Verify Proof of Reserves",
         }
     }
 
@@ -93,6 +104,9 @@ impl ExportModal {
             ImportExportType::Transactions => {
                 format!("liana-txs-{date}.csv")
             }
+            ImportExportType::AccountingReport { method, format, .. } => {
+                format!("liana-accounting-{format}-{method}-{date}.csv")
+            }
             ImportExportType::ExportPsbt(_) => "psbt.psbt".into(),
             ImportExportType::ExportXpub(_) | ImportExportType::ImportXpub(_) => "liana.pub".into(),
             ImportExportType::Descriptor(descriptor) => {
@@ -104,7 +118,21 @@ impl ExportModal {
                     .to_string();
                 format!("liana-{}.txt", checksum)
             }
+            ImportExportType::SignerDescriptor {
+                descriptor, format, ..
+            } => {
+                let checksum = descriptor
+                    .to_string()
+                    .split_once('#')
+                    .map(|(_, checksum)| checksum)
+                    .expect("cannot fail")
+                    .to_string();
+                format!("liana-{}-{}.{}", checksum, format, format.file_extension())
+            }
             ImportExportType::ExportEncryptedDescriptor(_) => "liana.bed".into(),
+            ImportExportType::ExportBsmsRecord(fingerprint, _) => {
+                format!("liana-{}.bsms", fingerprint)
+            }
             ImportExportType::ImportPsbt(_) => "psbt.psbt".into(),
             ImportExportType::ImportDescriptor => "descriptor.txt".into(),
             ImportExportType::ExportLabels => format!("liana-labels-{date}.jsonl"),
@@ -114,6 +142,10 @@ impl ExportModal {
             ImportExportType::FromBackup | ImportExportType::ImportBackup { .. } => {
                 "liana-backup.json".to_string()
             }
+            ImportExportType::ExportProofOfReserves(_)
+            | ImportExportType::VerifyProofOfReserves(_) => {
+                format!("liana-proof-of-reserves-{date}.psbt")
+            }
         }
     }
 
@@ -191,6 +223,11 @@ impl ExportModal {
                         ImportExportMessage::UpdateAliases(map.clone()).into()
                     });
                 }
+                Progress::ProofOfReserves(res) => {
+                    return Task::perform(async {}, move |_| {
+                        ImportExportMessage::ProofOfReserves(res.clone()).into()
+                    });
+                }
                 Progress::WalletFromBackup(_) | Progress::EncryptedFile(_) | Progress::Psbt(_) => {}
             },
             ImportExportMessage::TimedOut => {
@@ -274,6 +311,7 @@ impl ExportModal {
             }
             ImportExportMessage::UpdateAliases(_) => { /* unexpected */ }
             ImportExportMessage::Xpub(_) => { /* unexpected */ }
+            ImportExportMessage::ProofOfReserves(_) => { /* unexpected */ }
         }
         Task::none()
     }
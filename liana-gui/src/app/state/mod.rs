@@ -506,6 +506,8 @@ mod tests {
             is_immature: false,
             is_change: false,
             is_from_self: false,
+            is_frozen: false,
+            time_since_confirmation: None,
             spend_info: Some(LCSpendInfo {
                 txid: dummy_txid,
                 height: None,
@@ -526,6 +528,8 @@ mod tests {
             is_immature: false,
             is_change: true,
             is_from_self: false,
+            is_frozen: false,
+            time_since_confirmation: None,
             spend_info: None,
         });
         // Included in unconfirmed balance. Other values remain the same.
@@ -543,6 +547,8 @@ mod tests {
             is_immature: false,
             is_change: false,
             is_from_self: true,
+            is_frozen: false,
+            time_since_confirmation: None,
             spend_info: None,
         });
         // Included in confirmed balance. Other values remain the same.
@@ -565,6 +571,8 @@ mod tests {
             is_immature: false,
             is_change: false,
             is_from_self: false,
+            is_frozen: false,
+            time_since_confirmation: None,
             spend_info: None,
         });
         // Coin is added to confirmed balance. Not expiring, but remaining seq is set.
@@ -600,6 +608,8 @@ mod tests {
             is_immature: false,
             is_change: false,
             is_from_self: false,
+            is_frozen: false,
+            time_since_confirmation: None,
             spend_info: None,
         });
         // Only confirmed balance has changed.
@@ -622,6 +632,8 @@ mod tests {
             is_immature: false,
             is_change: false,
             is_from_self: false,
+            is_frozen: false,
+            time_since_confirmation: None,
             spend_info: None,
         });
         // Confirmed balance updated, as well as expiring coins and the remaining seq.
@@ -1,20 +1,21 @@
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::Duration;
 
-use iced::Subscription;
+use iced::{widget::qr_code, Subscription};
 
 use iced::Task;
 use liana::{
-    descriptors::LianaPolicy,
+    descriptors::{musig, LianaPolicy},
     miniscript::bitcoin::{bip32::Fingerprint, psbt::Psbt, Network, Txid},
 };
 use lianad::commands::CoinStatus;
 
-use liana_ui::component::toast;
+use liana_ui::component::{form, toast};
 use liana_ui::{widget::modal, widget::Element};
 
 use crate::daemon::model::LabelsLoader;
-use crate::export::{ImportExportMessage, ImportExportType, Progress};
+use crate::export::{self, ImportExportMessage, ImportExportType, Progress};
 use crate::{
     app::{
         cache::Cache,
@@ -30,6 +31,8 @@ use crate::{
     },
     dir::LianaDirectory,
     hw::{HardwareWallet, HardwareWallets},
+    qr,
+    signer::LockedSigner,
 };
 
 use super::export::ExportModal;
@@ -58,6 +61,8 @@ pub enum PsbtModal {
     Broadcast(BroadcastModal),
     Delete(DeleteModal),
     Export(ExportModal),
+    ExportQr(ExportQrModal),
+    ImportQr(ImportQrModal),
 }
 
 impl<'a> AsRef<dyn Modal + 'a> for PsbtModal {
@@ -68,6 +73,8 @@ impl<'a> AsRef<dyn Modal + 'a> for PsbtModal {
             Self::Broadcast(a) => a,
             Self::Delete(a) => a,
             Self::Export(a) => a,
+            Self::ExportQr(a) => a,
+            Self::ImportQr(a) => a,
         }
     }
 }
@@ -80,6 +87,8 @@ impl<'a> AsMut<dyn Modal + 'a> for PsbtModal {
             Self::Broadcast(a) => a,
             Self::Delete(a) => a,
             Self::Export(a) => a,
+            Self::ExportQr(a) => a,
+            Self::ImportQr(a) => a,
         }
     }
 }
@@ -154,6 +163,16 @@ impl PsbtState {
                     return launch;
                 }
             }
+            Message::View(view::Message::ExportPsbtQr) => {
+                if self.modal.is_none() {
+                    self.modal = Some(PsbtModal::ExportQr(ExportQrModal::new(&self.tx.psbt)));
+                }
+            }
+            Message::View(view::Message::ImportPsbtQr) => {
+                if self.modal.is_none() {
+                    self.modal = Some(PsbtModal::ImportQr(ImportQrModal::new(self.wallet.clone())));
+                }
+            }
             Message::View(view::Message::ImportExport(ImportExportMessage::Close)) => {
                 if matches!(self.modal, Some(PsbtModal::Export(_))) {
                     self.modal = None;
@@ -261,7 +280,10 @@ impl PsbtState {
                 }
             },
             Message::Export(ImportExportMessage::Progress(Progress::Psbt(psbt))) => {
-                merge_signatures(&mut self.tx.psbt, &psbt);
+                if let Err(e) = merge_signatures(&mut self.tx.psbt, &psbt) {
+                    self.warning = Some(e);
+                    return Task::none();
+                }
                 self.tx.sigs = self
                     .wallet
                     .main_descriptor
@@ -451,6 +473,226 @@ impl Modal for DeleteModal {
     }
 }
 
+/// The time each part of the animated QR code of a PSBT is displayed.
+const QR_PART_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Display the PSBT as an animated QR code to be scanned by an air-gapped signer.
+pub struct ExportQrModal {
+    encoder: Option<qr::PsbtQrEncoder>,
+    qr_code: Option<qr_code::Data>,
+    error: Option<qr::Error>,
+}
+
+impl ExportQrModal {
+    pub fn new(psbt: &Psbt) -> Self {
+        match qr::PsbtQrEncoder::new(psbt) {
+            Ok(encoder) => Self {
+                qr_code: qr_code::Data::new(encoder.part()).ok(),
+                encoder: Some(encoder),
+                error: None,
+            },
+            Err(e) => Self {
+                encoder: None,
+                qr_code: None,
+                error: Some(e),
+            },
+        }
+    }
+}
+
+impl Modal for ExportQrModal {
+    fn subscription(&self) -> Subscription<Message> {
+        if self.encoder.is_some() {
+            iced::time::every(QR_PART_INTERVAL)
+                .map(|_| Message::View(view::Message::PsbtQr(view::PsbtQrMessage::NextPart)))
+        } else {
+            Subscription::none()
+        }
+    }
+
+    fn update(
+        &mut self,
+        _daemon: Arc<dyn Daemon + Sync + Send>,
+        message: Message,
+        _tx: &mut SpendTx,
+    ) -> Task<Message> {
+        if let Message::View(view::Message::PsbtQr(view::PsbtQrMessage::NextPart)) = message {
+            if let Some(encoder) = &mut self.encoder {
+                if let Err(e) = encoder.next_part() {
+                    self.error = Some(e);
+                    self.encoder = None;
+                } else {
+                    self.qr_code = qr_code::Data::new(encoder.part()).ok();
+                }
+            }
+        }
+        Task::none()
+    }
+
+    fn view<'a>(&'a self, content: Element<'a, view::Message>) -> Element<'a, view::Message> {
+        modal::Modal::new(
+            content,
+            view::psbt::export_qr_modal(
+                self.qr_code.as_ref(),
+                self.encoder
+                    .as_ref()
+                    .map(|e| e.fragment_count())
+                    .unwrap_or(0),
+                self.error.as_ref(),
+            ),
+        )
+        .on_blur(Some(view::Message::Spend(view::SpendTxMessage::Cancel)))
+        .into()
+    }
+}
+
+/// Import the signatures of a PSBT from the QR codes displayed by an air-gapped signer, either
+/// pasted as UR text or scanned from images.
+pub struct ImportQrModal {
+    wallet: Arc<Wallet>,
+    decoder: qr::PsbtQrDecoder,
+    ur: form::Value<String>,
+    imported: bool,
+    error: Option<Error>,
+}
+
+impl ImportQrModal {
+    pub fn new(wallet: Arc<Wallet>) -> Self {
+        Self {
+            wallet,
+            decoder: qr::PsbtQrDecoder::default(),
+            ur: form::Value::default(),
+            imported: false,
+            error: None,
+        }
+    }
+
+    fn receive<'a>(&mut self, parts: impl Iterator<Item = &'a str>) -> Result<(), qr::Error> {
+        for part in parts {
+            self.decoder.receive(part)?;
+        }
+        Ok(())
+    }
+
+    /// Merge the signatures of the PSBT once all its parts were received, and save it.
+    fn import(
+        &mut self,
+        daemon: Arc<dyn Daemon + Sync + Send>,
+        tx: &mut SpendTx,
+    ) -> Result<Task<Message>, Error> {
+        let psbt = match self.decoder.psbt() {
+            Ok(Some(psbt)) => psbt,
+            Ok(None) => return Ok(Task::none()),
+            Err(e) => return Err(Error::Unexpected(e.to_string())),
+        };
+        if psbt.unsigned_tx.compute_txid() != tx.psbt.unsigned_tx.compute_txid() {
+            return Err(Error::ImportExport(export::Error::TxidNotMatch));
+        }
+        self.wallet
+            .main_descriptor
+            .partial_spend_info(&psbt)
+            .map_err(|_| Error::ImportExport(export::Error::InsanePsbt))?;
+        merge_signatures(&mut tx.psbt, &psbt)?;
+        tx.sigs = self
+            .wallet
+            .main_descriptor
+            .partial_spend_info(&tx.psbt)
+            .map_err(|_| Error::ImportExport(export::Error::InsanePsbt))?;
+        Ok(Task::perform(
+            async move { daemon.update_spend_tx(&psbt).await.map_err(|e| e.into()) },
+            Message::Updated,
+        ))
+    }
+}
+
+impl Modal for ImportQrModal {
+    fn update(
+        &mut self,
+        daemon: Arc<dyn Daemon + Sync + Send>,
+        message: Message,
+        tx: &mut SpendTx,
+    ) -> Task<Message> {
+        match message {
+            Message::View(view::Message::PsbtQr(view::PsbtQrMessage::UrEdited(ur))) => {
+                self.ur.value = ur;
+                self.ur.valid = true;
+            }
+            Message::View(view::Message::PsbtQr(view::PsbtQrMessage::SelectImages)) => {
+                self.error = None;
+                return Task::perform(scan_qr_images(), |res| {
+                    Message::View(view::Message::PsbtQr(view::PsbtQrMessage::ImagesScanned(
+                        res,
+                    )))
+                });
+            }
+            Message::View(view::Message::PsbtQr(view::PsbtQrMessage::ImagesScanned(res))) => {
+                match res.and_then(|contents| {
+                    self.receive(contents.iter().map(|c| c.as_str()))
+                        .map_err(|e| e.to_string())
+                }) {
+                    Ok(()) => match self.import(daemon, tx) {
+                        Ok(task) => return task,
+                        Err(e) => self.error = Some(e),
+                    },
+                    Err(e) => self.error = Some(Error::Unexpected(e)),
+                }
+            }
+            Message::View(view::Message::PsbtQr(view::PsbtQrMessage::Confirm)) => {
+                self.error = None;
+                let ur = std::mem::take(&mut self.ur.value);
+                match self.receive(ur.split_whitespace()) {
+                    Ok(()) => match self.import(daemon, tx) {
+                        Ok(task) => return task,
+                        Err(e) => self.error = Some(e),
+                    },
+                    Err(e) => {
+                        self.ur.value = ur;
+                        self.ur.valid = false;
+                        self.error = Some(Error::Unexpected(e.to_string()));
+                    }
+                }
+            }
+            Message::Updated(res) => match res {
+                Ok(()) => self.imported = true,
+                Err(e) => self.error = Some(e),
+            },
+            _ => {}
+        }
+        Task::none()
+    }
+
+    fn view<'a>(&'a self, content: Element<'a, view::Message>) -> Element<'a, view::Message> {
+        modal::Modal::new(
+            content,
+            view::psbt::import_qr_modal(
+                &self.ur,
+                self.decoder.progress(),
+                self.error.as_ref(),
+                self.imported,
+            ),
+        )
+        .on_blur(Some(view::Message::Spend(view::SpendTxMessage::Cancel)))
+        .into()
+    }
+}
+
+/// Ask the user for pictures of QR codes and return the content of the QR codes found in them.
+async fn scan_qr_images() -> Result<Vec<String>, String> {
+    let Some(files) = rfd::AsyncFileDialog::new()
+        .set_title("Choose pictures of the QR codes...")
+        .add_filter("Images", &["png", "jpg", "jpeg"])
+        .pick_files()
+        .await
+    else {
+        return Ok(Vec::new());
+    };
+    let mut contents = Vec::new();
+    for file in files {
+        contents.extend(qr::scan_image(file.path()).map_err(|e| e.to_string())?);
+    }
+    Ok(contents)
+}
+
 pub struct SignModal {
     wallet: Arc<Wallet>,
     hws: HardwareWallets,
@@ -460,6 +702,8 @@ pub struct SignModal {
     is_saved: bool,
     display_modal: bool,
     recovery_timelock: Option<u16>,
+    // Set when the user is asked the password of the locked hot signer.
+    hot_signer_password: Option<form::Value<String>>,
 }
 
 impl SignModal {
@@ -480,6 +724,7 @@ impl SignModal {
             is_saved,
             display_modal: true,
             recovery_timelock,
+            hot_signer_password: None,
         }
     }
 
@@ -518,25 +763,80 @@ impl Modal for SignModal {
                 }
             }
             Message::View(view::Message::Spend(view::SpendTxMessage::SelectHotSigner)) => {
+                // The mnemonic of the hot signer is encrypted, ask for its password first.
+                if self.wallet.signer.is_none() && self.wallet.locked_signer.is_some() {
+                    self.hot_signer_password = Some(form::Value::default());
+                    return Task::none();
+                }
                 return Task::perform(
                     sign_psbt_with_hot_signer(self.wallet.clone(), tx.psbt.clone()),
                     |(fg, res)| Message::Signed(fg, res),
                 );
             }
+            Message::View(view::Message::Spend(view::SpendTxMessage::HotSignerPasswordEdited(
+                password,
+            ))) => {
+                if let Some(value) = &mut self.hot_signer_password {
+                    value.value = password;
+                    value.valid = true;
+                }
+            }
+            Message::View(view::Message::Spend(view::SpendTxMessage::UnlockHotSigner)) => {
+                if let (Some(signer), Some(password)) =
+                    (&self.wallet.locked_signer, &self.hot_signer_password)
+                {
+                    let fingerprint = signer.fingerprint();
+                    return Task::perform(
+                        sign_psbt_with_locked_signer(
+                            signer.clone(),
+                            password.value.clone(),
+                            tx.psbt.clone(),
+                        ),
+                        move |res| Message::Signed(fingerprint, res),
+                    );
+                }
+            }
             Message::Signed(fingerprint, res) => {
                 self.signing.remove(&fingerprint);
+                let is_locked_signer = self
+                    .wallet
+                    .locked_signer
+                    .as_ref()
+                    .is_some_and(|s| s.fingerprint() == fingerprint);
                 match res {
                     Err(e) => {
                         self.display_modal = true;
+                        if is_locked_signer {
+                            if let Some(password) = &mut self.hot_signer_password {
+                                password.valid = false;
+                            }
+                        }
                         if !matches!(e, Error::HardwareWallet(async_hwi::Error::UserRefused)) {
                             self.error = Some(e)
                         }
                     }
                     Ok(psbt) => {
+                        if is_locked_signer {
+                            self.hot_signer_password = None;
+                        }
+                        if let Err(e) = merge_signatures(&mut tx.psbt, &psbt) {
+                            self.error = Some(e);
+                            return Task::none();
+                        }
+                        tx.sigs = match self.wallet.main_descriptor.partial_spend_info(&tx.psbt) {
+                            Ok(sigs) => sigs,
+                            Err(e) => {
+                                self.error = Some(Error::Unexpected(e.to_string()));
+                                return Task::none();
+                            }
+                        };
                         self.error = None;
-                        self.signed.insert(fingerprint);
+                        // A MuSig2 participant only provides its nonces in the first round, it
+                        // must be offered to sign again for the second one.
+                        if tx.signers().contains(&fingerprint) {
+                            self.signed.insert(fingerprint);
+                        }
                         let daemon = daemon.clone();
-                        merge_signatures(&mut tx.psbt, &psbt);
                         if self.is_saved {
                             return Task::perform(
                                 async move { daemon.update_spend_tx(&psbt).await.map_err(|e| e.into()) },
@@ -595,11 +895,11 @@ impl Modal for SignModal {
                     self.error.as_ref(),
                     &self.hws.list,
                     &self.wallet.main_descriptor,
-                    self.wallet.signer.as_ref().map(|s| s.fingerprint()),
+                    self.wallet.hot_signer_fingerprint(),
                     self.wallet
-                        .signer
-                        .as_ref()
-                        .and_then(|signer| self.wallet.keys_aliases.get(&signer.fingerprint)),
+                        .hot_signer_fingerprint()
+                        .and_then(|fingerprint| self.wallet.keys_aliases.get(&fingerprint)),
+                    self.hot_signer_password.as_ref(),
                     &self.signed,
                     &self.signing,
                     self.recovery_timelock,
@@ -613,9 +913,12 @@ impl Modal for SignModal {
     }
 }
 
-fn merge_signatures(psbt: &mut Psbt, signed_psbt: &Psbt) {
+/// Merge the signatures and MuSig2 session fields of `signed_psbt` into `psbt`. If a MuSig2 nonce
+/// or partial signature conflicts with an existing one, `psbt` is left untouched.
+fn merge_signatures(psbt: &mut Psbt, signed_psbt: &Psbt) -> Result<(), Error> {
+    let mut merged = psbt.clone();
     for i in 0..signed_psbt.inputs.len() {
-        let psbtin = match psbt.inputs.get_mut(i) {
+        let psbtin = match merged.inputs.get_mut(i) {
             Some(psbtin) => psbtin,
             None => continue,
         };
@@ -632,7 +935,11 @@ fn merge_signatures(psbt: &mut Psbt, signed_psbt: &Psbt) {
         if let Some(sig) = signed_psbtin.tap_key_sig {
             psbtin.tap_key_sig = Some(sig);
         }
+        musig::merge_session_fields(psbtin, signed_psbtin)
+            .map_err(|e| Error::Unexpected(e.to_string()))?;
     }
+    *psbt = merged;
+    Ok(())
 }
 
 async fn sign_psbt_with_hot_signer(
@@ -653,6 +960,17 @@ async fn sign_psbt_with_hot_signer(
     }
 }
 
+async fn sign_psbt_with_locked_signer(
+    signer: Arc<LockedSigner>,
+    password: String,
+    psbt: Psbt,
+) -> Result<Psbt, Error> {
+    signer
+        .sign_psbt(&password, psbt)
+        .map_err(|e| WalletError::HotSigner(format!("Hot signer failed to sign psbt: {}", e)))
+        .map_err(|e| e.into())
+}
+
 async fn sign_psbt(
     wallet: Arc<Wallet>,
     hw: std::sync::Arc<dyn async_hwi::HWI + Send + Sync>,
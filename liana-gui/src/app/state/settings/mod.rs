@@ -8,6 +8,7 @@ use std::sync::Arc;
 use iced::{Subscription, Task};
 
 use liana_ui::{component::form, widget::Element};
+use lianad::accounting::CostBasisMethod;
 
 use bitcoind::BitcoindSettingsState;
 use wallet::{update_aliases, WalletSettingsState};
@@ -23,9 +24,10 @@ use crate::{
         wallet::Wallet,
         Config,
     },
-    daemon::{Daemon, DaemonBackend},
+    daemon::{model::VerifyProofOfReservesResult, Daemon, DaemonBackend},
     dir::LianaDirectory,
     export::{ImportExportMessage, ImportExportType},
+    services::fiat::history::HISTORICAL_PRICES_FILE_NAME,
 };
 
 use super::export::ExportModal;
@@ -248,6 +250,9 @@ pub struct ImportExportSettingsState {
     modal: Option<ExportModal>,
     wallet: Arc<Wallet>,
     config: Arc<Config>,
+    cost_basis_method: CostBasisMethod,
+    reserves_message: form::Value<String>,
+    proven_reserves: Option<VerifyProofOfReservesResult>,
 }
 
 impl ImportExportSettingsState {
@@ -255,6 +260,9 @@ impl ImportExportSettingsState {
         Self {
             warning: None,
             modal: None,
+            cost_basis_method: CostBasisMethod::default(),
+            reserves_message: form::Value::default(),
+            proven_reserves: None,
             wallet,
             config,
         }
@@ -271,7 +279,13 @@ macro_rules! launch {
 
 impl State for ImportExportSettingsState {
     fn view<'a>(&'a self, cache: &'a Cache) -> Element<'a, view::Message> {
-        let content = view::settings::import_export(cache, self.warning.as_ref());
+        let content = view::settings::import_export(
+            cache,
+            self.warning.as_ref(),
+            self.cost_basis_method,
+            &self.reserves_message,
+            self.proven_reserves.as_ref(),
+        );
         if let Some(modal) = &self.modal {
             modal.view(content)
         } else {
@@ -315,6 +329,8 @@ impl State for ImportExportSettingsState {
                         ),
                         Message::WalletUpdated,
                     );
+                } else if let ImportExportMessage::ProofOfReserves(res) = m {
+                    self.proven_reserves = Some(res);
                 } else if let Some(modal) = self.modal.as_mut() {
                     return modal.update(m);
                 };
@@ -348,18 +364,111 @@ impl State for ImportExportSettingsState {
                     launch!(self, modal, true);
                 }
             }
+            Message::View(view::Message::Settings(
+                view::SettingsMessage::ExportSignerDescriptor(format),
+            )) => {
+                if self.modal.is_none() {
+                    let modal = ExportModal::new(
+                        Some(daemon),
+                        ImportExportType::SignerDescriptor {
+                            descriptor: Box::new(self.wallet.main_descriptor.clone()),
+                            format,
+                            name: self
+                                .wallet
+                                .alias
+                                .clone()
+                                .unwrap_or_else(|| "Liana".to_string()),
+                            network: cache.network,
+                        },
+                    );
+                    launch!(self, modal, true);
+                }
+            }
             Message::View(view::Message::Settings(view::SettingsMessage::ExportTransactions)) => {
                 if self.modal.is_none() {
                     let modal = ExportModal::new(Some(daemon), ImportExportType::Transactions);
                     launch!(self, modal, true);
                 }
             }
+            Message::View(view::Message::Settings(
+                view::SettingsMessage::CostBasisMethodSelected(method),
+            )) => {
+                self.cost_basis_method = method;
+            }
+            Message::View(view::Message::Settings(
+                view::SettingsMessage::ExportAccountingReport(format),
+            )) => {
+                if self.modal.is_none() {
+                    let fiat = self
+                        .wallet
+                        .fiat_price_setting
+                        .as_ref()
+                        .filter(|sett| sett.is_enabled)
+                        .map(|sett| (sett.source, sett.currency));
+                    let prices_path = cache
+                        .datadir_path
+                        .network_directory(cache.network)
+                        .path()
+                        .join(HISTORICAL_PRICES_FILE_NAME);
+                    let modal = ExportModal::new(
+                        Some(daemon),
+                        ImportExportType::AccountingReport {
+                            method: self.cost_basis_method,
+                            format,
+                            fiat,
+                            prices_path,
+                        },
+                    );
+                    launch!(self, modal, true);
+                }
+            }
             Message::View(view::Message::Settings(view::SettingsMessage::ExportLabels)) => {
                 if self.modal.is_none() {
                     let modal = ExportModal::new(Some(daemon), ImportExportType::ExportLabels);
                     launch!(self, modal, true);
                 }
             }
+            Message::View(view::Message::Settings(
+                view::SettingsMessage::ProofOfReservesMessageEdited(msg),
+            )) => {
+                self.reserves_message.valid = !msg.is_empty();
+                self.reserves_message.value = msg;
+            }
+            Message::View(view::Message::Settings(
+                view::SettingsMessage::ExportProofOfReserves,
+            )) => {
+                if self.modal.is_none() {
+                    if self.reserves_message.value.is_empty() {
+                        self.reserves_message.valid = false;
+                    } else {
+                        let modal = ExportModal::new(
+                            Some(daemon),
+                            ImportExportType::ExportProofOfReserves(
+                                self.reserves_message.value.clone(),
+                            ),
+                        );
+                        launch!(self, modal, true);
+                    }
+                }
+            }
+            Message::View(view::Message::Settings(
+                view::SettingsMessage::VerifyProofOfReserves,
+            )) => {
+                if self.modal.is_none() {
+                    if self.reserves_message.value.is_empty() {
+                        self.reserves_message.valid = false;
+                    } else {
+                        self.proven_reserves = None;
+                        let modal = ExportModal::new(
+                            Some(daemon),
+                            ImportExportType::VerifyProofOfReserves(
+                                self.reserves_message.value.clone(),
+                            ),
+                        );
+                        launch!(self, modal, false);
+                    }
+                }
+            }
             Message::View(view::Message::Settings(view::SettingsMessage::ExportWallet)) => {
                 if self.modal.is_none() {
                     let datadir = cache.datadir_path.clone();
@@ -7,6 +7,7 @@ use iced::{Subscription, Task};
 use liana::{
     descriptors::LianaDescriptor,
     miniscript::bitcoin::{bip32::Fingerprint, Network},
+    signer::EncryptedMnemonic,
 };
 
 use liana_ui::{
@@ -22,7 +23,7 @@ use crate::{
         settings::{self, update_settings_file, LianaSettings},
         state::{export::ExportModal, State},
         view,
-        wallet::Wallet,
+        wallet::{Wallet, WalletError},
         Config,
     },
     daemon::{Daemon, DaemonBackend},
@@ -30,6 +31,7 @@ use crate::{
     export::{ImportExportMessage, ImportExportType},
     hw::{HardwareWallet, HardwareWalletConfig, HardwareWallets},
     services::connect::client::backend::api::WALLET_ALIAS_MAXIMUM_LENGTH,
+    signer::LockedSigner,
 };
 
 enum Modal {
@@ -51,6 +53,8 @@ pub struct WalletSettingsState {
     keys_aliases: Vec<(Fingerprint, form::Value<String>)>,
     wallet: Arc<Wallet>,
     wallet_alias: form::Value<String>,
+    hot_signer_password: form::Value<String>,
+    hot_signer_password_confirmation: form::Value<String>,
     modal: Modal,
     processing: bool,
     updated: bool,
@@ -68,6 +72,8 @@ impl WalletSettingsState {
                 warning: None,
                 valid: true,
             },
+            hot_signer_password: form::Value::default(),
+            hot_signer_password_confirmation: form::Value::default(),
             wallet,
             warning: None,
             modal: Modal::None,
@@ -114,6 +120,13 @@ impl State for WalletSettingsState {
             &self.wallet_alias,
             &self.keys_aliases,
             &self.wallet.provider_keys,
+            self.wallet.signer.as_ref().map(|signer| {
+                (
+                    signer.fingerprint(),
+                    &self.hot_signer_password,
+                    &self.hot_signer_password_confirmation,
+                )
+            }),
             self.processing,
             self.updated,
         );
@@ -161,6 +174,8 @@ impl State for WalletSettingsState {
                         Ok(wallet) => {
                             self.keys_aliases = Self::keys_aliases(&wallet);
                             self.wallet = wallet;
+                            self.hot_signer_password = form::Value::default();
+                            self.hot_signer_password_confirmation = form::Value::default();
                             self.updated = true;
                         }
                         Err(e) => self.warning = Some(e),
@@ -221,6 +236,37 @@ impl State for WalletSettingsState {
                     Message::WalletUpdated,
                 )
             }
+            Message::View(view::Message::Settings(
+                view::SettingsMessage::HotSignerPasswordEdited(password),
+            )) => {
+                self.hot_signer_password.value = password;
+                self.hot_signer_password_confirmation.valid =
+                    self.hot_signer_password_confirmation.value.is_empty()
+                        || self.hot_signer_password_confirmation.value
+                            == self.hot_signer_password.value;
+                Task::none()
+            }
+            Message::View(view::Message::Settings(
+                view::SettingsMessage::HotSignerPasswordConfirmationEdited(confirmation),
+            )) => {
+                self.hot_signer_password_confirmation.valid =
+                    confirmation.is_empty() || confirmation == self.hot_signer_password.value;
+                self.hot_signer_password_confirmation.value = confirmation;
+                Task::none()
+            }
+            Message::View(view::Message::Settings(view::SettingsMessage::EncryptHotSigner)) => {
+                self.processing = true;
+                self.updated = false;
+                Task::perform(
+                    encrypt_hot_signer(
+                        self.data_dir.clone(),
+                        cache.network,
+                        self.wallet.clone(),
+                        self.hot_signer_password.value.clone(),
+                    ),
+                    Message::WalletUpdated,
+                )
+            }
             Message::View(view::Message::Close) => {
                 self.modal = Modal::None;
                 Task::none()
@@ -549,3 +595,31 @@ pub async fn update_aliases(
 
     Ok(Arc::new(wallet))
 }
+
+/// Encrypt the mnemonic of the wallet hot signer stored on disk. The hot signer is then locked and
+/// must be unlocked using the password to sign.
+pub async fn encrypt_hot_signer(
+    data_dir: LianaDirectory,
+    network: Network,
+    wallet: Arc<Wallet>,
+    password: String,
+) -> Result<Arc<Wallet>, Error> {
+    let mut wallet = wallet.as_ref().clone();
+
+    if let Some(signer) = wallet.signer.take() {
+        signer
+            .encrypt_stored(&data_dir, network, &password)
+            .map_err(|e| WalletError::HotSigner(format!("Failed to encrypt mnemonic: {}", e)))?;
+        let fingerprint = signer.fingerprint();
+        let mnemonic = EncryptedMnemonic::from_datadir(data_dir.path(), network)
+            .map_err(|e| WalletError::HotSigner(e.to_string()))?
+            .into_iter()
+            .find(|m| m.fingerprint() == fingerprint)
+            .ok_or_else(|| {
+                WalletError::HotSigner("Encrypted mnemonic not found after encryption".to_string())
+            })?;
+        wallet = wallet.with_locked_signer(LockedSigner::new(mnemonic, network));
+    }
+
+    Ok(Arc::new(wallet))
+}
@@ -58,7 +58,10 @@ impl CreateSpendPanel {
     ) -> Self {
         let timelock = wallet.as_ref().main_descriptor.first_timelock_value();
         Self {
-            draft: step::TransactionDraft::new(network, Some(timelock)),
+            draft: step::TransactionDraft::new(
+                network,
+                Some(step::RecoveryTimelock::Relative(timelock)),
+            ),
             current: 0,
             steps: vec![
                 Box::new(step::SelectRecoveryPath::new(
@@ -72,7 +75,8 @@ impl CreateSpendPanel {
                         wallet.clone(),
                         coins,
                         blockheight,
-                        Some(timelock), // the recovery timelock must always be set to a value
+                        // the recovery timelock must always be set to a value
+                        Some(step::RecoveryTimelock::Relative(timelock)),
                         false,
                     )
                     .with_coins_sorted(blockheight),
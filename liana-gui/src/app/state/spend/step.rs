@@ -5,19 +5,20 @@ use std::{
     iter::FromIterator,
     str::FromStr,
     sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use iced::{Subscription, Task};
 use liana::{
     miniscript::bitcoin::{
-        address,
+        absolute::LOCK_TIME_THRESHOLD,
         bip32::{DerivationPath, Fingerprint},
         psbt::Psbt,
         secp256k1, Address, Amount, Denomination, Network, OutPoint,
     },
     spend::{SpendCreationError, DUST_OUTPUT_SATS, MAX_FEERATE},
 };
-use lianad::commands::ListCoinsEntry;
+use lianad::{commands::ListCoinsEntry, payments};
 
 use liana_ui::{component::form, widget::Element};
 
@@ -31,11 +32,25 @@ use crate::{
         wallet::Wallet,
     },
     daemon::{
-        model::{coin_is_owned, remaining_sequence, Coin, CreateSpendResult, SpendTx},
+        model::{
+            coin_is_owned, remaining_sequence, remaining_time, Coin, CreateSpendResult,
+            Destination, SpendTx,
+        },
         Daemon,
     },
 };
 
+/// The timelock of the recovery path used for a recovery spend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryTimelock {
+    /// A relative timelock, in blocks.
+    Relative(u16),
+    /// A relative timelock, in number of 512-second intervals.
+    RelativeTime(u16),
+    /// An absolute timelock, as a consensus-encoded nLockTime.
+    Absolute(u32),
+}
+
 #[derive(Clone)]
 pub struct TransactionDraft {
     network: Network,
@@ -49,11 +64,11 @@ pub struct TransactionDraft {
     /// If the primary path will be used, this value will remain as `None`.
     /// Otherwise, its value should always be set to a recovery path,
     /// which may change from one to another.
-    recovery_timelock: Option<u16>,
+    recovery_timelock: Option<RecoveryTimelock>,
 }
 
 impl TransactionDraft {
-    pub fn new(network: Network, recovery_timelock: Option<u16>) -> Self {
+    pub fn new(network: Network, recovery_timelock: Option<RecoveryTimelock>) -> Self {
         Self {
             network,
             inputs: Vec::new(),
@@ -94,7 +109,7 @@ pub trait Step {
 /// while for a primary path spend, no coins will be selected.
 fn filter_coins(
     coins: &[Coin],
-    recovery_timelock: Option<u16>,
+    recovery_timelock: Option<RecoveryTimelock>,
     tip_height: i32,
     selected: Option<HashSet<OutPoint>>,
 ) -> Vec<(Coin, bool)> {
@@ -104,8 +119,14 @@ fn filter_coins(
             if c.spend_info.is_none() && !c.is_immature {
                 if let Some(recovery_timelock) = recovery_timelock {
                     c.block_height
-                        .filter(|bh| {
-                            tip_height + 1 >= bh + <u16 as Into<i32>>::into(recovery_timelock)
+                        .filter(|bh| match recovery_timelock {
+                            RecoveryTimelock::Relative(tl) => {
+                                tip_height + 1 >= bh + <u16 as Into<i32>>::into(tl)
+                            }
+                            RecoveryTimelock::RelativeTime(tl) => remaining_time(c, tl) == Some(0),
+                            // The path itself is only selectable once its timelock has
+                            // expired, at which point any confirmed coin can be recovered.
+                            RecoveryTimelock::Absolute(_) => true,
                         })
                         .map(|_| {
                             (
@@ -151,7 +172,7 @@ pub struct DefineSpend {
     ///
     /// For a recovery path spend, this value can change from one timelock to another, but
     /// it must never be set to `None`.
-    recovery_timelock: Option<u16>,
+    recovery_timelock: Option<RecoveryTimelock>,
     tip_height: u32,
     coins: Vec<(Coin, bool)>,
     coins_labels: HashMap<String, String>,
@@ -172,7 +193,7 @@ impl DefineSpend {
         wallet: Arc<Wallet>,
         coins: &[Coin],
         tip_height: u32,
-        recovery_timelock: Option<u16>,
+        recovery_timelock: Option<RecoveryTimelock>,
         is_first_step: bool,
     ) -> Self {
         let coins = filter_coins(
@@ -243,11 +264,13 @@ impl DefineSpend {
 
     /// This is used for calculating a coin's remaining sequence.
     ///
-    /// Use the first timelock if this is a primary path spend and otherwise the same
-    /// timelock as used for the recovery.
+    /// Use the first timelock if this is a primary path spend or a time-based or absolute
+    /// timelock recovery, and otherwise the same timelock as used for the recovery.
     pub fn timelock(&self) -> u16 {
-        self.recovery_timelock
-            .unwrap_or_else(|| self.wallet.main_descriptor.first_timelock_value())
+        match self.recovery_timelock {
+            Some(RecoveryTimelock::Relative(tl)) => tl,
+            _ => self.wallet.main_descriptor.first_timelock_value(),
+        }
     }
 
     // If `is_redraft`, the validation of recipients will take into account
@@ -275,6 +298,20 @@ impl DefineSpend {
         false
     }
 
+    // Add a recipient for each of these payments. The recipient of the form is replaced if it
+    // wasn't filled in yet.
+    fn add_destinations(&mut self, destinations: Vec<Destination>) {
+        if self.recipients.len() == 1 && self.recipients[0].is_empty() {
+            self.recipients.clear();
+            self.send_max_to_recipient = None;
+        }
+        for destination in destinations {
+            let mut recipient = Recipient::default();
+            recipient.set_destination(self.network, destination);
+            self.recipients.push(recipient);
+        }
+    }
+
     fn check_valid(&mut self) {
         self.is_valid =
             self.form_values_are_valid(false) && self.coins.iter().any(|(_, selected)| *selected);
@@ -283,7 +320,7 @@ impl DefineSpend {
     /// redraft calculates the amount left to select and auto selects coins
     /// if the user did not select a coin manually
     fn redraft(&mut self, daemon: Arc<dyn Daemon + Sync + Send>) {
-        if !self.form_values_are_valid(true) || self.exists_duplicate() {
+        if !self.form_values_are_valid(true) {
             // The current form details are not valid to draft a spend, so remove any previously
             // calculated amount as it will no longer be valid and could be misleading, e.g. if
             // the user removes the amount from one of the recipients.
@@ -308,7 +345,7 @@ impl DefineSpend {
         // Define the destinations for a primary path spend from all non-max recipients.
         // TODO: Set this variable only in the non-recovery case. For now, we use it later below
         // for setting `amount_left_to_select`, which is only required in the non-recovery case.
-        let destinations: HashMap<Address<address::NetworkUnchecked>, u64> = self
+        let destinations: Vec<Destination> = self
             .recipients
             .iter()
             .enumerate()
@@ -321,7 +358,7 @@ impl DefineSpend {
                 if self.send_max_to_recipient == Some(i) {
                     None
                 } else {
-                    Some((
+                    Some(Destination::new(
                         Address::from_str(&recipient.address.value).expect("Checked before"),
                         recipient.amount().expect("Checked before"),
                     ))
@@ -380,7 +417,12 @@ impl DefineSpend {
                 // doesn't take account of the fee, but passing an empty list to `create_spend_tx`
                 // would use auto-selection and so we settle for this approximation.
                 // Note that for a recovery, the amount left to select is ignored by the view.
-                self.amount_left_to_select = Some(Amount::from_sat(destinations.values().sum()));
+                self.amount_left_to_select = Some(Amount::from_sat(
+                    destinations
+                        .iter()
+                        .map(|destination| destination.amount)
+                        .sum(),
+                ));
                 self.fee_amount = None;
                 return;
             }
@@ -419,14 +461,33 @@ impl DefineSpend {
         match tokio::runtime::Handle::current().block_on(async {
             // If recovery timelock is set, create a recovery transaction. Otherwise, a regular spend.
             if let Some(reco_tl) = recovery_timelock {
-                daemon
-                    .create_recovery(max_address.clone(), &outpoints, feerate_vb, Some(reco_tl))
-                    .await
-                    // Map the PSBT to `CreateSpendResult` result. We only need the PSBT below.
-                    .map(|psbt| CreateSpendResult::Success {
-                        psbt,
-                        warnings: vec![],
-                    })
+                match reco_tl {
+                    RecoveryTimelock::Relative(tl) => {
+                        daemon
+                            .create_recovery(max_address.clone(), &outpoints, feerate_vb, Some(tl))
+                            .await
+                    }
+                    RecoveryTimelock::RelativeTime(tl) => {
+                        daemon
+                            .create_time_recovery(max_address.clone(), &outpoints, feerate_vb, tl)
+                            .await
+                    }
+                    RecoveryTimelock::Absolute(tl) => {
+                        daemon
+                            .create_absolute_recovery(
+                                max_address.clone(),
+                                &outpoints,
+                                feerate_vb,
+                                tl,
+                            )
+                            .await
+                    }
+                }
+                // Map the PSBT to `CreateSpendResult` result. We only need the PSBT below.
+                .map(|psbt| CreateSpendResult::Success {
+                    psbt,
+                    warnings: vec![],
+                })
             } else {
                 daemon
                     .create_spend_tx(
@@ -494,6 +555,18 @@ impl DefineSpend {
     }
 }
 
+/// Ask the user for a CSV file of payments and parse it. No payment is returned if the user
+/// didn't choose any file.
+async fn import_payments() -> Result<Vec<Destination>, Error> {
+    let Some(path) = crate::export::get_path("payments.csv".to_string(), false).await else {
+        return Ok(Vec::new());
+    };
+    let content = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| Error::Unexpected(format!("Failed to read {}: {}", path.display(), e)))?;
+    payments::parse_csv(&content).map_err(|e| Error::Unexpected(e.to_string()))
+}
+
 #[allow(clippy::too_many_arguments)]
 fn handle_max_under_dust(
     fee_amount: &mut Option<Amount>,
@@ -627,6 +700,9 @@ impl Step for DefineSpend {
                     view::CreateSpendMessage::AddRecipient => {
                         self.recipients.push(Recipient::default());
                     }
+                    view::CreateSpendMessage::ImportPayments => {
+                        return Task::perform(import_payments(), Message::ImportedPayments);
+                    }
                     view::CreateSpendMessage::DeleteRecipient(i) => {
                         self.recipients.remove(i);
                         if self.recipients.len() < 2 {
@@ -681,8 +757,6 @@ impl Step for DefineSpend {
                                 },
                             )
                             .collect();
-                        let mut outputs: HashMap<Address<address::NetworkUnchecked>, u64> =
-                            HashMap::new();
                         let feerate_vb = self.feerate.value.parse::<u64>().unwrap_or(0);
                         self.warning = None;
                         if let Some(reco_tl) = self.recovery_timelock {
@@ -697,26 +771,58 @@ impl Step for DefineSpend {
                             .expect("Checked before");
                             return Task::perform(
                                 async move {
-                                    daemon
-                                        .create_recovery(
-                                            recovery_address,
-                                            &inputs,
-                                            feerate_vb,
-                                            Some(reco_tl),
-                                        )
-                                        .await
-                                        .map_err(|e| e.into())
-                                        .map(|psbt| (psbt, vec![]))
+                                    match reco_tl {
+                                        RecoveryTimelock::Relative(tl) => {
+                                            daemon
+                                                .create_recovery(
+                                                    recovery_address,
+                                                    &inputs,
+                                                    feerate_vb,
+                                                    Some(tl),
+                                                )
+                                                .await
+                                        }
+                                        RecoveryTimelock::RelativeTime(tl) => {
+                                            daemon
+                                                .create_time_recovery(
+                                                    recovery_address,
+                                                    &inputs,
+                                                    feerate_vb,
+                                                    tl,
+                                                )
+                                                .await
+                                        }
+                                        RecoveryTimelock::Absolute(tl) => {
+                                            daemon
+                                                .create_absolute_recovery(
+                                                    recovery_address,
+                                                    &inputs,
+                                                    feerate_vb,
+                                                    tl,
+                                                )
+                                                .await
+                                        }
+                                    }
+                                    .map_err(|e| e.into())
+                                    .map(|psbt| (psbt, vec![]))
                                 },
                                 Message::Psbt,
                             );
                         } else {
-                            for recipient in &self.recipients {
-                                let address = Address::from_str(&recipient.address.value)
-                                    .expect("Checked before");
-                                outputs
-                                    .insert(address, recipient.amount().expect("Checked before"));
-                            }
+                            // The labels of the recipients are set on their outputs.
+                            let outputs: Vec<Destination> = self
+                                .recipients
+                                .iter()
+                                .map(|recipient| Destination {
+                                    label: (!recipient.label.value.is_empty())
+                                        .then(|| recipient.label.value.clone()),
+                                    ..Destination::new(
+                                        Address::from_str(&recipient.address.value)
+                                            .expect("Checked before"),
+                                        recipient.amount().expect("Checked before"),
+                                    )
+                                })
+                                .collect();
                             return Task::perform(
                                 async move {
                                     daemon
@@ -776,6 +882,17 @@ impl Step for DefineSpend {
                 }
                 Err(e) => self.warning = Some(e),
             },
+            Message::ImportedPayments(res) => match res {
+                Ok(destinations) => {
+                    if !destinations.is_empty() {
+                        self.warning = None;
+                        self.add_destinations(destinations);
+                        self.redraft(daemon);
+                        self.check_valid();
+                    }
+                }
+                Err(e) => self.warning = Some(e),
+            },
             Message::Labels(res) => match res {
                 Ok(labels) => {
                     self.coins_labels = labels;
@@ -822,27 +939,16 @@ impl Step for DefineSpend {
             .collect();
         if let Some((psbt, _)) = &self.generated {
             draft.labels.clone_from(&self.coins_labels);
-            for (i, output) in psbt.unsigned_tx.output.iter().enumerate() {
-                if let Some(label) = self
-                    .recipients
-                    .iter()
-                    .find(|recipient| {
-                        !recipient.label.value.is_empty()
-                            && Address::from_str(&recipient.address.value)
-                                .unwrap()
-                                .assume_checked()
-                                .matches_script_pubkey(&output.script_pubkey)
-                            && output.value.to_sat() == recipient.amount().unwrap()
-                    })
-                    .map(|recipient| recipient.label.value.to_string())
-                {
+            // The recipients' outputs come first, in the same order.
+            for (i, recipient) in self.recipients.iter().enumerate() {
+                if !recipient.label.value.is_empty() {
                     draft.labels.insert(
                         OutPoint {
                             txid: psbt.unsigned_tx.compute_txid(),
                             vout: i as u32,
                         }
                         .to_string(),
-                        label,
+                        recipient.label.value.to_string(),
                     );
                 }
             }
@@ -875,7 +981,7 @@ impl Step for DefineSpend {
             self.is_valid,
             self.is_duplicate,
             self.timelock(),
-            self.recovery_timelock,
+            self.recovery_timelock.is_some(),
             &self.coins,
             &self.coins_labels,
             &self.batch_label,
@@ -945,6 +1051,38 @@ impl Recipient {
         !self.address.value.is_empty() && self.address.valid
     }
 
+    fn is_empty(&self) -> bool {
+        self.address.value.is_empty() && self.amount.value.is_empty() && self.label.value.is_empty()
+    }
+
+    fn set_destination(&mut self, network: Network, destination: Destination) {
+        // The index is not used when updating a single recipient.
+        self.update(
+            network,
+            view::CreateSpendMessage::RecipientEdited(
+                0,
+                "address",
+                destination.address.assume_checked_ref().to_string(),
+            ),
+        );
+        self.update(
+            network,
+            view::CreateSpendMessage::RecipientEdited(
+                0,
+                "amount",
+                Amount::from_sat(destination.amount).to_btc().to_string(),
+            ),
+        );
+        self.update(
+            network,
+            view::CreateSpendMessage::RecipientEdited(
+                0,
+                "label",
+                destination.label.unwrap_or_default(),
+            ),
+        );
+    }
+
     fn valid(&self) -> bool {
         self.address_valid()
             && !self.amount.value.is_empty()
@@ -955,6 +1093,13 @@ impl Recipient {
     fn update(&mut self, network: Network, message: view::CreateSpendMessage) {
         match message {
             view::CreateSpendMessage::RecipientEdited(_, "address", address) => {
+                // A BIP21 URI with an amount fills in the whole recipient.
+                if payments::is_bip21_uri(&address) {
+                    if let Ok(destination) = payments::parse_bip21(&address) {
+                        self.set_destination(network, destination);
+                        return;
+                    }
+                }
                 self.address.value = address;
                 if let Ok(address) = Address::from_str(&self.address.value) {
                     self.address.valid = address.is_valid_for_network(network);
@@ -1199,17 +1344,17 @@ impl SelectRecoveryPath {
     pub fn load_from_coins_and_tip_height(&mut self, coins: &[Coin], tip_height: i32) {
         self.warning = None;
         // Update the available recovery paths, maintaining any selected path.
-        let selected_seq = self.selected_path.and_then(|selected| {
+        let selected_tl = self.selected_path.and_then(|selected| {
             self.recovery_paths
                 .get(selected)
-                .map(|reco_path| reco_path.sequence)
+                .map(|reco_path| reco_path.timelock)
         });
         self.recovery_paths = recovery_paths(&self.wallet, coins, tip_height);
-        self.selected_path = selected_seq.and_then(|seq| {
+        self.selected_path = selected_tl.and_then(|tl| {
             self.recovery_paths
                 .iter()
                 .enumerate()
-                .find_map(|(i, path)| (path.sequence == seq).then_some(i))
+                .find_map(|(i, path)| (path.timelock == tl).then_some(i))
         });
     }
 }
@@ -1237,6 +1382,14 @@ impl Step for SelectRecoveryPath {
                             &path.origins,
                             path.total_amount,
                             path.number_of_coins,
+                            match path.timelock {
+                                RecoveryTimelock::Absolute(tl) => Some(tl),
+                                _ => None,
+                            },
+                            match path.timelock {
+                                RecoveryTimelock::RelativeTime(tl) => Some(tl),
+                                _ => None,
+                            },
                             &self.wallet.keys_aliases,
                             self.selected_path == Some(i),
                         ))
@@ -1282,7 +1435,7 @@ impl Step for SelectRecoveryPath {
     fn apply(&self, draft: &mut TransactionDraft) {
         if let Some(selected_path) = self.selected_path {
             if let Some(path) = self.recovery_paths.get(selected_path) {
-                draft.recovery_timelock = Some(path.sequence);
+                draft.recovery_timelock = Some(path.timelock);
             }
         }
     }
@@ -1290,38 +1443,92 @@ impl Step for SelectRecoveryPath {
 
 pub struct RecoveryPath {
     threshold: usize,
-    sequence: u16,
+    timelock: RecoveryTimelock,
     origins: Vec<(Fingerprint, HashSet<DerivationPath>)>,
     total_amount: Amount,
     number_of_coins: usize,
 }
 
+/// Whether an absolute timelock has expired at the given tip.
+///
+/// Timestamp-based timelocks are compared against the current system time. This is only an
+/// approximation of the median time past the daemon checks against when creating the recovery.
+fn absolute_timelock_expired(timelock: u32, tip_height: i32) -> bool {
+    if timelock < LOCK_TIME_THRESHOLD {
+        timelock as i64 <= tip_height as i64
+    } else {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() > timelock as u64)
+            .unwrap_or(false)
+    }
+}
+
 fn recovery_paths(wallet: &Wallet, coins: &[Coin], tip_height: i32) -> Vec<RecoveryPath> {
-    wallet
-        .main_descriptor
-        .policy()
-        .recovery_paths()
+    // Only confirmed coins are included in a recovery transaction.
+    let recoverable = |coin: &&Coin| coin.block_height.is_some() && coin.spend_info.is_none();
+    let count = |(number_of_coins, total_amount): (usize, Amount), coin: &Coin| {
+        (number_of_coins + 1, total_amount + coin.amount)
+    };
+    let policy = wallet.main_descriptor.policy();
+    let relative_paths = policy.recovery_paths().iter().map(|(&sequence, path)| {
+        let (number_of_coins, total_amount) = coins
+            .iter()
+            .filter(recoverable)
+            .filter(|coin| remaining_sequence(coin, tip_height as u32, sequence) <= 1)
+            .fold((0, Amount::from_sat(0)), count);
+        (
+            RecoveryTimelock::Relative(sequence),
+            path,
+            number_of_coins,
+            total_amount,
+        )
+    });
+    let time_paths = policy
+        .time_recovery_paths()
         .iter()
-        .map(|(&sequence, path)| {
+        .map(|(&timelock, path)| {
             let (number_of_coins, total_amount) = coins
                 .iter()
-                .filter(|coin| {
-                    coin.block_height.is_some() // only confirmed coins are included in a recovery transaction
-                        && coin.spend_info.is_none()
-                        && remaining_sequence(coin, tip_height as u32, sequence) <= 1
-                })
-                .fold(
-                    (0, Amount::from_sat(0)),
-                    |(number_of_coins, total_amount), coin| {
-                        (number_of_coins + 1, total_amount + coin.amount)
-                    },
-                );
-
+                .filter(recoverable)
+                .filter(|coin| remaining_time(coin, timelock) == Some(0))
+                .fold((0, Amount::from_sat(0)), count);
+            (
+                RecoveryTimelock::RelativeTime(timelock),
+                path,
+                number_of_coins,
+                total_amount,
+            )
+        });
+    let absolute_paths = policy
+        .absolute_recovery_paths()
+        .iter()
+        .map(|(&timelock, path)| {
+            let (number_of_coins, total_amount) = if absolute_timelock_expired(timelock, tip_height)
+            {
+                coins
+                    .iter()
+                    .filter(recoverable)
+                    .fold((0, Amount::from_sat(0)), count)
+            } else {
+                (0, Amount::from_sat(0))
+            };
+            (
+                RecoveryTimelock::Absolute(timelock),
+                path,
+                number_of_coins,
+                total_amount,
+            )
+        });
+    relative_paths
+        .chain(time_paths)
+        .chain(absolute_paths)
+        .map(|(timelock, path, number_of_coins, total_amount)| {
             let (threshold, origins) = path.thresh_origins();
             RecoveryPath {
                 total_amount,
                 number_of_coins,
-                sequence,
+                timelock,
                 threshold,
                 origins: origins.into_iter().collect(),
             }
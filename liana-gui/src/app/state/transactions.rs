@@ -41,6 +41,7 @@ use super::export::ExportModal;
 #[derive(Debug)]
 pub enum TransactionsModal {
     CreateRbf(CreateRbfModal),
+    CreateCpfp(CreateCpfpModal),
     Export(ExportModal),
     None,
 }
@@ -88,6 +89,7 @@ impl State for TransactionsPanel {
             );
             match &self.modal {
                 TransactionsModal::CreateRbf(rbf) => rbf.view(content),
+                TransactionsModal::CreateCpfp(cpfp) => cpfp.view(content),
                 _ => content,
             }
         } else {
@@ -153,26 +155,76 @@ impl State for TransactionsPanel {
                     self.warning = e.into();
                 }
             },
+            Message::CpfpModal(tx, res) => match res {
+                Ok(outpoints) => {
+                    if outpoints.is_empty() {
+                        self.warning = Some(Error::Unexpected(
+                            "This transaction has no unconfirmed coin left to spend.".to_string(),
+                        ));
+                    } else {
+                        let modal = CreateCpfpModal::new(*tx, outpoints);
+                        self.modal = TransactionsModal::CreateCpfp(modal);
+                    }
+                }
+                Err(e) => {
+                    self.warning = e.into();
+                }
+            },
             Message::View(view::Message::Reload) | Message::View(view::Message::Close) => {
                 return self.reload(daemon, self.wallet.clone());
             }
             Message::View(view::Message::Select(i)) => {
                 self.selected_tx = self.txs.get(i).cloned();
                 // Clear modal if it's for a different tx.
-                if let TransactionsModal::CreateRbf(modal) = &self.modal {
-                    if Some(modal.tx.tx.compute_txid())
+                let modal_txid = match &self.modal {
+                    TransactionsModal::CreateRbf(modal) => Some(modal.tx.tx.compute_txid()),
+                    TransactionsModal::CreateCpfp(modal) => Some(modal.tx.tx.compute_txid()),
+                    _ => None,
+                };
+                if modal_txid.is_some()
+                    && modal_txid
                         != self
                             .selected_tx
                             .as_ref()
                             .map(|selected| selected.tx.compute_txid())
-                    {
-                        self.modal = TransactionsModal::None;
-                    }
+                {
+                    self.modal = TransactionsModal::None;
                 }
             }
-            Message::View(view::Message::CreateRbf(view::CreateRbfMessage::Cancel)) => {
+            Message::View(view::Message::CreateRbf(view::CreateRbfMessage::Cancel))
+            | Message::View(view::Message::CreateCpfp(view::CreateCpfpMessage::Cancel)) => {
                 self.modal = TransactionsModal::None;
             }
+            Message::View(view::Message::CreateCpfp(view::CreateCpfpMessage::New)) => {
+                if let Some(tx) = &self.selected_tx {
+                    let tx = tx.clone();
+                    let txid = tx.tx.compute_txid();
+                    let outpoints: Vec<_> = tx
+                        .change_indexes
+                        .iter()
+                        .map(|vout| {
+                            OutPoint::new(
+                                txid,
+                                (*vout)
+                                    .try_into()
+                                    .expect("number of transaction outputs must fit in u32"),
+                            )
+                        })
+                        .collect();
+                    return Task::perform(
+                        async move {
+                            // Only the coins which are not spent yet can be used by the child.
+                            let res = daemon
+                                .list_coins(&[CoinStatus::Unconfirmed], &outpoints)
+                                .await
+                                .map(|res| res.coins.iter().map(|c| c.outpoint).collect())
+                                .map_err(|e| e.into());
+                            (Box::new(tx), res)
+                        },
+                        |(tx, res)| Message::CpfpModal(tx, res),
+                    );
+                }
+            }
             Message::View(view::Message::CreateRbf(view::CreateRbfMessage::New(is_cancel))) => {
                 if let Some(tx) = &self.selected_tx {
                     if tx.fee_amount.is_some() {
@@ -285,6 +337,7 @@ impl State for TransactionsPanel {
             ref msg => {
                 return match &mut self.modal {
                     TransactionsModal::CreateRbf(modal) => modal.update(daemon, _cache, message),
+                    TransactionsModal::CreateCpfp(modal) => modal.update(daemon, _cache, message),
                     TransactionsModal::Export(modal) => {
                         if let Message::View(view::Message::ImportExport(m)) = msg {
                             modal.update::<Message>(m.clone())
@@ -463,6 +516,133 @@ impl CreateRbfModal {
     }
 }
 
+#[derive(Debug)]
+pub struct CreateCpfpModal {
+    /// Transaction to speed up.
+    tx: model::HistoryTransaction,
+    /// Unconfirmed coins of the transaction to be spent by the child.
+    outpoints: Vec<OutPoint>,
+    /// Feerate form value.
+    feerate_val: form::Value<String>,
+    /// Parsed target feerate for the package.
+    feerate_vb: Option<u64>,
+    /// Child transaction ID.
+    child_txid: Option<Txid>,
+
+    processing: bool,
+    warning: Option<Error>,
+}
+
+impl CreateCpfpModal {
+    fn new(tx: model::HistoryTransaction, outpoints: Vec<OutPoint>) -> Self {
+        // If we know the transaction fee, suggest a feerate just above its own.
+        let feerate_vb = tx
+            .fee_amount
+            .and_then(|fee| {
+                fee.to_sat()
+                    .checked_div(tx.tx.vsize().try_into().expect("vsize must fit in u64"))
+            })
+            .map(|feerate| feerate.saturating_add(1).min(MAX_FEERATE))
+            .unwrap_or(1);
+        Self {
+            tx,
+            outpoints,
+            feerate_val: form::Value {
+                valid: true,
+                warning: None,
+                value: feerate_vb.to_string(),
+            },
+            feerate_vb: Some(feerate_vb),
+            child_txid: None,
+            warning: None,
+            processing: false,
+        }
+    }
+
+    fn update(
+        &mut self,
+        daemon: Arc<dyn Daemon + Sync + Send>,
+        _cache: &Cache,
+        message: Message,
+    ) -> Task<Message> {
+        match message {
+            Message::View(view::Message::CreateCpfp(view::CreateCpfpMessage::FeerateEdited(s))) => {
+                self.warning = None;
+                if let Ok(value) = s.parse::<u64>() {
+                    self.feerate_val.valid = (1..=MAX_FEERATE).contains(&value);
+                    if self.feerate_val.valid {
+                        self.feerate_vb = Some(value);
+                    }
+                } else {
+                    self.feerate_val.valid = false;
+                }
+                if !self.feerate_val.valid {
+                    self.feerate_vb = None;
+                }
+                self.feerate_val.value = s; // save form value even if it cannot be parsed
+            }
+            Message::CpfpPsbt(res) => {
+                self.processing = false;
+                match res {
+                    Ok(txid) => {
+                        self.child_txid = Some(txid);
+                    }
+                    Err(e) => self.warning = Some(e),
+                }
+            }
+            Message::View(view::Message::CreateCpfp(view::CreateCpfpMessage::Confirm)) => {
+                if let Some(feerate_vb) = self.feerate_vb {
+                    self.warning = None;
+                    self.processing = true;
+                    return Task::perform(
+                        cpfp(daemon, self.outpoints.clone(), feerate_vb),
+                        Message::CpfpPsbt,
+                    );
+                }
+            }
+            _ => {}
+        }
+        Task::none()
+    }
+
+    fn view<'a>(&'a self, content: Element<'a, view::Message>) -> Element<'a, view::Message> {
+        let modal = Modal::new(
+            content,
+            view::transactions::create_cpfp_modal(
+                &self.feerate_val,
+                self.child_txid,
+                self.warning.as_ref(),
+            ),
+        );
+        if self.processing {
+            modal
+        } else {
+            modal.on_blur(Some(view::Message::CreateCpfp(
+                view::CreateCpfpMessage::Cancel,
+            )))
+        }
+        .into()
+    }
+}
+
+async fn cpfp(
+    daemon: Arc<dyn Daemon + Sync + Send>,
+    outpoints: Vec<OutPoint>,
+    feerate_vb: u64,
+) -> Result<Txid, Error> {
+    let psbt = match daemon.cpfp_bump(&outpoints, feerate_vb).await? {
+        CreateSpendResult::Success { psbt, .. } => psbt,
+        CreateSpendResult::InsufficientFunds { missing } => {
+            return Err(
+                SpendCreationError::CoinSelection(liana::spend::InsufficientFunds { missing })
+                    .into(),
+            );
+        }
+    };
+    daemon.update_spend_tx(&psbt).await?;
+    Ok(psbt.unsigned_tx.compute_txid())
+}
+
 async fn rbf(
     daemon: Arc<dyn Daemon + Sync + Send>,
     previous_tx: model::HistoryTransaction,
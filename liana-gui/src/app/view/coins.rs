@@ -116,6 +116,11 @@ fn coin_list_view<'a>(
                                     Container::new(Space::with_width(Length::Fill))
                                         .width(Length::Fill)
                                 })
+                                .push_maybe(if coin.is_frozen {
+                                    Some(badge::frozen())
+                                } else {
+                                    None
+                                })
                                 .push(if coin.spend_info.is_some() {
                                     badge::spent()
                                 } else if coin.block_height.is_none() {
@@ -298,18 +303,32 @@ fn coin_list_view<'a>(
                                 .spacing(5)
                         } else {
                             Column::new().push(
-                                Row::new().push(Space::with_width(Length::Fill)).push({
-                                    let (icon, label) =
-                                        (Some(icon::arrow_repeat()), "Refresh coin");
-                                    let refresh_btn = if seq == 0 {
-                                        button::primary(icon, label)
-                                    } else {
-                                        button::secondary(icon, label)
-                                    };
-                                    refresh_btn.on_press(Message::Menu(Menu::RefreshCoins(vec![
-                                        coin.outpoint,
-                                    ])))
-                                }),
+                                Row::new()
+                                    .spacing(10)
+                                    .push(Space::with_width(Length::Fill))
+                                    .push(
+                                        button::secondary(
+                                            None,
+                                            if coin.is_frozen {
+                                                "Unfreeze coin"
+                                            } else {
+                                                "Freeze coin"
+                                            },
+                                        )
+                                        .on_press(Message::ToggleFreezeCoin(index)),
+                                    )
+                                    .push({
+                                        let (icon, label) =
+                                            (Some(icon::arrow_repeat()), "Refresh coin");
+                                        let refresh_btn = if seq == 0 {
+                                            button::primary(icon, label)
+                                        } else {
+                                            button::secondary(icon, label)
+                                        };
+                                        refresh_btn.on_press(Message::Menu(Menu::RefreshCoins(
+                                            vec![coin.outpoint],
+                                        )))
+                                    }),
                             )
                         }),
                 )
@@ -1,11 +1,15 @@
 use crate::{
     app::menu::Menu,
     app::view::FiatAmountConverter,
-    export::ImportExportMessage,
+    export::{AccountingFormat, ImportExportMessage},
     node::bitcoind::RpcAuthType,
     services::fiat::{Currency, PriceSource},
 };
-use liana::miniscript::bitcoin::{bip32::Fingerprint, Address, OutPoint};
+use liana::{
+    descriptors::ExportFormat,
+    miniscript::bitcoin::{bip32::Fingerprint, Address, OutPoint},
+};
+use lianad::accounting::CostBasisMethod;
 
 pub trait Close {
     fn close() -> Self;
@@ -19,6 +23,7 @@ pub enum Message {
     Menu(Menu),
     Close,
     Select(usize),
+    ToggleFreezeCoin(usize),
     SelectPayment(OutPoint),
     Label(Vec<String>, LabelMessage),
     NextReceiveAddress,
@@ -32,11 +37,15 @@ pub enum Message {
     Previous,
     SelectHardwareWallet(usize),
     CreateRbf(CreateRbfMessage),
+    CreateCpfp(CreateCpfpMessage),
     ShowQrCode(usize),
     ImportExport(ImportExportMessage),
     HideRescanWarning,
     ExportPsbt,
     ImportPsbt,
+    ExportPsbtQr,
+    ImportPsbtQr,
+    PsbtQr(PsbtQrMessage),
     OpenUrl(String),
 }
 
@@ -56,6 +65,7 @@ pub enum LabelMessage {
 #[derive(Debug, Clone)]
 pub enum CreateSpendMessage {
     AddRecipient,
+    ImportPayments,
     BatchLabelEdited(String),
     DeleteRecipient(usize),
     SelectCoin(usize),
@@ -75,6 +85,17 @@ pub enum ImportSpendMessage {
     Confirm,
 }
 
+#[derive(Debug, Clone)]
+pub enum PsbtQrMessage {
+    /// Display the next part of the animated QR code.
+    NextPart,
+    UrEdited(String),
+    SelectImages,
+    /// The contents of the QR codes scanned from the selected images.
+    ImagesScanned(Result<Vec<String>, String>),
+    Confirm,
+}
+
 #[derive(Debug, Clone)]
 pub enum SpendTxMessage {
     Delete,
@@ -84,6 +105,8 @@ pub enum SpendTxMessage {
     Confirm,
     Cancel,
     SelectHotSigner,
+    HotSignerPasswordEdited(String),
+    UnlockHotSigner,
     EditPsbt,
     PsbtEdited(String),
     Next,
@@ -102,8 +125,14 @@ pub enum SettingsMessage {
     ImportExportSection,
     ExportEncryptedDescriptor,
     ExportPlaintextDescriptor,
+    ExportSignerDescriptor(ExportFormat),
     ExportTransactions,
+    CostBasisMethodSelected(CostBasisMethod),
+    ExportAccountingReport(AccountingFormat),
     ExportLabels,
+    ProofOfReservesMessageEdited(String),
+    ExportProofOfReserves,
+    VerifyProofOfReserves,
     ExportWallet,
     ImportWallet,
     AboutSection,
@@ -111,6 +140,9 @@ pub enum SettingsMessage {
     FingerprintAliasEdited(Fingerprint, String),
     WalletAliasEdited(String),
     Save,
+    HotSignerPasswordEdited(String),
+    HotSignerPasswordConfirmationEdited(String),
+    EncryptHotSigner,
     GeneralSection,
     Fiat(FiatMessage),
 }
@@ -140,6 +172,14 @@ pub enum CreateRbfMessage {
     Confirm,
 }
 
+#[derive(Debug, Clone)]
+pub enum CreateCpfpMessage {
+    New,
+    FeerateEdited(String),
+    Cancel,
+    Confirm,
+}
+
 #[derive(Debug, Clone)]
 pub enum FiatMessage {
     Enable(bool),
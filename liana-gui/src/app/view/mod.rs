@@ -179,6 +179,14 @@ pub fn sidebar<'a>(menu: &Menu, cache: &'a Cache) -> Container<'a, Message> {
                                 .padding(5)
                                 .style(theme::pill::simple)
                         }))
+                        .push_maybe(cache.address_discovery().map(|p| {
+                            Container::new(text(format!(
+                                "  Discovering addresses...{}/{}  ",
+                                p.receive_index, p.change_index
+                            )))
+                            .padding(5)
+                            .style(theme::pill::simple)
+                        }))
                         .push(recovery_button)
                         .push(settings_button),
                 )
@@ -1,7 +1,10 @@
 use std::collections::{HashMap, HashSet};
 
 use iced::{
-    widget::{scrollable, tooltip, Space},
+    widget::{
+        qr_code::{self, QRCode},
+        scrollable, tooltip, Space,
+    },
     Alignment, Length,
 };
 
@@ -35,6 +38,7 @@ use crate::{
     },
     daemon::model::{Coin, SpendStatus, SpendTx},
     hw::HardwareWallet,
+    qr,
 };
 
 #[allow(clippy::too_many_arguments)]
@@ -281,6 +285,100 @@ pub fn delete_action<'a>(warning: Option<&Error>, deleted: bool) -> Element<'a,
     }
 }
 
+/// Return the modal view displaying the animated QR code of the PSBT, for air-gapped signers.
+pub fn export_qr_modal<'a>(
+    qr_code: Option<&'a qr_code::Data>,
+    fragment_count: usize,
+    warning: Option<&qr::Error>,
+) -> Element<'a, Message> {
+    card::simple(
+        Column::new()
+            .spacing(10)
+            .align_x(Alignment::Center)
+            .push_maybe(warning.map(|w| text(w.to_string()).style(theme::text::error)))
+            .push(text("Scan the animated QR code with your signing device"))
+            .push_maybe(
+                qr_code.map(|qr| {
+                    Container::new(QRCode::<theme::Theme>::new(qr).cell_size(6)).padding(10)
+                }),
+            )
+            .push(
+                p2_regular(format!(
+                    "The PSBT is split in {} part{}",
+                    fragment_count,
+                    if fragment_count > 1 { "s" } else { "" }
+                ))
+                .style(theme::text::secondary),
+            )
+            .push(Row::new().push(Space::with_width(Length::Fill)).push(
+                button::secondary(None, "Close").on_press(Message::Spend(SpendTxMessage::Cancel)),
+            )),
+    )
+    .width(Length::Fixed(500.0))
+    .into()
+}
+
+/// Return the modal view to import a signed PSBT from the QR codes of an air-gapped signer.
+///
+/// `progress` is the number of parts received and the number of parts the PSBT was split into,
+/// if known.
+pub fn import_qr_modal<'a>(
+    ur: &'a form::Value<String>,
+    progress: (usize, Option<usize>),
+    warning: Option<&Error>,
+    imported: bool,
+) -> Element<'a, Message> {
+    if imported {
+        return card::simple(text("Signatures from the QR codes were imported"))
+            .width(Length::Fixed(500.0))
+            .align_x(iced::alignment::Horizontal::Center)
+            .into();
+    }
+    card::simple(
+        Column::new()
+            .spacing(10)
+            .push_maybe(warning.map(|w| warn(Some(w))))
+            .push(text("Import the signed PSBT from QR codes").bold())
+            .push(
+                p2_regular("Paste the UR text of the QR codes, separating parts with spaces, or select pictures of the QR codes.")
+                    .style(theme::text::secondary),
+            )
+            .push(
+                form::Form::new_trimmed("ur:crypto-psbt/...", ur, |msg| {
+                    Message::PsbtQr(PsbtQrMessage::UrEdited(msg))
+                })
+                .warning("Please enter valid crypto-psbt UR parts")
+                .size(P1_SIZE)
+                .padding(10),
+            )
+            .push_maybe((progress.0 > 0).then(|| {
+                p2_regular(if let Some(count) = progress.1 {
+                    format!("Received {} part(s) of a PSBT split in {} parts", progress.0, count)
+                } else {
+                    format!("Received {} part(s)", progress.0)
+                })
+                .style(theme::text::secondary)
+            }))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(Space::with_width(Length::Fill))
+                    .push(
+                        button::secondary(Some(icon::restore_icon()), "Select images")
+                            .on_press(Message::PsbtQr(PsbtQrMessage::SelectImages)),
+                    )
+                    .push(
+                        button::primary(None, "Import").on_press_maybe(
+                            (!ur.value.is_empty())
+                                .then_some(Message::PsbtQr(PsbtQrMessage::Confirm)),
+                        ),
+                    ),
+            ),
+    )
+    .width(Length::Fixed(500.0))
+    .into()
+}
+
 pub fn spend_header<'a>(
     tx: &'a SpendTx,
     labels_editing: &'a HashMap<String, form::Value<String>>,
@@ -375,6 +473,28 @@ pub fn spend_overview_view<'a>(
                                                 } else {
                                                     Some(Message::ImportPsbt)
                                                 }),
+                                            )
+                                            .push(
+                                                button::secondary(
+                                                    Some(icon::qr_code_icon()),
+                                                    "Show QR",
+                                                )
+                                                .on_press_maybe(if currently_signing {
+                                                    None
+                                                } else {
+                                                    Some(Message::ExportPsbtQr)
+                                                }),
+                                            )
+                                            .push(
+                                                button::secondary(
+                                                    Some(icon::qr_code_scan_icon()),
+                                                    "Scan QR",
+                                                )
+                                                .on_press_maybe(if currently_signing {
+                                                    None
+                                                } else {
+                                                    Some(Message::ImportPsbtQr)
+                                                }),
                                             ),
                                     )
                                     .align_y(Alignment::Center),
@@ -564,6 +684,39 @@ pub fn path_view<'a>(
     sigs: &'a PathSpendInfo,
     key_aliases: &'a HashMap<Fingerprint, String>,
 ) -> Element<'a, Message> {
+    // A nested threshold is displayed as the number of sub-paths still to be completed,
+    // followed by each of these sub-paths indented below it.
+    if let PathInfo::Thresh(_, subs) = path {
+        let missing_subs = sigs.threshold.saturating_sub(sigs.sigs_count);
+        return subs
+            .iter()
+            .zip(sigs.sub_paths.iter())
+            .fold(
+                Column::new().spacing(5).push(
+                    Row::new()
+                        .align_y(Alignment::Center)
+                        .push(if missing_subs == 0 {
+                            icon::circle_check_icon().style(theme::text::success)
+                        } else {
+                            icon::circle_cross_icon().style(theme::text::secondary)
+                        })
+                        .push(Space::with_width(Length::Fixed(20.0)))
+                        .push(
+                            p1_regular(format!("{} more of the following:", missing_subs))
+                                .style(theme::text::secondary),
+                        ),
+                ),
+                |col, (sub, sub_sigs)| {
+                    col.push(
+                        Row::new()
+                            .push(Space::with_width(Length::Fixed(40.0)))
+                            .push(path_view(sub, sub_sigs, key_aliases)),
+                    )
+                },
+            )
+            .into();
+    }
+
     // We get a sorted list of all the fingerprints (which correspond to a signer) from this
     // spending path, and from it get an iterator on those of these fingerprints for which a
     // signature was provided in the PSBT, and those for which there isn't any.
@@ -1035,6 +1188,7 @@ pub fn sign_action<'a>(
     descriptor: &LianaDescriptor,
     signer: Option<Fingerprint>,
     signer_alias: Option<&'a String>,
+    hot_signer_password: Option<&'a form::Value<String>>,
     signed: &HashSet<Fingerprint>,
     signing: &HashSet<Fingerprint>,
     recovery_timelock: Option<u16>,
@@ -1085,6 +1239,31 @@ pub fn sign_action<'a>(
                                 }
                             })
                         })
+                        .push_maybe(hot_signer_password.map(|password| {
+                            Row::new()
+                                .spacing(10)
+                                .align_y(Alignment::Center)
+                                .push(
+                                    form::Form::new("Hot signer password", password, |msg| {
+                                        Message::Spend(SpendTxMessage::HotSignerPasswordEdited(msg))
+                                    })
+                                    .secure(true)
+                                    .warning("Failed to unlock the hot signer")
+                                    .on_submit_maybe(if password.value.is_empty() {
+                                        None
+                                    } else {
+                                        Some(Message::Spend(SpendTxMessage::UnlockHotSigner))
+                                    })
+                                    .padding(10),
+                                )
+                                .push(button::primary(None, "Unlock").on_press_maybe(
+                                    if password.value.is_empty() {
+                                        None
+                                    } else {
+                                        Some(Message::Spend(SpendTxMessage::UnlockHotSigner))
+                                    },
+                                ))
+                        }))
                         .width(Length::Fill),
                 )
                 .spacing(20)
@@ -1,11 +1,13 @@
 use std::collections::{HashMap, HashSet};
 
+use chrono::{DateTime, Local, Utc};
 use iced::{
     widget::{checkbox, tooltip, Space},
     Alignment, Length,
 };
 
 use liana::miniscript::bitcoin::{
+    absolute::LOCK_TIME_THRESHOLD,
     bip32::{DerivationPath, Fingerprint},
     Amount,
 };
@@ -87,12 +89,15 @@ pub fn recovery<'a>(
     )
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn recovery_path_view<'a>(
     index: usize,
     threshold: usize,
     origins: &'a [(Fingerprint, HashSet<DerivationPath>)],
     total_amount: Amount,
     number_of_coins: usize,
+    absolute_timelock: Option<u32>,
+    time_timelock: Option<u16>,
     key_aliases: &'a HashMap<Fingerprint, String>,
     selected: bool,
 ) -> Element<'a, Message> {
@@ -148,6 +153,27 @@ pub fn recovery_path_view<'a>(
                         )))
                         .push(amount(&total_amount)),
                 )
+                .push_maybe(absolute_timelock.map(|tl| {
+                    text(if tl < LOCK_TIME_THRESHOLD {
+                        format!("Available since block {}", tl)
+                    } else {
+                        format!(
+                            "Available since {}",
+                            DateTime::<Utc>::from_timestamp(tl as i64, 0)
+                                .expect("Correct unix timestamp")
+                                .with_timezone(&Local)
+                                .format("%b. %d, %Y - %T")
+                        )
+                    })
+                    .small()
+                }))
+                .push_maybe(time_timelock.map(|tl| {
+                    text(format!(
+                        "For coins inactive for ~{} days",
+                        u32::from(tl) * 512 / 86400
+                    ))
+                    .small()
+                }))
                 .spacing(5),
         )
         .width(Length::Fill)
@@ -4,7 +4,7 @@ use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 
 use iced::alignment::{Horizontal, Vertical};
-use iced::widget::{container, Column, Rule};
+use iced::widget::{container, pick_list, Column, Rule};
 use iced::{
     alignment,
     widget::{radio, scrollable, tooltip as iced_tooltip, Space},
@@ -12,15 +12,15 @@ use iced::{
 };
 
 use liana::{
-    descriptors::{LianaDescriptor, LianaPolicy},
+    descriptors::{ExportFormat, LianaDescriptor, LianaPolicy},
     miniscript::bitcoin::{bip32::Fingerprint, Network},
 };
-use lianad::config::BitcoindRpcAuth;
+use lianad::{accounting::CostBasisMethod, config::BitcoindRpcAuth};
 
 use super::{dashboard, message::*};
 
 use liana_ui::{
-    component::{badge, button, card, form, separation, text::*, tooltip::tooltip},
+    component::{amount::*, badge, button, card, form, separation, text::*, tooltip::tooltip},
     icon,
     theme::{self},
     widget::*,
@@ -34,6 +34,8 @@ use crate::{
         settings::ProviderKey,
         view::{hw, warning::warn},
     },
+    daemon::model::VerifyProofOfReservesResult,
+    export::AccountingFormat,
     help,
     hw::HardwareWallet,
     node::{
@@ -113,6 +115,56 @@ fn export_section(
     .style(theme::card::simple)
 }
 
+fn export_signer_descriptor(format: ExportFormat) -> Container<'static, Message> {
+    let (title, description) = match format {
+        ExportFormat::Coldcard => (
+            "Descriptor - Coldcard",
+            "Descriptor file to register the wallet on a Coldcard.",
+        ),
+        ExportFormat::Bip388 => (
+            "Descriptor - BIP 388 wallet policy",
+            "Wallet policy file (.json) for signing devices supporting BIP 388.",
+        ),
+        ExportFormat::Specter => (
+            "Descriptor - Specter / Sparrow",
+            "Wallet file (.json) to import in Specter or Sparrow.",
+        ),
+        ExportFormat::Bsms => (
+            "Descriptor - BSMS",
+            "BIP 129 descriptor record (.bsms), with the first address for verification.",
+        ),
+    };
+    export_section(
+        title,
+        description,
+        icon::backup_icon(),
+        Message::Settings(SettingsMessage::ExportSignerDescriptor(format)),
+    )
+}
+
+fn export_accounting_report(format: AccountingFormat) -> Container<'static, Message> {
+    let (title, description) = match format {
+        AccountingFormat::Csv => (
+            "Accounting report",
+            ".CSV file of past transactions with their cost basis and realized gain.",
+        ),
+        AccountingFormat::Koinly => (
+            "Accounting report - Koinly",
+            ".CSV file of past transactions to import in Koinly.",
+        ),
+        AccountingFormat::CoinTracker => (
+            "Accounting report - CoinTracker",
+            ".CSV file of past transactions to import in CoinTracker.",
+        ),
+    };
+    export_section(
+        title,
+        description,
+        icon::backup_icon(),
+        Message::Settings(SettingsMessage::ExportAccountingReport(format)),
+    )
+}
+
 pub fn list(cache: &Cache, is_remote_backend: bool) -> Element<Message> {
     let header = Button::new(text("Settings").size(30).bold())
         .style(theme::button::transparent)
@@ -206,7 +258,13 @@ pub fn bitcoind_settings<'a>(
     )
 }
 
-pub fn import_export<'a>(cache: &'a Cache, warning: Option<&Error>) -> Element<'a, Message> {
+pub fn import_export<'a>(
+    cache: &'a Cache,
+    warning: Option<&Error>,
+    cost_basis_method: CostBasisMethod,
+    reserves_message: &'a form::Value<String>,
+    proven_reserves: Option<&'a VerifyProofOfReservesResult>,
+) -> Element<'a, Message> {
     let header = header("Import/Export", SettingsMessage::ImportExportSection);
 
     let description = Row::new()
@@ -258,6 +316,62 @@ pub fn import_export<'a>(cache: &'a Cache, warning: Option<&Error>) -> Element<'
         Message::Settings(SettingsMessage::ImportWallet),
     );
 
+    let cost_basis = Row::new()
+        .spacing(20)
+        .align_y(Alignment::Center)
+        .push(Space::with_width(15))
+        .push(text("Accounting report cost basis method:").bold())
+        .push(Space::with_width(Length::Fill))
+        .push(
+            pick_list(&CostBasisMethod::ALL[..], Some(cost_basis_method), |m| {
+                Message::Settings(SettingsMessage::CostBasisMethodSelected(m))
+            })
+            .style(theme::pick_list::primary)
+            .padding(10),
+        );
+
+    let reserves_message = Row::new()
+        .spacing(20)
+        .align_y(Alignment::Center)
+        .push(Space::with_width(15))
+        .push(text("Proof of reserves message:").bold())
+        .push(
+            form::Form::new("Message", reserves_message, |msg| {
+                Message::Settings(SettingsMessage::ProofOfReservesMessageEdited(msg))
+            })
+            .warning("Please enter the message the proof commits to")
+            .size(P1_SIZE)
+            .padding(10),
+        );
+
+    let export_proof_of_reserves = export_section(
+        "Proof of reserves",
+        "Unsigned BIP 127 proof of the confirmed coins, to be signed like a PSBT.",
+        icon::backup_icon(),
+        Message::Settings(SettingsMessage::ExportProofOfReserves),
+    );
+
+    let verify_proof_of_reserves = export_section(
+        "Verify proof of reserves",
+        "Check a signed BIP 127 proof against the message and the current UTXO set.",
+        icon::restore_icon(),
+        Message::Settings(SettingsMessage::VerifyProofOfReserves),
+    );
+
+    let proven_reserves = proven_reserves.map(|res| {
+        Row::new()
+            .spacing(10)
+            .align_y(Alignment::Center)
+            .push(Space::with_width(15))
+            .push(text("Proven reserves:").bold())
+            .push(amount(&res.amount))
+            .push(text(format!(
+                "({} coin{})",
+                res.coins.len(),
+                if res.coins.len() == 1 { "" } else { "s" }
+            )))
+    });
+
     let separator = Row::new()
         .push(Space::with_width(30))
         .push(text("Other formats"))
@@ -280,7 +394,26 @@ pub fn import_export<'a>(cache: &'a Cache, warning: Option<&Error>) -> Element<'
             .push(separator)
             .push(export_labels)
             .push(export_transactions)
+            .push(cost_basis)
+            .push(
+                AccountingFormat::ALL
+                    .iter()
+                    .fold(Column::new().spacing(20), |col, format| {
+                        col.push(export_accounting_report(*format))
+                    }),
+            )
+            .push(reserves_message)
+            .push(export_proof_of_reserves)
+            .push(verify_proof_of_reserves)
+            .push_maybe(proven_reserves)
             .push(export_descriptor)
+            .push(
+                ExportFormat::ALL
+                    .iter()
+                    .fold(Column::new().spacing(20), |col, format| {
+                        col.push(export_signer_descriptor(*format))
+                    }),
+            )
             .width(Length::Fill),
     )
 }
@@ -1013,6 +1146,11 @@ pub fn wallet_settings<'a>(
     wallet_alias: &'a form::Value<String>,
     keys_aliases: &'a [(Fingerprint, form::Value<String>)],
     provider_keys: &'a HashMap<Fingerprint, ProviderKey>,
+    unencrypted_hot_signer: Option<(
+        Fingerprint,
+        &'a form::Value<String>,
+        &'a form::Value<String>,
+    )>,
     processing: bool,
     updated: bool,
 ) -> Element<'a, Message> {
@@ -1144,17 +1282,93 @@ pub fn wallet_settings<'a>(
                 ))
                 .width(Length::Fill),
             )
-            .push(aliases),
+            .push(aliases)
+            .push_maybe(
+                unencrypted_hot_signer.map(|(fingerprint, password, confirmation)| {
+                    hot_signer_encryption(fingerprint, password, confirmation, processing)
+                }),
+            ),
     )
 }
 
+fn hot_signer_encryption<'a>(
+    fingerprint: Fingerprint,
+    password: &'a form::Value<String>,
+    confirmation: &'a form::Value<String>,
+    processing: bool,
+) -> Element<'a, Message> {
+    card::simple(
+        Column::new()
+            .spacing(10)
+            .push(text("Hot signer:").bold())
+            .push(text(format!(
+                "The mnemonic of the hot signer {} is stored unencrypted on this computer. \
+                 Set a password to encrypt it, it will be asked before signing with this key.",
+                fingerprint
+            )))
+            .push(
+                form::Form::new("Password", password, |msg| {
+                    Message::Settings(SettingsMessage::HotSignerPasswordEdited(msg))
+                })
+                .secure(true)
+                .size(P1_SIZE)
+                .padding(10),
+            )
+            .push(
+                form::Form::new("Confirm password", confirmation, |msg| {
+                    Message::Settings(SettingsMessage::HotSignerPasswordConfirmationEdited(msg))
+                })
+                .secure(true)
+                .warning("Passwords do not match")
+                .size(P1_SIZE)
+                .padding(10),
+            )
+            .push(Row::new().push(Space::with_width(Length::Fill)).push(
+                if !processing && !password.value.is_empty() && password.value == confirmation.value
+                {
+                    button::secondary(None, "Encrypt")
+                        .on_press(Message::Settings(SettingsMessage::EncryptHotSigner))
+                } else {
+                    button::secondary(None, "Encrypt")
+                },
+            )),
+    )
+    .width(Length::Fill)
+    .into()
+}
+
 fn display_policy<'a>(
     policy: LianaPolicy,
     keys_aliases: &'a [(Fingerprint, form::Value<String>)],
     provider_keys: &'a HashMap<Fingerprint, ProviderKey>,
 ) -> Element<'a, Message> {
     let (primary_threshold, primary_keys) = policy.primary_path().thresh_origins();
-    let recovery_paths = policy.recovery_paths();
+    // Block-based recovery paths first, then time-based ones.
+    let recovery_paths: Vec<_> = policy
+        .recovery_paths()
+        .iter()
+        .map(|(sequence, path)| {
+            (
+                format!(
+                    "{} blocks (~{})",
+                    sequence,
+                    expire_message_units(*sequence as u32).join(",")
+                ),
+                *sequence == u16::MAX,
+                path,
+            )
+        })
+        .chain(policy.time_recovery_paths().iter().map(|(units, path)| {
+            (
+                format!(
+                    "~{}",
+                    duration_message_units(*units as u32 * 512 / 60).join(",")
+                ),
+                false,
+                path,
+            )
+        }))
+        .collect();
 
     // The iteration over an HashMap keys can have a different order at each refresh
     let mut primary_keys: Vec<Fingerprint> = primary_keys.into_keys().collect();
@@ -1208,7 +1422,7 @@ fn display_policy<'a>(
             )
             .push(text("can always spend this wallet's funds (Primary path)")),
     );
-    for (i, (sequence, recovery_path)) in recovery_paths.iter().enumerate() {
+    for (i, (timelock, max_timelock, recovery_path)) in recovery_paths.iter().enumerate() {
         let (threshold, recovery_keys) = recovery_path.thresh_origins();
 
         // The iteration over an HashMap keys can have a different order at each refresh
@@ -1260,17 +1474,10 @@ fn display_policy<'a>(
                     },
                 ))
                 .push(text("can spend coins inactive for"))
-                .push(
-                    text(format!(
-                        "{} blocks (~{})",
-                        sequence,
-                        expire_message_units(*sequence as u32).join(",")
-                    ))
-                    .bold(),
-                )
+                .push(text(timelock).bold())
                 .push(text(
                     // If max timelock and all keys are from provider, then it's a safety net path.
-                    if *sequence == u16::MAX
+                    if *max_timelock
                         && recovery_keys
                             .iter()
                             .all(|fg| provider_keys.contains_key(fg))
@@ -1293,7 +1500,11 @@ fn display_policy<'a>(
 
 /// returns y,m,d
 fn expire_message_units(sequence: u32) -> Vec<String> {
-    let mut n_minutes = sequence * 10;
+    duration_message_units(sequence * 10)
+}
+
+/// returns y,m,d or h,m for a duration in minutes
+fn duration_message_units(mut n_minutes: u32) -> Vec<String> {
     let n_years = n_minutes / 525960;
     n_minutes -= n_years * 525960;
     let n_months = n_minutes / 43830;
@@ -132,7 +132,7 @@ pub fn create_spend_tx<'a>(
     is_valid: bool,
     duplicate: bool,
     timelock: u16,
-    recovery_timelock: Option<u16>,
+    is_recovery: bool,
     coins: &[(Coin, bool)],
     coins_labels: &'a HashMap<String, String>,
     batch_label: &form::Value<String>,
@@ -146,7 +146,7 @@ pub fn create_spend_tx<'a>(
     let is_self_send = recipients.is_empty();
 
     // Title
-    let title = h3(if recovery_timelock.is_some() {
+    let title = h3(if is_recovery {
         "Recovery"
     } else if is_self_send {
         "Self-transfer"
@@ -171,16 +171,22 @@ pub fn create_spend_tx<'a>(
 
     // Add payment row
     let duplicates_warning = duplicate.then_some(
-        Container::new(text("Two payment addresses are the same").style(theme::text::warning))
+        Container::new(text("Some payments are to the same address").style(theme::text::warning))
             .padding(10),
     );
-    let add_payment_btn = (!(is_self_send || recovery_timelock.is_some())).then_some(
+    let import_payments_btn = (!(is_self_send || is_recovery)).then_some(
+        button::secondary(Some(icon::import_icon()), "Import payments")
+            .on_press(Message::CreateSpend(CreateSpendMessage::ImportPayments)),
+    );
+    let add_payment_btn = (!(is_self_send || is_recovery)).then_some(
         button::secondary(Some(icon::plus_icon()), "Add payment")
             .on_press(Message::CreateSpend(CreateSpendMessage::AddRecipient)),
     );
     let add_payment_row = Row::new()
+        .spacing(10)
         .push_maybe(duplicates_warning)
         .push(Space::with_width(Length::Fill))
+        .push_maybe(import_payments_btn)
         .push_maybe(add_payment_btn);
 
     // Fee-rate row
@@ -217,7 +223,7 @@ pub fn create_spend_tx<'a>(
         .wrap();
 
     // Coin selection
-    let selected_amount = (is_self_send || recovery_timelock.is_some()).then_some(
+    let selected_amount = (is_self_send || is_recovery).then_some(
         Row::new()
             .spacing(5)
             .push(amount_with_size(
@@ -310,11 +316,8 @@ pub fn create_spend_tx<'a>(
         .on_press(Message::CreateSpend(CreateSpendMessage::Clear))
         .width(Length::Fixed(100.0));
     let next = if is_valid
-        && !duplicate
         && error.is_none()
-        && (is_self_send
-            || recovery_timelock.is_some()
-            || Some(&Amount::from_sat(0)) == amount_left)
+        && (is_self_send || is_recovery || Some(&Amount::from_sat(0)) == amount_left)
     {
         button::primary(None, "Next")
             .on_press(Message::CreateSpend(CreateSpendMessage::Generate))
@@ -341,7 +344,7 @@ pub fn create_spend_tx<'a>(
         .spacing(20);
 
     dashboard(
-        if recovery_timelock.is_some() {
+        if is_recovery {
             &Menu::Recovery
         } else {
             &Menu::CreateSpendTx
@@ -20,7 +20,7 @@ use crate::{
         menu::Menu,
         view::{
             dashboard, label,
-            message::{CreateRbfMessage, Message},
+            message::{CreateCpfpMessage, CreateRbfMessage, Message},
             warning::warn,
         },
     },
@@ -301,6 +301,73 @@ pub fn create_rbf_modal<'a>(
     .into()
 }
 
+/// Return the modal view for a new CPFP transaction, spending the unconfirmed
+/// coins of the transaction to speed up.
+pub fn create_cpfp_modal<'a>(
+    feerate: &form::Value<String>,
+    child_txid: Option<Txid>,
+    warning: Option<&'a Error>,
+) -> Element<'a, Message> {
+    let mut confirm_button = button::secondary(None, "Confirm").width(Length::Fixed(200.0));
+    if feerate.valid {
+        confirm_button = confirm_button.on_press(Message::CreateCpfp(CreateCpfpMessage::Confirm));
+    }
+    card::simple(
+        Column::new()
+            .spacing(10)
+            .push(Container::new(h4_bold("Speed up transaction")).width(Length::Fill))
+            .push(Row::new().push(text(
+                "Create a new transaction spending the unconfirmed coins \
+                this transaction pays to your wallet, with a fee high enough \
+                for both transactions to reach the target feerate. \
+                New inputs may be used for the new transaction.",
+            )))
+            .push(
+                Row::new()
+                    .push(Container::new(p1_bold("Feerate")).padding(10))
+                    .spacing(10)
+                    .push(
+                        if child_txid.is_none() {
+                            form::Form::new_trimmed("", feerate, move |msg| {
+                                Message::CreateCpfp(CreateCpfpMessage::FeerateEdited(msg))
+                            })
+                            .warning(
+                                "Feerate must be greater than 0 and \
+                                less than or equal to 1000 sats/vbyte",
+                            )
+                        } else {
+                            form::Form::new_disabled("", feerate)
+                        }
+                        .size(P1_SIZE)
+                        .padding(10),
+                    )
+                    .width(Length::Fill),
+            )
+            .push(warn(warning))
+            .push(Row::new().push(if child_txid.is_none() {
+                Row::new().push(confirm_button)
+            } else {
+                Row::new()
+                    .spacing(10)
+                    .align_y(Alignment::Center)
+                    .push(icon::circle_check_icon().style(theme::text::secondary))
+                    .push(
+                        text("PSBT created successfully and ready to be signed")
+                            .style(theme::text::success),
+                    )
+            }))
+            .push_maybe(child_txid.map(|id| {
+                Row::new().push(
+                    button::primary(None, "Go to PSBT")
+                        .width(Length::Fixed(200.0))
+                        .on_press(Message::Menu(Menu::PsbtPreSelected(id))),
+                )
+            })),
+    )
+    .width(Length::Fixed(800.0))
+    .into()
+}
+
 pub fn tx_view<'a>(
     cache: &'a Cache,
     tx: &'a HistoryTransaction,
@@ -367,15 +434,16 @@ pub fn tx_view<'a>(
             )
             // If unconfirmed, give option to use RBF.
             // Check fee amount is some as otherwise we may be missing coins for this transaction.
-            .push_maybe(if tx.time.is_none() && tx.fee_amount.is_some() {
+            // If some of its outputs are ours, also give option to use CPFP.
+            .push_maybe(if tx.time.is_none() && (tx.fee_amount.is_some() || !tx.change_indexes.is_empty()) {
                 Some(
                     Row::new()
-                        .push(
+                        .push_maybe(tx.fee_amount.map(|_| {
                             button::secondary(None, "Bump fee")
                                 .width(Length::Fixed(200.0))
-                                .on_press(Message::CreateRbf(super::CreateRbfMessage::New(false))),
-                        )
-                        .push(
+                                .on_press(Message::CreateRbf(super::CreateRbfMessage::New(false)))
+                        }))
+                        .push_maybe(tx.fee_amount.map(|_| {
                             tooltip::Tooltip::new(
                                 button::secondary(None, "Cancel transaction")
                                 .width(Length::Fixed(200.0))
@@ -383,7 +451,18 @@ pub fn tx_view<'a>(
                                 "Best effort attempt at double spending an unconfirmed outgoing transaction",
                                 tooltip::Position::Top,
                             )
-                        )
+                        }))
+                        .push_maybe(if tx.change_indexes.is_empty() {
+                            None
+                        } else {
+                            Some(tooltip::Tooltip::new(
+                                button::secondary(None, "Speed up")
+                                .width(Length::Fixed(200.0))
+                                .on_press(Message::CreateCpfp(CreateCpfpMessage::New)),
+                                "Spend the coins this transaction pays to the wallet to incentivize its confirmation",
+                                tooltip::Position::Top,
+                            ))
+                        })
                         .spacing(10),
                 )
             } else {
@@ -4,10 +4,17 @@ use std::sync::Arc;
 use crate::app::cache::FiatPrice;
 use crate::dir::LianaDirectory;
 use crate::{
-    app::settings, daemon::DaemonBackend, hw::HardwareWalletConfig, node::NodeType, signer::Signer,
+    app::settings,
+    daemon::DaemonBackend,
+    hw::HardwareWalletConfig,
+    node::NodeType,
+    signer::{LockedSigner, Signer},
 };
 
-use liana::{miniscript::bitcoin, signer::HotSigner};
+use liana::{
+    miniscript::bitcoin,
+    signer::{EncryptedMnemonic, HotSigner},
+};
 
 use liana::descriptors::LianaDescriptor;
 use liana::miniscript::bitcoin::bip32::Fingerprint;
@@ -42,6 +49,8 @@ pub struct Wallet {
     pub provider_keys: HashMap<Fingerprint, settings::ProviderKey>,
     pub hardware_wallets: Vec<HardwareWalletConfig>,
     pub signer: Option<Arc<Signer>>,
+    // A hot signer whose mnemonic is encrypted, which needs a password to sign.
+    pub locked_signer: Option<Arc<LockedSigner>>,
     pub fiat_price_setting: Option<fiat::PriceSetting>,
     pub remote_backend_auth: Option<settings::AuthConfig>,
 }
@@ -63,6 +72,7 @@ impl Wallet {
             provider_keys: HashMap::new(),
             hardware_wallets: Vec::new(),
             signer: None,
+            locked_signer: None,
             fiat_price_setting: None,
             remote_backend_auth: None,
         }
@@ -111,6 +121,19 @@ impl Wallet {
         self
     }
 
+    pub fn with_locked_signer(mut self, locked_signer: LockedSigner) -> Self {
+        self.locked_signer = Some(Arc::new(locked_signer));
+        self
+    }
+
+    /// The fingerprint of the hot signer of this wallet, whether it is locked or not.
+    pub fn hot_signer_fingerprint(&self) -> Option<Fingerprint> {
+        self.signer
+            .as_ref()
+            .map(|s| s.fingerprint())
+            .or_else(|| self.locked_signer.as_ref().map(|s| s.fingerprint()))
+    }
+
     pub fn with_fiat_price_setting(
         mut self,
         fiat_price_setting: Option<fiat::PriceSetting>,
@@ -158,7 +181,7 @@ impl Wallet {
         datadir_path: &LianaDirectory,
         network: bitcoin::Network,
     ) -> Result<Self, WalletError> {
-        let hot_signers = match HotSigner::from_datadir(datadir_path.path(), network) {
+        let hot_signers = match HotSigner::from_datadir(datadir_path.path(), network, None) {
             Ok(signers) => signers,
             Err(e) => match e {
                 liana::signer::SignerError::MnemonicStorage(e) => {
@@ -178,7 +201,17 @@ impl Wallet {
             .into_iter()
             .find(|s| keys.contains(&s.fingerprint(&curve)))
         {
-            Ok(self.with_signer(Signer::new(hot_signer)))
+            return Ok(self.with_signer(Signer::new(hot_signer)));
+        }
+
+        // If the mnemonic is encrypted, it will only be decrypted when the user needs to sign.
+        let encrypted_mnemonics =
+            EncryptedMnemonic::from_datadir(datadir_path.path(), network).unwrap_or_default();
+        if let Some(mnemonic) = encrypted_mnemonics
+            .into_iter()
+            .find(|m| keys.contains(&m.fingerprint()))
+        {
+            Ok(self.with_locked_signer(LockedSigner::new(mnemonic, network)))
         } else {
             Ok(self)
         }
@@ -292,6 +325,7 @@ pub fn sync_status(
         // treat it the same as bitcoind to be sure we don't mislead the user.
         if daemon_backend == DaemonBackend::RemoteBackend
             || daemon_backend == DaemonBackend::EmbeddedLianad(Some(NodeType::Electrum))
+            || daemon_backend == DaemonBackend::EmbeddedLianad(Some(NodeType::Esplora))
         {
             return SyncStatus::WalletFullScan;
         }
@@ -4,7 +4,10 @@ use std::iter::FromIterator;
 
 use async_trait::async_trait;
 use lianad::bip329::Labels;
-use lianad::commands::{GetLabelsBip329Result, UpdateDerivIndexesResult};
+use lianad::commands::{
+    CreateProofOfReservesResult, DiscoverAddressesResult, GetLabelsBip329Result,
+    UpdateDerivIndexesResult,
+};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -115,6 +118,13 @@ impl<C: Client + Send + Sync + Debug> Daemon for Lianad<C> {
         self.call("updatederivationindexes", Some(vec![receive, change]))
     }
 
+    async fn discover_addresses(
+        &self,
+        gap_limit: Option<u32>,
+    ) -> Result<DiscoverAddressesResult, DaemonError> {
+        self.call("discoveraddresses", Some(vec![gap_limit]))
+    }
+
     async fn list_coins(
         &self,
         statuses: &[CoinStatus],
@@ -129,6 +139,18 @@ impl<C: Client + Send + Sync + Debug> Daemon for Lianad<C> {
         )
     }
 
+    async fn freeze_coins(&self, outpoints: &[OutPoint]) -> Result<(), DaemonError> {
+        let _res: serde_json::value::Value =
+            self.call("freezecoins", Some(vec![json!(outpoints)]))?;
+        Ok(())
+    }
+
+    async fn unfreeze_coins(&self, outpoints: &[OutPoint]) -> Result<(), DaemonError> {
+        let _res: serde_json::value::Value =
+            self.call("unfreezecoins", Some(vec![json!(outpoints)]))?;
+        Ok(())
+    }
+
     async fn list_spend_txs(&self) -> Result<ListSpendResult, DaemonError> {
         self.call("listspendtxs", Option::<Request>::None)
     }
@@ -136,7 +158,7 @@ impl<C: Client + Send + Sync + Debug> Daemon for Lianad<C> {
     async fn create_spend_tx(
         &self,
         coins_outpoints: &[OutPoint],
-        destinations: &HashMap<Address<address::NetworkUnchecked>, u64>,
+        destinations: &[Destination],
         feerate_vb: u64,
         change_address: Option<Address<address::NetworkUnchecked>>,
     ) -> Result<CreateSpendResult, DaemonError> {
@@ -164,6 +186,14 @@ impl<C: Client + Send + Sync + Debug> Daemon for Lianad<C> {
         self.call("rbfpsbt", Some(input))
     }
 
+    async fn cpfp_bump(
+        &self,
+        outpoints: &[OutPoint],
+        feerate_vb: u64,
+    ) -> Result<CreateSpendResult, DaemonError> {
+        self.call("cpfpbump", Some(vec![json!(outpoints), json!(feerate_vb)]))
+    }
+
     async fn update_spend_tx(&self, psbt: &Psbt) -> Result<(), DaemonError> {
         let spend_tx = psbt.to_string();
         let _res: serde_json::value::Value = self.call("updatespend", Some(vec![spend_tx]))?;
@@ -221,6 +251,38 @@ impl<C: Client + Send + Sync + Debug> Daemon for Lianad<C> {
         Ok(res.psbt)
     }
 
+    async fn create_absolute_recovery(
+        &self,
+        address: Address<address::NetworkUnchecked>,
+        coins_outpoints: &[OutPoint],
+        feerate_vb: u64,
+        timelock: u32,
+    ) -> Result<Psbt, DaemonError> {
+        let mut params = serde_json::Map::new();
+        params.insert("address".to_string(), json!(address));
+        params.insert("outpoints".to_string(), json!(coins_outpoints));
+        params.insert("feerate".to_string(), json!(feerate_vb));
+        params.insert("absolute_timelock".to_string(), json!(timelock));
+        let res: CreateRecoveryResult = self.call("createrecovery", Some(params))?;
+        Ok(res.psbt)
+    }
+
+    async fn create_time_recovery(
+        &self,
+        address: Address<address::NetworkUnchecked>,
+        coins_outpoints: &[OutPoint],
+        feerate_vb: u64,
+        timelock: u16,
+    ) -> Result<Psbt, DaemonError> {
+        let mut params = serde_json::Map::new();
+        params.insert("address".to_string(), json!(address));
+        params.insert("outpoints".to_string(), json!(coins_outpoints));
+        params.insert("feerate".to_string(), json!(feerate_vb));
+        params.insert("time_timelock".to_string(), json!(timelock));
+        let res: CreateRecoveryResult = self.call("createrecovery", Some(params))?;
+        Ok(res.psbt)
+    }
+
     async fn get_labels(
         &self,
         items: &HashSet<LabelItem>,
@@ -250,6 +312,29 @@ impl<C: Client + Send + Sync + Debug> Daemon for Lianad<C> {
             self.call("getlabelsbip329", Some(vec![json!(offset), json!(limit)]))?;
         Ok(res.labels)
     }
+
+    async fn create_proof_of_reserves(
+        &self,
+        message: &str,
+        coins_outpoints: &[OutPoint],
+    ) -> Result<Psbt, DaemonError> {
+        let res: CreateProofOfReservesResult = self.call(
+            "createproofofreserves",
+            Some(vec![json!(message), json!(coins_outpoints)]),
+        )?;
+        Ok(res.psbt)
+    }
+
+    async fn verify_proof_of_reserves(
+        &self,
+        psbt: &Psbt,
+        message: &str,
+    ) -> Result<VerifyProofOfReservesResult, DaemonError> {
+        self.call(
+            "verifyproofofreserves",
+            Some(vec![psbt.to_string(), message.to_string()]),
+        )
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
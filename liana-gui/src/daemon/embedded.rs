@@ -1,6 +1,8 @@
 use lianad::bip329::Labels;
-use lianad::commands::UpdateDerivIndexesResult;
+use lianad::commands::{DiscoverAddressesResult, UpdateDerivIndexesResult};
 use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Weak};
 use tokio::sync::Mutex;
 
 use super::{model::*, node, Daemon, DaemonBackend, DaemonError};
@@ -12,36 +14,286 @@ use liana::miniscript::bitcoin::{
 use lianad::{
     commands::{CoinStatus, LabelItem},
     config::Config,
+    datadir::DataDirectory,
+    wallets::Wallets,
     DaemonControl, DaemonHandle,
 };
+use tracing::{info, warn};
 
-pub struct EmbeddedDaemon {
+/// The file in the data directory of a wallet hosted by the daemon of another wallet, which holds
+/// the path to the data directory of the latter.
+const HOST_FILE_NAME: &str = "hosted_by";
+
+/// The suffix appended to the database file of a wallet once it was imported into the database
+/// of the daemon hosting it. It is kept as a backup.
+const IMPORTED_DB_SUFFIX: &str = "imported";
+
+// The daemons running in this process. Wallets for the same network and Bitcoin backend are hosted
+// by a single daemon, sharing its connection to the backend and its poller. A daemon is stopped
+// once no wallet uses it anymore.
+static HOSTS: std::sync::Mutex<Vec<Weak<Host>>> = std::sync::Mutex::new(Vec::new());
+
+/// A daemon hosting one or more wallets. Its main wallet is the one it was started for.
+struct Host {
     config: Config,
+    data_dir: PathBuf,
+    wallets: Wallets,
     handle: Mutex<Option<DaemonHandle>>,
 }
 
-impl EmbeddedDaemon {
-    pub fn start(config: Config) -> Result<EmbeddedDaemon, DaemonError> {
+impl Host {
+    fn start(config: Config, data_dir: PathBuf) -> Result<Arc<Host>, DaemonError> {
         let handle =
             DaemonHandle::start_default(config.clone(), false).map_err(DaemonError::Start)?;
-        Ok(Self {
+        let wallets = match &handle {
+            DaemonHandle::Controller { wallets, .. } => wallets.clone(),
+            _ => unreachable!("No lianad rpc server must be started"),
+        };
+        let host = Arc::new(Host {
+            config,
+            data_dir,
+            wallets,
             handle: Mutex::new(Some(handle)),
+        });
+        let mut hosts = HOSTS.lock().expect("Hosts lock poisoned");
+        hosts.retain(|h| h.strong_count() > 0);
+        hosts.push(Arc::downgrade(&host));
+        Ok(host)
+    }
+
+    /// Get the running daemon whose main wallet has this data directory, if any.
+    fn running(data_dir: &Path) -> Option<Arc<Host>> {
+        HOSTS
+            .lock()
+            .expect("Hosts lock poisoned")
+            .iter()
+            .filter_map(Weak::upgrade)
+            .find(|h| h.data_dir == data_dir)
+    }
+
+    /// Get a running daemon which may host a wallet with this configuration, if any.
+    fn compatible(config: &Config) -> Option<Arc<Host>> {
+        let backend = serde_json::to_value(&config.bitcoin_backend).ok()?;
+        HOSTS
+            .lock()
+            .expect("Hosts lock poisoned")
+            .iter()
+            .filter_map(Weak::upgrade)
+            .find(|h| {
+                h.config.bitcoin_config.network == config.bitcoin_config.network
+                    && serde_json::to_value(&h.config.bitcoin_backend)
+                        .ok()
+                        .as_ref()
+                        == Some(&backend)
+            })
+    }
+
+    /// Get the daemon whose main wallet has this data directory, starting it if it isn't running.
+    fn get_or_start(data_dir: &Path) -> Result<Arc<Host>, DaemonError> {
+        if let Some(host) = Host::running(data_dir) {
+            return Ok(host);
+        }
+        let config = Config::from_file(Some(data_dir.join("daemon.toml")))
+            .map_err(|e| DaemonError::Unexpected(e.to_string()))?;
+        Host::start(config, data_dir.to_path_buf())
+    }
+
+    /// Stop the daemon if this was its last user.
+    fn release(self: Arc<Self>) -> Result<(), DaemonError> {
+        if let Some(host) = Arc::into_inner(self) {
+            if let Some(h) = host.handle.into_inner() {
+                h.stop()
+                    .map_err(|e| DaemonError::Unexpected(e.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// The name under which a wallet is hosted by the daemon of another wallet.
+fn hosted_wallet_name(data_dir: &DataDirectory) -> Option<String> {
+    data_dir
+        .path()
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.to_string())
+}
+
+// The data directory of the main wallet of the daemon hosting this wallet, if it is hosted by
+// another wallet's daemon.
+fn hosted_by(data_dir: &DataDirectory) -> Option<PathBuf> {
+    std::fs::read_to_string(data_dir.path().join(HOST_FILE_NAME))
+        .ok()
+        .map(|path| PathBuf::from(path.trim()))
+}
+
+/// Whether the database of the wallet with this data directory also stores wallets hosted by its
+/// daemon.
+pub fn hosts_other_wallets(data_dir: &DataDirectory, wallet_data_dirs: &[DataDirectory]) -> bool {
+    // The data directories in the daemon configurations are canonicalized.
+    let path = data_dir
+        .path()
+        .canonicalize()
+        .unwrap_or_else(|_| data_dir.path().to_path_buf());
+    wallet_data_dirs
+        .iter()
+        .any(|dir| hosted_by(dir).as_deref() == Some(path.as_path()))
+}
+
+pub struct EmbeddedDaemon {
+    config: Config,
+    host: Mutex<Option<Arc<Host>>>,
+    // The name of this wallet in the daemon hosting it, if it isn't its main wallet.
+    wallet: Option<String>,
+}
+
+impl EmbeddedDaemon {
+    /// Start the daemon of the wallet with this configuration. If a daemon for the same network
+    /// and Bitcoin backend is already running, the wallet is hosted by this daemon instead. It is
+    /// then stored in the database of the latter from now on.
+    pub fn start(config: Config) -> Result<EmbeddedDaemon, DaemonError> {
+        let data_dir = config.data_directory().ok_or_else(|| {
+            DaemonError::Unexpected("No data directory for the wallet".to_string())
+        })?;
+
+        // This wallet was previously stored in the database of another wallet.
+        if let Some(host_dir) = hosted_by(&data_dir) {
+            if config_backend_differs(&config, &host_dir) {
+                warn!(
+                    "The Bitcoin backend of the wallet at '{}' differs from the one of the wallet hosting it, which is used.",
+                    data_dir.path().display()
+                );
+            }
+            let host = Host::get_or_start(&host_dir)?;
+            let name = hosted_wallet_name(&data_dir).ok_or_else(|| {
+                DaemonError::Unexpected("Invalid wallet data directory".to_string())
+            })?;
+            if let Err(e) = host.wallets.load_wallet_from(name.clone(), &data_dir) {
+                host.release()?;
+                return Err(DaemonError::Unexpected(e.to_string()));
+            }
+            return Ok(Self::hosted(config, host, name));
+        }
+
+        // The daemon of this wallet is already running, hosting other wallets.
+        if let Some(host) = Host::running(data_dir.path()) {
+            return Ok(Self::main(config, host));
+        }
+
+        // Otherwise try to host this wallet in the daemon of another one.
+        if let Some(host) = Host::compatible(&config) {
+            if let Some(name) = hosted_wallet_name(&data_dir) {
+                let db_path = data_dir.sqlite_db_file_path();
+                match host
+                    .wallets
+                    .import_wallet(name.clone(), &db_path, &data_dir)
+                {
+                    Ok(()) => {
+                        if let Err(e) = mark_as_hosted(&data_dir, &host.data_dir) {
+                            // Keep using the database of the wallet then.
+                            if let Err(e) = host.wallets.unload_wallet(name) {
+                                warn!("Error unloading wallet: '{}'", e);
+                            }
+                            host.release()?;
+                            return Err(DaemonError::Unexpected(e.to_string()));
+                        }
+                        info!(
+                            "Wallet at '{}' is now hosted by the daemon of the wallet at '{}'.",
+                            data_dir.path().display(),
+                            host.data_dir.display()
+                        );
+                        return Ok(Self::hosted(config, host, name));
+                    }
+                    // For instance if the database of the wallet must first be upgraded by its own
+                    // daemon.
+                    Err(e) => warn!("Could not host wallet '{}': '{}'", name, e),
+                }
+            }
+        }
+
+        let host = Host::start(config.clone(), data_dir.path().to_path_buf())?;
+        Ok(Self::main(config, host))
+    }
+
+    fn main(config: Config, host: Arc<Host>) -> Self {
+        Self {
             config,
-        })
+            host: Mutex::new(Some(host)),
+            wallet: None,
+        }
+    }
+
+    fn hosted(config: Config, host: Arc<Host>, name: String) -> Self {
+        Self {
+            config,
+            host: Mutex::new(Some(host)),
+            wallet: Some(name),
+        }
     }
 
     pub async fn command<T, F>(&self, method: F) -> Result<T, DaemonError>
     where
         F: FnOnce(&mut DaemonControl) -> Result<T, DaemonError>,
     {
-        match self.handle.lock().await.as_mut() {
-            Some(DaemonHandle::Controller { control, .. }) => method(control),
-            Some(_) => unreachable!("No lianad rpc server must be started"),
-            None => Err(DaemonError::DaemonStopped),
+        let host = self.host.lock().await;
+        match host.as_ref() {
+            Some(host) if host.handle.lock().await.is_some() => {
+                let mut control = host
+                    .wallets
+                    .get(self.wallet.as_deref())
+                    .map_err(|e| DaemonError::Unexpected(e.to_string()))?;
+                method(&mut control)
+            }
+            _ => Err(DaemonError::DaemonStopped),
         }
     }
 }
 
+// Whether the Bitcoin backend of this configuration differs from the one of the wallet with this
+// data directory.
+fn config_backend_differs(config: &Config, data_dir: &Path) -> bool {
+    Config::from_file(Some(data_dir.join("daemon.toml")))
+        .ok()
+        .map(|host_config| {
+            serde_json::to_value(&host_config.bitcoin_backend).ok()
+                != serde_json::to_value(&config.bitcoin_backend).ok()
+        })
+        .unwrap_or(false)
+}
+
+/// Apply the Bitcoin backend of this configuration to the daemon hosting the wallet, if it is
+/// hosted by the daemon of another wallet. The backend is shared by all the wallets it hosts, and
+/// the new one is used once it is restarted.
+pub fn update_host_backend(config: &Config) -> Result<(), DaemonError> {
+    let Some(host_dir) = config.data_directory().as_ref().and_then(hosted_by) else {
+        return Ok(());
+    };
+    let config_path = host_dir.join("daemon.toml");
+    let mut host_config = Config::from_file(Some(config_path.clone()))
+        .map_err(|e| DaemonError::Unexpected(e.to_string()))?;
+    host_config.bitcoin_backend = config.bitcoin_backend.clone();
+    let content =
+        toml::to_string(&host_config).map_err(|e| DaemonError::Unexpected(e.to_string()))?;
+    std::fs::write(config_path, content).map_err(|e| DaemonError::Unexpected(e.to_string()))
+}
+
+// Record that the wallet with this data directory is now stored in the database of the wallet with
+// the given data directory. Its own database is kept as a backup but isn't used anymore.
+fn mark_as_hosted(data_dir: &DataDirectory, host_dir: &Path) -> Result<(), std::io::Error> {
+    std::fs::write(
+        data_dir.path().join(HOST_FILE_NAME),
+        host_dir.to_string_lossy().as_bytes(),
+    )?;
+    let db_path = data_dir.sqlite_db_file_path();
+    if let Err(e) = std::fs::rename(
+        &db_path,
+        db_path.with_extension(format!("sqlite3.{}", IMPORTED_DB_SUFFIX)),
+    ) {
+        warn!("Error renaming the database of the hosted wallet: '{}'", e);
+    }
+    Ok(())
+}
+
 impl<T> From<std::sync::PoisonError<T>> for DaemonError {
     fn from(value: std::sync::PoisonError<T>) -> Self {
         DaemonError::Unexpected(format!("Daemon panic: {}", value))
@@ -61,7 +313,7 @@ impl Daemon for EmbeddedDaemon {
             .config
             .bitcoin_backend
             .as_ref()
-            .map(node::NodeType::from);
+            .and_then(node::NodeType::from_backend);
         DaemonBackend::EmbeddedLianad(node_type)
     }
 
@@ -74,25 +326,32 @@ impl Daemon for EmbeddedDaemon {
         _datadir: &LianaDirectory,
         _network: Network,
     ) -> Result<(), DaemonError> {
-        let mut handle = self.handle.lock().await;
-        if let Some(h) = handle.as_ref() {
-            if h.is_alive() {
-                return Ok(());
+        let host = self.host.lock().await;
+        if let Some(host) = host.as_ref() {
+            let mut handle = host.handle.lock().await;
+            if let Some(h) = handle.as_ref() {
+                if h.is_alive() {
+                    return Ok(());
+                }
+            }
+            // if the daemon poller is not alive, we try to terminate it to fetch the error.
+            if let Some(h) = handle.take() {
+                h.stop()
+                    .map_err(|e| DaemonError::Unexpected(e.to_string()))?;
             }
-        }
-        // if the daemon poller is not alive, we try to terminate it to fetch the error.
-        if let Some(h) = handle.take() {
-            h.stop()
-                .map_err(|e| DaemonError::Unexpected(e.to_string()))?;
         }
         Ok(())
     }
 
     async fn stop(&self) -> Result<(), DaemonError> {
-        let mut handle = self.handle.lock().await;
-        if let Some(h) = handle.take() {
-            h.stop()
-                .map_err(|e| DaemonError::Unexpected(e.to_string()))?;
+        let host = self.host.lock().await.take();
+        if let Some(host) = host {
+            if let Some(name) = &self.wallet {
+                if let Err(e) = host.wallets.unload_wallet(name.clone()) {
+                    warn!("Error unloading wallet '{}': '{}'", name, e);
+                }
+            }
+            host.release()?;
         }
         Ok(())
     }
@@ -133,6 +392,18 @@ impl Daemon for EmbeddedDaemon {
         .await
     }
 
+    async fn discover_addresses(
+        &self,
+        gap_limit: Option<u32>,
+    ) -> Result<DiscoverAddressesResult, DaemonError> {
+        self.command(|daemon| {
+            daemon
+                .discover_addresses(gap_limit)
+                .map_err(|e| DaemonError::Unexpected(e.to_string()))
+        })
+        .await
+    }
+
     async fn list_coins(
         &self,
         statuses: &[CoinStatus],
@@ -142,6 +413,24 @@ impl Daemon for EmbeddedDaemon {
             .await
     }
 
+    async fn freeze_coins(&self, outpoints: &[OutPoint]) -> Result<(), DaemonError> {
+        self.command(|daemon| {
+            daemon
+                .freeze_coins(outpoints)
+                .map_err(|e| DaemonError::Unexpected(e.to_string()))
+        })
+        .await
+    }
+
+    async fn unfreeze_coins(&self, outpoints: &[OutPoint]) -> Result<(), DaemonError> {
+        self.command(|daemon| {
+            daemon
+                .unfreeze_coins(outpoints)
+                .map_err(|e| DaemonError::Unexpected(e.to_string()))
+        })
+        .await
+    }
+
     async fn list_spend_txs(&self) -> Result<ListSpendResult, DaemonError> {
         self.command(|daemon| {
             daemon
@@ -169,7 +458,7 @@ impl Daemon for EmbeddedDaemon {
     async fn create_spend_tx(
         &self,
         coins_outpoints: &[OutPoint],
-        destinations: &HashMap<Address<address::NetworkUnchecked>, u64>,
+        destinations: &[Destination],
         feerate_vb: u64,
         change_address: Option<Address<address::NetworkUnchecked>>,
     ) -> Result<CreateSpendResult, DaemonError> {
@@ -195,6 +484,19 @@ impl Daemon for EmbeddedDaemon {
         .await
     }
 
+    async fn cpfp_bump(
+        &self,
+        outpoints: &[OutPoint],
+        feerate_vb: u64,
+    ) -> Result<CreateSpendResult, DaemonError> {
+        self.command(|daemon| {
+            daemon
+                .cpfp_bump(outpoints, feerate_vb)
+                .map_err(|e| DaemonError::Unexpected(e.to_string()))
+        })
+        .await
+    }
+
     async fn update_spend_tx(&self, psbt: &Psbt) -> Result<(), DaemonError> {
         self.command(|daemon| {
             daemon
@@ -246,6 +548,38 @@ impl Daemon for EmbeddedDaemon {
         .await
     }
 
+    async fn create_absolute_recovery(
+        &self,
+        address: Address<address::NetworkUnchecked>,
+        coins_outpoints: &[OutPoint],
+        feerate_vb: u64,
+        timelock: u32,
+    ) -> Result<Psbt, DaemonError> {
+        self.command(|daemon| {
+            daemon
+                .create_absolute_recovery(address, coins_outpoints, feerate_vb, timelock)
+                .map(|res| res.psbt)
+                .map_err(|e| DaemonError::Unexpected(e.to_string()))
+        })
+        .await
+    }
+
+    async fn create_time_recovery(
+        &self,
+        address: Address<address::NetworkUnchecked>,
+        coins_outpoints: &[OutPoint],
+        feerate_vb: u64,
+        timelock: u16,
+    ) -> Result<Psbt, DaemonError> {
+        self.command(|daemon| {
+            daemon
+                .create_time_recovery(address, coins_outpoints, feerate_vb, timelock)
+                .map(|res| res.psbt)
+                .map_err(|e| DaemonError::Unexpected(e.to_string()))
+        })
+        .await
+    }
+
     async fn get_labels(
         &self,
         items: &HashSet<LabelItem>,
@@ -269,4 +603,31 @@ impl Daemon for EmbeddedDaemon {
         self.command(|daemon| Ok(daemon.get_labels_bip329(offset, limit).labels))
             .await
     }
+
+    async fn create_proof_of_reserves(
+        &self,
+        message: &str,
+        coins_outpoints: &[OutPoint],
+    ) -> Result<Psbt, DaemonError> {
+        self.command(|daemon| {
+            daemon
+                .create_proof_of_reserves(message, coins_outpoints)
+                .map(|res| res.psbt)
+                .map_err(|e| DaemonError::Unexpected(e.to_string()))
+        })
+        .await
+    }
+
+    async fn verify_proof_of_reserves(
+        &self,
+        psbt: &Psbt,
+        message: &str,
+    ) -> Result<VerifyProofOfReservesResult, DaemonError> {
+        self.command(|daemon| {
+            daemon
+                .verify_proof_of_reserves(psbt, message)
+                .map_err(|e| DaemonError::Unexpected(e.to_string()))
+        })
+        .await
+    }
 }
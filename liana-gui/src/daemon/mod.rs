@@ -17,7 +17,7 @@ use liana::miniscript::bitcoin::{
     secp256k1, Address, Network, OutPoint, Txid,
 };
 use lianad::bip329::Labels;
-use lianad::commands::UpdateDerivIndexesResult;
+use lianad::commands::{DiscoverAddressesResult, UpdateDerivIndexesResult};
 use lianad::{
     commands::{CoinStatus, LabelItem, TransactionInfo},
     config::Config,
@@ -123,16 +123,22 @@ pub trait Daemon: Debug {
         receive: Option<u32>,
         change: Option<u32>,
     ) -> Result<UpdateDerivIndexesResult, DaemonError>;
+    async fn discover_addresses(
+        &self,
+        gap_limit: Option<u32>,
+    ) -> Result<DiscoverAddressesResult, DaemonError>;
     async fn list_coins(
         &self,
         statuses: &[CoinStatus],
         outpoints: &[OutPoint],
     ) -> Result<model::ListCoinsResult, DaemonError>;
+    async fn freeze_coins(&self, outpoints: &[OutPoint]) -> Result<(), DaemonError>;
+    async fn unfreeze_coins(&self, outpoints: &[OutPoint]) -> Result<(), DaemonError>;
     async fn list_spend_txs(&self) -> Result<model::ListSpendResult, DaemonError>;
     async fn create_spend_tx(
         &self,
         coins_outpoints: &[OutPoint],
-        destinations: &HashMap<Address<address::NetworkUnchecked>, u64>,
+        destinations: &[model::Destination],
         feerate_vb: u64,
         change_address: Option<Address<address::NetworkUnchecked>>,
     ) -> Result<model::CreateSpendResult, DaemonError>;
@@ -142,6 +148,11 @@ pub trait Daemon: Debug {
         is_cancel: bool,
         feerate_vb: Option<u64>,
     ) -> Result<model::CreateSpendResult, DaemonError>;
+    async fn cpfp_bump(
+        &self,
+        outpoints: &[OutPoint],
+        feerate_vb: u64,
+    ) -> Result<model::CreateSpendResult, DaemonError>;
     async fn update_spend_tx(&self, psbt: &Psbt) -> Result<(), DaemonError>;
     async fn delete_spend_tx(&self, txid: &Txid) -> Result<(), DaemonError>;
     async fn broadcast_spend_tx(&self, txid: &Txid) -> Result<(), DaemonError>;
@@ -159,6 +170,20 @@ pub trait Daemon: Debug {
         feerate_vb: u64,
         sequence: Option<u16>,
     ) -> Result<Psbt, DaemonError>;
+    async fn create_absolute_recovery(
+        &self,
+        address: Address<address::NetworkUnchecked>,
+        coins_outpoints: &[OutPoint],
+        feerate_vb: u64,
+        timelock: u32,
+    ) -> Result<Psbt, DaemonError>;
+    async fn create_time_recovery(
+        &self,
+        address: Address<address::NetworkUnchecked>,
+        coins_outpoints: &[OutPoint],
+        feerate_vb: u64,
+        timelock: u16,
+    ) -> Result<Psbt, DaemonError>;
     async fn list_txs(&self, txid: &[Txid]) -> Result<model::ListTransactionsResult, DaemonError>;
     async fn get_labels(
         &self,
@@ -169,6 +194,16 @@ pub trait Daemon: Debug {
         labels: &HashMap<LabelItem, Option<String>>,
     ) -> Result<(), DaemonError>;
     async fn get_labels_bip329(&self, offset: u32, limit: u32) -> Result<Labels, DaemonError>;
+    async fn create_proof_of_reserves(
+        &self,
+        message: &str,
+        coins_outpoints: &[OutPoint],
+    ) -> Result<Psbt, DaemonError>;
+    async fn verify_proof_of_reserves(
+        &self,
+        psbt: &Psbt,
+        message: &str,
+    ) -> Result<model::VerifyProofOfReservesResult, DaemonError>;
     async fn send_wallet_invitation(&self, _email: &str) -> Result<(), DaemonError> {
         Ok(())
     }
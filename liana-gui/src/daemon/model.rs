@@ -11,9 +11,10 @@ pub use liana::{
     },
 };
 pub use lianad::commands::{
-    CreateSpendResult, GetAddressResult, GetInfoResult, GetLabelsResult, LabelItem, ListCoinsEntry,
-    ListCoinsResult, ListRevealedAddressesEntry, ListRevealedAddressesResult, ListSpendEntry,
-    ListSpendResult, ListTransactionsResult, TransactionInfo,
+    CreateSpendResult, Destination, GetAddressResult, GetInfoResult, GetLabelsResult, LabelItem,
+    ListCoinsEntry, ListCoinsResult, ListRevealedAddressesEntry, ListRevealedAddressesResult,
+    ListSpendEntry, ListSpendResult, ListTransactionsResult, TransactionInfo,
+    VerifyProofOfReservesResult,
 };
 
 pub type Coin = ListCoinsEntry;
@@ -30,6 +31,14 @@ pub fn remaining_sequence(coin: &Coin, blockheight: u32, timelock: u16) -> u32 {
     }
 }
 
+/// The number of seconds before the time-based relative timelock, in 512-second intervals,
+/// expires for this coin. `None` if the time elapsed since the coin's confirmation is unknown,
+/// for instance because the coin is unconfirmed.
+pub fn remaining_time(coin: &Coin, timelock: u16) -> Option<u32> {
+    coin.time_since_confirmation
+        .map(|elapsed| (u32::from(timelock) * 512).saturating_sub(elapsed))
+}
+
 /// Whether the coin is owned by this wallet.
 /// This comprises all confirmed coins together with those
 /// unconfirmed coins from self.
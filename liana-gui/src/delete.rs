@@ -3,6 +3,7 @@ use std::collections::HashSet;
 
 use crate::{
     app::settings::{self, LianaSettings, SettingsError, WalletSettings},
+    daemon::embedded,
     dir::NetworkDirectory,
     services::connect::{
         client::{
@@ -20,6 +21,7 @@ pub enum DeleteError {
     Settings(SettingsError),
     ConnectCache(ConnectCacheError),
     Connect(String),
+    HostsWallets,
 }
 
 impl std::fmt::Display for DeleteError {
@@ -29,6 +31,10 @@ impl std::fmt::Display for DeleteError {
             Self::Settings(e) => write!(f, "{}", e),
             Self::ConnectCache(e) => write!(f, "{}", e),
             Self::Connect(e) => write!(f, "{}", e),
+            Self::HostsWallets => write!(
+                f,
+                "The database of this wallet also stores other wallets, delete them first"
+            ),
         }
     }
 }
@@ -109,6 +115,17 @@ pub async fn delete_wallet(
     let wallet_id = wallet.wallet_id();
     let lianad_directory = network_dir.lianad_data_directory(&wallet_id);
 
+    // The other wallets hosted by the daemon of this wallet are stored in its database.
+    let wallet_data_dirs: Vec<_> = LianaSettings::from_file(network_dir)
+        .map_err(DeleteError::Settings)?
+        .wallets
+        .iter()
+        .map(|settings| network_dir.lianad_data_directory(&settings.wallet_id()))
+        .collect();
+    if embedded::hosts_other_wallets(&lianad_directory, &wallet_data_dirs) {
+        return Err(DeleteError::HostsWallets);
+    }
+
     if !wallet_id.is_legacy() {
         ignore_not_found(tokio::fs::remove_dir_all(lianad_directory.path()).await)?;
     } else {
@@ -15,13 +15,16 @@ use tokio::sync::mpsc::{channel, unbounded_channel, Sender, UnboundedReceiver, U
 use async_hwi::bitbox::api::btc::Fingerprint;
 use chrono::{DateTime, Duration, Utc};
 use liana::{
-    descriptors::{bip341_nums, LianaDescriptor},
+    descriptors::{bip341_nums, ExportFormat, LianaDescriptor},
     miniscript::{
         bitcoin::{Amount, Network, Psbt, Txid},
         DescriptorPublicKey,
     },
 };
 use lianad::{
+    accounting::{
+        compute_cost_basis, AccountingEntry, AccountingKind, AccountingTx, CostBasisMethod,
+    },
     bip329::{error::ExportError, Labels},
     commands::LabelItem,
 };
@@ -42,12 +45,15 @@ use crate::{
     },
     backup::{self, Backup},
     daemon::{
-        model::{HistoryTransaction, Labelled},
+        model::{HistoryTransaction, Labelled, VerifyProofOfReservesResult},
         Daemon, DaemonBackend, DaemonError,
     },
     dir::{LianaDirectory, NetworkDirectory},
     node::bitcoind::Bitcoind,
-    services::connect::client::backend::api::DEFAULT_LIMIT,
+    services::{
+        connect::client::backend::api::DEFAULT_LIMIT,
+        fiat::{Currency, HistoricalPrices, PriceClient, PriceSource},
+    },
 };
 
 const DUMP_LABELS_LIMIT: u32 = 100;
@@ -86,6 +92,7 @@ pub enum ImportExportMessage {
     Ignore,
     UpdateAliases(HashMap<Fingerprint, settings::KeySetting>),
     Xpub(String),
+    ProofOfReserves(VerifyProofOfReservesResult),
 }
 
 impl From<ImportExportMessage> for view::Message {
@@ -118,8 +125,10 @@ pub enum Error {
     Daemon(String),
     TxTimeMissing,
     DaemonMissing,
+    FiatPrice(String),
     ParsePsbt,
     ParseDescriptor,
+    DescriptorExport(String),
     Bip329Export(String),
     BackupImport(String),
     Backup(backup::Error),
@@ -145,8 +154,10 @@ impl Display for Error {
             Error::Daemon(e) => write!(f, "ImportExport daemon error: {e}"),
             Error::TxTimeMissing => write!(f, "ImportExport: transaction block height missing"),
             Error::DaemonMissing => write!(f, "ImportExport: the daemon is missing"),
+            Error::FiatPrice(e) => write!(f, "ImportExport: failed to get the fiat price: {e}"),
             Error::ParsePsbt => write!(f, "ImportExport: fail to parse PSBT"),
             Error::ParseDescriptor => write!(f, "ImportExport: fail to parse descriptor"),
+            Error::DescriptorExport(e) => write!(f, "DescriptorExport: {e}"),
             Error::Bip329Export(e) => write!(f, "Bip329Export: {e}"),
             Error::BackupImport(e) => write!(f, "BackupImport: {e}"),
             Error::Backup(e) => write!(f, "Backup: {e}"),
@@ -168,9 +179,18 @@ impl Display for Error {
 #[derive(Debug, Clone)]
 pub enum ImportExportType {
     Transactions,
+    AccountingReport {
+        method: CostBasisMethod,
+        format: AccountingFormat,
+        /// The source and currency of the fiat prices to value the transactions with, if any.
+        fiat: Option<(PriceSource, Currency)>,
+        /// The file the historical prices are cached in.
+        prices_path: PathBuf,
+    },
     ExportPsbt(String),
     ExportXpub(String),
     ExportEncryptedDescriptor(Box<LianaDescriptor>),
+    ExportBsmsRecord(Fingerprint, String),
     ExportProcessBackup(LianaDirectory, Network, Arc<Config>, Arc<Wallet>),
     ImportBackup {
         network_dir: NetworkDirectory,
@@ -180,27 +200,42 @@ pub enum ImportExportType {
     },
     FromBackup,
     Descriptor(LianaDescriptor),
+    SignerDescriptor {
+        descriptor: Box<LianaDescriptor>,
+        format: ExportFormat,
+        name: String,
+        network: Network,
+    },
     ExportLabels,
     ImportPsbt(Option<Txid>),
     ImportXpub(Network),
     ImportDescriptor,
+    /// Create a proof of reserves of all the confirmed coins, committing to this message.
+    ExportProofOfReserves(String),
+    /// Verify a signed proof of reserves committing to this message.
+    VerifyProofOfReserves(String),
 }
 
 impl ImportExportType {
     pub fn end_message(&self) -> &str {
         match self {
             ImportExportType::Transactions
+            | ImportExportType::AccountingReport { .. }
             | ImportExportType::ExportPsbt(_)
             | ImportExportType::Descriptor(_)
+            | ImportExportType::SignerDescriptor { .. }
             | ImportExportType::ExportProcessBackup(..)
             | ImportExportType::ExportXpub(_)
             | ImportExportType::ExportEncryptedDescriptor(_)
-            | ImportExportType::ExportLabels => "Export successful!",
+            | ImportExportType::ExportBsmsRecord(..)
+            | ImportExportType::ExportLabels
+            | ImportExportType::ExportProofOfReserves(_) => "Export successful!",
             ImportExportType::ImportBackup { .. }
             | ImportExportType::ImportPsbt(_)
             | ImportExportType::ImportXpub(_)
             | ImportExportType::FromBackup
             | ImportExportType::ImportDescriptor => "Import successful",
+            ImportExportType::VerifyProofOfReserves(_) => "The proof of reserves is valid.",
         }
     }
 }
@@ -265,6 +300,7 @@ pub enum Progress {
         ),
     ),
     EncryptedFile(Vec<u8>),
+    ProofOfReserves(VerifyProofOfReservesResult),
 }
 
 pub struct Export {
@@ -301,10 +337,25 @@ impl Export {
     ) {
         if let Err(e) = match export_type {
             ImportExportType::Transactions => export_transactions(&sender, daemon, path).await,
+            ImportExportType::AccountingReport {
+                method,
+                format,
+                fiat,
+                prices_path,
+            } => {
+                export_accounting_report(&sender, daemon, path, method, format, fiat, prices_path)
+                    .await
+            }
             ImportExportType::ExportPsbt(str) => export_string(&sender, path, str).await,
             ImportExportType::Descriptor(descriptor) => {
                 export_descriptor(&sender, path, descriptor).await
             }
+            ImportExportType::SignerDescriptor {
+                descriptor,
+                format,
+                name,
+                network,
+            } => export_signer_descriptor(&sender, path, *descriptor, format, name, network).await,
             ImportExportType::ExportLabels => export_labels(&sender, daemon, path).await,
             ImportExportType::ImportPsbt(txid) => import_psbt(daemon, &sender, path, txid).await,
             ImportExportType::ImportXpub(network) => import_xpub(&sender, path, network).await,
@@ -313,6 +364,9 @@ impl Export {
                 export_encrypted_descriptor(&sender, path, *descr).await
             }
             ImportExportType::ExportXpub(xpub_str) => export_string(&sender, path, xpub_str).await,
+            ImportExportType::ExportBsmsRecord(_, record) => {
+                export_string(&sender, path, record).await
+            }
             ImportExportType::ExportProcessBackup(datadir, network, config, wallet) => {
                 app_backup_export(
                     datadir,
@@ -331,6 +385,12 @@ impl Export {
                 ..
             } => import_backup(&network_dir, wallet, &sender, path, daemon).await,
             ImportExportType::FromBackup => from_backup(&sender, path).await,
+            ImportExportType::ExportProofOfReserves(message) => {
+                export_proof_of_reserves(&sender, daemon, path, message).await
+            }
+            ImportExportType::VerifyProofOfReserves(message) => {
+                verify_proof_of_reserves(&sender, daemon, path, message).await
+            }
         } {
             if let Err(e) = sender.send(Progress::Error(e)) {
                 tracing::error!("Import/Export fail to send msg: {}", e);
@@ -432,17 +492,12 @@ pub fn export_subscription(
     })
 }
 
-pub async fn export_transactions(
+// Fetch all the confirmed transactions of the wallet, most recent first. Reports progress up to
+// 80%.
+async fn fetch_history_txs(
     sender: &UnboundedSender<Progress>,
-    daemon: Option<Arc<dyn Daemon + Sync + Send>>,
-    path: PathBuf,
-) -> Result<(), Error> {
-    let daemon = daemon.ok_or(Error::DaemonMissing)?;
-    let mut file = open_file_write(&path).await?;
-
-    let header = "Date,Label,Value,Fee,Txid,Block\n".to_string();
-    file.write_all(header.as_bytes())?;
-
+    daemon: &Arc<dyn Daemon + Sync + Send>,
+) -> Result<Vec<HistoryTransaction>, Error> {
     // look 2 hour forward
     // https://github.com/bitcoin/bitcoin/blob/62bd61de110b057cbfd6e31e4d0b727d93119c72/src/chain.h#L29
     let mut end = ((Utc::now() + Duration::hours(2)).timestamp()) as u32;
@@ -518,7 +573,21 @@ pub async fn export_transactions(
 
     let mut txs: Vec<_> = map.into_values().collect();
     txs.sort_by(|a, b| b.compare(a));
+    Ok(txs)
+}
 
+pub async fn export_transactions(
+    sender: &UnboundedSender<Progress>,
+    daemon: Option<Arc<dyn Daemon + Sync + Send>>,
+    path: PathBuf,
+) -> Result<(), Error> {
+    let daemon = daemon.ok_or(Error::DaemonMissing)?;
+    let mut file = open_file_write(&path).await?;
+
+    let header = "Date,Label,Value,Fee,Txid,Block\n".to_string();
+    file.write_all(header.as_bytes())?;
+
+    let txs = fetch_history_txs(sender, &daemon).await?;
     for mut tx in txs {
         let date_time = tx
             .time
@@ -571,6 +640,245 @@ pub async fn export_transactions(
     Ok(())
 }
 
+/// The layout of an accounting report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountingFormat {
+    /// A table of the cost basis and realized gain of each transaction.
+    Csv,
+    /// Koinly's universal CSV layout.
+    Koinly,
+    /// CoinTracker's CSV layout.
+    CoinTracker,
+}
+
+impl AccountingFormat {
+    pub const ALL: [AccountingFormat; 3] = [
+        AccountingFormat::Csv,
+        AccountingFormat::Koinly,
+        AccountingFormat::CoinTracker,
+    ];
+}
+
+impl Display for AccountingFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Csv => write!(f, "csv"),
+            Self::Koinly => write!(f, "koinly"),
+            Self::CoinTracker => write!(f, "cointracker"),
+        }
+    }
+}
+
+// Quote a CSV field if necessary.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn format_time(time: Option<u32>, format: &str) -> String {
+    time.and_then(|t| DateTime::from_timestamp(t as i64, 0))
+        .map(|d| d.format(format).to_string())
+        .unwrap_or_default()
+}
+
+fn format_fiat(value: Option<f64>) -> String {
+    value.map(|v| format!("{:.2}", v)).unwrap_or_default()
+}
+
+/// Write the accounting report for these entries, oldest first, in the given layout. The fiat
+/// values are expressed in the given currency, if any.
+pub fn accounting_report(
+    entries: &[AccountingEntry],
+    labels: &HashMap<Txid, String>,
+    format: AccountingFormat,
+    currency: Option<Currency>,
+) -> String {
+    let currency = currency.map(|c| c.to_string()).unwrap_or_default();
+    let mut report = match format {
+        AccountingFormat::Csv => format!(
+            "Date,Label,Kind,Amount,Fee,Price ({c}),Value ({c}),Cost basis ({c}),Gain ({c}),Unmatched,Txid,Block\n",
+            c = currency
+        ),
+        AccountingFormat::Koinly => "Date,Sent Amount,Sent Currency,Received Amount,Received Currency,Fee Amount,Fee Currency,Net Worth Amount,Net Worth Currency,Label,Description,TxHash\n".to_string(),
+        AccountingFormat::CoinTracker => "Date,Received Quantity,Received Currency,Sent Quantity,Sent Currency,Fee Amount,Fee Currency,Tag\n".to_string(),
+    };
+
+    for entry in entries {
+        let label = csv_field(labels.get(&entry.txid).map(|l| l.as_str()).unwrap_or(""));
+        let fee = entry
+            .fee
+            .map(|f| f.to_btc().to_string())
+            .unwrap_or_default();
+        let disposed = if entry.amount.is_negative() {
+            Amount::from_sat(entry.amount.to_sat().unsigned_abs())
+        } else {
+            Amount::ZERO
+        };
+        // What was sent to others, if the fee is accounted for separately.
+        let sent = disposed
+            .checked_sub(entry.fee.unwrap_or(Amount::ZERO))
+            .unwrap_or(disposed);
+        let line = match format {
+            AccountingFormat::Csv => format!(
+                "{},{},{},{},{},{},{},{},{},{},{},{}\n",
+                format_time(entry.time, "%Y-%m-%d %H:%M:%S"),
+                label,
+                match entry.kind {
+                    AccountingKind::Receive => "receive",
+                    AccountingKind::Send => "send",
+                    AccountingKind::SelfTransfer => "self-transfer",
+                },
+                entry.amount.to_btc(),
+                fee,
+                format_fiat(entry.price),
+                format_fiat(entry.value),
+                format_fiat(entry.cost_basis),
+                format_fiat(entry.gain),
+                entry.unmatched.to_btc(),
+                entry.txid,
+                entry
+                    .block_height
+                    .map(|h| h.to_string())
+                    .unwrap_or_default(),
+            ),
+            AccountingFormat::Koinly => {
+                let (sent, received, fee, koinly_label) = match entry.kind {
+                    AccountingKind::Receive => (
+                        String::new(),
+                        entry.amount.to_btc().to_string(),
+                        String::new(),
+                        "",
+                    ),
+                    AccountingKind::Send => (sent.to_btc().to_string(), String::new(), fee, ""),
+                    // Koinly expects fee-only transactions to be recorded as a cost.
+                    AccountingKind::SelfTransfer => (
+                        disposed.to_btc().to_string(),
+                        String::new(),
+                        String::new(),
+                        "cost",
+                    ),
+                };
+                format!(
+                    "{},{},{},{},{},{},{},{},{},{},{},{}\n",
+                    format_time(entry.time, "%Y-%m-%d %H:%M:%S UTC"),
+                    sent,
+                    if sent.is_empty() { "" } else { "BTC" },
+                    received,
+                    if received.is_empty() { "" } else { "BTC" },
+                    fee,
+                    if fee.is_empty() { "" } else { "BTC" },
+                    format_fiat(entry.value),
+                    if entry.value.is_some() {
+                        currency.as_str()
+                    } else {
+                        ""
+                    },
+                    koinly_label,
+                    label,
+                    entry.txid,
+                )
+            }
+            AccountingFormat::CoinTracker => {
+                let (received, sent, fee) = match entry.kind {
+                    AccountingKind::Receive => (
+                        entry.amount.to_btc().to_string(),
+                        String::new(),
+                        String::new(),
+                    ),
+                    AccountingKind::Send => (String::new(), sent.to_btc().to_string(), fee),
+                    AccountingKind::SelfTransfer => {
+                        (String::new(), String::new(), disposed.to_btc().to_string())
+                    }
+                };
+                format!(
+                    "{},{},{},{},{},{},{},\n",
+                    format_time(entry.time, "%m/%d/%Y %H:%M:%S"),
+                    received,
+                    if received.is_empty() { "" } else { "BTC" },
+                    sent,
+                    if sent.is_empty() { "" } else { "BTC" },
+                    fee,
+                    if fee.is_empty() { "" } else { "BTC" },
+                )
+            }
+        };
+        report.push_str(&line);
+    }
+
+    report
+}
+
+pub async fn export_accounting_report(
+    sender: &UnboundedSender<Progress>,
+    daemon: Option<Arc<dyn Daemon + Sync + Send>>,
+    path: PathBuf,
+    method: CostBasisMethod,
+    format: AccountingFormat,
+    fiat: Option<(PriceSource, Currency)>,
+    prices_path: PathBuf,
+) -> Result<(), Error> {
+    let daemon = daemon.ok_or(Error::DaemonMissing)?;
+    let mut file = open_file_write(&path).await?;
+
+    let txs = fetch_history_txs(sender, &daemon).await?;
+
+    // Value the transactions at the price of the day they were confirmed on. Keep the prices
+    // fetched so far even if we fail to get one of them.
+    let mut prices = HistoricalPrices::load(&prices_path);
+    let client =
+        fiat.map(|(source, _)| PriceClient::<reqwest::Client>::default_from_source(source));
+    let mut accounting_txs = Vec::with_capacity(txs.len());
+    let mut labels = HashMap::new();
+    for (i, tx) in txs.iter().enumerate() {
+        let price = match (&client, &fiat, tx.time) {
+            (Some(client), Some((_, currency)), Some(time)) => {
+                match prices.get_or_fetch(client, *currency, time as u64).await {
+                    Ok(price) => Some(price),
+                    Err(e) => {
+                        if let Err(e) = prices.save() {
+                            tracing::error!("Failed to save the historical prices: {}", e);
+                        }
+                        return Err(Error::FiatPrice(e.to_string()));
+                    }
+                }
+            }
+            _ => None,
+        };
+        accounting_txs.push(AccountingTx {
+            txid: tx.txid,
+            time: tx.time,
+            block_height: tx.height,
+            received: tx.incoming_amount,
+            spent: tx.coins.values().map(|coin| coin.amount).sum(),
+            fee: tx.fee_amount,
+            price,
+        });
+        if let Some(label) = tx.labels.get(&tx.txid.to_string()) {
+            labels.insert(tx.txid, label.clone());
+        }
+        send_progress!(
+            sender,
+            Progress(80.0 + (i as f32) / (txs.len() as f32) * 15.0)
+        );
+    }
+    if client.is_some() {
+        if let Err(e) = prices.save() {
+            tracing::error!("Failed to save the historical prices: {}", e);
+        }
+    }
+
+    let entries = compute_cost_basis(&accounting_txs, method);
+    let report = accounting_report(&entries, &labels, format, fiat.map(|(_, c)| c));
+    file.write_all(report.as_bytes())?;
+
+    send_progress!(sender, Progress(100.0));
+    send_progress!(sender, Ended);
+    Ok(())
+}
+
 pub async fn export_descriptor(
     sender: &UnboundedSender<Progress>,
     path: PathBuf,
@@ -586,6 +894,20 @@ pub async fn export_descriptor(
     Ok(())
 }
 
+pub async fn export_signer_descriptor(
+    sender: &UnboundedSender<Progress>,
+    path: PathBuf,
+    descriptor: LianaDescriptor,
+    format: ExportFormat,
+    name: String,
+    network: Network,
+) -> Result<(), Error> {
+    let content = descriptor
+        .export(format, &name, network)
+        .map_err(|e| Error::DescriptorExport(e.to_string()))?;
+    export_string(sender, path, content).await
+}
+
 pub async fn export_string(
     sender: &UnboundedSender<Progress>,
     path: PathBuf,
@@ -696,6 +1018,43 @@ pub async fn import_psbt(
     Ok(())
 }
 
+pub async fn export_proof_of_reserves(
+    sender: &UnboundedSender<Progress>,
+    daemon: Option<Arc<dyn Daemon + Sync + Send>>,
+    path: PathBuf,
+    message: String,
+) -> Result<(), Error> {
+    let daemon = daemon.ok_or(Error::DaemonMissing)?;
+    let psbt = daemon.create_proof_of_reserves(&message, &[]).await?;
+    send_progress!(sender, Progress(50.0));
+
+    let mut file = open_file_write(&path).await?;
+    file.write_all(psbt.to_string().as_bytes())?;
+    send_progress!(sender, Progress(100.0));
+    send_progress!(sender, Ended);
+    Ok(())
+}
+
+pub async fn verify_proof_of_reserves(
+    sender: &UnboundedSender<Progress>,
+    daemon: Option<Arc<dyn Daemon + Sync + Send>>,
+    path: PathBuf,
+    message: String,
+) -> Result<(), Error> {
+    let mut file = File::open(&path)?;
+    let daemon = daemon.ok_or(Error::DaemonMissing)?;
+
+    let mut psbt_str = String::new();
+    file.read_to_string(&mut psbt_str)?;
+    let psbt = Psbt::from_str(psbt_str.trim()).map_err(|_| Error::ParsePsbt)?;
+    send_progress!(sender, Progress(50.0));
+
+    let res = daemon.verify_proof_of_reserves(&psbt, &message).await?;
+    send_progress!(sender, ProofOfReserves(res));
+    send_progress!(sender, Progress(100.0));
+    Ok(())
+}
+
 pub async fn import_descriptor(
     sender: &UnboundedSender<Progress>,
     path: PathBuf,
@@ -1444,6 +1803,89 @@ mod tests {
         assert!(matches!(Progress::Descriptor(descr), _msg));
     }
 
+    #[test]
+    fn test_accounting_report() {
+        let txid = |n: u8| Txid::from_str(&format!("{:064x}", n)).unwrap();
+        // Receive 1BTC at 30k, then pay 0.7499BTC plus a 0.0001BTC fee at 40k.
+        let entries = compute_cost_basis(
+            &[
+                AccountingTx {
+                    txid: txid(1),
+                    time: Some(1_700_000_000),
+                    block_height: Some(800_000),
+                    received: Amount::from_sat(100_000_000),
+                    spent: Amount::ZERO,
+                    fee: None,
+                    price: Some(30_000.0),
+                },
+                AccountingTx {
+                    txid: txid(2),
+                    time: Some(1_700_086_400),
+                    block_height: Some(800_144),
+                    received: Amount::from_sat(25_000_000),
+                    spent: Amount::from_sat(100_000_000),
+                    fee: Some(Amount::from_sat(10_000)),
+                    price: Some(40_000.0),
+                },
+            ],
+            CostBasisMethod::Fifo,
+        );
+        let labels: HashMap<_, _> = [(txid(2), "Rent, October".to_string())]
+            .iter()
+            .cloned()
+            .collect();
+
+        let report = accounting_report(
+            &entries,
+            &labels,
+            AccountingFormat::Csv,
+            Some(Currency::USD),
+        );
+        assert_eq!(
+            report.lines().collect::<Vec<_>>(),
+            vec![
+                "Date,Label,Kind,Amount,Fee,Price (USD),Value (USD),Cost basis (USD),Gain (USD),Unmatched,Txid,Block".to_string(),
+                format!("2023-11-14 22:13:20,,receive,1,,30000.00,30000.00,,,0,{},800000", txid(1)),
+                format!("2023-11-15 22:13:20,\"Rent, October\",send,-0.75,0.0001,40000.00,30000.00,22500.00,7500.00,0,{},800144", txid(2)),
+            ]
+        );
+
+        let report = accounting_report(
+            &entries,
+            &labels,
+            AccountingFormat::Koinly,
+            Some(Currency::USD),
+        );
+        assert_eq!(
+            report.lines().skip(1).collect::<Vec<_>>(),
+            vec![
+                format!("2023-11-14 22:13:20 UTC,,,1,BTC,,,30000.00,USD,,,{}", txid(1)),
+                format!("2023-11-15 22:13:20 UTC,0.7499,BTC,,,0.0001,BTC,30000.00,USD,,\"Rent, October\",{}", txid(2)),
+            ]
+        );
+
+        // Without fiat valuation.
+        let entries: Vec<_> = entries
+            .into_iter()
+            .map(|e| AccountingEntry {
+                price: None,
+                value: None,
+                cost_basis: None,
+                gain: None,
+                ..e
+            })
+            .collect();
+        let report = accounting_report(&entries, &labels, AccountingFormat::CoinTracker, None);
+        assert_eq!(
+            report.lines().collect::<Vec<_>>(),
+            vec![
+                "Date,Received Quantity,Received Currency,Sent Quantity,Sent Currency,Fee Amount,Fee Currency,Tag",
+                "11/14/2023 22:13:20,1,BTC,,,,,",
+                "11/15/2023 22:13:20,,,0.7499,BTC,0.0001,BTC,",
+            ]
+        );
+    }
+
     #[test]
     fn test_parse_coldcard_xpub_json() {
         let path = env::current_dir()
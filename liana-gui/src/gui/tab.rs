@@ -473,6 +473,7 @@ pub fn create_app_with_remote_backend(
             daemon_cache: DaemonCache {
                 coins: coins.coins,
                 rescan_progress: None,
+                address_discovery: None,
                 sync_progress: 1.0, // Remote backend is always synced
                 blockheight: wallet.tip_height.unwrap_or(0),
                 // We ignore last poll fields for remote backend.
@@ -11,7 +11,7 @@ use crate::{
     signer::Signer,
 };
 use async_hwi::DeviceKind;
-use liana::{descriptors::LianaDescriptor, miniscript::bitcoin};
+use liana::{bsms, descriptors::LianaDescriptor, miniscript::bitcoin};
 use lianad::config::{BitcoinBackend, BitcoinConfig};
 
 #[derive(Debug, Clone)]
@@ -59,6 +59,8 @@ pub struct Context {
     pub descriptor_template: DescriptorTemplate,
     pub descriptor: Option<LianaDescriptor>,
     pub keys: HashMap<bitcoin::bip32::Fingerprint, KeySetting>,
+    // The tokens given to the signers whose key was imported from a BSMS key record.
+    pub bsms_tokens: HashMap<bitcoin::bip32::Fingerprint, bsms::Token>,
     pub hws: Vec<(DeviceKind, bitcoin::bip32::Fingerprint, Option<[u8; 32]>)>,
     pub liana_directory: LianaDirectory,
     pub network: bitcoin::Network,
@@ -66,6 +68,8 @@ pub struct Context {
     // In case a user entered a mnemonic,
     // we don't want to override the generated signer with it.
     pub recovered_signer: Option<Arc<Signer>>,
+    // The password to encrypt the stored mnemonic with, if the user chose to set one.
+    pub mnemonic_password: Option<String>,
     pub bitcoind_is_external: bool,
     pub internal_bitcoind_config: Option<InternalBitcoindConfig>,
     pub internal_bitcoind: Option<Bitcoind>,
@@ -88,12 +92,14 @@ impl Context {
             },
             hws: Vec::new(),
             keys: HashMap::new(),
+            bsms_tokens: HashMap::new(),
             bitcoin_backend: None,
             descriptor: None,
             liana_directory,
             network,
             hw_is_used: false,
             recovered_signer: None,
+            mnemonic_password: None,
             bitcoind_is_external: true,
             internal_bitcoind_config: None,
             internal_bitcoind: None,
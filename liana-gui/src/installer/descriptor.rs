@@ -1,7 +1,10 @@
 use async_hwi::{DeviceKind, Version};
-use liana::miniscript::{
-    bitcoin::bip32::{ChildNumber, Fingerprint},
-    descriptor::DescriptorPublicKey,
+use liana::{
+    bsms,
+    miniscript::{
+        bitcoin::bip32::{ChildNumber, Fingerprint},
+        descriptor::DescriptorPublicKey,
+    },
 };
 
 use crate::{
@@ -22,6 +25,8 @@ pub enum KeySource {
     Manual,
     /// A token for a key with the given kind.
     Token(KeyKind, ProviderKey),
+    /// An xpub imported from a BSMS key record, created by the signer for the given token.
+    Bsms(bsms::Token),
 }
 
 impl KeySource {
@@ -61,7 +66,7 @@ impl KeySource {
         match self {
             Self::Device(_, _) => KeySourceKind::Device,
             Self::HotSigner => KeySourceKind::HotSigner,
-            Self::Manual => KeySourceKind::Manual,
+            Self::Manual | Self::Bsms(_) => KeySourceKind::Manual,
             Self::Token(kind, _) => KeySourceKind::Token(*kind),
         }
     }
@@ -82,6 +87,14 @@ impl KeySource {
         }
     }
 
+    pub fn bsms_token(&self) -> Option<&bsms::Token> {
+        if let KeySource::Bsms(token) = self {
+            Some(token)
+        } else {
+            None
+        }
+    }
+
     pub fn provider_key_kind(&self) -> Option<KeyKind> {
         if let KeySource::Token(key_kind, _) = self {
             Some(*key_kind)
@@ -113,8 +126,30 @@ pub struct Key {
     pub account: Option<ChildNumber>,
 }
 
+/// A group of keys with its own threshold, counting as a single item of its path.
+pub struct KeyGroup {
+    pub keys: Vec<Option<Key>>,
+    pub threshold: usize,
+}
+
+impl KeyGroup {
+    pub fn new() -> Self {
+        Self {
+            keys: vec![None, None],
+            threshold: 2,
+        }
+    }
+}
+
+impl Default for KeyGroup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct Path {
     pub keys: Vec<Option<Key>>,
+    pub groups: Vec<KeyGroup>,
     pub threshold: usize,
     pub sequence: PathSequence,
     pub warning: Option<PathWarning>,
@@ -129,6 +164,7 @@ impl Path {
         };
         Self {
             keys: vec![None],
+            groups: Vec::new(),
             threshold: 1,
             sequence,
             warning: None,
@@ -168,8 +204,72 @@ impl Path {
         self.sequence.path_kind()
     }
 
+    /// The number of items the path threshold applies to: its keys and its key groups.
+    pub fn n_items(&self) -> usize {
+        self.keys.len() + self.groups.len()
+    }
+
+    /// All the keys of this path, followed by the keys of each of its groups.
+    ///
+    /// A key of the path is identified by its index in this iterator.
+    pub fn all_keys(&self) -> impl Iterator<Item = &Option<Key>> {
+        self.keys
+            .iter()
+            .chain(self.groups.iter().flat_map(|g| g.keys.iter()))
+    }
+
+    pub fn all_keys_mut(&mut self) -> impl Iterator<Item = &mut Option<Key>> {
+        self.keys
+            .iter_mut()
+            .chain(self.groups.iter_mut().flat_map(|g| g.keys.iter_mut()))
+    }
+
+    pub fn key(&self, j: usize) -> Option<&Option<Key>> {
+        self.all_keys().nth(j)
+    }
+
+    pub fn key_mut(&mut self, j: usize) -> Option<&mut Option<Key>> {
+        self.all_keys_mut().nth(j)
+    }
+
+    /// The index of the first key of the group `g` in [`Path::all_keys`].
+    pub fn group_offset(&self, g: usize) -> usize {
+        self.keys.len()
+            + self.groups[..g]
+                .iter()
+                .map(|group| group.keys.len())
+                .sum::<usize>()
+    }
+
+    /// Remove the key at index `j` in [`Path::all_keys`], as well as its group if it was the
+    /// last key in it. Thresholds are lowered as needed.
+    pub fn remove_key(&mut self, j: usize) {
+        if j < self.keys.len() {
+            self.keys.remove(j);
+        } else {
+            let mut k = j - self.keys.len();
+            for g in 0..self.groups.len() {
+                let group = &mut self.groups[g];
+                if k < group.keys.len() {
+                    group.keys.remove(k);
+                    if group.threshold > group.keys.len() {
+                        group.threshold -= 1;
+                    }
+                    if group.keys.is_empty() {
+                        self.groups.remove(g);
+                    }
+                    break;
+                }
+                k -= group.keys.len();
+            }
+        }
+        if self.threshold > self.n_items() {
+            self.threshold -= 1;
+        }
+    }
+
     pub fn valid(&self) -> bool {
-        !self.keys.is_empty() && !self.keys.iter().any(|k| k.is_none()) && self.warning.is_none()
+        self.n_items() > 0 && !self.all_keys().any(|k| k.is_none()) && self.warning.is_none()
     }
 }
 
@@ -27,7 +27,7 @@ use crate::{
     installer::{decrypt::Decrypt, descriptor::PathKind},
     node::{
         bitcoind::{Bitcoind, ConfigField, RpcAuthType},
-        electrum, NodeType,
+        electrum, esplora, NodeType,
     },
     services::{
         self,
@@ -64,11 +64,14 @@ pub enum Message {
     WalletRegistered(Result<(Fingerprint, Option<[u8; 32]>), Error>),
     MnemonicWord(usize, String),
     ImportMnemonic(bool),
+    MnemonicPassword(String),
+    MnemonicPasswordConfirmation(String),
     RedeemNextKey,
     KeyRedeemed(ProviderKey, Result<(), services::keys::Error>),
     AllKeysRedeemed,
     BackupDescriptor,
     ExportEncryptedDescriptor(Result<Box<LianaDescriptor>, encrypted_backup::Error>),
+    ExportBsmsRecord(Fingerprint),
     ExportXpub(String),
     ImportExport(ImportExportMessage),
     ImportBackup,
@@ -142,11 +145,17 @@ pub enum DefineElectrum {
     ValidDomainChanged(bool),
 }
 
+#[derive(Debug, Clone)]
+pub enum DefineEsplora {
+    ConfigFieldEdited(esplora::ConfigField, String),
+}
+
 #[derive(Debug, Clone)]
 pub enum DefineNode {
     NodeTypeSelected(NodeType),
     DefineBitcoind(DefineBitcoind),
     DefineElectrum(DefineElectrum),
+    DefineEsplora(DefineEsplora),
     PingResult((NodeType, Result<(), Error>)),
     Ping,
 }
@@ -194,6 +203,10 @@ pub enum DefinePath {
     SequenceEdited(u16),
     EditSequence,
     EditThreshold,
+    AddKeyGroup,
+    AddGroupKey(usize),
+    EditGroupThreshold(usize),
+    GroupThresholdEdited(usize, usize),
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -8,7 +8,10 @@ mod view;
 
 pub use context::{Context, RemoteBackend};
 use iced::{clipboard, Subscription, Task};
-use liana::miniscript::bitcoin::{self, Network};
+use liana::{
+    bsms::BsmsError,
+    miniscript::bitcoin::{self, Network},
+};
 use liana_ui::{
     component::network_banner,
     widget::{Column, Element},
@@ -586,6 +589,7 @@ pub async fn install_local_wallet(
                 wallet_id
                     .timestamp
                     .expect("Every new wallet have now a timestamp"),
+                ctx.mnemonic_password.as_deref(),
             )
             .map_err(|e| Error::Unexpected(format!("Failed to store mnemonic: {}", e)))?;
 
@@ -601,6 +605,7 @@ pub async fn install_local_wallet(
                 wallet_id
                     .timestamp
                     .expect("Every new wallet have now a timestamp"),
+                ctx.mnemonic_password.as_deref(),
             )
             .map_err(|e| Error::Unexpected(format!("Failed to store mnemonic: {}", e)))?;
 
@@ -668,6 +673,7 @@ pub async fn create_remote_wallet(
                 wallet_id
                     .timestamp
                     .expect("Every new wallet have now a timestamp"),
+                ctx.mnemonic_password.as_deref(),
             )
             .map_err(|e| Error::Unexpected(format!("Failed to store mnemonic: {}", e)))?;
 
@@ -683,6 +689,7 @@ pub async fn create_remote_wallet(
                 wallet_id
                     .timestamp
                     .expect("Every new wallet have now a timestamp"),
+                ctx.mnemonic_password.as_deref(),
             )
             .map_err(|e| Error::Unexpected(format!("Failed to store mnemonic: {}", e)))?;
 
@@ -813,6 +820,7 @@ pub async fn import_remote_wallet(
                 wallet_id
                     .timestamp
                     .expect("Every new wallet have now a timestamp"),
+                ctx.mnemonic_password.as_deref(),
             )
             .map_err(|e| Error::Unexpected(format!("Failed to store mnemonic: {}", e)))?;
 
@@ -952,6 +960,7 @@ pub enum Error {
     Settings(SettingsError),
     Bitcoind(String),
     Electrum(String),
+    Esplora(String),
     CannotCreateDatadir(String),
     CannotCreateFile(String),
     CannotWriteToFile(String),
@@ -959,6 +968,7 @@ pub enum Error {
     Unexpected(String),
     HardwareWallet(async_hwi::Error),
     Backup(encrypted_backup::Error),
+    Bsms(BsmsError),
 }
 
 impl From<jsonrpc::simple_http::Error> for Error {
@@ -985,6 +995,12 @@ impl From<DaemonError> for Error {
     }
 }
 
+impl From<BsmsError> for Error {
+    fn from(value: BsmsError) -> Self {
+        Self::Bsms(value)
+    }
+}
+
 impl From<AuthError> for Error {
     fn from(value: AuthError) -> Self {
         Self::Auth(value)
@@ -1006,6 +1022,7 @@ impl std::fmt::Display for Error {
             Self::Settings(e) => write!(f, "Settings file error: {}", e),
             Self::Bitcoind(e) => write!(f, "Failed to ping bitcoind: {}", e),
             Self::Electrum(e) => write!(f, "Failed to ping Electrum: {}", e),
+            Self::Esplora(e) => write!(f, "Failed to ping Esplora: {}", e),
             Self::CannotCreateDatadir(e) => write!(f, "Failed to create datadir: {}", e),
             Self::CannotGetAvailablePort(e) => write!(f, "Failed to get available port: {}", e),
             Self::CannotWriteToFile(e) => write!(f, "Failed to write to file: {}", e),
@@ -1013,6 +1030,7 @@ impl std::fmt::Display for Error {
             Self::Unexpected(e) => write!(f, "Unexpected: {}", e),
             Self::HardwareWallet(e) => write!(f, "Hardware Wallet: {}", e),
             Self::Backup(e) => write!(f, "Backup: {:?}", e),
+            Self::Bsms(e) => write!(f, "BSMS: {}", e),
         }
     }
 }
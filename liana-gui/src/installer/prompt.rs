@@ -7,6 +7,8 @@ Without the descriptor, your wallet may not find your coins — even if you stil
 When you click “Back Up Descriptor”, Liana creates an encrypted file that can only be decrypted using one of your wallet’s public keys. 
 Liana handles this automatically during the restore of a wallet process by asking you to connect a device or enter a key.
 This file is safer and more private than copying the descriptor manually.";
+pub const BSMS_DESCRIPTOR_RECORD_HELP: &str = "Some keys were imported from a BSMS key record. Export the descriptor record for each of these signers and import it on the device to register the wallet. The record is encrypted with the token shared with this signer. Check that the first address displayed by the signer matches the one below.";
 pub const REGISTER_DESCRIPTOR_HELP: &str = "To be used with the wallet, a signing device needs the descriptor. If the descriptor contains one or more keys imported from an external signing device, the descriptor must be registered on it. Registration confirms that the device is able to handle the policy. Registration on a device is not a substitute for backing up the descriptor.";
 pub const MNEMONIC_HELP: &str = "A hot key generated on this computer was used for creating this wallet. It needs to be backed up. \n Keep it in a safe place. Never share it with anyone.";
+pub const MNEMONIC_PASSWORD_HELP: &str = "You may set a password to encrypt the mnemonic stored on this computer. It will be asked before signing with this key. If you forget it, you will need to recover the key from its mnemonic.";
 pub const RECOVER_MNEMONIC_HELP: &str = "If you were using a hot key (a key stored on the computer) in your wallet, you will need to recover it from mnemonics to be able to sign transactions again. Otherwise you can directly go the next step.";
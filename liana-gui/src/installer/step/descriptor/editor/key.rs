@@ -12,12 +12,17 @@ use iced::{
     widget::{column, container, pick_list, row, Column, Row, Space},
     Length, Subscription, Task,
 };
-use liana::miniscript::{
-    bitcoin::{
-        bip32::{ChildNumber, DerivationPath, Fingerprint, Xpub},
-        Network,
+use liana::{
+    bsms::{self, BsmsError, KeyRecord},
+    miniscript::{
+        bitcoin::{
+            bip32::{ChildNumber, DerivationPath, Fingerprint, Xpub},
+            Network,
+        },
+        descriptor::{
+            DerivPaths, DescriptorMultiXKey, DescriptorPublicKey, DescriptorXKey, Wildcard,
+        },
     },
-    descriptor::{DerivPaths, DescriptorMultiXKey, DescriptorPublicKey, DescriptorXKey, Wildcard},
 };
 
 use liana_ui::{
@@ -104,6 +109,7 @@ enum Focus {
     GenerateHotKey,
     EnterSafetyNetToken,
     EnterCosignerToken,
+    EnterBsmsRecord,
 }
 
 #[derive(Debug, Clone)]
@@ -121,6 +127,9 @@ pub enum SelectKeySourceMessage {
     SelectEnterCosignerToken,
     PasteToken,
     Token(String),
+    SelectEnterBsmsRecord,
+    PasteBsmsRecord,
+    BsmsRecord(String),
     Previous,
     Next,
     Alias(String),
@@ -190,12 +199,15 @@ pub struct SelectKeySource {
     error: Option<String>,
     details_error: Option<String>,
     import_xpub_error: Option<String>,
+    /// The token given to the signer to create its BSMS key record.
+    bsms_token: Option<bsms::Token>,
 
     // fields
     form_alias: form::Value<String>,
     form_xpub: form::Value<String>,
     form_safety_net_token: form::Value<String>,
     form_cosigner_token: form::Value<String>,
+    form_bsms_record: form::Value<String>,
     form_account: Option<ChildNumber>,
 
     options_collapsed: bool,
@@ -226,10 +238,12 @@ impl SelectKeySource {
             error: None,
             details_error: None,
             import_xpub_error: None,
+            bsms_token: None,
             form_alias: Default::default(),
             form_xpub: Default::default(),
             form_safety_net_token: Default::default(),
             form_cosigner_token: Default::default(),
+            form_bsms_record: Default::default(),
             form_account: None,
             options_collapsed: false,
         }
@@ -757,6 +771,73 @@ impl SelectKeySource {
             })
         })
     }
+    fn on_select_enter_bsms_record(&mut self) -> Task<Message> {
+        self.focus = Focus::EnterBsmsRecord;
+        if self.bsms_token.is_none() {
+            match bsms::Token::generate() {
+                Ok(token) => self.bsms_token = Some(token),
+                Err(e) => self.error = Some(e.to_string()),
+            }
+        }
+        Task::none()
+    }
+    fn on_update_bsms_record(&mut self, record: String) -> Task<Message> {
+        self.selected_key = SelectedKey::None;
+        self.form_bsms_record.value = record.clone();
+        let token = match &self.bsms_token {
+            Some(token) => token.clone(),
+            None => return Task::none(),
+        };
+        match KeyRecord::parse(record.trim(), &token) {
+            Ok(KeyRecord { key, description }) => {
+                let fingerprint = key.origin.as_ref().expect("Checked when parsing").0;
+                self.form_bsms_record.warning =
+                    if !check_key_network(&DescriptorPublicKey::XPub(key.clone()), self.network) {
+                        Some("Wrong network")
+                    } else if self.keys.contains_key(&fingerprint) {
+                        Some("Key already used")
+                    } else {
+                        None
+                    };
+                self.form_bsms_record.valid = self.form_bsms_record.warning.is_none();
+                if self.form_bsms_record.valid {
+                    self.selected_key = SelectedKey::New(Box::new(Key {
+                        source: KeySource::Bsms(token),
+                        fingerprint,
+                        name: "".to_string(),
+                        key: DescriptorPublicKey::XPub(key),
+                        account: None,
+                    }));
+                    // Suggest the description the signer gave to its key as alias.
+                    let alias: String = description.chars().take(MAX_ALIAS_LEN).collect();
+                    self.form_alias.warning = alias_already_exists(&alias, fingerprint, &self.keys)
+                        .then_some("This alias is already used for another key");
+                    self.form_alias.valid = self.form_alias.warning.is_none();
+                    self.form_alias.value = alias;
+                    let _ = self.on_next();
+                }
+            }
+            Err(e) => {
+                self.form_bsms_record.valid = record.is_empty();
+                self.form_bsms_record.warning = (!record.is_empty()).then_some(match e {
+                    BsmsError::Mac => "Wrong token or corrupted record",
+                    BsmsError::UnexpectedToken => "Record created for another token",
+                    BsmsError::InvalidSignature => "Record not signed by its key",
+                    BsmsError::InvalidKey(_) => "Invalid key in record",
+                    _ => "Invalid key record",
+                });
+            }
+        }
+        Task::none()
+    }
+    fn on_paste_bsms_record(&mut self) -> Task<Message> {
+        clipboard::read().map(|t| {
+            Self::route(match t {
+                Some(record) => SelectKeySourceMessage::BsmsRecord(record),
+                None => SelectKeySourceMessage::None,
+            })
+        })
+    }
     fn on_update_alias(&mut self, alias: String) -> Task<Message> {
         // We do not allow editing of existing key
         if let SelectedKey::Existing(_) = self.selected_key {
@@ -838,6 +919,10 @@ impl SelectKeySource {
             self.form_xpub.value = "".to_string();
             self.form_xpub.valid = true;
             self.form_xpub.warning = None;
+
+            self.form_bsms_record.value = "".to_string();
+            self.form_bsms_record.valid = true;
+            self.form_bsms_record.warning = None;
         }
         Task::none()
     }
@@ -1057,6 +1142,10 @@ impl SelectKeySource {
 
         let load_key = safety_net_token.is_none().then_some(self.widget_load_key());
 
+        let bsms_record = safety_net_token
+            .is_none()
+            .then_some(self.widget_paste_bsms_record());
+
         let mut col = Column::new()
             .push(option_section)
             .spacing(modal::V_SPACING)
@@ -1065,6 +1154,7 @@ impl SelectKeySource {
             col = col
                 .push_maybe(load_key)
                 .push_maybe(paste_xpub)
+                .push_maybe(bsms_record)
                 .push_maybe(hot_signer)
                 .push_maybe(cosigner_token)
                 .push_maybe(safety_net_token);
@@ -1143,7 +1233,7 @@ impl SelectKeySource {
         let icon = match source {
             KeySource::Device(..) => icon::usb_drive_icon(),
             KeySource::HotSigner => icon::round_key_icon().color(color::RED),
-            KeySource::Manual => icon::round_key_icon(),
+            KeySource::Manual | KeySource::Bsms(_) => icon::round_key_icon(),
             KeySource::Token(..) => icon::hdd_icon(),
         };
         let message = if let KeySource::Token(kind, _) = source {
@@ -1199,6 +1289,23 @@ impl SelectKeySource {
             || Self::route(SelectKeySourceMessage::SelectEnterXpub),
         )
     }
+    fn widget_paste_bsms_record(&self) -> Element<Message> {
+        // The token is only generated once the user selected this option.
+        let label = match (&self.bsms_token, self.focus == Focus::EnterBsmsRecord) {
+            (Some(token), true) => format!("Paste the BSMS key record created for token {}", token),
+            _ => "Import a BSMS key record".to_string(),
+        };
+        collapsible_input_button(
+            self.focus == Focus::EnterBsmsRecord,
+            Some(icon::enter_box_icon()),
+            label,
+            "Encrypted key record".to_string(),
+            &self.form_bsms_record,
+            Some(|record| Self::route(SelectKeySourceMessage::BsmsRecord(record))),
+            Some(|| Self::route(SelectKeySourceMessage::PasteBsmsRecord)),
+            || Self::route(SelectKeySourceMessage::SelectEnterBsmsRecord),
+        )
+    }
     fn widget_paste_safety_net_token(&self) -> Element<Message> {
         collapsible_input_button(
             self.focus == Focus::EnterSafetyNetToken,
@@ -1284,6 +1391,9 @@ impl super::DescriptorEditModal for SelectKeySource {
                 }
                 SelectKeySourceMessage::PasteToken => self.on_paste_token(),
                 SelectKeySourceMessage::Token(token) => self.on_update_token(token),
+                SelectKeySourceMessage::SelectEnterBsmsRecord => self.on_select_enter_bsms_record(),
+                SelectKeySourceMessage::PasteBsmsRecord => self.on_paste_bsms_record(),
+                SelectKeySourceMessage::BsmsRecord(record) => self.on_update_bsms_record(record),
                 SelectKeySourceMessage::Next => self.on_next(),
                 SelectKeySourceMessage::Previous => self.on_previous(),
                 SelectKeySourceMessage::Alias(alias) => self.on_update_alias(alias),
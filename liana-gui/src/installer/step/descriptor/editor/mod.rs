@@ -26,7 +26,7 @@ use crate::{
     hw::HardwareWallets,
     installer::{
         context::DescriptorTemplate,
-        descriptor::{Key, Path, PathKind, PathSequence, PathWarning},
+        descriptor::{Key, KeyGroup, Path, PathKind, PathSequence, PathWarning},
         message::{self, Message},
         step::{Context, Step},
         view,
@@ -94,15 +94,14 @@ impl DefineDescriptor {
         for path in &mut self.paths {
             if duplicate_sequences.contains(&path.sequence.as_u16()) {
                 path.warning = Some(PathWarning::DuplicateSequence);
-            } else if path.keys.iter().all(|key| {
+            } else if path.all_keys().all(|key| {
                 // All keys must be Some for warning to apply.
                 key.as_ref()
                     .is_some_and(|k| k.source.provider_key_kind() == Some(KeyKind::Cosigner))
             }) {
                 path.warning = Some(PathWarning::OnlyCosignerKeys);
             } else if path
-                .keys
-                .iter()
+                .all_keys()
                 .flatten() // can ignore None
                 .any(|key| !path.kind().can_choose_key_source_kind(&key.source.kind()))
             {
@@ -118,8 +117,7 @@ impl DefineDescriptor {
             !path.valid()
                 || (self.use_taproot
                     && path
-                        .keys
-                        .iter()
+                        .all_keys()
                         .any(|k| !k.as_ref().is_some_and(|k| k.source.is_compatible_taproot())))
         }) && self.paths.len() >= 2
     }
@@ -156,7 +154,7 @@ impl DefineDescriptor {
             .map(|(fg, key)| (fg, (vec![], key)))
             .collect();
         for (i, path) in self.paths.iter().enumerate() {
-            for (j, key) in path.keys.iter().enumerate() {
+            for (j, key) in path.all_keys().enumerate() {
                 if let Some(k) = key {
                     let fg = k.fingerprint;
                     if !keys.contains_key(&fg) {
@@ -175,8 +173,7 @@ impl DefineDescriptor {
         let mut out = vec![];
         for path in paths {
             let mut fgs: Vec<_> = self.paths[path]
-                .keys
-                .iter()
+                .all_keys()
                 .filter_map(|k| k.as_ref().map(|k| k.fingerprint))
                 .collect();
             out.append(&mut fgs);
@@ -252,12 +249,11 @@ impl Step for DefineDescriptor {
                                 return Task::none();
                             }
                             for coordinate in coordinates {
-                                if !self.paths[coordinate.0]
-                                    .keys
-                                    .contains(&Some(existing_key.clone()))
-                                {
-                                    self.paths[coordinate.0].keys[coordinate.1] =
-                                        Some(existing_key.clone());
+                                let path = &mut self.paths[coordinate.0];
+                                if !path.all_keys().any(|k| k.as_ref() == Some(existing_key)) {
+                                    if let Some(k) = path.key_mut(coordinate.1) {
+                                        *k = Some(existing_key.clone());
+                                    }
                                 } else {
                                     tracing::error!(
                                         "Key {fingerprint} already in path {}",
@@ -281,8 +277,11 @@ impl Step for DefineDescriptor {
                         self.keys.insert(key.fingerprint, *key.clone());
                         hws.aliases.insert(key.fingerprint, key.name.clone());
                         for coordinate in coordinates {
-                            if !self.paths[coordinate.0].keys.contains(&Some(*key.clone())) {
-                                self.paths[coordinate.0].keys[coordinate.1] = Some(*key.clone());
+                            let path = &mut self.paths[coordinate.0];
+                            if !path.all_keys().any(|k| k.as_ref() == Some(&*key)) {
+                                if let Some(k) = path.key_mut(coordinate.1) {
+                                    *k = Some(*key.clone());
+                                }
                             } else {
                                 tracing::error!(
                                     "Key {} already in path {}",
@@ -315,7 +314,7 @@ impl Step for DefineDescriptor {
                 // source of truth should be self.keys
                 for p in &mut self.paths {
                     #[allow(clippy::manual_flatten)]
-                    for k in &mut p.keys {
+                    for k in p.all_keys_mut() {
                         if let Some(key) = k {
                             if key.fingerprint == fg {
                                 key.name = alias.clone();
@@ -360,10 +359,42 @@ impl Step for DefineDescriptor {
                         if let Some(path) = self.paths.get(i) {
                             self.modal = Some(Box::new(EditThresholdModal::new(
                                 i,
-                                (path.threshold, path.keys.len()),
+                                None,
+                                (path.threshold, path.n_items()),
                             )));
                         }
                     }
+                    message::DefinePath::EditGroupThreshold(g) => {
+                        if let Some(group) = self.paths.get(i).and_then(|p| p.groups.get(g)) {
+                            self.modal = Some(Box::new(EditThresholdModal::new(
+                                i,
+                                Some(g),
+                                (group.threshold, group.keys.len()),
+                            )));
+                        }
+                    }
+                    message::DefinePath::GroupThresholdEdited(g, t) => {
+                        self.modal = None;
+                        if let Some(group) = self.paths.get_mut(i).and_then(|p| p.groups.get_mut(g))
+                        {
+                            group.threshold = t;
+                        }
+                    }
+                    message::DefinePath::AddKeyGroup => {
+                        if let Some(path) = self.paths.get_mut(i) {
+                            path.groups.push(KeyGroup::new());
+                            path.threshold += 1;
+                            self.check_for_warning();
+                        }
+                    }
+                    message::DefinePath::AddGroupKey(g) => {
+                        if let Some(group) = self.paths.get_mut(i).and_then(|p| p.groups.get_mut(g))
+                        {
+                            group.keys.push(None);
+                            group.threshold += 1;
+                            self.check_for_warning();
+                        }
+                    }
 
                     message::DefinePath::AddKey => {
                         if let Some(path) = self.paths.get_mut(i) {
@@ -382,7 +413,7 @@ impl Step for DefineDescriptor {
                                 let coordinates = vec![(i, j)];
                                 let path_kind = self.paths[i].sequence.into();
                                 if let Some(path) = self.paths.get(i) {
-                                    if let Some(Some(key)) = path.keys.get(j) {
+                                    if let Some(Some(key)) = path.key(j) {
                                         let fg = key.fingerprint;
                                         let alias = key.name.clone();
                                         let modal = EditKeyAlias::new(
@@ -407,17 +438,14 @@ impl Step for DefineDescriptor {
                             }
                             message::DefineKey::Delete => {
                                 if let Some(path) = self.paths.get_mut(i) {
-                                    path.keys.remove(j);
-                                    if path.threshold > path.keys.len() {
-                                        path.threshold -= 1;
-                                    }
+                                    path.remove_key(j);
                                 }
                                 // Only delete non-primary paths.
                                 if i > 0 // we could alternatively check `path_kind != PathKind::Primary`
                                 && self
                                     .paths
                                     .get(i)
-                                    .map(|path| path.keys.is_empty())
+                                    .map(|path| path.n_items() == 0)
                                     .unwrap_or(false)
                                 {
                                     self.paths.remove(i);
@@ -469,11 +497,13 @@ impl Step for DefineDescriptor {
 
         ctx.bitcoin_config.network = self.network;
         ctx.keys = HashMap::new();
+        ctx.bsms_tokens = HashMap::new();
         let mut hw_is_used = false;
-        let mut spending_keys: Vec<DescriptorPublicKey> = Vec::new();
         let mut key_derivation_index = HashMap::<Fingerprint, usize>::new();
-        for spending_key in self.paths[0].keys.iter().clone() {
-            let fingerprint = spending_key
+        // Get the descriptor key for a key of a path, registering its settings along the way.
+        // A key used in more than one path is given a new derivation index each time.
+        let mut desc_key = |key: &Option<Key>| -> Option<DescriptorPublicKey> {
+            let fingerprint = key
                 .as_ref()
                 .expect("Must be present at this step")
                 .fingerprint;
@@ -494,72 +524,44 @@ impl Step for DefineDescriptor {
                     if key.source.device_kind().is_some() {
                         hw_is_used = true;
                     }
+                    if let Some(token) = key.source.bsms_token() {
+                        ctx.bsms_tokens.insert(master_fingerprint, token.clone());
+                    }
                 }
-                let derivation_index = key_derivation_index.get(&fingerprint).unwrap_or(&0);
-                spending_keys.push(DescriptorPublicKey::MultiXPub(new_multixkey_from_xpub(
-                    xpub.clone(),
-                    *derivation_index,
-                )));
+                let derivation_index = *key_derivation_index.get(&fingerprint).unwrap_or(&0);
                 key_derivation_index.insert(fingerprint, derivation_index + 1);
+                Some(DescriptorPublicKey::MultiXPub(new_multixkey_from_xpub(
+                    xpub.clone(),
+                    derivation_index,
+                )))
+            } else {
+                None
             }
-        }
-
-        let mut recovery_paths = BTreeMap::new();
-
-        for path in &self.paths[1..] {
-            let mut recovery_keys: Vec<DescriptorPublicKey> = Vec::new();
-            for recovery_key in path.keys.iter().clone() {
-                let fingerprint = recovery_key
-                    .as_ref()
-                    .expect("Must be present at this step")
-                    .fingerprint;
-                let key = self
-                    .keys
-                    .get(&fingerprint)
-                    .expect("Must be present at this step");
-                if let DescriptorPublicKey::XPub(xpub) = &key.key {
-                    if let Some((master_fingerprint, _)) = xpub.origin {
-                        ctx.keys.insert(
-                            master_fingerprint,
-                            KeySetting {
-                                master_fingerprint,
-                                name: key.name.clone(),
-                                provider_key: key.source.provider_key(),
-                            },
-                        );
-                        if key.source.device_kind().is_some() {
-                            hw_is_used = true;
-                        }
-                    }
-
-                    let derivation_index = key_derivation_index.get(&fingerprint).unwrap_or(&0);
-                    recovery_keys.push(DescriptorPublicKey::MultiXPub(new_multixkey_from_xpub(
-                        xpub.clone(),
-                        *derivation_index,
-                    )));
-                    key_derivation_index.insert(fingerprint, derivation_index + 1);
-                }
+        };
+        let mut path_info = |path: &Path| -> PathInfo {
+            let keys: Vec<_> = path.keys.iter().filter_map(&mut desc_key).collect();
+            if path.groups.is_empty() {
+                return keys_path_info(path.threshold, keys);
             }
+            // Each key group is a sub-threshold of the path.
+            let mut subs: Vec<_> = keys.into_iter().map(PathInfo::Single).collect();
+            for group in &path.groups {
+                let keys = group.keys.iter().filter_map(&mut desc_key).collect();
+                subs.push(keys_path_info(group.threshold, keys));
+            }
+            PathInfo::Thresh(path.threshold, subs)
+        };
 
-            let recovery_keys = if recovery_keys.len() == 1 {
-                PathInfo::Single(recovery_keys[0].clone())
-            } else {
-                PathInfo::Multi(path.threshold, recovery_keys)
-            };
-
-            recovery_paths.insert(path.sequence.as_u16(), recovery_keys);
-        }
+        let spending_keys = path_info(&self.paths[0]);
+        let recovery_paths: BTreeMap<_, _> = self.paths[1..]
+            .iter()
+            .map(|path| (path.sequence.as_u16(), path_info(path)))
+            .collect();
 
-        if spending_keys.is_empty() {
+        if spending_keys.keys().is_empty() {
             return false;
         }
 
-        let spending_keys = if spending_keys.len() == 1 {
-            PathInfo::Single(spending_keys[0].clone())
-        } else {
-            PathInfo::Multi(self.paths[0].threshold, spending_keys)
-        };
-
         let policy = match if self.use_taproot {
             LianaPolicy::new(spending_keys, recovery_paths)
         } else {
@@ -638,6 +640,15 @@ impl From<DefineDescriptor> for Box<dyn Step> {
     }
 }
 
+/// The spending path for the given keys and threshold.
+fn keys_path_info(threshold: usize, mut keys: Vec<DescriptorPublicKey>) -> PathInfo {
+    if keys.len() == 1 {
+        PathInfo::Single(keys.remove(0))
+    } else {
+        PathInfo::Multi(threshold, keys)
+    }
+}
+
 pub struct EditSequenceModal {
     path_index: usize,
     sequence: form::Value<String>,
@@ -705,13 +716,16 @@ impl DescriptorEditModal for EditSequenceModal {
 pub struct EditThresholdModal {
     threshold: (usize, usize),
     path_index: usize,
+    /// The key group of the path whose threshold is edited, if not the path's own threshold.
+    group_index: Option<usize>,
 }
 
 impl EditThresholdModal {
-    pub fn new(path_index: usize, threshold: (usize, usize)) -> Self {
+    pub fn new(path_index: usize, group_index: Option<usize>, threshold: (usize, usize)) -> Self {
         Self {
             threshold,
             path_index,
+            group_index,
         }
     }
 }
@@ -733,13 +747,18 @@ impl DescriptorEditModal for EditThresholdModal {
                 }
                 message::ThresholdSequenceModal::Confirm => {
                     let path_index = self.path_index;
+                    let group_index = self.group_index;
                     let threshold = self.threshold.0;
                     return Task::perform(
-                        async move { (path_index, threshold) },
-                        |(path_index, threshold)| {
+                        async move { (path_index, group_index, threshold) },
+                        |(path_index, group_index, threshold)| {
                             message::DefineDescriptor::Path(
                                 path_index,
-                                message::DefinePath::ThresholdEdited(threshold),
+                                if let Some(g) = group_index {
+                                    message::DefinePath::GroupThresholdEdited(g, threshold)
+                                } else {
+                                    message::DefinePath::ThresholdEdited(threshold)
+                                },
                             )
                         },
                     )
@@ -989,4 +1008,95 @@ mod tests {
             assert!(ctx.hw_is_used);
         });
     }
+
+    #[tokio::test]
+    async fn test_define_descriptor_key_group() {
+        let mut ctx = Context::new(
+            Network::Testnet,
+            LianaDirectory::new(PathBuf::from_str("/").unwrap()),
+            crate::installer::context::RemoteBackend::None,
+        );
+        let sandbox: Sandbox<DefineDescriptor> = Sandbox::new(DefineDescriptor::new(
+            Network::Testnet,
+            Arc::new(Mutex::new(Signer::generate(Network::Testnet).unwrap())),
+        ));
+        sandbox.load(&ctx).await;
+        let manual_key = |name: &str, xpub: &str| {
+            let key = DescriptorPublicKey::from_str(xpub).unwrap();
+            Key {
+                name: name.to_string(),
+                fingerprint: key.master_fingerprint(),
+                key,
+                source: KeySource::Manual,
+                account: None,
+            }
+        };
+        let keys = [
+            manual_key("CFO", "[08d94091/48'/1'/0'/2']tpubDEPcGiMo7Z9bwxKvqGU6Zwis2xoESJGKmbcX9Eu6puUgriny9UDCHCF1CpZyGT8s1Kj5diyT2kbe7tj1caWwVb2UYNF129rwNobcq4KTQbs"),
+            manual_key("Board 1", "[19608592/48'/1'/0'/2']tpubDEjf1AbrUjxnw8jg6Gi12CunPqnCobLP6Ktoy4Hd52pa65d6QRPg5CSkdFrqPDjJ8BAUuMEDVDRQVjtuWWksMqBeZCqyABFucN9ErQq8oVX"),
+            manual_key("Board 2", "[2522f23c/48'/1'/0'/2']tpubDEoTU4bDW1EXN1rnLXnRfue1a7DeqjJcs39PkEeLcVXhVKzCnFo9yQX2EeeXJ6kh4hgbz5o9v7YAc1EE97AEJpJbKNmDxE3ZQo4msGPSp2J"),
+            manual_key("Recovery", "[f5acc2fd/48'/1'/0'/2']tpubDFAqEGNyad35aBCKUAXbQGDjdVhNueno5ZZVEn3sQbW5ci457gLR7HyTmHBg93oourBssgUxuWz1jX5uhc1qaqFo9VsybY1J5FuedLfm4dK"),
+        ];
+
+        // Primary path is CFO and 1 of the 2 board members.
+        sandbox
+            .update(Message::DefineDescriptor(message::DefineDescriptor::Path(
+                0,
+                message::DefinePath::AddKeyGroup,
+            )))
+            .await;
+        sandbox
+            .update(Message::DefineDescriptor(message::DefineDescriptor::Path(
+                0,
+                message::DefinePath::GroupThresholdEdited(0, 1),
+            )))
+            .await;
+        for (coordinates, key) in [(0, 0), (0, 1), (0, 2), (1, 0)].iter().zip(keys.iter()) {
+            sandbox
+                .update(Message::DefineDescriptor(
+                    message::DefineDescriptor::KeysEdited(
+                        vec![*coordinates],
+                        SelectedKey::New(Box::new(key.clone())),
+                    ),
+                ))
+                .await;
+        }
+        sandbox.check(|step| {
+            let primary_path = &step.paths[0];
+            assert_eq!((primary_path.threshold, primary_path.n_items()), (2, 2));
+            assert_eq!(primary_path.groups[0].threshold, 1);
+            assert!(primary_path.valid());
+            assert!((step).apply(&mut ctx));
+            let policy = ctx.descriptor.as_ref().unwrap().policy();
+            match policy.primary_path() {
+                PathInfo::Thresh(2, subs) => {
+                    assert!(subs.iter().any(|sub| matches!(sub, PathInfo::Single(_))));
+                    assert!(subs
+                        .iter()
+                        .any(|sub| matches!(sub, PathInfo::Multi(1, keys) if keys.len() == 2)));
+                }
+                path => panic!("unexpected primary path {:?}", path),
+            }
+        });
+
+        // Removing all the keys of the group removes the group itself.
+        for _ in 0..2 {
+            sandbox
+                .update(Message::DefineDescriptor(message::DefineDescriptor::Path(
+                    0,
+                    message::DefinePath::Key(1, message::DefineKey::Delete),
+                )))
+                .await;
+        }
+        sandbox.check(|step| {
+            let primary_path = &step.paths[0];
+            assert!(primary_path.groups.is_empty());
+            assert_eq!((primary_path.threshold, primary_path.n_items()), (1, 1));
+            assert!((step).apply(&mut ctx));
+            assert!(matches!(
+                ctx.descriptor.as_ref().unwrap().policy().primary_path(),
+                PathInfo::Single(_)
+            ));
+        });
+    }
 }
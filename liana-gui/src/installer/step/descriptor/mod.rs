@@ -7,8 +7,9 @@ use std::{
 
 use iced::{Subscription, Task};
 use liana::{
+    bsms,
     descriptors::LianaDescriptor,
-    miniscript::bitcoin::{bip32::Fingerprint, Network},
+    miniscript::bitcoin::{bip32::Fingerprint, Address, Network},
 };
 
 use liana_ui::{component::form, widget::Element};
@@ -420,6 +421,32 @@ pub struct BackupDescriptor {
     modal: Option<ExportModal>,
     error: Option<Error>,
     context: Option<Context>,
+    // The encrypted descriptor record for each signer whose key was collected through BSMS.
+    bsms_records: Vec<(Fingerprint, String)>,
+    // The first address of the wallet, as verified from the BSMS descriptor records.
+    first_address: Option<Address>,
+}
+
+impl BackupDescriptor {
+    /// Create the BSMS descriptor records for the signers which shared their key with a token,
+    /// checking each of them against the descriptor before they are handed out.
+    #[allow(clippy::type_complexity)]
+    fn bsms_records(
+        descriptor: &LianaDescriptor,
+        ctx: &Context,
+    ) -> Result<(Vec<(Fingerprint, String)>, Option<Address>), Error> {
+        let mut records = Vec::with_capacity(ctx.bsms_tokens.len());
+        let mut first_address = None;
+        for (fingerprint, token) in &ctx.bsms_tokens {
+            let record = bsms::descriptor_record(descriptor, ctx.network, token);
+            let address = bsms::verify_descriptor_record(&record, token, descriptor, ctx.network)?;
+            first_address = Some(address);
+            records.push((*fingerprint, record));
+        }
+        // The iteration over an HashMap keys can have a different order at each refresh
+        records.sort_by_key(|(fg, _)| *fg);
+        Ok((records, first_address))
+    }
 }
 
 impl Step for BackupDescriptor {
@@ -476,6 +503,20 @@ impl Step for BackupDescriptor {
                     return launch;
                 }
             }
+            Message::ExportBsmsRecord(fingerprint) => {
+                if let (None, Some((_, record))) = (
+                    &self.modal,
+                    self.bsms_records.iter().find(|(fg, _)| *fg == fingerprint),
+                ) {
+                    let modal = ExportModal::new(
+                        None,
+                        ImportExportType::ExportBsmsRecord(fingerprint, record.clone()),
+                    );
+                    let launch = modal.launch(true);
+                    self.modal = Some(modal);
+                    return launch;
+                }
+            }
             Message::UserActionDone(done) => {
                 self.done = done;
             }
@@ -494,6 +535,20 @@ impl Step for BackupDescriptor {
             .values()
             .map(|k| (k.master_fingerprint, k.clone()))
             .collect();
+        self.bsms_records.clear();
+        self.first_address = None;
+        if let Some(descriptor) = &ctx.descriptor {
+            match Self::bsms_records(descriptor, ctx) {
+                Ok((records, first_address)) => {
+                    self.bsms_records = records;
+                    self.first_address = first_address;
+                }
+                Err(e) => {
+                    tracing::error!("Creating BSMS descriptor records: {}", e);
+                    self.error = Some(e);
+                }
+            }
+        }
     }
     fn view<'a>(
         &'a self,
@@ -506,6 +561,8 @@ impl Step for BackupDescriptor {
             email,
             self.descriptor.as_ref().expect("Must be a descriptor"),
             &self.keys,
+            &self.bsms_records,
+            self.first_address.as_ref(),
             self.error.as_ref(),
             self.done,
         );
@@ -4,7 +4,7 @@ use std::sync::{Arc, Mutex};
 use iced::Task;
 use liana::{bip39, signer::HotSigner};
 
-use liana_ui::widget::Element;
+use liana_ui::{component::form, widget::Element};
 
 use crate::{
     hw::HardwareWallets,
@@ -12,9 +12,44 @@ use crate::{
     signer::Signer,
 };
 
+// The optional password to encrypt the mnemonic stored on disk with, and its confirmation.
+#[derive(Default)]
+struct MnemonicPassword {
+    password: form::Value<String>,
+    confirmation: form::Value<String>,
+}
+
+impl MnemonicPassword {
+    fn update(&mut self, message: &Message) {
+        match message {
+            Message::MnemonicPassword(password) => self.password.value = password.clone(),
+            Message::MnemonicPasswordConfirmation(confirmation) => {
+                self.confirmation.value = confirmation.clone()
+            }
+            _ => return,
+        }
+        self.confirmation.valid =
+            self.confirmation.value.is_empty() || self.confirmation.value == self.password.value;
+    }
+
+    fn is_valid(&self) -> bool {
+        self.password.value == self.confirmation.value
+    }
+
+    // An empty password means the mnemonic is stored unencrypted.
+    fn apply(&self, ctx: &mut Context) {
+        ctx.mnemonic_password = if self.password.value.is_empty() {
+            None
+        } else {
+            Some(self.password.value.clone())
+        };
+    }
+}
+
 pub struct BackupMnemonic {
     words: [&'static str; 12],
     done: bool,
+    password: MnemonicPassword,
     signer: Arc<Mutex<Signer>>,
 }
 
@@ -24,6 +59,7 @@ impl BackupMnemonic {
         Self {
             done: false,
             words,
+            password: MnemonicPassword::default(),
             signer,
         }
     }
@@ -40,8 +76,16 @@ impl Step for BackupMnemonic {
         if let Message::UserActionDone(done) = message {
             self.done = done;
         }
+        self.password.update(&message);
         Task::none()
     }
+    fn apply(&mut self, ctx: &mut Context) -> bool {
+        if !self.password.is_valid() {
+            return false;
+        }
+        self.password.apply(ctx);
+        true
+    }
     fn skip(&self, ctx: &Context) -> bool {
         if let Some(descriptor) = &ctx.descriptor {
             !descriptor
@@ -57,7 +101,14 @@ impl Step for BackupMnemonic {
         progress: (usize, usize),
         email: Option<&'a str>,
     ) -> Element<'a, Message> {
-        view::backup_mnemonic(progress, email, &self.words, self.done)
+        view::backup_mnemonic(
+            progress,
+            email,
+            &self.words,
+            self.done,
+            &self.password.password,
+            &self.password.confirmation,
+        )
     }
 }
 
@@ -67,6 +118,7 @@ pub struct RecoverMnemonic {
     current: usize,
     suggestions: Vec<String>,
     error: Option<String>,
+    password: MnemonicPassword,
     skip: bool,
     recover: bool,
 }
@@ -79,6 +131,7 @@ impl Default for RecoverMnemonic {
             current: 0,
             suggestions: Vec::new(),
             error: None,
+            password: MnemonicPassword::default(),
             skip: false,
             recover: false,
         }
@@ -114,6 +167,9 @@ impl Step for RecoverMnemonic {
                 }
             }
             Message::ImportMnemonic(recover) => self.recover = recover,
+            Message::MnemonicPassword(_) | Message::MnemonicPasswordConfirmation(_) => {
+                self.password.update(&message)
+            }
             Message::Skip => {
                 self.skip = true;
                 return Task::perform(async {}, |_| Message::Next);
@@ -165,6 +221,11 @@ impl Step for RecoverMnemonic {
             }
         }
 
+        if !self.password.is_valid() {
+            return false;
+        }
+        self.password.apply(ctx);
+
         ctx.recovered_signer = Some(Arc::new(signer));
         true
     }
@@ -182,6 +243,8 @@ impl Step for RecoverMnemonic {
             &self.suggestions,
             self.recover,
             self.error.as_ref(),
+            &self.password.password,
+            &self.password.confirmation,
         )
     }
 }
@@ -0,0 +1,64 @@
+use iced::Task;
+use liana_ui::{component::form, widget::*};
+use lianad::{config::EsploraConfig, EsploraClient};
+
+use crate::{
+    installer::{
+        context::Context,
+        message::{self, Message},
+        view, Error,
+    },
+    node::esplora::ConfigField,
+};
+
+#[derive(Clone, Default)]
+pub struct DefineEsplora {
+    address: form::Value<String>,
+}
+
+impl DefineEsplora {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn can_try_ping(&self) -> bool {
+        !self.address.value.is_empty() && self.address.valid
+    }
+
+    pub fn update(&mut self, message: message::DefineNode) -> Task<Message> {
+        if let message::DefineNode::DefineEsplora(msg) = message {
+            match msg {
+                message::DefineEsplora::ConfigFieldEdited(field, value) => match field {
+                    ConfigField::Address => {
+                        self.address.value.clone_from(&value);
+                        self.address.valid = crate::node::esplora::is_esplora_address_valid(&value);
+                    }
+                },
+            };
+        };
+        Task::none()
+    }
+
+    fn config(&self) -> EsploraConfig {
+        EsploraConfig {
+            addr: self.address.value.clone(),
+        }
+    }
+
+    pub fn apply(&mut self, ctx: &mut Context) -> bool {
+        if self.can_try_ping() {
+            ctx.bitcoin_backend = Some(lianad::config::BitcoinBackend::Esplora(self.config()));
+            return true;
+        }
+        false
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        view::define_esplora(&self.address)
+    }
+
+    pub fn ping(&self) -> Result<(), Error> {
+        EsploraClient::new(&self.config()).map_err(|e| Error::Esplora(e.to_string()))?;
+        Ok(())
+    }
+}
@@ -81,16 +81,21 @@ pub fn define_descriptor_advanced_settings<'a>(use_taproot: bool) -> Element<'a,
     .into()
 }
 
-pub fn path(
+#[allow(clippy::too_many_arguments)]
+pub fn path<'a>(
     color: iced::Color,
     title: Option<String>,
     sequence: PathSequence,
     warning: Option<PathWarning>,
     threshold: usize,
-    keys: Vec<Element<message::DefinePath>>,
+    keys: Vec<Element<'a, message::DefinePath>>,
+    groups: Option<Vec<Element<'a, message::DefinePath>>>,
     fixed: bool,
-) -> Element<message::DefinePath> {
-    let keys_len = keys.len();
+) -> Element<'a, message::DefinePath> {
+    // Key groups can only be added if they are displayed.
+    let can_add_group = groups.is_some();
+    let groups = groups.unwrap_or_default();
+    let items_len = keys.len() + groups.len();
     Container::new(
         Column::new()
             .spacing(10)
@@ -100,19 +105,20 @@ pub fn path(
                 Column::new()
                     .spacing(5)
                     .align_x(Alignment::Center)
-                    .push(Column::with_children(keys).spacing(5)),
+                    .push(Column::with_children(keys).spacing(5))
+                    .push(Column::with_children(groups).spacing(5)),
             )
             .push_maybe(if fixed {
-                if keys_len == 1 {
+                if items_len == 1 {
                     None
                 } else {
-                    Some(Row::new().push(defined_threshold(color, fixed, (threshold, keys_len))))
+                    Some(Row::new().push(defined_threshold(color, fixed, (threshold, items_len))))
                 }
             } else {
                 Some(
                     Row::new()
                         .spacing(10)
-                        .push(defined_threshold(color, fixed, (threshold, keys_len)))
+                        .push(defined_threshold(color, fixed, (threshold, items_len)))
                         .push(
                             button::secondary(
                                 Some(icon::plus_icon()),
@@ -123,7 +129,11 @@ pub fn path(
                                 },
                             )
                             .on_press(message::DefinePath::AddKey),
-                        ),
+                        )
+                        .push_maybe(can_add_group.then(|| {
+                            button::secondary(Some(icon::plus_icon()), "Add key group")
+                                .on_press(message::DefinePath::AddKeyGroup)
+                        })),
                 )
             }),
     )
@@ -132,6 +142,41 @@ pub fn path(
     .into()
 }
 
+/// A group of keys nested in a path, with its own threshold.
+pub fn key_group(
+    color: iced::Color,
+    group_index: usize,
+    threshold: usize,
+    keys: Vec<Element<'_, message::DefinePath>>,
+) -> Element<'_, message::DefinePath> {
+    let keys_len = keys.len();
+    Container::new(
+        Column::new()
+            .spacing(10)
+            .push(Column::with_children(keys).spacing(5))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(
+                        defined_threshold(color, false, (threshold, keys_len)).map(move |msg| {
+                            if let message::DefinePath::EditThreshold = msg {
+                                message::DefinePath::EditGroupThreshold(group_index)
+                            } else {
+                                msg
+                            }
+                        }),
+                    )
+                    .push(
+                        button::secondary(Some(icon::plus_icon()), "Add key to group")
+                            .on_press(message::DefinePath::AddGroupKey(group_index)),
+                    ),
+            ),
+    )
+    .padding(10)
+    .style(theme::card::border)
+    .into()
+}
+
 pub fn uneditable_defined_key<'a>(
     alias: &'a str,
     color: iced::Color,
@@ -18,7 +18,9 @@ use crate::installer::{
     descriptor::Path,
     message::{self, Message},
     view::{
-        editor::{define_descriptor_advanced_settings, defined_key, path, undefined_key},
+        editor::{
+            define_descriptor_advanced_settings, defined_key, key_group, path, undefined_key,
+        },
         layout,
     },
 };
@@ -62,7 +64,7 @@ pub fn custom_template<'a>(
     num_non_primary_paths: usize,
     valid: bool,
 ) -> Element<'a, Message> {
-    let prim_keys_fixed = primary_path.keys.len() < 2; // can only delete a primary key if there are 2 or more
+    let prim_keys_fixed = primary_path.n_items() < 2; // can only delete a primary key if there are 2 or more
     layout(
         progress,
         None,
@@ -128,6 +130,12 @@ pub fn custom_template<'a>(
                             .map(move |msg| message::DefinePath::Key(i, msg))
                         })
                         .collect(),
+                    Some(key_groups(
+                        color::GREEN,
+                        "Primary key",
+                        use_taproot,
+                        primary_path,
+                    )),
                     false,
                 )
                 .map(|msg| Message::DefineDescriptor(message::DefineDescriptor::Path(0, msg))),
@@ -149,7 +157,7 @@ pub fn custom_template<'a>(
                                     // We cannot delete a key if doing so would remove all recovery paths,
                                     // i.e. if there is only 1 recovery path and it contains only 1 key,
                                     // and there is no safety net path.
-                                    let fixed = num_non_primary_paths < 2 && p.keys.len() < 2;
+                                    let fixed = num_non_primary_paths < 2 && p.n_items() < 2;
                                     if let Some(key) = recovery_key {
                                         defined_key(
                                             &key.name,
@@ -173,6 +181,7 @@ pub fn custom_template<'a>(
                                     .map(move |msg| message::DefinePath::Key(j, msg))
                                 })
                                 .collect(),
+                            Some(key_groups(color::ORANGE, "Recovery key", use_taproot, p)),
                             false,
                         )
                         .map(move |msg| {
@@ -246,6 +255,7 @@ pub fn custom_template<'a>(
                             .map(move |msg| message::DefinePath::Key(i, msg))
                         })
                         .collect(),
+                    None,
                     false,
                 )
                 .map(move |msg| {
@@ -274,3 +284,53 @@ pub fn custom_template<'a>(
         Some(Message::Previous),
     )
 }
+
+/// The key groups of a path. Their keys are indexed following the keys of the path itself.
+fn key_groups<'a>(
+    color: iced::Color,
+    title: &'static str,
+    use_taproot: bool,
+    p: &'a Path,
+) -> Vec<Element<'a, message::DefinePath>> {
+    p.groups
+        .iter()
+        .enumerate()
+        .map(|(g, group)| {
+            let offset = p.group_offset(g);
+            key_group(
+                color,
+                g,
+                group.threshold,
+                group
+                    .keys
+                    .iter()
+                    .enumerate()
+                    .map(|(k, group_key)| {
+                        let j = offset + k;
+                        if let Some(key) = group_key {
+                            defined_key(
+                                &key.name,
+                                color,
+                                title,
+                                if use_taproot && !key.source.is_compatible_taproot() {
+                                    Some("This device does not support Taproot")
+                                } else {
+                                    None
+                                },
+                                false,
+                            )
+                        } else {
+                            undefined_key(
+                                color,
+                                title,
+                                !p.all_keys().take(j).any(|k| k.is_none()),
+                                false,
+                            )
+                        }
+                        .map(move |msg| message::DefinePath::Key(j, msg))
+                    })
+                    .collect(),
+            )
+        })
+        .collect()
+}
@@ -129,6 +129,7 @@ pub fn inheritance_template<'a>(
                         undefined_key(color::GREEN, "Primary key", true, true)
                     }
                     .map(|msg| message::DefinePath::Key(0, msg))],
+                    None,
                     true,
                 )
                 .map(|msg| Message::DefineDescriptor(message::DefineDescriptor::Path(0, msg))),
@@ -156,6 +157,7 @@ pub fn inheritance_template<'a>(
                         undefined_key(color::WHITE, "Inheritance key", primary_key.is_some(), true)
                     }
                     .map(|msg| message::DefinePath::Key(0, msg))],
+                    None,
                     true,
                 )
                 .map(|msg| Message::DefineDescriptor(message::DefineDescriptor::Path(1, msg))),
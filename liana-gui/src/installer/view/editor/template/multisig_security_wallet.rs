@@ -146,6 +146,7 @@ pub fn multisig_security_template<'a>(
                             .map(move |msg| message::DefinePath::Key(i, msg))
                         })
                         .collect(),
+                    None,
                     true,
                 )
                 .map(move |msg| {
@@ -212,6 +213,7 @@ pub fn multisig_security_template<'a>(
                             .map(move |msg| message::DefinePath::Key(j, msg))
                         })
                         .collect(),
+                    None,
                     true,
                 )
                 .map(move |msg| {
@@ -46,7 +46,7 @@ use crate::{
     },
     node::{
         bitcoind::{ConfigField, RpcAuthType, RpcAuthValues, StartInternalBitcoindError},
-        electrum, NodeType,
+        electrum, esplora, NodeType,
     },
 };
 
@@ -729,11 +729,14 @@ pub fn register_descriptor<'a>(
     )
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn backup_descriptor<'a>(
     progress: (usize, usize),
     email: Option<&'a str>,
     descriptor: &'a LianaDescriptor,
     keys: &'a HashMap<Fingerprint, settings::KeySetting>,
+    bsms_records: &'a [(Fingerprint, String)],
+    first_address: Option<&'a bitcoin::Address>,
     error: Option<&Error>,
     done: bool,
 ) -> Element<'a, Message> {
@@ -809,6 +812,11 @@ pub fn backup_descriptor<'a>(
                 )
                 .max_width(1500),
             )
+            .push_maybe((!bsms_records.is_empty()).then(|| {
+                card::simple(bsms_descriptor_records(keys, bsms_records, first_address))
+                    .width(Length::Fill)
+                    .max_width(1500)
+            }))
             .push(
                 card::simple(display_policy(descriptor.policy(), keys))
                     .width(Length::Fill)
@@ -831,6 +839,48 @@ pub fn backup_descriptor<'a>(
     )
 }
 
+fn bsms_descriptor_records<'a>(
+    keys: &'a HashMap<Fingerprint, settings::KeySetting>,
+    bsms_records: &'a [(Fingerprint, String)],
+    first_address: Option<&'a bitcoin::Address>,
+) -> Element<'a, Message> {
+    bsms_records
+        .iter()
+        .fold(
+            Column::new()
+                .spacing(10)
+                .push(text("BSMS descriptor records:").small().bold())
+                .push(text(prompt::BSMS_DESCRIPTOR_RECORD_HELP).small())
+                .push_maybe(first_address.map(|address| {
+                    Row::new()
+                        .spacing(5)
+                        .push(text("First address:").small().bold())
+                        .push(text(address.to_string()).small())
+                })),
+            |col, (fingerprint, _)| {
+                col.push(
+                    Row::new()
+                        .align_y(Alignment::Center)
+                        .spacing(10)
+                        .push(
+                            text(
+                                keys.get(fingerprint)
+                                    .map(|k| format!("{} #{}", k.name, fingerprint))
+                                    .unwrap_or_else(|| format!("#{}", fingerprint)),
+                            )
+                            .small(),
+                        )
+                        .push(Space::with_width(Length::Fill))
+                        .push(
+                            button::secondary(Some(icon::backup_icon()), "Export record")
+                                .on_press(Message::ExportBsmsRecord(*fingerprint)),
+                        ),
+                )
+            },
+        )
+        .into()
+}
+
 fn display_policy(
     policy: LianaPolicy,
     keys: &HashMap<Fingerprint, settings::KeySetting>,
@@ -1023,6 +1073,7 @@ pub fn define_bitcoin_node<'a>(
                         match node_type {
                             NodeType::Bitcoind => "Bitcoin Core",
                             NodeType::Electrum => "Electrum",
+                            NodeType::Esplora => "Esplora",
                         },
                         node_type,
                         Some(selected_node_type),
@@ -1241,6 +1292,25 @@ pub fn define_electrum<'a>(
     Column::new().push(col_address).spacing(50).into()
 }
 
+pub fn define_esplora<'a>(address: &form::Value<String>) -> Element<'a, Message> {
+    let col_address = Column::new()
+        .push(text("Address:").bold())
+        .push(
+            form::Form::new_trimmed("https://mempool.space/api", address, |msg| {
+                Message::DefineNode(DefineNode::DefineEsplora(
+                    message::DefineEsplora::ConfigFieldEdited(esplora::ConfigField::Address, msg),
+                ))
+            })
+            .warning("Please enter a correct URL, starting with http:// or https://")
+            .size(text::P1_SIZE)
+            .padding(10),
+        )
+        .push(text(esplora::ADDRESS_NOTES))
+        .spacing(10);
+
+    Column::new().push(col_address).spacing(50).into()
+}
+
 pub fn select_bitcoind_type<'a>(progress: (usize, usize)) -> Element<'a, Message> {
     layout(
         progress,
@@ -1745,11 +1815,39 @@ pub fn hw_list_view<'a>(
     bttn.into()
 }
 
+fn mnemonic_password<'a>(
+    password: &'a form::Value<String>,
+    confirmation: &'a form::Value<String>,
+) -> Element<'a, Message> {
+    Column::new()
+        .spacing(10)
+        .push(text(prompt::MNEMONIC_PASSWORD_HELP))
+        .push(
+            form::Form::new("Password (optional)", password, Message::MnemonicPassword)
+                .secure(true)
+                .padding(10),
+        )
+        .push(
+            form::Form::new(
+                "Confirm password",
+                confirmation,
+                Message::MnemonicPasswordConfirmation,
+            )
+            .secure(true)
+            .warning("Passwords do not match")
+            .padding(10),
+        )
+        .width(Length::Fixed(500.0))
+        .into()
+}
+
 pub fn backup_mnemonic<'a>(
     progress: (usize, usize),
     email: Option<&'a str>,
     words: &'a [&'static str; 12],
     done: bool,
+    password: &'a form::Value<String>,
+    password_confirmation: &'a form::Value<String>,
 ) -> Element<'a, Message> {
     layout(
         progress,
@@ -1774,7 +1872,8 @@ pub fn backup_mnemonic<'a>(
                     }),
             )
             .push(checkbox("I have backed up my mnemonic", done).on_toggle(Message::UserActionDone))
-            .push(if done {
+            .push(mnemonic_password(password, password_confirmation))
+            .push(if done && password.value == password_confirmation.value {
                 button::secondary(None, "Next")
                     .on_press(Message::Next)
                     .width(Length::Fixed(200.0))
@@ -1788,6 +1887,7 @@ pub fn backup_mnemonic<'a>(
     )
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn recover_mnemonic<'a>(
     progress: (usize, usize),
     email: Option<&'a str>,
@@ -1796,6 +1896,8 @@ pub fn recover_mnemonic<'a>(
     suggestions: &'a [String],
     recover: bool,
     error: Option<&'a String>,
+    password: &'a form::Value<String>,
+    password_confirmation: &'a form::Value<String>,
 ) -> Element<'a, Message> {
     layout(
         progress,
@@ -1854,6 +1956,8 @@ pub fn recover_mnemonic<'a>(
                             },
                         ))
                         .push(Space::with_height(Length::Fixed(50.0)))
+                        .push(mnemonic_password(password, password_confirmation))
+                        .push(Space::with_height(Length::Fixed(50.0)))
                         .push_maybe(
                             error.map(|e| card::invalid(text(e).style(theme::text::error))),
                         ),
@@ -1883,7 +1987,10 @@ pub fn recover_mnemonic<'a>(
                             .width(Length::Fixed(200.0)),
                     )
                     .push(
-                        if words.iter().any(|(_, valid)| !valid) || error.is_some() {
+                        if words.iter().any(|(_, valid)| !valid)
+                            || error.is_some()
+                            || password.value != password_confirmation.value
+                        {
                             button::secondary(None, "Next").width(Length::Fixed(200.0))
                         } else {
                             button::secondary(None, "Next")
@@ -14,6 +14,7 @@ pub mod launcher;
 pub mod loader;
 pub mod logger;
 pub mod node;
+pub mod qr;
 pub mod services;
 pub mod signer;
 pub mod utils;
@@ -0,0 +1,46 @@
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ConfigField {
+    Address,
+}
+
+pub const ADDRESS_NOTES: &str = "Note: this is the base URL of the Esplora REST API, \
+    for instance \"https://mempool.space/api\".";
+
+impl fmt::Display for ConfigField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigField::Address => write!(f, "API URL"),
+        }
+    }
+}
+
+pub fn is_esplora_address_valid(value: &str) -> bool {
+    let value_noprefix = if let Some(v) = value.strip_prefix("https://") {
+        v
+    } else if let Some(v) = value.strip_prefix("http://") {
+        v
+    } else {
+        return false;
+    };
+    let host = value_noprefix.split('/').next().unwrap_or_default();
+    !host.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn esplora_address_validity() {
+        assert!(is_esplora_address_valid("https://mempool.space/api"));
+        assert!(is_esplora_address_valid("http://127.0.0.1:3002"));
+        assert!(is_esplora_address_valid("http://127.0.0.1:3002/"));
+        assert!(!is_esplora_address_valid("127.0.0.1:3002"));
+        assert!(!is_esplora_address_valid("https://"));
+        assert!(!is_esplora_address_valid(
+            "ssl://electrum.blockstream.info:60002"
+        ));
+    }
+}
@@ -2,18 +2,24 @@ use lianad::config::BitcoinBackend;
 
 pub mod bitcoind;
 pub mod electrum;
+pub mod esplora;
 
 #[derive(Debug, Clone, PartialEq, Eq, Copy)]
 pub enum NodeType {
     Bitcoind,
     Electrum,
+    Esplora,
 }
 
-impl From<&BitcoinBackend> for NodeType {
-    fn from(bitcoin_backend: &BitcoinBackend) -> Self {
+impl NodeType {
+    /// The type of node for this Bitcoin backend, if it is one the GUI can manage. Syncing using
+    /// compact block filters can only be configured by editing the daemon's configuration file.
+    pub fn from_backend(bitcoin_backend: &BitcoinBackend) -> Option<Self> {
         match bitcoin_backend {
-            BitcoinBackend::Bitcoind(_) => Self::Bitcoind,
-            BitcoinBackend::Electrum(_) => Self::Electrum,
+            BitcoinBackend::Bitcoind(_) => Some(Self::Bitcoind),
+            BitcoinBackend::Electrum(_) => Some(Self::Electrum),
+            BitcoinBackend::Esplora(_) => Some(Self::Esplora),
+            BitcoinBackend::Cbf(_) => None,
         }
     }
 }
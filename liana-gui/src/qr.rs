@@ -0,0 +1,300 @@
+//! Transfer of PSBTs to and from air-gapped signing devices using QR codes.
+//!
+//! PSBTs are encoded as a BC-UR of type `crypto-psbt` (BCR-2020-006), the CBOR byte string of the
+//! serialized PSBT. As they usually do not fit in a single QR code, they are split by a fountain
+//! encoder in parts to be displayed one after the other as an animated QR code. The decoder
+//! reassembles them in whatever order they are scanned.
+
+use std::{collections::HashSet, convert::TryFrom, fmt::Display, path::Path};
+
+use liana::miniscript::bitcoin::psbt::Psbt;
+
+/// The type of the BC-UR of a PSBT.
+pub const PSBT_UR_TYPE: &str = "crypto-psbt";
+
+/// The maximum length in bytes of the fragment of the PSBT encoded in each part. This keeps the
+/// QR codes small enough to be scanned by the cameras of signing devices.
+const MAX_FRAGMENT_LEN: usize = 200;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The string is not a valid BC-UR.
+    Ur(String),
+    /// The BC-UR is not of type `crypto-psbt`.
+    UnexpectedType,
+    /// The BC-UR payload is not a CBOR byte string.
+    Cbor,
+    Psbt(String),
+    Image(String),
+    NoQrCode,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ur(e) => write!(f, "Invalid UR: {}", e),
+            Self::UnexpectedType => write!(f, "The UR is not of type '{}'", PSBT_UR_TYPE),
+            Self::Cbor => write!(f, "Invalid UR payload: not a CBOR byte string"),
+            Self::Psbt(e) => write!(f, "Invalid PSBT: {}", e),
+            Self::Image(e) => write!(f, "Failed to read image: {}", e),
+            Self::NoQrCode => write!(f, "No QR code found in image"),
+        }
+    }
+}
+
+/// Encode bytes as a CBOR byte string (major type 2).
+fn cbor_bytes(data: &[u8]) -> Vec<u8> {
+    let len = data.len();
+    let mut cbor = Vec::with_capacity(len + 9);
+    if len < 24 {
+        cbor.push(0x40 | len as u8);
+    } else if len <= u8::MAX as usize {
+        cbor.push(0x58);
+        cbor.push(len as u8);
+    } else if len <= u16::MAX as usize {
+        cbor.push(0x59);
+        cbor.extend_from_slice(&(len as u16).to_be_bytes());
+    } else if len <= u32::MAX as usize {
+        cbor.push(0x5a);
+        cbor.extend_from_slice(&(len as u32).to_be_bytes());
+    } else {
+        cbor.push(0x5b);
+        cbor.extend_from_slice(&(len as u64).to_be_bytes());
+    }
+    cbor.extend_from_slice(data);
+    cbor
+}
+
+/// Get the content of a CBOR byte string (major type 2) of definite length.
+fn cbor_bytes_content(cbor: &[u8]) -> Result<&[u8], Error> {
+    let (header, rest) = cbor.split_first().ok_or(Error::Cbor)?;
+    if header >> 5 != 2 {
+        return Err(Error::Cbor);
+    }
+    let len_size = match header & 0x1f {
+        info @ 0..=23 => return rest.get(..info as usize).ok_or(Error::Cbor),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        _ => return Err(Error::Cbor),
+    };
+    let len_bytes = rest.get(..len_size).ok_or(Error::Cbor)?;
+    let len = len_bytes
+        .iter()
+        .fold(0u64, |len, byte| (len << 8) | *byte as u64);
+    let start = len_size;
+    let end = usize::try_from(len)
+        .ok()
+        .and_then(|len| start.checked_add(len))
+        .ok_or(Error::Cbor)?;
+    rest.get(start..end).ok_or(Error::Cbor)
+}
+
+/// Split a PSBT in the parts of an animated QR code.
+pub struct PsbtQrEncoder {
+    encoder: ur::Encoder<'static>,
+    part: String,
+}
+
+impl PsbtQrEncoder {
+    pub fn new(psbt: &Psbt) -> Result<Self, Error> {
+        let cbor = cbor_bytes(&psbt.serialize());
+        let mut encoder = ur::Encoder::new(&cbor, MAX_FRAGMENT_LEN, PSBT_UR_TYPE)
+            .map_err(|e| Error::Ur(e.to_string()))?;
+        let part = encoder.next_part().map_err(|e| Error::Ur(e.to_string()))?;
+        Ok(Self { encoder, part })
+    }
+
+    /// The current part, upper-cased so it can be encoded in the denser alphanumeric mode of QR
+    /// codes.
+    pub fn part(&self) -> String {
+        self.part.to_uppercase()
+    }
+
+    /// Move to the next part. After all the fragments of the PSBT were displayed once, the
+    /// fountain encoder keeps on generating parts mixing several of them, so any missed part
+    /// does not require waiting for a whole new cycle.
+    pub fn next_part(&mut self) -> Result<(), Error> {
+        self.part = self
+            .encoder
+            .next_part()
+            .map_err(|e| Error::Ur(e.to_string()))?;
+        Ok(())
+    }
+
+    /// The number of fragments the PSBT was split into.
+    pub fn fragment_count(&self) -> usize {
+        self.encoder.fragment_count()
+    }
+
+    /// The sequence number of the current part, starting at 1.
+    pub fn current_index(&self) -> usize {
+        self.encoder.current_index()
+    }
+}
+
+/// Reassemble a PSBT from the scanned parts of an animated QR code, or from a single-part UR.
+#[derive(Default)]
+pub struct PsbtQrDecoder {
+    decoder: ur::Decoder,
+    /// The parts received so far.
+    parts: HashSet<String>,
+    /// The number of fragments the PSBT was split into, as announced by the parts.
+    fragment_count: Option<usize>,
+    /// The payload of a single-part UR.
+    single_part: Option<Vec<u8>>,
+}
+
+impl PsbtQrDecoder {
+    /// Receive a scanned or pasted UR, either a single-part one or a part of a multi-part one.
+    /// Parts received more than once are ignored.
+    pub fn receive(&mut self, part: &str) -> Result<(), Error> {
+        let part = part.trim().to_lowercase();
+        let ur_type = part
+            .strip_prefix("ur:")
+            .and_then(|p| p.split('/').next())
+            .ok_or_else(|| Error::Ur("missing 'ur:' scheme".to_string()))?;
+        if ur_type != PSBT_UR_TYPE {
+            return Err(Error::UnexpectedType);
+        }
+        if self.parts.contains(&part) {
+            return Ok(());
+        }
+        match ur::decode(&part).map_err(|e| Error::Ur(e.to_string()))? {
+            (ur::ur::Kind::SinglePart, payload) => {
+                self.single_part = Some(payload);
+            }
+            (ur::ur::Kind::MultiPart, _) => {
+                self.decoder
+                    .receive(&part)
+                    .map_err(|e| Error::Ur(e.to_string()))?;
+                // The sequence of a part is "<index>-<count>".
+                self.fragment_count = part
+                    .split('/')
+                    .nth(1)
+                    .and_then(|seq| seq.split('-').nth(1))
+                    .and_then(|count| count.parse().ok());
+            }
+        }
+        self.parts.insert(part);
+        Ok(())
+    }
+
+    /// The number of distinct parts received and the number of fragments of the PSBT, if known.
+    /// More parts than fragments may be needed to reassemble the PSBT.
+    pub fn progress(&self) -> (usize, Option<usize>) {
+        (self.parts.len(), self.fragment_count)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.single_part.is_some() || self.decoder.complete()
+    }
+
+    /// The reassembled PSBT, if all the parts were received.
+    pub fn psbt(&self) -> Result<Option<Psbt>, Error> {
+        let cbor = if let Some(payload) = &self.single_part {
+            payload.clone()
+        } else if let Some(message) = self
+            .decoder
+            .message()
+            .map_err(|e| Error::Ur(e.to_string()))?
+        {
+            message
+        } else {
+            return Ok(None);
+        };
+        Psbt::deserialize(cbor_bytes_content(&cbor)?)
+            .map(Some)
+            .map_err(|e| Error::Psbt(e.to_string()))
+    }
+}
+
+/// Scan the QR codes found in the image at the given path and return their content.
+pub fn scan_image(path: &Path) -> Result<Vec<String>, Error> {
+    let image = image::open(path)
+        .map_err(|e| Error::Image(e.to_string()))?
+        .to_luma8();
+    let mut image = rqrr::PreparedImage::prepare(image);
+    let contents: Vec<String> = image
+        .detect_grids()
+        .into_iter()
+        .filter_map(|grid| grid.decode().ok().map(|(_, content)| content))
+        .collect();
+    if contents.is_empty() {
+        Err(Error::NoQrCode)
+    } else {
+        Ok(contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const PSBT: &str = "cHNidP8BAIkCAAAAAc0x/jtWvFugrl8zc34KVIlWCugXT6JNtgir6UqX+Vv6AQAAAAD9////AkBCDwAAAAAAIgAgtQu/fA/8rQhJ0I6wUoBDO0vNa3lgsEpEIj7rTOMnBcXuIEkBAAAAACIAIOdCiXh7yL2V/f6S6KMTOzgqKkqyIXgmFuwDnmXbIiosAAAAAAABAP04AQIAAAAAAQKYYriMs/PtSqm6LPNWWFYskTL6nWZegJdwxYcVCRn8vwEAAAAA/f///87D7dkdgMd1Laj/v6xspNRtrQXGP+8BPFMLqkeBb6MRAQAAAAD9////AuGQDgAAAAAAIlEg7DgdNxI7WybaPUZXcMCh+uN1E4X8E5DzJIlj83S+tIMQZFgBAAAAACIAIJZAn7j5iOen7xo2sKzjMc24llTZIuS+RpdwcLHtE6ufAUCksqYUJBbHB9x8eHdoRvRqiGzG4wQXpmY96vh14zAJEM2CS/oZaNVC4Wj8rY2cdjAvZj9dlVZFPbOxx9g5tFxUAUA24s2KJ7sjSHUAcUSd4yqRK/G3CZM8qhkhyHhGDSS0zZvZaIcgoqOPe23gH32wAI9Aax1gJUDv4kKOqOx64ltg9BADAAEBKxBkWAEAAAAAIgAglkCfuPmI56fvGjawrOMxzbiWVNki5L5Gl3Bwse0Tq58BBYZSIQIeYxzruE4/cvi6zbRmB1asJO0bMfUutoH0bpubw1zAZSEDLZSmORZKW/k5A+4QxJR2/H+vcV8U0WPX9SvS+MRMffNSrnNkdqkUmNf1mL657o/oxxnHkIrtdNkbge+IrGt2qRSIigBO15eaB9dj93ihNpAX9HHDuoisbJNRiAP//wCyaCIGAh5jHOu4Tj9y+LrNtGYHVqwk7Rsx9S62gfRum5vDXMBlHPcUwigwAACAAQAAgAAAAIACAACAAAAAAAAAAAAiBgIr7HqsyKEvERWQsmsv6FleMuXThpI77+TVkQ3TSOOLURz3FMIoMAAAgAEAAIAAAACAAgAAgAIAAAAAAAAAIgYDLZSmORZKW/k5A+4QxJR2/H+vcV8U0WPX9SvS+MRMffMcJSLyPDAAAIABAACAAAAAgAIAAIAAAAAAAAAAACIGA/h0pUXGHq1+kSuTYVTO8RHKfQLJlhfNtm+qdcIIr09jHCUi8jwwAACAAQAAgAAAAIACAACAAgAAAAAAAAAAIgICGAO/4xFiX/S5DXTV6uARFTcMwP1hto8BtPkdn3gIjf0c9xTCKDAAAIABAACAAAAAgAIAAIACAAAAAgAAACICAuNOSbsNRv31XkF2ygwCOuCnsJNRLhV0isJ/VRdj1k7IHPcUwigwAACAAQAAgAAAAIACAACAAAAAAAIAAAAiAgOpBJHEchNOeXuQwuLHlwOfkAyfoGvrYfb4pCFLKEPw2hwlIvI8MAAAgAEAAIAAAACAAgAAgAIAAAACAAAAIgIDyLkJiZTjLCysDOQotYs9us5CEYev4kyTYW2uL2r5H1McJSLyPDAAAIABAACAAAAAgAIAAIAAAAAAAgAAAAAiAgIlvGBvHRPmmVP6sn9g/akW2VJAvbJagMnZ/24gLdITsxz3FMIoMAAAgAEAAIAAAACAAgAAgAMAAAADAAAAIgIDNmVQOMMezQgABjk1zjfc3I2eKFJ4xLqT55jG4BP4p0Ec9xTCKDAAAIABAACAAAAAgAIAAIABAAAAAwAAACICA4Subm7T6yYCMWLgDtMy92hOgjanJefukbCOSVEHlX0IHCUi8jwwAACAAQAAgAAAAIACAACAAQAAAAMAAAAiAgPpsETw12nxLEM6OSOPfxp4YYj8NtRcLdqBpi3S4/BTuRwlIvI8MAAAgAEAAIAAAACAAgAAgAMAAAADAAAAAA==";
+
+    #[test]
+    fn cbor_byte_strings() {
+        for len in [0, 23, 24, 255, 256, 65535, 65536] {
+            let data = vec![0xab; len];
+            let cbor = cbor_bytes(&data);
+            assert_eq!(cbor_bytes_content(&cbor).unwrap(), &data[..]);
+            // Truncated payloads are rejected.
+            if len > 0 {
+                assert_eq!(
+                    cbor_bytes_content(&cbor[..cbor.len() - 1]),
+                    Err(Error::Cbor)
+                );
+            }
+        }
+        assert_eq!(cbor_bytes(&[0x01, 0x02]), vec![0x42, 0x01, 0x02]);
+        assert_eq!(cbor_bytes(&[0x00; 24])[..2], [0x58, 24]);
+        assert_eq!(cbor_bytes(&[0x00; 256])[..3], [0x59, 0x01, 0x00]);
+        // Not a byte string.
+        assert_eq!(cbor_bytes_content(&[0x62, 0x61, 0x62]), Err(Error::Cbor));
+        assert_eq!(cbor_bytes_content(&[]), Err(Error::Cbor));
+    }
+
+    #[test]
+    fn psbt_qr_roundtrip() {
+        let psbt = Psbt::from_str(PSBT).unwrap();
+        let mut encoder = PsbtQrEncoder::new(&psbt).unwrap();
+        assert!(encoder.fragment_count() > 1);
+        assert!(encoder.part().starts_with("UR:CRYPTO-PSBT/"));
+
+        // Skip some of the parts, as if they were missed by the camera. The fountain-encoded
+        // parts following the fragments make up for them.
+        let mut decoder = PsbtQrDecoder::default();
+        for i in 0..encoder.fragment_count() * 4 {
+            if i % 3 != 1 {
+                decoder.receive(&encoder.part()).unwrap();
+                // Parts received twice are ignored.
+                decoder.receive(&encoder.part()).unwrap();
+            }
+            if decoder.is_complete() {
+                break;
+            }
+            encoder.next_part().unwrap();
+        }
+        assert!(decoder.is_complete());
+        assert_eq!(decoder.progress().1, Some(encoder.fragment_count()));
+        assert_eq!(decoder.psbt().unwrap(), Some(psbt));
+    }
+
+    #[test]
+    fn psbt_qr_decoder_errors() {
+        let mut decoder = PsbtQrDecoder::default();
+        assert!(matches!(
+            decoder.receive("cHNidP8BAIkCAAAAAc0x"),
+            Err(Error::Ur(_))
+        ));
+        assert_eq!(
+            decoder.receive("ur:crypto-account/oeadcyemrewytyaolntaadmutaaddlo"),
+            Err(Error::UnexpectedType)
+        );
+        assert_eq!(decoder.progress(), (0, None));
+        assert_eq!(decoder.psbt(), Ok(None));
+    }
+}
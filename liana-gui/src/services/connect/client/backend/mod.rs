@@ -18,7 +18,10 @@ use liana::{
 };
 use lianad::{
     bip329::Labels,
-    commands::{CoinStatus, GetInfoDescriptors, LCSpendInfo, LabelItem, UpdateDerivIndexesResult},
+    commands::{
+        CoinStatus, DiscoverAddressesResult, GetInfoDescriptors, LCSpendInfo, LabelItem,
+        UpdateDerivIndexesResult,
+    },
     config::Config,
 };
 use reqwest::{Error, IntoUrl, Method, RequestBuilder};
@@ -584,6 +587,7 @@ impl Daemon for BackendWalletClient {
             block_height: wallet.tip_height.unwrap_or(0),
             descriptors: GetInfoDescriptors {
                 main: wallet.descriptor.to_owned(),
+                watchonly: Vec::new(),
             },
             sync: 1.0,
             rescan_progress: None,
@@ -592,6 +596,7 @@ impl Daemon for BackendWalletClient {
             last_poll_timestamp: None,
             receive_index: wallet.deposit_derivation_index,
             change_index: wallet.change_derivation_index,
+            address_discovery: None,
         })
     }
 
@@ -657,6 +662,13 @@ impl Daemon for BackendWalletClient {
         Err(DaemonError::NotImplemented)
     }
 
+    async fn discover_addresses(
+        &self,
+        _gap_limit: Option<u32>,
+    ) -> Result<DiscoverAddressesResult, DaemonError> {
+        Err(DaemonError::NotImplemented)
+    }
+
     /// Spent coins are not returned if statuses is empty, unless their outpoints are specified.
     async fn list_coins(
         &self,
@@ -688,11 +700,22 @@ impl Daemon for BackendWalletClient {
                         height: info.height,
                     }),
                     is_from_self: c.is_from_self,
+                    is_frozen: false,
+                    time_since_confirmation: None,
                 })
                 .collect(),
+            watchonly_coins: Vec::new(),
         })
     }
 
+    async fn freeze_coins(&self, _outpoints: &[OutPoint]) -> Result<(), DaemonError> {
+        Err(DaemonError::NotImplemented)
+    }
+
+    async fn unfreeze_coins(&self, _outpoints: &[OutPoint]) -> Result<(), DaemonError> {
+        Err(DaemonError::NotImplemented)
+    }
+
     async fn list_spend_txs(&self) -> Result<ListSpendResult, DaemonError> {
         let res = self.list_psbts(&[]).await?;
         Ok(ListSpendResult {
@@ -749,15 +772,15 @@ impl Daemon for BackendWalletClient {
     async fn create_spend_tx(
         &self,
         coins_outpoints: &[OutPoint],
-        destinations: &HashMap<Address<address::NetworkUnchecked>, u64>,
+        destinations: &[Destination],
         feerate_vb: u64,
         change_address: Option<Address<address::NetworkUnchecked>>,
     ) -> Result<CreateSpendResult, DaemonError> {
         let mut recipients: Vec<api::payload::Recipient> = destinations
             .iter()
-            .map(|(addr, amt)| api::payload::Recipient {
-                amount: Some(*amt),
-                address: addr.clone(),
+            .map(|destination| api::payload::Recipient {
+                amount: Some(destination.amount),
+                address: destination.address.clone(),
                 is_max: false,
             })
             .collect();
@@ -851,6 +874,14 @@ impl Daemon for BackendWalletClient {
         }
     }
 
+    async fn cpfp_bump(
+        &self,
+        _outpoints: &[OutPoint],
+        _feerate_vb: u64,
+    ) -> Result<CreateSpendResult, DaemonError> {
+        Err(DaemonError::NotImplemented)
+    }
+
     async fn update_spend_tx(&self, psbt: &Psbt) -> Result<(), DaemonError> {
         self.inner
             .exec_request(
@@ -932,6 +963,26 @@ impl Daemon for BackendWalletClient {
         Ok(res.raw)
     }
 
+    async fn create_absolute_recovery(
+        &self,
+        _address: Address<address::NetworkUnchecked>,
+        _coins_outpoints: &[OutPoint],
+        _feerate_vb: u64,
+        _timelock: u32,
+    ) -> Result<Psbt, DaemonError> {
+        Err(DaemonError::NotImplemented)
+    }
+
+    async fn create_time_recovery(
+        &self,
+        _address: Address<address::NetworkUnchecked>,
+        _coins_outpoints: &[OutPoint],
+        _feerate_vb: u64,
+        _timelock: u16,
+    ) -> Result<Psbt, DaemonError> {
+        Err(DaemonError::NotImplemented)
+    }
+
     async fn get_labels(
         &self,
         items: &HashSet<LabelItem>,
@@ -1102,6 +1153,22 @@ impl Daemon for BackendWalletClient {
 
         Ok(res.labels)
     }
+
+    async fn create_proof_of_reserves(
+        &self,
+        _message: &str,
+        _coins_outpoints: &[OutPoint],
+    ) -> Result<Psbt, DaemonError> {
+        Err(DaemonError::NotImplemented)
+    }
+
+    async fn verify_proof_of_reserves(
+        &self,
+        _psbt: &Psbt,
+        _message: &str,
+    ) -> Result<VerifyProofOfReservesResult, DaemonError> {
+        Err(DaemonError::NotImplemented)
+    }
 }
 
 fn history_tx_from_api(value: api::Transaction, network: Network) -> HistoryTransaction {
@@ -1127,6 +1194,8 @@ fn history_tx_from_api(value: api::Transaction, network: Network) -> HistoryTran
                         height: info.height,
                     }),
                     is_from_self: c.is_from_self,
+                    is_frozen: false,
+                    time_since_confirmation: None,
                 });
             }
         }
@@ -1183,6 +1252,8 @@ fn spend_tx_from_api(
                         height: info.height,
                     }),
                     is_from_self: c.is_from_self,
+                    is_frozen: false,
+                    time_since_confirmation: None,
                 });
             }
         }
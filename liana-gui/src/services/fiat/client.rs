@@ -31,6 +31,16 @@ impl PriceApi for PriceClient<reqwest::Client> {
         self.source.parse_price_data(currency, &data)
     }
 
+    async fn get_historical_price(
+        &self,
+        currency: Currency,
+        timestamp: u64,
+    ) -> Result<GetPriceResult, PriceApiError> {
+        let url = self.source.get_historical_price_url(currency, timestamp);
+        let data = get_data(&self.inner, &url).await?;
+        self.source.parse_historical_price_data(currency, &data)
+    }
+
     async fn list_currencies(&self) -> Result<ListCurrenciesResult, PriceApiError> {
         let url = self.source.list_currencies_url();
         let data = get_data(&self.inner, &url).await?;
@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use super::api::{PriceApi, PriceApiError};
+use super::client::PriceClient;
+use super::currency::Currency;
+use super::source::PriceSource;
+
+/// The name of the file the historical prices are cached in, within the network directory.
+pub const HISTORICAL_PRICES_FILE_NAME: &str = "historical_prices.json";
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Daily prices fetched from the price sources, persisted to a JSON file so they need not be
+/// queried again for the next exports.
+#[derive(Debug, Clone, Default)]
+pub struct HistoricalPrices {
+    path: PathBuf,
+    // Indexed by `source:currency:day`, where `day` is the number of days since the UNIX epoch.
+    prices: BTreeMap<String, f64>,
+}
+
+fn key(source: PriceSource, currency: Currency, timestamp: u64) -> String {
+    format!("{}:{}:{}", source, currency, timestamp / SECONDS_PER_DAY)
+}
+
+impl HistoricalPrices {
+    /// Load the cached prices from the given file. Start from an empty cache if the file does
+    /// not exist or cannot be parsed.
+    pub fn load(path: &Path) -> Self {
+        let prices = match std::fs::read(path) {
+            Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|e| {
+                tracing::warn!("Ignoring invalid historical prices cache: {}", e);
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+        Self {
+            path: path.to_path_buf(),
+            prices,
+        }
+    }
+
+    pub fn save(&self) -> Result<(), std::io::Error> {
+        let content = serde_json::to_vec(&self.prices).map_err(std::io::Error::other)?;
+        std::fs::write(&self.path, content)
+    }
+
+    /// The cached price for the day of this timestamp, if any.
+    pub fn get(&self, source: PriceSource, currency: Currency, timestamp: u64) -> Option<f64> {
+        self.prices.get(&key(source, currency, timestamp)).copied()
+    }
+
+    pub fn insert(&mut self, source: PriceSource, currency: Currency, timestamp: u64, value: f64) {
+        self.prices.insert(key(source, currency, timestamp), value);
+    }
+
+    /// Get the price for the day of this timestamp, querying the price source if it isn't cached.
+    pub async fn get_or_fetch(
+        &mut self,
+        client: &PriceClient<reqwest::Client>,
+        currency: Currency,
+        timestamp: u64,
+    ) -> Result<f64, PriceApiError> {
+        if let Some(value) = self.get(client.source, currency, timestamp) {
+            return Ok(value);
+        }
+        // Always query the price at the start of the day, for it to match the cache granularity.
+        let day_start = timestamp - timestamp % SECONDS_PER_DAY;
+        let value = client
+            .get_historical_price(currency, day_start)
+            .await?
+            .value;
+        self.insert(client.source, currency, timestamp, value);
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn historical_prices_cache() {
+        let path = std::env::temp_dir().join(format!(
+            "liana-historical-prices-{}.json",
+            std::process::id()
+        ));
+        let mut prices = HistoricalPrices::load(&path);
+        assert_eq!(prices.get(PriceSource::CoinGecko, Currency::USD, 0), None);
+
+        // Prices are cached by day, source and currency.
+        prices.insert(
+            PriceSource::CoinGecko,
+            Currency::USD,
+            1_700_000_000,
+            37_000.0,
+        );
+        assert_eq!(
+            prices.get(PriceSource::CoinGecko, Currency::USD, 1_699_920_000),
+            Some(37_000.0)
+        );
+        assert_eq!(
+            prices.get(PriceSource::CoinGecko, Currency::USD, 1_700_006_400),
+            None
+        );
+        assert_eq!(
+            prices.get(PriceSource::MempoolSpace, Currency::USD, 1_700_000_000),
+            None
+        );
+        assert_eq!(
+            prices.get(PriceSource::CoinGecko, Currency::EUR, 1_700_000_000),
+            None
+        );
+
+        prices.save().unwrap();
+        let prices = HistoricalPrices::load(&path);
+        assert_eq!(
+            prices.get(PriceSource::CoinGecko, Currency::USD, 1_700_000_000),
+            Some(37_000.0)
+        );
+
+        std::fs::write(&path, "not json").unwrap();
+        let prices = HistoricalPrices::load(&path);
+        assert_eq!(
+            prices.get(PriceSource::CoinGecko, Currency::USD, 1_700_000_000),
+            None
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
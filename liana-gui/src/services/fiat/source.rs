@@ -1,5 +1,7 @@
 use std::str::FromStr;
 
+use chrono::DateTime;
+
 use super::api::{GetPriceResult, ListCurrenciesResult, PriceApiError};
 use super::currency::Currency;
 
@@ -53,6 +55,26 @@ impl PriceSource {
         }
     }
 
+    /// Returns the URL to fetch the price for a given currency at the given UNIX timestamp.
+    pub fn get_historical_price_url(&self, currency: Currency, timestamp: u64) -> String {
+        match self {
+            // The daily price is given at 00:00 UTC for the requested date.
+            Self::CoinGecko => {
+                let date = DateTime::from_timestamp(timestamp as i64, 0)
+                    .unwrap_or_default()
+                    .format("%d-%m-%Y");
+                format!(
+                    "https://api.coingecko.com/api/v3/coins/bitcoin/history?date={}&localization=false",
+                    date
+                )
+            }
+            Self::MempoolSpace => format!(
+                "https://mempool.space/api/v1/historical-price?currency={}&timestamp={}",
+                currency, timestamp
+            ),
+        }
+    }
+
     /// Returns the URL to fetch the list of supported currencies.
     pub fn list_currencies_url(&self) -> String {
         match self {
@@ -90,6 +112,39 @@ impl PriceSource {
         Ok(GetPriceResult { value, updated_at })
     }
 
+    /// Parses the price data in the API response from the `get_historical_price_url` endpoint.
+    pub fn parse_historical_price_data(
+        &self,
+        currency: Currency,
+        data: &serde_json::Value,
+    ) -> Result<GetPriceResult, PriceApiError> {
+        let (value, updated_at) = match self {
+            Self::CoinGecko => {
+                let value = data
+                    .get("market_data")
+                    .and_then(|market_data| market_data.get("current_price"))
+                    .and_then(|prices| prices.get(currency.to_string().to_lowercase()))
+                    .and_then(|num| num.as_f64())
+                    .ok_or(PriceApiError::CannotParseData("price".to_string()))?;
+                (value, None)
+            }
+            Self::MempoolSpace => {
+                let price = data
+                    .get("prices")
+                    .and_then(|prices| prices.as_array())
+                    .and_then(|prices| prices.first())
+                    .ok_or(PriceApiError::CannotParseData("prices".to_string()))?;
+                let value = price
+                    .get(currency.to_string())
+                    .and_then(|curr| curr.as_f64())
+                    .ok_or(PriceApiError::CannotParseData("price".to_string()))?;
+                let updated_at = price.get("time").and_then(|t| t.as_u64());
+                (value, updated_at)
+            }
+        };
+        Ok(GetPriceResult { value, updated_at })
+    }
+
     /// Parses the currencies data in the API response from the `list_currencies_url` endpoint.
     pub fn parse_currencies_data(
         &self,
@@ -1,5 +1,5 @@
 pub use liana::signer::SignerError;
-use std::str::FromStr;
+use std::{str::FromStr, sync::Mutex};
 
 use liana::{
     miniscript::bitcoin::{
@@ -7,7 +7,7 @@ use liana::{
         psbt::Psbt,
         secp256k1, Network,
     },
-    signer::{self, HotSigner},
+    signer::{self, EncryptedMnemonic, HotSigner},
 };
 
 use crate::dir::{LianaDirectory, NetworkDirectory};
@@ -59,20 +59,82 @@ impl Signer {
         self.key.sign_psbt(psbt, &self.curve)
     }
 
+    pub fn has_pending_musig_sessions(&self) -> bool {
+        self.key.has_pending_musig_sessions()
+    }
+
     pub fn store(
         &self,
         datadir_root: &LianaDirectory,
         network: Network,
         checksum: &str,
         timestamp: i64,
+        password: Option<&str>,
     ) -> Result<(), SignerError> {
         self.key.store(
             datadir_root.path(),
             network,
             &self.curve,
             Some((checksum.to_string(), timestamp)),
+            password,
         )
     }
+
+    /// Encrypt with the given password the mnemonic files of this signer stored in plaintext.
+    pub fn encrypt_stored(
+        &self,
+        datadir_root: &LianaDirectory,
+        network: Network,
+        password: &str,
+    ) -> Result<usize, SignerError> {
+        self.key
+            .encrypt_stored(datadir_root.path(), network, &self.curve, password)
+    }
+}
+
+/// A hot signer whose mnemonic is stored encrypted. It must be unlocked with its password before
+/// it can be used.
+#[derive(Debug)]
+pub struct LockedSigner {
+    mnemonic: EncryptedMnemonic,
+    network: Network,
+    // The unlocked signer is kept in memory as long as it holds the secret nonces of MuSig2
+    // signing sessions in progress, since they are needed to provide our partial signatures.
+    unlocked: Mutex<Option<Signer>>,
+}
+
+impl LockedSigner {
+    pub fn new(mnemonic: EncryptedMnemonic, network: Network) -> Self {
+        Self {
+            mnemonic,
+            network,
+            unlocked: Mutex::new(None),
+        }
+    }
+
+    pub fn fingerprint(&self) -> Fingerprint {
+        self.mnemonic.fingerprint()
+    }
+
+    pub fn unlock(&self, password: &str) -> Result<Signer, SignerError> {
+        Ok(Signer::new(self.mnemonic.decrypt(self.network, password)?))
+    }
+
+    /// Unlock the signer with the given password and sign the PSBT. The password is always
+    /// checked, even if the signer is still unlocked from the first round of a MuSig2 session.
+    pub fn sign_psbt(&self, password: &str, psbt: Psbt) -> Result<Psbt, SignerError> {
+        let signer = self.unlock(password)?;
+        let mut unlocked = self.unlocked.lock().expect("Never poisoned.");
+        let signer = match unlocked.take() {
+            Some(unlocked) if unlocked.has_pending_musig_sessions() => unlocked,
+            _ => signer,
+        };
+        let res = signer.sign_psbt(psbt);
+        if signer.has_pending_musig_sessions() {
+            *unlocked = Some(signer);
+        }
+        res
+    }
 }
 
 pub fn delete_wallet_mnemonics(
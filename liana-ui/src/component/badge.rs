@@ -71,6 +71,13 @@ pub fn spent<'a, T: 'a>() -> Container<'a, T> {
     )
 }
 
+pub fn frozen<'a, T: 'a>() -> Container<'a, T> {
+    badge_pill(
+        "  Frozen  ",
+        "This coin is not selected automatically when creating a transaction.",
+    )
+}
+
 pub fn badge_pill<'a, T: 'a>(label: &'a str, tooltip: &'a str) -> Container<'a, T> {
     Container::new({
         tooltip::Tooltip::new(
@@ -114,6 +114,12 @@ where
         self
     }
 
+    /// Sets whether the [`Form`] is a secure password input, hiding its content.
+    pub fn secure(mut self, is_secure: bool) -> Self {
+        self.input = self.input.secure(is_secure);
+        self
+    }
+
     /// Sets the padding of the [`Form`].
     pub fn padding(mut self, units: u16) -> Self {
         self.input = self.input.padding(units);
@@ -155,6 +155,14 @@ pub fn usb_drive_icon() -> Text<'static> {
     bootstrap_icon('\u{F6F2}')
 }
 
+pub fn qr_code_icon() -> Text<'static> {
+    bootstrap_icon('\u{F6AE}')
+}
+
+pub fn qr_code_scan_icon() -> Text<'static> {
+    bootstrap_icon('\u{F6AD}')
+}
+
 pub fn hdd_icon() -> Text<'static> {
     bootstrap_icon('\u{F412}')
 }
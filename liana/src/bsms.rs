@@ -0,0 +1,494 @@
+//! Bitcoin Secure Multisig Setup (BIP129)
+//!
+//! Helpers for Liana to act as the Coordinator of a BSMS setup: generating a token for each
+//! signer, verifying the key records returned by the signers and creating the descriptor record
+//! for them to register the wallet.
+
+use crate::{
+    descriptors::LianaDescriptor,
+    random::{self, RandomnessError},
+};
+
+use std::{
+    convert::TryInto,
+    error, fmt,
+    str::{self, FromStr},
+};
+
+use aes::cipher::{KeyIvInit, StreamCipher};
+use miniscript::{
+    bitcoin::{
+        self,
+        bip32::{self, Xpub},
+        hashes::{hmac, sha256, sha512, Hash, HashEngine},
+        hex::{DisplayHex, FromHex},
+        secp256k1,
+        sign_message::{signed_msg_hash, MessageSignature},
+    },
+    descriptor::{DescriptorPublicKey, DescriptorXKey, Wildcard},
+};
+
+/// The version line starting all BSMS records.
+pub const BSMS_VERSION: &str = "BSMS 1.0";
+
+// The password used to derive the encryption key from the token.
+const ENCRYPTION_PASSWORD: &[u8] = b"No SPOF";
+const PBKDF2_ITERATIONS: u32 = 2048;
+// Size in bytes of a token we generate. BIP129 allows 64 or 128 bits.
+const TOKEN_LEN: usize = 8;
+const MAC_LEN: usize = 32;
+const IV_LEN: usize = 16;
+
+type Aes256Ctr = ctr::Ctr128BE<aes::Aes256>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BsmsError {
+    Randomness(String),
+    InvalidToken(String),
+    /// The record does not start with the supported BSMS version.
+    Version,
+    UnexpectedToken,
+    MalformedRecord(String),
+    /// The MAC of an encrypted record does not match. Most likely a wrong token was used.
+    Mac,
+    InvalidKey(String),
+    InvalidSignature,
+    DescriptorMismatch,
+    AddressMismatch,
+}
+
+impl fmt::Display for BsmsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Randomness(e) => write!(f, "Generating token: {}", e),
+            Self::InvalidToken(t) => write!(f, "Invalid BSMS token: '{}'.", t),
+            Self::Version => write!(f, "Unsupported BSMS version, expected '{}'.", BSMS_VERSION),
+            Self::UnexpectedToken => write!(f, "The record was not created for this token."),
+            Self::MalformedRecord(e) => write!(f, "Malformed BSMS record: {}", e),
+            Self::Mac => write!(
+                f,
+                "Failed to decrypt the record. Either the token is wrong or the data is corrupted."
+            ),
+            Self::InvalidKey(e) => write!(f, "Invalid key in key record: {}", e),
+            Self::InvalidSignature => write!(f, "The key record is not signed by its key."),
+            Self::DescriptorMismatch => {
+                write!(f, "The descriptor record is not for this descriptor.")
+            }
+            Self::AddressMismatch => write!(
+                f,
+                "The first address in the descriptor record does not match the descriptor."
+            ),
+        }
+    }
+}
+
+impl error::Error for BsmsError {}
+
+impl From<RandomnessError> for BsmsError {
+    fn from(e: RandomnessError) -> Self {
+        Self::Randomness(e.to_string())
+    }
+}
+
+/// The secret shared between the Coordinator and a signer, used to authenticate and encrypt the
+/// records they exchange.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Token {
+    /// The `00` token, records are exchanged in plaintext.
+    NoEncryption,
+    Standard(Vec<u8>),
+}
+
+impl Token {
+    /// Generate a new random 64-bit token.
+    pub fn generate() -> Result<Self, BsmsError> {
+        let bytes = random::random_bytes()?;
+        Ok(Self::Standard(bytes[..TOKEN_LEN].to_vec()))
+    }
+
+    // The PBKDF2-SHA512 derivation of the encryption key from the token. The output length is
+    // below the hash size so a single block is computed.
+    fn encryption_key(bytes: &[u8]) -> [u8; 32] {
+        let hmac = |data: &[&[u8]]| {
+            let mut engine = hmac::HmacEngine::<sha512::Hash>::new(ENCRYPTION_PASSWORD);
+            for d in data {
+                engine.input(d);
+            }
+            hmac::Hmac::<sha512::Hash>::from_engine(engine).to_byte_array()
+        };
+        let mut u = hmac(&[bytes, &1u32.to_be_bytes()]);
+        let mut t = u;
+        for _ in 1..PBKDF2_ITERATIONS {
+            u = hmac(&[&u]);
+            for (t, u) in t.iter_mut().zip(u.iter()) {
+                *t ^= u;
+            }
+        }
+        t[..32].try_into().expect("32 bytes")
+    }
+
+    fn mac(&self, encryption_key: &[u8; 32], data: &str) -> [u8; MAC_LEN] {
+        let hmac_key = sha256::Hash::hash(encryption_key);
+        let mut engine = hmac::HmacEngine::<sha256::Hash>::new(hmac_key.as_byte_array());
+        engine.input(self.to_string().as_bytes());
+        engine.input(data.as_bytes());
+        hmac::Hmac::<sha256::Hash>::from_engine(engine).to_byte_array()
+    }
+
+    /// Encrypt a record for this token, as hex. The record is returned as-is with the `00`
+    /// token.
+    pub fn encrypt(&self, data: &str) -> String {
+        match self {
+            Self::NoEncryption => data.to_string(),
+            Self::Standard(bytes) => {
+                let key = Self::encryption_key(bytes);
+                let mac = self.mac(&key, data);
+                let mut ciphertext = data.as_bytes().to_vec();
+                Aes256Ctr::new_from_slices(&key, &mac[..IV_LEN])
+                    .expect("Valid key and IV lengths")
+                    .apply_keystream(&mut ciphertext);
+                let mut res = mac.to_vec();
+                res.extend_from_slice(&ciphertext);
+                res.to_lower_hex_string()
+            }
+        }
+    }
+
+    /// Decrypt a record encrypted for this token, checking its integrity.
+    pub fn decrypt(&self, record: &str) -> Result<String, BsmsError> {
+        match self {
+            Self::NoEncryption => Ok(record.to_string()),
+            Self::Standard(bytes) => {
+                let data = Vec::<u8>::from_hex(record.trim())
+                    .map_err(|e| BsmsError::MalformedRecord(e.to_string()))?;
+                if data.len() <= MAC_LEN {
+                    return Err(BsmsError::MalformedRecord(
+                        "encrypted record too short".to_string(),
+                    ));
+                }
+                let (mac, ciphertext) = data.split_at(MAC_LEN);
+                let key = Self::encryption_key(bytes);
+                let mut plaintext = ciphertext.to_vec();
+                Aes256Ctr::new_from_slices(&key, &mac[..IV_LEN])
+                    .expect("Valid key and IV lengths")
+                    .apply_keystream(&mut plaintext);
+                let plaintext = String::from_utf8(plaintext).map_err(|_| BsmsError::Mac)?;
+                if self.mac(&key, &plaintext)[..] != *mac {
+                    return Err(BsmsError::Mac);
+                }
+                Ok(plaintext)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoEncryption => write!(f, "00"),
+            Self::Standard(bytes) => write!(f, "{}", bytes.to_lower_hex_string()),
+        }
+    }
+}
+
+impl str::FromStr for Token {
+    type Err = BsmsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "00" {
+            return Ok(Self::NoEncryption);
+        }
+        match Vec::<u8>::from_hex(s) {
+            Ok(bytes) if bytes.len() == 8 || bytes.len() == 16 => Ok(Self::Standard(bytes)),
+            _ => Err(BsmsError::InvalidToken(s.to_string())),
+        }
+    }
+}
+
+// Split a decrypted record into its lines, checking the version and the number of lines.
+fn record_lines(record: &str, n_lines: usize) -> Result<Vec<&str>, BsmsError> {
+    let lines: Vec<&str> = record.trim().lines().map(|l| l.trim_end()).collect();
+    if lines.first() != Some(&BSMS_VERSION) {
+        return Err(BsmsError::Version);
+    }
+    if lines.len() != n_lines {
+        return Err(BsmsError::MalformedRecord(format!(
+            "expected {} lines, got {}",
+            n_lines,
+            lines.len()
+        )));
+    }
+    Ok(lines)
+}
+
+/// A record sent by a signer to the Coordinator, containing its key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyRecord {
+    /// The key of the signer, with its origin.
+    pub key: DescriptorXKey<Xpub>,
+    /// The description of the key set by the signer.
+    pub description: String,
+}
+
+impl KeyRecord {
+    /// Parse a key record, which must have been created for the given token. If the token is not
+    /// `00`, the record is decrypted first. The record must be signed by the key it contains.
+    pub fn parse(record: &str, token: &Token) -> Result<Self, BsmsError> {
+        let record = token.decrypt(record)?;
+        let lines = record_lines(&record, 5)?;
+        if Token::from_str(lines[1])? != *token {
+            return Err(BsmsError::UnexpectedToken);
+        }
+
+        let key = match DescriptorPublicKey::from_str(lines[2]) {
+            Ok(DescriptorPublicKey::XPub(key))
+                if key.origin.is_some()
+                    && key.derivation_path.is_master()
+                    && key.wildcard == Wildcard::None =>
+            {
+                key
+            }
+            Ok(_) => {
+                return Err(BsmsError::InvalidKey(
+                    "must be an xpub with an origin and no derivation".to_string(),
+                ))
+            }
+            Err(e) => return Err(BsmsError::InvalidKey(e.to_string())),
+        };
+
+        // The signature commits to the first four lines of the record.
+        let signature =
+            MessageSignature::from_base64(lines[4]).map_err(|_| BsmsError::InvalidSignature)?;
+        let msg_hash = signed_msg_hash(&lines[..4].join("\n"));
+        let secp = secp256k1::Secp256k1::verification_only();
+        let pubkey = signature
+            .recover_pubkey(&secp, msg_hash)
+            .map_err(|_| BsmsError::InvalidSignature)?;
+        if pubkey.inner != key.xkey.public_key {
+            return Err(BsmsError::InvalidSignature);
+        }
+
+        Ok(Self {
+            key,
+            description: lines[3].to_string(),
+        })
+    }
+}
+
+/// Create the descriptor record for the signer with the given token, encrypted unless the
+/// token is `00`.
+pub fn descriptor_record(
+    descriptor: &LianaDescriptor,
+    network: bitcoin::Network,
+    token: &Token,
+) -> String {
+    token.encrypt(&descriptor.bsms_record(network))
+}
+
+/// Check a descriptor record created for the given token is for this descriptor, and that the
+/// first address it contains was derived from it. Returns the first address.
+pub fn verify_descriptor_record(
+    record: &str,
+    token: &Token,
+    descriptor: &LianaDescriptor,
+    network: bitcoin::Network,
+) -> Result<bitcoin::Address, BsmsError> {
+    let record = token.decrypt(record)?;
+    let lines = record_lines(&record, 4)?;
+    let record_desc = LianaDescriptor::from_str(lines[1])
+        .map_err(|e| BsmsError::MalformedRecord(e.to_string()))?;
+    if record_desc != *descriptor {
+        return Err(BsmsError::DescriptorMismatch);
+    }
+
+    let address = bitcoin::Address::from_str(lines[3])
+        .ok()
+        .and_then(|addr| addr.require_network(network).ok())
+        .ok_or_else(|| BsmsError::MalformedRecord(format!("invalid address '{}'", lines[3])))?;
+    let secp = secp256k1::Secp256k1::verification_only();
+    let first_address = descriptor
+        .receive_descriptor()
+        .derive(bip32::ChildNumber::from(0), &secp)
+        .address(network);
+    if address != first_address {
+        return Err(BsmsError::AddressMismatch);
+    }
+
+    Ok(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use miniscript::bitcoin::{bip32::DerivationPath, secp256k1::Message};
+
+    // Create a signed key record for the key at this derivation path of the given master key.
+    fn key_record(
+        master: &bip32::Xpriv,
+        path: &str,
+        token: &Token,
+        description: &str,
+    ) -> (String, DescriptorXKey<Xpub>) {
+        let secp = secp256k1::Secp256k1::new();
+        let path = DerivationPath::from_str(path).unwrap();
+        let xpriv = master.derive_priv(&secp, &path).unwrap();
+        let key = DescriptorXKey {
+            origin: Some((master.fingerprint(&secp), path)),
+            xkey: Xpub::from_priv(&secp, &xpriv),
+            derivation_path: DerivationPath::master(),
+            wildcard: Wildcard::None,
+        };
+        let payload = format!(
+            "{}\n{}\n{}\n{}",
+            BSMS_VERSION,
+            token,
+            DescriptorPublicKey::XPub(key.clone()),
+            description
+        );
+        let msg = Message::from_digest(signed_msg_hash(&payload).to_byte_array());
+        let sig = secp.sign_ecdsa_recoverable(&msg, &xpriv.private_key);
+        let record = format!(
+            "{}\n{}",
+            payload,
+            MessageSignature::new(sig, true).to_base64()
+        );
+        (token.encrypt(&record), key)
+    }
+
+    #[test]
+    fn bsms_token() {
+        let token = Token::generate().unwrap();
+        assert_ne!(token, Token::generate().unwrap());
+        assert_eq!(token.to_string().len(), 16);
+        assert_eq!(Token::from_str(&token.to_string()).unwrap(), token);
+        assert_eq!(Token::from_str("00").unwrap(), Token::NoEncryption);
+        Token::from_str("a1b2c3d4e5f6a7b8c9d0e1f2a3b4c5d6").unwrap();
+        Token::from_str("a1b2c3").unwrap_err();
+        Token::from_str("zzb2c3d4e5f6a7b8").unwrap_err();
+
+        // Records are encrypted and authenticated using the token.
+        let data = "BSMS 1.0\n00\nsome data";
+        assert_eq!(Token::NoEncryption.encrypt(data), data);
+        let encrypted = token.encrypt(data);
+        assert_ne!(encrypted, data);
+        assert_eq!(token.decrypt(&encrypted).unwrap(), data);
+        assert_eq!(
+            Token::generate().unwrap().decrypt(&encrypted),
+            Err(BsmsError::Mac)
+        );
+        let mut tampered = encrypted.clone();
+        let last = if tampered.ends_with('0') { "1" } else { "0" };
+        tampered.replace_range(tampered.len() - 1.., last);
+        assert_eq!(token.decrypt(&tampered), Err(BsmsError::Mac));
+        assert!(matches!(
+            token.decrypt(&encrypted[..MAC_LEN * 2]),
+            Err(BsmsError::MalformedRecord(_))
+        ));
+    }
+
+    #[test]
+    fn bsms_key_record() {
+        let master = bip32::Xpriv::new_master(bitcoin::Network::Testnet, &[42; 32]).unwrap();
+        for token in [Token::NoEncryption, Token::generate().unwrap()].iter() {
+            let (record, key) = key_record(&master, "m/48'/1'/0'/2'", token, "Alice's key");
+            let parsed = KeyRecord::parse(&record, token).unwrap();
+            assert_eq!(parsed.key, key);
+            assert_eq!(parsed.description, "Alice's key");
+        }
+
+        // A record for another token is rejected, be it encrypted or not.
+        let token = Token::generate().unwrap();
+        let (record, _) = key_record(&master, "m/48'/1'/0'/2'", &token, "Alice's key");
+        assert_eq!(
+            KeyRecord::parse(&record, &Token::generate().unwrap()),
+            Err(BsmsError::Mac)
+        );
+        let (record, _) = key_record(&master, "m/48'/1'/0'/2'", &token, "Alice's key");
+        let record = token.decrypt(&record).unwrap();
+        assert_eq!(
+            KeyRecord::parse(&Token::NoEncryption.encrypt(&record), &Token::NoEncryption),
+            Err(BsmsError::UnexpectedToken)
+        );
+
+        // The record must be signed by the key it contains.
+        let (record, _) = key_record(&master, "m/48'/1'/0'/2'", &Token::NoEncryption, "Alice");
+        let (other_record, _) =
+            key_record(&master, "m/48'/1'/1'/2'", &Token::NoEncryption, "Alice");
+        let forged = format!(
+            "{}\n{}",
+            record.rsplit_once('\n').unwrap().0,
+            other_record.rsplit_once('\n').unwrap().1
+        );
+        assert_eq!(
+            KeyRecord::parse(&forged, &Token::NoEncryption),
+            Err(BsmsError::InvalidSignature)
+        );
+        let tampered = record.replace("Alice", "Mallory");
+        assert_eq!(
+            KeyRecord::parse(&tampered, &Token::NoEncryption),
+            Err(BsmsError::InvalidSignature)
+        );
+        assert_eq!(
+            KeyRecord::parse(
+                &record.replace("BSMS 1.0", "BSMS 2.0"),
+                &Token::NoEncryption
+            ),
+            Err(BsmsError::Version)
+        );
+    }
+
+    #[test]
+    fn bsms_descriptor_record() {
+        let desc = LianaDescriptor::from_str("wsh(or_d(multi(1,[573fb35b/48'/1'/0'/2']tpubDFKp9T7WAYDcENSjoifkrpq1gMDF47KGJcJrpxzX23Qor8wuGbrEVs9utNq1MDS8E2WXJSBk1qoPQLpwyokW7DiUNPwFuxQkL7owNkLAb9W/<0;1>/*,[573fb35c/48'/1'/1'/2']tpubDFGezyzuHJPhdP3jHGW7v7Hwes4Hihqv5W2yyCmRY9VZJCRchETvxrMC8uECeJZdxQ14V4iD4DecoArkUSDwj8ogYE9WEv4MNZr12thNHCs/<0;1>/*),and_v(v:multi(2,[573fb35b/48'/1'/2'/2']tpubDDwxQauiaU964vPzt5Vd7jnDHEUtp2Vc34PaWpEXg5TQ3bRccxnc1MKKh88Hi7xiMeZo9Tm6fBcq4UGXqnDtGUniJLjqAD8SjQ8Eci3aSR7/<0;1>/*,[573fb35c/48'/1'/3'/2']tpubDE37XAVB5CQ1x85md3BQ5uHCoMwT5fgT8X13zzCUQ3x5o2jskYxKjj7Qcxt1Jpj4QB8tqspn2dooPCekRuQDYrDHov7J1ueUNu2wcvgRDxr/<0;1>/*),older(1000))))").unwrap();
+        let network = bitcoin::Network::Testnet;
+        let secp = secp256k1::Secp256k1::verification_only();
+        let first_address = desc
+            .receive_descriptor()
+            .derive(0.into(), &secp)
+            .address(network);
+
+        let token = Token::generate().unwrap();
+        let record = descriptor_record(&desc, network, &token);
+        assert!(!record.contains("BSMS"));
+        assert_eq!(
+            verify_descriptor_record(&record, &token, &desc, network).unwrap(),
+            first_address
+        );
+        assert_eq!(
+            verify_descriptor_record(&record, &Token::generate().unwrap(), &desc, network),
+            Err(BsmsError::Mac)
+        );
+
+        // A record with another address or descriptor is rejected.
+        let plain_record = descriptor_record(&desc, network, &Token::NoEncryption);
+        let other_address = desc
+            .receive_descriptor()
+            .derive(1.into(), &secp)
+            .address(network);
+        let wrong_address =
+            plain_record.replace(&first_address.to_string(), &other_address.to_string());
+        assert_eq!(
+            verify_descriptor_record(&wrong_address, &Token::NoEncryption, &desc, network),
+            Err(BsmsError::AddressMismatch)
+        );
+        let wrong_desc = plain_record.replace("older(1000)", "older(1001)");
+        assert!(matches!(
+            verify_descriptor_record(&wrong_desc, &Token::NoEncryption, &desc, network),
+            Err(BsmsError::MalformedRecord(_))
+        ));
+        let other_desc = LianaDescriptor::from_str(
+            &desc
+                .to_string()
+                .split_once('#')
+                .unwrap()
+                .0
+                .replace("older(1000)", "older(1001)"),
+        )
+        .unwrap();
+        assert_eq!(
+            verify_descriptor_record(&plain_record, &Token::NoEncryption, &other_desc, network),
+            Err(BsmsError::DescriptorMismatch)
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use crate::descriptors::musig::MusigPrimaryKey;
+
 use miniscript::{
     bitcoin::{
         self, bip32,
@@ -6,7 +8,7 @@ use miniscript::{
     },
     descriptor,
     policy::{Concrete as ConcretePolicy, Liftable, Semantic as SemanticPolicy},
-    RelLockTime, ScriptContext, Threshold,
+    AbsLockTime, RelLockTime, ScriptContext, Threshold,
 };
 
 use miniscript::bitcoin::bip32::Fingerprint;
@@ -33,6 +35,9 @@ pub enum LianaPolicyError {
     /// The spending policy is not a valid Miniscript policy: it may for instance be malleable, or
     /// overflow some limit.
     InvalidPolicy(miniscript::Error),
+    /// The primary path keys can't be aggregated into the Taproot internal key.
+    InvalidMusigPrimaryPath,
+    InvalidMusigExpression(String),
 }
 
 impl std::fmt::Display for LianaPolicyError {
@@ -63,20 +68,20 @@ impl std::fmt::Display for LianaPolicyError {
             ),
             Self::InvalidPolicy(e) => write!(f, "Invalid Miniscript policy: {}", e),
             Self::PolicyAnalysis(e) => write!(f, "Analyzing the policy of the miniscript: {}", e),
+            Self::InvalidMusigPrimaryPath => write!(f, "The primary path keys can only be aggregated using MuSig2 under Taproot, if the signatures of all keys are required. There must be at least 2 keys, all of the form '[aaff0099]xpub.../<0;1>/*' with the same multipath step."),
+            Self::InvalidMusigExpression(e) => write!(f, "Invalid musig() key expression: {}", e),
         }
     }
 }
 
 impl error::Error for LianaPolicyError {}
 
-// Whether a Miniscript policy node represents a key check (or several of them).
-fn is_single_key_or_multisig(policy: &SemanticPolicy<descriptor::DescriptorPublicKey>) -> bool {
+// Whether a Miniscript policy node represents a key check (or several of them, possibly within
+// nested thresholds).
+fn is_key_checks(policy: &SemanticPolicy<descriptor::DescriptorPublicKey>) -> bool {
     match policy {
         SemanticPolicy::Key(..) => true,
-        SemanticPolicy::Thresh(thresh) => thresh
-            .data()
-            .iter()
-            .all(|sub| matches!(sub.as_ref(), SemanticPolicy::Key(_))),
+        SemanticPolicy::Thresh(thresh) => thresh.data().iter().all(|sub| is_key_checks(sub)),
         _ => false,
     }
 }
@@ -147,6 +152,55 @@ fn csv_check(csv_value: u32) -> Result<u16, LianaPolicyError> {
     }
 }
 
+// The flag in a CSV value signaling the relative locktime is in units of 512 seconds, as defined
+// in BIP68.
+const SEQUENCE_LOCKTIME_TYPE_FLAG: u32 = 1 << 22;
+
+// A time-based relative locktime must meet the same requirements as a block-based one, except for
+// the type flag being set. The value is then a number of 512-second intervals.
+fn csv_time_check(csv_value: u32) -> Result<u16, LianaPolicyError> {
+    if csv_value & SEQUENCE_LOCKTIME_TYPE_FLAG == 0 {
+        return Err(LianaPolicyError::InsaneTimelock(csv_value));
+    }
+    csv_check(csv_value ^ SEQUENCE_LOCKTIME_TYPE_FLAG)
+        .map_err(|_| LianaPolicyError::InsaneTimelock(csv_value))
+}
+
+// We require the absolute locktime to be a valid `after()` value. That is, neither 0 nor with the
+// most significant bit set. It may either be a block height or a timestamp.
+fn cltv_check(cltv_value: u32) -> Result<u32, LianaPolicyError> {
+    if cltv_value > 0 && cltv_value < 0x80_00_00_00 {
+        Ok(cltv_value)
+    } else {
+        Err(LianaPolicyError::InsaneTimelock(cltv_value))
+    }
+}
+
+// The timelock a recovery path is behind.
+enum PathTimelock {
+    Relative(u16),
+    RelativeTime(u16),
+    Absolute(u32),
+}
+
+// Get the timelock of a recovery path from the semantic policy of one of its subs, if any.
+fn timelock_check(
+    policy: &SemanticPolicy<descriptor::DescriptorPublicKey>,
+) -> Option<Result<PathTimelock, LianaPolicyError>> {
+    match policy {
+        SemanticPolicy::Older(val) if val.is_time_locked() => {
+            Some(csv_time_check(val.to_consensus_u32()).map(PathTimelock::RelativeTime))
+        }
+        SemanticPolicy::Older(val) => {
+            Some(csv_check(val.to_consensus_u32()).map(PathTimelock::Relative))
+        }
+        SemanticPolicy::After(val) => {
+            Some(cltv_check(val.to_consensus_u32()).map(PathTimelock::Absolute))
+        }
+        _ => None,
+    }
+}
+
 // Get the fingerprint and the full derivation paths (path from the master fingerprint in the
 // origin, with the xpub derivation path appended) for a multipath xpub.
 fn key_origins(
@@ -175,14 +229,43 @@ fn key_origins(
 /// Information about a single spending path in the descriptor.
 #[derive(Debug, Eq, PartialEq, Clone, Ord, PartialOrd, Hash)]
 pub enum PathInfo {
+    /// A single key.
     Single(descriptor::DescriptorPublicKey),
+    /// A threshold of keys.
     Multi(usize, Vec<descriptor::DescriptorPublicKey>),
+    /// A threshold of sub-paths, at least one of which is itself a threshold. For instance 2 of
+    /// (CFO, CEO, 2 of (board member 1, board member 2, board member 3)).
+    ///
+    /// When creating a policy, a nested threshold is normalized the same way as its Miniscript
+    /// semantic policy would be. That is, sub-thresholds are merged into their parent where they
+    /// don't change the semantic (an and() of and()s or an or() of or()s) and a threshold of single
+    /// keys is a [`PathInfo::Multi`]. The sub-paths are also sorted.
+    Thresh(usize, Vec<PathInfo>),
 }
 
 impl PathInfo {
+    // Create a threshold of these sub-paths, as a multisig if they are all single keys. The
+    // sub-paths of a nested threshold are sorted, as the Miniscript compiler may not preserve their
+    // order.
+    fn from_subs(k: usize, mut subs: Vec<PathInfo>) -> PathInfo {
+        if subs.iter().all(|sub| matches!(sub, PathInfo::Single(_))) {
+            let keys = subs
+                .into_iter()
+                .map(|sub| match sub {
+                    PathInfo::Single(key) => key,
+                    _ => unreachable!("Just checked all subs are single keys."),
+                })
+                .collect();
+            PathInfo::Multi(k, keys)
+        } else {
+            subs.sort();
+            PathInfo::Thresh(k, subs)
+        }
+    }
+
     /// Get the information about the primary spending path.
     /// Returns None if the policy does not describe the primary spending path of a Liana
-    /// descriptor (that is, a set of keys).
+    /// descriptor (that is, a set of keys, possibly within nested thresholds).
     pub fn from_primary_path(
         policy: SemanticPolicy<descriptor::DescriptorPublicKey>,
     ) -> Result<PathInfo, LianaPolicyError> {
@@ -190,15 +273,12 @@ impl PathInfo {
             SemanticPolicy::Key(key) => Ok(PathInfo::Single(key)),
             SemanticPolicy::Thresh(thresh) if thresh.k() > 0 && thresh.n() >= thresh.k() => {
                 let k = thresh.k();
-                let keys: Result<_, LianaPolicyError> = thresh
+                let subs = thresh
                     .into_data()
                     .into_iter()
-                    .map(|sub| match sub.as_ref() {
-                        SemanticPolicy::Key(key) => Ok(key.clone()),
-                        _ => Err(LianaPolicyError::IncompatibleDesc),
-                    })
-                    .collect();
-                Ok(PathInfo::Multi(k, keys?))
+                    .map(|sub| PathInfo::from_primary_path(sub.as_ref().clone()))
+                    .collect::<Result<_, _>>()?;
+                Ok(PathInfo::from_subs(k, subs))
             }
             _ => Err(LianaPolicyError::IncompatibleDesc),
         }
@@ -206,14 +286,49 @@ impl PathInfo {
 
     /// Get the information about the recovery spending path.
     /// Returns None if the policy does not describe the recovery spending path of a Liana
-    /// descriptor (that is, a set of keys after a timelock).
+    /// descriptor (that is, a set of keys after a relative timelock).
     pub fn from_recovery_path(
         policy: SemanticPolicy<descriptor::DescriptorPublicKey>,
     ) -> Result<(u16, PathInfo), LianaPolicyError> {
+        match PathInfo::from_timelocked_path(policy)? {
+            (PathTimelock::Relative(timelock), info) => Ok((timelock, info)),
+            _ => Err(LianaPolicyError::IncompatibleDesc),
+        }
+    }
+
+    /// Get the information about a time-based relative timelocked recovery spending path.
+    /// Returns None if the policy does not describe a time-based recovery spending path of a Liana
+    /// descriptor (that is, a set of keys after a relative timelock in units of 512 seconds).
+    pub fn from_time_recovery_path(
+        policy: SemanticPolicy<descriptor::DescriptorPublicKey>,
+    ) -> Result<(u16, PathInfo), LianaPolicyError> {
+        match PathInfo::from_timelocked_path(policy)? {
+            (PathTimelock::RelativeTime(timelock), info) => Ok((timelock, info)),
+            _ => Err(LianaPolicyError::IncompatibleDesc),
+        }
+    }
+
+    /// Get the information about an absolute timelocked recovery spending path.
+    /// Returns None if the policy does not describe an absolute timelocked recovery spending path
+    /// of a Liana descriptor (that is, a set of keys after a block height or a date).
+    pub fn from_absolute_recovery_path(
+        policy: SemanticPolicy<descriptor::DescriptorPublicKey>,
+    ) -> Result<(u32, PathInfo), LianaPolicyError> {
+        match PathInfo::from_timelocked_path(policy)? {
+            (PathTimelock::Absolute(timelock), info) => Ok((timelock, info)),
+            _ => Err(LianaPolicyError::IncompatibleDesc),
+        }
+    }
+
+    fn from_timelocked_path(
+        policy: SemanticPolicy<descriptor::DescriptorPublicKey>,
+    ) -> Result<(PathTimelock, PathInfo), LianaPolicyError> {
         // The recovery spending path must always be a policy of type `thresh(2, older(x), thresh(n, key1,
         // key2, ..))`. In the special case n == 1, it is only `thresh(2, older(x), key)`. In the
         // special case n == len(keys) (i.e. it's an N-of-N multisig), it is normalized as
-        // `thresh(n+1, older(x), key1, key2, ...)`.
+        // `thresh(n+1, older(x), key1, key2, ...)`. The same goes for time-based relative
+        // timelocks and for absolute timelocked recovery paths, with `after(x)` in place of
+        // `older(x)`. Any of the keys may also be a (nested) threshold of keys.
         let (k, subs) = match policy {
             SemanticPolicy::Thresh(thresh) => (thresh.k(), thresh.into_data()),
             _ => return Err(LianaPolicyError::IncompatibleDesc),
@@ -223,29 +338,28 @@ impl PathInfo {
             // of the same form as a primary path.
             let tl_value = subs
                 .iter()
-                .find_map(|s| match s.as_ref() {
-                    SemanticPolicy::Older(val) => Some(csv_check(val.to_consensus_u32())),
-                    _ => None,
-                })
+                .find_map(|s| timelock_check(s.as_ref()))
                 .ok_or(LianaPolicyError::IncompatibleDesc)??;
             let keys_sub = subs
                 .into_iter()
-                .find(|sub| is_single_key_or_multisig(sub.as_ref()))
+                .find(|sub| is_key_checks(sub.as_ref()))
                 .ok_or(LianaPolicyError::IncompatibleDesc)?;
             PathInfo::from_primary_path(keys_sub.as_ref().clone()).map(|info| (tl_value, info))
         } else if k == subs.len() && subs.len() > 2 {
-            // The N-of-N case. All subs but the threshold must be keys (if one had been thresh()
-            // of keys it would have been normalized).
+            // The N-of-N case. All subs but the threshold must be keys, or thresholds of keys
+            // which aren't N-of-N (if one had been it would have been normalized).
             let mut tl_value = None;
             let mut keys = Vec::with_capacity(subs.len());
             for sub in subs {
                 match sub.as_ref() {
-                    SemanticPolicy::Key(key) => keys.push(key.clone()),
-                    SemanticPolicy::Older(val) => {
+                    SemanticPolicy::Older(_) | SemanticPolicy::After(_) => {
                         if tl_value.is_some() {
                             return Err(LianaPolicyError::IncompatibleDesc);
                         }
-                        tl_value = Some(csv_check(val.to_consensus_u32())?);
+                        tl_value = timelock_check(sub.as_ref()).transpose()?;
+                    }
+                    sub if is_key_checks(sub) => {
+                        keys.push(PathInfo::from_primary_path(sub.clone())?);
                     }
                     _ => return Err(LianaPolicyError::IncompatibleDesc),
                 }
@@ -253,7 +367,7 @@ impl PathInfo {
             assert!(keys.len() > 1); // At least 3 subs, only one of which may be older().
             Ok((
                 tl_value.ok_or(LianaPolicyError::IncompatibleDesc)?,
-                PathInfo::Multi(k - 1, keys),
+                PathInfo::from_subs(k - 1, keys),
             ))
         } else {
             // If there is less than 2 subs, there can't be both a timelock and keys. If the
@@ -270,6 +384,74 @@ impl PathInfo {
                 keys.push(key);
                 self
             }
+            Self::Thresh(_, ref mut subs) => {
+                subs.push(Self::Single(key));
+                self
+            }
+        }
+    }
+
+    /// All the keys in this path, including those in nested thresholds.
+    pub fn keys(&self) -> Vec<&descriptor::DescriptorPublicKey> {
+        match self {
+            Self::Single(key) => vec![key],
+            Self::Multi(_, keys) => keys.iter().collect(),
+            Self::Thresh(_, subs) => subs.iter().flat_map(|sub| sub.keys()).collect(),
+        }
+    }
+
+    /// Whether this path contains a nested threshold.
+    pub fn is_nested(&self) -> bool {
+        matches!(self, Self::Thresh(..))
+    }
+
+    // Make sure all the nested thresholds in this path are between 1 and their number of subs.
+    fn check_thresholds(&self) -> Result<(), LianaPolicyError> {
+        match self {
+            Self::Thresh(k, subs) if *k > 0 && *k <= subs.len() => {
+                subs.iter().try_for_each(|sub| sub.check_thresholds())
+            }
+            Self::Thresh(k, _) => Err(LianaPolicyError::InvalidMultiThresh(*k)),
+            // The multisig thresholds are checked by the Miniscript compiler.
+            Self::Single(_) | Self::Multi(..) => Ok(()),
+        }
+    }
+
+    // Normalize the nested thresholds of this path the same way Miniscript normalizes a semantic
+    // policy, so a path is the same once it went through a descriptor. A sub-threshold is merged
+    // into its parent if both are an and() or both are an or(), and a threshold of a single sub
+    // is the sub itself. Assumes the thresholds were checked.
+    fn normalized(self) -> PathInfo {
+        let (k, subs) = match self {
+            Self::Thresh(k, subs) => (k, subs),
+            path => return path,
+        };
+        let (is_and, is_or) = (k == subs.len(), k == 1);
+        let mut norm_subs = Vec::with_capacity(subs.len());
+        for sub in subs.into_iter().map(PathInfo::normalized) {
+            match sub {
+                Self::Multi(sub_k, keys)
+                    if (is_and && !is_or && sub_k == keys.len())
+                        || (is_or && !is_and && sub_k == 1) =>
+                {
+                    norm_subs.extend(keys.into_iter().map(Self::Single))
+                }
+                Self::Thresh(sub_k, sub_subs)
+                    if (is_and && !is_or && sub_k == sub_subs.len())
+                        || (is_or && !is_and && sub_k == 1) =>
+                {
+                    norm_subs.extend(sub_subs)
+                }
+                sub => norm_subs.push(sub),
+            }
+        }
+
+        if norm_subs.len() == 1 {
+            norm_subs.pop().expect("Just checked the length.")
+        } else if is_and {
+            PathInfo::from_subs(norm_subs.len(), norm_subs)
+        } else {
+            PathInfo::from_subs(k, norm_subs)
         }
     }
 
@@ -278,6 +460,9 @@ impl PathInfo {
     /// mapping from a master extended key fingerprint, to a set of derivation paths. This is
     /// because we are using multipath descriptors. The derivation paths included the xpub's
     /// derivation path appended to the origin's derivation path (without the wildcard step).
+    ///
+    /// For a nested threshold, the required number is that of sub-paths and the set of keys
+    /// contains the keys of all the sub-paths.
     pub fn thresh_origins(
         &self,
     ) -> (
@@ -291,7 +476,8 @@ impl PathInfo {
                 all_origins.insert(fg, der_path);
                 (1, all_origins)
             }
-            PathInfo::Multi(k, keys) => {
+            PathInfo::Multi(k, _) | PathInfo::Thresh(k, _) => {
+                let keys = self.keys();
                 let mut all_origins: HashMap<_, HashSet<_>> = HashMap::with_capacity(keys.len());
                 for key in keys {
                     let (fg, der_paths) =
@@ -309,10 +495,36 @@ impl PathInfo {
 
     /// Get the spend information for this descriptor based from the list of all pubkeys that
     /// signed the transaction.
+    ///
+    /// For a nested threshold, the number of signatures is the number of sub-paths which have
+    /// enough signatures.
     pub fn spend_info<'a>(
         &self,
         all_pubkeys_signed: impl Iterator<Item = &'a (bip32::Fingerprint, bip32::DerivationPath)>,
     ) -> PathSpendInfo {
+        if let PathInfo::Thresh(k, subs) = self {
+            let all_pubkeys_signed: Vec<_> = all_pubkeys_signed.collect();
+            let sub_paths: Vec<_> = subs
+                .iter()
+                .map(|sub| sub.spend_info(all_pubkeys_signed.iter().copied()))
+                .collect();
+            let mut signed_pubkeys = HashMap::new();
+            for sub_info in &sub_paths {
+                for (fg, count) in &sub_info.signed_pubkeys {
+                    *signed_pubkeys.entry(*fg).or_insert(0) += count;
+                }
+            }
+            return PathSpendInfo {
+                threshold: *k,
+                sigs_count: sub_paths
+                    .iter()
+                    .filter(|sub_info| sub_info.sigs_count >= sub_info.threshold)
+                    .count(),
+                signed_pubkeys,
+                sub_paths,
+            };
+        }
+
         let mut signed_pubkeys = HashMap::new();
         let mut sigs_count = 0;
         let (threshold, origins) = self.thresh_origins();
@@ -349,6 +561,7 @@ impl PathInfo {
             threshold,
             sigs_count,
             signed_pubkeys,
+            sub_paths: Vec::new(),
         }
     }
 
@@ -367,6 +580,15 @@ impl PathInfo {
                 )
                 .map_err(|e| LianaPolicyError::InvalidPolicy(miniscript::Error::Threshold(e)))?,
             ),
+            PathInfo::Thresh(thresh, subs) => ConcretePolicy::Thresh(
+                Threshold::new(
+                    thresh,
+                    subs.into_iter()
+                        .map(|sub| sub.into_ms_policy().map(sync::Arc::new))
+                        .collect::<Result<_, _>>()?,
+                )
+                .map_err(|e| LianaPolicyError::InvalidPolicy(miniscript::Error::Threshold(e)))?,
+            ),
         })
     }
 
@@ -376,6 +598,19 @@ impl PathInfo {
     }
 }
 
+// Check and normalize the nested thresholds of these timelocked paths.
+fn normalized_paths<T: Ord>(
+    paths: BTreeMap<T, PathInfo>,
+) -> Result<BTreeMap<T, PathInfo>, LianaPolicyError> {
+    paths
+        .into_iter()
+        .map(|(timelock, path)| {
+            path.check_thresholds()?;
+            Ok((timelock, path.normalized()))
+        })
+        .collect()
+}
+
 // See
 // https://github.com/bitcoin/bips/blob/master/bip-0341.mediawiki#constructing-and-spending-taproot-outputs:
 // > One example of such a point is H =
@@ -464,7 +699,15 @@ pub fn unspendable_internal_key(
 /// A Liana spending policy is one composed of at least two spending paths:
 ///     - A directly available path with any number of keys checks; or
 ///     - One or more recovery paths with any number of keys checks, behind increasing relative
-///     timelocks. No two recovery paths may have the same timelock.
+///     timelocks in number of blocks. No two recovery paths may have the same timelock.
+///     - Optionally, any number of additional recovery paths with any number of keys checks,
+///     behind relative timelocks in units of 512 seconds. No two such recovery paths may have
+///     the same timelock.
+///     - Optionally, any number of additional recovery paths with any number of keys checks,
+///     behind absolute timelocks (a block height or a date). No two absolute recovery paths may
+///     have the same timelock.
+/// Under Taproot, the keys of an N-of-N primary path may optionally be aggregated into the
+/// internal key using MuSig2.
 /// A Liana policy can be created from some settings (the primary and recovery keys, the
 /// timelock(s)) and be used to derive a descriptor. It can also be inferred from a descriptor and
 /// be used to retrieve the settings.
@@ -474,7 +717,10 @@ pub fn unspendable_internal_key(
 pub struct LianaPolicy {
     pub(super) primary_path: PathInfo,
     pub(super) recovery_paths: BTreeMap<u16, PathInfo>,
+    pub(super) time_recovery_paths: BTreeMap<u16, PathInfo>,
+    pub(super) absolute_recovery_paths: BTreeMap<u32, PathInfo>,
     is_taproot: bool,
+    musig_primary: bool,
 }
 
 impl LianaPolicy {
@@ -485,7 +731,10 @@ impl LianaPolicy {
     fn _new(
         primary_path: PathInfo,
         recovery_paths: BTreeMap<u16, PathInfo>,
+        time_recovery_paths: BTreeMap<u16, PathInfo>,
+        absolute_recovery_paths: BTreeMap<u32, PathInfo>,
         is_taproot: bool,
+        musig_primary: bool,
         compile: bool,
     ) -> Result<LianaPolicy, LianaPolicyError> {
         if recovery_paths.is_empty() {
@@ -502,6 +751,37 @@ impl LianaPolicy {
         if recovery_paths.contains_key(&0) {
             return Err(LianaPolicyError::InsaneTimelock(0));
         }
+        // The same goes for the time-based relative timelocks, expressed in number of 512-second
+        // intervals.
+        if time_recovery_paths.contains_key(&0) {
+            return Err(LianaPolicyError::InsaneTimelock(
+                SEQUENCE_LOCKTIME_TYPE_FLAG,
+            ));
+        }
+        // Absolute timelocks may be expressed either as a block height or as a timestamp, but they
+        // must be valid `after()` values.
+        for timelock in absolute_recovery_paths.keys() {
+            cltv_check(*timelock)?;
+        }
+
+        // Nested thresholds are normalized the same way they would be once in a descriptor, for a
+        // policy to be the same after going through it.
+        primary_path.check_thresholds()?;
+        let primary_path = primary_path.normalized();
+        let recovery_paths = normalized_paths(recovery_paths)?;
+        let time_recovery_paths = normalized_paths(time_recovery_paths)?;
+        let absolute_recovery_paths = normalized_paths(absolute_recovery_paths)?;
+
+        // The primary path keys may only be aggregated into the Taproot internal key if all of
+        // them must sign.
+        if musig_primary {
+            match primary_path {
+                PathInfo::Multi(k, ref keys) if is_taproot && k == keys.len() => {
+                    MusigPrimaryKey::new(keys.clone())?;
+                }
+                _ => return Err(LianaPolicyError::InvalidMusigPrimaryPath),
+            }
+        }
 
         // Check all keys are valid according to our standard (this checks all are multipath keys).
         // Note while the Miniscript compiler does check for duplicate, it does so at the
@@ -509,30 +789,26 @@ impl LianaPolicy {
         // ourselves here.
         let spending_paths = recovery_paths
             .values()
+            .chain(time_recovery_paths.values())
+            .chain(absolute_recovery_paths.values())
             .chain(std::iter::once(&primary_path));
         let mut key_checker = DescKeyChecker::new();
         for path in spending_paths {
-            match path {
-                PathInfo::Single(ref key) => {
-                    let _ = key_checker.check(key)?;
-                }
-                PathInfo::Multi(_, ref keys) => {
-                    // Record the origins of the keys for this spending path. If any two keys share
-                    // the same origin, they are from the same signer. We restrict using a signer
-                    // more than once within a single spending path as it can lead to surprising
-                    // behaviour. For details see:
-                    // https://github.com/wizardsardine/liana/pull/706#issuecomment-1744705808
-                    let mut origin_fingerprints = HashSet::with_capacity(keys.len());
-                    for key in keys {
-                        let fg = key_checker.check(key)?;
-                        if origin_fingerprints.contains(&fg) {
-                            return Err(LianaPolicyError::DuplicateOriginSamePath(
-                                key.clone().into(),
-                            ));
-                        }
-                        origin_fingerprints.insert(fg);
-                    }
+            // Record the origins of the keys for this spending path. If any two keys share the
+            // same origin, they are from the same signer. We restrict using a signer more than
+            // once within a single spending path (including within its nested thresholds) as it
+            // can lead to surprising behaviour. For details see:
+            // https://github.com/wizardsardine/liana/pull/706#issuecomment-1744705808
+            let keys = path.keys();
+            let mut origin_fingerprints = HashSet::with_capacity(keys.len());
+            for key in keys {
+                let fg = key_checker.check(key)?;
+                if origin_fingerprints.contains(&fg) {
+                    return Err(LianaPolicyError::DuplicateOriginSamePath(
+                        key.clone().into(),
+                    ));
                 }
+                origin_fingerprints.insert(fg);
             }
         }
 
@@ -540,7 +816,10 @@ impl LianaPolicy {
         let policy = LianaPolicy {
             primary_path,
             recovery_paths,
+            time_recovery_paths,
+            absolute_recovery_paths,
             is_taproot,
+            musig_primary,
         };
         if compile {
             policy.clone().compile_multipath_descriptor_fallible()?;
@@ -556,7 +835,10 @@ impl LianaPolicy {
         Self::_new(
             primary_path,
             recovery_paths,
+            BTreeMap::new(),
+            BTreeMap::new(),
             /* is_taproot = */ true,
+            /* musig_primary = */ false,
             /* compile = */ true,
         )
     }
@@ -569,7 +851,70 @@ impl LianaPolicy {
         Self::_new(
             primary_path,
             recovery_paths,
+            BTreeMap::new(),
+            BTreeMap::new(),
             /* is_taproot = */ false,
+            /* musig_primary = */ false,
+            /* compile = */ true,
+        )
+    }
+
+    /// Add recovery paths behind absolute timelocks to this Liana policy. The absolute timelocks
+    /// are consensus-encoded `nLockTime` values: below 500'000'000 they are interpreted as a
+    /// block height, above as a UNIX timestamp.
+    ///
+    /// Contrary to the relative timelocks, the absolute timelocks do not reset when the coins
+    /// are moved. Do note this replaces any absolute recovery path previously set.
+    pub fn with_absolute_recovery_paths(
+        self,
+        absolute_recovery_paths: BTreeMap<u32, PathInfo>,
+    ) -> Result<LianaPolicy, LianaPolicyError> {
+        Self::_new(
+            self.primary_path,
+            self.recovery_paths,
+            self.time_recovery_paths,
+            absolute_recovery_paths,
+            self.is_taproot,
+            self.musig_primary,
+            /* compile = */ true,
+        )
+    }
+
+    /// Add recovery paths behind time-based relative timelocks to this Liana policy. The
+    /// timelocks are expressed in number of 512-second intervals, as defined in BIP68.
+    ///
+    /// Contrary to block-based relative timelocks, they do not drift with the hashrate. Do note
+    /// this replaces any time-based recovery path previously set.
+    pub fn with_time_recovery_paths(
+        self,
+        time_recovery_paths: BTreeMap<u16, PathInfo>,
+    ) -> Result<LianaPolicy, LianaPolicyError> {
+        Self::_new(
+            self.primary_path,
+            self.recovery_paths,
+            time_recovery_paths,
+            self.absolute_recovery_paths,
+            self.is_taproot,
+            self.musig_primary,
+            /* compile = */ true,
+        )
+    }
+
+    /// Aggregate the keys of the primary path into the internal key of the Taproot descriptor
+    /// using MuSig2, instead of checking them in a Taproot leaf. This makes spending through the
+    /// primary path cheaper and doesn't reveal the spending policy, but requires the signers to
+    /// support MuSig2.
+    ///
+    /// This is only possible for a Taproot policy whose primary path requires the signatures of
+    /// all of its keys (an N-of-N multisig), all with the same derivation.
+    pub fn with_musig_primary_path(self) -> Result<LianaPolicy, LianaPolicyError> {
+        Self::_new(
+            self.primary_path,
+            self.recovery_paths,
+            self.time_recovery_paths,
+            self.absolute_recovery_paths,
+            self.is_taproot,
+            /* musig_primary = */ true,
             /* compile = */ true,
         )
     }
@@ -578,6 +923,15 @@ impl LianaPolicy {
     /// (P2WSH, multipath, ..) and has a valid Liana semantic.
     pub fn from_multipath_descriptor(
         desc: &descriptor::Descriptor<descriptor::DescriptorPublicKey>,
+    ) -> Result<LianaPolicy, LianaPolicyError> {
+        Self::from_musig_multipath_descriptor(desc, None)
+    }
+
+    /// Create a Liana policy from a descriptor whose internal key may be the MuSig2 aggregate of
+    /// the primary path keys.
+    pub(super) fn from_musig_multipath_descriptor(
+        desc: &descriptor::Descriptor<descriptor::DescriptorPublicKey>,
+        musig_key: Option<&MusigPrimaryKey>,
     ) -> Result<LianaPolicy, LianaPolicyError> {
         // Lift a semantic policy out of this Miniscript and normalize it to make sure we compare
         // apples to apples below.
@@ -598,13 +952,31 @@ impl LianaPolicy {
                         .ok_or(LianaPolicyError::IncompatibleDesc)?;
                     let desc_int_xpub = get_multi_xkey(desc.internal_key())
                         .ok_or(LianaPolicyError::IncompatibleDesc)?;
-                    if *desc_int_xpub == unspend_int_xpub {
-                        tree_policy
-                    } else {
+                    // If the internal key is the aggregate of the primary path keys, the primary
+                    // path is the N-of-N of the participants' keys.
+                    let int_key_policy = match musig_key {
+                        Some(musig_key) if *desc.internal_key() == musig_key.aggregate_key() => {
+                            let keys = musig_key
+                                .participants()
+                                .iter()
+                                .map(|key| sync::Arc::new(SemanticPolicy::Key(key.clone())))
+                                .collect::<Vec<_>>();
+                            Some(SemanticPolicy::Thresh(
+                                Threshold::new(keys.len(), keys)
+                                    .expect("Threshold is the number of keys."),
+                            ))
+                        }
+                        Some(_) => return Err(LianaPolicyError::IncompatibleDesc),
+                        None if *desc_int_xpub == unspend_int_xpub => None,
+                        None => Some(SemanticPolicy::Key(desc.internal_key().clone())),
+                    };
+                    if let Some(int_key_policy) = int_key_policy {
                         SemanticPolicy::Thresh(Threshold::or(
-                            sync::Arc::new(SemanticPolicy::Key(desc.internal_key().clone())),
+                            sync::Arc::new(int_key_policy),
                             sync::Arc::new(tree_policy),
                         ))
+                    } else {
+                        tree_policy
                     }
                 } else {
                     // A Liana descriptor must contain a timelocked path.
@@ -615,6 +987,9 @@ impl LianaPolicy {
             _ => return Err(LianaPolicyError::IncompatibleDesc),
         }
         .normalized();
+        if musig_key.is_some() && !matches!(desc, descriptor::Descriptor::Tr(..)) {
+            return Err(LianaPolicyError::IncompatibleDesc);
+        }
         let is_taproot = matches!(desc, descriptor::Descriptor::Tr(..));
 
         // The policy must always be "1 of N spending paths" with at least an always-available
@@ -629,7 +1004,9 @@ impl LianaPolicy {
 
         // Fetch all spending paths' semantic policies. The primary path is identified as the only
         // one that isn't timelocked.
-        let (mut primary_path, mut recovery_paths) = (None::<PathInfo>, BTreeMap::new());
+        let (mut primary_subs, mut recovery_paths, mut time_recovery_paths) =
+            (Vec::new(), BTreeMap::new(), BTreeMap::new());
+        let mut absolute_recovery_paths = BTreeMap::new();
         for sub in subs {
             // Rust-Miniscript now forces the policy in thresholds to be wrapped into an Arc. Since
             // we lift the policy from the descriptor right above, there is necessarily a single
@@ -638,42 +1015,56 @@ impl LianaPolicy {
             let sub =
                 sync::Arc::try_unwrap(sub).expect("Only a single reference, created right above.");
 
-            // This is a (multi)key check. It must be the primary path.
-            if is_single_key_or_multisig(&sub) {
+            // This is a (multi)key check. It must be (part of) the primary path.
+            if is_key_checks(&sub) {
                 // We only support a single primary path. But it may be that the primary path is a
-                // 1-of-N multisig. In this case the policy is normalized from `thresh(1, thresh(1,
-                // pk(A), pk(B)), thresh(2, older(42), pk(C)))` to `thresh(1, pk(A), pk(B),
-                // thresh(2, older(42), pk(C)))`.
-                if let Some(prim_path) = primary_path {
-                    if let SemanticPolicy::Key(key) = sub {
-                        primary_path = Some(prim_path.with_added_key(key.clone()));
-                    } else {
-                        return Err(LianaPolicyError::IncompatibleDesc);
-                    }
-                } else {
-                    primary_path = Some(PathInfo::from_primary_path(sub)?);
-                }
+                // 1-of-N threshold. In this case the policy is normalized from `thresh(1, thresh(1,
+                // pk(A), thresh(2, pk(B), pk(C), pk(D))), thresh(2, older(42), pk(E)))` to
+                // `thresh(1, pk(A), thresh(2, pk(B), pk(C), pk(D)), thresh(2, older(42), pk(E)))`.
+                primary_subs.push(PathInfo::from_primary_path(sub)?);
             } else {
                 // If it's not a simple (multi)key check, it must be (one of) the timelocked
                 // recovery path(s).
-                let (timelock, path_info) = PathInfo::from_recovery_path(sub)?;
-                if recovery_paths.contains_key(&timelock) {
-                    return Err(LianaPolicyError::IncompatibleDesc);
+                match PathInfo::from_timelocked_path(sub)? {
+                    (PathTimelock::Relative(timelock), path_info) => {
+                        if recovery_paths.contains_key(&timelock) {
+                            return Err(LianaPolicyError::IncompatibleDesc);
+                        }
+                        recovery_paths.insert(timelock, path_info);
+                    }
+                    (PathTimelock::RelativeTime(timelock), path_info) => {
+                        if time_recovery_paths.contains_key(&timelock) {
+                            return Err(LianaPolicyError::IncompatibleDesc);
+                        }
+                        time_recovery_paths.insert(timelock, path_info);
+                    }
+                    (PathTimelock::Absolute(timelock), path_info) => {
+                        if absolute_recovery_paths.contains_key(&timelock) {
+                            return Err(LianaPolicyError::IncompatibleDesc);
+                        }
+                        absolute_recovery_paths.insert(timelock, path_info);
+                    }
                 }
-                recovery_paths.insert(timelock, path_info);
             }
         }
 
         // Use the constructor for sanity checking the keys and the Miniscript policy. Note this
         // makes sure the recovery paths mapping isn't empty, too.
-        let prim_path = primary_path.ok_or(LianaPolicyError::IncompatibleDesc)?;
+        let prim_path = match primary_subs.len() {
+            0 => return Err(LianaPolicyError::IncompatibleDesc),
+            1 => primary_subs.pop().expect("Just checked the length."),
+            _ => PathInfo::from_subs(1, primary_subs),
+        };
         // We don't compile the policy as we assume it compiles given we started with a descriptor.
         // This will still perform all other checks to make sure the descriptor conforms to
         // a Liana policy.
         LianaPolicy::_new(
             prim_path,
             recovery_paths,
+            time_recovery_paths,
+            absolute_recovery_paths,
             is_taproot,
+            /* musig_primary = */ musig_key.is_some(),
             /* compile = */ false,
         )
     }
@@ -689,25 +1080,79 @@ impl LianaPolicy {
         &self.recovery_paths
     }
 
+    /// Timelocks, in number of 512-second intervals, and path info of the recovery paths behind a
+    /// time-based relative timelock. This mapping may be empty.
+    pub fn time_recovery_paths(&self) -> &BTreeMap<u16, PathInfo> {
+        &self.time_recovery_paths
+    }
+
+    /// Absolute timelocks and path info of the recovery paths behind an absolute timelock. This
+    /// mapping may be empty.
+    pub fn absolute_recovery_paths(&self) -> &BTreeMap<u32, PathInfo> {
+        &self.absolute_recovery_paths
+    }
+
+    /// The MuSig2 aggregate of the primary path keys, if they are aggregated into the Taproot
+    /// internal key.
+    pub fn musig_primary_key(&self) -> Option<MusigPrimaryKey> {
+        match self.primary_path {
+            PathInfo::Multi(_, ref keys) if self.musig_primary => {
+                Some(MusigPrimaryKey::new(keys.clone()).expect("Checked when creating the policy."))
+            }
+            _ => None,
+        }
+    }
+
     fn into_policy(
         self,
     ) -> Result<miniscript::policy::Concrete<descriptor::DescriptorPublicKey>, LianaPolicyError>
     {
+        let musig_key = self.musig_primary_key();
         let LianaPolicy {
             primary_path,
             recovery_paths,
+            time_recovery_paths,
+            absolute_recovery_paths,
             ..
         } = self;
 
-        // Start with the primary spending path. We'll then or() all the recovery paths to it.
-        let primary_keys = primary_path.into_ms_policy()?;
+        // Start with the primary spending path. We'll then or() all the recovery paths to it. If
+        // its keys are aggregated, the aggregate key will end up as the Taproot internal key.
+        let primary_keys = match musig_key {
+            Some(musig_key) => ConcretePolicy::Key(musig_key.aggregate_key()),
+            None => primary_path.into_ms_policy()?,
+        };
 
-        // Incrementally create the top-level policy using all recovery paths.
+        // Incrementally create the top-level policy using all recovery paths. The time-based
+        // relative timelocked ones come after the block-based ones, and the absolute timelocked
+        // ones come last.
         assert!(!recovery_paths.is_empty());
-        recovery_paths
+        let relative_timelocks = recovery_paths.into_iter().map(|(timelock, path_info)| {
+            (
+                ConcretePolicy::Older(RelLockTime::from_height(timelock)),
+                path_info,
+            )
+        });
+        let time_timelocks = time_recovery_paths
             .into_iter()
+            .map(|(timelock, path_info)| {
+                (
+                    ConcretePolicy::Older(RelLockTime::from_512_second_intervals(timelock)),
+                    path_info,
+                )
+            });
+        let absolute_timelocks =
+            absolute_recovery_paths
+                .into_iter()
+                .map(|(timelock, path_info)| {
+                    let timelock = AbsLockTime::from_consensus(timelock)
+                        .expect("Checked when creating the policy.");
+                    (ConcretePolicy::After(timelock), path_info)
+                });
+        relative_timelocks
+            .chain(time_timelocks)
+            .chain(absolute_timelocks)
             .try_fold(primary_keys, |tl_policy, (timelock, path_info)| {
-                let timelock = ConcretePolicy::Older(RelLockTime::from_height(timelock));
                 let keys = path_info.into_ms_policy()?;
                 let recovery_branch = ConcretePolicy::And(vec![keys.into(), timelock.into()]);
                 // We assume the larger the timelock the less likely a branch would be used.
@@ -809,6 +1254,9 @@ pub struct PathSpendInfo {
     /// The keys for which a signature was provided and the number (always >=1) of
     /// signatures provided for this key.
     pub signed_pubkeys: HashMap<bip32::Fingerprint, usize>,
+    /// For a nested threshold, the spend information for each of its sub-paths. In this case the
+    /// threshold and the number of signatures are those of sub-paths. Empty otherwise.
+    pub sub_paths: Vec<PathSpendInfo>,
 }
 
 /// Information about a partial spend of Liana coins
@@ -819,6 +1267,12 @@ pub struct PartialSpendInfo {
     /// Number of signatures present for the recovery path, only present for the recovery paths
     /// that are available.
     pub(super) recovery_paths: BTreeMap<u16, PathSpendInfo>,
+    /// Number of signatures present for the time-based relative timelocked recovery paths, only
+    /// present for those that are available.
+    pub(super) time_recovery_paths: BTreeMap<u16, PathSpendInfo>,
+    /// Number of signatures present for the absolute timelocked recovery paths, only present for
+    /// those that are available.
+    pub(super) absolute_recovery_paths: BTreeMap<u32, PathSpendInfo>,
 }
 
 impl PartialSpendInfo {
@@ -832,6 +1286,18 @@ impl PartialSpendInfo {
     pub fn recovery_paths(&self) -> &BTreeMap<u16, PathSpendInfo> {
         &self.recovery_paths
     }
+
+    /// Get the number of signatures present for each time-based relative timelocked recovery
+    /// path. Only present for available paths.
+    pub fn time_recovery_paths(&self) -> &BTreeMap<u16, PathSpendInfo> {
+        &self.time_recovery_paths
+    }
+
+    /// Get the number of signatures present for each absolute timelocked recovery path. Only
+    /// present for available paths.
+    pub fn absolute_recovery_paths(&self) -> &BTreeMap<u32, PathSpendInfo> {
+        &self.absolute_recovery_paths
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,373 @@
+//! Export of a Liana descriptor in the formats expected by signing devices and other wallets to
+//! register it.
+
+use crate::descriptors::LianaDescriptor;
+
+use miniscript::{
+    bitcoin::{self, bip32, secp256k1},
+    descriptor::{self, DescriptorPublicKey},
+    ForEachKey,
+};
+
+use std::{collections::BTreeSet, error, fmt, str};
+
+use serde::Serialize;
+
+/// The formats in which a descriptor may be exported for registration on a signer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A text file containing the descriptor, as imported by Coldcard.
+    Coldcard,
+    /// A BIP388 wallet policy (descriptor template and keys information) as JSON.
+    Bip388,
+    /// The wallet JSON imported by Specter and Sparrow.
+    Specter,
+    /// A BIP129 (BSMS) descriptor record.
+    Bsms,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 4] = [
+        ExportFormat::Coldcard,
+        ExportFormat::Bip388,
+        ExportFormat::Specter,
+        ExportFormat::Bsms,
+    ];
+
+    /// The extension of a file containing a descriptor exported in this format.
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            Self::Coldcard => "txt",
+            Self::Bip388 | Self::Specter => "json",
+            Self::Bsms => "bsms",
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Coldcard => write!(f, "coldcard"),
+            Self::Bip388 => write!(f, "bip388"),
+            Self::Specter => write!(f, "specter"),
+            Self::Bsms => write!(f, "bsms"),
+        }
+    }
+}
+
+impl str::FromStr for ExportFormat {
+    type Err = ExportError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "coldcard" => Ok(Self::Coldcard),
+            "bip388" => Ok(Self::Bip388),
+            "specter" => Ok(Self::Specter),
+            "bsms" => Ok(Self::Bsms),
+            _ => Err(ExportError::UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExportError {
+    UnknownFormat(String),
+    /// A key which can't be expressed in a wallet policy. We only support xpubs with a single
+    /// multipath derivation step before the wildcard.
+    UnsupportedKey(String),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownFormat(s) => write!(f, "Unknown export format: '{}'.", s),
+            Self::UnsupportedKey(k) => write!(
+                f,
+                "Key '{}' can't be part of a wallet policy: it must be an xpub with a single \
+                 multipath derivation step before the wildcard.",
+                k
+            ),
+        }
+    }
+}
+
+impl error::Error for ExportError {}
+
+/// A wallet policy as defined in BIP388.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WalletPolicy {
+    /// The descriptor with each key replaced by a `@i` placeholder referring to `keys_info`.
+    pub descriptor_template: String,
+    /// The keys, in the form `[fingerprint/origin/path]xpub`, in the order of their first
+    /// appearance in the descriptor.
+    pub keys_info: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Bip388Export<'a> {
+    name: &'a str,
+    #[serde(flatten)]
+    policy: WalletPolicy,
+}
+
+#[derive(Serialize)]
+struct SpecterDevice {
+    #[serde(rename = "type")]
+    device_type: &'static str,
+    label: String,
+}
+
+#[derive(Serialize)]
+struct SpecterExport<'a> {
+    label: &'a str,
+    descriptor: String,
+    devices: Vec<SpecterDevice>,
+}
+
+// The key information of a key which may be part of a wallet policy: the xpub along with its
+// origin, without the derivation steps.
+fn key_info(key: &DescriptorPublicKey) -> Result<String, ExportError> {
+    match key {
+        DescriptorPublicKey::MultiXPub(xkey)
+            if xkey.wildcard == descriptor::Wildcard::Unhardened
+                && xkey.derivation_paths.paths().iter().all(|p| p.len() == 1) =>
+        {
+            Ok(DescriptorPublicKey::XPub(descriptor::DescriptorXKey {
+                origin: xkey.origin.clone(),
+                xkey: xkey.xkey,
+                derivation_path: bip32::DerivationPath::master(),
+                wildcard: descriptor::Wildcard::None,
+            })
+            .to_string())
+        }
+        _ => Err(ExportError::UnsupportedKey(key.to_string())),
+    }
+}
+
+impl LianaDescriptor {
+    /// Get the BIP388 wallet policy for this descriptor. The participants to a MuSig2 aggregate
+    /// key are kept within the `musig()` expression, as `musig(@0,@1)/<0;1>/*`.
+    pub fn wallet_policy(&self) -> Result<WalletPolicy, ExportError> {
+        let musig_aggregate_key = self.musig_key.as_ref().map(|k| k.aggregate_key());
+        let mut desc_keys = Vec::new();
+        self.multi_desc.for_each_key(|k| {
+            if Some(k) != musig_aggregate_key.as_ref() {
+                desc_keys.push(k.clone());
+            }
+            true
+        });
+        desc_keys.extend(
+            self.musig_key
+                .iter()
+                .flat_map(|k| k.participants())
+                .cloned(),
+        );
+        let infos = desc_keys
+            .iter()
+            .map(key_info)
+            .collect::<Result<BTreeSet<_>, _>>()?;
+
+        // Replace each key information in the descriptor string by its placeholder, leaving the
+        // derivation steps in place. The placeholders are numbered by order of first appearance.
+        // Always match the longest key information, since a key without origin could otherwise
+        // match within the same key with an origin.
+        let desc_str = self.to_string();
+        let desc_str = desc_str
+            .split_once('#')
+            .map(|(payload, _)| payload)
+            .unwrap_or(&desc_str);
+        let mut descriptor_template = String::with_capacity(desc_str.len());
+        let mut keys_info: Vec<String> = Vec::new();
+        let mut rest = desc_str;
+        while let Some(c) = rest.chars().next() {
+            match infos
+                .iter()
+                .filter(|info| rest.starts_with(info.as_str()))
+                .max_by_key(|info| info.len())
+            {
+                Some(info) => {
+                    let index = match keys_info.iter().position(|i| i == info) {
+                        Some(i) => i,
+                        None => {
+                            keys_info.push(info.clone());
+                            keys_info.len() - 1
+                        }
+                    };
+                    descriptor_template.push_str(&format!("@{}", index));
+                    rest = &rest[info.len()..];
+                }
+                None => {
+                    descriptor_template.push(c);
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+
+        Ok(WalletPolicy {
+            descriptor_template,
+            keys_info,
+        })
+    }
+
+    /// Export this descriptor in the given format, for registration on a signer. The name is
+    /// the wallet name displayed by the signer.
+    pub fn export(
+        &self,
+        format: ExportFormat,
+        name: &str,
+        network: bitcoin::Network,
+    ) -> Result<String, ExportError> {
+        match format {
+            ExportFormat::Coldcard => Ok(format!(
+                "# Liana wallet descriptor\n# Name: {}\n{}\n",
+                name, self
+            )),
+            ExportFormat::Bip388 => {
+                let export = Bip388Export {
+                    name,
+                    policy: self.wallet_policy()?,
+                };
+                Ok(serde_json::to_string_pretty(&export).expect("Serializable"))
+            }
+            ExportFormat::Specter => {
+                let fingerprints: BTreeSet<_> = self
+                    .spendable_keys()
+                    .iter()
+                    .map(|k| k.master_fingerprint())
+                    .collect();
+                let export = SpecterExport {
+                    label: name,
+                    // Specter doesn't support multipath descriptors.
+                    descriptor: self.receive_descriptor().to_string(),
+                    devices: fingerprints
+                        .into_iter()
+                        .map(|fg| SpecterDevice {
+                            device_type: "other",
+                            label: fg.to_string(),
+                        })
+                        .collect(),
+                };
+                Ok(serde_json::to_string_pretty(&export).expect("Serializable"))
+            }
+            ExportFormat::Bsms => Ok(self.bsms_record(network)),
+        }
+    }
+
+    /// The BIP129 descriptor record for this descriptor. It contains the first receive address
+    /// for the user to check it against the one displayed by the signer. The derivation paths are
+    /// already restricted by the multipath keys.
+    pub fn bsms_record(&self, network: bitcoin::Network) -> String {
+        let secp = secp256k1::Secp256k1::verification_only();
+        let address = self
+            .receive_descriptor()
+            .derive(0.into(), &secp)
+            .address(network);
+        format!(
+            "{}\n{}\nNo path restrictions\n{}",
+            crate::bsms::BSMS_VERSION,
+            self,
+            address
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::str::FromStr;
+
+    const WSH_DESC: &str = "wsh(or_d(multi(1,[573fb35b/48'/1'/0'/2']tpubDFKp9T7WAYDcENSjoifkrpq1gMDF47KGJcJrpxzX23Qor8wuGbrEVs9utNq1MDS8E2WXJSBk1qoPQLpwyokW7DiUNPwFuxQkL7owNkLAb9W/<0;1>/*,[573fb35c/48'/1'/1'/2']tpubDFGezyzuHJPhdP3jHGW7v7Hwes4Hihqv5W2yyCmRY9VZJCRchETvxrMC8uECeJZdxQ14V4iD4DecoArkUSDwj8ogYE9WEv4MNZr12thNHCs/<0;1>/*),and_v(v:multi(2,[573fb35b/48'/1'/0'/2']tpubDFKp9T7WAYDcENSjoifkrpq1gMDF47KGJcJrpxzX23Qor8wuGbrEVs9utNq1MDS8E2WXJSBk1qoPQLpwyokW7DiUNPwFuxQkL7owNkLAb9W/<2;3>/*,[573fb35c/48'/1'/3'/2']tpubDE37XAVB5CQ1x85md3BQ5uHCoMwT5fgT8X13zzCUQ3x5o2jskYxKjj7Qcxt1Jpj4QB8tqspn2dooPCekRuQDYrDHov7J1ueUNu2wcvgRDxr/<0;1>/*),older(1000))))";
+
+    #[test]
+    fn export_format_roundtrip() {
+        for format in ExportFormat::ALL {
+            assert_eq!(ExportFormat::from_str(&format.to_string()).unwrap(), format);
+        }
+        assert_eq!(
+            ExportFormat::from_str("electrum"),
+            Err(ExportError::UnknownFormat("electrum".to_string()))
+        );
+    }
+
+    #[test]
+    fn descriptor_wallet_policy() {
+        let desc = LianaDescriptor::from_str(WSH_DESC).unwrap();
+        let policy = desc.wallet_policy().unwrap();
+        assert_eq!(
+            policy.descriptor_template,
+            "wsh(or_d(multi(1,@0/<0;1>/*,@1/<0;1>/*),and_v(v:multi(2,@0/<2;3>/*,@2/<0;1>/*),older(1000))))"
+        );
+        assert_eq!(
+            policy.keys_info,
+            vec![
+                "[573fb35b/48'/1'/0'/2']tpubDFKp9T7WAYDcENSjoifkrpq1gMDF47KGJcJrpxzX23Qor8wuGbrEVs9utNq1MDS8E2WXJSBk1qoPQLpwyokW7DiUNPwFuxQkL7owNkLAb9W",
+                "[573fb35c/48'/1'/1'/2']tpubDFGezyzuHJPhdP3jHGW7v7Hwes4Hihqv5W2yyCmRY9VZJCRchETvxrMC8uECeJZdxQ14V4iD4DecoArkUSDwj8ogYE9WEv4MNZr12thNHCs",
+                "[573fb35c/48'/1'/3'/2']tpubDE37XAVB5CQ1x85md3BQ5uHCoMwT5fgT8X13zzCUQ3x5o2jskYxKjj7Qcxt1Jpj4QB8tqspn2dooPCekRuQDYrDHov7J1ueUNu2wcvgRDxr",
+            ]
+        );
+
+        // Keys with more than one derivation step can't be part of a wallet policy.
+        let desc = LianaDescriptor::from_str(&WSH_DESC.replace("<2;3>", "0/<0;1>")).unwrap();
+        assert!(matches!(
+            desc.wallet_policy(),
+            Err(ExportError::UnsupportedKey(_))
+        ));
+        desc.export(ExportFormat::Bip388, "Savings", bitcoin::Network::Testnet)
+            .unwrap_err();
+        // But the descriptor may still be exported in the other formats.
+        desc.export(ExportFormat::Coldcard, "Savings", bitcoin::Network::Testnet)
+            .unwrap();
+    }
+
+    #[test]
+    fn descriptor_export() {
+        let desc = LianaDescriptor::from_str(WSH_DESC).unwrap();
+        let network = bitcoin::Network::Testnet;
+
+        let coldcard = desc
+            .export(ExportFormat::Coldcard, "Savings", network)
+            .unwrap();
+        assert!(coldcard.contains("# Name: Savings\n"));
+        assert!(coldcard.contains(&desc.to_string()));
+
+        let bip388: serde_json::Value = serde_json::from_str(
+            &desc
+                .export(ExportFormat::Bip388, "Savings", network)
+                .unwrap(),
+        )
+        .unwrap();
+        let policy = desc.wallet_policy().unwrap();
+        assert_eq!(bip388["name"], "Savings");
+        assert_eq!(bip388["descriptor_template"], policy.descriptor_template);
+        assert_eq!(bip388["keys_info"].as_array().unwrap().len(), 3);
+
+        let specter: serde_json::Value = serde_json::from_str(
+            &desc
+                .export(ExportFormat::Specter, "Savings", network)
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(specter["label"], "Savings");
+        assert_eq!(specter["descriptor"], desc.receive_descriptor().to_string());
+        // The two signers are identified by their master fingerprint.
+        let devices = specter["devices"].as_array().unwrap();
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[0]["label"], "573fb35b");
+        assert_eq!(devices[1]["label"], "573fb35c");
+
+        let bsms = desc.export(ExportFormat::Bsms, "Savings", network).unwrap();
+        let lines: Vec<_> = bsms.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "BSMS 1.0");
+        assert_eq!(lines[1], desc.to_string());
+        let secp = secp256k1::Secp256k1::verification_only();
+        assert_eq!(
+            lines[3],
+            desc.receive_descriptor()
+                .derive(0.into(), &secp)
+                .address(network)
+                .to_string()
+        );
+    }
+}
@@ -28,6 +28,12 @@ pub use keys::*;
 pub mod analysis;
 pub use analysis::*;
 
+pub mod musig;
+pub use musig::MusigPrimaryKey;
+
+pub mod export;
+pub use export::{ExportError, ExportFormat, WalletPolicy};
+
 #[derive(Debug)]
 pub enum LianaDescError {
     Miniscript(miniscript::Error),
@@ -93,6 +99,11 @@ pub struct LianaDescriptor {
     multi_desc: descriptor::Descriptor<descriptor::DescriptorPublicKey>,
     receive_desc: SinglePathLianaDesc,
     change_desc: SinglePathLianaDesc,
+    /// If the internal key of this Taproot descriptor is the MuSig2 aggregate of the primary path
+    /// keys, the participants to the aggregate key. The aggregate key itself is the internal key
+    /// of `multi_desc`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    musig_key: Option<MusigPrimaryKey>,
 }
 
 /// A Miniscript descriptor with a main, unencombered, branch (the main owner of the coins)
@@ -106,7 +117,11 @@ pub struct DerivedSinglePathLianaDesc(descriptor::Descriptor<DerivedPublicKey>);
 
 impl fmt::Display for LianaDescriptor {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.multi_desc)
+        if let Some(ref musig_key) = self.musig_key {
+            write!(f, "{}", musig_key.descriptor_string(&self.multi_desc))
+        } else {
+            write!(f, "{}", self.multi_desc)
+        }
     }
 }
 
@@ -114,14 +129,22 @@ impl str::FromStr for LianaDescriptor {
     type Err = LianaDescError;
 
     fn from_str(s: &str) -> Result<LianaDescriptor, Self::Err> {
+        // Rust-Miniscript doesn't support musig() key expressions. If the internal key is the
+        // aggregate of the primary path keys, parse the descriptor with the aggregate key in its
+        // place instead.
+        let (desc_str, musig_key) = match MusigPrimaryKey::from_descriptor_str(s)? {
+            Some((desc_str, musig_key)) => (desc_str, Some(musig_key)),
+            None => (s.to_string(), None),
+        };
+
         // Parse a descriptor and check it is a multipath descriptor corresponding to a valid Liana
         // spending policy.
         // Sanity checks are not always performed when calling `Descriptor::from_str`, so we perform
         // them explicitly. See https://github.com/rust-bitcoin/rust-miniscript/issues/734.
-        let desc = descriptor::Descriptor::<descriptor::DescriptorPublicKey>::from_str(s)
+        let desc = descriptor::Descriptor::<descriptor::DescriptorPublicKey>::from_str(&desc_str)
             .and_then(|desc| desc.sanity_check().map(|_| desc))
             .map_err(LianaDescError::Miniscript)?;
-        LianaPolicy::from_multipath_descriptor(&desc)?;
+        LianaPolicy::from_musig_multipath_descriptor(&desc, musig_key.as_ref())?;
 
         // Compute the receive and change "sub" descriptors right away. According to our pubkey
         // check above, there must be only two of those, 0 and 1.
@@ -140,6 +163,7 @@ impl str::FromStr for LianaDescriptor {
             multi_desc: desc,
             receive_desc,
             change_desc,
+            musig_key,
         })
     }
 }
@@ -178,6 +202,7 @@ impl ChangeOutput {
 impl LianaDescriptor {
     pub fn new(spending_policy: LianaPolicy) -> LianaDescriptor {
         // Get the descriptor from the chosen spending policy.
+        let musig_key = spending_policy.musig_primary_key();
         let multi_desc = spending_policy.compile_multipath_descriptor();
 
         // Compute the receive and change "sub" descriptors right away. According to our pubkey
@@ -197,6 +222,7 @@ impl LianaDescriptor {
             multi_desc,
             receive_desc,
             change_desc,
+            musig_key,
         }
     }
 
@@ -215,6 +241,11 @@ impl LianaDescriptor {
     pub fn contains_fingerprint(&self, fg: Fingerprint) -> bool {
         self.multi_desc
             .for_any_key(|k| k.master_fingerprint() == fg)
+            || self
+                .musig_key
+                .as_ref()
+                .map(|musig_key| musig_key.contains_fingerprint(fg))
+                .unwrap_or(false)
     }
 
     /// Determine whether the fingerprint is part of a specific path of this descriptor.
@@ -267,16 +298,22 @@ impl LianaDescriptor {
 
     /// Get the spending policy of this descriptor.
     pub fn policy(&self) -> LianaPolicy {
-        LianaPolicy::from_multipath_descriptor(&self.multi_desc)
+        LianaPolicy::from_musig_multipath_descriptor(&self.multi_desc, self.musig_key.as_ref())
             .expect("We never create a Liana descriptor with an invalid Liana policy.")
     }
 
+    /// Get the MuSig2 aggregate of the primary path keys, if it is used as the internal key of
+    /// this Taproot descriptor.
+    pub fn musig_primary_key(&self) -> Option<&MusigPrimaryKey> {
+        self.musig_key.as_ref()
+    }
+
     /// Get the set of Xpubs that can sign (excluding unspendable key) in the form of an
     /// DescriptorPublicKey::XPub with empty derivation path, in order to avoid duplicates.
     pub fn spendable_keys(&self) -> Vec<DescriptorPublicKey> {
         let mut keys = BTreeSet::new();
         let nums = bip341_nums();
-        self.multi_desc.for_each_key(|k| {
+        let mut insert_key = |k: &DescriptorPublicKey| {
             if let DescriptorPublicKey::MultiXPub(multixkey) = k {
                 let key = DescriptorPublicKey::XPub(DescriptorXKey {
                     origin: multixkey.origin.clone(),
@@ -291,8 +328,18 @@ impl LianaDescriptor {
             } else {
                 unreachable!("all keys must be of MultiXpub type");
             }
+        };
+        // The MuSig2 aggregate key is not a signer's key, list the participants' keys instead.
+        let musig_aggregate_key = self.musig_key.as_ref().map(|k| k.aggregate_key());
+        self.multi_desc.for_each_key(|k| {
+            if Some(k) != musig_aggregate_key.as_ref() {
+                insert_key(k);
+            }
             true
         });
+        for k in self.musig_key.iter().flat_map(|k| k.participants()) {
+            insert_key(k);
+        }
         keys.into_iter().collect()
     }
 
@@ -325,12 +372,12 @@ impl LianaDescriptor {
     pub fn max_sat_weight(&self, use_primary_path: bool) -> usize {
         if use_primary_path {
             // Get the keys from the primary path, to get a satisfaction size estimation only
-            // considering those.
-            let keys = self
-                .policy()
-                .primary_path
-                .thresh_origins()
-                .1
+            // considering those. If they are aggregated, this is the aggregate key.
+            let origins = match self.musig_key {
+                Some(ref musig_key) => std::iter::once(musig_key.aggregate_origins()).collect(),
+                None => self.policy().primary_path.thresh_origins().1,
+            };
+            let keys = origins
                 .into_iter()
                 .fold(BTreeSet::new(), |mut keys, (fg, der_paths)| {
                     for der_path in der_paths {
@@ -411,6 +458,15 @@ impl LianaDescriptor {
         32 + 4 + 4 + 1 + self.max_sat_vbytes(use_primary_path)
     }
 
+    /// Update the PSBT input information for a coin of this descriptor with the participants to
+    /// the MuSig2 aggregate key, if the primary path keys are aggregated. This is a no-op
+    /// otherwise.
+    pub fn update_musig_psbt_in(&self, psbt_in: &mut PsbtIn) {
+        if let Some(ref musig_key) = self.musig_key {
+            musig_key.update_psbt_in(psbt_in);
+        }
+    }
+
     /// Whether this is a Taproot descriptor.
     pub fn is_taproot(&self) -> bool {
         matches!(self.multi_desc, descriptor::Descriptor::Tr(..))
@@ -424,6 +480,7 @@ impl LianaDescriptor {
         &self,
         psbt_in: &PsbtIn,
         txin: &bitcoin::TxIn,
+        lock_time: bitcoin::absolute::LockTime,
     ) -> PartialSpendInfo {
         let is_taproot = self.is_taproot();
         // Get the origin ECDSA or Schnorr signatures, depending on the descriptor type.
@@ -460,9 +517,14 @@ impl LianaDescriptor {
 
         // Determine the structure of the descriptor. Then compute the spend info for the primary
         // and recovery paths. Only provide the spend info for the recovery path if it is available
-        // (ie if the nSequence is >= to the chosen CSV value).
+        // (ie if the nSequence is >= to the chosen CSV value and of the same unit, or if the
+        // nLockTime is >= to the chosen CLTV value and of the same unit).
         let desc_info = self.policy();
-        let primary_path = desc_info.primary_path.spend_info(pubkeys_signed.clone());
+        let primary_path = match self.musig_key {
+            // The signatures for the aggregate key are the MuSig2 partial signatures.
+            Some(ref musig_key) => musig_key.spend_info(psbt_in),
+            None => desc_info.primary_path.spend_info(pubkeys_signed.clone()),
+        };
         let recovery_paths = desc_info
             .recovery_paths
             .iter()
@@ -474,10 +536,39 @@ impl LianaDescriptor {
                 }
             })
             .collect();
+        let time_recovery_paths = desc_info
+            .time_recovery_paths
+            .iter()
+            .filter_map(
+                |(timelock, path_info)| match txin.sequence.to_relative_lock_time() {
+                    Some(bitcoin::relative::LockTime::Time(time)) if time.value() >= *timelock => {
+                        Some((*timelock, path_info.spend_info(pubkeys_signed.clone())))
+                    }
+                    _ => None,
+                },
+            )
+            .collect();
+        let absolute_recovery_paths = desc_info
+            .absolute_recovery_paths
+            .iter()
+            .filter_map(|(timelock, path_info)| {
+                let timelock = bitcoin::absolute::LockTime::from_consensus(*timelock);
+                if txin.sequence.enables_absolute_lock_time() && timelock.is_implied_by(lock_time) {
+                    Some((
+                        timelock.to_consensus_u32(),
+                        path_info.spend_info(pubkeys_signed.clone()),
+                    ))
+                } else {
+                    None
+                }
+            })
+            .collect();
 
         PartialSpendInfo {
             primary_path,
             recovery_paths,
+            time_recovery_paths,
+            absolute_recovery_paths,
         }
     }
 
@@ -512,12 +603,13 @@ impl LianaDescriptor {
                 .expect("We checked at least one is present."),
             txins.next().expect("We checked at least one is present."),
         );
-        let spend_info = self.partial_spend_info_txin(first_psbt_in, first_txin);
+        let lock_time = psbt.unsigned_tx.lock_time;
+        let spend_info = self.partial_spend_info_txin(first_psbt_in, first_txin, lock_time);
         for (psbt_in, txin) in psbt_ins.zip(txins) {
             // TODO: maybe it's better to not error if one of the input has more, or different
             // signatures? Instead of erroring we could ignore the superfluous data?
             if txin.sequence != first_txin.sequence
-                || spend_info != self.partial_spend_info_txin(psbt_in, txin)
+                || spend_info != self.partial_spend_info_txin(psbt_in, txin, lock_time)
             {
                 return Err(LianaDescError::InconsistentPsbt);
             }
@@ -592,6 +684,10 @@ impl LianaDescriptor {
     pub fn prune_bip32_derivs(&self, mut psbt: Psbt, spending_path: &PathInfo) -> Psbt {
         // (Fingerprint, derivation path) pairs uniquely identify a key used in this spending path.
         let (_, path_origins) = spending_path.thresh_origins();
+        // If the primary path keys are aggregated, keep the origins of the key path: those of the
+        // internal key and of the participants to the aggregate key.
+        let keep_key_path =
+            self.musig_key.is_some() && *spending_path == self.policy().primary_path;
 
         // Go through all the PSBT inputs and drop the BIP32 derivations for keys that are not from
         // this spending path.
@@ -603,7 +699,10 @@ impl LianaDescriptor {
                 .retain(|_, (fg, der_path)| key_is_for_path(&path_origins, fg, der_path));
             psbt_in
                 .tap_key_origins
-                .retain(|_, (_, (fg, der_path))| key_is_for_path(&path_origins, fg, der_path));
+                .retain(|_, (leaf_hashes, (fg, der_path))| {
+                    (keep_key_path && leaf_hashes.is_empty())
+                        || key_is_for_path(&path_origins, fg, der_path)
+                });
         }
 
         psbt
@@ -616,6 +715,11 @@ impl LianaDescriptor {
     /// - If there is two recovery paths, and the PSBT's first input nSequence is set to unlock the
     ///     first one, prune all but the first recovery path's bip32 derivations.
     /// - Etc..
+    ///
+    /// Relative timelocked recovery paths take precedence over the absolute timelocked ones, and
+    /// block-based relative timelocks over time-based ones. That is, the latest available
+    /// time-based recovery path is only used if no block-based one is available, and the latest
+    /// available absolute timelocked recovery path only if no relative one is available.
     pub fn prune_bip32_derivs_last_avail(&self, psbt: Psbt) -> Result<Psbt, LianaDescError> {
         let spend_info = self.partial_spend_info(&psbt)?;
         let policy = self.policy();
@@ -629,6 +733,26 @@ impl LianaDescriptor {
                     .get(tl)
                     .expect("Same timelocks must be keys in both mappings.")
             })
+            .or_else(|| {
+                spend_info.time_recovery_paths.iter().last().map(|(tl, _)| {
+                    policy
+                        .time_recovery_paths
+                        .get(tl)
+                        .expect("Same timelocks must be keys in both mappings.")
+                })
+            })
+            .or_else(|| {
+                spend_info
+                    .absolute_recovery_paths
+                    .iter()
+                    .last()
+                    .map(|(tl, _)| {
+                        policy
+                            .absolute_recovery_paths
+                            .get(tl)
+                            .expect("Same timelocks must be keys in both mappings.")
+                    })
+            })
             .unwrap_or(&policy.primary_path);
         Ok(self.prune_bip32_derivs(psbt, path_info))
     }
@@ -1220,6 +1344,509 @@ mod tests {
         assert_eq!(desc.first_timelock_value(), 0xffff);
     }
 
+    #[test]
+    fn absolute_timelock_recovery_paths() {
+        let secp = secp256k1::Secp256k1::signing_only();
+        let prim_key = PathInfo::Single(random_desc_key(&secp));
+        let reco_key = PathInfo::Single(random_desc_key(&secp));
+        let heir_key = PathInfo::Single(random_desc_key(&secp));
+        let other_heir_key = PathInfo::Single(random_desc_key(&secp));
+
+        // An absolute timelock may be either a height or a timestamp, but it must be a valid
+        // `after()` value.
+        for timelock in [0, 0x80_00_00_00] {
+            assert!(matches!(
+                LianaPolicy::new(
+                    prim_key.clone(),
+                    [(52560, reco_key.clone())].iter().cloned().collect(),
+                )
+                .unwrap()
+                .with_absolute_recovery_paths(
+                    [(timelock, heir_key.clone())].iter().cloned().collect()
+                ),
+                Err(LianaPolicyError::InsaneTimelock(tl)) if tl == timelock
+            ));
+        }
+
+        // The absolute recovery paths are preserved when going through a descriptor, both under
+        // P2WSH and Taproot.
+        let abs_paths: BTreeMap<_, _> = [
+            (1_000_000, heir_key.clone()),
+            (1_893_456_000, other_heir_key.clone()),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+        for policy in [
+            LianaPolicy::new_legacy(
+                prim_key.clone(),
+                [(52560, reco_key.clone())].iter().cloned().collect(),
+            ),
+            LianaPolicy::new(
+                prim_key.clone(),
+                [(52560, reco_key.clone())].iter().cloned().collect(),
+            ),
+        ] {
+            let policy = policy
+                .unwrap()
+                .with_absolute_recovery_paths(abs_paths.clone())
+                .unwrap();
+            let desc = LianaDescriptor::new(policy);
+            let desc = LianaDescriptor::from_str(&desc.to_string()).unwrap();
+            assert_eq!(desc.policy().absolute_recovery_paths(), &abs_paths);
+            assert_eq!(desc.first_timelock_value(), 52560);
+        }
+
+        // A descriptor with only an absolute timelocked recovery path isn't a Liana descriptor.
+        LianaDescriptor::from_str("wsh(or_d(pk([abcdef01]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),and_v(v:pkh([abcdef01]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*),after(1893456000))))").unwrap_err();
+        let desc = LianaDescriptor::from_str("wsh(or_d(pk([abcdef01]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),or_i(and_v(v:pkh([abcdef01]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*),older(10000)),and_v(v:pkh([ffd63c8d/48'/1'/0'/2']tpubDExA3EC3iAsPxPhFn4j6gMiVup6V2eH3qKyk69RcTc9TTNRfFYVPad8bJD5FCHVQxyBT4izKsvr7Btd2R4xmQ1hZkvsqGBaeE82J71uTK4N/<0;1>/*),after(1893456000)))))").unwrap();
+        let policy = desc.policy();
+        assert_eq!(
+            policy.recovery_paths().keys().collect::<Vec<_>>(),
+            vec![&10000]
+        );
+        assert_eq!(
+            policy.absolute_recovery_paths().keys().collect::<Vec<_>>(),
+            vec![&1_893_456_000]
+        );
+
+        // The absolute recovery path is only available if the transaction's locktime is at least
+        // the path's and of the same unit, and the locktime is enabled by the input's nSequence.
+        let mut psbt = Psbt::from_unsigned_tx(bitcoin::Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::from_consensus(1_893_456_000),
+            input: vec![bitcoin::TxIn {
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                ..bitcoin::TxIn::default()
+            }],
+            output: vec![bitcoin::TxOut {
+                value: bitcoin::Amount::from_sat(10_000),
+                script_pubkey: bitcoin::ScriptBuf::new(),
+            }],
+        })
+        .unwrap();
+        let info = desc.partial_spend_info(&psbt).unwrap();
+        assert!(info.recovery_paths().is_empty());
+        let abs_info = info.absolute_recovery_paths().get(&1_893_456_000).unwrap();
+        assert_eq!(abs_info.threshold, 1);
+        assert_eq!(abs_info.sigs_count, 0);
+        psbt.unsigned_tx.lock_time = bitcoin::absolute::LockTime::from_consensus(1_893_456_001);
+        let info = desc.partial_spend_info(&psbt).unwrap();
+        assert!(info.absolute_recovery_paths().contains_key(&1_893_456_000));
+        psbt.unsigned_tx.lock_time = bitcoin::absolute::LockTime::from_consensus(1_893_455_999);
+        let info = desc.partial_spend_info(&psbt).unwrap();
+        assert!(info.absolute_recovery_paths().is_empty());
+        psbt.unsigned_tx.lock_time = bitcoin::absolute::LockTime::from_consensus(499_999_999);
+        let info = desc.partial_spend_info(&psbt).unwrap();
+        assert!(info.absolute_recovery_paths().is_empty());
+        psbt.unsigned_tx.lock_time = bitcoin::absolute::LockTime::from_consensus(1_893_456_000);
+        psbt.unsigned_tx.input[0].sequence = Sequence::MAX;
+        let info = desc.partial_spend_info(&psbt).unwrap();
+        assert!(info.absolute_recovery_paths().is_empty());
+    }
+
+    #[test]
+    fn time_timelock_recovery_paths() {
+        let secp = secp256k1::Secp256k1::signing_only();
+        let prim_key = PathInfo::Single(random_desc_key(&secp));
+        let reco_key = PathInfo::Single(random_desc_key(&secp));
+        let heir_key = PathInfo::Single(random_desc_key(&secp));
+        let other_heir_key = PathInfo::Single(random_desc_key(&secp));
+
+        // Miniscript requires a time-based relative timelock not to be null either.
+        assert!(matches!(
+            LianaPolicy::new(
+                prim_key.clone(),
+                [(52560, reco_key.clone())].iter().cloned().collect(),
+            )
+            .unwrap()
+            .with_time_recovery_paths([(0, heir_key.clone())].iter().cloned().collect()),
+            Err(LianaPolicyError::InsaneTimelock(0x00_40_00_00))
+        ));
+
+        // The time-based recovery paths are preserved when going through a descriptor, both under
+        // P2WSH and Taproot. Here ~90 and ~180 days.
+        let time_paths: BTreeMap<_, _> =
+            [(15188, heir_key.clone()), (30375, other_heir_key.clone())]
+                .iter()
+                .cloned()
+                .collect();
+        for policy in [
+            LianaPolicy::new_legacy(
+                prim_key.clone(),
+                [(52560, reco_key.clone())].iter().cloned().collect(),
+            ),
+            LianaPolicy::new(
+                prim_key.clone(),
+                [(52560, reco_key.clone())].iter().cloned().collect(),
+            ),
+        ] {
+            let policy = policy
+                .unwrap()
+                .with_time_recovery_paths(time_paths.clone())
+                .unwrap();
+            let desc = LianaDescriptor::new(policy);
+            assert!(desc.to_string().contains("older(4209492)"));
+            let desc = LianaDescriptor::from_str(&desc.to_string()).unwrap();
+            assert_eq!(desc.policy().time_recovery_paths(), &time_paths);
+            assert_eq!(desc.first_timelock_value(), 52560);
+        }
+
+        // A descriptor with only a time-based recovery path isn't a Liana descriptor.
+        LianaDescriptor::from_str("wsh(or_d(pk([abcdef01]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),and_v(v:pkh([abcdef01]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*),older(4209492))))").unwrap_err();
+        let desc = LianaDescriptor::from_str("wsh(or_d(pk([abcdef01]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),or_i(and_v(v:pkh([abcdef01]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*),older(10000)),and_v(v:pkh([ffd63c8d/48'/1'/0'/2']tpubDExA3EC3iAsPxPhFn4j6gMiVup6V2eH3qKyk69RcTc9TTNRfFYVPad8bJD5FCHVQxyBT4izKsvr7Btd2R4xmQ1hZkvsqGBaeE82J71uTK4N/<0;1>/*),older(4209492)))))").unwrap();
+        let policy = desc.policy();
+        assert_eq!(
+            policy.recovery_paths().keys().collect::<Vec<_>>(),
+            vec![&10000]
+        );
+        assert_eq!(
+            policy.time_recovery_paths().keys().collect::<Vec<_>>(),
+            vec![&15188]
+        );
+
+        // The time-based recovery path is only available if the input's nSequence is a time-based
+        // relative locktime at least as large as the path's.
+        let mut psbt = Psbt::from_unsigned_tx(bitcoin::Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn {
+                sequence: Sequence::from_512_second_intervals(15188),
+                ..bitcoin::TxIn::default()
+            }],
+            output: vec![bitcoin::TxOut {
+                value: bitcoin::Amount::from_sat(10_000),
+                script_pubkey: bitcoin::ScriptBuf::new(),
+            }],
+        })
+        .unwrap();
+        let info = desc.partial_spend_info(&psbt).unwrap();
+        assert!(info.recovery_paths().is_empty());
+        let time_info = info.time_recovery_paths().get(&15188).unwrap();
+        assert_eq!(time_info.threshold, 1);
+        assert_eq!(time_info.sigs_count, 0);
+        psbt.unsigned_tx.input[0].sequence = Sequence::from_512_second_intervals(15189);
+        let info = desc.partial_spend_info(&psbt).unwrap();
+        assert!(info.time_recovery_paths().contains_key(&15188));
+        psbt.unsigned_tx.input[0].sequence = Sequence::from_512_second_intervals(15187);
+        let info = desc.partial_spend_info(&psbt).unwrap();
+        assert!(info.time_recovery_paths().is_empty());
+        // A block-based nSequence of the same value only unlocks the block-based recovery path.
+        psbt.unsigned_tx.input[0].sequence = Sequence::from_height(15188);
+        let info = desc.partial_spend_info(&psbt).unwrap();
+        assert!(info.time_recovery_paths().is_empty());
+        assert!(info.recovery_paths().contains_key(&10000));
+    }
+
+    #[test]
+    fn nested_thresholds() {
+        let secp = secp256k1::Secp256k1::signing_only();
+        let (cfo, ceo) = (random_desc_key(&secp), random_desc_key(&secp));
+        let board: Vec<_> = (0..3).map(|_| random_desc_key(&secp)).collect();
+        let reco_key = random_desc_key(&secp);
+        let reco_paths: BTreeMap<_, _> = [(52560, PathInfo::Single(reco_key.clone()))]
+            .iter()
+            .cloned()
+            .collect();
+
+        // 2 of (CFO, CEO, 2 of the board members) as primary path, under both P2WSH and Taproot.
+        // The nested threshold is preserved through the descriptor.
+        let prim_path = PathInfo::Thresh(
+            2,
+            vec![
+                PathInfo::Single(cfo.clone()),
+                PathInfo::Single(ceo.clone()),
+                PathInfo::Multi(2, board.clone()),
+            ],
+        );
+        for policy in [
+            LianaPolicy::new_legacy(prim_path.clone(), reco_paths.clone()),
+            LianaPolicy::new(prim_path.clone(), reco_paths.clone()),
+        ] {
+            let policy = policy.unwrap();
+            assert!(matches!(policy.primary_path(), PathInfo::Thresh(2, subs) if subs.len() == 3));
+            let desc = LianaDescriptor::new(policy.clone());
+            let desc = LianaDescriptor::from_str(&desc.to_string()).unwrap();
+            assert_eq!(desc.policy().primary_path(), policy.primary_path());
+            assert!(desc.policy().primary_path().is_nested());
+            assert_eq!(desc.policy().primary_path().keys().len(), 5);
+        }
+
+        // The same goes for a nested threshold in a recovery path, including when it is normalized
+        // along with the timelock (here an and() of the CFO and 1 of the board members), and for a
+        // 1-of-N primary path which is normalized along with the recovery paths.
+        let nested_reco_path = PathInfo::Thresh(
+            2,
+            vec![
+                PathInfo::Single(cfo.clone()),
+                PathInfo::Multi(1, board.clone()),
+            ],
+        );
+        let nested_prim_path = PathInfo::Thresh(
+            1,
+            vec![
+                PathInfo::Single(ceo.clone()),
+                PathInfo::Multi(2, board.clone()),
+            ],
+        );
+        for policy in [
+            LianaPolicy::new_legacy(
+                PathInfo::Single(ceo.clone()),
+                [(52560, nested_reco_path.clone())]
+                    .iter()
+                    .cloned()
+                    .collect(),
+            ),
+            LianaPolicy::new(nested_prim_path.clone(), reco_paths.clone()),
+        ] {
+            let policy = policy.unwrap();
+            let desc = LianaDescriptor::new(policy.clone());
+            let desc = LianaDescriptor::from_str(&desc.to_string()).unwrap();
+            assert_eq!(desc.policy(), policy);
+        }
+        assert_eq!(
+            LianaDescriptor::new(
+                LianaPolicy::new_legacy(
+                    PathInfo::Single(ceo.clone()),
+                    [(52560, nested_reco_path.clone())]
+                        .iter()
+                        .cloned()
+                        .collect(),
+                )
+                .unwrap()
+            )
+            .policy()
+            .recovery_paths()[&52560],
+            nested_reco_path
+        );
+
+        // Nested thresholds which don't change the semantic are normalized.
+        let policy = LianaPolicy::new(
+            PathInfo::Thresh(
+                2,
+                vec![
+                    PathInfo::Single(cfo.clone()),
+                    PathInfo::Multi(2, board[..2].to_vec()),
+                ],
+            ),
+            reco_paths.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            policy.primary_path(),
+            &PathInfo::Multi(3, vec![cfo.clone(), board[0].clone(), board[1].clone()])
+        );
+        let policy = LianaPolicy::new(
+            PathInfo::Thresh(
+                1,
+                vec![
+                    PathInfo::Thresh(1, vec![PathInfo::Single(cfo.clone())]),
+                    PathInfo::Multi(1, board[..2].to_vec()),
+                ],
+            ),
+            reco_paths.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            policy.primary_path(),
+            &PathInfo::Multi(1, vec![cfo.clone(), board[0].clone(), board[1].clone()])
+        );
+
+        // The thresholds must be between 1 and the number of sub-paths.
+        for k in [0, 3] {
+            assert!(matches!(
+                LianaPolicy::new(
+                    PathInfo::Thresh(
+                        k,
+                        vec![PathInfo::Single(cfo.clone()), PathInfo::Multi(2, board.clone())]
+                    ),
+                    reco_paths.clone(),
+                ),
+                Err(LianaPolicyError::InvalidMultiThresh(t)) if t == k
+            ));
+        }
+
+        // A signer can't be used twice within the same path, even in different sub-thresholds.
+        let signer = HotSigner::generate(bitcoin::Network::Bitcoin).unwrap();
+        let signer_key = |account: &str| {
+            let der_path = bip32::DerivationPath::from_str(account).unwrap();
+            descriptor::DescriptorPublicKey::from_str(&format!(
+                "[{}/{}]{}/<0;1>/*",
+                signer.fingerprint(&secp),
+                account.trim_start_matches("m/"),
+                signer.xpub_at(&der_path, &secp)
+            ))
+            .unwrap()
+        };
+        assert!(matches!(
+            LianaPolicy::new(
+                PathInfo::Thresh(
+                    2,
+                    vec![
+                        PathInfo::Single(signer_key("m/0'")),
+                        PathInfo::Multi(1, vec![signer_key("m/1'"), cfo.clone()]),
+                        PathInfo::Single(ceo.clone()),
+                    ]
+                ),
+                reco_paths.clone(),
+            ),
+            Err(LianaPolicyError::DuplicateOriginSamePath(_))
+        ));
+
+        // The spend info reports the progress of each of the sub-paths. The CFO signed, as well as
+        // 2 of the board members: the path is complete.
+        let origin = |key: &descriptor::DescriptorPublicKey, der_path: &str| {
+            (
+                key.master_fingerprint(),
+                bip32::DerivationPath::from_str(der_path).unwrap(),
+            )
+        };
+        let pubkeys_signed = [origin(&cfo, "m/0/12"), origin(&board[0], "m/0/12")];
+        let info = prim_path.spend_info(pubkeys_signed.iter());
+        assert_eq!((info.threshold, info.sigs_count), (2, 1));
+        assert_eq!(info.sub_paths.len(), 3);
+        assert_eq!(info.sub_paths[0].sigs_count, 1);
+        assert_eq!(info.sub_paths[1].sigs_count, 0);
+        assert_eq!(
+            (info.sub_paths[2].threshold, info.sub_paths[2].sigs_count),
+            (2, 1)
+        );
+        let pubkeys_signed = [
+            origin(&cfo, "m/0/12"),
+            origin(&board[0], "m/0/12"),
+            origin(&board[2], "m/1/3"),
+        ];
+        let info = prim_path.spend_info(pubkeys_signed.iter());
+        assert_eq!((info.threshold, info.sigs_count), (2, 2));
+        assert_eq!(info.signed_pubkeys.len(), 3);
+        assert!(!info.signed_pubkeys.contains_key(&ceo.master_fingerprint()));
+    }
+
+    #[test]
+    fn musig_primary_path() {
+        let secp = secp256k1::Secp256k1::new();
+        let (key_a, key_b) = (random_desc_key(&secp), random_desc_key(&secp));
+        let prim_keys = PathInfo::Multi(2, vec![key_a.clone(), key_b.clone()]);
+        let reco_key = PathInfo::Single(random_desc_key(&secp));
+        let reco_paths: BTreeMap<_, _> = [(52560, reco_key)].iter().cloned().collect();
+
+        // Only the primary keys of a Taproot policy may be aggregated.
+        assert!(matches!(
+            LianaPolicy::new_legacy(prim_keys.clone(), reco_paths.clone())
+                .unwrap()
+                .with_musig_primary_path(),
+            Err(LianaPolicyError::InvalidMusigPrimaryPath)
+        ));
+        // The primary path must require the signatures of all its keys.
+        assert!(matches!(
+            LianaPolicy::new(
+                PathInfo::Multi(1, vec![key_a.clone(), key_b.clone()]),
+                reco_paths.clone()
+            )
+            .unwrap()
+            .with_musig_primary_path(),
+            Err(LianaPolicyError::InvalidMusigPrimaryPath)
+        ));
+        // The keys must not have any derivation step besides the multipath one.
+        let deep_key = descriptor::DescriptorPublicKey::from_str(
+            &key_b.to_string().replace("/<0;1>/*", "/0/<0;1>/*"),
+        )
+        .unwrap();
+        assert!(matches!(
+            LianaPolicy::new(
+                PathInfo::Multi(2, vec![key_a.clone(), deep_key]),
+                reco_paths.clone()
+            )
+            .unwrap()
+            .with_musig_primary_path(),
+            Err(LianaPolicyError::InvalidMusigPrimaryPath)
+        ));
+
+        // The aggregate key is the internal key of the descriptor, and the participants are
+        // preserved through its string representation.
+        let policy = LianaPolicy::new(prim_keys, reco_paths.clone())
+            .unwrap()
+            .with_musig_primary_path()
+            .unwrap();
+        let desc = LianaDescriptor::new(policy);
+        let desc_str = desc.to_string();
+        assert!(desc_str.starts_with("tr(musig("));
+        let musig_key = desc.musig_primary_key().unwrap();
+        assert_eq!(musig_key.participants().len(), 2);
+        assert!(musig_key.participants().contains(&key_a));
+        assert!(musig_key.participants().contains(&key_b));
+        let parsed_desc = LianaDescriptor::from_str(&desc_str).unwrap();
+        assert_eq!(parsed_desc.to_string(), desc_str);
+        assert_eq!(parsed_desc, desc);
+        assert!(matches!(
+            parsed_desc.policy().primary_path(),
+            PathInfo::Multi(2, _)
+        ));
+        // The checksum is over the string containing the musig() expression.
+        LianaDescriptor::from_str(&desc_str.replace("tr(musig(", "tr( musig(")).unwrap_err();
+        assert!(desc.contains_fingerprint_in_primary_path(key_a.master_fingerprint()));
+        let spendable_keys = desc.spendable_keys();
+        assert_eq!(spendable_keys.len(), 3);
+        assert!(spendable_keys
+            .iter()
+            .any(|k| k.master_fingerprint() == key_b.master_fingerprint()));
+
+        // The participants are set in the PSBT input, and the spend info for the primary path
+        // accounts for their partial signatures.
+        let der_desc = desc.receive_descriptor().derive(0.into(), &secp);
+        let mut psbt_in = PsbtIn::default();
+        der_desc.update_psbt_in(&mut psbt_in);
+        desc.update_musig_psbt_in(&mut psbt_in);
+        let (_, participants) = musig::psbt_in_participants(&psbt_in).unwrap();
+        assert_eq!(participants, musig_key.participant_pubkeys());
+        // The internal key is the derived aggregate key.
+        musig::psbt_in_key_agg_ctx(&psbt_in, &participants, &secp).unwrap();
+        let info = desc.partial_spend_info_txin(
+            &psbt_in,
+            &bitcoin::TxIn::default(),
+            bitcoin::absolute::LockTime::ZERO,
+        );
+        assert_eq!(info.primary_path().threshold, 2);
+        assert_eq!(info.primary_path().sigs_count, 0);
+
+        // Merging the nonces and partial signatures of another copy of the PSBT input only adds
+        // those we don't have, and fails on a different value for one we have.
+        let session_key = |type_value, key: u8| bitcoin::psbt::raw::Key {
+            type_value,
+            key: vec![key; 66],
+        };
+        let nonce_a = session_key(musig::PSBT_IN_MUSIG2_PUB_NONCE, 0xaa);
+        let nonce_b = session_key(musig::PSBT_IN_MUSIG2_PUB_NONCE, 0xbb);
+        let sig_a = session_key(musig::PSBT_IN_MUSIG2_PARTIAL_SIG, 0xaa);
+        psbt_in.unknown.insert(nonce_a.clone(), vec![1; 66]);
+        let mut other_psbt_in = psbt_in.clone();
+        other_psbt_in.unknown.insert(nonce_b.clone(), vec![2; 66]);
+        other_psbt_in.unknown.insert(sig_a.clone(), vec![3; 32]);
+        musig::merge_session_fields(&mut psbt_in, &other_psbt_in).unwrap();
+        assert_eq!(psbt_in, other_psbt_in);
+        other_psbt_in.unknown.insert(nonce_a.clone(), vec![4; 66]);
+        other_psbt_in.unknown.insert(
+            session_key(musig::PSBT_IN_MUSIG2_PUB_NONCE, 0xcc),
+            vec![5; 66],
+        );
+        let before = psbt_in.clone();
+        assert_eq!(
+            musig::merge_session_fields(&mut psbt_in, &other_psbt_in),
+            Err(musig::SessionFieldConflict(nonce_a))
+        );
+        assert_eq!(psbt_in, before);
+
+        // A smaller satisfaction than with a multi_a() in a leaf.
+        let multi_desc = LianaDescriptor::new(
+            LianaPolicy::new(
+                PathInfo::Multi(2, vec![key_a.clone(), key_b.clone()]),
+                reco_paths,
+            )
+            .unwrap(),
+        );
+        assert!(desc.max_sat_weight(true) < multi_desc.max_sat_weight(true));
+    }
+
     #[test]
     fn inheritance_descriptor_sat_size() {
         let desc = LianaDescriptor::from_str("wsh(or_d(pk([92162c45]tpubD6NzVbkrYhZ4WzTf9SsD6h7AH7oQEippXK2KP8qvhMMqFoNeN5YFVi7vRyeRSDGtgd2bPyMxUNmHui8t5yCgszxPPxMafu1VVzDpg9aruYW/<0;1>/*),and_v(v:pkh([abcdef01]tpubD6NzVbkrYhZ4Wdgu2yfdmrce5g4fiH1ZLmKhewsnNKupbi4sxjH1ZVAorkBLWSkhsjhg8kiq8C4BrBjMy3SjAKDyDdbuvUa1ToAHbiR98js/<0;1>/*),older(2))))#ravw7jw5").unwrap();
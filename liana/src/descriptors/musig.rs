@@ -0,0 +1,548 @@
+//! MuSig2 aggregation of the primary path keys into the Taproot internal key.
+//!
+//! The aggregate key is represented in the descriptor as a `musig()` key expression (BIP390). It
+//! is derived using the synthetic xpub defined in BIP328, and the signing sessions are coordinated
+//! through the PSBT input fields defined in BIP373.
+
+use crate::descriptors::analysis::{LianaPolicyError, PathSpendInfo};
+
+use miniscript::{
+    bitcoin::{
+        self, bip32,
+        hashes::{hex::DisplayHex, Hash},
+        psbt::{raw, Input as PsbtIn},
+        secp256k1,
+        sighash::TapSighashType,
+        taproot,
+    },
+    descriptor::{self, checksum, DescriptorPublicKey},
+};
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    str::{self, FromStr},
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// The chain code of the synthetic xpub for an aggregate key, as defined in BIP328.
+const MUSIG_CHAINCODE: [u8; 32] = [
+    0x86, 0x80, 0x87, 0xca, 0x02, 0xa6, 0xf9, 0x74, 0xc4, 0x59, 0x89, 0x24, 0xc3, 0x6b, 0x57, 0x76,
+    0x2d, 0x32, 0xcb, 0x45, 0x71, 0x71, 0x67, 0xe3, 0x00, 0x62, 0x2c, 0x71, 0x67, 0xe3, 0x89, 0x65,
+];
+
+/// The PSBT input field containing the participants to an aggregate key, keyed by the aggregate
+/// key (BIP373).
+pub const PSBT_IN_MUSIG2_PARTICIPANT_PUBKEYS: u8 = 0x1a;
+/// The PSBT input field containing the public nonce of a participant, keyed by the participant's
+/// key and the aggregate key (BIP373).
+pub const PSBT_IN_MUSIG2_PUB_NONCE: u8 = 0x1b;
+/// The PSBT input field containing the partial signature of a participant, keyed by the
+/// participant's key and the aggregate key (BIP373).
+pub const PSBT_IN_MUSIG2_PARTIAL_SIG: u8 = 0x1c;
+
+// The synthetic xpub for this aggregate key, as defined in BIP328.
+fn synthetic_xpub(
+    aggregate_key: secp256k1::PublicKey,
+    network: bitcoin::NetworkKind,
+) -> bip32::Xpub {
+    bip32::Xpub {
+        network,
+        depth: 0,
+        parent_fingerprint: [0; 4].into(),
+        child_number: 0.into(),
+        public_key: aggregate_key,
+        chain_code: MUSIG_CHAINCODE.into(),
+    }
+}
+
+// Compute the checksum of a descriptor string.
+fn desc_checksum(desc: &str) -> Result<String, miniscript::Error> {
+    let mut engine = checksum::Engine::new();
+    engine.input(desc)?;
+    Ok(engine.checksum())
+}
+
+/// The keys of an N-of-N primary path, aggregated using MuSig2 into the internal key of a Taproot
+/// descriptor. Spending through the primary path then only requires a single signature on the key
+/// path, which does not reveal the spending policy.
+///
+/// The participants' keys must all have the same derivation, which is applied to the aggregate key
+/// instead. That is, all keys must be of the form `[aaff0099]xpub.../<0;1>/*`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MusigPrimaryKey {
+    // Sorted by public key, as per the KeySort algorithm from BIP327.
+    participants: Vec<DescriptorPublicKey>,
+}
+
+// Get the multipath xpub of a participant's key. Only call this on keys that were checked when
+// creating the `MusigPrimaryKey`.
+fn participant_xkey(key: &DescriptorPublicKey) -> &descriptor::DescriptorMultiXKey<bip32::Xpub> {
+    match key {
+        DescriptorPublicKey::MultiXPub(xkey) => xkey,
+        _ => unreachable!("Checked when creating the MusigPrimaryKey."),
+    }
+}
+
+impl MusigPrimaryKey {
+    /// Create an aggregate key from the keys of a primary path. The keys must all be multipath
+    /// xpubs with an origin, and share the same (single) derivation step before the wildcard.
+    pub fn new(mut keys: Vec<DescriptorPublicKey>) -> Result<Self, LianaPolicyError> {
+        if keys.len() < 2 {
+            return Err(LianaPolicyError::InvalidMusigPrimaryPath);
+        }
+        let (der_paths, network) = match keys[0] {
+            DescriptorPublicKey::MultiXPub(ref xkey) => {
+                (xkey.derivation_paths.clone(), xkey.xkey.network)
+            }
+            _ => return Err(LianaPolicyError::InvalidMusigPrimaryPath),
+        };
+        for key in &keys {
+            let valid = match key {
+                DescriptorPublicKey::MultiXPub(xkey) => {
+                    xkey.origin.is_some()
+                        && xkey.wildcard == descriptor::Wildcard::Unhardened
+                        && xkey.derivation_paths == der_paths
+                        && xkey.derivation_paths.paths().iter().all(|p| p.len() == 1)
+                        && xkey.xkey.network == network
+                }
+                _ => false,
+            };
+            if !valid {
+                return Err(LianaPolicyError::InvalidMusigPrimaryPath);
+            }
+        }
+        keys.sort_by_key(|key| participant_xkey(key).xkey.public_key.serialize());
+
+        Ok(Self { participants: keys })
+    }
+
+    /// The keys of the participants, in aggregation order.
+    pub fn participants(&self) -> &[DescriptorPublicKey] {
+        &self.participants
+    }
+
+    /// The public keys of the participants, in aggregation order.
+    pub fn participant_pubkeys(&self) -> Vec<secp256k1::PublicKey> {
+        self.participants
+            .iter()
+            .map(|key| participant_xkey(key).xkey.public_key)
+            .collect()
+    }
+
+    /// The synthetic xpub for the aggregate of the participants' keys (BIP328).
+    pub fn aggregate_xpub(&self) -> bip32::Xpub {
+        let key_agg_ctx = musig2::KeyAggContext::new(self.participant_pubkeys())
+            .expect("Aggregate of distinct valid keys can't be the point at infinity.");
+        let network = participant_xkey(&self.participants[0]).xkey.network;
+        synthetic_xpub(key_agg_ctx.aggregated_pubkey(), network)
+    }
+
+    /// The aggregate key, with the derivation of the participants' keys, as it appears in the
+    /// descriptor.
+    pub fn aggregate_key(&self) -> DescriptorPublicKey {
+        let first_key = participant_xkey(&self.participants[0]);
+        DescriptorPublicKey::MultiXPub(descriptor::DescriptorMultiXKey {
+            origin: None,
+            xkey: self.aggregate_xpub(),
+            derivation_paths: first_key.derivation_paths.clone(),
+            wildcard: descriptor::Wildcard::Unhardened,
+        })
+    }
+
+    /// The fingerprint and derivation paths (without the wildcard step) of the aggregate key.
+    pub fn aggregate_origins(&self) -> (bip32::Fingerprint, HashSet<bip32::DerivationPath>) {
+        let first_key = participant_xkey(&self.participants[0]);
+        (
+            self.aggregate_xpub().fingerprint(),
+            first_key.derivation_paths.paths().iter().cloned().collect(),
+        )
+    }
+
+    /// Whether one of the participants is derived from this master fingerprint.
+    pub fn contains_fingerprint(&self, fingerprint: bip32::Fingerprint) -> bool {
+        self.participants
+            .iter()
+            .any(|key| key.master_fingerprint() == fingerprint)
+    }
+
+    /// Record the participants to the aggregate key and their origins in this PSBT input, for them
+    /// to be able to take part in the signing session.
+    pub fn update_psbt_in(&self, psbt_in: &mut PsbtIn) {
+        let participants = self.participant_pubkeys();
+        psbt_in.unknown.insert(
+            raw::Key {
+                type_value: PSBT_IN_MUSIG2_PARTICIPANT_PUBKEYS,
+                key: self.aggregate_xpub().public_key.serialize().to_vec(),
+            },
+            participants.iter().flat_map(|pk| pk.serialize()).collect(),
+        );
+        for key in &self.participants {
+            let xkey = participant_xkey(key);
+            let origin = xkey.origin.clone().expect("Checked at creation.");
+            psbt_in.tap_key_origins.insert(
+                xkey.xkey.public_key.x_only_public_key().0,
+                (Vec::new(), origin),
+            );
+        }
+    }
+
+    /// Get the spend information for the aggregate key from the partial signatures in this PSBT
+    /// input. If they were already aggregated into a key path signature, all participants are
+    /// accounted as having signed.
+    pub fn spend_info(&self, psbt_in: &PsbtIn) -> PathSpendInfo {
+        let complete = psbt_in.tap_key_sig.is_some();
+        let mut signed_pubkeys = HashMap::new();
+        for key in &self.participants {
+            let pubkey = participant_xkey(key).xkey.public_key.serialize();
+            let signed = complete
+                || psbt_in.unknown.keys().any(|k| {
+                    k.type_value == PSBT_IN_MUSIG2_PARTIAL_SIG && k.key.starts_with(&pubkey)
+                });
+            if signed {
+                *signed_pubkeys.entry(key.master_fingerprint()).or_insert(0) += 1;
+            }
+        }
+
+        PathSpendInfo {
+            threshold: self.participants.len(),
+            sigs_count: signed_pubkeys.values().sum(),
+            signed_pubkeys,
+            sub_paths: Vec::new(),
+        }
+    }
+
+    /// Get the string representation of this descriptor, with the aggregate key as a `musig()`
+    /// key expression.
+    pub(super) fn descriptor_string(
+        &self,
+        desc: &descriptor::Descriptor<DescriptorPublicKey>,
+    ) -> String {
+        let desc_str = desc.to_string();
+        let payload = desc_str
+            .split_once('#')
+            .map(|(payload, _)| payload)
+            .unwrap_or(&desc_str);
+        let payload = payload.replacen(&self.aggregate_key().to_string(), &self.to_string(), 1);
+        let checksum = desc_checksum(&payload).expect("Only contains valid characters.");
+        format!("{}#{}", payload, checksum)
+    }
+
+    /// Parse a descriptor string which may contain a `musig()` key expression. If it does, returns
+    /// the descriptor string with the aggregate key in its place (and without checksum) along with
+    /// the aggregate key.
+    pub(super) fn from_descriptor_str(
+        s: &str,
+    ) -> Result<Option<(String, MusigPrimaryKey)>, LianaPolicyError> {
+        let start = match s.find("musig(") {
+            Some(start) => start,
+            None => return Ok(None),
+        };
+
+        // The checksum, if present, is for the descriptor containing the musig() expression.
+        let payload = match s.split_once('#') {
+            Some((payload, checksum)) => {
+                let expected = desc_checksum(payload)
+                    .map_err(|e| LianaPolicyError::InvalidMusigExpression(e.to_string()))?;
+                if checksum != expected {
+                    return Err(LianaPolicyError::InvalidMusigExpression(format!(
+                        "invalid checksum '{}', expected '{}'",
+                        checksum, expected
+                    )));
+                }
+                payload
+            }
+            None => s,
+        };
+
+        // The key expression spans until the end of the participants list, followed by the
+        // derivation steps up to the next argument or the end of the descriptor.
+        let expr = &payload[start..];
+        let keys_end = expr.find(')').ok_or_else(|| {
+            LianaPolicyError::InvalidMusigExpression("unterminated key list".to_string())
+        })?;
+        let expr_len = expr[keys_end + 1..]
+            .find([',', ')'])
+            .map(|i| keys_end + 1 + i)
+            .unwrap_or(expr.len());
+        let musig_key = MusigPrimaryKey::from_str(&expr[..expr_len])?;
+
+        Ok(Some((
+            format!(
+                "{}{}{}",
+                &payload[..start],
+                musig_key.aggregate_key(),
+                &payload[start + expr_len..]
+            ),
+            musig_key,
+        )))
+    }
+}
+
+impl fmt::Display for MusigPrimaryKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "musig(")?;
+        for (i, key) in self.participants.iter().enumerate() {
+            let xkey = participant_xkey(key);
+            let key = DescriptorPublicKey::XPub(descriptor::DescriptorXKey {
+                origin: xkey.origin.clone(),
+                xkey: xkey.xkey,
+                derivation_path: bip32::DerivationPath::master(),
+                wildcard: descriptor::Wildcard::None,
+            });
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", key)?;
+        }
+        // The derivation steps of the aggregate key follow the base58-encoded xpub.
+        let aggregate_key = self.aggregate_key().to_string();
+        let derivation = aggregate_key
+            .find('/')
+            .map(|i| &aggregate_key[i..])
+            .unwrap_or("");
+        write!(f, "){}", derivation)
+    }
+}
+
+impl str::FromStr for MusigPrimaryKey {
+    type Err = LianaPolicyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (keys, derivation) = s
+            .strip_prefix("musig(")
+            .and_then(|s| s.split_once(')'))
+            .ok_or_else(|| LianaPolicyError::InvalidMusigExpression(s.to_string()))?;
+        let keys = keys
+            .split(',')
+            .map(|key| {
+                DescriptorPublicKey::from_str(&format!("{}{}", key, derivation))
+                    .map_err(|e| LianaPolicyError::InvalidMusigExpression(e.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        MusigPrimaryKey::new(keys)
+    }
+}
+
+impl Serialize for MusigPrimaryKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for MusigPrimaryKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        MusigPrimaryKey::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Get the aggregate key and the keys of its participants recorded in this PSBT input, if any.
+pub fn psbt_in_participants(
+    psbt_in: &PsbtIn,
+) -> Option<(secp256k1::PublicKey, Vec<secp256k1::PublicKey>)> {
+    psbt_in.unknown.iter().find_map(|(key, value)| {
+        if key.type_value != PSBT_IN_MUSIG2_PARTICIPANT_PUBKEYS || value.len() % 33 != 0 {
+            return None;
+        }
+        let aggregate_key = secp256k1::PublicKey::from_slice(&key.key).ok()?;
+        let participants = value
+            .chunks(33)
+            .map(|pk| secp256k1::PublicKey::from_slice(pk).ok())
+            .collect::<Option<Vec<_>>>()?;
+        Some((aggregate_key, participants))
+    })
+}
+
+/// Get the key aggregation context for a key path spend of this PSBT input. That is, the
+/// aggregate of the participants' keys tweaked with the BIP32 derivation of the internal key
+/// (BIP328) and with the Taproot commitment.
+///
+/// Also returns the internal key, which identifies the signing session in the nonces and partial
+/// signatures fields. Returns `None` if the internal key isn't derived from the aggregate of these
+/// participants' keys.
+pub fn psbt_in_key_agg_ctx(
+    psbt_in: &PsbtIn,
+    participants: &[secp256k1::PublicKey],
+    secp: &secp256k1::Secp256k1<impl secp256k1::Verification>,
+) -> Option<(secp256k1::PublicKey, musig2::KeyAggContext)> {
+    let internal_key = psbt_in.tap_internal_key?;
+    let (_, (_, der_path)) = psbt_in.tap_key_origins.get(&internal_key)?;
+
+    // Apply the derivation of the synthetic xpub as plain tweaks to the aggregate key.
+    let mut key_agg_ctx = musig2::KeyAggContext::new(participants.iter().copied()).ok()?;
+    let mut xpub = synthetic_xpub(key_agg_ctx.aggregated_pubkey(), bitcoin::NetworkKind::Main);
+    for child in der_path {
+        let (tweak, _) = xpub.ckd_pub_tweak(*child).ok()?;
+        key_agg_ctx = key_agg_ctx.with_plain_tweak(tweak).ok()?;
+        xpub = xpub.ckd_pub(secp, *child).ok()?;
+    }
+    let derived_key: secp256k1::PublicKey = key_agg_ctx.aggregated_pubkey();
+    if derived_key.x_only_public_key().0 != internal_key {
+        return None;
+    }
+
+    let key_agg_ctx = match psbt_in.tap_merkle_root {
+        Some(merkle_root) => key_agg_ctx
+            .with_taproot_tweak(&merkle_root.to_byte_array())
+            .ok()?,
+        None => key_agg_ctx.with_unspendable_taproot_tweak().ok()?,
+    };
+    Some((derived_key, key_agg_ctx))
+}
+
+// The key of a participant's nonce or partial signature field for the given aggregate key.
+fn session_key(
+    type_value: u8,
+    participant: &secp256k1::PublicKey,
+    aggregate_key: &secp256k1::PublicKey,
+) -> raw::Key {
+    raw::Key {
+        type_value,
+        key: participant
+            .serialize()
+            .iter()
+            .chain(aggregate_key.serialize().iter())
+            .copied()
+            .collect(),
+    }
+}
+
+/// Whether this PSBT input field is part of a signing session, i.e. a nonce or partial signature.
+pub fn is_session_field(key: &raw::Key) -> bool {
+    key.type_value == PSBT_IN_MUSIG2_PUB_NONCE || key.type_value == PSBT_IN_MUSIG2_PARTIAL_SIG
+}
+
+/// A signing session field of a PSBT input which was given two different values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionFieldConflict(pub raw::Key);
+
+impl fmt::Display for SessionFieldConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Conflicting values for the MuSig2 session field of type {:#04x} and key {}",
+            self.0.type_value,
+            self.0.key.to_lower_hex_string(),
+        )
+    }
+}
+
+impl std::error::Error for SessionFieldConflict {}
+
+/// Merge the signing session fields, i.e. the nonces and partial signatures, of `other` into
+/// this PSBT input. The fields already present are never replaced: a participant's nonce must not
+/// change once it was used to sign. A field present in both with different values is an error, in
+/// which case this PSBT input is left untouched.
+pub fn merge_session_fields(
+    psbt_in: &mut PsbtIn,
+    other: &PsbtIn,
+) -> Result<(), SessionFieldConflict> {
+    let new_fields: Vec<_> = other
+        .unknown
+        .iter()
+        .filter(|(k, _)| is_session_field(k))
+        .filter_map(|(k, v)| match psbt_in.unknown.get(k) {
+            None => Some(Ok((k.clone(), v.clone()))),
+            Some(existing) if existing == v => None,
+            Some(_) => Some(Err(SessionFieldConflict(k.clone()))),
+        })
+        .collect::<Result<_, _>>()?;
+    psbt_in.unknown.extend(new_fields);
+    Ok(())
+}
+
+/// Get the public nonce of this participant for this aggregate key.
+pub fn pub_nonce(
+    psbt_in: &PsbtIn,
+    participant: &secp256k1::PublicKey,
+    aggregate_key: &secp256k1::PublicKey,
+) -> Option<musig2::PubNonce> {
+    let key = session_key(PSBT_IN_MUSIG2_PUB_NONCE, participant, aggregate_key);
+    psbt_in
+        .unknown
+        .get(&key)
+        .and_then(|nonce| musig2::PubNonce::from_bytes(nonce).ok())
+}
+
+/// Set the public nonce of this participant for this aggregate key.
+pub fn insert_pub_nonce(
+    psbt_in: &mut PsbtIn,
+    participant: &secp256k1::PublicKey,
+    aggregate_key: &secp256k1::PublicKey,
+    pub_nonce: &musig2::PubNonce,
+) {
+    let key = session_key(PSBT_IN_MUSIG2_PUB_NONCE, participant, aggregate_key);
+    psbt_in.unknown.insert(key, pub_nonce.serialize().to_vec());
+}
+
+/// Get the partial signature of this participant for this aggregate key.
+pub fn partial_sig(
+    psbt_in: &PsbtIn,
+    participant: &secp256k1::PublicKey,
+    aggregate_key: &secp256k1::PublicKey,
+) -> Option<musig2::PartialSignature> {
+    let key = session_key(PSBT_IN_MUSIG2_PARTIAL_SIG, participant, aggregate_key);
+    psbt_in
+        .unknown
+        .get(&key)
+        .and_then(|sig| musig2::PartialSignature::from_slice(sig).ok())
+}
+
+/// Set the partial signature of this participant for this aggregate key.
+pub fn insert_partial_sig(
+    psbt_in: &mut PsbtIn,
+    participant: &secp256k1::PublicKey,
+    aggregate_key: &secp256k1::PublicKey,
+    partial_sig: &musig2::PartialSignature,
+) {
+    let key = session_key(PSBT_IN_MUSIG2_PARTIAL_SIG, participant, aggregate_key);
+    psbt_in
+        .unknown
+        .insert(key, partial_sig.serialize().to_vec());
+}
+
+/// If all participants provided their partial signature, aggregate them into the key path
+/// signature for this PSBT input. Returns whether the signature was set.
+pub fn aggregate_partial_sigs(
+    psbt_in: &mut PsbtIn,
+    participants: &[secp256k1::PublicKey],
+    aggregate_key: &secp256k1::PublicKey,
+    key_agg_ctx: &musig2::KeyAggContext,
+    sighash: &[u8; 32],
+) -> bool {
+    let pub_nonces = participants
+        .iter()
+        .map(|pk| pub_nonce(psbt_in, pk, aggregate_key))
+        .collect::<Option<Vec<_>>>();
+    let partial_sigs = participants
+        .iter()
+        .map(|pk| partial_sig(psbt_in, pk, aggregate_key))
+        .collect::<Option<Vec<_>>>();
+    let (pub_nonces, partial_sigs) = match (pub_nonces, partial_sigs) {
+        (Some(pub_nonces), Some(partial_sigs)) => (pub_nonces, partial_sigs),
+        _ => return false,
+    };
+
+    let agg_nonce = musig2::AggNonce::sum(&pub_nonces);
+    let signature: musig2::LiftedSignature = match musig2::aggregate_partial_signatures(
+        key_agg_ctx,
+        &agg_nonce,
+        partial_sigs,
+        sighash,
+    ) {
+        Ok(sig) => sig,
+        Err(e) => {
+            log::error!("Invalid partial signatures for aggregate key: {}", e);
+            return false;
+        }
+    };
+    let signature = secp256k1::schnorr::Signature::from_slice(&signature.serialize())
+        .expect("Valid BIP340 signature.");
+    psbt_in.tap_key_sig = Some(taproot::Signature {
+        signature,
+        sighash_type: TapSighashType::Default,
+    });
+    true
+}
@@ -1,3 +1,4 @@
+pub mod bsms;
 pub mod descriptors;
 pub mod random;
 pub mod signer;
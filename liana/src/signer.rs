@@ -3,16 +3,22 @@
 //! Some helpers to facilitate the usage of a signer in client of the Liana daemon. For now
 //! only contains a hot signer.
 
-use crate::random;
+use crate::{descriptors::musig, random};
 
 use std::{
+    collections::HashMap,
     convert::TryInto,
     error, fmt, fs,
     io::{self, Write},
     path,
     str::FromStr,
+    sync,
 };
 
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
 use miniscript::bitcoin::{
     self,
     bip32::{self, Error as Bip32Error, Fingerprint},
@@ -30,6 +36,8 @@ pub enum SignerError {
     Mnemonic(bip39::Error),
     Bip32(Bip32Error),
     MnemonicStorage(io::Error),
+    InvalidEncryptedMnemonic(String),
+    MnemonicDecryption,
     InsanePsbt,
     IncompletePsbt,
 }
@@ -41,6 +49,11 @@ impl fmt::Display for SignerError {
             Self::Mnemonic(s) => write!(f, "Error when working with mnemonics: {}", s),
             Self::Bip32(e) => write!(f, "BIP32 error: {}", e),
             Self::MnemonicStorage(e) => write!(f, "BIP39 mnemonic storage error: {}", e),
+            Self::InvalidEncryptedMnemonic(s) => write!(f, "Invalid encrypted mnemonic: {}", s),
+            Self::MnemonicDecryption => write!(
+                f,
+                "Failed to decrypt the mnemonic. Either the password is wrong or the data is corrupted."
+            ),
             Self::InsanePsbt => write!(f, "Information contained in the PSBT is wrong."),
             Self::IncompletePsbt => write!(
                 f,
@@ -54,12 +67,33 @@ impl error::Error for SignerError {}
 
 pub const MNEMONICS_FOLDER_NAME: &str = "mnemonics";
 
-// TODO: zeroize, mlock, etc.. For now we don't even encrypt the seed on disk so that'd be
-// overkill.
+// Encrypted mnemonic files start with this magic, followed by a version byte. This is how we tell
+// them apart from the legacy files containing the mnemonic words in plaintext.
+const ENCRYPTED_MNEMONIC_MAGIC: &[u8; 8] = b"lianaenc";
+const ENCRYPTED_MNEMONIC_VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+// magic | version | fingerprint | m_cost | t_cost | p_cost | salt | nonce
+const ENCRYPTED_MNEMONIC_HEADER_LEN: usize = 8 + 1 + 4 + 4 + 4 + 4 + SALT_LEN + NONCE_LEN;
+
+// The Argon2id (memory cost in KiB, number of iterations, parallelism) used when encrypting a
+// mnemonic. They are stored in the header so they can be raised without breaking existing files.
+#[cfg(not(test))]
+const KDF_PARAMS: (u32, u32, u32) = (64 * 1024, 3, 1);
+// Argon2 is very slow without optimizations, don't make the unit tests crawl.
+#[cfg(test)]
+const KDF_PARAMS: (u32, u32, u32) = (1024, 1, 1);
+// Don't let a (corrupted or malicious) header make us allocate an unreasonable amount of memory.
+const KDF_MAX_MEMORY_COST: u32 = 1024 * 1024;
+
+// TODO: zeroize, mlock, etc..
 /// A signer that keeps the key on the laptop. Based on BIP39.
 pub struct HotSigner {
     mnemonic: bip39::Mnemonic,
     master_xpriv: bip32::Xpriv,
+    // The secret nonces of the MuSig2 signing sessions we took part in, by participant key,
+    // aggregate key and sighash. They are only kept in memory: they must never be reused.
+    musig_secnonces: sync::Mutex<HashMap<[u8; 98], musig2::SecNonce>>,
 }
 
 // TODO: instead of copying them here we could have a util module with those helpers.
@@ -109,6 +143,7 @@ impl HotSigner {
         Ok(Self {
             mnemonic,
             master_xpriv,
+            musig_secnonces: sync::Mutex::new(HashMap::new()),
         })
     }
 
@@ -137,18 +172,46 @@ impl HotSigner {
     }
 
     /// Read all the mnemonics from the datadir for the given network.
+    ///
+    /// Mnemonics stored in plaintext are always loaded. Encrypted ones are only loaded if they can
+    /// be decrypted using the given password, since different mnemonics may have been encrypted
+    /// with different passwords. See [`EncryptedMnemonic::from_datadir`] to list them all.
     pub fn from_datadir(
         datadir_root: &path::Path,
         network: bitcoin::Network,
+        password: Option<&str>,
+    ) -> Result<Vec<Self>, SignerError> {
+        Self::from_folder(
+            &Self::mnemonics_folder(datadir_root, network),
+            network,
+            password,
+        )
+    }
+
+    /// Read all the mnemonics stored in the given folder, in the same format as within a datadir.
+    /// See [`HotSigner::from_datadir`].
+    pub fn from_folder(
+        folder: &path::Path,
+        network: bitcoin::Network,
+        password: Option<&str>,
     ) -> Result<Vec<Self>, SignerError> {
         let mut signers = Vec::new();
 
-        let mnemonic_paths = fs::read_dir(Self::mnemonics_folder(datadir_root, network))
-            .map_err(SignerError::MnemonicStorage)?;
-        for entry in mnemonic_paths {
-            let mnemonic = fs::read_to_string(entry.map_err(SignerError::MnemonicStorage)?.path())
-                .map_err(SignerError::MnemonicStorage)?;
-            signers.push(Self::from_str(network, &mnemonic)?);
+        for (_, mnemonic) in read_mnemonic_files(folder)? {
+            match mnemonic {
+                StoredMnemonic::Plaintext(mnemonic) => {
+                    signers.push(Self::from_str(network, &mnemonic)?)
+                }
+                StoredMnemonic::Encrypted(encrypted) => {
+                    if let Some(password) = password {
+                        match encrypted.decrypt(network, password) {
+                            Ok(signer) => signers.push(signer),
+                            Err(SignerError::MnemonicDecryption) => {}
+                            Err(e) => return Err(e),
+                        }
+                    }
+                }
+            }
         }
 
         Ok(signers)
@@ -186,33 +249,90 @@ impl HotSigner {
     /// Store the mnemonic in a file within the given "data directory".
     /// The file is stored within a "mnemonics" folder, with the filename set to the fingerprint of
     /// the master xpub corresponding to this mnemonic.
-    /// returns the filename
+    /// If a password is given the mnemonic is stored encrypted, otherwise in plaintext.
     pub fn store(
         &self,
         datadir_root: &path::Path,
         network: bitcoin::Network,
         secp: &secp256k1::Secp256k1<impl secp256k1::Signing>,
         descriptor_info: Option<(String, i64)>,
+        password: Option<&str>,
     ) -> Result<(), SignerError> {
         let mnemonics_folder = Self::mnemonics_folder(datadir_root, network);
         if !mnemonics_folder.exists() {
             create_dir(&mnemonics_folder).map_err(SignerError::MnemonicStorage)?;
         }
 
+        let fingerprint = self.fingerprint(secp);
+        let data = match password {
+            Some(password) => {
+                EncryptedMnemonic::encrypt(&self.mnemonic_str(), fingerprint, password)?.to_bytes()
+            }
+            None => self.mnemonic_str().into_bytes(),
+        };
+
         // This will fail if a file with this fingerprint exists already.
         let filename = MnemonicFileName {
-            fingerprint: self.fingerprint(secp),
+            fingerprint,
             descriptor_info,
         };
         let mut mnemonic_file = create_file(&mnemonics_folder.join(filename.to_string()))
             .map_err(SignerError::MnemonicStorage)?;
         mnemonic_file
-            .write_all(self.mnemonic_str().as_bytes())
+            .write_all(&data)
             .map_err(SignerError::MnemonicStorage)?;
 
         Ok(())
     }
 
+    /// Encrypt using the given password the files from the datadir which contain the mnemonic of
+    /// this signer in plaintext. The files are replaced in place and keep their name. Returns the
+    /// number of files which were encrypted.
+    ///
+    /// Note this does not securely erase the plaintext mnemonic from the disk.
+    pub fn encrypt_stored(
+        &self,
+        datadir_root: &path::Path,
+        network: bitcoin::Network,
+        secp: &secp256k1::Secp256k1<impl secp256k1::Signing>,
+        password: &str,
+    ) -> Result<usize, SignerError> {
+        let fingerprint = self.fingerprint(secp);
+        let mut count = 0;
+
+        let folder = Self::mnemonics_folder(datadir_root, network);
+        for (path, mnemonic) in read_mnemonic_files(&folder)? {
+            let is_ours = match mnemonic {
+                StoredMnemonic::Plaintext(mnemonic) => bip39::Mnemonic::from_str(&mnemonic)
+                    .map(|m| m == self.mnemonic)
+                    .unwrap_or(false),
+                StoredMnemonic::Encrypted(_) => false,
+            };
+            if !is_ours {
+                continue;
+            }
+
+            // Write the encrypted mnemonic to a temporary file first and then move it over the
+            // plaintext one, so we never lose the mnemonic if we crash in the middle.
+            let data =
+                EncryptedMnemonic::encrypt(&self.mnemonic_str(), fingerprint, password)?.to_bytes();
+            let tmp_path = path.with_extension(TMP_FILE_EXTENSION);
+            if tmp_path.exists() {
+                fs::remove_file(&tmp_path).map_err(SignerError::MnemonicStorage)?;
+            }
+            let mut tmp_file = create_file(&tmp_path).map_err(SignerError::MnemonicStorage)?;
+            tmp_file
+                .write_all(&data)
+                .and_then(|_| tmp_file.sync_all())
+                .map_err(SignerError::MnemonicStorage)?;
+            // TODO: replacing a read-only file may fail on Windows..
+            fs::rename(&tmp_path, &path).map_err(SignerError::MnemonicStorage)?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
     fn xpriv_at(
         &self,
         der_path: &bip32::DerivationPath,
@@ -355,8 +475,134 @@ impl HotSigner {
         Ok(())
     }
 
+    // Take part in the MuSig2 signing session for a keypath spend of this transaction input, if
+    // the internal key is an aggregate of keys derived from our master secret. The first round
+    // sets our public nonce. Once the public nonces of all participants are present, the second
+    // round sets our partial signature. Finally the partial signatures are aggregated into the
+    // keypath signature once all participants provided theirs.
+    fn sign_musig(
+        &self,
+        secp: &secp256k1::Secp256k1<secp256k1::All>,
+        sighash_cache: &mut sighash::SighashCache<&bitcoin::Transaction>,
+        master_fingerprint: bip32::Fingerprint,
+        prevouts: &[bitcoin::TxOut],
+        psbt_in: &mut PsbtIn,
+        input_index: usize,
+    ) -> Result<(), SignerError> {
+        if psbt_in.tap_key_sig.is_some() {
+            return Ok(());
+        }
+        let participants = match musig::psbt_in_participants(psbt_in) {
+            Some((_, participants)) => participants,
+            None => return Ok(()),
+        };
+        let (aggregate_key, key_agg_ctx) = musig::psbt_in_key_agg_ctx(psbt_in, &participants, secp)
+            .ok_or(SignerError::InsanePsbt)?;
+        let sighash = sighash_cache
+            .taproot_key_spend_signature_hash(
+                input_index,
+                &sighash::Prevouts::All(prevouts),
+                sighash::TapSighashType::Default,
+            )
+            .map_err(|_| SignerError::InsanePsbt)?
+            .to_byte_array();
+
+        // Get the participants whose key is derived from our master secret.
+        let mut our_keys = Vec::new();
+        for participant in &participants {
+            let xonly_key = participant.x_only_public_key().0;
+            if let Some((_, (fg, der_path))) = psbt_in.tap_key_origins.get(&xonly_key) {
+                if *fg != master_fingerprint {
+                    continue;
+                }
+                let privkey = self.xpriv_at(der_path, secp).to_priv();
+                if privkey.public_key(secp).inner != *participant {
+                    return Err(SignerError::InsanePsbt);
+                }
+                our_keys.push((*participant, privkey.inner));
+            }
+        }
+
+        let mut secnonces = self.musig_secnonces.lock().expect("Never poisoned.");
+        for (participant, seckey) in our_keys {
+            // The sighash commits to the transaction and the input index, so that two inputs
+            // (or two transactions) spending from the same aggregate key don't share a session.
+            let mut session_id = [0; 98];
+            session_id[..33].copy_from_slice(&participant.serialize());
+            session_id[33..66].copy_from_slice(&aggregate_key.serialize());
+            session_id[66..].copy_from_slice(&sighash);
+
+            if musig::partial_sig(psbt_in, &participant, &aggregate_key).is_some() {
+                continue;
+            }
+            let our_nonce = match musig::pub_nonce(psbt_in, &participant, &aggregate_key) {
+                Some(pub_nonce) => pub_nonce,
+                None => {
+                    // First round: provide a public nonce for this session.
+                    let seed = random::random_bytes().map_err(SignerError::Randomness)?;
+                    let secnonce = musig2::SecNonce::build_with_seckey(seed, seckey)
+                        .with_message(&sighash)
+                        .with_aggregated_pubkey(
+                            key_agg_ctx.aggregated_pubkey::<secp256k1::PublicKey>(),
+                        )
+                        .build();
+                    musig::insert_pub_nonce(
+                        psbt_in,
+                        &participant,
+                        &aggregate_key,
+                        &secnonce.public_nonce(),
+                    );
+                    secnonces.insert(session_id, secnonce);
+                    continue;
+                }
+            };
+
+            // Second round: provide a partial signature once all the public nonces are present.
+            // We can only do so if we generated the nonce for this session ourselves.
+            let pub_nonces = match participants
+                .iter()
+                .map(|pk| musig::pub_nonce(psbt_in, pk, &aggregate_key))
+                .collect::<Option<Vec<_>>>()
+            {
+                Some(pub_nonces) => pub_nonces,
+                None => continue,
+            };
+            let secnonce = match secnonces.get(&session_id) {
+                Some(secnonce) if secnonce.public_nonce() == our_nonce => {
+                    secnonces.remove(&session_id).expect("Just checked.")
+                }
+                _ => {
+                    log::warn!(
+                        "No secret nonce for the MuSig2 session of participant '{}'.",
+                        participant
+                    );
+                    continue;
+                }
+            };
+            let agg_nonce = musig2::AggNonce::sum(&pub_nonces);
+            let partial_sig: musig2::PartialSignature =
+                musig2::sign_partial(&key_agg_ctx, seckey, secnonce, &agg_nonce, sighash)
+                    .map_err(|_| SignerError::InsanePsbt)?;
+            musig::insert_partial_sig(psbt_in, &participant, &aggregate_key, &partial_sig);
+        }
+
+        musig::aggregate_partial_sigs(
+            psbt_in,
+            &participants,
+            &aggregate_key,
+            &key_agg_ctx,
+            &sighash,
+        );
+        Ok(())
+    }
+
     /// Sign all inputs of the given PSBT.
     ///
+    /// For inputs whose Taproot internal key is a MuSig2 aggregate of our keys, this sets our
+    /// public nonce on a first call and our partial signature on a subsequent call once all
+    /// participants' nonces are present. It must be called on the same `HotSigner` instance for
+    /// both rounds.
+    ///
     /// **This does not perform any check. It will blindly sign anything that's passed.**
     pub fn sign_psbt(
         &self,
@@ -394,12 +640,30 @@ impl HotSigner {
                     &mut psbt.inputs[i],
                     i,
                 )?;
+                self.sign_musig(
+                    secp,
+                    &mut sighash_cache,
+                    master_fingerprint,
+                    &prevouts,
+                    &mut psbt.inputs[i],
+                    i,
+                )?;
             }
         }
 
         Ok(psbt)
     }
 
+    /// Whether this signer provided a MuSig2 public nonce for a signing session it did not yet
+    /// provide a partial signature for.
+    pub fn has_pending_musig_sessions(&self) -> bool {
+        !self
+            .musig_secnonces
+            .lock()
+            .expect("Never poisoned.")
+            .is_empty()
+    }
+
     /// Change the network of generated extended keys. Note this value only has to do with the
     /// BIP32 encoding of those keys (xpubs, tpubs, ..) but does not affect any data (whether it is
     /// the keys or the mnemonics).
@@ -408,6 +672,210 @@ impl HotSigner {
     }
 }
 
+// Temporary files created when encrypting a plaintext mnemonic file in place.
+const TMP_FILE_EXTENSION: &str = "tmp";
+
+// The content of a file in the mnemonics folder.
+enum StoredMnemonic {
+    // The legacy format: the mnemonic words in plaintext.
+    Plaintext(String),
+    Encrypted(EncryptedMnemonic),
+}
+
+// Read all the files from the given mnemonics folder.
+fn read_mnemonic_files(
+    folder: &path::Path,
+) -> Result<Vec<(path::PathBuf, StoredMnemonic)>, SignerError> {
+    let mut mnemonics = Vec::new();
+
+    let mnemonic_paths = fs::read_dir(folder).map_err(SignerError::MnemonicStorage)?;
+    for entry in mnemonic_paths {
+        let path = entry.map_err(SignerError::MnemonicStorage)?.path();
+        // A leftover from an interrupted encryption. The original file is still there.
+        if path.extension().and_then(|ext| ext.to_str()) == Some(TMP_FILE_EXTENSION) {
+            continue;
+        }
+        let data = fs::read(&path).map_err(SignerError::MnemonicStorage)?;
+        let mnemonic = if data.starts_with(ENCRYPTED_MNEMONIC_MAGIC) {
+            StoredMnemonic::Encrypted(EncryptedMnemonic::from_bytes(&data)?)
+        } else {
+            StoredMnemonic::Plaintext(String::from_utf8(data).map_err(|e| {
+                SignerError::MnemonicStorage(io::Error::new(io::ErrorKind::InvalidData, e))
+            })?)
+        };
+        mnemonics.push((path, mnemonic));
+    }
+
+    Ok(mnemonics)
+}
+
+// Derive the encryption key from the password using Argon2id.
+fn derive_key(
+    password: &str,
+    salt: &[u8],
+    (m_cost, t_cost, p_cost): (u32, u32, u32),
+) -> Result<chacha20poly1305::Key, SignerError> {
+    if m_cost > KDF_MAX_MEMORY_COST {
+        return Err(SignerError::InvalidEncryptedMnemonic(format!(
+            "KDF memory cost too large: {}KiB",
+            m_cost
+        )));
+    }
+    let params = argon2::Params::new(m_cost, t_cost, p_cost, Some(32))
+        .map_err(|e| SignerError::InvalidEncryptedMnemonic(e.to_string()))?;
+    let mut key = chacha20poly1305::Key::default();
+    argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| SignerError::InvalidEncryptedMnemonic(e.to_string()))?;
+    Ok(key)
+}
+
+/// A mnemonic stored encrypted in the datadir. It must be decrypted with the password it was
+/// stored with before it can be used.
+///
+/// The mnemonic is encrypted using XChaCha20-Poly1305 with a key derived from the password using
+/// Argon2id. It is prefixed with a versioned header containing the fingerprint of the signer and
+/// the parameters necessary for decryption. The header is not encrypted but it is authenticated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptedMnemonic {
+    fingerprint: Fingerprint,
+    kdf_params: (u32, u32, u32),
+    salt: [u8; SALT_LEN],
+    nonce: [u8; NONCE_LEN],
+    ciphertext: Vec<u8>,
+}
+
+impl EncryptedMnemonic {
+    fn encrypt(
+        mnemonic_str: &str,
+        fingerprint: Fingerprint,
+        password: &str,
+    ) -> Result<Self, SignerError> {
+        let mut salt = [0; SALT_LEN];
+        salt.copy_from_slice(&random::random_bytes().map_err(SignerError::Randomness)?[..SALT_LEN]);
+        let mut nonce = [0; NONCE_LEN];
+        nonce.copy_from_slice(
+            &random::random_bytes().map_err(SignerError::Randomness)?[..NONCE_LEN],
+        );
+        let mut encrypted = Self {
+            fingerprint,
+            kdf_params: KDF_PARAMS,
+            salt,
+            nonce,
+            ciphertext: Vec::new(),
+        };
+
+        let key = derive_key(password, &encrypted.salt, encrypted.kdf_params)?;
+        encrypted.ciphertext = XChaCha20Poly1305::new(&key)
+            .encrypt(
+                XNonce::from_slice(&encrypted.nonce),
+                Payload {
+                    msg: mnemonic_str.as_bytes(),
+                    aad: &encrypted.header(),
+                },
+            )
+            .expect("A mnemonic is never too large to be encrypted.");
+
+        Ok(encrypted)
+    }
+
+    /// Parse an encrypted mnemonic as stored on disk.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, SignerError> {
+        if data.len() < ENCRYPTED_MNEMONIC_HEADER_LEN || !data.starts_with(ENCRYPTED_MNEMONIC_MAGIC)
+        {
+            return Err(SignerError::InvalidEncryptedMnemonic(
+                "Missing or truncated header".to_string(),
+            ));
+        }
+        let version = data[8];
+        if version != ENCRYPTED_MNEMONIC_VERSION {
+            return Err(SignerError::InvalidEncryptedMnemonic(format!(
+                "Unknown version: {}",
+                version
+            )));
+        }
+
+        let u32_at =
+            |i: usize| u32::from_le_bytes(data[i..i + 4].try_into().expect("Slice of 4 bytes"));
+        let fingerprint: [u8; 4] = data[9..13].try_into().expect("Slice of 4 bytes");
+        let salt_start = 25;
+        let nonce_start = salt_start + SALT_LEN;
+        Ok(Self {
+            fingerprint: fingerprint.into(),
+            kdf_params: (u32_at(13), u32_at(17), u32_at(21)),
+            salt: data[salt_start..nonce_start]
+                .try_into()
+                .expect("Slice of SALT_LEN bytes"),
+            nonce: data[nonce_start..ENCRYPTED_MNEMONIC_HEADER_LEN]
+                .try_into()
+                .expect("Slice of NONCE_LEN bytes"),
+            ciphertext: data[ENCRYPTED_MNEMONIC_HEADER_LEN..].to_vec(),
+        })
+    }
+
+    /// Serialize this encrypted mnemonic as it is stored on disk.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = self.header();
+        data.extend_from_slice(&self.ciphertext);
+        data
+    }
+
+    fn header(&self) -> Vec<u8> {
+        let (m_cost, t_cost, p_cost) = self.kdf_params;
+        let mut header = Vec::with_capacity(ENCRYPTED_MNEMONIC_HEADER_LEN);
+        header.extend_from_slice(ENCRYPTED_MNEMONIC_MAGIC);
+        header.push(ENCRYPTED_MNEMONIC_VERSION);
+        header.extend_from_slice(self.fingerprint.as_bytes());
+        header.extend_from_slice(&m_cost.to_le_bytes());
+        header.extend_from_slice(&t_cost.to_le_bytes());
+        header.extend_from_slice(&p_cost.to_le_bytes());
+        header.extend_from_slice(&self.salt);
+        header.extend_from_slice(&self.nonce);
+        header
+    }
+
+    /// Read all the encrypted mnemonics from the datadir for the given network.
+    pub fn from_datadir(
+        datadir_root: &path::Path,
+        network: bitcoin::Network,
+    ) -> Result<Vec<Self>, SignerError> {
+        let folder = HotSigner::mnemonics_folder(datadir_root, network);
+        Ok(read_mnemonic_files(&folder)?
+            .into_iter()
+            .filter_map(|(_, mnemonic)| match mnemonic {
+                StoredMnemonic::Encrypted(encrypted) => Some(encrypted),
+                StoredMnemonic::Plaintext(_) => None,
+            })
+            .collect())
+    }
+
+    /// The fingerprint of the master xpub of the encrypted signer.
+    pub fn fingerprint(&self) -> Fingerprint {
+        self.fingerprint
+    }
+
+    /// Decrypt the mnemonic using the given password and get the corresponding signer.
+    pub fn decrypt(
+        &self,
+        network: bitcoin::Network,
+        password: &str,
+    ) -> Result<HotSigner, SignerError> {
+        let key = derive_key(password, &self.salt, self.kdf_params)?;
+        let plaintext = XChaCha20Poly1305::new(&key)
+            .decrypt(
+                XNonce::from_slice(&self.nonce),
+                Payload {
+                    msg: &self.ciphertext,
+                    aad: &self.header(),
+                },
+            )
+            .map_err(|_| SignerError::MnemonicDecryption)?;
+        let mnemonic_str =
+            String::from_utf8(plaintext).map_err(|_| SignerError::MnemonicDecryption)?;
+        HotSigner::from_str(network, &mnemonic_str)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MnemonicFileName {
     pub fingerprint: Fingerprint,
@@ -565,11 +1033,11 @@ mod tests {
         let words_set: HashSet<_> = (0..10)
             .map(|_| {
                 let signer = HotSigner::generate(network).unwrap();
-                signer.store(&tmp_dir, network, &secp, None).unwrap();
+                signer.store(&tmp_dir, network, &secp, None, None).unwrap();
                 signer.words()
             })
             .collect();
-        let words_read: HashSet<_> = HotSigner::from_datadir(&tmp_dir, network)
+        let words_read: HashSet<_> = HotSigner::from_datadir(&tmp_dir, network, None)
             .unwrap()
             .into_iter()
             .map(|signer| signer.words())
@@ -579,6 +1047,151 @@ mod tests {
         fs::remove_dir_all(tmp_dir).unwrap();
     }
 
+    #[test]
+    fn hot_signer_encrypted_storage() {
+        let secp = secp256k1::Secp256k1::signing_only();
+        let tmp_dir = tmp_dir();
+        fs::create_dir_all(&tmp_dir).unwrap();
+        let network = bitcoin::Network::Bitcoin;
+
+        // Store a signer in plaintext and two others encrypted with different passwords.
+        let plain_signer = HotSigner::generate(network).unwrap();
+        plain_signer
+            .store(&tmp_dir, network, &secp, None, None)
+            .unwrap();
+        let signer_a = HotSigner::generate(network).unwrap();
+        signer_a
+            .store(
+                &tmp_dir,
+                network,
+                &secp,
+                Some(("checksum".to_string(), 1)),
+                Some("password a"),
+            )
+            .unwrap();
+        let signer_b = HotSigner::generate(network).unwrap();
+        signer_b
+            .store(&tmp_dir, network, &secp, None, Some("password b"))
+            .unwrap();
+
+        // The encrypted files don't contain the mnemonic in plaintext.
+        let folder = HotSigner::mnemonics_folder(&tmp_dir, network);
+        for entry in fs::read_dir(&folder).unwrap() {
+            let data = fs::read(entry.unwrap().path()).unwrap();
+            assert_eq!(
+                data.starts_with(ENCRYPTED_MNEMONIC_MAGIC),
+                !data.starts_with(plain_signer.mnemonic_str().as_bytes())
+            );
+        }
+
+        // Without a password only the plaintext signer is loaded. With a password, the plaintext
+        // signer and the ones encrypted with this password are.
+        let words_read = |password| -> HashSet<_> {
+            HotSigner::from_datadir(&tmp_dir, network, password)
+                .unwrap()
+                .into_iter()
+                .map(|signer| signer.words())
+                .collect()
+        };
+        assert_eq!(words_read(None), HashSet::from([plain_signer.words()]));
+        assert_eq!(
+            words_read(Some("password a")),
+            HashSet::from([plain_signer.words(), signer_a.words()])
+        );
+        assert_eq!(
+            words_read(Some("password b")),
+            HashSet::from([plain_signer.words(), signer_b.words()])
+        );
+        assert_eq!(
+            words_read(Some("password c")),
+            HashSet::from([plain_signer.words()])
+        );
+
+        // The encrypted signers can be listed without a password.
+        let encrypted = EncryptedMnemonic::from_datadir(&tmp_dir, network).unwrap();
+        assert_eq!(encrypted.len(), 2);
+        let enc_a = encrypted
+            .iter()
+            .find(|enc| enc.fingerprint() == signer_a.fingerprint(&secp))
+            .unwrap();
+        assert!(encrypted
+            .iter()
+            .any(|enc| enc.fingerprint() == signer_b.fingerprint(&secp)));
+        assert_eq!(
+            enc_a.decrypt(network, "password a").unwrap().words(),
+            signer_a.words()
+        );
+        assert!(matches!(
+            enc_a.decrypt(network, "password b"),
+            Err(SignerError::MnemonicDecryption)
+        ));
+
+        // Roundtrip through serialization, and check the header is authenticated.
+        let data = enc_a.to_bytes();
+        assert_eq!(&EncryptedMnemonic::from_bytes(&data).unwrap(), enc_a);
+        let mut tampered = data.clone();
+        tampered[9] ^= 1;
+        assert!(matches!(
+            EncryptedMnemonic::from_bytes(&tampered)
+                .unwrap()
+                .decrypt(network, "password a"),
+            Err(SignerError::MnemonicDecryption)
+        ));
+        let mut tampered = data.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            EncryptedMnemonic::from_bytes(&tampered)
+                .unwrap()
+                .decrypt(network, "password a"),
+            Err(SignerError::MnemonicDecryption)
+        ));
+        let mut unknown_version = data.clone();
+        unknown_version[8] = 2;
+        assert!(matches!(
+            EncryptedMnemonic::from_bytes(&unknown_version),
+            Err(SignerError::InvalidEncryptedMnemonic(_))
+        ));
+        assert!(matches!(
+            EncryptedMnemonic::from_bytes(&data[..ENCRYPTED_MNEMONIC_HEADER_LEN - 1]),
+            Err(SignerError::InvalidEncryptedMnemonic(_))
+        ));
+
+        // Migrate the plaintext signer. Its file keeps the same name but it's now encrypted.
+        let filenames = |dir: &path::Path| -> HashSet<_> {
+            fs::read_dir(dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name())
+                .collect()
+        };
+        let filenames_before = filenames(&folder);
+        assert_eq!(
+            signer_a
+                .encrypt_stored(&tmp_dir, network, &secp, "password a")
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            plain_signer
+                .encrypt_stored(&tmp_dir, network, &secp, "password c")
+                .unwrap(),
+            1
+        );
+        assert_eq!(filenames(&folder), filenames_before);
+        assert!(words_read(None).is_empty());
+        assert_eq!(
+            words_read(Some("password c")),
+            HashSet::from([plain_signer.words()])
+        );
+        assert_eq!(
+            EncryptedMnemonic::from_datadir(&tmp_dir, network)
+                .unwrap()
+                .len(),
+            3
+        );
+
+        fs::remove_dir_all(tmp_dir).unwrap();
+    }
+
     #[test]
     fn hot_signer_sign_p2wsh() {
         let secp = secp256k1::Secp256k1::new();
@@ -1156,6 +1769,210 @@ mod tests {
             .all(|psbt_in| psbt_in.partial_sigs.is_empty()));
     }
 
+    // Create a Liana descriptor with as primary path the MuSig2 aggregate of the keys of two
+    // hot signers, and a single hot signer as recovery path.
+    fn musig_descriptor(
+        secp: &secp256k1::Secp256k1<secp256k1::All>,
+        network: bitcoin::Network,
+    ) -> (
+        HotSigner,
+        HotSigner,
+        HotSigner,
+        descriptors::LianaDescriptor,
+    ) {
+        let (prim_signer_a, prim_signer_b, recov_signer) = (
+            HotSigner::generate(network).unwrap(),
+            HotSigner::generate(network).unwrap(),
+            HotSigner::generate(network).unwrap(),
+        );
+        let multi_key = |signer: &HotSigner, origin_der: &str| {
+            let origin_der = bip32::DerivationPath::from_str(origin_der).unwrap();
+            DescriptorPublicKey::MultiXPub(DescriptorMultiXKey {
+                origin: Some((signer.fingerprint(secp), origin_der.clone())),
+                xkey: signer.xpub_at(&origin_der, secp),
+                derivation_paths: DerivPaths::new(vec![
+                    bip32::DerivationPath::from_str("m/0").unwrap(),
+                    bip32::DerivationPath::from_str("m/1").unwrap(),
+                ])
+                .unwrap(),
+                wildcard: Wildcard::Unhardened,
+            })
+        };
+        let prim_keys = descriptors::PathInfo::Multi(
+            2,
+            vec![
+                multi_key(&prim_signer_a, "m/48'/0'/0'/2'"),
+                multi_key(&prim_signer_b, "m/48'/0'/0'/2'"),
+            ],
+        );
+        let recov_keys = descriptors::PathInfo::Single(multi_key(&recov_signer, "m/48'/0'/1'"));
+        let policy =
+            descriptors::LianaPolicy::new(prim_keys, [(46, recov_keys)].iter().cloned().collect())
+                .unwrap()
+                .with_musig_primary_path()
+                .unwrap();
+        let desc = descriptors::LianaDescriptor::new(policy);
+        (prim_signer_a, prim_signer_b, recov_signer, desc)
+    }
+
+    #[test]
+    fn hot_signer_sign_musig() {
+        let secp = secp256k1::Secp256k1::new();
+        let network = bitcoin::Network::Bitcoin;
+        let (prim_signer_a, prim_signer_b, recov_signer, desc) = musig_descriptor(&secp, network);
+
+        // Create a dummy PSBT spending a coin from this descriptor through the key path.
+        let spent_coin_desc = desc.change_descriptor().derive(7.into(), &secp);
+        let mut psbt_in = PsbtIn::default();
+        spent_coin_desc.update_psbt_in(&mut psbt_in);
+        desc.update_musig_psbt_in(&mut psbt_in);
+        let prevout = bitcoin::TxOut {
+            value: Amount::from_sat(19_000),
+            script_pubkey: spent_coin_desc.script_pubkey(),
+        };
+        psbt_in.witness_utxo = Some(prevout.clone());
+        let mut psbt = Psbt::from_unsigned_tx(bitcoin::Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn {
+                sequence: bitcoin::Sequence::ENABLE_RBF_NO_LOCKTIME,
+                ..bitcoin::TxIn::default()
+            }],
+            output: vec![bitcoin::TxOut {
+                value: Amount::from_sat(18_420),
+                script_pubkey: prevout.script_pubkey.clone(),
+            }],
+        })
+        .unwrap();
+        psbt.inputs[0] = psbt_in;
+        let sigs_count = |psbt: &Psbt| {
+            desc.partial_spend_info(psbt)
+                .unwrap()
+                .primary_path()
+                .sigs_count
+        };
+
+        // The recovery signer doesn't take part in the session.
+        let psbt = recov_signer.sign_psbt(psbt, &secp).unwrap();
+        assert!(psbt.inputs[0]
+            .unknown
+            .keys()
+            .all(|k| !musig::is_session_field(k)));
+
+        // First round: both participants provide a nonce. Signing again before all nonces are
+        // present doesn't change anything.
+        let psbt = prim_signer_a.sign_psbt(psbt, &secp).unwrap();
+        assert_eq!(psbt.inputs[0].unknown.len(), 2);
+        let psbt = prim_signer_a.sign_psbt(psbt, &secp).unwrap();
+        assert_eq!(psbt.inputs[0].unknown.len(), 2);
+        let psbt = prim_signer_b.sign_psbt(psbt, &secp).unwrap();
+        assert_eq!(psbt.inputs[0].unknown.len(), 3);
+        assert_eq!(sigs_count(&psbt), 0);
+        assert!(prim_signer_a.has_pending_musig_sessions());
+        assert!(prim_signer_b.has_pending_musig_sessions());
+        assert!(!recov_signer.has_pending_musig_sessions());
+
+        // Second round: both participants provide a partial signature, which are then aggregated
+        // into a keypath signature.
+        let psbt = prim_signer_a.sign_psbt(psbt, &secp).unwrap();
+        assert_eq!(psbt.inputs[0].unknown.len(), 4);
+        assert_eq!(sigs_count(&psbt), 1);
+        assert!(psbt.inputs[0].tap_key_sig.is_none());
+        assert!(!prim_signer_a.has_pending_musig_sessions());
+        // The signer can't provide a partial signature twice for the same nonce.
+        let psbt = prim_signer_a.sign_psbt(psbt, &secp).unwrap();
+        assert_eq!(psbt.inputs[0].unknown.len(), 4);
+        let psbt = prim_signer_b.sign_psbt(psbt, &secp).unwrap();
+        assert_eq!(sigs_count(&psbt), 2);
+        let sig = psbt.inputs[0].tap_key_sig.unwrap();
+        // Only the recovery signer signed for a script path.
+        assert_eq!(psbt.inputs[0].tap_script_sigs.len(), 1);
+
+        // This is a valid signature for the output key.
+        let sighash = sighash::SighashCache::new(&psbt.unsigned_tx)
+            .taproot_key_spend_signature_hash(
+                0,
+                &sighash::Prevouts::All(std::slice::from_ref(&prevout)),
+                sig.sighash_type,
+            )
+            .unwrap();
+        let sighash = secp256k1::Message::from_digest_slice(sighash.as_byte_array()).unwrap();
+        let output_key =
+            secp256k1::XOnlyPublicKey::from_slice(&prevout.script_pubkey.as_bytes()[2..]).unwrap();
+        secp.verify_schnorr(&sig.signature, &sighash, &output_key)
+            .unwrap();
+
+        // A signer that wasn't used for the first round can't provide a partial signature.
+        let mut psbt = psbt;
+        psbt.inputs[0].tap_key_sig = None;
+        psbt.inputs[0]
+            .unknown
+            .retain(|k, _| k.type_value != musig::PSBT_IN_MUSIG2_PARTIAL_SIG);
+        let other_signer_a =
+            HotSigner::from_str(network, &prim_signer_a.words().join(" ")).unwrap();
+        let psbt = other_signer_a.sign_psbt(psbt, &secp).unwrap();
+        assert_eq!(sigs_count(&psbt), 0);
+    }
+
+    #[test]
+    fn hot_signer_sign_musig_same_key() {
+        let secp = secp256k1::Secp256k1::new();
+        let network = bitcoin::Network::Bitcoin;
+        let (prim_signer_a, prim_signer_b, _, desc) = musig_descriptor(&secp, network);
+
+        // Create a dummy PSBT spending two coins from the same address through the key path.
+        let spent_coin_desc = desc.receive_descriptor().derive(3.into(), &secp);
+        let mut psbt_in = PsbtIn::default();
+        spent_coin_desc.update_psbt_in(&mut psbt_in);
+        desc.update_musig_psbt_in(&mut psbt_in);
+        let prevout = bitcoin::TxOut {
+            value: Amount::from_sat(19_000),
+            script_pubkey: spent_coin_desc.script_pubkey(),
+        };
+        psbt_in.witness_utxo = Some(prevout.clone());
+        let txin = |vout| bitcoin::TxIn {
+            previous_output: bitcoin::OutPoint::new(bitcoin::Txid::all_zeros(), vout),
+            sequence: bitcoin::Sequence::ENABLE_RBF_NO_LOCKTIME,
+            ..bitcoin::TxIn::default()
+        };
+        let mut psbt = Psbt::from_unsigned_tx(bitcoin::Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![txin(0), txin(1)],
+            output: vec![bitcoin::TxOut {
+                value: Amount::from_sat(37_420),
+                script_pubkey: prevout.script_pubkey.clone(),
+            }],
+        })
+        .unwrap();
+        psbt.inputs = vec![psbt_in.clone(), psbt_in];
+
+        // Both inputs have their own signing session, even though the participants and the
+        // aggregate key are the same. The nonce of the first input isn't overwritten by the one of
+        // the second input, and both end up signed.
+        let psbt = prim_signer_a.sign_psbt(psbt, &secp).unwrap();
+        let psbt = prim_signer_b.sign_psbt(psbt, &secp).unwrap();
+        let psbt = prim_signer_a.sign_psbt(psbt, &secp).unwrap();
+        let psbt = prim_signer_b.sign_psbt(psbt, &secp).unwrap();
+        let mut sighash_cache = sighash::SighashCache::new(&psbt.unsigned_tx);
+        let prevouts = [prevout.clone(), prevout.clone()];
+        let output_key =
+            secp256k1::XOnlyPublicKey::from_slice(&prevout.script_pubkey.as_bytes()[2..]).unwrap();
+        for (i, psbt_in) in psbt.inputs.iter().enumerate() {
+            let sig = psbt_in.tap_key_sig.expect("Both inputs are signed.");
+            let sighash = sighash_cache
+                .taproot_key_spend_signature_hash(
+                    i,
+                    &sighash::Prevouts::All(&prevouts),
+                    sig.sighash_type,
+                )
+                .unwrap();
+            let sighash = secp256k1::Message::from_digest_slice(sighash.as_byte_array()).unwrap();
+            secp.verify_schnorr(&sig.signature, &sighash, &output_key)
+                .unwrap();
+        }
+    }
+
     #[test]
     fn signer_set_net() {
         let secp = secp256k1::Secp256k1::signing_only();
@@ -683,13 +683,23 @@ pub fn create_spend(
         value: bitcoin::Amount::MAX,
         script_pubkey: change_addr.addr.script_pubkey(),
     };
-    // If no candidates have relative locktime, then we should use the primary spending path.
+    // If no candidates have relative locktime (be it block-based or time-based), nor an explicit
+    // nSequence enabling a transaction locktime which unlocks an absolute timelocked recovery
+    // path, then we should use the primary spending path.
     // Note we set this value before actually selecting the coins, but we expect either all
-    // candidates or none to have relative locktime sequence so this is fine.
+    // candidates or none to have a recovery sequence so this is fine.
+    let unlocks_absolute_path = main_descriptor
+        .policy()
+        .absolute_recovery_paths()
+        .keys()
+        .any(|timelock| LockTime::from_consensus(*timelock).is_implied_by(locktime));
     let use_primary_path = !candidate_coins
         .iter()
         .filter_map(|cand| cand.sequence)
-        .any(|seq| seq.is_relative_lock_time());
+        .any(|seq| {
+            seq.is_relative_lock_time()
+                || (unlocks_absolute_path && seq.enables_absolute_lock_time())
+        });
     // Now select the coins necessary using the provided candidates and determine whether
     // there is any leftover to create a change output.
     let CoinSelectionRes {
@@ -778,6 +788,7 @@ pub fn create_spend(
         let mut psbt_in = PsbtIn::default();
         let coin_desc = derived_desc(secp, main_descriptor, cand);
         coin_desc.update_psbt_in(&mut psbt_in);
+        main_descriptor.update_musig_psbt_in(&mut psbt_in);
         psbt_in.witness_utxo = Some(bitcoin::TxOut {
             value: cand.amount,
             script_pubkey: coin_desc.script_pubkey(),
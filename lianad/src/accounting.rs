@@ -0,0 +1,365 @@
+//! # Accounting
+//!
+//! Match the coins disposed of by each of our transactions with the ones we acquired before it,
+//! following a [`CostBasisMethod`]. When the fiat price of bitcoin at the time of the transactions
+//! is known, this gives the cost basis of the disposed coins and the realized gain or loss.
+
+use std::{collections::VecDeque, fmt, str::FromStr};
+
+use miniscript::bitcoin::{self, amount, Amount, SignedAmount};
+use serde::{Deserialize, Serialize};
+
+/// How to match the coins disposed of by a transaction with the ones acquired before it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CostBasisMethod {
+    /// First in, first out: the oldest acquisitions are disposed of first.
+    #[default]
+    Fifo,
+    /// Last in, first out: the most recent acquisitions are disposed of first.
+    Lifo,
+}
+
+impl CostBasisMethod {
+    pub const ALL: [CostBasisMethod; 2] = [CostBasisMethod::Fifo, CostBasisMethod::Lifo];
+}
+
+impl fmt::Display for CostBasisMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Fifo => write!(f, "fifo"),
+            Self::Lifo => write!(f, "lifo"),
+        }
+    }
+}
+
+impl FromStr for CostBasisMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fifo" => Ok(Self::Fifo),
+            "lifo" => Ok(Self::Lifo),
+            _ => Err(format!("Unknown cost basis method: '{}'", s)),
+        }
+    }
+}
+
+/// How a transaction affected our wallet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountingKind {
+    /// We received coins, possibly while spending some of ours (for instance in a payjoin).
+    Receive,
+    /// We paid coins to someone else.
+    Send,
+    /// We only sent coins to ourselves. Only the fee was disposed of.
+    SelfTransfer,
+}
+
+/// The effect of a transaction on the wallet, as input to [`compute_cost_basis`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountingTx {
+    pub txid: bitcoin::Txid,
+    /// Time of the block this transaction was confirmed in, if any.
+    pub time: Option<u32>,
+    pub block_height: Option<i32>,
+    /// Value of the outputs of this transaction paying to us, change included.
+    pub received: Amount,
+    /// Value of our coins spent by this transaction.
+    pub spent: Amount,
+    /// The fee paid by this transaction, if all its inputs are ours.
+    pub fee: Option<Amount>,
+    /// Fiat price of one bitcoin at the time of this transaction, if known.
+    pub price: Option<f64>,
+}
+
+/// A part of a former acquisition disposed of by a transaction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LotDisposal {
+    /// The transaction through which these coins were acquired.
+    pub txid: bitcoin::Txid,
+    pub time: Option<u32>,
+    #[serde(with = "amount::serde::as_sat")]
+    pub amount: Amount,
+    /// Fiat value of this amount at the time it was acquired, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_basis: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountingEntry {
+    pub txid: bitcoin::Txid,
+    pub time: Option<u32>,
+    pub block_height: Option<i32>,
+    pub kind: AccountingKind,
+    /// Change in our balance, fee included. Negative if coins were disposed of.
+    #[serde(with = "amount::serde::as_sat")]
+    pub amount: SignedAmount,
+    #[serde(default, with = "amount::serde::as_sat::opt")]
+    pub fee: Option<Amount>,
+    /// Fiat price of one bitcoin at the time of this transaction, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price: Option<f64>,
+    /// Fiat value of the coins acquired or disposed of, if the price is known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<f64>,
+    /// The former acquisitions disposed of by this transaction.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lots: Vec<LotDisposal>,
+    /// Part of the disposed amount which could not be matched with any former acquisition, for
+    /// instance because it was received before the history we were given.
+    #[serde(with = "amount::serde::as_sat")]
+    pub unmatched: Amount,
+    /// Fiat value of the disposed coins at the time they were acquired. Only known if all of
+    /// them were matched with an acquisition of known price.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_basis: Option<f64>,
+    /// The realized gain (or loss, if negative) in fiat.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gain: Option<f64>,
+}
+
+// Coins acquired by a transaction, not yet disposed of.
+struct Lot {
+    txid: bitcoin::Txid,
+    time: Option<u32>,
+    remaining: Amount,
+    price: Option<f64>,
+}
+
+fn fiat_value(amount: Amount, price: Option<f64>) -> Option<f64> {
+    price.map(|p| amount.to_btc() * p)
+}
+
+/// Go through the given transactions in chronological order, unconfirmed ones last, and match
+/// the coins disposed of by each with the ones acquired before following the given method.
+pub fn compute_cost_basis(txs: &[AccountingTx], method: CostBasisMethod) -> Vec<AccountingEntry> {
+    let mut txs: Vec<&AccountingTx> = txs.iter().collect();
+    txs.sort_by_key(|tx| (tx.time.unwrap_or(u32::MAX), tx.block_height));
+
+    let mut lots = VecDeque::<Lot>::new();
+    let mut entries = Vec::with_capacity(txs.len());
+    for tx in txs {
+        let net = tx.received.to_sat() as i64 - tx.spent.to_sat() as i64;
+        let mut entry = AccountingEntry {
+            txid: tx.txid,
+            time: tx.time,
+            block_height: tx.block_height,
+            kind: AccountingKind::Receive,
+            amount: SignedAmount::from_sat(net),
+            fee: tx.fee,
+            price: tx.price,
+            value: fiat_value(Amount::from_sat(net.unsigned_abs()), tx.price),
+            lots: Vec::new(),
+            unmatched: Amount::ZERO,
+            cost_basis: None,
+            gain: None,
+        };
+
+        if net >= 0 {
+            if net > 0 {
+                lots.push_back(Lot {
+                    txid: tx.txid,
+                    time: tx.time,
+                    remaining: Amount::from_sat(net as u64),
+                    price: tx.price,
+                });
+            }
+            entries.push(entry);
+            continue;
+        }
+
+        let disposed = Amount::from_sat(net.unsigned_abs());
+        entry.kind = if tx.fee == Some(disposed) {
+            AccountingKind::SelfTransfer
+        } else {
+            AccountingKind::Send
+        };
+        let mut to_dispose = disposed;
+        while to_dispose > Amount::ZERO {
+            let lot = match method {
+                CostBasisMethod::Fifo => lots.front_mut(),
+                CostBasisMethod::Lifo => lots.back_mut(),
+            };
+            let lot = match lot {
+                Some(lot) => lot,
+                None => break,
+            };
+            let amount = std::cmp::min(lot.remaining, to_dispose);
+            entry.lots.push(LotDisposal {
+                txid: lot.txid,
+                time: lot.time,
+                amount,
+                cost_basis: fiat_value(amount, lot.price),
+            });
+            lot.remaining -= amount;
+            to_dispose -= amount;
+            if lot.remaining == Amount::ZERO {
+                match method {
+                    CostBasisMethod::Fifo => lots.pop_front(),
+                    CostBasisMethod::Lifo => lots.pop_back(),
+                };
+            }
+        }
+        entry.unmatched = to_dispose;
+        if entry.unmatched == Amount::ZERO {
+            entry.cost_basis = entry
+                .lots
+                .iter()
+                .map(|lot| lot.cost_basis)
+                .sum::<Option<f64>>();
+        }
+        if let (Some(value), Some(cost_basis)) = (entry.value, entry.cost_basis) {
+            entry.gain = Some(value - cost_basis);
+        }
+        entries.push(entry);
+    }
+
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::Hash;
+
+    fn txid(n: u8) -> bitcoin::Txid {
+        bitcoin::Txid::from_byte_array([n; 32])
+    }
+
+    fn tx(
+        n: u8,
+        time: u32,
+        received: u64,
+        spent: u64,
+        fee: Option<u64>,
+        price: Option<f64>,
+    ) -> AccountingTx {
+        AccountingTx {
+            txid: txid(n),
+            time: Some(time),
+            block_height: Some(time as i32),
+            received: Amount::from_sat(received),
+            spent: Amount::from_sat(spent),
+            fee: fee.map(Amount::from_sat),
+            price,
+        }
+    }
+
+    #[test]
+    fn cost_basis_methods() {
+        // Receive 1BTC at 10k, 1BTC at 20k, then pay 1.5BTC (fee included) at 30k. The
+        // transactions are not given in order.
+        let txs = [
+            tx(
+                3,
+                300,
+                50_000_000,
+                200_000_000,
+                Some(10_000),
+                Some(30_000.0),
+            ),
+            tx(1, 100, 100_000_000, 0, None, Some(10_000.0)),
+            tx(2, 200, 100_000_000, 0, None, Some(20_000.0)),
+        ];
+
+        let entries = compute_cost_basis(&txs, CostBasisMethod::Fifo);
+        assert_eq!(
+            entries.iter().map(|e| e.txid).collect::<Vec<_>>(),
+            vec![txid(1), txid(2), txid(3)]
+        );
+        assert_eq!(entries[0].kind, AccountingKind::Receive);
+        assert_eq!(entries[0].amount, SignedAmount::from_sat(100_000_000));
+        assert_eq!(entries[0].value, Some(10_000.0));
+        assert!(entries[0].lots.is_empty() && entries[0].gain.is_none());
+        let spend = &entries[2];
+        assert_eq!(spend.kind, AccountingKind::Send);
+        assert_eq!(spend.amount, SignedAmount::from_sat(-150_000_000));
+        assert_eq!(spend.value, Some(45_000.0));
+        assert_eq!(
+            spend
+                .lots
+                .iter()
+                .map(|l| (l.txid, l.amount.to_sat()))
+                .collect::<Vec<_>>(),
+            vec![(txid(1), 100_000_000), (txid(2), 50_000_000)]
+        );
+        assert_eq!(spend.unmatched, Amount::ZERO);
+        assert_eq!(spend.cost_basis, Some(20_000.0));
+        assert_eq!(spend.gain, Some(25_000.0));
+
+        let entries = compute_cost_basis(&txs, CostBasisMethod::Lifo);
+        let spend = &entries[2];
+        assert_eq!(
+            spend
+                .lots
+                .iter()
+                .map(|l| (l.txid, l.amount.to_sat()))
+                .collect::<Vec<_>>(),
+            vec![(txid(2), 100_000_000), (txid(1), 50_000_000)]
+        );
+        assert_eq!(spend.cost_basis, Some(25_000.0));
+        assert_eq!(spend.gain, Some(20_000.0));
+    }
+
+    #[test]
+    fn cost_basis_lots() {
+        // A self-transfer only disposes of the fee. The remainder of the lot is used afterward.
+        let txs = [
+            tx(1, 100, 100_000, 0, None, Some(10_000.0)),
+            tx(2, 200, 99_000, 100_000, Some(1_000), Some(20_000.0)),
+            tx(3, 300, 0, 99_000, None, Some(20_000.0)),
+        ];
+        let entries = compute_cost_basis(&txs, CostBasisMethod::Fifo);
+        assert_eq!(entries[1].kind, AccountingKind::SelfTransfer);
+        assert_eq!(entries[1].lots[0].amount, Amount::from_sat(1_000));
+        assert_eq!(entries[2].kind, AccountingKind::Send);
+        assert_eq!(entries[2].lots.len(), 1);
+        assert_eq!(entries[2].lots[0].amount, Amount::from_sat(99_000));
+        assert_eq!(entries[2].fee, None);
+
+        // Without prices, the lots are still matched but there is no fiat valuation.
+        let txs = [
+            tx(1, 100, 100_000_000, 0, None, None),
+            tx(2, 200, 0, 100_000_000, Some(1_000), Some(20_000.0)),
+        ];
+        let entries = compute_cost_basis(&txs, CostBasisMethod::Fifo);
+        assert_eq!(entries[0].value, None);
+        assert_eq!(entries[1].value, Some(20_000.0));
+        assert_eq!(entries[1].lots[0].amount, Amount::from_sat(100_000_000));
+        assert_eq!((entries[1].cost_basis, entries[1].gain), (None, None));
+
+        // Coins spent without a known acquisition are reported as unmatched.
+        let txs = [
+            tx(1, 100, 100_000, 0, None, Some(10_000.0)),
+            tx(2, 200, 0, 150_000, Some(1_000), Some(20_000.0)),
+            tx(3, 300, 10_000, 0, None, Some(20_000.0)),
+        ];
+        let entries = compute_cost_basis(&txs, CostBasisMethod::Lifo);
+        assert_eq!(entries[1].unmatched, Amount::from_sat(50_000));
+        assert_eq!((entries[1].cost_basis, entries[1].gain), (None, None));
+        assert_eq!(entries[2].kind, AccountingKind::Receive);
+
+        // Unconfirmed transactions come last.
+        let mut unconfirmed = tx(4, 0, 10_000, 0, None, None);
+        unconfirmed.time = None;
+        let entries = compute_cost_basis(&[unconfirmed, txs[0].clone()], CostBasisMethod::Fifo);
+        assert_eq!(entries[1].txid, txid(4));
+    }
+
+    #[test]
+    fn cost_basis_method_from_str() {
+        for method in CostBasisMethod::ALL.iter() {
+            assert_eq!(
+                CostBasisMethod::from_str(&method.to_string()).unwrap(),
+                *method
+            );
+        }
+        assert_eq!(
+            CostBasisMethod::from_str("FIFO").unwrap(),
+            CostBasisMethod::Fifo
+        );
+        assert!(CostBasisMethod::from_str("hifo").is_err());
+    }
+}
@@ -1,9 +1,9 @@
 #![cfg(not(target_os = "windows"))]
 
-use lianad::config::Config;
+use lianad::{config::Config, payments};
 
 use std::{
-    env,
+    env, fs,
     io::{Read, Write},
     path::PathBuf,
     process,
@@ -16,12 +16,17 @@ use std::os::unix::net::UnixStream;
 // Exits with error
 fn show_usage() {
     eprintln!("Usage:");
-    eprintln!(" liana-cli [--conf conf_path] [--raw] <command> [<param 1> <param 2> ...]");
+    eprintln!(" liana-cli [--conf conf_path] [--raw] [--payments csv_path] <command> [<param 1> <param 2> ...]");
+    eprintln!();
+    eprintln!(" --payments reads the 'destinations' of 'createspend' from a CSV file. Each line of the file");
+    eprintln!(" is either 'address,amount,label' (with the amount in BTC and an optional label) or a BIP21 URI.");
     process::exit(1);
 }
 
-// Returns (Maybe(special conf file), Raw, Method name, Maybe(List of parameters))
-fn parse_args(mut args: Vec<String>) -> (Option<PathBuf>, bool, String, Vec<String>) {
+// Returns (Maybe(special conf file), Raw, Maybe(payments file), Method name, Maybe(List of parameters))
+fn parse_args(
+    mut args: Vec<String>,
+) -> (Option<PathBuf>, bool, Option<PathBuf>, String, Vec<String>) {
     if args.len() < 2 {
         eprintln!("Not enough arguments.");
         show_usage();
@@ -32,6 +37,7 @@ fn parse_args(mut args: Vec<String>) -> (Option<PathBuf>, bool, String, Vec<Stri
     let mut args = args.into_iter();
     let mut raw = false;
     let mut conf_file = None;
+    let mut payments_file = None;
 
     loop {
         match args.next().as_deref() {
@@ -50,7 +56,23 @@ fn parse_args(mut args: Vec<String>) -> (Option<PathBuf>, bool, String, Vec<Stri
                 }
                 raw = true;
             }
-            Some(method) => return (conf_file, raw, method.to_owned(), args.collect()),
+            Some("--payments") => {
+                if args.len() < 2 {
+                    eprintln!("Not enough arguments.");
+                    show_usage();
+                }
+
+                payments_file = Some(PathBuf::from(args.next().expect("Just checked")));
+            }
+            Some(method) => {
+                return (
+                    conf_file,
+                    raw,
+                    payments_file,
+                    method.to_owned(),
+                    args.collect(),
+                )
+            }
             None => {
                 // Should never happen...
                 eprintln!("Not enough arguments.");
@@ -83,6 +105,19 @@ fn rpc_request(method: String, params: Vec<String>) -> Json {
     Json::Object(object)
 }
 
+// Read the payments from this CSV file as a JSON list of destinations.
+fn payments_from_file(path: PathBuf) -> String {
+    let content = fs::read_to_string(&path).unwrap_or_else(|e| {
+        eprintln!("Error reading payments file {:?}: '{}'", path, e);
+        process::exit(1);
+    });
+    let destinations = payments::parse_csv(&content).unwrap_or_else(|e| {
+        eprintln!("Error parsing payments file {:?}: '{}'", path, e);
+        process::exit(1);
+    });
+    serde_json::to_string(&destinations).expect("Serialization can't fail")
+}
+
 fn socket_file(conf_file: Option<PathBuf>) -> PathBuf {
     let config = Config::from_file(conf_file).unwrap_or_else(|e| {
         eprintln!("Error getting config: {}", e);
@@ -111,7 +146,14 @@ fn trimmed(mut vec: Vec<u8>, bytes_read: usize) -> Vec<u8> {
 
 fn main() {
     let args = env::args().collect();
-    let (conf_file, raw, method, params) = parse_args(args);
+    let (conf_file, raw, payments_file, method, mut params) = parse_args(args);
+    if let Some(path) = payments_file {
+        if method != "createspend" {
+            eprintln!("--payments may only be used with 'createspend'.");
+            show_usage();
+        }
+        params.insert(0, payments_from_file(path));
+    }
     let request = rpc_request(method, params);
     let socket_file = socket_file(conf_file);
     let mut raw_response = vec![0; 256];
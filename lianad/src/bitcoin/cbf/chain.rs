@@ -0,0 +1,471 @@
+//! The chain of block headers of the best chain known to our peers.
+//!
+//! The headers are checked to connect to each other, to follow the difficulty rules of the
+//! network and to be valid proofs of work. Among competing chains, the one with the most work is
+//! kept. The headers are persisted to disk in order not to download the whole chain again at
+//! every startup.
+
+use std::{
+    convert::TryInto,
+    fmt, fs,
+    io::{self, Read, Seek, Write},
+    path,
+};
+
+use miniscript::bitcoin::{
+    self, block::Header, consensus, constants, params::Params, BlockHash, CompactTarget, Work,
+};
+
+use crate::bitcoin::BlockChainTip;
+
+// The size of a serialized block header.
+const HEADER_SIZE: usize = 80;
+
+// The number of blocks whose times are considered for the median time past (see BIP113).
+const MEDIAN_TIME_SPAN: usize = 11;
+
+/// An error when connecting new headers to our chain.
+#[derive(Debug)]
+pub enum ChainError {
+    Io(io::Error),
+    /// The first header doesn't connect to any header in our chain.
+    Disconnected(BlockHash),
+    /// The header doesn't follow the previous one.
+    NotContinuous(BlockHash),
+    /// The header's target doesn't follow the difficulty rules of the network.
+    InvalidTarget(BlockHash),
+    /// The header's hash doesn't meet its target.
+    InvalidPow(BlockHash),
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "I/O error with headers file: '{}'", e),
+            Self::Disconnected(hash) => write!(
+                f,
+                "Header '{}' does not connect to our chain of headers",
+                hash
+            ),
+            Self::NotContinuous(hash) => {
+                write!(f, "Header '{}' does not follow the previous header", hash)
+            }
+            Self::InvalidTarget(hash) => write!(f, "Header '{}' has an invalid target", hash),
+            Self::InvalidPow(hash) => write!(f, "Header '{}' has an invalid proof of work", hash),
+        }
+    }
+}
+
+impl From<io::Error> for ChainError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+// What we keep in memory about each header.
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    hash: BlockHash,
+    time: u32,
+    bits: CompactTarget,
+}
+
+impl Entry {
+    fn work(&self) -> Work {
+        bitcoin::Target::from_compact(self.bits).to_work()
+    }
+}
+
+impl From<&Header> for Entry {
+    fn from(header: &Header) -> Self {
+        Entry {
+            hash: header.block_hash(),
+            time: header.time,
+            bits: header.bits,
+        }
+    }
+}
+
+fn height_i32_from_usize(height: usize) -> i32 {
+    height.try_into().expect("height must fit into i32")
+}
+
+/// The chain of headers, from the genesis block to the best block known to our peers.
+pub struct HeaderChain {
+    // The entry at index `i` is the one for the header at height `i`.
+    entries: Vec<Entry>,
+    // The file containing the serialized headers, in the same order.
+    file: fs::File,
+    // The consensus parameters of the network, for the difficulty rules.
+    params: Params,
+}
+
+impl HeaderChain {
+    /// Load the chain of headers stored in this file for this network, creating the file if it
+    /// doesn't exist. If the file is corrupted, the headers following the last valid one are
+    /// dropped.
+    pub fn load(path: &path::Path, network: bitcoin::Network) -> Result<Self, ChainError> {
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        let genesis = constants::genesis_block(network).header;
+        let mut entries = vec![Entry::from(&genesis)];
+        let stored_genesis = data
+            .get(..HEADER_SIZE)
+            .and_then(|chunk| consensus::deserialize::<Header>(chunk).ok());
+        if stored_genesis == Some(genesis) {
+            for (height, chunk) in data.chunks_exact(HEADER_SIZE).enumerate().skip(1) {
+                let header: Header = match consensus::deserialize(chunk) {
+                    Ok(header) => header,
+                    Err(_) => break,
+                };
+                if header.prev_blockhash != entries[height - 1].hash {
+                    log::warn!("Discontinuity in the headers file at height {}.", height);
+                    break;
+                }
+                entries.push(Entry::from(&header));
+            }
+            // Drop any invalid data following the last valid header.
+            file.set_len((entries.len() * HEADER_SIZE) as u64)?;
+        } else {
+            if !data.is_empty() {
+                log::warn!("The headers file is not for this network. Starting over.");
+            }
+            file.set_len(0)?;
+            file.seek(io::SeekFrom::Start(0))?;
+            file.write_all(&consensus::serialize(&genesis))?;
+        }
+
+        let chain = HeaderChain {
+            entries,
+            file,
+            params: Params::new(network),
+        };
+        log::info!(
+            "Loaded {} block headers from disk.",
+            chain.entries.len() - 1
+        );
+
+        Ok(chain)
+    }
+
+    /// The best block of the chain.
+    pub fn tip(&self) -> BlockChainTip {
+        let height = self.entries.len() - 1;
+        BlockChainTip {
+            height: height_i32_from_usize(height),
+            hash: self.entries[height].hash,
+        }
+    }
+
+    /// The hash of the block at this height, if it is part of the chain.
+    pub fn hash_at(&self, height: i32) -> Option<BlockHash> {
+        let height: usize = height.try_into().ok()?;
+        self.entries.get(height).map(|entry| entry.hash)
+    }
+
+    /// The time set in the header of the block at this height, if it is part of the chain.
+    pub fn time_at(&self, height: i32) -> Option<u32> {
+        let height: usize = height.try_into().ok()?;
+        self.entries.get(height).map(|entry| entry.time)
+    }
+
+    /// The median time past (as defined in BIP113) of the block at this height.
+    pub fn median_time_past(&self, height: i32) -> Option<u32> {
+        let height: usize = height.try_into().ok()?;
+        if height >= self.entries.len() {
+            return None;
+        }
+        let start = (height + 1).saturating_sub(MEDIAN_TIME_SPAN);
+        let mut times: Vec<u32> = self.entries[start..=height]
+            .iter()
+            .map(|entry| entry.time)
+            .collect();
+        times.sort_unstable();
+        Some(times[times.len() / 2])
+    }
+
+    /// The last block of the chain with a timestamp below this one. The genesis block if there is
+    /// none.
+    pub fn block_before_date(&self, timestamp: u32) -> BlockChainTip {
+        let height = self
+            .entries
+            .iter()
+            .rposition(|entry| entry.time < timestamp)
+            .unwrap_or(0);
+        BlockChainTip {
+            height: height_i32_from_usize(height),
+            hash: self.entries[height].hash,
+        }
+    }
+
+    /// A locator for the tip of our chain, to be used when asking headers to a peer.
+    pub fn locator(&self) -> Vec<BlockHash> {
+        let mut locator = Vec::new();
+        let mut height = self.entries.len() - 1;
+        let mut step = 1;
+        loop {
+            locator.push(self.entries[height].hash);
+            if height == 0 {
+                break;
+            }
+            // Past the last ten blocks, go exponentially further back.
+            if locator.len() >= 10 {
+                step *= 2;
+            }
+            height = height.saturating_sub(step);
+        }
+        locator
+    }
+
+    // Whether the target of this header follows the difficulty rules of the network, were it to
+    // be appended to our chain up to `fork_height` followed by `new_entries`.
+    //
+    // NOTE: on the test networks which allow for minimum difficulty blocks we only check the
+    // target is not above the maximum.
+    fn valid_target(&self, fork_height: usize, new_entries: &[Entry], header: &Header) -> bool {
+        if header.target() > self.params.max_attainable_target {
+            return false;
+        }
+        if self.params.no_pow_retargeting || self.params.allow_min_difficulty_blocks {
+            return true;
+        }
+
+        let entry_at = |height: usize| {
+            if height <= fork_height {
+                self.entries[height]
+            } else {
+                new_entries[height - fork_height - 1]
+            }
+        };
+        let height = fork_height + new_entries.len() + 1;
+        let prev = entry_at(height - 1);
+        let interval: usize = self
+            .params
+            .difficulty_adjustment_interval()
+            .try_into()
+            .expect("Fits in a usize");
+        // The target only changes at the first block of a difficulty adjustment period, depending
+        // on the time it took to mine the previous period.
+        let expected_bits = if height % interval == 0 {
+            let first = entry_at(height - interval);
+            let timespan = prev.time.saturating_sub(first.time);
+            CompactTarget::from_next_work_required(prev.bits, timespan.into(), &self.params)
+        } else {
+            prev.bits
+        };
+        header.bits == expected_bits
+    }
+
+    /// Connect these headers, sent by a peer, to our chain. If they fork our chain, they replace
+    /// the headers after the fork only if the resulting chain has more work. Returns whether the
+    /// chain was updated.
+    ///
+    /// NOTE: we only check the headers connect to each other, that their target follows the
+    /// difficulty rules of the network and that their proof of work is valid.
+    pub fn connect(&mut self, headers: &[Header]) -> Result<bool, ChainError> {
+        let first = match headers.first() {
+            Some(header) => header,
+            None => return Ok(false),
+        };
+        // The headers usually extend our tip, so look for their parent from there.
+        let fork_height = self
+            .entries
+            .iter()
+            .rposition(|entry| entry.hash == first.prev_blockhash)
+            .ok_or_else(|| ChainError::Disconnected(first.block_hash()))?;
+
+        let mut new_entries: Vec<Entry> = Vec::with_capacity(headers.len());
+        let mut prev_hash = first.prev_blockhash;
+        for header in headers {
+            let hash = header.block_hash();
+            if header.prev_blockhash != prev_hash {
+                return Err(ChainError::NotContinuous(hash));
+            }
+            if !self.valid_target(fork_height, &new_entries, header) {
+                return Err(ChainError::InvalidTarget(hash));
+            }
+            if header.validate_pow(header.target()).is_err() {
+                return Err(ChainError::InvalidPow(hash));
+            }
+            new_entries.push(Entry::from(header));
+            prev_hash = hash;
+        }
+
+        // Only switch to a chain with more work than ours. Among chains with as much work, the
+        // first one we heard of is kept.
+        let sum_work = |entries: &[Entry]| {
+            entries
+                .iter()
+                .fold(Work::from_be_bytes([0; 32]), |work, entry| {
+                    work + entry.work()
+                })
+        };
+        if sum_work(&new_entries) <= sum_work(&self.entries[fork_height + 1..]) {
+            log::debug!(
+                "Ignoring headers forking at height {} as the resulting chain would not have more work.",
+                fork_height
+            );
+            return Ok(false);
+        }
+        if fork_height + 1 < self.entries.len() {
+            log::info!(
+                "Block chain reorganization: replacing headers after height {}.",
+                fork_height
+            );
+        }
+
+        // Update the file first so we never have in memory a header which is not persisted.
+        let offset = ((fork_height + 1) * HEADER_SIZE) as u64;
+        self.file.set_len(offset)?;
+        self.file.seek(io::SeekFrom::Start(offset))?;
+        let mut data = Vec::with_capacity(headers.len() * HEADER_SIZE);
+        for header in headers {
+            data.extend_from_slice(&consensus::serialize(header));
+        }
+        self.file.write_all(&data)?;
+
+        self.entries.truncate(fork_height + 1);
+        self.entries.extend(new_entries);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_chain() {
+        let path = std::env::temp_dir().join(format!("lianad-cbf-headers-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let network = bitcoin::Network::Regtest;
+        let genesis = constants::genesis_block(network).header;
+
+        // Mine a few blocks on top of the genesis block. The regtest target is trivial.
+        let mine = |prev: &Header, nonce_seed: u32| {
+            let mut header = Header {
+                prev_blockhash: prev.block_hash(),
+                time: prev.time + 600,
+                ..*prev
+            };
+            header.nonce = nonce_seed;
+            while header.validate_pow(header.target()).is_err() {
+                header.nonce += 1;
+            }
+            header
+        };
+        let mut headers = vec![mine(&genesis, 0)];
+        for _ in 0..4 {
+            let next = mine(headers.last().unwrap(), 0);
+            headers.push(next);
+        }
+
+        let mut chain = HeaderChain::load(&path, network).unwrap();
+        assert_eq!(chain.tip().height, 0);
+        assert_eq!(chain.tip().hash, genesis.block_hash());
+        assert!(chain.connect(&headers).unwrap());
+        assert_eq!(chain.tip().height, 5);
+        assert_eq!(chain.hash_at(3), Some(headers[2].block_hash()));
+        assert_eq!(chain.hash_at(6), None);
+        assert_eq!(chain.locator()[0], headers[4].block_hash());
+        assert_eq!(*chain.locator().last().unwrap(), genesis.block_hash());
+        assert_eq!(chain.block_before_date(headers[2].time).height, 2);
+        assert_eq!(chain.block_before_date(0).height, 0);
+        assert_eq!(chain.median_time_past(5), Some(headers[2].time));
+
+        // Headers which don't connect are rejected.
+        let orphan = mine(&headers[4], 0);
+        assert!(matches!(
+            chain.connect(&[mine(&orphan, 0)]),
+            Err(ChainError::Disconnected(_))
+        ));
+
+        // A shorter fork is ignored, a longer one replaces our chain after the fork.
+        let fork_a = mine(&headers[2], 1_000_000);
+        assert!(!chain.connect(&[fork_a]).unwrap());
+        assert_eq!(chain.tip().hash, headers[4].block_hash());
+        let fork_b = mine(&fork_a, 0);
+        let fork_c = mine(&fork_b, 0);
+        assert!(chain.connect(&[fork_a, fork_b, fork_c]).unwrap());
+        assert_eq!(chain.tip().height, 6);
+        assert_eq!(chain.hash_at(3), Some(headers[2].block_hash()));
+        assert_eq!(chain.hash_at(4), Some(fork_a.block_hash()));
+
+        // A fork with as much work as our chain is ignored too.
+        let fork_d = mine(&headers[2], 2_000_000);
+        let fork_e = mine(&fork_d, 0);
+        let fork_f = mine(&fork_e, 0);
+        assert!(!chain.connect(&[fork_d, fork_e, fork_f]).unwrap());
+        assert_eq!(chain.tip().hash, fork_c.block_hash());
+
+        // A header with a target above the maximum of the network is rejected.
+        let mut easy = mine(&fork_c, 0);
+        easy.bits = bitcoin::CompactTarget::from_consensus(0x2100ffff);
+        assert!(matches!(
+            chain.connect(&[easy]),
+            Err(ChainError::InvalidTarget(_))
+        ));
+
+        // The chain is persisted.
+        drop(chain);
+        let chain = HeaderChain::load(&path, network).unwrap();
+        assert_eq!(chain.tip().height, 6);
+        assert_eq!(chain.tip().hash, fork_c.block_hash());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn header_chain_difficulty() {
+        let path = std::env::temp_dir().join(format!(
+            "lianad-cbf-headers-difficulty-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        let network = bitcoin::Network::Bitcoin;
+        let genesis = constants::genesis_block(network).header;
+        let mut chain = HeaderChain::load(&path, network).unwrap();
+
+        // Outside of a difficulty adjustment, the target must be the same as the previous block's.
+        let next = |prev: &Header, bits: u32| Header {
+            prev_blockhash: prev.block_hash(),
+            time: prev.time + 600,
+            bits: bitcoin::CompactTarget::from_consensus(bits),
+            ..*prev
+        };
+        assert!(matches!(
+            chain.connect(&[next(&genesis, 0x1c7fff80)]),
+            Err(ChainError::InvalidTarget(_))
+        ));
+        assert!(matches!(
+            chain.connect(&[next(&genesis, 0x1d00ffff)]),
+            Err(ChainError::InvalidPow(_))
+        ));
+
+        // At a difficulty adjustment, the target depends on the time it took to mine the previous
+        // period. Here it was mined twice as fast, so the target is halved.
+        let mut entries = vec![Entry::from(&genesis)];
+        for height in 1..2015 {
+            entries.push(Entry {
+                time: genesis.time + height * 300,
+                ..entries[0]
+            });
+        }
+        entries.push(Entry {
+            time: genesis.time + 2 * 7 * 24 * 60 * 60 / 2,
+            ..entries[0]
+        });
+        chain.entries = entries;
+        assert!(!chain.valid_target(2015, &[], &next(&genesis, 0x1d00ffff)));
+        assert!(chain.valid_target(2015, &[], &next(&genesis, 0x1c7fff80)));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,543 @@
+//! A light client Bitcoin backend, syncing using compact block filters (BIP157/158).
+//!
+//! We download the headers of the best chain from our peers, then the filters of the blocks we
+//! haven't scanned yet. Only the blocks whose filter matches one of our Scripts are downloaded, and
+//! the transactions relevant to our wallet they contain are recorded in a BDK-based wallet. This
+//! way our peers don't learn which addresses are ours. Transactions are broadcast to our peers.
+//!
+//! The filters are checked against the chain of filter headers of our peer. When several peers
+//! are configured, the filter headers are cross-checked with the other ones so that a single peer
+//! can't hide our transactions from us by serving us altered filters.
+//!
+//! Since we don't have a mempool, we only see transactions paying to us once they are confirmed.
+
+use std::{
+    cmp,
+    collections::HashMap,
+    convert::TryInto,
+    fmt, path,
+    sync::{Arc, Mutex, MutexGuard},
+    time,
+};
+
+use bdk_electrum::bdk_chain::bitcoin::{
+    bip158::{BlockFilter, FilterHash, FilterHeader},
+    bip32::ChildNumber,
+    hashes::Hash,
+    OutPoint,
+};
+use miniscript::bitcoin;
+
+pub mod chain;
+pub mod peer;
+use crate::{
+    bitcoin::{electrum::wallet::BdkWallet, Block, BlockChainTip, Coin},
+    config,
+};
+
+// The maximum number of headers a peer sends in a single message.
+const MAX_HEADERS_PER_MESSAGE: usize = 2_000;
+
+// The maximum number of filters we may request at once (see BIP157).
+const MAX_FILTERS_PER_REQUEST: i32 = 1_000;
+
+/// An error in the compact block filters interface.
+#[derive(Debug)]
+pub enum CbfError {
+    NoPeer,
+    Peer(String, peer::Error),
+    Chain(chain::ChainError),
+    Filter(bitcoin::bip158::Error),
+    UnexpectedFilter(bitcoin::BlockHash, bitcoin::BlockHash),
+    InvalidFilterHeaders(String, bitcoin::BlockHash),
+    FilterHeadersMismatch(String, String, bitcoin::BlockHash),
+    InvalidFilter(bitcoin::BlockHash),
+    InvalidBlock(bitcoin::BlockHash),
+}
+
+impl fmt::Display for CbfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoPeer => write!(f, "No peer to connect to."),
+            Self::Peer(addr, e) => write!(f, "Error with peer '{}': '{}'.", addr, e),
+            Self::Chain(e) => write!(f, "Error updating the chain of headers: '{}'.", e),
+            Self::Filter(e) => write!(f, "Error matching a block filter: '{}'.", e),
+            Self::UnexpectedFilter(expected, received) => write!(
+                f,
+                "Expected the filter for block '{}' but received the one for block '{}'.",
+                expected, received
+            ),
+            Self::InvalidFilterHeaders(addr, stop_hash) => write!(
+                f,
+                "Peer '{}' sent invalid filter headers up to block '{}'.",
+                addr, stop_hash
+            ),
+            Self::FilterHeadersMismatch(addr, other_addr, stop_hash) => write!(
+                f,
+                "Peers '{}' and '{}' disagree on the filter headers up to block '{}'.",
+                addr, other_addr, stop_hash
+            ),
+            Self::InvalidFilter(hash) => write!(
+                f,
+                "The filter for block '{}' does not match its filter header.",
+                hash
+            ),
+            Self::InvalidBlock(hash) => write!(f, "Block '{}' is invalid.", hash),
+        }
+    }
+}
+
+// Connect to the first of these peers we can reach.
+fn connect(peers: &[String], network: bitcoin::Network) -> Result<peer::Peer, CbfError> {
+    let mut error = CbfError::NoPeer;
+    for addr in peers {
+        match peer::Peer::connect(addr, network) {
+            Ok(peer) => return Ok(peer),
+            Err(e) => {
+                log::warn!("Could not connect to peer '{}': '{}'.", addr, e);
+                error = CbfError::Peer(addr.clone(), e);
+            }
+        }
+    }
+    Err(error)
+}
+
+fn now() -> u64 {
+    time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// The connection to our peers and the chain of headers of the best chain they know. It is
+/// shared by all the wallets hosted by the daemon.
+#[derive(Clone)]
+pub struct Connection {
+    peers: Vec<String>,
+    network: bitcoin::Network,
+    // The connection to the peer we are using, if any.
+    peer: Arc<Mutex<Option<peer::Peer>>>,
+    headers: Arc<Mutex<chain::HeaderChain>>,
+}
+
+impl Connection {
+    /// Connect to one of the peers of this configuration. The chain of headers is loaded from the
+    /// file at this path.
+    pub fn new(
+        cbf_config: &config::CbfConfig,
+        network: bitcoin::Network,
+        headers_path: &path::Path,
+    ) -> Result<Self, CbfError> {
+        let headers = chain::HeaderChain::load(headers_path, network).map_err(CbfError::Chain)?;
+        let peer = connect(&cbf_config.peers, network)?;
+        Ok(Self {
+            peers: cbf_config.peers.clone(),
+            network,
+            peer: Arc::new(Mutex::new(Some(peer))),
+            headers: Arc::new(Mutex::new(headers)),
+        })
+    }
+
+    // Get the connection to our peer, connecting to one if necessary.
+    fn peer<'a>(&self, peer: &'a mut Option<peer::Peer>) -> Result<&'a mut peer::Peer, CbfError> {
+        if peer.is_none() {
+            *peer = Some(connect(&self.peers, self.network)?);
+        }
+        Ok(peer.as_mut().expect("Just set"))
+    }
+
+    // Connect to the peers besides the one at this address, to cross-check what it tells us. The
+    // peers we can't reach are skipped.
+    fn other_peers(&self, addr: &str) -> Vec<peer::Peer> {
+        self.peers
+            .iter()
+            .filter(|other_addr| *other_addr != addr)
+            .filter_map(
+                |other_addr| match peer::Peer::connect(other_addr, self.network) {
+                    Ok(peer) => Some(peer),
+                    Err(e) => {
+                        log::warn!(
+                            "Could not connect to peer '{}' to check the filter headers: '{}'.",
+                            other_addr,
+                            e
+                        );
+                        None
+                    }
+                },
+            )
+            .collect()
+    }
+}
+
+/// Interface for the compact block filters backend.
+pub struct Cbf {
+    connection: Connection,
+    bdk_wallet: BdkWallet,
+    // The transactions we broadcast since the last sync. They are added to the wallet as
+    // unconfirmed at the next sync.
+    broadcast_txs: Mutex<Vec<bitcoin::Transaction>>,
+    // If set, the filters of the blocks from this date are scanned at the next sync. This is the
+    // case for a new wallet and upon rescan.
+    scan_timestamp: Option<u32>,
+    // Whether the scan from `scan_timestamp` was requested by a rescan.
+    rescanning: bool,
+}
+
+impl Cbf {
+    /// Sync this wallet using this connection to our peers. If `scan_timestamp` is set, the
+    /// filters of the blocks from this date will be scanned at the next sync.
+    pub fn new(
+        connection: Connection,
+        bdk_wallet: BdkWallet,
+        scan_timestamp: Option<u32>,
+        rescanning: bool,
+    ) -> Self {
+        Self {
+            connection,
+            bdk_wallet,
+            broadcast_txs: Mutex::new(Vec::new()),
+            scan_timestamp,
+            rescanning,
+        }
+    }
+
+    /// The connection to our peers used by this interface.
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// Get all coins stored in the wallet.
+    pub fn wallet_coins(&self, outpoints: Option<&[OutPoint]>) -> HashMap<OutPoint, Coin> {
+        self.bdk_wallet.coins(outpoints, None)
+    }
+
+    /// Get the tip of the wallet's local chain.
+    pub fn wallet_tip(&self) -> BlockChainTip {
+        let tip = self.bdk_wallet.local_chain().tip();
+        BlockChainTip {
+            hash: tip.hash(),
+            height: tip.height().try_into().expect("height must fit into i32"),
+        }
+    }
+
+    /// The chain of headers of the best chain known to our peers.
+    pub fn headers(&self) -> MutexGuard<'_, chain::HeaderChain> {
+        self.connection
+            .headers
+            .lock()
+            .expect("Headers lock poisoned")
+    }
+
+    /// Whether we'll scan past blocks at the next poll upon the request of a rescan.
+    pub fn is_rescanning(&self) -> bool {
+        self.rescanning
+    }
+
+    /// Scan the blocks since this date at the next poll.
+    pub fn trigger_rescan(&mut self, timestamp: u32) {
+        self.scan_timestamp = Some(timestamp);
+        self.rescanning = true;
+    }
+
+    /// Send this transaction to our peer.
+    pub fn broadcast_tx(&self, tx: &bitcoin::Transaction) -> Result<(), CbfError> {
+        let mut peer = self.connection.peer.lock().expect("Peer lock poisoned");
+        let res = self.connection.peer(&mut peer)?.broadcast_tx(tx);
+        if let Err(e) = res {
+            let addr = peer.take().expect("Just set").addr().to_string();
+            return Err(CbfError::Peer(addr, e));
+        }
+        self.broadcast_txs
+            .lock()
+            .expect("Broadcast transactions lock poisoned")
+            .push(tx.clone());
+        Ok(())
+    }
+
+    /// Sync the wallet with the best chain of our peers. If there was any reorg since the last
+    /// poll, this returns the latest block of the wallet's chain which is still part of the best
+    /// chain.
+    pub fn sync_wallet(
+        &mut self,
+        receive_index: ChildNumber,
+        change_index: ChildNumber,
+    ) -> Result<Option<BlockChainTip>, CbfError> {
+        self.bdk_wallet.reveal_spks(receive_index, change_index);
+        let seen_at = now();
+        let broadcast_txs: Vec<_> = self
+            .broadcast_txs
+            .get_mut()
+            .expect("Broadcast transactions lock poisoned")
+            .drain(..)
+            .collect();
+        for tx in broadcast_txs {
+            self.bdk_wallet.insert_unconfirmed_tx(tx, seen_at);
+        }
+
+        // The connection is shared with the other wallets, hold it for the whole sync.
+        let connection = self.connection.clone();
+        let mut headers = connection.headers.lock().expect("Headers lock poisoned");
+        let mut peer = connection.peer.lock().expect("Peer lock poisoned");
+        let res = self.sync_headers(&mut headers, &mut peer).and_then(|_| {
+            let reorg_common_ancestor = self.disconnect_stale_blocks(&headers);
+            self.scan_blocks(&headers, &mut peer)?;
+            Ok(reorg_common_ancestor)
+        });
+        // Connect to a peer again at the next sync, possibly to another one.
+        if res.is_err() {
+            *peer = None;
+        }
+        res
+    }
+
+    // Download the headers of the best chain of our peer.
+    fn sync_headers(
+        &self,
+        headers: &mut chain::HeaderChain,
+        peer: &mut Option<peer::Peer>,
+    ) -> Result<(), CbfError> {
+        loop {
+            let locator = headers.locator();
+            let peer = self.connection.peer(peer)?;
+            let new_headers = peer
+                .get_headers(locator)
+                .map_err(|e| CbfError::Peer(peer.addr().to_string(), e))?;
+            let updated = headers.connect(&new_headers).map_err(CbfError::Chain)?;
+            // Don't ask again for headers which would not give us a chain with more work.
+            if !updated || new_headers.len() < MAX_HEADERS_PER_MESSAGE {
+                log::debug!("Headers synced up to '{}'.", headers.tip());
+                return Ok(());
+            }
+        }
+    }
+
+    // Remove the blocks of the wallet's chain which are not part of the best chain anymore.
+    // Returns the latest block of the wallet's chain which still is, if any was removed.
+    fn disconnect_stale_blocks(&mut self, headers: &chain::HeaderChain) -> Option<BlockChainTip> {
+        let wallet_tip = self.wallet_tip();
+        let common_ancestor = self.last_common_block(headers);
+        if common_ancestor == wallet_tip {
+            return None;
+        }
+
+        log::info!(
+            "Block chain reorganization detected. Common ancestor is '{}'.",
+            common_ancestor
+        );
+        let height = common_ancestor
+            .height
+            .try_into()
+            .expect("Height is never negative.");
+        self.bdk_wallet.disconnect_above(height);
+        Some(common_ancestor)
+    }
+
+    /// The latest block of the wallet's chain which is part of the best chain.
+    pub fn common_ancestor(&self) -> BlockChainTip {
+        self.last_common_block(&self.headers())
+    }
+
+    fn last_common_block(&self, headers: &chain::HeaderChain) -> BlockChainTip {
+        self.bdk_wallet
+            .local_chain()
+            .iter_checkpoints()
+            .find(|cp| {
+                headers.hash_at(cp.height().try_into().expect("height must fit into i32"))
+                    == Some(cp.hash())
+            })
+            .map(|cp| BlockChainTip {
+                hash: cp.hash(),
+                height: cp.height().try_into().expect("height must fit into i32"),
+            })
+            .expect("The genesis block is always part of both chains.")
+    }
+
+    // Whether the block of this filter may contain a transaction paying to or spending from one
+    // of our Scripts.
+    fn filter_matches(
+        &self,
+        filter: &bitcoin::p2p::message_filter::CFilter,
+    ) -> Result<bool, CbfError> {
+        // We include the lookahead Scripts.
+        let all_spks = self.bdk_wallet.index().inner().all_spks();
+        BlockFilter::new(&filter.filter)
+            .match_any(
+                &filter.block_hash,
+                all_spks.values().map(|spk| spk.as_bytes()),
+            )
+            .map_err(CbfError::Filter)
+    }
+
+    // Get the hashes of the filters of the blocks from `height` up to the block with hash
+    // `stop_hash` from our peer. They must follow the filter header `prev_filter_header` if it is
+    // known. The filter headers they commit to are checked to be the same for all `other_peers`,
+    // those which fail to answer are not asked again. Returns the filter hashes along with the
+    // filter header of the last block.
+    fn filter_hashes(
+        &self,
+        peer: &mut Option<peer::Peer>,
+        other_peers: &mut Vec<peer::Peer>,
+        height: i32,
+        stop_hash: bitcoin::BlockHash,
+        count: usize,
+        prev_filter_header: Option<FilterHeader>,
+    ) -> Result<(Vec<FilterHash>, FilterHeader), CbfError> {
+        let start_height = height.try_into().expect("Height is never negative.");
+        let peer = self.connection.peer(peer)?;
+        let cfheaders = peer
+            .get_filter_headers(start_height, stop_hash)
+            .map_err(|e| CbfError::Peer(peer.addr().to_string(), e))?;
+        if cfheaders.filter_hashes.len() != count
+            || prev_filter_header.is_some_and(|prev| prev != cfheaders.previous_filter_header)
+        {
+            return Err(CbfError::InvalidFilterHeaders(
+                peer.addr().to_string(),
+                stop_hash,
+            ));
+        }
+
+        // Compare the whole range, as the previous filter header may not have been cross-checked.
+        let mut i = 0;
+        while i < other_peers.len() {
+            let other_peer = &mut other_peers[i];
+            match other_peer.get_filter_headers(start_height, stop_hash) {
+                Ok(other_cfheaders) => {
+                    if other_cfheaders != cfheaders {
+                        return Err(CbfError::FilterHeadersMismatch(
+                            peer.addr().to_string(),
+                            other_peer.addr().to_string(),
+                            stop_hash,
+                        ));
+                    }
+                    i += 1;
+                }
+                Err(e) => {
+                    log::warn!(
+                        "Error getting the filter headers from peer '{}': '{}'.",
+                        other_peer.addr(),
+                        e
+                    );
+                    other_peers.remove(i);
+                }
+            }
+        }
+
+        let last_filter_header = cfheaders
+            .filter_hashes
+            .iter()
+            .fold(cfheaders.previous_filter_header, |prev, filter_hash| {
+                filter_hash.filter_header(&prev)
+            });
+        Ok((cfheaders.filter_hashes, last_filter_header))
+    }
+
+    // Scan the filters of the blocks the wallet didn't scan yet, and apply the matching blocks to
+    // the wallet.
+    fn scan_blocks(
+        &mut self,
+        headers: &chain::HeaderChain,
+        peer: &mut Option<peer::Peer>,
+    ) -> Result<(), CbfError> {
+        let tip = headers.tip();
+        let wallet_tip = self.wallet_tip();
+        let mut height = wallet_tip.height + 1;
+        if let Some(timestamp) = self.scan_timestamp {
+            let start_block = headers.block_before_date(timestamp);
+            height = if self.rescanning {
+                cmp::min(height, start_block.height + 1)
+            } else {
+                // There can't be any transaction for a new wallet before its creation.
+                cmp::max(height, start_block.height + 1)
+            };
+        }
+        let mut other_peers = Vec::new();
+        if height <= tip.height {
+            log::info!(
+                "Scanning block filters from height {} to {}.",
+                height,
+                tip.height
+            );
+            let addr = self.connection.peer(peer)?.addr().to_string();
+            other_peers = self.connection.other_peers(&addr);
+        }
+
+        let mut prev_filter_header = None;
+        while height <= tip.height {
+            let stop_height = cmp::min(height + MAX_FILTERS_PER_REQUEST - 1, tip.height);
+            let stop_hash = headers
+                .hash_at(stop_height)
+                .expect("Height is below the tip.");
+            let count = (stop_height - height + 1) as usize;
+            let (filter_hashes, last_filter_header) = self.filter_hashes(
+                peer,
+                &mut other_peers,
+                height,
+                stop_hash,
+                count,
+                prev_filter_header,
+            )?;
+            let filters = {
+                let peer = self.connection.peer(peer)?;
+                peer.get_filters(
+                    height.try_into().expect("Height is never negative."),
+                    stop_hash,
+                    count,
+                )
+                .map_err(|e| CbfError::Peer(peer.addr().to_string(), e))?
+            };
+
+            for ((filter, filter_hash), filter_height) in
+                filters.iter().zip(filter_hashes.iter()).zip(height..)
+            {
+                let block_hash = headers
+                    .hash_at(filter_height)
+                    .expect("Height is below the tip.");
+                if filter.block_hash != block_hash {
+                    return Err(CbfError::UnexpectedFilter(block_hash, filter.block_hash));
+                }
+                if FilterHash::hash(&filter.filter) != *filter_hash {
+                    return Err(CbfError::InvalidFilter(block_hash));
+                }
+                // Check again for each block, as a matching block may have revealed new Scripts.
+                if !self.filter_matches(filter)? {
+                    continue;
+                }
+
+                log::debug!(
+                    "Filter of block '{}' at height {} matches. Downloading the block.",
+                    block_hash,
+                    filter_height
+                );
+                let peer = self.connection.peer(peer)?;
+                let block = peer
+                    .get_block(block_hash)
+                    .map_err(|e| CbfError::Peer(peer.addr().to_string(), e))?;
+                if !block.check_merkle_root() || !block.check_witness_commitment() {
+                    return Err(CbfError::InvalidBlock(block_hash));
+                }
+                self.bdk_wallet.apply_block(
+                    &block,
+                    filter_height.try_into().expect("Height is never negative."),
+                );
+            }
+            height = stop_height + 1;
+            prev_filter_header = Some(last_filter_header);
+        }
+
+        // The whole chain was scanned.
+        self.bdk_wallet.insert_block(tip);
+        self.scan_timestamp = None;
+        if self.rescanning {
+            log::info!("Rescan complete.");
+            self.rescanning = false;
+        }
+        Ok(())
+    }
+
+    pub fn wallet_transaction(
+        &self,
+        txid: &bitcoin::Txid,
+    ) -> Option<(bitcoin::Transaction, Option<Block>)> {
+        self.bdk_wallet.get_transaction(txid)
+    }
+}
@@ -0,0 +1,294 @@
+//! A minimal client for the Bitcoin P2P protocol. It only implements what we need to sync using
+//! compact block filters: downloading headers, filter headers, filters and blocks and broadcasting
+//! transactions.
+
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    time,
+};
+
+use miniscript::bitcoin::{
+    self,
+    consensus::{self, encode},
+    hashes::Hash,
+    p2p::{
+        address::Address,
+        message::{NetworkMessage, RawNetworkMessage},
+        message_blockdata::{GetHeadersMessage, Inventory},
+        message_filter::{CFHeaders, CFilter, GetCFHeaders, GetCFilters},
+        message_network::VersionMessage,
+        Magic, ServiceFlags,
+    },
+    BlockHash,
+};
+
+// Give up connecting to a peer after this many seconds.
+const CONNECT_TIMEOUT: time::Duration = time::Duration::from_secs(10);
+
+// Give up on a peer which doesn't send us anything for this many seconds while we are waiting for
+// its answer. This is generous as a peer may take a while to load large blocks from disk.
+const READ_TIMEOUT: time::Duration = time::Duration::from_secs(60);
+
+// The size of the header of a P2P message: magic, command, payload length and checksum.
+const MESSAGE_HEADER_SIZE: usize = 24;
+
+// Don't allocate more than this for a message. This is the maximum size of a serialized object
+// accepted by Bitcoin Core.
+const MAX_MESSAGE_SIZE: u32 = 0x0200_0000;
+
+// The type of the only filter defined in BIP158.
+const BASIC_FILTER_TYPE: u8 = 0;
+
+/// An error when communicating with a peer.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Decode(encode::Error),
+    UnresolvedAddress(String),
+    WrongNetwork(Magic),
+    MessageTooLarge(u32),
+    NoCompactFilters,
+    BlockNotFound(BlockHash),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "I/O error: '{}'", e),
+            Self::Decode(e) => write!(f, "Error decoding message: '{}'", e),
+            Self::UnresolvedAddress(addr) => write!(f, "Could not resolve address '{}'", addr),
+            Self::WrongNetwork(magic) => {
+                write!(
+                    f,
+                    "Peer sent a message for another network (magic: {})",
+                    magic
+                )
+            }
+            Self::MessageTooLarge(size) => {
+                write!(f, "Peer sent a message too large ({} bytes)", size)
+            }
+            Self::NoCompactFilters => write!(f, "Peer does not serve compact block filters"),
+            Self::BlockNotFound(hash) => write!(f, "Peer does not have block '{}'", hash),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<encode::Error> for Error {
+    fn from(e: encode::Error) -> Self {
+        Self::Decode(e)
+    }
+}
+
+// A nonce for our version and ping messages. It only needs to differ between messages.
+fn nonce() -> u64 {
+    time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+/// A connection to a peer on the Bitcoin network.
+pub struct Peer {
+    stream: TcpStream,
+    magic: Magic,
+    addr: String,
+}
+
+impl Peer {
+    /// Connect to the peer at this address and perform the version handshake. Fails if the peer
+    /// is not on this network or does not serve compact block filters.
+    pub fn connect(addr: &str, network: bitcoin::Network) -> Result<Peer, Error> {
+        let socket_addr = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| Error::UnresolvedAddress(addr.to_string()))?;
+        let stream = TcpStream::connect_timeout(&socket_addr, CONNECT_TIMEOUT)?;
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        stream.set_write_timeout(Some(READ_TIMEOUT))?;
+
+        let mut peer = Peer {
+            stream,
+            magic: Magic::from(network),
+            addr: addr.to_string(),
+        };
+        peer.handshake(&socket_addr)?;
+        log::info!("Connected to peer '{}'.", peer.addr);
+
+        Ok(peer)
+    }
+
+    /// The address of this peer, as configured.
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    fn handshake(&mut self, socket_addr: &SocketAddr) -> Result<(), Error> {
+        let timestamp = time::SystemTime::now()
+            .duration_since(time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        let local_addr = self.stream.local_addr()?;
+        let version = VersionMessage::new(
+            ServiceFlags::NONE,
+            timestamp,
+            Address::new(socket_addr, ServiceFlags::NONE),
+            Address::new(&local_addr, ServiceFlags::NONE),
+            nonce(),
+            format!("/liana:{}/", env!("CARGO_PKG_VERSION")),
+            0,
+        );
+        self.send(NetworkMessage::Version(version))?;
+
+        let (mut got_version, mut got_verack) = (false, false);
+        while !(got_version && got_verack) {
+            match self.receive()? {
+                NetworkMessage::Version(version) => {
+                    if !version.services.has(ServiceFlags::COMPACT_FILTERS) {
+                        return Err(Error::NoCompactFilters);
+                    }
+                    self.send(NetworkMessage::Verack)?;
+                    got_version = true;
+                }
+                NetworkMessage::Verack => got_verack = true,
+                // Feature negotiation messages (such as 'sendaddrv2' or 'wtxidrelay'). We don't
+                // make use of any.
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn send(&mut self, msg: NetworkMessage) -> Result<(), Error> {
+        let raw_msg = RawNetworkMessage::new(self.magic, msg);
+        self.stream.write_all(&consensus::serialize(&raw_msg))?;
+        Ok(())
+    }
+
+    // Read the next message sent by the peer. Pings are answered as they are received.
+    fn receive(&mut self) -> Result<NetworkMessage, Error> {
+        loop {
+            let mut data = vec![0; MESSAGE_HEADER_SIZE];
+            self.stream.read_exact(&mut data)?;
+            let magic = Magic::from_bytes([data[0], data[1], data[2], data[3]]);
+            if magic != self.magic {
+                return Err(Error::WrongNetwork(magic));
+            }
+            let payload_len = u32::from_le_bytes([data[16], data[17], data[18], data[19]]);
+            if payload_len > MAX_MESSAGE_SIZE {
+                return Err(Error::MessageTooLarge(payload_len));
+            }
+            data.resize(MESSAGE_HEADER_SIZE + payload_len as usize, 0);
+            self.stream.read_exact(&mut data[MESSAGE_HEADER_SIZE..])?;
+
+            let raw_msg: RawNetworkMessage = consensus::deserialize(&data)?;
+            match raw_msg.into_payload() {
+                NetworkMessage::Ping(nonce) => self.send(NetworkMessage::Pong(nonce))?,
+                msg => return Ok(msg),
+            }
+        }
+    }
+
+    /// Get the headers following the first block of the locator which is part of the peer's best
+    /// chain. At most 2000 headers are returned at once.
+    pub fn get_headers(
+        &mut self,
+        locator: Vec<BlockHash>,
+    ) -> Result<Vec<bitcoin::block::Header>, Error> {
+        let msg = GetHeadersMessage::new(locator, BlockHash::all_zeros());
+        self.send(NetworkMessage::GetHeaders(msg))?;
+        loop {
+            if let NetworkMessage::Headers(headers) = self.receive()? {
+                return Ok(headers);
+            }
+        }
+    }
+
+    /// Get the hashes of the basic filters of the blocks from `start_height` up to the block with
+    /// hash `stop_hash`, along with the filter header of the block preceding them. At most 2000
+    /// filter hashes may be requested at once.
+    pub fn get_filter_headers(
+        &mut self,
+        start_height: u32,
+        stop_hash: BlockHash,
+    ) -> Result<CFHeaders, Error> {
+        let msg = GetCFHeaders {
+            filter_type: BASIC_FILTER_TYPE,
+            start_height,
+            stop_hash,
+        };
+        self.send(NetworkMessage::GetCFHeaders(msg))?;
+        loop {
+            if let NetworkMessage::CFHeaders(headers) = self.receive()? {
+                if headers.filter_type == BASIC_FILTER_TYPE && headers.stop_hash == stop_hash {
+                    return Ok(headers);
+                }
+            }
+        }
+    }
+
+    /// Get the basic filters of the `count` blocks from `start_height` up to the block with hash
+    /// `stop_hash`, in this order. At most 1000 filters may be requested at once.
+    pub fn get_filters(
+        &mut self,
+        start_height: u32,
+        stop_hash: BlockHash,
+        count: usize,
+    ) -> Result<Vec<CFilter>, Error> {
+        let msg = GetCFilters {
+            filter_type: BASIC_FILTER_TYPE,
+            start_height,
+            stop_hash,
+        };
+        self.send(NetworkMessage::GetCFilters(msg))?;
+        let mut filters = Vec::with_capacity(count);
+        while filters.len() < count {
+            if let NetworkMessage::CFilter(filter) = self.receive()? {
+                if filter.filter_type == BASIC_FILTER_TYPE {
+                    filters.push(filter);
+                }
+            }
+        }
+        Ok(filters)
+    }
+
+    /// Get the block with this hash, including its witnesses.
+    pub fn get_block(&mut self, hash: BlockHash) -> Result<bitcoin::Block, Error> {
+        self.send(NetworkMessage::GetData(vec![Inventory::WitnessBlock(hash)]))?;
+        loop {
+            match self.receive()? {
+                NetworkMessage::Block(block) if block.block_hash() == hash => return Ok(block),
+                NetworkMessage::NotFound(inv) if inv.contains(&Inventory::WitnessBlock(hash)) => {
+                    return Err(Error::BlockNotFound(hash))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Send this transaction to the peer. The peer does not tell us whether it accepted it, but
+    /// this makes sure it processed it before returning.
+    pub fn broadcast_tx(&mut self, tx: &bitcoin::Transaction) -> Result<(), Error> {
+        self.send(NetworkMessage::Tx(tx.clone()))?;
+
+        // Messages are processed in order. Once the peer answered a ping sent after the
+        // transaction, the transaction was processed.
+        let ping_nonce = nonce();
+        self.send(NetworkMessage::Ping(ping_nonce))?;
+        loop {
+            if let NetworkMessage::Pong(nonce) = self.receive()? {
+                if nonce == ping_nonce {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
@@ -4,7 +4,10 @@
 
 mod utils;
 use crate::{
-    bitcoin::{Block, BlockChainTip},
+    bitcoin::{
+        feerate_vb_from_btc_kvb, Block, BlockChainTip, FeeEstimate, FeeEstimateMode, WatchonlyUTxO,
+        WATCHONLY_RANGE,
+    },
     config,
 };
 use liana::descriptors::LianaDescriptor;
@@ -16,6 +19,7 @@ use std::{
     convert::TryInto,
     fs, io,
     str::FromStr,
+    sync::Arc,
     thread,
     time::Duration,
 };
@@ -209,9 +213,19 @@ impl std::fmt::Display for WalletError {
     }
 }
 
-pub struct BitcoinD {
+/// The connection to bitcoind. It is shared by the interfaces to the watchonly wallets of all the
+/// wallets hosted by the daemon.
+#[derive(Clone)]
+pub struct BitcoindConnection {
     /// Client for generalistic calls.
-    node_client: Client,
+    node_client: Arc<Client>,
+    /// Used to create the clients for the calls related to a watchonly wallet.
+    transport: minreq_http::Builder,
+    addr: String,
+}
+
+pub struct BitcoinD {
+    connection: BitcoindConnection,
     /// A client that will disregard responses to the queries it makes.
     sendonly_client: Client,
     /// A client for calls related to the wallet.
@@ -279,7 +293,11 @@ impl BitcoinD {
                 .build(),
         );
         let dummy_bitcoind = BitcoinD {
-            node_client: dummy_node_client,
+            connection: BitcoindConnection {
+                node_client: Arc::new(dummy_node_client),
+                transport: builder.clone(),
+                addr: config.addr.to_string(),
+            },
             sendonly_client,
             watchonly_client: dummy_wo_client,
             watchonly_wallet_path: watchonly_wallet_path.clone(),
@@ -298,8 +316,26 @@ impl BitcoinD {
                 .timeout(Duration::from_secs(RPC_SOCKET_TIMEOUT))
                 .build(),
         );
+        let connection = BitcoindConnection {
+            node_client: Arc::new(node_client),
+            transport: builder,
+            addr: config.addr.to_string(),
+        };
+        BitcoinD::with_connection(&connection, watchonly_wallet_path)
+    }
+
+    /// Create an interface to this watchonly wallet, reusing an existing connection to bitcoind.
+    pub fn with_connection(
+        connection: &BitcoindConnection,
+        watchonly_wallet_path: String,
+    ) -> Result<BitcoinD, BitcoindError> {
+        let watchonly_url = format!(
+            "http://{}/wallet/{}",
+            connection.addr, watchonly_wallet_path
+        );
         let sendonly_client = Client::with_transport(
-            builder
+            connection
+                .transport
                 .clone()
                 .url(&watchonly_url)
                 .map_err(BitcoindError::from)?
@@ -307,14 +343,16 @@ impl BitcoinD {
                 .build(),
         );
         let watchonly_client = Client::with_transport(
-            builder
+            connection
+                .transport
+                .clone()
                 .url(&watchonly_url)
                 .map_err(BitcoindError::from)?
                 .timeout(Duration::from_secs(RPC_SOCKET_TIMEOUT))
                 .build(),
         );
         Ok(BitcoinD {
-            node_client,
+            connection: connection.clone(),
             sendonly_client,
             watchonly_client,
             watchonly_wallet_path,
@@ -322,6 +360,11 @@ impl BitcoinD {
         })
     }
 
+    /// The connection to bitcoind used by this interface.
+    pub fn connection(&self) -> &BitcoindConnection {
+        &self.connection
+    }
+
     fn check_client(&self, client: &Client) -> Result<(), BitcoindError> {
         if let Err(e) = self.make_request(client, "echo", None) {
             if e.is_warming_up() {
@@ -338,7 +381,7 @@ impl BitcoinD {
     // Make sure bitcoind is reachable through all clients. Note we don't check the sendonly client
     // since it has precisely a very low timeout for the purpose of ignoring responses.
     fn check_connection(&self) -> Result<(), BitcoindError> {
-        self.check_client(&self.node_client)?;
+        self.check_client(&self.connection.node_client)?;
         self.check_client(&self.watchonly_client)?;
         Ok(())
     }
@@ -444,7 +487,7 @@ impl BitcoinD {
         method: &str,
         params: Option<&serde_json::value::RawValue>,
     ) -> Result<Json, BitcoindError> {
-        self.make_request(&self.node_client, method, params)
+        self.make_request(&self.connection.node_client, method, params)
     }
 
     fn make_node_request(
@@ -452,7 +495,7 @@ impl BitcoinD {
         method: &str,
         params: Option<&serde_json::value::RawValue>,
     ) -> Json {
-        self.make_request(&self.node_client, method, params)
+        self.make_request(&self.connection.node_client, method, params)
             .expect("We must not fail to make a request for more than a minute")
     }
 
@@ -880,6 +923,33 @@ impl BitcoinD {
         .is_none()
     }
 
+    /// Get the output at this outpoint if it is unspent, confirmed or not. Unlike wallet calls,
+    /// this isn't restricted to the outputs of our descriptors.
+    pub fn unspent_output(&self, op: &bitcoin::OutPoint) -> Option<bitcoin::TxOut> {
+        let res = self.make_node_request(
+            "gettxout",
+            params!(
+                Json::String(op.txid.to_string()),
+                Json::Number(op.vout.into()),
+                Json::Bool(true) // Whether to account for the spends in mempool.
+            ),
+        );
+        let value = res
+            .get("value")
+            .and_then(Json::as_f64)
+            .and_then(|a| bitcoin::Amount::from_btc(a).ok())?;
+        let script_pubkey = res
+            .get("scriptPubKey")
+            .and_then(|spk| spk.get("hex"))
+            .and_then(Json::as_str)
+            .and_then(|s| bitcoin::ScriptBuf::from_hex(s).ok())
+            .expect("bitcoind can't give a bad scriptPubKey");
+        Some(bitcoin::TxOut {
+            value,
+            script_pubkey,
+        })
+    }
+
     /// So, bitcoind has no API for getting the transaction spending a wallet UTXO. Instead we are
     /// therefore using a rather convoluted way to get it the other way around, since the spending
     /// transaction is actually *part of the wallet transactions*.
@@ -1055,6 +1125,30 @@ impl BitcoinD {
         Ok(())
     }
 
+    /// Check whether this transaction would be accepted in our node's mempool. Returns the
+    /// reason it would be rejected, if any.
+    pub fn test_mempool_accept(
+        &self,
+        tx: &bitcoin::Transaction,
+    ) -> Result<Option<String>, BitcoindError> {
+        let res = self.make_fallible_node_request(
+            "testmempoolaccept",
+            params!(Json::Array(vec![Json::String(
+                bitcoin::consensus::encode::serialize_hex(tx)
+            )])),
+        )?;
+        let res = res.get(0).expect("There is one result per transaction");
+        if res.get("allowed").and_then(Json::as_bool) == Some(true) {
+            return Ok(None);
+        }
+        Ok(Some(
+            res.get("reject-reason")
+                .and_then(Json::as_str)
+                .unwrap_or("unknown")
+                .to_string(),
+        ))
+    }
+
     // For the given descriptor strings check if they are imported at this timestamp in the
     // watchonly wallet.
     fn check_descs_timestamp(
@@ -1096,27 +1190,14 @@ impl BitcoinD {
         Err(BitcoindError::RescanPastPruneHeight)
     }
 
-    pub fn start_rescan(
-        &mut self,
-        desc: &LianaDescriptor,
+    // Import these descriptors to the watchonly wallet at this timestamp, which triggers a rescan
+    // of the block chain from this date.
+    fn import_descriptors_rescan(
+        &self,
+        descs: &[&Descriptor<DescriptorPublicKey>],
         timestamp: u32,
+        range: u32,
     ) -> Result<(), BitcoindError> {
-        // Re-import the receive and change descriptors to the watchonly wallet for the purpose of
-        // rescanning.
-        // The range of the newly imported descriptors supposed to update the existing ones must
-        // have a range inclusive of the existing ones. We always use 0 as the initial index so
-        // this is just determining the maximum index to use.
-        let max_range = self
-            .list_descriptors()
-            .into_iter()
-            // 1_000 is bitcoind's default and what we use at initial import.
-            .fold(1_000, |range, entry| {
-                cmp::max(range, entry.range.map(|r| r[1]).unwrap_or(0))
-            });
-        let descs = [
-            desc.receive_descriptor().as_descriptor_public_key(),
-            desc.change_descriptor().as_descriptor_public_key(),
-        ];
         let desc_json: Vec<Json> = descs
             .iter()
             .map(|desc| {
@@ -1124,7 +1205,7 @@ impl BitcoinD {
                     "desc": desc.to_string(),
                     "timestamp": timestamp,
                     "active": false,
-                    "range": max_range,
+                    "range": range,
                 })
             })
             .collect();
@@ -1155,7 +1236,7 @@ impl BitcoinD {
             }
 
             i += 1;
-            if self.check_descs_timestamp(&descs, timestamp) {
+            if self.check_descs_timestamp(descs, timestamp) {
                 return Ok(());
             } else if i >= NUM_RETRIES {
                 return Err(BitcoindError::StartRescan);
@@ -1166,6 +1247,152 @@ impl BitcoinD {
         }
     }
 
+    /// The last derivation index of the receive and change descriptors of this descriptor
+    /// imported to the watchonly wallet. The Scripts past it are not tracked.
+    pub fn imported_range_end(&self, desc: &LianaDescriptor) -> u32 {
+        let descs = [
+            desc.receive_descriptor()
+                .as_descriptor_public_key()
+                .to_string(),
+            desc.change_descriptor()
+                .as_descriptor_public_key()
+                .to_string(),
+        ];
+        self.list_descriptors()
+            .into_iter()
+            .filter(|entry| {
+                Descriptor::<DescriptorPublicKey>::from_str(&entry.desc)
+                    .map(|entry_desc| descs.contains(&entry_desc.to_string()))
+                    .unwrap_or(false)
+            })
+            .filter_map(|entry| entry.range.map(|r| r[1]))
+            .min()
+            .unwrap_or(0)
+    }
+
+    pub fn start_rescan(
+        &mut self,
+        desc: &LianaDescriptor,
+        timestamp: u32,
+    ) -> Result<(), BitcoindError> {
+        // Re-import the receive and change descriptors to the watchonly wallet for the purpose of
+        // rescanning.
+        // The range of the newly imported descriptors supposed to update the existing ones must
+        // have a range inclusive of the existing ones. We always use 0 as the initial index so
+        // this is just determining the maximum index to use.
+        let max_range = self
+            .list_descriptors()
+            .into_iter()
+            // 1_000 is bitcoind's default and what we use at initial import.
+            .fold(1_000, |range, entry| {
+                cmp::max(range, entry.range.map(|r| r[1]).unwrap_or(0))
+            });
+        let descs = [
+            desc.receive_descriptor().as_descriptor_public_key(),
+            desc.change_descriptor().as_descriptor_public_key(),
+        ];
+        self.import_descriptors_rescan(&descs, timestamp, max_range)
+    }
+
+    /// Import an additional watch-only descriptor to the watchonly wallet, looking for its coins
+    /// since the given date. A multipath descriptor is imported as its single-path descriptors.
+    pub fn import_watchonly_descriptor(
+        &mut self,
+        desc: &Descriptor<DescriptorPublicKey>,
+        timestamp: u32,
+    ) -> Result<(), BitcoindError> {
+        let single_descs = desc
+            .clone()
+            .into_single_descriptors()
+            .expect("Can't error, all paths have the same length");
+        let descs: Vec<_> = single_descs.iter().collect();
+        self.import_descriptors_rescan(&descs, timestamp, WATCHONLY_RANGE)
+    }
+
+    /// Get the coins of the watchonly wallet paying to any of these Scripts, whether they are
+    /// confirmed or not.
+    pub fn list_unspent(&self, spks: &HashSet<&bitcoin::Script>) -> Vec<WatchonlyUTxO> {
+        let tip_height = self.chain_tip().height;
+        self.make_wallet_request(
+            "listunspent",
+            params!(
+                Json::Number(0.into()),         // Minimum confirmations
+                Json::Number(9_999_999.into()), // Maximum confirmations
+                Json::Array(vec![]),            // No filter on the addresses
+                Json::Bool(true)                // Include outputs not safe to spend
+            ),
+        )
+        .as_array()
+        .expect("Always returns an array")
+        .iter()
+        .filter_map(|entry| {
+            let script_pubkey = entry
+                .get("scriptPubKey")
+                .and_then(Json::as_str)
+                .and_then(|s| bitcoin::ScriptBuf::from_hex(s).ok())
+                .expect("bitcoind can't give a bad scriptPubKey");
+            if !spks.contains(script_pubkey.as_script()) {
+                return None;
+            }
+            let txid = entry
+                .get("txid")
+                .and_then(Json::as_str)
+                .and_then(|s| bitcoin::Txid::from_str(s).ok())
+                .expect("bitcoind can't give a bad txid");
+            let vout = entry
+                .get("vout")
+                .and_then(Json::as_u64)
+                .expect("bitcoind can't give a bad vout") as u32;
+            let amount = entry
+                .get("amount")
+                .and_then(Json::as_f64)
+                .and_then(|a| bitcoin::Amount::from_btc(a).ok())
+                .expect("bitcoind won't give us a bad amount");
+            let confirmations = entry
+                .get("confirmations")
+                .and_then(Json::as_i64)
+                .expect("bitcoind always gives the number of confirmations")
+                as i32;
+            Some(WatchonlyUTxO {
+                outpoint: bitcoin::OutPoint { txid, vout },
+                amount,
+                script_pubkey,
+                block_height: (confirmations > 0).then(|| tip_height - confirmations + 1),
+            })
+        })
+        .collect()
+    }
+
+    /// Get those of these Scripts which received coins in any transaction of the watchonly wallet,
+    /// confirmed or not.
+    ///
+    /// NOTE: only the Scripts within the range of the imported descriptors are tracked.
+    pub fn received_spks(&self, spks: &HashSet<&bitcoin::Script>) -> HashSet<bitcoin::ScriptBuf> {
+        self.make_wallet_request(
+            "listreceivedbyaddress",
+            params!(
+                Json::Number(0.into()), // Minimum confirmations
+                Json::Bool(false),      // Don't include addresses which didn't receive anything
+                Json::Bool(true)        // Whether to include watchonly
+            ),
+        )
+        .as_array()
+        .expect("Always returns an array")
+        .iter()
+        .filter_map(|entry| {
+            let script_pubkey = entry
+                .get("address")
+                .and_then(Json::as_str)
+                .and_then(|s| bitcoin::Address::from_str(s).ok())
+                .expect("bitcoind can't give a bad address")
+                .assume_checked()
+                .script_pubkey();
+            spks.contains(script_pubkey.as_script())
+                .then_some(script_pubkey)
+        })
+        .collect()
+    }
+
     /// Get the progress of the ongoing rescan, if there is any.
     pub fn rescan_progress(&self) -> Option<f64> {
         self.make_wallet_request("getwalletinfo", None)
@@ -1231,6 +1458,50 @@ impl BitcoinD {
             .collect()
     }
 
+    /// Get a feerate estimate for a confirmation within `conf_target` blocks using
+    /// `estimatesmartfee`. The estimate is never lower than the minimum feerate for a transaction
+    /// to be accepted in our mempool.
+    ///
+    /// Returns `None` if bitcoind does not have enough data to provide an estimate.
+    pub fn estimate_smart_fee(
+        &self,
+        conf_target: u16,
+        mode: FeeEstimateMode,
+    ) -> Option<FeeEstimate> {
+        let res = self.make_node_request(
+            "estimatesmartfee",
+            params!(
+                Json::Number(conf_target.into()),
+                Json::String(mode.to_arg().to_string()),
+            ),
+        );
+        // The feerate is absent from the result if there isn't enough data to provide an estimate.
+        let feerate_vb = match res.get("feerate").and_then(Json::as_f64) {
+            Some(feerate) => feerate_vb_from_btc_kvb(feerate)
+                .expect("bitcoind must return a valid feerate in 'estimatesmartfee'"),
+            None => {
+                log::debug!("No feerate estimate available: {:?}", res.get("errors"));
+                return None;
+            }
+        };
+        let blocks = res
+            .get("blocks")
+            .and_then(Json::as_u64)
+            .and_then(|b| b.try_into().ok())
+            .expect("No valid 'blocks' in 'estimatesmartfee' response?");
+        let min_feerate_vb = self
+            .make_node_request("getmempoolinfo", None)
+            .get("mempoolminfee")
+            .and_then(Json::as_f64)
+            .and_then(feerate_vb_from_btc_kvb)
+            .expect("No valid 'mempoolminfee' in 'getmempoolinfo' response?");
+
+        Some(FeeEstimate {
+            feerate_vb: cmp::max(feerate_vb, min_feerate_vb),
+            blocks,
+        })
+    }
+
     /// Stop bitcoind.
     pub fn stop(&self) {
         self.make_node_request("stop", None);
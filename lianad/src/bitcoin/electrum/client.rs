@@ -1,4 +1,4 @@
-use std::{collections::HashSet, convert::TryInto};
+use std::{cmp, collections::HashSet, convert::TryInto};
 
 use bdk_electrum::{
     bdk_chain::{
@@ -12,10 +12,14 @@ use bdk_electrum::{
 };
 
 use super::utils::{
-    block_id_from_tip, height_i32_from_usize, height_usize_from_i32, outpoints_from_tx,
+    block_id_from_tip, feerate_from_histogram, height_i32_from_usize, height_usize_from_i32,
+    outpoints_from_tx,
 };
 use crate::{
-    bitcoin::{electrum::utils::tip_from_block_id, BlockChainTip, MempoolEntry, MempoolEntryFees},
+    bitcoin::{
+        electrum::utils::tip_from_block_id, feerate_vb_from_btc_kvb, BlockChainTip, MempoolEntry,
+        MempoolEntryFees, WatchonlyUTxO,
+    },
     config,
 };
 
@@ -118,6 +122,135 @@ impl Client {
             .map(|bh| bh.time)
     }
 
+    /// Get the median of the timestamps of the last 11 blocks, as defined in BIP113.
+    pub fn tip_median_time_past(&self) -> Result<u32, Error> {
+        let tip_height = self.chain_tip()?.height;
+        self.median_time_past(tip_height)
+    }
+
+    /// Get the median of the timestamps of the 11 blocks up to the one at this height, as
+    /// defined in BIP113.
+    pub fn median_time_past(&self, height: i32) -> Result<u32, Error> {
+        let height = height_usize_from_i32(height);
+        let start_height = height.saturating_sub(10);
+        let mut times: Vec<_> = self
+            .0
+            .inner
+            .block_headers(start_height, height + 1 - start_height)
+            .map_err(Error::Server)?
+            .headers
+            .into_iter()
+            .map(|bh| bh.time)
+            .collect();
+        times.sort_unstable();
+        times.get(times.len() / 2).copied().ok_or_else(|| {
+            Error::Server(electrum_client::Error::Message(
+                "No block header returned.".to_string(),
+            ))
+        })
+    }
+
+    /// Get the mempool fee histogram from the server, as a list of `(feerate, vsize)` pairs in
+    /// decreasing feerate order.
+    fn fee_histogram(&self) -> Result<Vec<(f64, u64)>, Error> {
+        let res = self
+            .0
+            .inner
+            .raw_call(
+                "mempool.get_fee_histogram",
+                Vec::<electrum_client::Param>::new(),
+            )
+            .map_err(Error::Server)?;
+        Ok(res
+            .as_array()
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter_map(|entry| {
+                        let entry = entry.as_array()?;
+                        Some((entry.first()?.as_f64()?, entry.get(1)?.as_u64()?))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// Get a feerate estimate, in sats/vb, for a confirmation within `conf_target` blocks.
+    ///
+    /// If the server has no estimate available, one is computed from its mempool fee histogram.
+    /// The estimate is never lower than the server's minimum relay feerate.
+    pub fn estimate_fee(&self, conf_target: u16) -> Result<u64, Error> {
+        let relay_feerate_vb = self
+            .0
+            .inner
+            .relay_fee()
+            .map_err(Error::Server)
+            .map(|f| feerate_vb_from_btc_kvb(f).unwrap_or(1))?;
+        let estimate = self
+            .0
+            .inner
+            .estimate_fee(conf_target.into())
+            .map_err(Error::Server)?;
+        // The server returns -1 if it does not have enough data to provide an estimate.
+        let feerate_vb = if estimate > 0.0 {
+            feerate_vb_from_btc_kvb(estimate)
+        } else {
+            feerate_from_histogram(&self.fee_histogram()?, conf_target)
+        };
+        // If the mempool would be cleared within the target, the minimum relay feerate is enough.
+        Ok(cmp::max(
+            feerate_vb.unwrap_or(relay_feerate_vb),
+            relay_feerate_vb,
+        ))
+    }
+
+    /// Get the unspent coins paying to any of these Scripts, whether they are confirmed or not.
+    pub fn list_unspent(&self, spks: &[bitcoin::ScriptBuf]) -> Result<Vec<WatchonlyUTxO>, Error> {
+        let mut utxos = Vec::new();
+        for spks in spks.chunks(DEFAULT_BATCH_SIZE) {
+            let res = self
+                .0
+                .inner
+                .batch_script_list_unspent(spks.iter().map(|spk| spk.as_script()))
+                .map_err(Error::Server)?;
+            for (spk, entries) in spks.iter().zip(res) {
+                utxos.extend(entries.into_iter().map(|entry| WatchonlyUTxO {
+                    outpoint: bitcoin::OutPoint {
+                        txid: entry.tx_hash,
+                        vout: entry.tx_pos.try_into().expect("vout must fit into u32"),
+                    },
+                    amount: bitcoin::Amount::from_sat(entry.value),
+                    script_pubkey: spk.clone(),
+                    // The height is 0 for unconfirmed transactions.
+                    block_height: (entry.height > 0).then(|| height_i32_from_usize(entry.height)),
+                }));
+            }
+        }
+        Ok(utxos)
+    }
+
+    /// Get those of these Scripts which appear in any transaction, confirmed or not.
+    pub fn used_spks(
+        &self,
+        spks: &[bitcoin::ScriptBuf],
+    ) -> Result<HashSet<bitcoin::ScriptBuf>, Error> {
+        let mut used = HashSet::new();
+        for spks in spks.chunks(DEFAULT_BATCH_SIZE) {
+            let res = self
+                .0
+                .inner
+                .batch_script_get_history(spks.iter().map(|spk| spk.as_script()))
+                .map_err(Error::Server)?;
+            used.extend(
+                spks.iter()
+                    .zip(res)
+                    .filter(|(_, history)| !history.is_empty())
+                    .map(|(spk, _)| spk.clone()),
+            );
+        }
+        Ok(used)
+    }
+
     /// Returns a reference to the wrapped `BdkElectrumClient`.
     pub fn bdk_electrum_client(&self) -> &BdkElectrumClient<electrum_client::Client> {
         &self.0
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use bdk_electrum::bdk_chain::{
     bitcoin::{self, bip32::ChildNumber, BlockHash, OutPoint},
@@ -8,7 +8,7 @@ use bdk_electrum::bdk_chain::{
 };
 
 pub mod client;
-mod utils;
+pub mod utils;
 pub mod wallet;
 use crate::bitcoin::{Block, BlockChainTip, Coin};
 
@@ -41,7 +41,8 @@ impl std::fmt::Display for ElectrumError {
 
 /// Interface for Electrum backend.
 pub struct Electrum {
-    client: client::Client,
+    /// The client is shared by the wallets hosted by the daemon.
+    client: Arc<client::Client>,
     bdk_wallet: wallet::BdkWallet,
     /// Used for setting the `last_seen` of unconfirmed transactions in a strictly
     /// increasing manner.
@@ -49,19 +50,24 @@ pub struct Electrum {
     /// Set to `true` to force a full scan from the genesis block regardless of
     /// the wallet's local chain height.
     full_scan: bool,
+    /// The number of consecutive unused Scripts after which we stop looking for transactions
+    /// during a full scan.
+    stop_gap: usize,
 }
 
 impl Electrum {
     pub fn new(
-        client: client::Client,
+        client: Arc<client::Client>,
         bdk_wallet: wallet::BdkWallet,
         full_scan: bool,
+        stop_gap: usize,
     ) -> Result<Self, ElectrumError> {
         Ok(Self {
             client,
             bdk_wallet,
             sync_count: 0,
             full_scan,
+            stop_gap,
         })
     }
 
@@ -82,7 +88,7 @@ impl Electrum {
         Ok(())
     }
 
-    pub fn client(&self) -> &client::Client {
+    pub fn client(&self) -> &Arc<client::Client> {
         &self.client
     }
 
@@ -135,7 +141,6 @@ impl Electrum {
         // We'll only need to calculate fees of mempool transactions and this will be done separately from our graph
         // so we don't need to fetch prev txouts. In any case, we'll already have these for our own transactions.
         const FETCH_PREV_TXOUTS: bool = false;
-        const STOP_GAP: usize = 200;
 
         // TODO: See if this caching can be done in a more optimal way, e.g. only new txs after syncing.
         self.client
@@ -174,7 +179,7 @@ impl Electrum {
                 .client
                 .full_scan_with_confirmation_time_height_anchor(
                     request,
-                    STOP_GAP,
+                    self.stop_gap,
                     FETCH_PREV_TXOUTS,
                 )
                 .map_err(ElectrumError::Client)?;
@@ -4,6 +4,9 @@ use bdk_electrum::bdk_chain::{bitcoin, BlockId, ConfirmationTimeHeightAnchor};
 
 use crate::bitcoin::{BlockChainTip, BlockInfo};
 
+/// The maximum virtual size of a block.
+const MAX_BLOCK_VSIZE: u64 = 1_000_000;
+
 pub fn height_u32_from_i32(height: i32) -> u32 {
     height.try_into().expect("height must fit into u32")
 }
@@ -53,3 +56,36 @@ pub fn outpoints_from_tx(tx: &bitcoin::Transaction) -> Vec<bitcoin::OutPoint> {
         })
         .collect::<Vec<_>>()
 }
+
+/// Estimate the feerate, in sats/vb, necessary for a transaction to be included within
+/// `conf_target` blocks given a mempool fee histogram: a list of `(feerate, vsize)` pairs in
+/// decreasing feerate order as returned by `mempool.get_fee_histogram`.
+///
+/// Returns `None` if the whole mempool would be included within `conf_target` blocks.
+pub fn feerate_from_histogram(histogram: &[(f64, u64)], conf_target: u16) -> Option<u64> {
+    let target_vsize = MAX_BLOCK_VSIZE.saturating_mul(conf_target.into());
+    let mut cumulated_vsize: u64 = 0;
+    for (feerate, vsize) in histogram {
+        cumulated_vsize = cumulated_vsize.saturating_add(*vsize);
+        if cumulated_vsize >= target_vsize {
+            // We need to outbid the transactions in this bucket to be included.
+            return Some((feerate.floor() as u64).saturating_add(1));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_feerate() {
+        let histogram = [(50.0, 500_000), (20.5, 600_000), (5.0, 2_000_000)];
+        assert_eq!(feerate_from_histogram(&histogram, 1), Some(21));
+        assert_eq!(feerate_from_histogram(&histogram, 2), Some(6));
+        assert_eq!(feerate_from_histogram(&histogram, 3), Some(6));
+        assert_eq!(feerate_from_histogram(&histogram, 4), None);
+        assert_eq!(feerate_from_histogram(&[], 1), None);
+    }
+}
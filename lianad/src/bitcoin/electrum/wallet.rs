@@ -329,4 +329,47 @@ impl BdkWallet {
     pub fn apply_keychain_update(&mut self, keychain_update: BTreeMap<KeychainType, u32>) {
         let _ = self.graph.index.reveal_to_target_multi(&keychain_update);
     }
+
+    /// Insert a block in the local chain.
+    pub fn insert_block(&mut self, tip: BlockChainTip) {
+        if let Err(e) = self.local_chain.insert_block(block_id_from_tip(tip)) {
+            log::error!(
+                "Could not insert block '{}' in the local chain: {:?}",
+                tip,
+                e
+            );
+        }
+    }
+
+    /// Apply the transactions of this block which are relevant to the wallet, and insert the
+    /// block in the local chain.
+    pub fn apply_block(&mut self, block: &bitcoin::Block, height: u32) {
+        let _ = self.graph.apply_block_relevant(block, height);
+        self.insert_block(BlockChainTip {
+            hash: block.block_hash(),
+            height: height_i32_from_u32(height),
+        });
+    }
+
+    /// Remove the blocks above this height from the local chain.
+    pub fn disconnect_above(&mut self, height: u32) {
+        let first_stale = self
+            .local_chain
+            .iter_checkpoints()
+            .take_while(|cp| cp.height() > height)
+            .last()
+            .map(|cp| cp.block_id());
+        if let Some(block_id) = first_stale {
+            self.local_chain
+                .disconnect_from(block_id)
+                .expect("The genesis block is never disconnected.");
+        }
+    }
+
+    /// Insert a transaction which isn't confirmed yet, last seen at this time.
+    pub fn insert_unconfirmed_tx(&mut self, tx: bitcoin::Transaction, seen_at: u64) {
+        let txid = tx.compute_txid();
+        let _ = self.graph.insert_tx(tx);
+        let _ = self.graph.insert_seen_at(txid, seen_at);
+    }
 }
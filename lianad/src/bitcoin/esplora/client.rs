@@ -0,0 +1,497 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use miniscript::bitcoin::{
+    self,
+    consensus::{self, encode},
+    hashes::{sha256, Hash},
+    BlockHash, Txid,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{
+    bitcoin::{BlockChainTip, MempoolEntry, MempoolEntryFees, WatchonlyUTxO},
+    config,
+};
+
+// If the server takes more than 3 minutes to answer one of our queries, fail.
+const REQUEST_TIMEOUT_SECS: u64 = 180;
+
+// When first connecting, don't wait too long for the server to answer.
+const PING_TIMEOUT_SECS: u64 = 3;
+
+// The number of confirmed transactions returned at once by the server for a Script. If a page
+// is full there may be more.
+const CONFIRMED_TXS_PER_PAGE: usize = 25;
+
+/// An error in the Esplora client.
+#[derive(Debug)]
+pub enum Error {
+    Http(minreq::Error),
+    Status(i32, String),
+    InvalidResponse(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Http(e) => write!(f, "Esplora HTTP error: '{}'.", e),
+            Error::Status(code, body) => {
+                write!(f, "Esplora server error: status {} ('{}').", code, body)
+            }
+            Error::InvalidResponse(e) => write!(f, "Invalid response from Esplora: '{}'.", e),
+        }
+    }
+}
+
+impl From<encode::Error> for Error {
+    fn from(e: encode::Error) -> Self {
+        Error::InvalidResponse(e.to_string())
+    }
+}
+
+/// Confirmation status of a transaction, as returned by the server.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TxStatus {
+    pub confirmed: bool,
+    pub block_height: Option<u32>,
+    pub block_hash: Option<BlockHash>,
+    pub block_time: Option<u64>,
+}
+
+/// An input of a transaction, as returned by the server.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TxIn {
+    pub txid: Txid,
+    pub vout: u32,
+    pub is_coinbase: bool,
+}
+
+/// A transaction, as returned by the server.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Tx {
+    pub txid: Txid,
+    pub vin: Vec<TxIn>,
+    pub weight: u64,
+    pub fee: u64,
+    pub status: TxStatus,
+}
+
+/// The spending status of a transaction output, as returned by the server.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OutSpend {
+    pub spent: bool,
+    pub txid: Option<Txid>,
+    pub status: Option<TxStatus>,
+}
+
+/// Information about a block, as returned by the server.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlockInfo {
+    pub id: BlockHash,
+    pub height: u32,
+    pub timestamp: u32,
+    pub mediantime: u32,
+}
+
+/// The number of transactions involving a Script, as returned by the server.
+#[derive(Debug, Clone, Deserialize)]
+struct ScriptTxStats {
+    tx_count: u64,
+}
+
+/// Statistics about a Script, as returned by the server.
+#[derive(Debug, Clone, Deserialize)]
+struct ScriptStats {
+    chain_stats: ScriptTxStats,
+    mempool_stats: ScriptTxStats,
+}
+
+/// An unspent transaction output, as returned by the server.
+#[derive(Debug, Clone, Deserialize)]
+struct Utxo {
+    txid: Txid,
+    vout: u32,
+    value: u64,
+    status: TxStatus,
+}
+
+// The Script hash used by the server to index Scripts. Unlike for Electrum it is not reversed.
+fn script_hash(spk: &bitcoin::Script) -> String {
+    sha256::Hash::hash(spk.as_bytes()).to_string()
+}
+
+/// Get the feerate, in sats/vb rounded up, to be confirmed within `conf_target` blocks from the
+/// estimates returned by the server, a map from confirmation target to feerate in sats/vb.
+///
+/// The estimate for the lowest target at least as high as `conf_target` is used. If there is
+/// none, the estimate for the highest target is used. Returns the feerate along with its target.
+pub fn feerate_from_estimates(
+    estimates: &HashMap<u16, f64>,
+    conf_target: u16,
+) -> Option<(u64, u16)> {
+    let target = estimates
+        .keys()
+        .filter(|target| **target >= conf_target)
+        .min()
+        .or_else(|| estimates.keys().max())?;
+    let feerate = estimates.get(target)?.ceil() as u64;
+    Some((feerate.max(1), *target))
+}
+
+pub struct Client {
+    // The URL of the REST API, without trailing slash.
+    url: String,
+}
+
+impl Client {
+    /// Create a new client and check we can reach the server.
+    pub fn new(esplora_config: &config::EsploraConfig) -> Result<Self, Error> {
+        let client = Client {
+            url: esplora_config.addr.trim_end_matches('/').to_string(),
+        };
+        let url = format!("{}/blocks/tip/height", client.url);
+        let resp = minreq::get(url)
+            .with_timeout(PING_TIMEOUT_SECS)
+            .send()
+            .map_err(Error::Http)?;
+        if resp.status_code != 200 {
+            return Err(Error::Status(
+                resp.status_code,
+                resp.as_str().unwrap_or_default().to_string(),
+            ));
+        }
+        Ok(client)
+    }
+
+    // Query this path of the API. Returns `None` if the server doesn't know about the
+    // requested object.
+    fn get(&self, path: &str) -> Result<Option<minreq::Response>, Error> {
+        let url = format!("{}{}", self.url, path);
+        log::trace!("Esplora request: GET '{}'.", url);
+        let resp = minreq::get(url)
+            .with_timeout(REQUEST_TIMEOUT_SECS)
+            .send()
+            .map_err(Error::Http)?;
+        match resp.status_code {
+            200 => Ok(Some(resp)),
+            404 => Ok(None),
+            code => Err(Error::Status(
+                code,
+                resp.as_str().unwrap_or_default().to_string(),
+            )),
+        }
+    }
+
+    fn get_text(&self, path: &str) -> Result<Option<String>, Error> {
+        self.get(path)?
+            .map(|resp| {
+                resp.as_str()
+                    .map(|s| s.trim().to_string())
+                    .map_err(|e| Error::InvalidResponse(e.to_string()))
+            })
+            .transpose()
+    }
+
+    fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<Option<T>, Error> {
+        self.get(path)?
+            .map(|resp| {
+                serde_json::from_slice(resp.as_bytes())
+                    .map_err(|e| Error::InvalidResponse(e.to_string()))
+            })
+            .transpose()
+    }
+
+    // For objects which must always exist, such as the tip of the chain.
+    fn required<T>(res: Option<T>, path: &str) -> Result<T, Error> {
+        res.ok_or_else(|| Error::InvalidResponse(format!("'{}' not found.", path)))
+    }
+
+    /// Get the hash of the block at this height in the best chain, if there is one.
+    pub fn block_hash(&self, height: i32) -> Result<Option<BlockHash>, Error> {
+        self.get_text(&format!("/block-height/{}", height))?
+            .map(|hash| {
+                hash.parse()
+                    .map_err(|e| Error::InvalidResponse(format!("{}", e)))
+            })
+            .transpose()
+    }
+
+    /// Get information about the block with this hash.
+    pub fn block_info(&self, hash: &BlockHash) -> Result<BlockInfo, Error> {
+        let path = format!("/block/{}", hash);
+        Self::required(self.get_json(&path)?, &path)
+    }
+
+    /// Get the block at this height in the best chain.
+    pub fn block_at(&self, height: i32) -> Result<bitcoin::Block, Error> {
+        let hash = Self::required(self.block_hash(height)?, "/block-height")?;
+        let path = format!("/block/{}/raw", hash);
+        let resp = Self::required(self.get(&path)?, &path)?;
+        consensus::deserialize(resp.as_bytes()).map_err(Error::from)
+    }
+
+    fn block_info_at(&self, height: i32) -> Result<BlockInfo, Error> {
+        let hash = Self::required(self.block_hash(height)?, "/block-height")?;
+        self.block_info(&hash)
+    }
+
+    pub fn chain_tip(&self) -> Result<BlockChainTip, Error> {
+        let path = "/blocks/tip/hash";
+        let hash: BlockHash = Self::required(self.get_text(path)?, path)?
+            .parse()
+            .map_err(|e| Error::InvalidResponse(format!("{}", e)))?;
+        let info = self.block_info(&hash)?;
+        Ok(BlockChainTip {
+            hash,
+            height: info.height as i32,
+        })
+    }
+
+    pub fn genesis_block(&self) -> Result<BlockChainTip, Error> {
+        let hash = Self::required(self.block_hash(0)?, "/block-height/0")?;
+        Ok(BlockChainTip { hash, height: 0 })
+    }
+
+    pub fn genesis_block_timestamp(&self) -> Result<u32, Error> {
+        self.block_info_at(0).map(|info| info.timestamp)
+    }
+
+    pub fn tip_time(&self) -> Result<u32, Error> {
+        let tip = self.chain_tip()?;
+        self.block_info(&tip.hash).map(|info| info.timestamp)
+    }
+
+    /// Get the median of the timestamps of the last 11 blocks, as defined in BIP113.
+    pub fn tip_median_time_past(&self) -> Result<u32, Error> {
+        let tip = self.chain_tip()?;
+        self.block_info(&tip.hash).map(|info| info.mediantime)
+    }
+
+    /// Get the median time past, as defined in BIP113, of the block at this height.
+    pub fn median_time_past(&self, height: i32) -> Result<u32, Error> {
+        self.block_info_at(height).map(|info| info.mediantime)
+    }
+
+    /// Get all the transactions paying to or spending from this Script, whether they are
+    /// confirmed or not.
+    pub fn script_txs(&self, spk: &bitcoin::Script) -> Result<Vec<Tx>, Error> {
+        let hash = script_hash(spk);
+        let path = format!("/scripthash/{}/txs", hash);
+        // The first page contains the unconfirmed transactions and the latest confirmed ones.
+        let mut txs: Vec<Tx> = self.get_json(&path)?.unwrap_or_default();
+        let mut confirmed_count = txs.iter().filter(|tx| tx.status.confirmed).count();
+        while confirmed_count == CONFIRMED_TXS_PER_PAGE {
+            let last_txid = txs.last().expect("Page is not empty").txid;
+            let path = format!("/scripthash/{}/txs/chain/{}", hash, last_txid);
+            let page: Vec<Tx> = self.get_json(&path)?.unwrap_or_default();
+            confirmed_count = page.len();
+            txs.extend(page);
+        }
+        Ok(txs)
+    }
+
+    /// Get the transaction with this txid, if the server knows about it.
+    pub fn tx(&self, txid: &Txid) -> Result<Option<bitcoin::Transaction>, Error> {
+        self.get(&format!("/tx/{}/raw", txid))?
+            .map(|resp| consensus::deserialize(resp.as_bytes()).map_err(Error::from))
+            .transpose()
+    }
+
+    // Get information about the transaction with this txid, if the server knows about it.
+    fn tx_info(&self, txid: &Txid) -> Result<Option<Tx>, Error> {
+        self.get_json(&format!("/tx/{}", txid))
+    }
+
+    // Get the spending status of all the outputs of this transaction.
+    fn outspends(&self, txid: &Txid) -> Result<Vec<OutSpend>, Error> {
+        Ok(self
+            .get_json(&format!("/tx/{}/outspends", txid))?
+            .unwrap_or_default())
+    }
+
+    /// Get the spending status of this output.
+    pub fn outspend(&self, outpoint: &bitcoin::OutPoint) -> Result<Option<OutSpend>, Error> {
+        self.get_json(&format!("/tx/{}/outspend/{}", outpoint.txid, outpoint.vout))
+    }
+
+    pub fn broadcast_tx(&self, tx: &bitcoin::Transaction) -> Result<Txid, Error> {
+        let url = format!("{}/tx", self.url);
+        let resp = minreq::post(url)
+            .with_header("Content-Type", "text/plain")
+            .with_body(consensus::encode::serialize_hex(tx))
+            .with_timeout(REQUEST_TIMEOUT_SECS)
+            .send()
+            .map_err(Error::Http)?;
+        let body = resp
+            .as_str()
+            .map_err(|e| Error::InvalidResponse(e.to_string()))?;
+        if resp.status_code != 200 {
+            return Err(Error::Status(resp.status_code, body.to_string()));
+        }
+        body.trim()
+            .parse()
+            .map_err(|e| Error::InvalidResponse(format!("{}", e)))
+    }
+
+    /// Get the server's feerate estimates, as a map from confirmation target to feerate in
+    /// sats/vb.
+    pub fn fee_estimates(&self) -> Result<HashMap<u16, f64>, Error> {
+        let path = "/fee-estimates";
+        let estimates: HashMap<String, f64> = Self::required(self.get_json(path)?, path)?;
+        Ok(estimates
+            .into_iter()
+            .filter_map(|(target, feerate)| Some((target.parse().ok()?, feerate)))
+            .collect())
+    }
+
+    /// Get the unspent coins paying to any of these Scripts, whether they are confirmed or not.
+    pub fn list_unspent(&self, spks: &[bitcoin::ScriptBuf]) -> Result<Vec<WatchonlyUTxO>, Error> {
+        let mut utxos = Vec::new();
+        for spk in spks {
+            let path = format!("/scripthash/{}/utxo", script_hash(spk));
+            let entries: Vec<Utxo> = self.get_json(&path)?.unwrap_or_default();
+            utxos.extend(entries.into_iter().map(|entry| WatchonlyUTxO {
+                outpoint: bitcoin::OutPoint {
+                    txid: entry.txid,
+                    vout: entry.vout,
+                },
+                amount: bitcoin::Amount::from_sat(entry.value),
+                script_pubkey: spk.clone(),
+                block_height: entry.status.block_height.map(|h| h as i32),
+            }));
+        }
+        Ok(utxos)
+    }
+
+    /// Get those of these Scripts which appear in any transaction, confirmed or not.
+    pub fn used_spks(
+        &self,
+        spks: &[bitcoin::ScriptBuf],
+    ) -> Result<HashSet<bitcoin::ScriptBuf>, Error> {
+        let mut used = HashSet::new();
+        for spk in spks {
+            let path = format!("/scripthash/{}", script_hash(spk));
+            let stats: ScriptStats = Self::required(self.get_json(&path)?, &path)?;
+            if stats.chain_stats.tx_count + stats.mempool_stats.tx_count > 0 {
+                used.insert(spk.clone());
+            }
+        }
+        Ok(used)
+    }
+
+    /// Get the mempool entry for this transaction, or `None` if it is not unconfirmed.
+    ///
+    /// The server doesn't keep track of transaction packages, so we compute the ancestors' and
+    /// descendants' fees by walking through the unconfirmed parents and children.
+    pub fn mempool_entry(&self, txid: &Txid) -> Result<Option<MempoolEntry>, Error> {
+        let tx = match self.tx_info(txid)? {
+            Some(tx) if !tx.status.confirmed => tx,
+            _ => return Ok(None),
+        };
+        let vsize = tx.weight.div_ceil(4);
+        let (mut ancestor_fees, mut ancestor_vsize) = (tx.fee, vsize);
+        let mut descendant_fees = tx.fee;
+
+        let mut visited = HashSet::from([tx.txid]);
+        let mut to_visit: Vec<Txid> = tx
+            .vin
+            .iter()
+            .filter(|txin| !txin.is_coinbase)
+            .map(|txin| txin.txid)
+            .collect();
+        while let Some(txid) = to_visit.pop() {
+            if !visited.insert(txid) {
+                continue;
+            }
+            if let Some(anc) = self.tx_info(&txid)?.filter(|tx| !tx.status.confirmed) {
+                ancestor_fees += anc.fee;
+                ancestor_vsize += anc.weight.div_ceil(4);
+                to_visit.extend(anc.vin.iter().map(|txin| txin.txid));
+            }
+        }
+
+        let mut visited = HashSet::from([tx.txid]);
+        let mut to_visit = vec![tx.txid];
+        while let Some(txid) = to_visit.pop() {
+            for spender in self.outspends(&txid)? {
+                let spender_txid = match spender.txid {
+                    Some(txid) if spender.spent => txid,
+                    _ => continue,
+                };
+                if !visited.insert(spender_txid) {
+                    continue;
+                }
+                if let Some(desc) = self
+                    .tx_info(&spender_txid)?
+                    .filter(|tx| !tx.status.confirmed)
+                {
+                    descendant_fees += desc.fee;
+                    to_visit.push(spender_txid);
+                }
+            }
+        }
+
+        Ok(Some(MempoolEntry {
+            vsize,
+            fees: MempoolEntryFees {
+                base: bitcoin::Amount::from_sat(tx.fee),
+                ancestor: bitcoin::Amount::from_sat(ancestor_fees),
+                descendant: bitcoin::Amount::from_sat(descendant_fees),
+            },
+            ancestor_vsize,
+        }))
+    }
+
+    /// Get the mempool entries of the unconfirmed transactions spending these outpoints.
+    pub fn mempool_spenders(
+        &self,
+        outpoints: &[bitcoin::OutPoint],
+    ) -> Result<Vec<MempoolEntry>, Error> {
+        let mut spenders = HashSet::new();
+        for outpoint in outpoints {
+            if let Some(OutSpend {
+                spent: true,
+                txid: Some(txid),
+                status,
+            }) = self.outspend(outpoint)?
+            {
+                if !status.map(|s| s.confirmed).unwrap_or(false) {
+                    spenders.insert(txid);
+                }
+            }
+        }
+        let mut entries = Vec::with_capacity(spenders.len());
+        for txid in spenders {
+            entries.extend(self.mempool_entry(&txid)?);
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_feerate() {
+        let estimates = HashMap::from([(1, 20.5), (2, 15.0), (6, 10.2), (144, 0.4)]);
+        assert_eq!(feerate_from_estimates(&estimates, 1), Some((21, 1)));
+        assert_eq!(feerate_from_estimates(&estimates, 2), Some((15, 2)));
+        assert_eq!(feerate_from_estimates(&estimates, 3), Some((11, 6)));
+        assert_eq!(feerate_from_estimates(&estimates, 144), Some((1, 144)));
+        assert_eq!(feerate_from_estimates(&estimates, 1008), Some((1, 144)));
+        assert_eq!(feerate_from_estimates(&HashMap::new(), 1), None);
+    }
+
+    #[test]
+    fn script_hash_not_reversed() {
+        // The empty Script hashes to the SHA256 of the empty string.
+        assert_eq!(
+            script_hash(&bitcoin::ScriptBuf::new()),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
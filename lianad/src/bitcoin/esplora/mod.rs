@@ -0,0 +1,352 @@
+//! A Bitcoin backend using the REST API of an Esplora server (such as electrs-esplora or
+//! mempool.space).
+//!
+//! The server indexes the transactions of all Scripts. We query it for the history of each of our
+//! Scripts and record the transactions in a BDK-based wallet. Once synced, we only download the
+//! new blocks and query the history of the few Scripts which may be involved in an unconfirmed
+//! transaction of ours, unless we fell too far behind.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
+};
+
+use bdk_electrum::bdk_chain::{
+    bitcoin::{self, bip32::ChildNumber, OutPoint},
+    tx_graph::TxGraph,
+    BlockId, ConfirmationTimeHeightAnchor,
+};
+
+pub mod client;
+use crate::bitcoin::{
+    electrum::{
+        utils::{block_id_from_tip, height_i32_from_u32, height_u32_from_i32, tip_from_block_id},
+        wallet::BdkWallet,
+    },
+    Block, BlockChainTip, Coin,
+};
+
+// If the wallet is more than this many blocks behind the server, query the history of all our
+// Scripts instead of downloading the new blocks.
+const MAX_NEW_BLOCKS: i32 = 6;
+
+/// An error in the Esplora interface.
+#[derive(Debug)]
+pub enum EsploraError {
+    Client(client::Error),
+    GenesisHashMismatch(
+        bitcoin::BlockHash, /*expected hash*/
+        bitcoin::BlockHash, /*server hash*/
+        bitcoin::BlockHash, /*wallet hash*/
+    ),
+}
+
+impl std::fmt::Display for EsploraError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EsploraError::Client(e) => write!(f, "Esplora client error: '{}'.", e),
+            EsploraError::GenesisHashMismatch(expected, server, wallet) => {
+                write!(
+                    f,
+                    "Genesis hash mismatch. The genesis hash is expected to be '{}'. \
+                    The server has hash '{}' and the wallet has hash '{}'.",
+                    expected, server, wallet,
+                )
+            }
+        }
+    }
+}
+
+/// Interface for the Esplora backend.
+pub struct Esplora {
+    /// The client is shared by the wallets hosted by the daemon.
+    client: Arc<client::Client>,
+    bdk_wallet: BdkWallet,
+    /// Used for setting the `last_seen` of unconfirmed transactions in a strictly
+    /// increasing manner.
+    sync_count: u64,
+    /// Set to `true` to force a full scan of our Scripts regardless of the wallet's local chain
+    /// height.
+    full_scan: bool,
+    /// The number of consecutive unused Scripts after which we stop looking for transactions
+    /// during a full scan.
+    stop_gap: u32,
+}
+
+impl Esplora {
+    pub fn new(
+        client: Arc<client::Client>,
+        bdk_wallet: BdkWallet,
+        full_scan: bool,
+        stop_gap: u32,
+    ) -> Self {
+        Self {
+            client,
+            bdk_wallet,
+            sync_count: 0,
+            full_scan,
+            stop_gap,
+        }
+    }
+
+    pub fn sanity_checks(&self, expected_hash: &bitcoin::BlockHash) -> Result<(), EsploraError> {
+        let server_hash = self
+            .client
+            .genesis_block()
+            .map_err(EsploraError::Client)?
+            .hash;
+        let wallet_hash = self.bdk_wallet.local_chain().genesis_hash();
+        if server_hash != *expected_hash || wallet_hash != *expected_hash {
+            return Err(EsploraError::GenesisHashMismatch(
+                *expected_hash,
+                server_hash,
+                wallet_hash,
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn client(&self) -> &Arc<client::Client> {
+        &self.client
+    }
+
+    /// Get all coins stored in the wallet, taking into consideration only those unconfirmed
+    /// transactions that were seen in the last wallet sync.
+    pub fn wallet_coins(&self, outpoints: Option<&[OutPoint]>) -> HashMap<OutPoint, Coin> {
+        self.bdk_wallet.coins(outpoints, Some(self.sync_count))
+    }
+
+    /// Get the tip of the wallet's local chain.
+    pub fn wallet_tip(&self) -> BlockChainTip {
+        tip_from_block_id(self.bdk_wallet.local_chain().tip().block_id())
+    }
+
+    /// Whether `tip` exists in the wallet's `local_chain`.
+    ///
+    /// Returns `None` if no block at that height exists in `local_chain`.
+    pub fn is_in_wallet_chain(&self, tip: BlockChainTip) -> Option<bool> {
+        self.bdk_wallet.is_in_chain(tip)
+    }
+
+    /// Whether we'll perform a full scan at the next poll.
+    pub fn is_rescanning(&self) -> bool {
+        self.full_scan || self.bdk_wallet.local_chain().tip().height() == 0
+    }
+
+    /// Make the poller perform a full scan on the next iteration.
+    pub fn trigger_rescan(&mut self) {
+        self.full_scan = true;
+    }
+
+    // Remove the blocks of the wallet's chain which are not part of the server's best chain
+    // anymore. Returns the latest block of the wallet's chain which still is, if any was removed.
+    fn disconnect_stale_blocks(&mut self) -> Result<Option<BlockChainTip>, EsploraError> {
+        let checkpoints: Vec<_> = self
+            .bdk_wallet
+            .local_chain()
+            .iter_checkpoints()
+            .map(|cp| cp.block_id())
+            .collect();
+        for (i, block) in checkpoints.iter().enumerate() {
+            let hash = self
+                .client
+                .block_hash(height_i32_from_u32(block.height))
+                .map_err(EsploraError::Client)?;
+            if hash != Some(block.hash) {
+                continue;
+            }
+            if i == 0 {
+                return Ok(None);
+            }
+            log::info!("Block chain reorganization detected.");
+            self.bdk_wallet.disconnect_above(block.height);
+            return Ok(Some(tip_from_block_id(*block)));
+        }
+        unreachable!("The genesis block was checked at startup.")
+    }
+
+    // The Scripts which may be involved in an unconfirmed transaction of ours: those of the
+    // revealed addresses we didn't receive coins on yet and those of our coins which aren't spent
+    // by a confirmed transaction.
+    fn mempool_spks(&self) -> HashSet<bitcoin::ScriptBuf> {
+        let mut spks: HashSet<_> = self
+            .bdk_wallet
+            .index()
+            .unused_spks()
+            .map(|(_, spk)| spk.to_owned())
+            .collect();
+        spks.extend(
+            self.wallet_coins(None)
+                .values()
+                .filter(|coin| coin.spend_block.is_none())
+                .filter_map(|coin| self.bdk_wallet.graph().get_txout(coin.outpoint))
+                .map(|txout| txout.script_pubkey.clone()),
+        );
+        spks
+    }
+
+    // Record in this graph update the transactions of this Script which aren't in it yet. Returns
+    // whether there was any.
+    fn fetch_script_txs(
+        &self,
+        spk: &bitcoin::Script,
+        tip: BlockId,
+        graph_update: &mut TxGraph<ConfirmationTimeHeightAnchor>,
+    ) -> Result<bool, EsploraError> {
+        let txs = self.client.script_txs(spk).map_err(EsploraError::Client)?;
+        for tx_info in &txs {
+            let txid = tx_info.txid;
+            if graph_update.get_tx(txid).is_none() {
+                let tx = match self.bdk_wallet.graph().get_tx(txid) {
+                    Some(tx) => tx,
+                    None => match self.client.tx(&txid).map_err(EsploraError::Client)? {
+                        Some(tx) => tx.into(),
+                        // It may have been evicted from the mempool in the meantime.
+                        None => continue,
+                    },
+                };
+                let _ = graph_update.insert_tx(tx);
+            }
+            match (tx_info.status.block_height, tx_info.status.block_time) {
+                // Ignore confirmations above the tip we are syncing to. We'll get them at the
+                // next poll.
+                (Some(height), Some(time)) if tx_info.status.confirmed && height <= tip.height => {
+                    let _ = graph_update.insert_anchor(
+                        txid,
+                        ConfirmationTimeHeightAnchor {
+                            confirmation_height: height,
+                            confirmation_time: time,
+                            anchor_block: tip,
+                        },
+                    );
+                }
+                _ => {
+                    let _ = graph_update.insert_seen_at(txid, self.sync_count);
+                }
+            }
+        }
+        Ok(!txs.is_empty())
+    }
+
+    /// Sync the wallet with the Esplora server. If there was any reorg since the last poll, this
+    /// returns the first common ancestor between the previous and the new chain.
+    pub fn sync_wallet(
+        &mut self,
+        receive_index: ChildNumber,
+        change_index: ChildNumber,
+    ) -> Result<Option<BlockChainTip>, EsploraError> {
+        self.bdk_wallet.reveal_spks(receive_index, change_index);
+        let reorg_common_ancestor = self.disconnect_stale_blocks()?;
+        let tip = self.client.chain_tip().map_err(EsploraError::Client)?;
+        let tip_id = block_id_from_tip(tip);
+        log::debug!("Syncing wallet with Esplora up to tip '{}'.", tip);
+
+        // If we were already synced in this session and are only a few blocks behind, we download
+        // the new blocks and only query the history of the Scripts which may be involved in an
+        // unconfirmed transaction. We'd only see an unconfirmed payment to an address we didn't
+        // reveal yet, or reuse, once it's confirmed.
+        let wallet_tip = self.wallet_tip();
+        let mempool_spks = if !self.is_rescanning()
+            && self.sync_count > 0
+            && reorg_common_ancestor.is_none()
+            && tip.height - wallet_tip.height <= MAX_NEW_BLOCKS
+        {
+            Some(self.mempool_spks())
+        } else {
+            None
+        };
+
+        // Increment the sync count before recording the unconfirmed transactions, so those we
+        // don't see anymore are ignored.
+        self.sync_count = self.sync_count.checked_add(1).expect("must fit");
+        let mut graph_update = TxGraph::default();
+        let mut keychain_update = None;
+        let mut new_blocks = Vec::new();
+        if let Some(mempool_spks) = mempool_spks {
+            for height in wallet_tip.height + 1..=tip.height {
+                let block = self.client.block_at(height).map_err(EsploraError::Client)?;
+                new_blocks.push((block, height_u32_from_i32(height)));
+            }
+            log::debug!(
+                "num new blocks: {}, num SPKs for sync: {}",
+                new_blocks.len(),
+                mempool_spks.len()
+            );
+            for spk in mempool_spks {
+                self.fetch_script_txs(&spk, tip_id, &mut graph_update)?;
+            }
+            log::debug!("Sync complete.");
+        } else if !self.is_rescanning() {
+            // We include the lookahead Scripts.
+            let all_spks: Vec<_> = self
+                .bdk_wallet
+                .index()
+                .inner()
+                .all_spks()
+                .values()
+                .cloned()
+                .collect();
+            log::debug!("num SPKs for sync: {}", all_spks.len());
+            for spk in all_spks {
+                self.fetch_script_txs(&spk, tip_id, &mut graph_update)?;
+            }
+            log::debug!("Sync complete.");
+        } else {
+            log::info!("Performing full scan.");
+            let mut last_active_indices = BTreeMap::new();
+            for (keychain, spks) in self.bdk_wallet.index().all_unbounded_spk_iters() {
+                let mut unused_count = 0;
+                for (index, spk) in spks {
+                    if self.fetch_script_txs(&spk, tip_id, &mut graph_update)? {
+                        last_active_indices.insert(keychain, index);
+                        unused_count = 0;
+                    } else {
+                        unused_count += 1;
+                        if unused_count >= self.stop_gap {
+                            break;
+                        }
+                    }
+                }
+            }
+            // A full scan only makes sense to do once, in most cases. Don't do it again unless
+            // explicitly asked to by a user.
+            self.full_scan = false;
+            keychain_update = Some(last_active_indices);
+            log::info!("Full scan complete.");
+        }
+
+        // If the chain changed while we were syncing, some transactions may have been recorded as
+        // confirmed in a block which is now stale. Start over.
+        let new_tip = self.client.chain_tip().map_err(EsploraError::Client)?;
+        if new_tip != tip {
+            log::debug!("Chain tip changed while syncing. Restarting.");
+            if keychain_update.is_some() {
+                self.full_scan = true;
+            }
+            // Report the lowest common ancestor if we detected a reorg both times.
+            return self
+                .sync_wallet(receive_index, change_index)
+                .map(|ancestor| match (ancestor, reorg_common_ancestor) {
+                    (Some(a), Some(b)) => Some(if a.height < b.height { a } else { b }),
+                    (a, b) => a.or(b),
+                });
+        }
+
+        if let Some(keychain_update) = keychain_update {
+            self.bdk_wallet.apply_keychain_update(keychain_update);
+        }
+        for (block, height) in new_blocks {
+            self.bdk_wallet.apply_block(&block, height);
+        }
+        self.bdk_wallet.insert_block(tip);
+        self.bdk_wallet.apply_graph_update(graph_update);
+        Ok(reorg_common_ancestor)
+    }
+
+    pub fn wallet_transaction(
+        &self,
+        txid: &bitcoin::Txid,
+    ) -> Option<(bitcoin::Transaction, Option<Block>)> {
+        self.bdk_wallet.get_transaction(txid)
+    }
+}
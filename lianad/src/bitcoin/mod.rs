@@ -2,23 +2,45 @@
 //!
 //! Broadcast transactions, poll for new unspent coins, gather fee estimates.
 
+pub mod cbf;
 pub mod d;
 pub mod electrum;
+pub mod esplora;
 pub mod poller;
 
 use crate::bitcoin::d::{BitcoindError, CachedTxGetter, LSBlockEntry};
 pub use d::{MempoolEntry, MempoolEntryFees, SyncProgress};
 use liana::descriptors;
 
-use std::{fmt, sync};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, sync,
+};
 
-use miniscript::bitcoin::{self, address, bip32::ChildNumber};
+use miniscript::{
+    bitcoin::{self, address, bip32::ChildNumber},
+    descriptor::{Descriptor, DescriptorPublicKey},
+};
 
 // A spent coin's outpoint together with its spend transaction's txid, height and time.
 type SpentCoin = (bitcoin::OutPoint, bitcoin::Txid, i32, u32);
 
 const COINBASE_MATURITY: i32 = 100;
 
+// The error returned by the backends which can't test the mempool acceptance of a transaction.
+const BACKEND_NO_MEMPOOL_ACCEPT: &str = "Testing mempool acceptance requires the bitcoind backend.";
+
+// The errors returned when syncing using compact block filters for the queries about other
+// Scripts or outputs than the wallet's.
+const CBF_NO_USED_SPKS: &str =
+    "Looking for used addresses is not supported when syncing using compact block filters.";
+const CBF_NO_UNSPENT_OUTPUTS: &str =
+    "Querying the UTxO set is not supported when syncing using compact block filters.";
+
+/// The number of addresses we watch for each additional watch-only descriptor. This is bitcoind's
+/// default range for imported descriptors.
+pub const WATCHONLY_RANGE: u32 = 1_000;
+
 /// Information about a block
 #[derive(Debug, Clone, Eq, PartialEq, Copy)]
 pub struct Block {
@@ -40,6 +62,52 @@ impl fmt::Display for BlockChainTip {
     }
 }
 
+/// The fee estimation mode, following the semantics of bitcoind's `estimatesmartfee`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeeEstimateMode {
+    /// Less responsive to short-term drops in the fee market. Potentially higher estimate.
+    Conservative,
+    /// More responsive to short-term drops in the fee market. Potentially lower estimate.
+    Economical,
+}
+
+impl FeeEstimateMode {
+    pub fn from_arg(s: &str) -> Option<FeeEstimateMode> {
+        match s {
+            "conservative" => Some(FeeEstimateMode::Conservative),
+            "economical" => Some(FeeEstimateMode::Economical),
+            _ => None,
+        }
+    }
+
+    /// Converts a `FeeEstimateMode` to its equivalent argument name
+    /// as used in the `estimatefee` RPC command.
+    pub fn to_arg(&self) -> &'static str {
+        match self {
+            FeeEstimateMode::Conservative => "conservative",
+            FeeEstimateMode::Economical => "economical",
+        }
+    }
+}
+
+/// A feerate estimate from the Bitcoin backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeEstimate {
+    /// The estimated feerate in sats/vb, rounded up.
+    pub feerate_vb: u64,
+    /// The number of blocks within which a transaction at this feerate is expected to confirm.
+    /// This may be higher than the requested target if no estimate was available for it.
+    pub blocks: u16,
+}
+
+/// Convert a feerate in BTC/kvB, as returned by both bitcoind and Electrum, to a feerate in
+/// sats/vb rounded up.
+pub fn feerate_vb_from_btc_kvb(feerate: f64) -> Option<u64> {
+    bitcoin::Amount::from_btc(feerate)
+        .ok()
+        .map(|sat_kvb| sat_kvb.to_sat().div_ceil(1_000))
+}
+
 /// Our Bitcoin backend.
 pub trait BitcoinInterface: Send {
     fn genesis_block_timestamp(&self) -> u32;
@@ -57,6 +125,15 @@ pub trait BitcoinInterface: Send {
     /// Get the timestamp set in the best block's header.
     fn tip_time(&self) -> Option<u32>;
 
+    /// Get the median time past (as defined in BIP113) of the best block. A transaction with a
+    /// time-based nLockTime lower than this may be included in the next block.
+    fn tip_median_time_past(&self) -> Option<u32>;
+
+    /// Get the median time past (as defined in BIP113) of the block at this height in the best
+    /// chain. Time-based relative timelocks are measured from the median time past of the block
+    /// preceding the one a coin was confirmed in (see BIP68).
+    fn median_time_past(&self, height: i32) -> Option<u32>;
+
     /// Check whether this former tip is part of the current best chain.
     fn is_in_chain(&self, tip: &BlockChainTip) -> bool;
 
@@ -105,6 +182,11 @@ pub trait BitcoinInterface: Send {
     /// Broadcast this transaction to the Bitcoin P2P network
     fn broadcast_tx(&self, tx: &bitcoin::Transaction) -> Result<(), String>;
 
+    /// Check whether this transaction would be accepted in the mempool, without broadcasting
+    /// it. Returns the reason it would be rejected, if any. Only supported by `bitcoind`, other
+    /// backends return an error.
+    fn test_mempool_accept(&self, tx: &bitcoin::Transaction) -> Result<Option<String>, String>;
+
     /// Trigger a rescan of the block chain for transactions related to this descriptor since
     /// the given date.
     fn start_rescan(
@@ -133,6 +215,41 @@ pub trait BitcoinInterface: Send {
     ///
     /// Returns `None` if the transaction is not in the mempool.
     fn mempool_entry(&self, txid: &bitcoin::Txid) -> Option<MempoolEntry>;
+
+    /// Estimate the feerate necessary for a transaction to confirm within `conf_target` blocks.
+    ///
+    /// Returns `None` if the backend does not have enough data to provide an estimate. This is
+    /// always the case when syncing using compact block filters.
+    fn estimate_fee(&self, conf_target: u16, mode: FeeEstimateMode) -> Option<FeeEstimate>;
+
+    /// Start watching an additional descriptor, looking for its coins since the given date.
+    fn import_watchonly_descriptor(
+        &mut self,
+        desc: &Descriptor<DescriptorPublicKey>,
+        timestamp: u32,
+    ) -> Result<(), String>;
+
+    /// Get the unspent coins, confirmed or not, paying to any of these Scripts. The Scripts must
+    /// have been derived from an imported watch-only descriptor.
+    fn watchonly_coins(&self, spks: &[bitcoin::ScriptBuf]) -> Vec<WatchonlyUTxO>;
+
+    /// Get those of these Scripts which appear in any transaction, confirmed or not. Not supported
+    /// when syncing using compact block filters, which returns an error.
+    fn used_spks(&self, spks: &[bitcoin::ScriptBuf])
+        -> Result<HashSet<bitcoin::ScriptBuf>, String>;
+
+    /// The last derivation index of this descriptor up to which `used_spks` can tell whether its
+    /// Scripts were used, if the backend only tracks a limited range of them.
+    fn used_spks_max_index(&self, desc: &descriptors::LianaDescriptor) -> Option<u32>;
+
+    /// Get those of these outputs which are unspent, confirmed or not, from the UTxO set. They
+    /// need not be ours. Each outpoint is given along with the Script it is expected to pay to,
+    /// as some backends can only query the UTxO set by Script. Not supported when syncing using
+    /// compact block filters, which returns an error.
+    fn unspent_outputs(
+        &self,
+        outputs: &[(bitcoin::OutPoint, bitcoin::ScriptBuf)],
+    ) -> Result<HashMap<bitcoin::OutPoint, bitcoin::TxOut>, String>;
 }
 
 impl BitcoinInterface for d::BitcoinD {
@@ -362,6 +479,10 @@ impl BitcoinInterface for d::BitcoinD {
         }
     }
 
+    fn test_mempool_accept(&self, tx: &bitcoin::Transaction) -> Result<Option<String>, String> {
+        self.test_mempool_accept(tx).map_err(|e| e.to_string())
+    }
+
     fn start_rescan(
         &mut self,
         desc: &descriptors::LianaDescriptor,
@@ -385,6 +506,16 @@ impl BitcoinInterface for d::BitcoinD {
         Some(self.get_block_stats(tip.hash)?.time)
     }
 
+    fn tip_median_time_past(&self) -> Option<u32> {
+        let tip = self.chain_tip();
+        Some(self.get_block_stats(tip.hash)?.median_time_past)
+    }
+
+    fn median_time_past(&self, height: i32) -> Option<u32> {
+        let hash = self.get_block_hash(height)?;
+        Some(self.get_block_stats(hash)?.median_time_past)
+    }
+
     fn wallet_transaction(
         &self,
         txid: &bitcoin::Txid,
@@ -402,6 +533,166 @@ impl BitcoinInterface for d::BitcoinD {
     fn mempool_entry(&self, txid: &bitcoin::Txid) -> Option<MempoolEntry> {
         self.mempool_entry(txid)
     }
+
+    fn estimate_fee(&self, conf_target: u16, mode: FeeEstimateMode) -> Option<FeeEstimate> {
+        self.estimate_smart_fee(conf_target, mode)
+    }
+
+    fn import_watchonly_descriptor(
+        &mut self,
+        desc: &Descriptor<DescriptorPublicKey>,
+        timestamp: u32,
+    ) -> Result<(), String> {
+        self.import_watchonly_descriptor(desc, timestamp)
+            .map_err(|e| e.to_string())
+    }
+
+    fn watchonly_coins(&self, spks: &[bitcoin::ScriptBuf]) -> Vec<WatchonlyUTxO> {
+        let spks: HashSet<_> = spks.iter().map(|spk| spk.as_script()).collect();
+        self.list_unspent(&spks)
+    }
+
+    // The watchonly wallet only tracks the Scripts within the range of the imported descriptors.
+    // This range is extended as addresses get used.
+    fn used_spks(
+        &self,
+        spks: &[bitcoin::ScriptBuf],
+    ) -> Result<HashSet<bitcoin::ScriptBuf>, String> {
+        let spks: HashSet<_> = spks.iter().map(|spk| spk.as_script()).collect();
+        Ok(self.received_spks(&spks))
+    }
+
+    fn used_spks_max_index(&self, desc: &descriptors::LianaDescriptor) -> Option<u32> {
+        Some(self.imported_range_end(desc))
+    }
+
+    fn unspent_outputs(
+        &self,
+        outputs: &[(bitcoin::OutPoint, bitcoin::ScriptBuf)],
+    ) -> Result<HashMap<bitcoin::OutPoint, bitcoin::TxOut>, String> {
+        Ok(outputs
+            .iter()
+            .filter_map(|(op, _)| self.unspent_output(op).map(|txo| (*op, txo)))
+            .collect())
+    }
+}
+
+// The backends which keep track of the wallet's coins themselves (Electrum, compact block filters)
+// derive the updates to our state from the coins they know of.
+
+// Get those wallet coins that are either unconfirmed or have a confirmation height after tip. The
+// poller will then discard any that had already been received.
+fn received_wallet_coins(
+    wallet_coins: &HashMap<bitcoin::OutPoint, Coin>,
+    tip: &BlockChainTip,
+) -> Vec<UTxO> {
+    wallet_coins
+        .values()
+        .filter_map(|c| {
+            let height = c.block_info.map(|info| info.height);
+            if height.filter(|h| *h <= tip.height).is_some() {
+                None
+            } else {
+                Some(UTxO {
+                    outpoint: c.outpoint,
+                    block_height: height,
+                    amount: c.amount,
+                    address: UTxOAddress::DerivIndex(c.derivation_index, c.is_change),
+                    is_immature: c.is_immature,
+                })
+            }
+        })
+        .collect()
+}
+
+fn confirmed_wallet_coins(
+    wallet_coins: &HashMap<bitcoin::OutPoint, Coin>,
+    outpoints: &[bitcoin::OutPoint],
+) -> (Vec<(bitcoin::OutPoint, i32, u32)>, Vec<bitcoin::OutPoint>) {
+    let mut confirmed = Vec::new();
+    let mut expired = Vec::new();
+    for op in outpoints {
+        if let Some(w_c) = wallet_coins.get(op) {
+            if let Some(block) = w_c.block_info {
+                if w_c.is_immature {
+                    log::debug!(
+                        "Coin at '{}' comes from an immature coinbase transaction at \
+                        block height {}. Not marking it as confirmed for now.",
+                        op,
+                        block.height
+                    );
+                    continue;
+                }
+                confirmed.push((w_c.outpoint, block.height, block.time));
+            }
+        } else {
+            expired.push(*op);
+        }
+    }
+    (confirmed, expired)
+}
+
+fn spending_wallet_coins(
+    wallet_coins: &HashMap<bitcoin::OutPoint, Coin>,
+    outpoints: &[bitcoin::OutPoint],
+) -> Vec<(bitcoin::OutPoint, bitcoin::Txid)> {
+    outpoints
+        .iter()
+        .filter_map(|op| {
+            if let Some(w_c) = wallet_coins.get(op) {
+                w_c.spend_txid.map(|txid| (w_c.outpoint, txid))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+fn spent_wallet_coins(
+    wallet_coins: &HashMap<bitcoin::OutPoint, Coin>,
+    outpoints: &[(bitcoin::OutPoint, bitcoin::Txid)],
+) -> (Vec<SpentCoin>, Vec<bitcoin::OutPoint>) {
+    let mut spent = Vec::new();
+    let mut expired_spending = Vec::new();
+    for (op, spend_txid) in outpoints {
+        if let Some(w_c) = wallet_coins.get(op) {
+            if w_c.spend_txid != Some(*spend_txid) {
+                expired_spending.push(*op);
+            }
+            if let Some(block) = w_c.spend_block {
+                spent.push((*op, *spend_txid, block.height, block.time));
+            }
+        }
+    }
+    (spent, expired_spending)
+}
+
+// For the backends which index the UTxO set by Script (Electrum, Esplora), query the unspent
+// outputs paying to the expected Scripts and keep those at the requested outpoints.
+fn unspent_outputs_by_spk(
+    outputs: &[(bitcoin::OutPoint, bitcoin::ScriptBuf)],
+    list_unspent: impl Fn(&[bitcoin::ScriptBuf]) -> Result<Vec<WatchonlyUTxO>, String>,
+) -> Result<HashMap<bitcoin::OutPoint, bitcoin::TxOut>, String> {
+    let spks: Vec<_> = outputs
+        .iter()
+        .map(|(_, spk)| spk.clone())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let outpoints: HashSet<_> = outputs.iter().map(|(op, _)| *op).collect();
+    Ok(list_unspent(&spks)?
+        .into_iter()
+        .filter(|utxo| outpoints.contains(&utxo.outpoint))
+        .map(|utxo| {
+            (
+                utxo.outpoint,
+                bitcoin::TxOut {
+                    value: utxo.amount,
+                    script_pubkey: utxo.script_pubkey,
+                },
+            )
+        })
+        .collect())
 }
 
 impl BitcoinInterface for electrum::Electrum {
@@ -419,70 +710,21 @@ impl BitcoinInterface for electrum::Electrum {
         tip: &BlockChainTip,
         _descs: &[descriptors::SinglePathLianaDesc],
     ) -> Vec<UTxO> {
-        // Get those wallet coins that are either unconfirmed or have a confirmation height
-        // after tip. The poller will then discard any that had already been received.
-        self.wallet_coins(None)
-            .values()
-            .filter_map(|c| {
-                let height = c.block_info.map(|info| info.height);
-                if height.filter(|h| *h <= tip.height).is_some() {
-                    None
-                } else {
-                    Some(UTxO {
-                        outpoint: c.outpoint,
-                        block_height: height,
-                        amount: c.amount,
-                        address: UTxOAddress::DerivIndex(c.derivation_index, c.is_change),
-                        is_immature: c.is_immature,
-                    })
-                }
-            })
-            .collect()
+        received_wallet_coins(&self.wallet_coins(None), tip)
     }
 
     fn confirmed_coins(
         &self,
         outpoints: &[bitcoin::OutPoint],
     ) -> (Vec<(bitcoin::OutPoint, i32, u32)>, Vec<bitcoin::OutPoint>) {
-        let wallet_coins = &self.wallet_coins(Some(outpoints));
-        let mut confirmed = Vec::new();
-        let mut expired = Vec::new();
-        for op in outpoints {
-            if let Some(w_c) = wallet_coins.get(op) {
-                if let Some(block) = w_c.block_info {
-                    if w_c.is_immature {
-                        log::debug!(
-                            "Coin at '{}' comes from an immature coinbase transaction at \
-                            block height {}. Not marking it as confirmed for now.",
-                            op,
-                            block.height
-                        );
-                        continue;
-                    }
-                    confirmed.push((w_c.outpoint, block.height, block.time));
-                }
-            } else {
-                expired.push(*op);
-            }
-        }
-        (confirmed, expired)
+        confirmed_wallet_coins(&self.wallet_coins(Some(outpoints)), outpoints)
     }
 
     fn spending_coins(
         &self,
         outpoints: &[bitcoin::OutPoint],
     ) -> Vec<(bitcoin::OutPoint, bitcoin::Txid)> {
-        let wallet_coins = &self.wallet_coins(Some(outpoints));
-        outpoints
-            .iter()
-            .filter_map(|op| {
-                if let Some(w_c) = wallet_coins.get(op) {
-                    w_c.spend_txid.map(|txid| (w_c.outpoint, txid))
-                } else {
-                    None
-                }
-            })
-            .collect()
+        spending_wallet_coins(&self.wallet_coins(Some(outpoints)), outpoints)
     }
 
     fn spent_coins(
@@ -490,21 +732,7 @@ impl BitcoinInterface for electrum::Electrum {
         outpoints: &[(bitcoin::OutPoint, bitcoin::Txid)],
     ) -> (Vec<SpentCoin>, Vec<bitcoin::OutPoint>) {
         let ops: Vec<_> = outpoints.iter().map(|(op, _)| op).copied().collect();
-        let wallet_coins = &self.wallet_coins(Some(&ops));
-        let mut spent = Vec::new();
-        let mut expired_spending = Vec::new();
-
-        for (op, spend_txid) in outpoints {
-            if let Some(w_c) = wallet_coins.get(op) {
-                if w_c.spend_txid != Some(*spend_txid) {
-                    expired_spending.push(*op);
-                }
-                if let Some(block) = w_c.spend_block {
-                    spent.push((*op, *spend_txid, block.height, block.time));
-                }
-            }
-        }
-        (spent, expired_spending)
+        spent_wallet_coins(&self.wallet_coins(Some(&ops)), outpoints)
     }
 
     fn genesis_block_timestamp(&self) -> u32 {
@@ -543,6 +771,10 @@ impl BitcoinInterface for electrum::Electrum {
         }
     }
 
+    fn test_mempool_accept(&self, _tx: &bitcoin::Transaction) -> Result<Option<String>, String> {
+        Err(BACKEND_NO_MEMPOOL_ACCEPT.to_string())
+    }
+
     fn wallet_transaction(
         &self,
         txid: &bitcoin::Txid,
@@ -589,6 +821,415 @@ impl BitcoinInterface for electrum::Electrum {
     fn tip_time(&self) -> Option<u32> {
         self.client().tip_time().ok()
     }
+
+    fn tip_median_time_past(&self) -> Option<u32> {
+        self.client().tip_median_time_past().ok()
+    }
+
+    fn median_time_past(&self, height: i32) -> Option<u32> {
+        self.client().median_time_past(height).ok()
+    }
+
+    fn estimate_fee(&self, conf_target: u16, _mode: FeeEstimateMode) -> Option<FeeEstimate> {
+        // The Electrum protocol does not let us choose the estimation mode.
+        self.client()
+            .estimate_fee(conf_target)
+            .map(|feerate_vb| FeeEstimate {
+                feerate_vb,
+                blocks: conf_target,
+            })
+            .ok()
+    }
+
+    // The Electrum server indexes all Scripts, there is nothing to import.
+    fn import_watchonly_descriptor(
+        &mut self,
+        _desc: &Descriptor<DescriptorPublicKey>,
+        _timestamp: u32,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn watchonly_coins(&self, spks: &[bitcoin::ScriptBuf]) -> Vec<WatchonlyUTxO> {
+        match self.client().list_unspent(spks) {
+            Ok(utxos) => utxos,
+            Err(e) => {
+                log::error!("Error listing coins of watch-only descriptors: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    fn used_spks(
+        &self,
+        spks: &[bitcoin::ScriptBuf],
+    ) -> Result<HashSet<bitcoin::ScriptBuf>, String> {
+        self.client().used_spks(spks).map_err(|e| e.to_string())
+    }
+
+    fn used_spks_max_index(&self, _desc: &descriptors::LianaDescriptor) -> Option<u32> {
+        None
+    }
+
+    fn unspent_outputs(
+        &self,
+        outputs: &[(bitcoin::OutPoint, bitcoin::ScriptBuf)],
+    ) -> Result<HashMap<bitcoin::OutPoint, bitcoin::TxOut>, String> {
+        unspent_outputs_by_spk(outputs, |spks| {
+            self.client().list_unspent(spks).map_err(|e| e.to_string())
+        })
+    }
+}
+
+impl BitcoinInterface for esplora::Esplora {
+    fn sync_wallet(
+        &mut self,
+        receive_index: ChildNumber,
+        change_index: ChildNumber,
+    ) -> Result<Option<BlockChainTip>, String> {
+        self.sync_wallet(receive_index, change_index)
+            .map_err(|e| e.to_string())
+    }
+
+    fn received_coins(
+        &self,
+        tip: &BlockChainTip,
+        _descs: &[descriptors::SinglePathLianaDesc],
+    ) -> Vec<UTxO> {
+        received_wallet_coins(&self.wallet_coins(None), tip)
+    }
+
+    fn confirmed_coins(
+        &self,
+        outpoints: &[bitcoin::OutPoint],
+    ) -> (Vec<(bitcoin::OutPoint, i32, u32)>, Vec<bitcoin::OutPoint>) {
+        confirmed_wallet_coins(&self.wallet_coins(Some(outpoints)), outpoints)
+    }
+
+    fn spending_coins(
+        &self,
+        outpoints: &[bitcoin::OutPoint],
+    ) -> Vec<(bitcoin::OutPoint, bitcoin::Txid)> {
+        spending_wallet_coins(&self.wallet_coins(Some(outpoints)), outpoints)
+    }
+
+    fn spent_coins(
+        &self,
+        outpoints: &[(bitcoin::OutPoint, bitcoin::Txid)],
+    ) -> (Vec<SpentCoin>, Vec<bitcoin::OutPoint>) {
+        let ops: Vec<_> = outpoints.iter().map(|(op, _)| op).copied().collect();
+        spent_wallet_coins(&self.wallet_coins(Some(&ops)), outpoints)
+    }
+
+    fn genesis_block_timestamp(&self) -> u32 {
+        self.client()
+            .genesis_block_timestamp()
+            .expect("Genesis block timestamp must always be there")
+    }
+
+    fn genesis_block(&self) -> BlockChainTip {
+        self.client()
+            .genesis_block()
+            .expect("Genesis block must always be there")
+    }
+
+    fn chain_tip(&self) -> BlockChainTip {
+        // We want the wallet's local chain tip after syncing.
+        self.wallet_tip()
+    }
+
+    fn is_in_chain(&self, tip: &BlockChainTip) -> bool {
+        // Return `false` if no block at same height as `tip`
+        // is in wallet's local chain.
+        self.is_in_wallet_chain(*tip).unwrap_or_default()
+    }
+
+    /// FIXME: make the Bitcoin backend interface higher level. See the comment in the poller next
+    /// to the `sync_wallet()` call.
+    fn common_ancestor(&self, _tip: &BlockChainTip) -> Option<BlockChainTip> {
+        unreachable!("The common ancestor is returned in `sync_wallet()`. If no reorg was detected then, this method will never be called on an Esplora backend.")
+    }
+
+    fn broadcast_tx(&self, tx: &bitcoin::Transaction) -> Result<(), String> {
+        match self.client().broadcast_tx(tx) {
+            Ok(_txid) => Ok(()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn test_mempool_accept(&self, _tx: &bitcoin::Transaction) -> Result<Option<String>, String> {
+        Err(BACKEND_NO_MEMPOOL_ACCEPT.to_string())
+    }
+
+    fn wallet_transaction(
+        &self,
+        txid: &bitcoin::Txid,
+    ) -> Option<(bitcoin::Transaction, Option<Block>)> {
+        self.wallet_transaction(txid)
+    }
+
+    fn mempool_entry(&self, txid: &bitcoin::Txid) -> Option<MempoolEntry> {
+        self.client().mempool_entry(txid).ok()?
+    }
+
+    fn mempool_spenders(&self, outpoints: &[bitcoin::OutPoint]) -> Vec<MempoolEntry> {
+        self.client()
+            .mempool_spenders(outpoints)
+            .unwrap_or_default()
+    }
+
+    fn sync_progress(&self) -> SyncProgress {
+        // As for Electrum, the server is synced for us.
+        let blocks = self.chain_tip().height as u64;
+        SyncProgress::new(1.0, blocks, blocks)
+    }
+
+    fn start_rescan(
+        &mut self,
+        _desc: &descriptors::LianaDescriptor,
+        _timestamp: u32,
+    ) -> Result<(), String> {
+        self.trigger_rescan();
+        Ok(())
+    }
+
+    fn rescan_progress(&self) -> Option<f64> {
+        // Until we sync we're at 0%. After the sync, we're at 100%.
+        self.is_rescanning().then_some(0.0)
+    }
+
+    // The server indexes the whole history of all Scripts, a full scan always starts from the
+    // genesis block.
+    fn block_before_date(&self, _timestamp: u32) -> Option<BlockChainTip> {
+        Some(self.genesis_block())
+    }
+
+    fn tip_time(&self) -> Option<u32> {
+        self.client().tip_time().ok()
+    }
+
+    fn tip_median_time_past(&self) -> Option<u32> {
+        self.client().tip_median_time_past().ok()
+    }
+
+    fn median_time_past(&self, height: i32) -> Option<u32> {
+        self.client().median_time_past(height).ok()
+    }
+
+    fn estimate_fee(&self, conf_target: u16, _mode: FeeEstimateMode) -> Option<FeeEstimate> {
+        // The Esplora API does not let us choose the estimation mode.
+        let estimates = self.client().fee_estimates().ok()?;
+        esplora::client::feerate_from_estimates(&estimates, conf_target)
+            .map(|(feerate_vb, blocks)| FeeEstimate { feerate_vb, blocks })
+    }
+
+    // The Esplora server indexes all Scripts, there is nothing to import.
+    fn import_watchonly_descriptor(
+        &mut self,
+        _desc: &Descriptor<DescriptorPublicKey>,
+        _timestamp: u32,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn watchonly_coins(&self, spks: &[bitcoin::ScriptBuf]) -> Vec<WatchonlyUTxO> {
+        match self.client().list_unspent(spks) {
+            Ok(utxos) => utxos,
+            Err(e) => {
+                log::error!("Error listing coins of watch-only descriptors: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    fn used_spks(
+        &self,
+        spks: &[bitcoin::ScriptBuf],
+    ) -> Result<HashSet<bitcoin::ScriptBuf>, String> {
+        self.client().used_spks(spks).map_err(|e| e.to_string())
+    }
+
+    fn used_spks_max_index(&self, _desc: &descriptors::LianaDescriptor) -> Option<u32> {
+        None
+    }
+
+    fn unspent_outputs(
+        &self,
+        outputs: &[(bitcoin::OutPoint, bitcoin::ScriptBuf)],
+    ) -> Result<HashMap<bitcoin::OutPoint, bitcoin::TxOut>, String> {
+        unspent_outputs_by_spk(outputs, |spks| {
+            self.client().list_unspent(spks).map_err(|e| e.to_string())
+        })
+    }
+}
+
+impl BitcoinInterface for cbf::Cbf {
+    fn sync_wallet(
+        &mut self,
+        receive_index: ChildNumber,
+        change_index: ChildNumber,
+    ) -> Result<Option<BlockChainTip>, String> {
+        self.sync_wallet(receive_index, change_index)
+            .map_err(|e| e.to_string())
+    }
+
+    fn received_coins(
+        &self,
+        tip: &BlockChainTip,
+        _descs: &[descriptors::SinglePathLianaDesc],
+    ) -> Vec<UTxO> {
+        received_wallet_coins(&self.wallet_coins(None), tip)
+    }
+
+    fn confirmed_coins(
+        &self,
+        outpoints: &[bitcoin::OutPoint],
+    ) -> (Vec<(bitcoin::OutPoint, i32, u32)>, Vec<bitcoin::OutPoint>) {
+        confirmed_wallet_coins(&self.wallet_coins(Some(outpoints)), outpoints)
+    }
+
+    fn spending_coins(
+        &self,
+        outpoints: &[bitcoin::OutPoint],
+    ) -> Vec<(bitcoin::OutPoint, bitcoin::Txid)> {
+        spending_wallet_coins(&self.wallet_coins(Some(outpoints)), outpoints)
+    }
+
+    fn spent_coins(
+        &self,
+        outpoints: &[(bitcoin::OutPoint, bitcoin::Txid)],
+    ) -> (Vec<SpentCoin>, Vec<bitcoin::OutPoint>) {
+        let ops: Vec<_> = outpoints.iter().map(|(op, _)| op).copied().collect();
+        spent_wallet_coins(&self.wallet_coins(Some(&ops)), outpoints)
+    }
+
+    fn genesis_block_timestamp(&self) -> u32 {
+        self.headers()
+            .time_at(0)
+            .expect("Genesis block must always be there")
+    }
+
+    fn genesis_block(&self) -> BlockChainTip {
+        let height = 0;
+        let hash = self
+            .headers()
+            .hash_at(height)
+            .expect("Genesis block must always be there");
+        BlockChainTip { hash, height }
+    }
+
+    fn chain_tip(&self) -> BlockChainTip {
+        // Like for Electrum, we want the wallet's local chain tip after syncing.
+        self.wallet_tip()
+    }
+
+    fn is_in_chain(&self, tip: &BlockChainTip) -> bool {
+        self.headers().hash_at(tip.height) == Some(tip.hash)
+    }
+
+    // The common ancestor is returned in `sync_wallet()` if a reorg is detected then. Otherwise
+    // the wallet's chain is part of the best chain.
+    fn common_ancestor(&self, _tip: &BlockChainTip) -> Option<BlockChainTip> {
+        Some(self.common_ancestor())
+    }
+
+    fn broadcast_tx(&self, tx: &bitcoin::Transaction) -> Result<(), String> {
+        self.broadcast_tx(tx).map_err(|e| e.to_string())
+    }
+
+    fn test_mempool_accept(&self, _tx: &bitcoin::Transaction) -> Result<Option<String>, String> {
+        Err(BACKEND_NO_MEMPOOL_ACCEPT.to_string())
+    }
+
+    fn wallet_transaction(
+        &self,
+        txid: &bitcoin::Txid,
+    ) -> Option<(bitcoin::Transaction, Option<Block>)> {
+        self.wallet_transaction(txid)
+    }
+
+    // We don't have a mempool.
+    fn mempool_entry(&self, _txid: &bitcoin::Txid) -> Option<MempoolEntry> {
+        None
+    }
+
+    fn mempool_spenders(&self, _outpoints: &[bitcoin::OutPoint]) -> Vec<MempoolEntry> {
+        Vec::new()
+    }
+
+    fn sync_progress(&self) -> SyncProgress {
+        // The headers are synced along with the wallet, so as for Electrum always return 100%.
+        let blocks = self.chain_tip().height as u64;
+        SyncProgress::new(1.0, blocks, blocks)
+    }
+
+    fn start_rescan(
+        &mut self,
+        _desc: &descriptors::LianaDescriptor,
+        timestamp: u32,
+    ) -> Result<(), String> {
+        self.trigger_rescan(timestamp);
+        Ok(())
+    }
+
+    fn rescan_progress(&self) -> Option<f64> {
+        // Until we sync we're at 0%. After the sync, we're at 100%.
+        self.is_rescanning().then_some(0.0)
+    }
+
+    fn block_before_date(&self, timestamp: u32) -> Option<BlockChainTip> {
+        Some(self.headers().block_before_date(timestamp))
+    }
+
+    fn tip_time(&self) -> Option<u32> {
+        self.headers().time_at(self.chain_tip().height)
+    }
+
+    fn tip_median_time_past(&self) -> Option<u32> {
+        self.headers().median_time_past(self.chain_tip().height)
+    }
+
+    fn median_time_past(&self, height: i32) -> Option<u32> {
+        self.headers().median_time_past(height)
+    }
+
+    // Our peers don't give us any information about the fee market.
+    fn estimate_fee(&self, _conf_target: u16, _mode: FeeEstimateMode) -> Option<FeeEstimate> {
+        None
+    }
+
+    fn import_watchonly_descriptor(
+        &mut self,
+        _desc: &Descriptor<DescriptorPublicKey>,
+        _timestamp: u32,
+    ) -> Result<(), String> {
+        Err("Watch-only descriptors are not supported with compact block filters.".to_string())
+    }
+
+    fn watchonly_coins(&self, _spks: &[bitcoin::ScriptBuf]) -> Vec<WatchonlyUTxO> {
+        Vec::new()
+    }
+
+    // We would need to download the filters of the whole chain again to look for other Scripts
+    // than those of the wallet.
+    fn used_spks(
+        &self,
+        _spks: &[bitcoin::ScriptBuf],
+    ) -> Result<HashSet<bitcoin::ScriptBuf>, String> {
+        Err(CBF_NO_USED_SPKS.to_string())
+    }
+
+    fn used_spks_max_index(&self, _desc: &descriptors::LianaDescriptor) -> Option<u32> {
+        None
+    }
+
+    // Same as for `used_spks`, we only download the blocks relevant to the wallet.
+    fn unspent_outputs(
+        &self,
+        _outputs: &[(bitcoin::OutPoint, bitcoin::ScriptBuf)],
+    ) -> Result<HashMap<bitcoin::OutPoint, bitcoin::TxOut>, String> {
+        Err(CBF_NO_UNSPENT_OUTPUTS.to_string())
+    }
 }
 
 // FIXME: do we need to repeat the entire trait implementation? Isn't there a nicer way?
@@ -660,6 +1301,10 @@ impl BitcoinInterface for sync::Arc<sync::Mutex<dyn BitcoinInterface + 'static>>
         self.lock().unwrap().broadcast_tx(tx)
     }
 
+    fn test_mempool_accept(&self, tx: &bitcoin::Transaction) -> Result<Option<String>, String> {
+        self.lock().unwrap().test_mempool_accept(tx)
+    }
+
     fn start_rescan(
         &mut self,
         desc: &descriptors::LianaDescriptor,
@@ -680,6 +1325,14 @@ impl BitcoinInterface for sync::Arc<sync::Mutex<dyn BitcoinInterface + 'static>>
         self.lock().unwrap().tip_time()
     }
 
+    fn tip_median_time_past(&self) -> Option<u32> {
+        self.lock().unwrap().tip_median_time_past()
+    }
+
+    fn median_time_past(&self, height: i32) -> Option<u32> {
+        self.lock().unwrap().median_time_past(height)
+    }
+
     fn wallet_transaction(
         &self,
         txid: &bitcoin::Txid,
@@ -694,6 +1347,42 @@ impl BitcoinInterface for sync::Arc<sync::Mutex<dyn BitcoinInterface + 'static>>
     fn mempool_entry(&self, txid: &bitcoin::Txid) -> Option<MempoolEntry> {
         self.lock().unwrap().mempool_entry(txid)
     }
+
+    fn estimate_fee(&self, conf_target: u16, mode: FeeEstimateMode) -> Option<FeeEstimate> {
+        self.lock().unwrap().estimate_fee(conf_target, mode)
+    }
+
+    fn import_watchonly_descriptor(
+        &mut self,
+        desc: &Descriptor<DescriptorPublicKey>,
+        timestamp: u32,
+    ) -> Result<(), String> {
+        self.lock()
+            .unwrap()
+            .import_watchonly_descriptor(desc, timestamp)
+    }
+
+    fn watchonly_coins(&self, spks: &[bitcoin::ScriptBuf]) -> Vec<WatchonlyUTxO> {
+        self.lock().unwrap().watchonly_coins(spks)
+    }
+
+    fn used_spks(
+        &self,
+        spks: &[bitcoin::ScriptBuf],
+    ) -> Result<HashSet<bitcoin::ScriptBuf>, String> {
+        self.lock().unwrap().used_spks(spks)
+    }
+
+    fn used_spks_max_index(&self, desc: &descriptors::LianaDescriptor) -> Option<u32> {
+        self.lock().unwrap().used_spks_max_index(desc)
+    }
+
+    fn unspent_outputs(
+        &self,
+        outputs: &[(bitcoin::OutPoint, bitcoin::ScriptBuf)],
+    ) -> Result<HashMap<bitcoin::OutPoint, bitcoin::TxOut>, String> {
+        self.lock().unwrap().unspent_outputs(outputs)
+    }
 }
 
 // FIXME: We could avoid this type (and all the conversions entailing allocations) if bitcoind
@@ -707,6 +1396,15 @@ pub struct UTxO {
     pub is_immature: bool,
 }
 
+/// An unspent coin paying to one of the additional watch-only descriptors.
+#[derive(Debug, Clone)]
+pub struct WatchonlyUTxO {
+    pub outpoint: bitcoin::OutPoint,
+    pub amount: bitcoin::Amount,
+    pub script_pubkey: bitcoin::ScriptBuf,
+    pub block_height: Option<i32>,
+}
+
 /// Details about the UTXO address.
 #[derive(Debug, Clone)]
 pub enum UTxOAddress {
@@ -1,12 +1,22 @@
 use crate::{
-    bitcoin::{BitcoinInterface, BlockChainTip, UTxO, UTxOAddress},
-    database::{Coin, DatabaseConnection, DatabaseInterface},
+    bitcoin::{BitcoinInterface, BlockChainTip, UTxO, UTxOAddress, WATCHONLY_RANGE},
+    database::{Coin, DatabaseConnection, DatabaseInterface, WatchonlyCoin},
+    notifications::{Event, Notifier},
 };
 
-use std::{collections::HashSet, convert::TryInto, sync, thread, time};
+use std::{
+    cmp,
+    collections::{BTreeSet, HashMap, HashSet},
+    convert::TryInto,
+    ops::Range,
+    sync, thread, time,
+};
 
 use liana::descriptors;
-use miniscript::bitcoin::{self, secp256k1};
+use miniscript::{
+    bitcoin::{self, secp256k1},
+    descriptor::{Descriptor, DescriptorPublicKey},
+};
 
 #[derive(Debug, Clone)]
 struct UpdatedCoins {
@@ -91,6 +101,7 @@ fn update_coins(
                 spend_txid: None,
                 spend_block: None,
                 is_from_self: false,
+                is_frozen: false,
             };
             received.push(coin);
         }
@@ -191,6 +202,80 @@ fn add_txs_to_db(
     }
 }
 
+// Get the events corresponding to these updates of our coins. Must be called before the updates
+// are recorded in database.
+fn coins_events(
+    db_conn: &mut Box<dyn DatabaseConnection>,
+    updated_coins: &UpdatedCoins,
+    descs: &[descriptors::SinglePathLianaDesc],
+    secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+) -> Vec<Event> {
+    let network = db_conn.network();
+    let expired: HashSet<_> = updated_coins.expired.iter().collect();
+    let mut events: Vec<Event> = updated_coins
+        .received
+        .iter()
+        .filter(|coin| !expired.contains(&coin.outpoint))
+        .map(|coin| Event::CoinReceived {
+            outpoint: coin.outpoint,
+            amount: coin.amount,
+            address: descs[coin.is_change as usize]
+                .derive(coin.derivation_index, secp)
+                .address(network),
+            derivation_index: coin.derivation_index.into(),
+            is_change: coin.is_change,
+        })
+        .collect();
+    events.extend(
+        updated_coins
+            .confirmed
+            .iter()
+            .map(|(outpoint, height, time)| Event::CoinConfirmed {
+                outpoint: *outpoint,
+                height: *height,
+                time: *time,
+            }),
+    );
+
+    // Coins whose spending transaction is not confirmed are checked for spends at every poll. Only
+    // notify about the spending transactions we didn't know about yet.
+    let spending_outpoints: Vec<_> = updated_coins
+        .spending
+        .iter()
+        .map(|(outpoint, _)| *outpoint)
+        .collect();
+    let curr_coins = db_conn.coins(&[], &spending_outpoints);
+    events.extend(
+        updated_coins
+            .spending
+            .iter()
+            .filter(|(outpoint, txid)| {
+                curr_coins
+                    .get(outpoint)
+                    .and_then(|coin| coin.spend_txid)
+                    .as_ref()
+                    != Some(txid)
+            })
+            .map(|(outpoint, txid)| Event::CoinSpent {
+                outpoint: *outpoint,
+                spend_txid: *txid,
+            }),
+    );
+    events.extend(
+        updated_coins
+            .spent
+            .iter()
+            .map(|(outpoint, txid, height, time)| Event::SpendConfirmed {
+                outpoint: *outpoint,
+                spend_txid: *txid,
+                height: *height,
+                time: *time,
+            }),
+    );
+
+    events
+}
+
 #[derive(Debug, Clone, Copy)]
 enum TipUpdate {
     // The best block is still the same as in the previous poll.
@@ -243,6 +328,7 @@ fn updates(
     bit: &mut impl BitcoinInterface,
     descs: &[descriptors::SinglePathLianaDesc],
     secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+    notifier: &Notifier,
 ) {
     // Check if there was a new block before we update our state.
     //
@@ -264,7 +350,11 @@ fn updates(
                     // between our former chain and the new one, then restart fresh.
                     db_conn.rollback_tip(&new_tip);
                     log::info!("Tip was rolled back to '{}'.", new_tip);
-                    return updates(db_conn, bit, descs, secp);
+                    notifier.notify(&[Event::Reorg {
+                        height: new_tip.height,
+                        hash: new_tip.hash,
+                    }]);
+                    return updates(db_conn, bit, descs, secp, notifier);
                 }
             }
         }
@@ -279,18 +369,22 @@ fn updates(
             {
                 db_conn.rollback_tip(&reorg_common_ancestor);
                 log::info!("Tip was rolled back to '{}'.", &reorg_common_ancestor);
+                notifier.notify(&[Event::Reorg {
+                    height: reorg_common_ancestor.height,
+                    hash: reorg_common_ancestor.hash,
+                }]);
             } else {
                 log::info!(
                     "Tip was already earlier than common ancestor '{}'.",
                     &reorg_common_ancestor
                 );
             }
-            return updates(db_conn, bit, descs, secp);
+            return updates(db_conn, bit, descs, secp, notifier);
         }
         Err(e) => {
             log::error!("Error syncing wallet: '{}'.", e);
             thread::sleep(time::Duration::from_secs(2));
-            return updates(db_conn, bit, descs, secp);
+            return updates(db_conn, bit, descs, secp, notifier);
         }
     };
 
@@ -301,9 +395,11 @@ fn updates(
     // If the tip changed while we were polling our Bitcoin interface, start over.
     if bit.chain_tip() != latest_tip {
         log::info!("Chain tip changed while we were updating our state. Starting over.");
-        return updates(db_conn, bit, descs, secp);
+        return updates(db_conn, bit, descs, secp, notifier);
     }
 
+    let mut events = coins_events(db_conn, &updated_coins, descs, secp);
+
     // Transactions must be added to the DB before coins due to foreign key constraints.
     add_txs_to_db(bit, db_conn, &updated_coins);
     // The chain tip did not change since we started our updates. Record them and the latest tip.
@@ -321,9 +417,14 @@ fn updates(
     if latest_tip != current_tip {
         db_conn.update_tip(&latest_tip);
         log::debug!("New tip: '{}'", latest_tip);
+        events.push(Event::TipChanged {
+            height: latest_tip.height,
+            hash: latest_tip.hash,
+        });
     }
 
     log::debug!("Updates done.");
+    notifier.notify(&events);
 }
 
 // Check if there is any rescan of the backend ongoing or one that just finished.
@@ -332,6 +433,7 @@ fn rescan_check(
     bit: &mut impl BitcoinInterface,
     descs: &[descriptors::SinglePathLianaDesc],
     secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+    notifier: &Notifier,
 ) {
     log::debug!("Checking the state of an ongoing rescan if there is any");
 
@@ -368,12 +470,199 @@ fn rescan_check(
             "Rolling back our internal tip to '{}' to update our internal state with past transactions.",
             rescan_tip
         );
-        updates(db_conn, bit, descs, secp)
+        updates(db_conn, bit, descs, secp, notifier);
+        notifier.notify(&[Event::RescanFinished { timestamp }]);
     } else {
         log::debug!("No ongoing rescan.");
     }
 }
 
+// The number of consecutive unused addresses of a ranged watch-only descriptor we look at past the
+// last used one.
+const WATCHONLY_GAP_LIMIT: u32 = 20;
+
+// The Scripts of a single-path watch-only descriptor, derived as needed.
+struct WatchonlyScripts {
+    descriptor: Descriptor<DescriptorPublicKey>,
+    // The Scripts derived so far along with their address, if any. The entry at index `i` is the
+    // one at derivation index `i`.
+    derived: Vec<(bitcoin::ScriptBuf, Option<bitcoin::Address>)>,
+    // The derivation indexes of the Scripts which are known to have been used.
+    used: BTreeSet<u32>,
+}
+
+impl WatchonlyScripts {
+    fn new(descriptor: Descriptor<DescriptorPublicKey>) -> Self {
+        Self {
+            descriptor,
+            derived: Vec::new(),
+            used: BTreeSet::new(),
+        }
+    }
+
+    // The derivation indexes of the Scripts past the last used one which we look at. We only
+    // watch the first `WATCHONLY_RANGE` addresses of a ranged descriptor.
+    fn unused_range(&self) -> Range<u32> {
+        let start = self.used.last().map(|i| i + 1).unwrap_or(0);
+        let end = if self.descriptor.has_wildcard() {
+            cmp::min(start + WATCHONLY_GAP_LIMIT, WATCHONLY_RANGE)
+        } else {
+            1
+        };
+        start..cmp::max(start, end)
+    }
+
+    // Derive the Scripts up to this derivation index (excluded), if not done yet.
+    fn derive(
+        &mut self,
+        end: u32,
+        network: bitcoin::Network,
+        secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+    ) {
+        for index in (self.derived.len() as u32)..end {
+            let der_desc = self
+                .descriptor
+                .at_derivation_index(index)
+                .and_then(|desc| desc.derived_descriptor(secp))
+                .expect("Index is never hardened.");
+            self.derived
+                .push((der_desc.script_pubkey(), der_desc.address(network).ok()));
+        }
+    }
+}
+
+/// The Scripts of the watch-only descriptors, kept across polls so they are only derived once. It
+/// also records which of them were used, as we only need to look for coins on those and on the
+/// few unused ones following them.
+#[derive(Default)]
+pub struct WatchonlyCache {
+    // By single-path descriptor.
+    scripts: HashMap<Descriptor<DescriptorPublicKey>, WatchonlyScripts>,
+}
+
+// Update the unspent coins of the additional watch-only descriptors, if there is any. Contrary to
+// the coins of the main descriptor we don't keep track of their history: only of the coins that
+// are currently unspent.
+fn update_watchonly_coins(
+    db_conn: &mut Box<dyn DatabaseConnection>,
+    bit: &impl BitcoinInterface,
+    secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+    cache: &mut WatchonlyCache,
+) {
+    let watchonly_descs = db_conn.watchonly_descriptors();
+    if watchonly_descs.is_empty() {
+        return;
+    }
+    log::debug!("Updating the coins of the watch-only descriptors.");
+    let network = db_conn.network();
+
+    // The single-path descriptors to watch, along with the index of the watch-only descriptor
+    // they are part of.
+    let single_descs: Vec<_> = watchonly_descs
+        .iter()
+        .enumerate()
+        .flat_map(|(desc_index, (desc, _))| {
+            desc.clone()
+                .into_single_descriptors()
+                .expect("Can't error, all paths have the same length")
+                .into_iter()
+                .map(move |single_desc| (desc_index, single_desc))
+        })
+        .collect();
+    cache
+        .scripts
+        .retain(|desc, _| single_descs.iter().any(|(_, d)| d == desc));
+    for (_, single_desc) in &single_descs {
+        cache
+            .scripts
+            .entry(single_desc.clone())
+            .or_insert_with(|| WatchonlyScripts::new(single_desc.clone()));
+    }
+
+    // Look for the Scripts which were used among those following the last used one, as long as we
+    // find new ones. Query all the descriptors at once. If the backend can't tell which Scripts
+    // were used, look for coins on the unused ones too.
+    let mut query_unused = false;
+    loop {
+        let mut unused_spks = HashMap::new();
+        for (single_desc, scripts) in cache.scripts.iter_mut() {
+            let range = scripts.unused_range();
+            scripts.derive(range.end, network, secp);
+            for index in range {
+                let spk = scripts.derived[index as usize].0.clone();
+                unused_spks.insert(spk, (single_desc.clone(), index));
+            }
+        }
+        if unused_spks.is_empty() {
+            break;
+        }
+        let spk_list: Vec<_> = unused_spks.keys().cloned().collect();
+        let used_spks = match bit.used_spks(&spk_list) {
+            Ok(used_spks) => used_spks,
+            Err(e) => {
+                log::debug!("Could not check the usage of watch-only Scripts: '{}'.", e);
+                query_unused = true;
+                break;
+            }
+        };
+        if used_spks.is_empty() {
+            break;
+        }
+        for spk in used_spks {
+            if let Some((single_desc, index)) = unused_spks.get(&spk) {
+                if let Some(scripts) = cache.scripts.get_mut(single_desc) {
+                    scripts.used.insert(*index);
+                }
+            }
+        }
+    }
+
+    // Then look for the coins on the used Scripts. Record the descriptor, derivation index and
+    // address each Script corresponds to.
+    let mut spks = HashMap::new();
+    for (desc_index, single_desc) in &single_descs {
+        let scripts = &cache.scripts[single_desc];
+        let unused = if query_unused {
+            scripts.unused_range()
+        } else {
+            0..0
+        };
+        for index in scripts.used.iter().copied().chain(unused) {
+            if let (spk, Some(address)) = &scripts.derived[index as usize] {
+                spks.insert(spk.clone(), (*desc_index, index.into(), address.clone()));
+            }
+        }
+    }
+    let mut coins: Vec<_> = if spks.is_empty() {
+        Vec::new()
+    } else {
+        let spk_list: Vec<_> = spks.keys().cloned().collect();
+        bit.watchonly_coins(&spk_list)
+            .into_iter()
+            .filter_map(|utxo| {
+                let (desc_index, derivation_index, address) = spks.get(&utxo.script_pubkey)?;
+                Some(WatchonlyCoin {
+                    descriptor: watchonly_descs[*desc_index].0.clone(),
+                    outpoint: utxo.outpoint,
+                    amount: utxo.amount,
+                    address: address.clone(),
+                    derivation_index: *derivation_index,
+                    block_height: utxo.block_height,
+                })
+            })
+            .collect()
+    };
+
+    // Only write to the database if the set of coins changed.
+    let mut curr_coins = db_conn.watchonly_coins();
+    coins.sort_by_key(|c| c.outpoint);
+    curr_coins.sort_by_key(|c| c.outpoint);
+    if coins != curr_coins {
+        log::debug!("New watch-only coins: {:?}", coins);
+        db_conn.set_watchonly_coins(&coins);
+    }
+}
+
 /// If the database chain tip is NULL (first startup), initialize it.
 pub fn maybe_initialize_tip(bit: &impl BitcoinInterface, db: &impl DatabaseInterface) {
     let mut db_conn = db.connection();
@@ -400,10 +689,13 @@ pub fn poll(
     db: &sync::Arc<sync::Mutex<dyn DatabaseInterface>>,
     secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
     descs: &[descriptors::SinglePathLianaDesc],
+    notifier: &Notifier,
+    watchonly_cache: &mut WatchonlyCache,
 ) {
     let mut db_conn = db.connection();
-    updates(&mut db_conn, bit, descs, secp);
-    rescan_check(&mut db_conn, bit, descs, secp);
+    updates(&mut db_conn, bit, descs, secp, notifier);
+    rescan_check(&mut db_conn, bit, descs, secp, notifier);
+    update_watchonly_coins(&mut db_conn, bit, secp, watchonly_cache);
     let now: u32 = time::SystemTime::now()
         .duration_since(time::UNIX_EPOCH)
         .expect("current system time must be later than epoch")
@@ -412,3 +704,72 @@ pub fn poll(
         .expect("system clock year is earlier than 2106");
     db_conn.set_last_poll(now);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::{DummyBitcoind, DummyDatabase};
+    use std::str::FromStr;
+
+    #[test]
+    fn watchonly_coins() {
+        let secp = secp256k1::Secp256k1::verification_only();
+        let desc = Descriptor::<DescriptorPublicKey>::from_str("wpkh(xpub68JJTXc1MWK8KLW4HGLXZBJknja7kDUJuFHnM424LbziEXsfkh1WQCiEjjHw4zLqSUm4rvhgyGkkuRowE9tCJSgt3TQB5J3SKAbZ2SdcKST/*)").unwrap();
+        let spk_at = |index: u32| {
+            desc.at_derivation_index(index)
+                .unwrap()
+                .derived_descriptor(&secp)
+                .unwrap()
+                .script_pubkey()
+        };
+        let db = DummyDatabase::new();
+        let mut db_conn = db.connection();
+        db_conn.new_watchonly_descriptor(&desc, 0);
+
+        // The Scripts at index 15 and 30 are found as they are within the gap limit of the last
+        // used one. Not the one at index 60.
+        let mut bit = DummyBitcoind::new();
+        bit.used_spks = [0, 15, 30, 60].iter().map(|i| spk_at(*i)).collect();
+        let (op_30, op_60) = (
+            bitcoin::OutPoint::from_str(
+                "0000000000000000000000000000000000000000000000000000000000000001:0",
+            )
+            .unwrap(),
+            bitcoin::OutPoint::from_str(
+                "0000000000000000000000000000000000000000000000000000000000000001:1",
+            )
+            .unwrap(),
+        );
+        for (op, index) in [(op_30, 30), (op_60, 60)].iter() {
+            bit.utxos.insert(
+                *op,
+                bitcoin::TxOut {
+                    value: bitcoin::Amount::from_sat(10_000),
+                    script_pubkey: spk_at(*index),
+                },
+            );
+        }
+        let mut cache = WatchonlyCache::default();
+        update_watchonly_coins(&mut db_conn, &bit, &secp, &mut cache);
+        let coins = db_conn.watchonly_coins();
+        assert_eq!(coins.len(), 1);
+        assert_eq!(coins[0].outpoint, op_30);
+        assert_eq!(coins[0].derivation_index, 30.into());
+        let scripts = &cache.scripts[&desc];
+        assert_eq!(
+            scripts.used.iter().copied().collect::<Vec<_>>(),
+            vec![0, 15, 30]
+        );
+        assert_eq!(scripts.derived.len(), 30 + 1 + WATCHONLY_GAP_LIMIT as usize);
+
+        // Once a Script within the gap limit is used, the following ones are looked at.
+        bit.used_spks.insert(spk_at(45));
+        update_watchonly_coins(&mut db_conn, &bit, &secp, &mut cache);
+        let mut coins = db_conn.watchonly_coins();
+        coins.sort_by_key(|c| c.outpoint);
+        assert_eq!(
+            coins.iter().map(|c| c.outpoint).collect::<Vec<_>>(),
+            vec![op_30, op_60]
+        );
+    }
+}
@@ -1,9 +1,10 @@
 mod looper;
 
-use crate::{bitcoin::BitcoinInterface, database::DatabaseInterface};
+use crate::{bitcoin::BitcoinInterface, database::DatabaseInterface, notifications::Notifier};
 use liana::descriptors;
 
 use std::{
+    collections::BTreeMap,
     sync::{self, mpsc},
     time,
 };
@@ -18,6 +19,47 @@ pub enum PollerMessage {
     PollNow(mpsc::SyncSender<()>),
 }
 
+/// A wallet hosted along the main one, whose state is also kept up to date by the poller.
+#[derive(Clone)]
+pub struct PolledWallet {
+    bit: sync::Arc<sync::Mutex<dyn BitcoinInterface>>,
+    db: sync::Arc<sync::Mutex<dyn DatabaseInterface>>,
+    // The receive and change descriptors (in this order).
+    descs: [descriptors::SinglePathLianaDesc; 2],
+    notifier: Notifier,
+    // Shared as the wallet is cloned for each poll.
+    watchonly_cache: sync::Arc<sync::Mutex<looper::WatchonlyCache>>,
+}
+
+impl PolledWallet {
+    pub fn new(
+        bit: sync::Arc<sync::Mutex<dyn BitcoinInterface>>,
+        db: sync::Arc<sync::Mutex<dyn DatabaseInterface>>,
+        desc: &descriptors::LianaDescriptor,
+        notifier: Notifier,
+    ) -> PolledWallet {
+        let descs = [
+            desc.receive_descriptor().clone(),
+            desc.change_descriptor().clone(),
+        ];
+
+        // Same as for the main wallet, the poller relies on the tip being set.
+        looper::maybe_initialize_tip(&bit, &db);
+
+        PolledWallet {
+            bit,
+            db,
+            descs,
+            notifier,
+            watchonly_cache: sync::Arc::new(sync::Mutex::new(looper::WatchonlyCache::default())),
+        }
+    }
+}
+
+/// The additional wallets to be polled, by name. Shared between the poller and the API so wallets
+/// can be loaded and unloaded while the poller is running.
+pub type PolledWallets = sync::Arc<sync::Mutex<BTreeMap<String, PolledWallet>>>;
+
 /// The Bitcoin poller handler.
 pub struct Poller {
     bit: sync::Arc<sync::Mutex<dyn BitcoinInterface>>,
@@ -25,6 +67,9 @@ pub struct Poller {
     secp: secp256k1::Secp256k1<secp256k1::VerifyOnly>,
     // The receive and change descriptors (in this order).
     descs: [descriptors::SinglePathLianaDesc; 2],
+    notifier: Notifier,
+    watchonly_cache: looper::WatchonlyCache,
+    wallets: PolledWallets,
 }
 
 impl Poller {
@@ -32,6 +77,7 @@ impl Poller {
         bit: sync::Arc<sync::Mutex<dyn BitcoinInterface>>,
         db: sync::Arc<sync::Mutex<dyn DatabaseInterface>>,
         desc: descriptors::LianaDescriptor,
+        notifier: Notifier,
     ) -> Poller {
         let secp = secp256k1::Secp256k1::verification_only();
         let descs = [
@@ -47,6 +93,49 @@ impl Poller {
             db,
             secp,
             descs,
+            notifier,
+            watchonly_cache: looper::WatchonlyCache::default(),
+            wallets: sync::Arc::new(sync::Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    /// A handle to the additional wallets this poller keeps up to date.
+    pub fn wallets(&self) -> PolledWallets {
+        self.wallets.clone()
+    }
+
+    // Update the state of the main wallet then of each of the additional ones.
+    fn poll(&mut self) {
+        looper::poll(
+            &mut self.bit,
+            &self.db,
+            &self.secp,
+            &self.descs,
+            &self.notifier,
+            &mut self.watchonly_cache,
+        );
+
+        // Don't hold the lock while polling so the wallets can be (un)loaded in the meantime.
+        let wallets: Vec<(String, PolledWallet)> = self
+            .wallets
+            .lock()
+            .expect("Wallets lock poisoned")
+            .iter()
+            .map(|(name, wallet)| (name.clone(), wallet.clone()))
+            .collect();
+        for (name, mut wallet) in wallets {
+            log::debug!("Polling wallet '{}'.", name);
+            looper::poll(
+                &mut wallet.bit,
+                &wallet.db,
+                &self.secp,
+                &wallet.descs,
+                &wallet.notifier,
+                &mut wallet
+                    .watchonly_cache
+                    .lock()
+                    .expect("Watch-only cache lock poisoned"),
+            );
         }
     }
 
@@ -108,7 +197,7 @@ impl Poller {
                     // poll too soon.
                     last_poll = Some(time::Instant::now());
                     if synced {
-                        looper::poll(&mut self.bit, &self.db, &self.secp, &self.descs);
+                        self.poll();
                     } else {
                         log::warn!("Skipped poll as block chain is still synchronizing.");
                     }
@@ -142,7 +231,7 @@ impl Poller {
                 }
             }
 
-            looper::poll(&mut self.bit, &self.db, &self.secp, &self.descs);
+            self.poll();
         }
     }
 }
@@ -5,14 +5,24 @@
 mod utils;
 
 use crate::{
-    bitcoin::BitcoinInterface,
-    database::{Coin, DatabaseConnection, DatabaseInterface},
+    accounting::{compute_cost_basis, AccountingEntry, AccountingTx, CostBasisMethod},
+    bip329,
+    bitcoin::{BitcoinInterface, FeeEstimate},
+    database::{Coin, DatabaseConnection, DatabaseInterface, WatchonlyCoin},
     miniscript::bitcoin::absolute::LockTime,
+    notifications::{EventKind, Subscription},
+    payjoin, payments,
     poller::PollerMessage,
+    proof_of_reserves::{self, ProofError},
+    signing::{PolicyViolation, SpendSummary, DAILY_LIMIT_PERIOD},
+    spending_policy::{DestinationOutput, PolicySpend, SpendingPolicy, SpendingPolicyViolation},
     DaemonControl, VERSION,
 };
 
-pub use crate::database::{CoinStatus, LabelItem};
+pub use crate::{
+    bitcoin::FeeEstimateMode,
+    database::{CoinStatus, LabelItem},
+};
 
 use liana::{
     descriptors,
@@ -28,9 +38,11 @@ use utils::{
 };
 
 use std::{
+    cmp,
     collections::{hash_map, HashMap, HashSet},
     convert::TryInto,
     fmt,
+    str::FromStr,
     sync::{self, mpsc},
     time::SystemTime,
 };
@@ -39,12 +51,29 @@ use miniscript::{
     bitcoin::{
         self, address,
         bip32::{self, ChildNumber},
-        psbt::Psbt,
+        psbt::{Input as PsbtIn, Psbt},
+        secp256k1,
     },
+    descriptor::{DefiniteDescriptorKey, Descriptor, DescriptorPublicKey},
     psbt::PsbtExt,
 };
 use serde::{Deserialize, Serialize};
 
+/// The maximum length, in bytes, of a label.
+pub const MAX_LABEL_LEN: usize = 100;
+
+/// The maximum confirmation target, in blocks, for which a feerate estimate can be requested.
+pub const MAX_CONF_TARGET: u16 = 1_008;
+
+/// The maximum gap limit which can be used for address discovery.
+pub const MAX_GAP_LIMIT: u32 = 100_000;
+
+// The number of addresses we query the Bitcoin backend for at once during address discovery.
+const DISCOVERY_BATCH_SIZE: u32 = 100;
+
+// The number of created spends whose output labels we keep until they are stored.
+const MAX_PENDING_LABELS: usize = 100;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CommandError {
     NoOutpointForSelfSend,
@@ -60,6 +89,8 @@ pub enum CommandError {
         /* target feerate */ u64,
     ),
     UnknownSpend(bitcoin::Txid),
+    /// The PSBT contains a MuSig2 nonce or partial signature different from the stored one.
+    MusigSessionConflict(descriptors::musig::SessionFieldConflict),
     // FIXME: when upgrading Miniscript put the actual error there
     SpendFinalization(String),
     TxBroadcast(String),
@@ -69,11 +100,42 @@ pub enum CommandError {
     RescanTrigger(String),
     RecoveryNotAvailable,
     // Include timelock in error as it may not have been set explicitly by the user.
-    OutpointNotRecoverable(bitcoin::OutPoint, /* timelock */ u16),
+    OutpointNotRecoverable(bitcoin::OutPoint, /* timelock */ u32),
     /// Overflowing or unhardened derivation index.
     InvalidDerivationIndex,
     RbfError(RbfErrorInfo),
+    CpfpError(CpfpErrorInfo),
+    Payjoin(payjoin::SenderError),
     EmptyFilterList,
+    InvalidConfTarget(u16),
+    /// The Bitcoin backend could not provide a feerate estimate for this confirmation target.
+    FeeEstimationUnavailable(u16),
+    InvalidWatchonlyDescriptor(String),
+    NoWatchonlyCoins,
+    DescriptorExport(descriptors::ExportError),
+    /// No wallet with this name is loaded, or stored in the data directory.
+    WalletNotFound(String),
+    WalletAlreadyExists(String),
+    WalletAlreadyLoaded(String),
+    InvalidWalletName(String),
+    MainWalletUnload,
+    /// An error when setting up the database or the Bitcoin backend of a wallet.
+    WalletSetup(String),
+    InvalidGapLimit(u32),
+    AlreadyDiscoveringAddresses,
+    /// The Bitcoin backend could not tell us which addresses were used.
+    AddressDiscovery(String),
+    /// No `signing_config` was set in the configuration.
+    SigningDisabled,
+    UnknownSigner(bip32::Fingerprint),
+    SigningPolicy(PolicyViolation),
+    Signing(String),
+    SpendingPolicy(SpendingPolicyViolation),
+    /// There is no coin to prove the control of.
+    NoReserves,
+    InvalidProofOfReserves(ProofError),
+    /// The Bitcoin backend could not tell us which outputs are unspent.
+    UnspentOutputs(String),
 }
 
 impl fmt::Display for CommandError {
@@ -108,6 +170,7 @@ impl fmt::Display for CommandError {
                 }
             }
             Self::UnknownSpend(txid) => write!(f, "Unknown spend transaction '{}'.", txid),
+            Self::MusigSessionConflict(e) => write!(f, "{}.", e),
             Self::SpendFinalization(e) => {
                 write!(f, "Failed to finalize the spend transaction PSBT: '{}'.", e)
             }
@@ -131,7 +194,61 @@ impl fmt::Display for CommandError {
                 write!(f, "Unhardened or overflowing BIP32 derivation index.")
             }
             Self::RbfError(e) => write!(f, "RBF error: '{}'.", e),
+            Self::CpfpError(e) => write!(f, "CPFP error: '{}'.", e),
+            Self::Payjoin(e) => write!(f, "Payjoin error: '{}'.", e),
             Self::EmptyFilterList => write!(f, "Filter list is empty, should supply None instead."),
+            Self::InvalidConfTarget(target) => write!(
+                f,
+                "Invalid confirmation target: {}. Must be between 1 and {} blocks.",
+                target, MAX_CONF_TARGET
+            ),
+            Self::FeeEstimationUnavailable(target) => write!(
+                f,
+                "No feerate estimate available for a confirmation within {} blocks.",
+                target
+            ),
+            Self::InvalidWatchonlyDescriptor(e) => {
+                write!(f, "Invalid watch-only descriptor: '{}'.", e)
+            }
+            Self::NoWatchonlyCoins => write!(f, "No watch-only coin to sweep."),
+            Self::DescriptorExport(e) => write!(f, "Exporting the descriptor: {}", e),
+            Self::WalletNotFound(name) => write!(f, "Wallet '{}' not found.", name),
+            Self::WalletAlreadyExists(name) => write!(f, "Wallet '{}' already exists.", name),
+            Self::WalletAlreadyLoaded(name) => write!(f, "Wallet '{}' is already loaded.", name),
+            Self::InvalidWalletName(name) => write!(
+                f,
+                "Invalid wallet name '{}'. It must be made of at most 64 alphanumeric, '-' or '_' \
+                 characters and not be '{}'.",
+                name,
+                crate::wallets::MAIN_WALLET
+            ),
+            Self::MainWalletUnload => write!(f, "The main wallet can't be unloaded."),
+            Self::WalletSetup(e) => write!(f, "Error setting up the wallet: '{}'", e),
+            Self::InvalidGapLimit(gap) => write!(
+                f,
+                "Invalid gap limit: {}. Must be between 1 and {}.",
+                gap, MAX_GAP_LIMIT
+            ),
+            Self::AlreadyDiscoveringAddresses => write!(
+                f,
+                "There is already an address discovery ongoing. Please wait for it to complete first."
+            ),
+            Self::AddressDiscovery(e) => write!(f, "Error while discovering addresses: '{}'", e),
+            Self::SigningDisabled => write!(
+                f,
+                "Signing is not enabled. A 'signing_config' section must be set in the configuration."
+            ),
+            Self::UnknownSigner(fg) => write!(f, "No hot signer with fingerprint '{}'.", fg),
+            Self::SigningPolicy(e) => write!(f, "Refusing to sign: {}", e),
+            Self::Signing(e) => write!(f, "Error when signing: '{}'", e),
+            Self::SpendingPolicy(e) => {
+                write!(f, "The spend does not abide by the spending policy: {}", e)
+            }
+            Self::NoReserves => write!(f, "No unspent coin to prove the control of."),
+            Self::InvalidProofOfReserves(e) => write!(f, "Invalid proof of reserves: {}", e),
+            Self::UnspentOutputs(e) => {
+                write!(f, "Error when querying the unspent outputs: '{}'", e)
+            }
         }
     }
 }
@@ -144,6 +261,12 @@ impl From<SpendCreationError> for CommandError {
     }
 }
 
+impl From<payjoin::SenderError> for CommandError {
+    fn from(e: payjoin::SenderError) -> Self {
+        CommandError::Payjoin(e)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RbfErrorInfo {
     MissingFeerate,
@@ -169,6 +292,30 @@ impl fmt::Display for RbfErrorInfo {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpfpErrorInfo {
+    AlreadyConfirmed(bitcoin::OutPoint),
+    NotInMempool(bitcoin::Txid),
+    /// The transaction and its unconfirmed ancestors already pay this feerate, in sats/vb.
+    FeerateAlreadyReached(bitcoin::Txid, u64),
+}
+
+impl fmt::Display for CpfpErrorInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::AlreadyConfirmed(op) => write!(f, "Coin at '{}' is already confirmed.", op),
+            Self::NotInMempool(txid) => {
+                write!(f, "Transaction '{}' is not in the mempool.", txid)
+            }
+            Self::FeerateAlreadyReached(txid, feerate) => write!(
+                f,
+                "Transaction '{}' and its unconfirmed ancestors already pay {} sats/vb.",
+                txid, feerate
+            ),
+        }
+    }
+}
+
 /// A wallet transaction getter which fetches the transaction from our database backend with a cache
 /// to avoid needless redundant calls. Note the cache holds an Option<> so we also avoid redundant
 /// calls when the txid isn't known by our database backend.
@@ -201,6 +348,23 @@ impl TxGetter for DbTxGetter<'_> {
     }
 }
 
+fn curr_timestamp() -> u32 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("time measured now cannot be before unix epoch")
+        .as_secs()
+        .try_into()
+        .expect("u32 timestamp")
+}
+
+// The number of confirmations of this coin at the given tip height.
+fn coin_confirmations(coin: &Coin, tip_height: Option<i32>) -> u32 {
+    match (coin.block_info, tip_height) {
+        (Some(block), Some(tip_height)) => (tip_height - block.height + 1).try_into().unwrap_or(0),
+        _ => 0,
+    }
+}
+
 fn coin_to_candidate(
     coin: &Coin,
     must_select: bool,
@@ -218,6 +382,32 @@ fn coin_to_candidate(
     }
 }
 
+// Get the definite descriptor for this coin of a watch-only descriptor, from whichever of its
+// single-path descriptors it was derived.
+fn watchonly_coin_desc(
+    coin: &WatchonlyCoin,
+    network: bitcoin::Network,
+    secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+) -> Descriptor<DefiniteDescriptorKey> {
+    coin.descriptor
+        .clone()
+        .into_single_descriptors()
+        .expect("Checked at import")
+        .into_iter()
+        .map(|desc| {
+            desc.at_derivation_index(coin.derivation_index.into())
+                .expect("Index is never hardened.")
+        })
+        .find(|desc| {
+            desc.derived_descriptor(secp)
+                .ok()
+                .and_then(|desc| desc.address(network).ok())
+                .as_ref()
+                == Some(&coin.address)
+        })
+        .expect("The address of the coin was derived from this descriptor.")
+}
+
 impl DaemonControl {
     // Get the derived descriptor for this coin
     fn derived_desc(&self, coin: &Coin) -> descriptors::DerivedSinglePathLianaDesc {
@@ -315,6 +505,129 @@ impl DaemonControl {
             .expect("block height must fit in u32");
         spend::anti_fee_sniping_locktime(now, tip_height, tip_time)
     }
+
+    // The median time past from which time-based relative timelocks are counted for a coin
+    // confirmed at this height. As per BIP68 it's the one of the block preceding the coin's.
+    fn coin_median_time_past(&self, height: i32) -> Option<u32> {
+        self.bitcoin.median_time_past((height - 1).max(0))
+    }
+
+    // Describe this spend for the purpose of checking it against the spending policy. The
+    // outputs in `new_labels` count as labelled.
+    fn policy_spend(
+        &self,
+        db_conn: &mut Box<dyn DatabaseConnection>,
+        psbt: &Psbt,
+        new_labels: &HashSet<bitcoin::OutPoint>,
+    ) -> Result<PolicySpend, SpendingPolicyViolation> {
+        // Inputs which are not our coins, such as those of the receiver of a payjoin, are not
+        // accounted for.
+        let outpoints: Vec<bitcoin::OutPoint> = psbt
+            .unsigned_tx
+            .input
+            .iter()
+            .map(|txin| txin.previous_output)
+            .collect();
+        let coins = db_conn.coins_by_outpoints(&outpoints);
+        let our_coins: HashMap<_, _> = coins
+            .iter()
+            .map(|(outpoint, coin)| (*outpoint, coin.amount))
+            .collect();
+        let amount = SpendSummary::new(&self.config.main_descriptor, psbt, &our_coins, &self.secp)
+            .map_err(SpendingPolicyViolation::InsaneSpend)?
+            .amount;
+        let txid = psbt.unsigned_tx.compute_txid();
+        let network = self.config.bitcoin_config.network;
+
+        // An output is labelled if either itself or its address is.
+        let change_indexes: HashSet<usize> = self
+            .config
+            .main_descriptor
+            .change_indexes(psbt, &self.secp)
+            .into_iter()
+            .map(|change| change.index())
+            .collect();
+        let outputs: Vec<_> = psbt
+            .unsigned_tx
+            .output
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !change_indexes.contains(index))
+            .map(|(index, txo)| {
+                let vout = index
+                    .try_into()
+                    .expect("Number of outputs must fit in a u32");
+                let address = bitcoin::Address::from_script(&txo.script_pubkey, network).ok();
+                (
+                    bitcoin::OutPoint { txid, vout },
+                    txo.script_pubkey.clone(),
+                    address,
+                )
+            })
+            .collect();
+        let label_items: HashSet<LabelItem> = outputs
+            .iter()
+            .flat_map(|(outpoint, _, address)| {
+                std::iter::once(LabelItem::OutPoint(*outpoint))
+                    .chain(address.clone().map(LabelItem::Address))
+            })
+            .collect();
+        let labels = db_conn.labels(&label_items);
+        let destinations = outputs
+            .into_iter()
+            .map(|(outpoint, script_pubkey, address)| DestinationOutput {
+                outpoint,
+                script_pubkey,
+                is_labelled: new_labels.contains(&outpoint)
+                    || labels.contains_key(&outpoint.to_string())
+                    || address.map(|addr| labels.contains_key(&addr.to_string())) == Some(true),
+            })
+            .collect();
+
+        let tip_height = db_conn.chain_tip().map(|tip| tip.height);
+        let inputs = outpoints
+            .into_iter()
+            .filter_map(|op| {
+                coins
+                    .get(&op)
+                    .map(|coin| (op, coin_confirmations(coin, tip_height)))
+            })
+            .collect();
+
+        Ok(PolicySpend {
+            txid,
+            amount,
+            destinations,
+            inputs,
+        })
+    }
+
+    // Check this spend against the given spending policy. The outputs in `new_labels` count as
+    // labelled. The broadcast delay is only checked for a spend which is already stored. Returns
+    // the value the spend sends out of the wallet along with the rules it does not abide by.
+    fn spending_policy_violations(
+        &self,
+        db_conn: &mut Box<dyn DatabaseConnection>,
+        policy: &SpendingPolicy,
+        psbt: &Psbt,
+        new_labels: &HashSet<bitcoin::OutPoint>,
+        is_stored: bool,
+    ) -> Result<(bitcoin::Amount, Vec<SpendingPolicyViolation>), SpendingPolicyViolation> {
+        let spend = self.policy_spend(db_conn, psbt, new_labels)?;
+        let now = curr_timestamp();
+        let broadcast_spends =
+            db_conn.broadcast_spends_since(now.saturating_sub(policy.longest_window()));
+        let mut violations = policy.violations(&spend, &broadcast_spends, now);
+        if is_stored {
+            let stored_at = db_conn
+                .list_spend()
+                .into_iter()
+                .find(|(psbt, _)| psbt.unsigned_tx.compute_txid() == spend.txid)
+                .and_then(|(_, updated_at)| updated_at);
+            violations.extend(policy.broadcast_delay_violation(stored_at, now));
+        }
+        Ok((spend.amount, violations))
+    }
 }
 
 impl DaemonControl {
@@ -335,8 +648,17 @@ impl DaemonControl {
             sync: self.bitcoin.sync_progress().rounded_up_progress(),
             descriptors: GetInfoDescriptors {
                 main: self.config.main_descriptor.clone(),
+                watchonly: db_conn
+                    .watchonly_descriptors()
+                    .into_iter()
+                    .map(|(desc, _)| desc)
+                    .collect(),
             },
             rescan_progress,
+            address_discovery: *self
+                .address_discovery
+                .lock()
+                .expect("Address discovery lock poisoned"),
             timestamp: wallet.timestamp,
             last_poll_timestamp: wallet.last_poll_timestamp,
             receive_index,
@@ -344,6 +666,21 @@ impl DaemonControl {
         }
     }
 
+    /// Export the main descriptor in the given format, for registration on a signer under the
+    /// given name.
+    pub fn export_descriptor(
+        &self,
+        format: descriptors::ExportFormat,
+        name: &str,
+    ) -> Result<ExportDescriptorResult, CommandError> {
+        let content = self
+            .config
+            .main_descriptor
+            .export(format, name, self.config.bitcoin_config.network)
+            .map_err(CommandError::DescriptorExport)?;
+        Ok(ExportDescriptorResult { format, content })
+    }
+
     /// Get a new deposit address. This will always generate a new deposit address, regardless of
     /// whether it was actually used.
     pub fn get_new_address(&self) -> GetAddressResult {
@@ -416,6 +753,152 @@ impl DaemonControl {
         })
     }
 
+    // Find the last used address past this derivation index on one of our keychains, stopping
+    // after `gap_limit` consecutive unused addresses. Errors if the Bitcoin backend can't tell
+    // whether the addresses to check were used.
+    fn last_used_index(
+        &self,
+        is_change: bool,
+        from_index: u32,
+        gap_limit: u32,
+    ) -> Result<Option<u32>, CommandError> {
+        let desc = if is_change {
+            self.config.main_descriptor.change_descriptor()
+        } else {
+            self.config.main_descriptor.receive_descriptor()
+        };
+
+        // A backend which only tracks a limited range of addresses lists all of them anyway, so
+        // query all those within the gap limit at once.
+        let max_index = self
+            .bitcoin
+            .used_spks_max_index(&self.config.main_descriptor);
+        let batch_size = if max_index.is_some() {
+            gap_limit
+        } else {
+            DISCOVERY_BATCH_SIZE
+        };
+
+        let mut last_used = None;
+        let mut next_index = from_index + 1;
+        loop {
+            let unused_count = next_index - last_used.map(|i| i + 1).unwrap_or(from_index + 1);
+            if unused_count >= gap_limit {
+                return Ok(last_used);
+            }
+            let batch_end = next_index + cmp::min(gap_limit - unused_count, batch_size);
+            if let Some(max_index) = max_index.filter(|max_index| batch_end - 1 > *max_index) {
+                return Err(CommandError::AddressDiscovery(format!(
+                    "The Bitcoin backend only tracks the addresses up to derivation index {}, \
+                     use a lower gap limit.",
+                    max_index
+                )));
+            }
+            let spks = (next_index..batch_end)
+                .map(|index| {
+                    ChildNumber::from_normal_idx(index)
+                        .map(|index| desc.derive(index, &self.secp).script_pubkey())
+                        .map_err(|_| CommandError::InvalidDerivationIndex)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let used = self
+                .bitcoin
+                .used_spks(&spks)
+                .map_err(CommandError::AddressDiscovery)?;
+            for (index, spk) in (next_index..batch_end).zip(spks.iter()) {
+                if used.contains(spk) {
+                    last_used = Some(index);
+                }
+            }
+            next_index = batch_end;
+
+            if let Some(progress) = self
+                .address_discovery
+                .lock()
+                .expect("Address discovery lock poisoned")
+                .as_mut()
+            {
+                if is_change {
+                    progress.change_index = next_index - 1;
+                } else {
+                    progress.receive_index = next_index - 1;
+                }
+            }
+        }
+    }
+
+    /// Look for used addresses past our derivation indexes on both the receive and change
+    /// keychains, until `gap_limit` consecutive unused addresses are found on each. The gap limit
+    /// defaults to the one configured.
+    ///
+    /// If any is found, the derivation indexes are bumped accordingly and a rescan from the wallet
+    /// creation date is started to look for the transactions involving them.
+    pub fn discover_addresses(
+        &mut self,
+        gap_limit: Option<u32>,
+    ) -> Result<DiscoverAddressesResult, CommandError> {
+        let gap_limit = gap_limit.unwrap_or(self.config.gap_limit);
+        if gap_limit == 0 || gap_limit > MAX_GAP_LIMIT {
+            return Err(CommandError::InvalidGapLimit(gap_limit));
+        }
+
+        let mut db_conn = self.db.connection();
+        let db_receive: u32 = db_conn.receive_index().into();
+        let db_change: u32 = db_conn.change_index().into();
+        {
+            let mut progress = self
+                .address_discovery
+                .lock()
+                .expect("Address discovery lock poisoned");
+            if progress.is_some() {
+                return Err(CommandError::AlreadyDiscoveringAddresses);
+            }
+            *progress = Some(AddressDiscoveryProgress {
+                receive_index: db_receive,
+                change_index: db_change,
+            });
+        }
+        let last_used = self
+            .last_used_index(false, db_receive, gap_limit)
+            .and_then(|receive| {
+                self.last_used_index(true, db_change, gap_limit)
+                    .map(|change| (receive, change))
+            });
+        *self
+            .address_discovery
+            .lock()
+            .expect("Address discovery lock poisoned") = None;
+        let (last_receive, last_change) = last_used?;
+
+        if let Some(index) = last_receive {
+            log::info!("Discovered used receive address at index {}.", index);
+            db_conn.set_receive_index(index.into(), &self.secp);
+        }
+        if let Some(index) = last_change {
+            log::info!("Discovered used change address at index {}.", index);
+            db_conn.set_change_index(index.into(), &self.secp);
+        }
+
+        // We don't know about the transactions paying to the discovered addresses. Look for them.
+        let mut rescan = false;
+        if last_receive.is_some() || last_change.is_some() {
+            let timestamp = db_conn.wallet().timestamp;
+            match self.start_rescan(timestamp) {
+                Ok(()) => rescan = true,
+                Err(CommandError::AlreadyRescanning) => log::warn!(
+                    "Not starting a rescan for the discovered addresses, one is already ongoing."
+                ),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(DiscoverAddressesResult {
+            receive: last_receive.unwrap_or(db_receive),
+            change: last_change.unwrap_or(db_change),
+            rescan,
+        })
+    }
+
     /// list addresses
     pub fn list_addresses(
         &self,
@@ -578,6 +1061,20 @@ impl DaemonControl {
         outpoints: &[bitcoin::OutPoint],
     ) -> ListCoinsResult {
         let mut db_conn = self.db.connection();
+        // Only query the median time past of the coins' blocks if we have a use for it. Make sure
+        // to only query it once per block.
+        let tip_mtp = if self
+            .config
+            .main_descriptor
+            .policy()
+            .time_recovery_paths()
+            .is_empty()
+        {
+            None
+        } else {
+            self.bitcoin.tip_median_time_past()
+        };
+        let mut coins_mtp: HashMap<i32, Option<u32>> = HashMap::new();
         let coins: Vec<ListCoinsEntry> = db_conn
             .coins(statuses, outpoints)
             .into_values()
@@ -591,6 +1088,7 @@ impl DaemonControl {
                     is_immature,
                     is_change,
                     is_from_self,
+                    is_frozen,
                     derivation_index,
                     ..
                 } = coin;
@@ -599,6 +1097,12 @@ impl DaemonControl {
                     height: spend_block.map(|b| b.height),
                 });
                 let block_height = block_info.map(|b| b.height);
+                let time_since_confirmation = tip_mtp.zip(block_height).and_then(|(tip_mtp, h)| {
+                    coins_mtp
+                        .entry(h)
+                        .or_insert_with(|| self.coin_median_time_past(h))
+                        .map(|coin_mtp| tip_mtp.saturating_sub(coin_mtp))
+                });
                 let address = self
                     .derived_desc(&coin)
                     .address(self.config.bitcoin_config.network);
@@ -612,15 +1116,101 @@ impl DaemonControl {
                     is_immature,
                     is_change,
                     is_from_self,
+                    is_frozen,
+                    time_since_confirmation,
+                }
+            })
+            .collect();
+        // The coins of the watch-only descriptors are always unspent, as we only track their
+        // current unspent coins.
+        let watchonly_coins = db_conn
+            .watchonly_coins()
+            .into_iter()
+            .filter(|coin| {
+                let status = if coin.is_confirmed() {
+                    CoinStatus::Confirmed
+                } else {
+                    CoinStatus::Unconfirmed
+                };
+                (statuses.is_empty() || statuses.contains(&status))
+                    && (outpoints.is_empty() || outpoints.contains(&coin.outpoint))
+            })
+            .map(|coin| {
+                let WatchonlyCoin {
+                    descriptor,
+                    outpoint,
+                    amount,
+                    address,
+                    derivation_index,
+                    block_height,
+                } = coin;
+                ListWatchonlyCoinsEntry {
+                    descriptor,
+                    outpoint,
+                    amount,
+                    address,
+                    derivation_index,
+                    block_height,
                 }
             })
             .collect();
-        ListCoinsResult { coins }
+        ListCoinsResult {
+            coins,
+            watchonly_coins,
+        }
     }
 
+    /// Freeze a set of coins. Frozen coins are never selected automatically when creating a
+    /// transaction, but may still be spent if explicitly selected.
+    pub fn freeze_coins(&self, outpoints: &[bitcoin::OutPoint]) -> Result<(), CommandError> {
+        let mut db_conn = self.db.connection();
+        let coins = db_conn.coins(&[], outpoints);
+        for op in outpoints {
+            let coin = coins.get(op).ok_or(CommandError::UnknownOutpoint(*op))?;
+            if coin.is_spent() {
+                return Err(CommandError::AlreadySpent(*op));
+            }
+        }
+        db_conn.freeze_coins(outpoints);
+        Ok(())
+    }
+
+    /// Unfreeze a set of coins, making them available again for automatic coin selection.
+    pub fn unfreeze_coins(&self, outpoints: &[bitcoin::OutPoint]) -> Result<(), CommandError> {
+        let mut db_conn = self.db.connection();
+        let coins = db_conn.coins(&[], outpoints);
+        if let Some(op) = outpoints.iter().find(|op| !coins.contains_key(op)) {
+            return Err(CommandError::UnknownOutpoint(*op));
+        }
+        db_conn.unfreeze_coins(outpoints);
+        Ok(())
+    }
+
+    /// Estimate the feerate necessary for a transaction to confirm within `conf_target` blocks.
+    pub fn estimate_fee(
+        &self,
+        conf_target: u16,
+        mode: FeeEstimateMode,
+    ) -> Result<EstimateFeeResult, CommandError> {
+        if !(1..=MAX_CONF_TARGET).contains(&conf_target) {
+            return Err(CommandError::InvalidConfTarget(conf_target));
+        }
+        let FeeEstimate { feerate_vb, blocks } = self
+            .bitcoin
+            .estimate_fee(conf_target, mode)
+            .ok_or(CommandError::FeeEstimationUnavailable(conf_target))?;
+        Ok(EstimateFeeResult {
+            feerate: feerate_vb,
+            blocks,
+        })
+    }
+
+    /// Create a Spend transaction paying to `destinations`, in this order. The same address may
+    /// appear more than once. The label of a destination, if any, is set on its output once the
+    /// Spend is stored.
     pub fn create_spend(
         &self,
-        destinations: &HashMap<bitcoin::Address<bitcoin::address::NetworkUnchecked>, u64>,
+        destinations: &[Destination],
         coins_outpoints: &[bitcoin::OutPoint],
         feerate_vb: u64,
         change_address: Option<bitcoin::Address<bitcoin::address::NetworkUnchecked>>,
@@ -638,9 +1228,9 @@ impl DaemonControl {
 
         // Prepare the destination addresses.
         let mut destinations_checked = Vec::with_capacity(destinations.len());
-        for (address, value_sat) in destinations {
-            let address = self.validate_address(address.clone())?;
-            let amount = bitcoin::Amount::from_sat(*value_sat);
+        for destination in destinations {
+            let address = self.validate_address(destination.address.clone())?;
+            let amount = bitcoin::Amount::from_sat(destination.amount);
             let address = self.spend_addr(&mut db_conn, address);
             destinations_checked.push((address, amount));
         }
@@ -659,13 +1249,27 @@ impl DaemonControl {
         // If no coins have been specified, then coins will be selected automatically for
         // the spend from a set of optional candidates.
         // Otherwise, only the specified coins will be used, all as mandatory candidates.
+        // Frozen coins are never selected automatically, but the caller may still spend them
+        // explicitly in which case we warn about it.
+        let mut frozen_warnings = Vec::new();
         let candidate_coins: Vec<CandidateCoin> = if coins_outpoints.is_empty() {
+            // Coins with fewer confirmations than required by the spending policy are never
+            // selected automatically.
+            let min_confirmations = self
+                .config
+                .spending_policy
+                .as_ref()
+                .and_then(|policy| policy.min_confirmations)
+                .unwrap_or(0);
+            let tip_height = db_conn.chain_tip().map(|tip| tip.height);
             // From our unconfirmed coins, we only include those that are from self
             // since unconfirmed external deposits are more at risk of being dropped
             // unexpectedly from the mempool as they are beyond the user's control.
             db_conn
                 .coins(&[CoinStatus::Unconfirmed, CoinStatus::Confirmed], &[])
                 .into_iter()
+                .filter(|(_, c)| !c.is_frozen)
+                .filter(|(_, c)| coin_confirmations(c, tip_height) >= min_confirmations)
                 .filter_map(|(op, c)| {
                     if c.block_info.is_some() {
                         Some((c, None)) // confirmed coins have no ancestor info
@@ -710,6 +1314,12 @@ impl DaemonControl {
                 if coin.is_immature {
                     return Err(CommandError::ImmatureCoinbase(*op));
                 }
+                if coin.is_frozen {
+                    frozen_warnings.push(format!(
+                        "Coin {} is frozen. It was spent because it was explicitly selected.",
+                        op
+                    ));
+                }
             }
             coins
                 .into_iter()
@@ -769,16 +1379,59 @@ impl DaemonControl {
                 return Err(e.into());
             }
         };
+
+        // The destinations' outputs come first, in the order they were given.
+        let txid = psbt.unsigned_tx.compute_txid();
+        let labels: HashMap<LabelItem, Option<String>> = destinations
+            .iter()
+            .enumerate()
+            .filter_map(|(vout, destination)| {
+                let vout = vout
+                    .try_into()
+                    .expect("Number of outputs must fit in a u32");
+                destination.label.as_ref().map(|label| {
+                    (
+                        LabelItem::OutPoint(bitcoin::OutPoint { txid, vout }),
+                        Some(label.clone()),
+                    )
+                })
+            })
+            .collect();
+
+        // Make sure the spend abides by the spending policy, if any, before updating our state.
+        if let Some(policy) = &self.config.spending_policy {
+            let new_labels: HashSet<bitcoin::OutPoint> = labels
+                .keys()
+                .filter_map(|item| match item {
+                    LabelItem::OutPoint(outpoint) => Some(*outpoint),
+                    _ => None,
+                })
+                .collect();
+            let (_, violations) = self
+                .spending_policy_violations(&mut db_conn, policy, &psbt, &new_labels, false)
+                .map_err(CommandError::SpendingPolicy)?;
+            if let Some(violation) = violations.into_iter().next() {
+                return Err(CommandError::SpendingPolicy(violation));
+            }
+        }
+
         for (addr, _) in destinations_checked {
             self.maybe_increase_last_deriv_index(&mut db_conn, &addr.info);
         }
         if has_change {
             self.maybe_increase_last_deriv_index(&mut db_conn, &change_info);
         }
+        if !labels.is_empty() {
+            self.add_pending_labels(txid, labels);
+        }
 
         Ok(CreateSpendResult::Success {
             psbt,
-            warnings: warnings.iter().map(|w| w.to_string()).collect(),
+            warnings: warnings
+                .iter()
+                .map(|w| w.to_string())
+                .chain(frozen_warnings)
+                .collect(),
         })
     }
 
@@ -817,6 +1470,10 @@ impl DaemonControl {
                 if db_psbtin.tap_key_sig.is_none() {
                     db_psbtin.tap_key_sig = psbtin.tap_key_sig;
                 }
+                // Also merge the MuSig2 nonces and partial signatures. Those we have must not
+                // change, or the signing session could not complete.
+                descriptors::musig::merge_session_fields(db_psbtin, psbtin)
+                    .map_err(CommandError::MusigSessionConflict)?;
             }
             psbt = db_psbt;
         } else {
@@ -836,10 +1493,38 @@ impl DaemonControl {
 
         // Finally, insert (or update) the PSBT in database.
         db_conn.store_spend(&psbt);
+        self.store_pending_labels(&mut db_conn, &txid);
 
         Ok(())
     }
 
+    // Keep the labels of the outputs of this newly created spend until it is stored, so no label
+    // is left behind for a spend which is never stored. Only those of the latest spends are kept.
+    fn add_pending_labels(&self, txid: bitcoin::Txid, labels: HashMap<LabelItem, Option<String>>) {
+        let mut pending_labels = self.pending_labels.lock().expect("Must not be poisoned");
+        pending_labels.retain(|(pending_txid, _)| *pending_txid != txid);
+        if pending_labels.len() >= MAX_PENDING_LABELS {
+            pending_labels.pop_front();
+        }
+        pending_labels.push_back((txid, labels));
+    }
+
+    // Set the labels of the outputs of this spend we created, now that it was stored.
+    fn store_pending_labels(
+        &self,
+        db_conn: &mut Box<dyn DatabaseConnection>,
+        txid: &bitcoin::Txid,
+    ) {
+        let mut pending_labels = self.pending_labels.lock().expect("Must not be poisoned");
+        if let Some(index) = pending_labels
+            .iter()
+            .position(|(pending_txid, _)| pending_txid == txid)
+        {
+            let (_, labels) = pending_labels.remove(index).expect("Index was just found");
+            db_conn.update_labels(&labels);
+        }
+    }
+
     pub fn update_labels(&self, items: &HashMap<LabelItem, Option<String>>) {
         let mut db_conn = self.db.connection();
         db_conn.update_labels(items);
@@ -859,6 +1544,119 @@ impl DaemonControl {
         }
     }
 
+    /// Import labels from BIP329 records, one JSON object per line. Only the labels for
+    /// transactions, addresses and outputs are stored. The records which could not be imported
+    /// are reported along with the reason why.
+    pub fn import_labels_bip329(
+        &self,
+        records: &str,
+        conflict_policy: LabelsConflictPolicy,
+    ) -> ImportLabelsBip329Result {
+        let network = self.config.bitcoin_config.network;
+        let mut skipped = Vec::new();
+        // If the same item is labelled more than once, the last record wins.
+        let mut labels = HashMap::<LabelItem, (SkippedBip329Record, String)>::new();
+
+        for (i, line) in records.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            // Parse the record in two steps in order to report its type and reference even if
+            // Liana can't make sense of it.
+            let mut record = SkippedBip329Record {
+                line: i + 1,
+                type_: None,
+                ref_: None,
+                reason: SkippedBip329Reason::Invalid,
+            };
+            let value = match serde_json::from_str::<serde_json::Value>(line) {
+                Ok(value) => value,
+                Err(e) => {
+                    log::debug!("Invalid BIP329 record at line {}: {}", record.line, e);
+                    skipped.push(record);
+                    continue;
+                }
+            };
+            record.type_ = value.get("type").and_then(|t| t.as_str()).map(String::from);
+            record.ref_ = value.get("ref").and_then(|r| r.as_str()).map(String::from);
+            let parsed = match serde_json::from_value::<bip329::Label>(value) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    log::debug!("Invalid BIP329 record at line {}: {}", record.line, e);
+                    skipped.push(record);
+                    continue;
+                }
+            };
+
+            match LabelItem::from_bip329(&parsed, network) {
+                Some((_, label)) if label.len() > MAX_LABEL_LEN => {
+                    record.reason = SkippedBip329Reason::TooLong;
+                    skipped.push(record);
+                }
+                Some((item, label)) if !label.is_empty() => {
+                    labels.insert(item, (record, label));
+                }
+                _ => {
+                    record.reason = match &parsed {
+                        bip329::Label::Transaction(bip329::TransactionRecord { label, .. })
+                        | bip329::Label::Address(bip329::AddressRecord { label, .. })
+                        | bip329::Label::Output(bip329::OutputRecord { label, .. }) => {
+                            if label.as_deref().unwrap_or_default().is_empty() {
+                                SkippedBip329Reason::MissingLabel
+                            } else if matches!(parsed, bip329::Label::Address(_)) {
+                                SkippedBip329Reason::WrongNetwork
+                            } else {
+                                SkippedBip329Reason::Invalid
+                            }
+                        }
+                        _ => SkippedBip329Reason::Unsupported,
+                    };
+                    skipped.push(record);
+                }
+            }
+        }
+
+        let mut db_conn = self.db.connection();
+        if conflict_policy == LabelsConflictPolicy::Merge {
+            // Query the existing labels in multiple chunks.
+            let items: Vec<LabelItem> = labels.keys().cloned().collect();
+            let mut existing = HashSet::<String>::with_capacity(items.len());
+            const CHUNK_SIZE: usize = 100;
+            for chunk in items.chunks(CHUNK_SIZE) {
+                let chunk: HashSet<LabelItem> = chunk.iter().cloned().collect();
+                existing.extend(db_conn.labels(&chunk).into_keys());
+            }
+            labels.retain(|item, (record, _)| {
+                if existing.contains(&item.to_string()) {
+                    record.reason = SkippedBip329Reason::Conflict;
+                    skipped.push(record.clone());
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+
+        let imported = labels.len();
+        db_conn.update_labels(
+            &labels
+                .into_iter()
+                .map(|(item, (_, value))| (item, Some(value)))
+                .collect(),
+        );
+        skipped.sort_by_key(|record| record.line);
+
+        ImportLabelsBip329Result { imported, skipped }
+    }
+
+    /// Get notified of the events affecting the wallet, of the given kinds or all of them if
+    /// `None`.
+    pub fn subscribe(&self, kinds: Option<HashSet<EventKind>>) -> Subscription {
+        self.notifier.subscribe(kinds)
+    }
+
     pub fn list_spend(
         &self,
         txids: Option<Vec<bitcoin::Txid>>,
@@ -892,31 +1690,130 @@ impl DaemonControl {
         db_conn.delete_spend(txid);
     }
 
-    /// Finalize and broadcast this stored Spend transaction.
-    pub fn broadcast_spend(&self, txid: &bitcoin::Txid) -> Result<(), CommandError> {
+    /// Sign the stored spend with this txid using the hot signer with this fingerprint, provided
+    /// it abides by the signing policy. The signed PSBT is stored in place of the existing one.
+    pub fn sign_spend(
+        &self,
+        txid: &bitcoin::Txid,
+        fingerprint: bip32::Fingerprint,
+    ) -> Result<SignSpendResult, CommandError> {
+        let signers = self.signers.as_ref().ok_or(CommandError::SigningDisabled)?;
+        if !signers.fingerprints().contains(&fingerprint) {
+            return Err(CommandError::UnknownSigner(fingerprint));
+        }
         let mut db_conn = self.db.connection();
-
-        // First, try to finalize the spending transaction with the elements contained
-        // in the PSBT.
-        let mut spend_psbt = db_conn
+        let psbt = db_conn
             .spend_tx(txid)
             .ok_or(CommandError::UnknownSpend(*txid))?;
-        spend_psbt.finalize_mut(&self.secp).map_err(|e| {
-            CommandError::SpendFinalization(
-                e.into_iter()
-                    .next()
-                    .map(|e| e.to_string())
-                    .unwrap_or_default(),
-            )
-        })?;
 
-        // Then, broadcast it (or try to, we never know if we are not going to hit an
-        // error at broadcast time).
-        // These checks are already performed at Spend creation time. TODO: a belt-and-suspenders is still worth it though.
+        // Check the spend against the policy. The spends signed over the last 24 hours count
+        // towards the daily limit. Only our own coins count towards the amount it sends.
+        let outpoints: Vec<_> = psbt
+            .unsigned_tx
+            .input
+            .iter()
+            .map(|txin| txin.previous_output)
+            .collect();
+        let our_coins: HashMap<_, _> = db_conn
+            .coins_by_outpoints(&outpoints)
+            .into_iter()
+            .map(|(outpoint, coin)| (outpoint, coin.amount))
+            .collect();
+        let mut spend =
+            SpendSummary::new(&self.config.main_descriptor, &psbt, &our_coins, &self.secp)
+                .map_err(|e| CommandError::SigningPolicy(PolicyViolation::InsaneSpend(e)))?;
+        // The spends signed for all the wallets are recorded together, and no other spend may be
+        // signed until this one is recorded.
+        let signed_spends_db = signers.signed_spends_db();
+        let mut signed_spends_conn = signed_spends_db.connection();
+        let now = curr_timestamp();
+        let mut already_signed = bitcoin::Amount::ZERO;
+        for (signed_txid, amount) in
+            signed_spends_conn.signed_spends_since(now.saturating_sub(DAILY_LIMIT_PERIOD))
+        {
+            // If this spend was already signed, it counts at the highest value it was signed for.
+            if signed_txid == *txid {
+                spend.amount = spend.amount.max(amount);
+            } else {
+                already_signed += amount;
+            }
+        }
+        signers
+            .policy()
+            .check(&spend, already_signed)
+            .map_err(CommandError::SigningPolicy)?;
+
+        let signed_psbt = signers
+            .sign(fingerprint, psbt.clone())
+            .expect("We checked the fingerprint above.")
+            .map_err(|e| CommandError::Signing(e.to_string()))?;
+        // Only record the spend if our signer did take part in it.
+        if signed_psbt != psbt {
+            signed_spends_conn.record_signed_spend(txid, spend.amount, now);
+            db_conn.store_spend(&signed_psbt);
+            self.store_pending_labels(&mut db_conn, txid);
+        }
+
+        Ok(SignSpendResult { psbt: signed_psbt })
+    }
+
+    // Finalize the inputs of this PSBT. Inputs which are finalized already, such as the inputs
+    // of the receiver in a payjoin, are left untouched.
+    fn finalize_spend(&self, psbt: &mut Psbt) -> Result<(), CommandError> {
+        for index in 0..psbt.inputs.len() {
+            let psbt_in = &psbt.inputs[index];
+            if psbt_in.final_script_sig.is_some() || psbt_in.final_script_witness.is_some() {
+                continue;
+            }
+            psbt.finalize_inp_mut(&self.secp, index)
+                .map_err(|e| CommandError::SpendFinalization(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Finalize and broadcast this stored Spend transaction.
+    pub fn broadcast_spend(&self, txid: &bitcoin::Txid) -> Result<(), CommandError> {
+        let mut db_conn = self.db.connection();
+
+        // First, try to finalize the spending transaction with the elements contained
+        // in the PSBT.
+        let mut spend_psbt = db_conn
+            .spend_tx(txid)
+            .ok_or(CommandError::UnknownSpend(*txid))?;
+
+        // Make sure the spend abides by the spending policy, if any.
+        let policy_amount = match &self.config.spending_policy {
+            Some(policy) => {
+                let (amount, violations) = self
+                    .spending_policy_violations(
+                        &mut db_conn,
+                        policy,
+                        &spend_psbt,
+                        &HashSet::new(),
+                        true,
+                    )
+                    .map_err(CommandError::SpendingPolicy)?;
+                if let Some(violation) = violations.into_iter().next() {
+                    return Err(CommandError::SpendingPolicy(violation));
+                }
+                Some(amount)
+            }
+            None => None,
+        };
+
+        self.finalize_spend(&mut spend_psbt)?;
+
+        // Then, broadcast it (or try to, we never know if we are not going to hit an
+        // error at broadcast time).
+        // These checks are already performed at Spend creation time. TODO: a belt-and-suspenders is still worth it though.
         let final_tx = spend_psbt.extract_tx_unchecked_fee_rate();
         self.bitcoin
             .broadcast_tx(&final_tx)
             .map_err(CommandError::TxBroadcast)?;
+        // It counts towards the window limits of the spending policy from now on.
+        if let Some(amount) = policy_amount {
+            db_conn.record_broadcast_spend(txid, amount, curr_timestamp());
+        }
 
         // Finally, update our state with the changes from this transaction.
         let (tx, rx) = mpsc::sync_channel(0);
@@ -930,6 +1827,33 @@ impl DaemonControl {
         Ok(())
     }
 
+    /// Check this stored Spend transaction against the spending policy, without broadcasting it.
+    /// Returns all the rules it does not abide by. There is none if no policy is set.
+    pub fn check_spend(&self, txid: &bitcoin::Txid) -> Result<CheckSpendResult, CommandError> {
+        let mut db_conn = self.db.connection();
+        let psbt = db_conn
+            .spend_tx(txid)
+            .ok_or(CommandError::UnknownSpend(*txid))?;
+        let violations = match &self.config.spending_policy {
+            Some(policy) => {
+                match self.spending_policy_violations(
+                    &mut db_conn,
+                    policy,
+                    &psbt,
+                    &HashSet::new(),
+                    true,
+                ) {
+                    Ok((_, violations)) => violations,
+                    Err(e) => vec![e],
+                }
+            }
+            None => Vec::new(),
+        };
+        Ok(CheckSpendResult {
+            violations: violations.iter().map(|v| v.to_string()).collect(),
+        })
+    }
+
     /// Create PSBT to replace the given transaction using RBF.
     ///
     /// `txid` must either point to a PSBT in our database (not necessarily broadcast) or an
@@ -1116,8 +2040,9 @@ impl DaemonControl {
             .into_values()
             .filter_map(|c| {
                 // Make sure we don't have duplicate candidates in case any of the coins are not
-                // currently set as spending in the DB (and are therefore still confirmed).
-                if !prev_coins.contains_key(&c.outpoint) {
+                // currently set as spending in the DB (and are therefore still confirmed). Frozen
+                // coins are never added automatically.
+                if !prev_coins.contains_key(&c.outpoint) && !c.is_frozen {
                     Some(coin_to_candidate(
                         &c, /*must_select=*/ false, /*sequence=*/ None,
                         /*ancestor_info=*/ None,
@@ -1187,6 +2112,412 @@ impl DaemonControl {
         }
     }
 
+    /// Create a transaction spending the given unconfirmed coins back to ourselves in order to
+    /// speed up the confirmation of the transactions which created them (Child Pays For Parent).
+    /// The fee of this child transaction is set such as the package made of the child and all its
+    /// unconfirmed ancestors has a feerate of `feerate_vb`. If the value of the given coins isn't
+    /// enough to pay for this fee, confirmed coins are selected to be used as additional inputs.
+    pub fn cpfp_bump(
+        &self,
+        outpoints: &[bitcoin::OutPoint],
+        feerate_vb: u64,
+    ) -> Result<CreateSpendResult, CommandError> {
+        if outpoints.is_empty() {
+            return Err(CommandError::NoOutpointForSelfSend);
+        }
+        if feerate_vb < 1 {
+            return Err(CommandError::InvalidFeerate(feerate_vb));
+        }
+        let mut db_conn = self.db.connection();
+        let mut tx_getter = DbTxGetter::new(&self.db);
+
+        // The coins to bump are mandatory candidates. Each parent transaction's ancestor info is
+        // only attached to one of its coins, so that we don't pay for its ancestors twice.
+        let coins = db_conn.coins(&[], outpoints);
+        let mut parents = HashSet::with_capacity(outpoints.len());
+        let mut candidate_coins = Vec::with_capacity(outpoints.len());
+        for op in outpoints {
+            let coin = coins.get(op).ok_or(CommandError::UnknownOutpoint(*op))?;
+            if coin.is_spent() {
+                return Err(CommandError::AlreadySpent(*op));
+            }
+            if coin.is_immature {
+                return Err(CommandError::ImmatureCoinbase(*op));
+            }
+            if coin.block_info.is_some() {
+                return Err(CommandError::CpfpError(CpfpErrorInfo::AlreadyConfirmed(
+                    *op,
+                )));
+            }
+            let ancestor_info = if parents.insert(op.txid) {
+                let entry = self
+                    .bitcoin
+                    .mempool_entry(&op.txid)
+                    .ok_or(CommandError::CpfpError(CpfpErrorInfo::NotInMempool(
+                        op.txid,
+                    )))?;
+                let ancestor_fee = entry.fees.ancestor.to_sat();
+                if ancestor_fee >= feerate_vb.saturating_mul(entry.ancestor_vsize) {
+                    return Err(CommandError::CpfpError(
+                        CpfpErrorInfo::FeerateAlreadyReached(
+                            op.txid,
+                            ancestor_fee / entry.ancestor_vsize,
+                        ),
+                    ));
+                }
+                Some(AncestorInfo {
+                    vsize: entry.ancestor_vsize,
+                    fee: ancestor_fee
+                        .try_into()
+                        .expect("fee in sat should fit in u32"),
+                })
+            } else {
+                None
+            };
+            candidate_coins.push(coin_to_candidate(
+                coin,
+                /*must_select=*/ true,
+                /*sequence=*/ None,
+                ancestor_info,
+            ));
+        }
+        // Confirmed coins may be used to pay for the fee if necessary, unless frozen.
+        candidate_coins.extend(
+            db_conn
+                .coins(&[CoinStatus::Confirmed], &[])
+                .into_values()
+                .filter(|c| !c.is_frozen)
+                .map(|c| {
+                    coin_to_candidate(
+                        &c, /*must_select=*/ false, /*sequence=*/ None,
+                        /*ancestor_info=*/ None,
+                    )
+                }),
+        );
+
+        let change_address = self.next_change_addr(&mut db_conn);
+        let change_info = change_address.info;
+        let locktime = self.anti_fee_sniping_locktime();
+        let CreateSpendRes {
+            psbt,
+            has_change,
+            warnings,
+        } = match create_spend(
+            &self.config.main_descriptor,
+            &self.secp,
+            &mut tx_getter,
+            &[],
+            &candidate_coins,
+            SpendTxFees::Regular(feerate_vb),
+            change_address,
+            locktime,
+        ) {
+            Ok(res) => res,
+            Err(SpendCreationError::CoinSelection(e)) => {
+                return Ok(CreateSpendResult::InsufficientFunds { missing: e.missing });
+            }
+            Err(e) => {
+                return Err(e.into());
+            }
+        };
+        if has_change {
+            self.maybe_increase_last_deriv_index(&mut db_conn, &change_info);
+        }
+
+        Ok(CreateSpendResult::Success {
+            psbt,
+            warnings: warnings.iter().map(|w| w.to_string()).collect(),
+        })
+    }
+
+    /// Make a payjoin (BIP78) with the receiver of the payment requested by the `bip21` URI, which
+    /// must have a payjoin endpoint. The stored Spend transaction `txid` must contain this payment
+    /// and be signed already: it is sent to the receiver as the Original PSBT, which it may
+    /// broadcast if the payjoin doesn't go through.
+    ///
+    /// The payjoin proposal of the receiver is checked and stored as a new Spend transaction,
+    /// which must be signed again before being broadcast.
+    pub fn send_payjoin(
+        &self,
+        txid: &bitcoin::Txid,
+        bip21: &str,
+    ) -> Result<SendPayjoinResult, CommandError> {
+        let (destination, endpoint) = payments::parse_bip21_payjoin(bip21)
+            .map_err(|e| payjoin::SenderError::PaymentRequest(e.to_string()))?;
+        let endpoint = endpoint.ok_or(payjoin::SenderError::NoEndpoint)?;
+        let address = self.validate_address(destination.address.clone())?;
+        let mut db_conn = self.db.connection();
+        let psbt = db_conn
+            .spend_tx(txid)
+            .ok_or(CommandError::UnknownSpend(*txid))?;
+        let payment_index = psbt
+            .unsigned_tx
+            .output
+            .iter()
+            .position(|txo| {
+                txo.script_pubkey == address.script_pubkey()
+                    && txo.value.to_sat() >= destination.amount
+            })
+            .ok_or(payjoin::SenderError::NoPaymentOutput)?;
+
+        let mut finalized = psbt.clone();
+        self.finalize_spend(&mut finalized)?;
+        let fee = finalized
+            .fee()
+            .map_err(|e| CommandError::SpendFinalization(e.to_string()))?;
+        let vsize: u64 = finalized
+            .clone()
+            .extract_tx_unchecked_fee_rate()
+            .vsize()
+            .try_into()
+            .expect("vsize must fit in u64");
+        let feerate_vb = fee.to_sat() / vsize;
+
+        // The receiver may take the fee for its inputs from our change, as long as it doesn't
+        // make us pay a higher feerate than for our own inputs.
+        let input_vbytes: u64 = self
+            .config
+            .main_descriptor
+            .spender_input_size(false)
+            .try_into()
+            .expect("input size must fit in u64");
+        let additional_fee_output_index = self
+            .config
+            .main_descriptor
+            .change_indexes(&psbt, &self.secp)
+            .into_iter()
+            .map(|change| change.index())
+            .find(|index| *index != payment_index);
+        let original = payjoin::Original {
+            psbt,
+            payment_index,
+            feerate_vb,
+            input_vbytes,
+            params: payjoin::PayjoinParams {
+                additional_fee_output_index,
+                max_additional_fee_contribution: bitcoin::Amount::from_sat(
+                    feerate_vb.saturating_mul(input_vbytes),
+                ),
+                // We don't support the receiver replacing the output paying it.
+                disable_output_substitution: true,
+                min_feerate_vb: Some(feerate_vb),
+            },
+        };
+
+        let proposal = payjoin::send_original(&endpoint, &finalized, &original.params)?;
+        let proposal = original.check_proposal(proposal, |op| {
+            db_conn.coins_by_outpoints(&[*op]).contains_key(op)
+        })?;
+        db_conn.store_spend(&proposal);
+
+        Ok(SendPayjoinResult { psbt: proposal })
+    }
+
+    /// Create a payjoin (BIP78) proposal for the base64-encoded Original PSBT sent by a payjoin
+    /// sender along with the parameters in the `query` string, by contributing one of our
+    /// confirmed coins. The Original must be accepted by the mempool of our Bitcoin backend. The
+    /// proposal is stored as a Spend transaction for our input to be signed, and is pending until
+    /// finalized or abandoned. Returns its txid.
+    pub fn create_payjoin_proposal(
+        &self,
+        original: &str,
+        query: &str,
+    ) -> Result<bitcoin::Txid, payjoin::ReceiverError> {
+        let params = payjoin::PayjoinParams::from_query(query)?;
+        let original = Psbt::from_str(original.trim())
+            .map_err(|e| payjoin::ReceiverError::OriginalRejected(e.to_string()))?;
+        payjoin::check_original(&original, &params)?;
+
+        // Don't create another proposal for an Original we are already waiting on. Hold the lock
+        // until we are done so the same Original sent concurrently can't get another coin.
+        let original_txid = original.unsigned_tx.compute_txid();
+        let mut contributions = self
+            .payjoin_contributions
+            .lock()
+            .expect("Must not be poisoned");
+        let contribution = contributions.get(&original_txid).copied();
+        if let Some(PayjoinContribution {
+            pending_proposal: Some(txid),
+            ..
+        }) = contribution
+        {
+            log::info!(
+                "Payjoin proposal '{}' for this Original is already pending.",
+                txid
+            );
+            return Err(payjoin::ReceiverError::Unavailable);
+        }
+
+        // The Original PSBT must pay to one of our addresses and must not spend our own coins.
+        let mut db_conn = self.db.connection();
+        let outpoints: Vec<_> = original
+            .unsigned_tx
+            .input
+            .iter()
+            .map(|txin| txin.previous_output)
+            .collect();
+        if !db_conn.coins_by_outpoints(&outpoints).is_empty() {
+            return Err(payjoin::ReceiverError::OriginalRejected(
+                "the transaction spends coins of the receiver.".to_string(),
+            ));
+        }
+        let network = self.config.bitcoin_config.network;
+        let (output_index, (index, is_change)) = original
+            .unsigned_tx
+            .output
+            .iter()
+            .enumerate()
+            .find_map(|(i, txo)| {
+                let address = bitcoin::Address::from_script(&txo.script_pubkey, network).ok()?;
+                db_conn
+                    .derivation_index_by_address(&address)
+                    .map(|info| (i, info))
+            })
+            .ok_or_else(|| {
+                payjoin::ReceiverError::OriginalRejected(
+                    "the transaction doesn't pay to the receiver.".to_string(),
+                )
+            })?;
+
+        // The sender must be able to broadcast the Original if the payjoin doesn't go through.
+        // Only check it now, as it's more expensive than the checks above.
+        let original_tx = original.clone().extract_tx_unchecked_fee_rate();
+        match self.bitcoin.test_mempool_accept(&original_tx) {
+            Ok(None) => {}
+            Ok(Some(reason)) => {
+                return Err(payjoin::ReceiverError::OriginalRejected(format!(
+                    "the transaction is not accepted in the mempool: {}.",
+                    reason
+                )));
+            }
+            Err(e) => {
+                log::error!(
+                    "Error testing mempool acceptance of a payjoin Original: '{}'.",
+                    e
+                );
+                return Err(payjoin::ReceiverError::Unavailable);
+            }
+        }
+
+        // Contribute the coin we already offered for this Original, if any. Otherwise pick one of
+        // our available coins at random.
+        let mut coins: Vec<_> = db_conn
+            .coins(&[CoinStatus::Confirmed], &[])
+            .into_values()
+            .filter(|c| !c.is_frozen && !c.is_immature)
+            .collect();
+        let coin = if let Some(contribution) = contribution {
+            coins.into_iter().find(|c| c.outpoint == contribution.coin)
+        } else if coins.is_empty() {
+            None
+        } else {
+            let random = liana::random::random_bytes().map_err(|e| {
+                log::error!("Error getting randomness for payjoin: '{}'.", e);
+                payjoin::ReceiverError::Unavailable
+            })?;
+            let random_index = u64::from_le_bytes(random[..8].try_into().expect("8 bytes"));
+            Some(coins.swap_remove((random_index % coins.len() as u64) as usize))
+        }
+        .ok_or_else(|| {
+            log::info!("No coin available to contribute to a payjoin.");
+            payjoin::ReceiverError::Unavailable
+        })?;
+        let coin_desc = self.derived_desc(&coin);
+        let mut psbt_in = PsbtIn::default();
+        coin_desc.update_psbt_in(&mut psbt_in);
+        self.config
+            .main_descriptor
+            .update_musig_psbt_in(&mut psbt_in);
+        psbt_in.witness_utxo = Some(bitcoin::TxOut {
+            value: coin.amount,
+            script_pubkey: coin_desc.script_pubkey(),
+        });
+        if !self.config.main_descriptor.is_taproot() {
+            psbt_in.non_witness_utxo = DbTxGetter::new(&self.db).get_tx(&coin.outpoint.txid);
+        }
+
+        // Insert our input at a random position.
+        let position = liana::random::random_bytes()
+            .map(|bytes| bytes[0] as usize % (outpoints.len() + 1))
+            .map_err(|e| {
+                log::error!("Error getting randomness for payjoin: '{}'.", e);
+                payjoin::ReceiverError::Unavailable
+            })?;
+        let input_vbytes = self.config.main_descriptor.spender_input_size(true) as u64;
+        let mut proposal = payjoin::contribute(
+            &original,
+            &params,
+            output_index,
+            coin.outpoint,
+            psbt_in,
+            input_vbytes,
+            position,
+        )?;
+
+        // Have signing devices recognize the output paying us.
+        let desc = if is_change {
+            self.config.main_descriptor.change_descriptor()
+        } else {
+            self.config.main_descriptor.receive_descriptor()
+        };
+        desc.derive(index, &self.secp)
+            .update_change_psbt_out(&mut proposal.outputs[output_index]);
+        db_conn.store_spend(&proposal);
+
+        let txid = proposal.unsigned_tx.compute_txid();
+        contributions.insert(
+            original_txid,
+            PayjoinContribution {
+                coin: coin.outpoint,
+                pending_proposal: Some(txid),
+            },
+        );
+        Ok(txid)
+    }
+
+    // Mark the payjoin proposal `txid` as no longer pending.
+    fn end_payjoin_proposal(&self, txid: &bitcoin::Txid) {
+        let mut contributions = self
+            .payjoin_contributions
+            .lock()
+            .expect("Must not be poisoned");
+        for contribution in contributions.values_mut() {
+            if contribution.pending_proposal == Some(*txid) {
+                contribution.pending_proposal = None;
+            }
+        }
+    }
+
+    /// Delete the pending payjoin proposal `txid`, for instance if our input wasn't signed in
+    /// time. The same coin will be contributed if the sender sends the same Original again.
+    pub fn abandon_payjoin_proposal(&self, txid: &bitcoin::Txid) {
+        self.delete_spend(txid);
+        self.end_payjoin_proposal(txid);
+    }
+
+    /// Get the stored payjoin proposal `txid` ready to be sent back to the sender, which ends
+    /// it. Returns `None` if our input wasn't signed yet.
+    pub fn finalize_payjoin_proposal(&self, txid: &bitcoin::Txid) -> Option<Psbt> {
+        let mut db_conn = self.db.connection();
+        let mut psbt = db_conn.spend_tx(txid)?;
+        let outpoints: Vec<_> = psbt
+            .unsigned_tx
+            .input
+            .iter()
+            .map(|txin| txin.previous_output)
+            .collect();
+        let coins = db_conn.coins_by_outpoints(&outpoints);
+        let index = outpoints.iter().position(|op| coins.contains_key(op))?;
+        if let Err(e) = psbt.finalize_inp_mut(&self.secp, index) {
+            log::trace!("Payjoin proposal '{}' not signed yet: '{}'.", txid, e);
+            return None;
+        }
+
+        payjoin::strip_proposal(&mut psbt, index);
+        self.end_payjoin_proposal(txid);
+        Some(psbt)
+    }
+
     /// Trigger a rescan of the block chain for transactions involving our main descriptor between
     /// the given date and the current tip.
     /// The date must be after the genesis block time and before the current tip blocktime.
@@ -1217,6 +2548,183 @@ impl DaemonControl {
         Ok(())
     }
 
+    /// Start watching an additional descriptor, which need not be a Liana descriptor, looking for
+    /// its coins since the given date. The coins of this descriptor are listed separately and may
+    /// be swept into the main descriptor using [`DaemonControl::sweep_watchonly`].
+    /// The date must be after the genesis block time and before the current tip blocktime.
+    pub fn import_watchonly_descriptor(
+        &mut self,
+        descriptor: Descriptor<DescriptorPublicKey>,
+        timestamp: u32,
+    ) -> Result<(), CommandError> {
+        let mut db_conn = self.db.connection();
+        let genesis_timestamp = self.bitcoin.genesis_block_timestamp();
+
+        let future_timestamp = self
+            .bitcoin
+            .tip_time()
+            .map(|t| timestamp >= t)
+            .unwrap_or(false);
+        if timestamp < genesis_timestamp || future_timestamp {
+            return Err(CommandError::InsaneRescanTimestamp(timestamp));
+        }
+        if self.bitcoin.rescan_progress().is_some() {
+            return Err(CommandError::AlreadyRescanning);
+        }
+
+        descriptor
+            .sanity_check()
+            .map_err(|e| CommandError::InvalidWatchonlyDescriptor(e.to_string()))?;
+        let single_descs = descriptor
+            .clone()
+            .into_single_descriptors()
+            .map_err(|e| CommandError::InvalidWatchonlyDescriptor(e.to_string()))?;
+        let main_descs = [
+            self.config.main_descriptor.receive_descriptor(),
+            self.config.main_descriptor.change_descriptor(),
+        ];
+        for single_desc in &single_descs {
+            if main_descs.iter().any(|desc| *desc == single_desc) {
+                return Err(CommandError::InvalidWatchonlyDescriptor(
+                    "This is the main descriptor.".to_string(),
+                ));
+            }
+            // We must be able to derive addresses from this descriptor.
+            single_desc
+                .at_derivation_index(0)
+                .and_then(|desc| desc.derived_descriptor(&self.secp))
+                .map_err(|e| CommandError::InvalidWatchonlyDescriptor(e.to_string()))?
+                .address(self.config.bitcoin_config.network)
+                .map_err(|e| CommandError::InvalidWatchonlyDescriptor(e.to_string()))?;
+        }
+        if db_conn
+            .watchonly_descriptors()
+            .iter()
+            .any(|(desc, _)| desc.to_string() == descriptor.to_string())
+        {
+            return Err(CommandError::InvalidWatchonlyDescriptor(
+                "This descriptor is already imported.".to_string(),
+            ));
+        }
+
+        self.bitcoin
+            .import_watchonly_descriptor(&descriptor, timestamp)
+            .map_err(CommandError::RescanTrigger)?;
+        db_conn.new_watchonly_descriptor(&descriptor, timestamp);
+
+        Ok(())
+    }
+
+    /// Create a transaction sweeping coins of the additional watch-only descriptors to a new
+    /// receive address of the main descriptor, at the given feerate. The transaction is returned
+    /// as a PSBT to be signed by the signers of the watch-only descriptors.
+    ///
+    /// If `coins_outpoints` is empty, all the confirmed coins of the watch-only descriptors will
+    /// be swept. Otherwise only those specified, which may be unconfirmed.
+    pub fn sweep_watchonly(
+        &self,
+        coins_outpoints: &[bitcoin::OutPoint],
+        feerate_vb: u64,
+    ) -> Result<SweepWatchonlyResult, CommandError> {
+        if !(1..=spend::MAX_FEERATE).contains(&feerate_vb) {
+            return Err(CommandError::InvalidFeerate(feerate_vb));
+        }
+        let mut db_conn = self.db.connection();
+        let network = self.config.bitcoin_config.network;
+
+        let watchonly_coins = db_conn.watchonly_coins();
+        let coins: Vec<WatchonlyCoin> = if coins_outpoints.is_empty() {
+            watchonly_coins
+                .into_iter()
+                .filter(|coin| coin.is_confirmed())
+                .collect()
+        } else {
+            coins_outpoints
+                .iter()
+                .map(|op| {
+                    watchonly_coins
+                        .iter()
+                        .find(|coin| coin.outpoint == *op)
+                        .cloned()
+                        .ok_or(CommandError::UnknownOutpoint(*op))
+                })
+                .collect::<Result<_, _>>()?
+        };
+        if coins.is_empty() {
+            return Err(CommandError::NoWatchonlyCoins);
+        }
+
+        // Sweep to the next receive address. We only update the derivation index in DB once the
+        // transaction was successfully created.
+        let receive_index = db_conn
+            .receive_index()
+            .increment()
+            .expect("Can't get into hardened territory");
+        let receive_desc = self
+            .config
+            .main_descriptor
+            .receive_descriptor()
+            .derive(receive_index, &self.secp);
+        let in_value: bitcoin::Amount = coins.iter().map(|coin| coin.amount).sum();
+        let tx = bitcoin::Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: self.anti_fee_sniping_locktime(),
+            input: coins
+                .iter()
+                .map(|coin| bitcoin::TxIn {
+                    previous_output: coin.outpoint,
+                    sequence: bitcoin::Sequence::ENABLE_RBF_NO_LOCKTIME,
+                    ..bitcoin::TxIn::default()
+                })
+                .collect(),
+            output: vec![bitcoin::TxOut {
+                value: in_value,
+                script_pubkey: receive_desc.script_pubkey(),
+            }],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(tx).expect("No scriptSig nor witness.");
+
+        // Populate the PSBT inputs with the information needed by the signers, and compute the
+        // worst case weight of their satisfaction.
+        let mut sat_weight = 0;
+        for (i, coin) in coins.iter().enumerate() {
+            let coin_desc = watchonly_coin_desc(coin, network, &self.secp);
+            let psbt_in = &mut psbt.inputs[i];
+            psbt_in.witness_utxo = Some(bitcoin::TxOut {
+                value: coin.amount,
+                script_pubkey: coin.address.script_pubkey(),
+            });
+            if !matches!(coin_desc, Descriptor::Tr(..)) {
+                psbt_in.non_witness_utxo = self
+                    .bitcoin
+                    .wallet_transaction(&coin.outpoint.txid)
+                    .map(|(tx, _)| tx);
+            }
+            psbt.update_input_with_descriptor(i, &coin_desc)
+                .map_err(|e| CommandError::InvalidWatchonlyDescriptor(e.to_string()))?;
+            // Account for the witness stack length varint, as `max_weight_to_satisfy` gives the
+            // difference with an input which already has an empty witness.
+            sat_weight += coin_desc
+                .max_weight_to_satisfy()
+                .map_err(|e| CommandError::InvalidWatchonlyDescriptor(e.to_string()))?
+                .to_wu()
+                + 1;
+        }
+
+        // Deduce the fee from the swept value. The Segwit marker and flag account for 2 WU.
+        let weight = psbt.unsigned_tx.weight().to_wu() + 2 + sat_weight;
+        let fee = bitcoin::Amount::from_sat(weight.div_ceil(4) * feerate_vb);
+        let out_value = in_value
+            .checked_sub(fee)
+            .filter(|v| v.to_sat() >= spend::DUST_OUTPUT_SATS)
+            .ok_or(CommandError::InsufficientFunds(in_value, None, feerate_vb))?;
+        psbt.unsigned_tx.output[0].value = out_value;
+        receive_desc.update_change_psbt_out(&mut psbt.outputs[0]);
+        db_conn.set_receive_index(receive_index, &self.secp);
+
+        Ok(SweepWatchonlyResult { psbt })
+    }
+
     /// list_confirmed_transactions retrieves a limited list of transactions which occurred between two given dates.
     pub fn list_confirmed_transactions(
         &self,
@@ -1242,13 +2750,193 @@ impl DaemonControl {
         ListTransactionsResult { transactions }
     }
 
-    /// Create a transaction that sweeps coins using a timelocked recovery path to a
-    /// provided address with the provided feerate.
-    ///
-    /// The `timelock` parameter can be used to specify which recovery path to use. By default,
-    /// we'll use the first recovery path available.
-    ///
-    /// If `coins_outpoints` is empty, all coins for which the given recovery path is currently
+    /// Match the coins disposed of by each of our confirmed transactions with those we acquired
+    /// before, following the given cost basis method. The daemon doesn't know about fiat prices,
+    /// so the fiat valuation of the entries is left empty.
+    pub fn accounting_report(&self, method: CostBasisMethod) -> AccountingReportResult {
+        let mut db_conn = self.db.connection();
+        let txids = db_conn.list_txids(0, u32::MAX, u32::MAX as u64);
+        let coins = db_conn.coins(&[], &[]);
+
+        let mut received = HashMap::<bitcoin::Txid, bitcoin::Amount>::new();
+        let mut spent = HashMap::<bitcoin::Txid, bitcoin::Amount>::new();
+        for coin in coins.values() {
+            *received.entry(coin.outpoint.txid).or_default() += coin.amount;
+            if let Some(spend_txid) = coin.spend_txid {
+                *spent.entry(spend_txid).or_default() += coin.amount;
+            }
+        }
+
+        let txs: Vec<_> = db_conn
+            .list_wallet_transactions(&txids)
+            .into_iter()
+            .map(|(tx, block_height, time)| {
+                let txid = tx.compute_txid();
+                let spent = spent.get(&txid).copied().unwrap_or(bitcoin::Amount::ZERO);
+                // We can only tell the fee if we know the value of all the inputs.
+                let fee = if tx
+                    .input
+                    .iter()
+                    .all(|txin| coins.contains_key(&txin.previous_output))
+                {
+                    let outputs_value = tx.output.iter().map(|txo| txo.value).sum();
+                    spent.checked_sub(outputs_value)
+                } else {
+                    None
+                };
+                AccountingTx {
+                    txid,
+                    time,
+                    block_height,
+                    received: received
+                        .get(&txid)
+                        .copied()
+                        .unwrap_or(bitcoin::Amount::ZERO),
+                    spent,
+                    fee,
+                    price: None,
+                }
+            })
+            .collect();
+
+        AccountingReportResult {
+            method,
+            entries: compute_cost_basis(&txs, method),
+        }
+    }
+
+    /// Create a proof of reserves (BIP127) for the given coins, committing to the given message.
+    /// If `coins_outpoints` is empty, all the confirmed coins are used. The returned PSBT must be
+    /// signed for each of the coins, as a spend would. It is not stored, as it can never be
+    /// broadcast.
+    pub fn create_proof_of_reserves(
+        &self,
+        message: &str,
+        coins_outpoints: &[bitcoin::OutPoint],
+    ) -> Result<CreateProofOfReservesResult, CommandError> {
+        let mut db_conn = self.db.connection();
+        let coins = if coins_outpoints.is_empty() {
+            db_conn.coins(&[CoinStatus::Confirmed], &[])
+        } else {
+            let coins = db_conn.coins(&[], coins_outpoints);
+            for op in coins_outpoints {
+                let coin = coins.get(op).ok_or(CommandError::UnknownOutpoint(*op))?;
+                if coin.is_spent() {
+                    return Err(CommandError::AlreadySpent(*op));
+                }
+            }
+            coins
+        };
+        if coins.is_empty() {
+            return Err(CommandError::NoReserves);
+        }
+        let mut coins: Vec<_> = coins.into_values().collect();
+        coins.sort_by_key(|coin| coin.outpoint);
+
+        let mut tx_getter = DbTxGetter::new(&self.db);
+        let psbt = proof_of_reserves::create_proof(
+            &self.secp,
+            &self.config.main_descriptor,
+            &mut tx_getter,
+            message,
+            &coins,
+        );
+        Ok(CreateProofOfReservesResult { psbt })
+    }
+
+    /// Verify a signed proof of reserves (BIP127) committing to the given message. The coins need
+    /// not be ours, but they must all be unspent according to our Bitcoin backend.
+    pub fn verify_proof_of_reserves(
+        &self,
+        psbt: &Psbt,
+        message: &str,
+    ) -> Result<VerifyProofOfReservesResult, CommandError> {
+        let coins = proof_of_reserves::proof_coins(psbt, message)
+            .map_err(CommandError::InvalidProofOfReserves)?;
+        let utxos = self
+            .bitcoin
+            .unspent_outputs(&coins)
+            .map_err(CommandError::UnspentOutputs)?;
+        let coins: Vec<_> = proof_of_reserves::verify_proof(&self.secp, psbt, message, &utxos)
+            .map_err(CommandError::InvalidProofOfReserves)?
+            .into_iter()
+            .map(|(outpoint, amount)| ProvenCoin { outpoint, amount })
+            .collect();
+        Ok(VerifyProofOfReservesResult {
+            amount: coins.iter().map(|coin| coin.amount).sum(),
+            coins,
+        })
+    }
+
+    // Get the coins to consider for a recovery transaction. If `coins_outpoints` is empty, all
+    // the confirmed coins. Otherwise the specified coins, after checking they are known and
+    // unspent.
+    fn recovery_coins(
+        &self,
+        db_conn: &mut Box<dyn DatabaseConnection>,
+        coins_outpoints: &[bitcoin::OutPoint],
+    ) -> Result<HashMap<bitcoin::OutPoint, Coin>, CommandError> {
+        if coins_outpoints.is_empty() {
+            return Ok(db_conn.coins(&[CoinStatus::Confirmed], &[]));
+        }
+        // We could have used the same DB call for both cases by specifying the status and outpoints,
+        // but in order to give more helpful errors, we filter the DB call here only for outpoints
+        // and then check for coin status separately.
+        let coins_by_op = db_conn.coins(&[], coins_outpoints);
+        for op in coins_outpoints {
+            let coin = coins_by_op
+                .get(op)
+                .ok_or(CommandError::UnknownOutpoint(*op))?;
+            // We only check for spent coins here. Unconfirmed coins (including immature)
+            // will fail the check for recoverability further below.
+            if coin.is_spent() {
+                return Err(CommandError::AlreadySpent(*op));
+            }
+        }
+        Ok(coins_by_op)
+    }
+
+    // Create a transaction sweeping all these recovery candidates to the given address.
+    fn sweep_recovery_coins(
+        &self,
+        db_conn: &mut Box<dyn DatabaseConnection>,
+        sweep_addr: SpendOutputAddress,
+        sweepable_coins: &[CandidateCoin],
+        feerate_vb: u64,
+        locktime: LockTime,
+    ) -> Result<CreateRecoveryResult, CommandError> {
+        if sweepable_coins.is_empty() {
+            return Err(CommandError::RecoveryNotAvailable);
+        }
+
+        let mut tx_getter = DbTxGetter::new(&self.db);
+        let sweep_addr_info = sweep_addr.info;
+        let CreateSpendRes {
+            psbt, has_change, ..
+        } = create_spend(
+            &self.config.main_descriptor,
+            &self.secp,
+            &mut tx_getter,
+            &[], // No destination, only the change address.
+            sweepable_coins,
+            SpendTxFees::Regular(feerate_vb),
+            sweep_addr,
+            locktime,
+        )?;
+        if has_change {
+            self.maybe_increase_last_deriv_index(db_conn, &sweep_addr_info);
+        }
+
+        Ok(CreateRecoveryResult { psbt })
+    }
+
+    /// Create a transaction that sweeps coins using a timelocked recovery path to a
+    /// provided address with the provided feerate.
+    ///
+    /// The `timelock` parameter can be used to specify which recovery path to use. By default,
+    /// we'll use the first recovery path available.
+    ///
+    /// If `coins_outpoints` is empty, all coins for which the given recovery path is currently
     /// available will be used. Otherwise, only those specified will be considered. An error will
     /// be returned if any coins specified by `coins_outpoints` are unknown, already spent or
     /// otherwise not currently recoverable using the given recovery path.
@@ -1264,7 +2952,6 @@ impl DaemonControl {
         if feerate_vb < 1 {
             return Err(CommandError::InvalidFeerate(feerate_vb));
         }
-        let mut tx_getter = DbTxGetter::new(&self.db);
         let mut db_conn = self.db.connection();
         let sweep_addr = self.spend_addr(&mut db_conn, self.validate_address(address)?);
 
@@ -1274,25 +2961,7 @@ impl DaemonControl {
         let timelock =
             timelock.unwrap_or_else(|| self.config.main_descriptor.first_timelock_value());
         let height_delta: i32 = timelock.into();
-        let coins = if coins_outpoints.is_empty() {
-            db_conn.coins(&[CoinStatus::Confirmed], &[])
-        } else {
-            // We could have used the same DB call for both cases by specifying the status and outpoints,
-            // but in order to give more helpful errors, we filter the DB call here only for outpoints
-            // and then check for coin status separately.
-            let coins_by_op = db_conn.coins(&[], coins_outpoints);
-            for op in coins_outpoints {
-                let coin = coins_by_op
-                    .get(op)
-                    .ok_or(CommandError::UnknownOutpoint(*op))?;
-                // We only check for spent coins here. Unconfirmed coins (including immature)
-                // will fail the check for recoverability further below.
-                if coin.is_spent() {
-                    return Err(CommandError::AlreadySpent(*op));
-                }
-            }
-            coins_by_op
-        };
+        let coins = self.recovery_coins(&mut db_conn, coins_outpoints)?;
         let mut sweepable_coins = Vec::with_capacity(coins.len());
         for (op, c) in coins {
             // We are interested in coins available at the *next* block
@@ -1307,38 +2976,148 @@ impl DaemonControl {
                     /*ancestor_info=*/ None,
                 ));
             } else if !coins_outpoints.is_empty() {
-                return Err(CommandError::OutpointNotRecoverable(op, timelock));
+                return Err(CommandError::OutpointNotRecoverable(op, timelock.into()));
             }
         }
-        if sweepable_coins.is_empty() {
-            return Err(CommandError::RecoveryNotAvailable);
-        }
 
-        let sweep_addr_info = sweep_addr.info;
         let locktime = self.anti_fee_sniping_locktime();
-        let CreateSpendRes {
-            psbt, has_change, ..
-        } = create_spend(
-            &self.config.main_descriptor,
-            &self.secp,
-            &mut tx_getter,
-            &[], // No destination, only the change address.
+        self.sweep_recovery_coins(
+            &mut db_conn,
+            sweep_addr,
             &sweepable_coins,
-            SpendTxFees::Regular(feerate_vb),
+            feerate_vb,
+            locktime,
+        )
+    }
+
+    /// Create a transaction that sweeps coins using a recovery path behind a time-based relative
+    /// timelock to a provided address with the provided feerate.
+    ///
+    /// The `timelock` is the number of 512-second intervals of the recovery path to use. As per
+    /// BIP68, it becomes available for a coin once the median time past of the current tip is
+    /// this many seconds after the median time past of the block preceding the coin's.
+    ///
+    /// If `coins_outpoints` is empty, all coins for which the given recovery path is currently
+    /// available will be used. Otherwise, only those specified will be considered. An error will
+    /// be returned if any coins specified by `coins_outpoints` are unknown, already spent or
+    /// otherwise not currently recoverable using the given recovery path.
+    pub fn create_time_recovery(
+        &self,
+        address: bitcoin::Address<address::NetworkUnchecked>,
+        coins_outpoints: &[bitcoin::OutPoint],
+        feerate_vb: u64,
+        timelock: u16,
+    ) -> Result<CreateRecoveryResult, CommandError> {
+        if feerate_vb < 1 {
+            return Err(CommandError::InvalidFeerate(feerate_vb));
+        }
+        let mut db_conn = self.db.connection();
+        let sweep_addr = self.spend_addr(&mut db_conn, self.validate_address(address)?);
+
+        // The coins must be spendable through this recovery path in the *next* block, whose
+        // relative timelocks are checked against the median time past of the current tip.
+        let tip_mtp = self.bitcoin.tip_median_time_past();
+        let timelock_secs = u32::from(timelock) * 512;
+        let coins = self.recovery_coins(&mut db_conn, coins_outpoints)?;
+        let mut sweepable_coins = Vec::with_capacity(coins.len());
+        for (op, c) in coins {
+            let is_available = c
+                .block_info
+                .and_then(|b| self.coin_median_time_past(b.height))
+                .zip(tip_mtp)
+                .map(|(coin_mtp, tip_mtp)| tip_mtp >= coin_mtp + timelock_secs)
+                .unwrap_or(false);
+            if is_available {
+                sweepable_coins.push(coin_to_candidate(
+                    &c,
+                    /*must_select=*/ true,
+                    /*sequence=*/
+                    Some(bitcoin::Sequence::from_512_second_intervals(timelock)),
+                    /*ancestor_info=*/ None,
+                ));
+            } else if !coins_outpoints.is_empty() {
+                return Err(CommandError::OutpointNotRecoverable(op, timelock.into()));
+            }
+        }
+
+        let locktime = self.anti_fee_sniping_locktime();
+        self.sweep_recovery_coins(
+            &mut db_conn,
             sweep_addr,
+            &sweepable_coins,
+            feerate_vb,
             locktime,
-        )?;
-        if has_change {
-            self.maybe_increase_last_deriv_index(&mut db_conn, &sweep_addr_info);
+        )
+    }
+
+    /// Create a transaction that sweeps coins using a recovery path behind an absolute timelock
+    /// to a provided address with the provided feerate.
+    ///
+    /// The `timelock` is the consensus-encoded absolute timelock (a block height or a timestamp)
+    /// of the recovery path to use. It is set as the transaction's nLockTime. Contrary to relative
+    /// timelocks, it becomes available for all coins at the same time.
+    ///
+    /// If `coins_outpoints` is empty, all confirmed coins will be used. Otherwise, only those
+    /// specified will be considered. An error will be returned if any coins specified by
+    /// `coins_outpoints` are unknown, already spent or unconfirmed.
+    pub fn create_absolute_recovery(
+        &self,
+        address: bitcoin::Address<address::NetworkUnchecked>,
+        coins_outpoints: &[bitcoin::OutPoint],
+        feerate_vb: u64,
+        timelock: u32,
+    ) -> Result<CreateRecoveryResult, CommandError> {
+        if feerate_vb < 1 {
+            return Err(CommandError::InvalidFeerate(feerate_vb));
         }
+        let mut db_conn = self.db.connection();
+        let sweep_addr = self.spend_addr(&mut db_conn, self.validate_address(address)?);
 
-        Ok(CreateRecoveryResult { psbt })
+        // A transaction may only be included in the next block if its nLockTime is lower than
+        // the next block's height, or than the median time past of the current tip.
+        let locktime = LockTime::from_consensus(timelock);
+        let is_available = if locktime.is_block_height() {
+            let current_height = self.bitcoin.chain_tip().height;
+            i64::from(timelock) <= i64::from(current_height)
+        } else {
+            self.bitcoin
+                .tip_median_time_past()
+                .map(|mtp| timelock < mtp)
+                .unwrap_or(false)
+        };
+
+        let coins = self.recovery_coins(&mut db_conn, coins_outpoints)?;
+        let mut sweepable_coins = Vec::with_capacity(coins.len());
+        for (op, c) in coins {
+            // The nSequence must be set to enable the transaction's locktime.
+            if is_available && c.block_info.is_some() {
+                sweepable_coins.push(coin_to_candidate(
+                    &c,
+                    /*must_select=*/ true,
+                    /*sequence=*/ Some(bitcoin::Sequence::ENABLE_RBF_NO_LOCKTIME),
+                    /*ancestor_info=*/ None,
+                ));
+            } else if !coins_outpoints.is_empty() {
+                return Err(CommandError::OutpointNotRecoverable(op, timelock));
+            }
+        }
+
+        self.sweep_recovery_coins(
+            &mut db_conn,
+            sweep_addr,
+            &sweepable_coins,
+            feerate_vb,
+            locktime,
+        )
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetInfoDescriptors {
     pub main: descriptors::LianaDescriptor,
+    /// The additional descriptors we watch the coins of.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub watchonly: Vec<Descriptor<DescriptorPublicKey>>,
 }
 
 /// Information about the daemon
@@ -1351,6 +3130,9 @@ pub struct GetInfoResult {
     pub descriptors: GetInfoDescriptors,
     /// The progress as a percentage (between 0 and 1) of an ongoing rescan if there is any
     pub rescan_progress: Option<f64>,
+    /// The progress of an ongoing address discovery if there is any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address_discovery: Option<AddressDiscoveryProgress>,
     /// Timestamp at wallet creation date
     pub timestamp: u32,
     /// Timestamp of last poll, if any.
@@ -1361,12 +3143,47 @@ pub struct GetInfoResult {
     pub change_index: u32,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EstimateFeeResult {
+    /// The estimated feerate in sats/vb
+    pub feerate: u64,
+    /// The number of blocks within which a transaction at this feerate is expected to confirm
+    pub blocks: u16,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportDescriptorResult {
+    #[serde(serialize_with = "ser_to_string", deserialize_with = "deser_fromstr")]
+    pub format: descriptors::ExportFormat,
+    /// The exported descriptor, to be written to a file as-is.
+    pub content: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UpdateDerivIndexesResult {
     pub receive: u32,
     pub change: u32,
 }
 
+/// How far an ongoing address discovery went on each keychain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressDiscoveryProgress {
+    /// The derivation index up to which receive addresses were checked
+    pub receive_index: u32,
+    /// The derivation index up to which change addresses were checked
+    pub change_index: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiscoverAddressesResult {
+    /// Last index used to generate a receive address, after the discovery
+    pub receive: u32,
+    /// Last index used to generate a change address, after the discovery
+    pub change: u32,
+    /// Whether a rescan was started to look for the transactions of the discovered addresses
+    pub rescan: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetAddressResult {
     #[serde(deserialize_with = "deser_addr_assume_checked")]
@@ -1393,6 +3210,79 @@ pub struct GetLabelsBip329Result {
     pub labels: crate::bip329::Labels,
 }
 
+/// What to do when importing a label for an item which is already labelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelsConflictPolicy {
+    /// Keep the existing label.
+    Merge,
+    /// Replace the existing label with the imported one.
+    Overwrite,
+}
+
+impl LabelsConflictPolicy {
+    pub fn from_arg(s: &str) -> Option<LabelsConflictPolicy> {
+        match s {
+            "merge" => Some(LabelsConflictPolicy::Merge),
+            "overwrite" => Some(LabelsConflictPolicy::Overwrite),
+            _ => None,
+        }
+    }
+
+    /// Converts a `LabelsConflictPolicy` to its equivalent argument name
+    /// as used in the `importlabelsbip329` RPC command.
+    pub fn to_arg(&self) -> &'static str {
+        match self {
+            LabelsConflictPolicy::Merge => "merge",
+            LabelsConflictPolicy::Overwrite => "overwrite",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkippedBip329Reason {
+    /// The record could not be parsed.
+    Invalid,
+    /// Liana does not store labels for this type of record.
+    Unsupported,
+    /// The record has no label.
+    MissingLabel,
+    /// The address is not valid for the network of the wallet.
+    WrongNetwork,
+    /// The label is longer than the maximum length.
+    TooLong,
+    /// The item already has a label, which was kept.
+    Conflict,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkippedBip329Record {
+    /// The line of the record in the imported data, starting from 1.
+    pub line: usize,
+    #[serde(rename = "type")]
+    pub type_: Option<String>,
+    #[serde(rename = "ref")]
+    pub ref_: Option<String>,
+    pub reason: SkippedBip329Reason,
+}
+
+/// The coin we contribute to the payjoin proposals for an Original PSBT. The same coin is offered
+/// if the same Original is sent again, so a sender can't learn about our other coins by repeating
+/// it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PayjoinContribution {
+    pub coin: bitcoin::OutPoint,
+    /// The proposal waiting for our input to be signed, if any.
+    pub pending_proposal: Option<bitcoin::Txid>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportLabelsBip329Result {
+    /// The number of labels which were stored.
+    pub imported: usize,
+    pub skipped: Vec<SkippedBip329Record>,
+}
+
 #[derive(Debug, Clone, Serialize, PartialEq, Eq)]
 pub struct AddressInfo {
     index: u32,
@@ -1476,11 +3366,64 @@ pub struct ListCoinsEntry {
     /// this same wallet. If the coin is unconfirmed, it also means that all its
     /// unconfirmed ancestors, if any, are also from self.
     pub is_from_self: bool,
+    /// Whether the coin is frozen, in which case it is never selected automatically for spending.
+    #[serde(default)]
+    pub is_frozen: bool,
+    /// For a confirmed coin of a wallet with time-based relative timelocked recovery paths, the
+    /// number of seconds elapsed since its confirmation as measured by BIP68. That is, between
+    /// the median time past of the block preceding its confirmation block and the one of the
+    /// current tip. A time-based recovery path is available once this is at least its timelock.
+    #[serde(default)]
+    pub time_since_confirmation: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListWatchonlyCoinsEntry {
+    /// The watch-only descriptor this coin pays to.
+    pub descriptor: Descriptor<DescriptorPublicKey>,
+    #[serde(
+        serialize_with = "ser_amount",
+        deserialize_with = "deser_amount_from_sats"
+    )]
+    pub amount: bitcoin::Amount,
+    pub outpoint: bitcoin::OutPoint,
+    #[serde(
+        serialize_with = "ser_to_string",
+        deserialize_with = "deser_addr_assume_checked"
+    )]
+    pub address: bitcoin::Address,
+    /// Derivation index used to create the coin address.
+    pub derivation_index: bip32::ChildNumber,
+    pub block_height: Option<i32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ListCoinsResult {
     pub coins: Vec<ListCoinsEntry>,
+    /// The unspent coins of the additional watch-only descriptors.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub watchonly_coins: Vec<ListWatchonlyCoinsEntry>,
+}
+
+/// A payment to be made by a Spend transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Destination {
+    pub address: bitcoin::Address<address::NetworkUnchecked>,
+    /// The value of the payment, in satoshis.
+    pub amount: u64,
+    /// A label to set on the output of this payment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+impl Destination {
+    pub fn new(address: bitcoin::Address<address::NetworkUnchecked>, amount: u64) -> Self {
+        Destination {
+            address,
+            amount,
+            label: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -1496,6 +3439,23 @@ pub enum CreateSpendResult {
     },
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SendPayjoinResult {
+    #[serde(serialize_with = "ser_to_string", deserialize_with = "deser_fromstr")]
+    pub psbt: Psbt,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SignSpendResult {
+    #[serde(serialize_with = "ser_to_string", deserialize_with = "deser_fromstr")]
+    pub psbt: Psbt,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CheckSpendResult {
+    pub violations: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ListSpendEntry {
     #[serde(serialize_with = "ser_to_string", deserialize_with = "deser_fromstr")]
@@ -1513,6 +3473,12 @@ pub struct ListTransactionsResult {
     pub transactions: Vec<TransactionInfo>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountingReportResult {
+    pub method: CostBasisMethod,
+    pub entries: Vec<AccountingEntry>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionInfo {
     #[serde(serialize_with = "ser_hex", deserialize_with = "deser_hex")]
@@ -1527,10 +3493,47 @@ pub struct CreateRecoveryResult {
     pub psbt: Psbt,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SweepWatchonlyResult {
+    #[serde(serialize_with = "ser_to_string", deserialize_with = "deser_fromstr")]
+    pub psbt: Psbt,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CreateProofOfReservesResult {
+    #[serde(serialize_with = "ser_to_string", deserialize_with = "deser_fromstr")]
+    pub psbt: Psbt,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProvenCoin {
+    pub outpoint: bitcoin::OutPoint,
+    #[serde(
+        serialize_with = "ser_amount",
+        deserialize_with = "deser_amount_from_sats"
+    )]
+    pub amount: bitcoin::Amount,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct VerifyProofOfReservesResult {
+    /// The total value of the proven coins.
+    #[serde(
+        serialize_with = "ser_amount",
+        deserialize_with = "deser_amount_from_sats"
+    )]
+    pub amount: bitcoin::Amount,
+    pub coins: Vec<ProvenCoin>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{bitcoin::Block, database::BlockInfo, testutils::*};
+    use crate::{
+        bitcoin::{Block, BlockChainTip, MempoolEntry, MempoolEntryFees},
+        database::BlockInfo,
+        testutils::*,
+    };
     use liana::spend::InsaneFeeInfo;
 
     use bitcoin::{
@@ -1552,6 +3555,41 @@ mod tests {
         ms.shutdown();
     }
 
+    #[test]
+    fn estimatefee() {
+        let ms = DummyLiana::new(DummyBitcoind::new(), DummyDatabase::new());
+        let control = &ms.control();
+
+        // The confirmation target must be sane.
+        assert_eq!(
+            control.estimate_fee(0, FeeEstimateMode::Economical),
+            Err(CommandError::InvalidConfTarget(0))
+        );
+        assert_eq!(
+            control.estimate_fee(MAX_CONF_TARGET + 1, FeeEstimateMode::Economical),
+            Err(CommandError::InvalidConfTarget(MAX_CONF_TARGET + 1))
+        );
+
+        // The estimate is returned along with the target it is valid for, which may be higher
+        // than the requested one.
+        assert_eq!(
+            control.estimate_fee(1, FeeEstimateMode::Economical),
+            Ok(EstimateFeeResult {
+                feerate: 10,
+                blocks: 2
+            })
+        );
+        assert_eq!(
+            control.estimate_fee(6, FeeEstimateMode::Conservative),
+            Ok(EstimateFeeResult {
+                feerate: 20,
+                blocks: 6
+            })
+        );
+
+        ms.shutdown();
+    }
+
     #[test]
     fn getnewaddress() {
         let ms = DummyLiana::new(DummyBitcoind::new(), DummyDatabase::new());
@@ -1579,6 +3617,71 @@ mod tests {
         ms.shutdown();
     }
 
+    #[test]
+    fn discover_addresses() {
+        // Nothing was used, nothing is discovered.
+        let ms = DummyLiana::new(DummyBitcoind::new(), DummyDatabase::new());
+        let mut control = ms.control().clone();
+        assert_eq!(
+            control.discover_addresses(Some(0)),
+            Err(CommandError::InvalidGapLimit(0))
+        );
+        assert_eq!(
+            control.discover_addresses(Some(MAX_GAP_LIMIT + 1)),
+            Err(CommandError::InvalidGapLimit(MAX_GAP_LIMIT + 1))
+        );
+        let res = control.discover_addresses(None).unwrap();
+        assert_eq!((res.receive, res.change, res.rescan), (0, 0, false));
+        assert!(control.get_info().address_discovery.is_none());
+        let desc = control.config.main_descriptor.clone();
+        ms.shutdown();
+
+        // Mark a receive address far past our index and a change address as used.
+        let secp = secp256k1::Secp256k1::verification_only();
+        let mut bitcoind = DummyBitcoind::new();
+        for (desc, index) in [
+            (desc.receive_descriptor(), 150),
+            (desc.change_descriptor(), 20),
+        ]
+        .iter()
+        {
+            let index = ChildNumber::from_normal_idx(*index).unwrap();
+            bitcoind
+                .used_spks
+                .insert(desc.derive(index, &secp).script_pubkey());
+        }
+        let ms = DummyLiana::new(bitcoind, DummyDatabase::new());
+        let mut control = ms.control().clone();
+
+        // It's past a gap of 100 receive addresses, we won't find it with this gap limit.
+        let res = control.discover_addresses(Some(100)).unwrap();
+        assert_eq!((res.receive, res.change, res.rescan), (0, 20, true));
+
+        // But we will with the default one. The change index was already bumped.
+        let res = control.discover_addresses(None).unwrap();
+        assert_eq!((res.receive, res.change), (150, 20));
+        assert_eq!(
+            control.get_new_address().derivation_index,
+            ChildNumber::from_normal_idx(151).unwrap()
+        );
+        assert!(control.get_info().address_discovery.is_none());
+        ms.shutdown();
+
+        // The backend may only track the addresses up to some index, we can't look past it.
+        let mut bitcoind = DummyBitcoind::new();
+        bitcoind.used_spks_max_index = Some(999);
+        let ms = DummyLiana::new(bitcoind, DummyDatabase::new());
+        let mut control = ms.control().clone();
+        control.discover_addresses(Some(999)).unwrap();
+        assert!(matches!(
+            control.discover_addresses(Some(1_000)),
+            Err(CommandError::AddressDiscovery(..))
+        ));
+        assert!(control.get_info().address_discovery.is_none());
+
+        ms.shutdown();
+    }
+
     #[test]
     fn listaddresses() {
         let ms = DummyLiana::new(DummyBitcoind::new(), DummyDatabase::new());
@@ -1843,6 +3946,7 @@ mod tests {
             spend_txid: None,
             spend_block: None,
             is_from_self: true,
+            is_frozen: false,
         }]);
 
         // If we don't exclude used, results will be same as before, except index 5 is marked as used:
@@ -1940,6 +4044,7 @@ mod tests {
             spend_txid: None,
             spend_block: None,
             is_from_self: true,
+            is_frozen: false,
         }]);
 
         let list = control
@@ -2021,15 +4126,12 @@ mod tests {
         let dummy_addr =
             bitcoin::Address::from_str("bc1qnsexk3gnuyayu92fc3tczvc7k62u22a22ua2kv").unwrap();
         let dummy_value = 10_000;
-        let mut destinations = <HashMap<bitcoin::Address<address::NetworkUnchecked>, u64>>::new();
+        let mut destinations = Vec::<Destination>::new();
         assert_eq!(
             control.create_spend(&destinations, &[], 1, None),
             Err(CommandError::NoOutpointForSelfSend)
         );
-        destinations = [(dummy_addr.clone(), dummy_value)]
-            .iter()
-            .cloned()
-            .collect();
+        destinations = vec![Destination::new(dummy_addr.clone(), dummy_value)];
         // Insufficient funds for coin selection.
         assert!(matches!(
             control.create_spend(&destinations, &[], 1, None),
@@ -2056,6 +4158,7 @@ mod tests {
             spend_txid: None,
             spend_block: None,
             is_from_self: false,
+            is_frozen: false,
         }]);
         // If we try to use coin selection, the unconfirmed not-from-self coin will not be used
         // as a candidate and so we get a coin selection error due to insufficient funds.
@@ -2115,12 +4218,12 @@ mod tests {
             control.create_spend(&destinations, &[dummy_op], 10_000, None),
             Ok(CreateSpendResult::InsufficientFunds { .. }),
         ));
-        *destinations.get_mut(&dummy_addr).unwrap() = 100_001;
+        destinations[0].amount = 100_001;
         assert!(matches!(
             control.create_spend(&destinations, &[dummy_op], 1, None),
             Ok(CreateSpendResult::InsufficientFunds { .. }),
         ));
-        *destinations.get_mut(&dummy_addr).unwrap() = DUST - 1;
+        destinations[0].amount = DUST - 1;
         assert_eq!(
             control.create_spend(&destinations, &[dummy_op], 1, None),
             Err(CommandError::SpendCreation(
@@ -2131,8 +4234,7 @@ mod tests {
         // If we ask to create an output for an address from another network, it will fail.
         let invalid_addr =
             bitcoin::Address::from_str("tb1qfufcrdyarcg5eph608c6l8vktrc9re6agu4se2").unwrap();
-        let invalid_destinations: HashMap<bitcoin::Address<address::NetworkUnchecked>, u64> =
-            [(invalid_addr, dummy_value)].iter().cloned().collect();
+        let invalid_destinations = vec![Destination::new(invalid_addr, dummy_value)];
         assert!(matches!(
             control.create_spend(&invalid_destinations, &[dummy_op], 1, None),
             Err(CommandError::Address(
@@ -2140,9 +4242,61 @@ mod tests {
             ))
         ));
 
+        // We can pay more than once to the same address. The outputs are in the order of the
+        // destinations and get their label once the spend is stored.
+        let batch_destinations = vec![
+            Destination {
+                label: Some("first payment".to_string()),
+                ..Destination::new(dummy_addr.clone(), 20_000)
+            },
+            Destination::new(dummy_addr.clone(), 10_000),
+            Destination {
+                label: Some("third payment".to_string()),
+                ..Destination::new(dummy_addr.clone(), 30_000)
+            },
+        ];
+        let psbt = if let CreateSpendResult::Success { psbt, .. } = control
+            .create_spend(
+                &batch_destinations,
+                &[dummy_op],
+                1,
+                Some(dummy_addr.clone()),
+            )
+            .unwrap()
+        {
+            psbt
+        } else {
+            panic!("expect successful spend creation")
+        };
+        let tx = &psbt.unsigned_tx;
+        assert_eq!(tx.output.len(), 4);
+        for (txo, value) in tx.output.iter().zip([20_000, 10_000, 30_000]) {
+            assert_eq!(txo.value.to_sat(), value);
+        }
+        let txid = tx.compute_txid();
+        let outpoints: Vec<_> = (0..3)
+            .map(|vout| bitcoin::OutPoint { txid, vout })
+            .collect();
+        let label_items: HashSet<_> = outpoints
+            .iter()
+            .map(|op| LabelItem::OutPoint(*op))
+            .collect();
+        assert!(control.get_labels(&label_items).labels.is_empty());
+        control.update_spend(psbt).unwrap();
+        let labels = control.get_labels(&label_items).labels;
+        assert_eq!(labels.len(), 2);
+        assert_eq!(
+            labels.get(&outpoints[0].to_string()).map(String::as_str),
+            Some("first payment")
+        );
+        assert_eq!(
+            labels.get(&outpoints[2].to_string()).map(String::as_str),
+            Some("third payment")
+        );
+
         // If we ask for a large, but valid, output we won't get a change output. 99_500 because we
         // won't create an output lower than 500 sats.
-        *destinations.get_mut(&dummy_addr).unwrap() = COIN_VALUE - DUST;
+        destinations[0].amount = COIN_VALUE - DUST;
         let (psbt, warnings) = if let CreateSpendResult::Success { psbt, warnings } = control
             .create_spend(&destinations, &[dummy_op], 1, None)
             .unwrap()
@@ -2171,7 +4325,7 @@ mod tests {
         );
 
         // Increase the target value by the change amount and the warning will disappear.
-        *destinations.get_mut(&dummy_addr).unwrap() = (COIN_VALUE - DUST) + 339;
+        destinations[0].amount = (COIN_VALUE - DUST) + 339;
         let (psbt, warnings) = if let CreateSpendResult::Success { psbt, warnings } = control
             .create_spend(&destinations, &[dummy_op], 1, None)
             .unwrap()
@@ -2185,8 +4339,7 @@ mod tests {
         assert!(warnings.is_empty());
 
         // Now increase target also by the extra fee that was paying for change and we can still create the spend.
-        *destinations.get_mut(&dummy_addr).unwrap() =
-            (COIN_VALUE - DUST) + 330 + /* fee for change output */ 43;
+        destinations[0].amount = (COIN_VALUE - DUST) + 330 + /* fee for change output */ 43;
         let (psbt, warnings) = if let CreateSpendResult::Success { psbt, warnings } = control
             .create_spend(&destinations, &[dummy_op], 1, None)
             .unwrap()
@@ -2200,15 +4353,14 @@ mod tests {
         assert!(warnings.is_empty());
 
         // Now increase the target by 1 more sat and we will have insufficient funds.
-        *destinations.get_mut(&dummy_addr).unwrap() =
-            (COIN_VALUE - DUST) + 339 + /* fee for change output */ 43 + 1;
+        destinations[0].amount = (COIN_VALUE - DUST) + 339 + /* fee for change output */ 43 + 1;
         assert_eq!(
             control.create_spend(&destinations, &[dummy_op], 1, None),
             Ok(CreateSpendResult::InsufficientFunds { missing: 1 }),
         );
 
         // Now decrease the target so that the lost change is just 1 sat.
-        *destinations.get_mut(&dummy_addr).unwrap() =
+        destinations[0].amount =
             COIN_VALUE - /* fee without change */ 118 - /* extra fee for change output */ 43 - 1;
         let warnings = if let CreateSpendResult::Success { warnings, .. } = control
             .create_spend(&destinations, &[dummy_op], 1, None)
@@ -2229,7 +4381,7 @@ mod tests {
         );
 
         // Now decrease the target value so that we have enough for a change output.
-        *destinations.get_mut(&dummy_addr).unwrap() = (COIN_VALUE - DUST) - /* fee without change */ 118 - /* extra fee for change output */ 43;
+        destinations[0].amount = (COIN_VALUE - DUST) - /* fee without change */ 118 - /* extra fee for change output */ 43;
 
         let (psbt, warnings) = if let CreateSpendResult::Success { psbt, warnings } = control
             .create_spend(&destinations, &[dummy_op], 1, None)
@@ -2245,7 +4397,7 @@ mod tests {
         assert!(warnings.is_empty());
 
         // Now increase the target by 1 and we'll get a warning again, this time for 1 less than the dust threshold.
-        *destinations.get_mut(&dummy_addr).unwrap() = (COIN_VALUE - DUST) - /* fee without change */ 118 - /* extra fee for change output */ 43
+        destinations[0].amount = (COIN_VALUE - DUST) - /* fee without change */ 118 - /* extra fee for change output */ 43
             + 1;
         let warnings = if let CreateSpendResult::Success { warnings, .. } = control
             .create_spend(&destinations, &[dummy_op], 1, None)
@@ -2299,6 +4451,7 @@ mod tests {
             spend_txid: None,
             spend_block: None,
             is_from_self: false,
+            is_frozen: false,
         }]);
         assert_eq!(
             control.create_spend(&destinations, &[dummy_op_dup], 1_001, None),
@@ -2322,6 +4475,7 @@ mod tests {
             spend_txid: None,
             spend_block: None,
             is_from_self: true,
+            is_frozen: false,
         };
         db_conn.new_unspent_coins(&[unconfirmed_coin]);
         // Coin selection error due to insufficient funds.
@@ -2330,7 +4484,7 @@ mod tests {
             Ok(CreateSpendResult::InsufficientFunds { .. }),
         ));
         // Set destination amount equal to value of confirmed coins.
-        *destinations.get_mut(&dummy_addr).unwrap() = 80_000;
+        destinations[0].amount = 80_000;
         // Coin selection error occurs due to insufficient funds to pay fee.
         assert!(matches!(
             control.create_spend(&destinations, &[], 1, None),
@@ -2354,6 +4508,7 @@ mod tests {
             spend_txid: None,
             spend_block: None,
             is_from_self: false,
+            is_frozen: false,
         }]);
         // First, create a transaction using auto coin selection.
         let psbt = if let CreateSpendResult::Success { psbt, .. } =
@@ -2481,8 +4636,9 @@ mod tests {
             spend_txid: None,
             spend_block: None,
             is_from_self: false,
+            is_frozen: false,
         }]);
-        let empty_dest = &HashMap::<bitcoin::Address<address::NetworkUnchecked>, u64>::new();
+        let empty_dest: &[Destination] = &[];
         assert_eq!(
             control.create_spend(empty_dest, &[confirmed_op_3], 5, None),
             Ok(CreateSpendResult::InsufficientFunds { missing: 150 },)
@@ -2492,41 +4648,542 @@ mod tests {
             .create_spend(empty_dest, &[confirmed_op_3], 1, None)
             .unwrap()
         {
-            psbt
+            psbt
+        } else {
+            panic!("expect successful spend creation")
+        };
+        let tx = psbt.unsigned_tx;
+        let tx_prev_outpoints = tx
+            .input
+            .iter()
+            .map(|txin| txin.previous_output)
+            .collect::<Vec<OutPoint>>();
+        assert_eq!(tx.input.len(), 1);
+        assert_eq!(tx_prev_outpoints, vec![confirmed_op_3]);
+        assert_eq!(tx.output.len(), 1);
+
+        // Can't create a transaction that spends an immature coinbase deposit.
+        let imma_op = bitcoin::OutPoint::from_str(
+            "4753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:0",
+        )
+        .unwrap();
+        db_conn.new_unspent_coins(&[Coin {
+            outpoint: imma_op,
+            is_immature: true,
+            block_info: None,
+            amount: bitcoin::Amount::from_sat(COIN_VALUE),
+            derivation_index: bip32::ChildNumber::from(13),
+            is_change: false,
+            spend_txid: None,
+            spend_block: None,
+            is_from_self: false,
+            is_frozen: false,
+        }]);
+        assert_eq!(
+            control.create_spend(&destinations, &[imma_op], 1_001, None),
+            Err(CommandError::ImmatureCoinbase(imma_op))
+        );
+
+        ms.shutdown();
+    }
+
+    #[test]
+    fn freeze_coins() {
+        let dummy_tx = bitcoin::Transaction {
+            version: TxVersion::TWO,
+            lock_time: absolute::LockTime::Blocks(absolute::Height::ZERO),
+            input: vec![],
+            output: vec![],
+        };
+        let dummy_op = bitcoin::OutPoint::new(dummy_tx.compute_txid(), 0);
+        let unknown_op = bitcoin::OutPoint::new(dummy_tx.compute_txid(), 1);
+        let ms = DummyLiana::new(DummyBitcoind::new(), DummyDatabase::new());
+        let control = &ms.control();
+        let mut db_conn = control.db().lock().unwrap().connection();
+        db_conn.new_txs(&[dummy_tx]);
+        db_conn.new_unspent_coins(&[Coin {
+            outpoint: dummy_op,
+            is_immature: false,
+            block_info: Some(BlockInfo {
+                height: 174500,
+                time: 174500,
+            }),
+            amount: bitcoin::Amount::from_sat(100_000),
+            derivation_index: bip32::ChildNumber::from(13),
+            is_change: false,
+            spend_txid: None,
+            spend_block: None,
+            is_from_self: false,
+            is_frozen: false,
+        }]);
+        let dummy_addr =
+            bitcoin::Address::from_str("bc1qnsexk3gnuyayu92fc3tczvc7k62u22a22ua2kv").unwrap();
+        let destinations = vec![Destination::new(dummy_addr, 10_000)];
+
+        // We can't freeze or unfreeze a coin we don't know about.
+        assert_eq!(
+            control.freeze_coins(&[dummy_op, unknown_op]),
+            Err(CommandError::UnknownOutpoint(unknown_op))
+        );
+        assert_eq!(
+            control.unfreeze_coins(&[unknown_op]),
+            Err(CommandError::UnknownOutpoint(unknown_op))
+        );
+        assert!(!control.list_coins(&[], &[]).coins[0].is_frozen);
+
+        // Once frozen, the coin isn't selected automatically anymore.
+        control.freeze_coins(&[dummy_op]).unwrap();
+        assert!(control.list_coins(&[], &[]).coins[0].is_frozen);
+        assert!(matches!(
+            control.create_spend(&destinations, &[], 1, None),
+            Ok(CreateSpendResult::InsufficientFunds { .. }),
+        ));
+
+        // It can still be spent explicitly, with a warning.
+        if let CreateSpendResult::Success { psbt, warnings } = control
+            .create_spend(&destinations, &[dummy_op], 1, None)
+            .unwrap()
+        {
+            assert_eq!(psbt.unsigned_tx.input[0].previous_output, dummy_op);
+            assert_eq!(
+                warnings,
+                vec![format!(
+                    "Coin {} is frozen. It was spent because it was explicitly selected.",
+                    dummy_op
+                )]
+            );
+        } else {
+            panic!("expect successful spend creation")
+        };
+
+        // Once unfrozen, it's available again for coin selection.
+        control.unfreeze_coins(&[dummy_op]).unwrap();
+        assert!(!control.list_coins(&[], &[]).coins[0].is_frozen);
+        assert!(matches!(
+            control.create_spend(&destinations, &[], 1, None),
+            Ok(CreateSpendResult::Success { .. }),
+        ));
+
+        // A spent coin can't be frozen.
+        let spend_txid =
+            Txid::from_str("617eab1fc0b03ee7f82ba70166725291783461f1a0e7975eaf8b5f8f674234f3")
+                .unwrap();
+        db_conn.spend_coins(&[(dummy_op, spend_txid)]);
+        assert_eq!(
+            control.freeze_coins(&[dummy_op]),
+            Err(CommandError::AlreadySpent(dummy_op))
+        );
+
+        ms.shutdown();
+    }
+
+    #[test]
+    fn proof_of_reserves() {
+        let dummy_tx = bitcoin::Transaction {
+            version: TxVersion::TWO,
+            lock_time: absolute::LockTime::Blocks(absolute::Height::ZERO),
+            input: vec![],
+            output: vec![],
+        };
+        let (confirmed_op, unconfirmed_op, spent_op, unknown_op) = (
+            bitcoin::OutPoint::new(dummy_tx.compute_txid(), 0),
+            bitcoin::OutPoint::new(dummy_tx.compute_txid(), 1),
+            bitcoin::OutPoint::new(dummy_tx.compute_txid(), 2),
+            bitcoin::OutPoint::new(dummy_tx.compute_txid(), 3),
+        );
+        let ms = DummyLiana::new(DummyBitcoind::new(), DummyDatabase::new());
+        let control = &ms.control();
+        let message = "Proof of reserves for block 100";
+
+        // There must be coins to prove the control of.
+        assert_eq!(
+            control.create_proof_of_reserves(message, &[]),
+            Err(CommandError::NoReserves)
+        );
+
+        let mut db_conn = control.db().lock().unwrap().connection();
+        db_conn.new_txs(&[dummy_tx]);
+        db_conn.new_unspent_coins(&[
+            Coin {
+                outpoint: confirmed_op,
+                is_immature: false,
+                block_info: Some(BlockInfo {
+                    height: 174500,
+                    time: 174500,
+                }),
+                amount: bitcoin::Amount::from_sat(100_000),
+                derivation_index: bip32::ChildNumber::from(13),
+                is_change: false,
+                spend_txid: None,
+                spend_block: None,
+                is_from_self: false,
+                is_frozen: false,
+            },
+            Coin {
+                outpoint: unconfirmed_op,
+                is_immature: false,
+                block_info: None,
+                amount: bitcoin::Amount::from_sat(20_000),
+                derivation_index: bip32::ChildNumber::from(14),
+                is_change: true,
+                spend_txid: None,
+                spend_block: None,
+                is_from_self: false,
+                is_frozen: false,
+            },
+            Coin {
+                outpoint: spent_op,
+                is_immature: false,
+                block_info: Some(BlockInfo {
+                    height: 174500,
+                    time: 174500,
+                }),
+                amount: bitcoin::Amount::from_sat(30_000),
+                derivation_index: bip32::ChildNumber::from(15),
+                is_change: false,
+                spend_txid: None,
+                spend_block: None,
+                is_from_self: false,
+                is_frozen: false,
+            },
+        ]);
+        let spend_txid =
+            Txid::from_str("617eab1fc0b03ee7f82ba70166725291783461f1a0e7975eaf8b5f8f674234f3")
+                .unwrap();
+        db_conn.spend_coins(&[(spent_op, spend_txid)]);
+
+        // By default only the confirmed coins are used.
+        let psbt = control.create_proof_of_reserves(message, &[]).unwrap().psbt;
+        let tx = &psbt.unsigned_tx;
+        assert_eq!(
+            tx.input[0].previous_output,
+            proof_of_reserves::commitment_outpoint(message)
+        );
+        assert_eq!(
+            tx.input[1..]
+                .iter()
+                .map(|txin| txin.previous_output)
+                .collect::<Vec<_>>(),
+            vec![confirmed_op]
+        );
+        assert_eq!(tx.output[0].value, bitcoin::Amount::from_sat(100_000));
+
+        // Unconfirmed coins may be selected explicitly, but not unknown or spent ones.
+        let psbt = control
+            .create_proof_of_reserves(message, &[confirmed_op, unconfirmed_op])
+            .unwrap()
+            .psbt;
+        assert_eq!(psbt.unsigned_tx.input.len(), 3);
+        assert_eq!(psbt.inputs.len(), 3);
+        assert_eq!(
+            psbt.unsigned_tx.output[0].value,
+            bitcoin::Amount::from_sat(120_000)
+        );
+        assert_eq!(
+            control.create_proof_of_reserves(message, &[confirmed_op, unknown_op]),
+            Err(CommandError::UnknownOutpoint(unknown_op))
+        );
+        assert_eq!(
+            control.create_proof_of_reserves(message, &[spent_op]),
+            Err(CommandError::AlreadySpent(spent_op))
+        );
+
+        // The proof is only valid for its message, and its coins must be unspent.
+        assert_eq!(
+            control.verify_proof_of_reserves(&psbt, "Another message"),
+            Err(CommandError::InvalidProofOfReserves(ProofError::Commitment))
+        );
+        assert_eq!(
+            control.verify_proof_of_reserves(&psbt, message),
+            Err(CommandError::InvalidProofOfReserves(
+                ProofError::UnknownCoin(confirmed_op)
+            ))
+        );
+
+        ms.shutdown();
+    }
+
+    #[test]
+    fn cpfp_bump() {
+        let txs: Vec<_> = (0..3)
+            .map(|i| bitcoin::Transaction {
+                version: TxVersion::TWO,
+                lock_time: absolute::LockTime::from_height(i).unwrap(),
+                input: vec![],
+                output: vec![],
+            })
+            .collect();
+        let (parent_txid, dropped_txid, confirmed_txid) = (
+            txs[0].compute_txid(),
+            txs[1].compute_txid(),
+            txs[2].compute_txid(),
+        );
+        let (op_a, op_b) = (
+            bitcoin::OutPoint::new(parent_txid, 0),
+            bitcoin::OutPoint::new(parent_txid, 1),
+        );
+        let dropped_op = bitcoin::OutPoint::new(dropped_txid, 0);
+        let confirmed_op = bitcoin::OutPoint::new(confirmed_txid, 0);
+
+        // The parent transaction pays 1 sat/vb and has no unconfirmed ancestor.
+        let mut bitcoind = DummyBitcoind::new();
+        bitcoind.mempool_entries.insert(
+            parent_txid,
+            MempoolEntry {
+                vsize: 200,
+                ancestor_vsize: 200,
+                fees: MempoolEntryFees {
+                    base: Amount::from_sat(200),
+                    ancestor: Amount::from_sat(200),
+                    descendant: Amount::from_sat(200),
+                },
+            },
+        );
+        let ms = DummyLiana::new(bitcoind, DummyDatabase::new());
+        let control = &ms.control();
+        let mut db_conn = control.db().lock().unwrap().connection();
+        db_conn.new_txs(&txs);
+        let coin = |outpoint, amount, block_info| Coin {
+            outpoint,
+            is_immature: false,
+            block_info,
+            amount: Amount::from_sat(amount),
+            derivation_index: bip32::ChildNumber::from(13),
+            is_change: false,
+            spend_txid: None,
+            spend_block: None,
+            is_from_self: false,
+            is_frozen: false,
+        };
+        let block_info = Some(BlockInfo {
+            height: 174500,
+            time: 174500,
+        });
+        db_conn.new_unspent_coins(&[
+            coin(op_a, 100_000, None),
+            coin(op_b, 2_000, None),
+            coin(dropped_op, 100_000, None),
+            coin(confirmed_op, 1_000_000, block_info),
+        ]);
+
+        // Sanity checks.
+        assert_eq!(
+            control.cpfp_bump(&[], 10),
+            Err(CommandError::NoOutpointForSelfSend)
+        );
+        assert_eq!(
+            control.cpfp_bump(&[op_a], 0),
+            Err(CommandError::InvalidFeerate(0))
+        );
+        let unknown_op = bitcoin::OutPoint::new(parent_txid, 2);
+        assert_eq!(
+            control.cpfp_bump(&[unknown_op], 10),
+            Err(CommandError::UnknownOutpoint(unknown_op))
+        );
+        assert_eq!(
+            control.cpfp_bump(&[confirmed_op], 10),
+            Err(CommandError::CpfpError(CpfpErrorInfo::AlreadyConfirmed(
+                confirmed_op
+            )))
+        );
+        assert_eq!(
+            control.cpfp_bump(&[dropped_op], 10),
+            Err(CommandError::CpfpError(CpfpErrorInfo::NotInMempool(
+                dropped_txid
+            )))
+        );
+        assert_eq!(
+            control.cpfp_bump(&[op_a], 1),
+            Err(CommandError::CpfpError(
+                CpfpErrorInfo::FeerateAlreadyReached(parent_txid, 1)
+            ))
+        );
+
+        // Bump the parent by spending both its outputs. The fee for its ancestors is only paid
+        // once: (200 - 200 / 10) * 10 = 1800 sats.
+        let (psbt, warnings) = if let CreateSpendResult::Success { psbt, warnings } =
+            control.cpfp_bump(&[op_a, op_b], 10).unwrap()
+        {
+            (psbt, warnings)
         } else {
-            panic!("expect successful spend creation")
+            panic!("expect successful CPFP creation")
         };
         let tx = psbt.unsigned_tx;
-        let tx_prev_outpoints = tx
+        let mut prev_outpoints: Vec<_> = tx.input.iter().map(|txin| txin.previous_output).collect();
+        prev_outpoints.sort();
+        let mut expected_outpoints = vec![op_a, op_b];
+        expected_outpoints.sort();
+        assert_eq!(prev_outpoints, expected_outpoints);
+        assert_eq!(tx.output.len(), 1);
+        assert!(tx.output[0].value.to_sat() < 102_000 - 1_800);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("increased by 1800 sats"));
+
+        // If the coin to bump can't pay for the fee, a confirmed coin is used too.
+        let psbt = if let CreateSpendResult::Success { psbt, .. } =
+            control.cpfp_bump(&[op_b], 50).unwrap()
+        {
+            psbt
+        } else {
+            panic!("expect successful CPFP creation")
+        };
+        let mut prev_outpoints: Vec<_> = psbt
+            .unsigned_tx
             .input
             .iter()
             .map(|txin| txin.previous_output)
-            .collect::<Vec<OutPoint>>();
-        assert_eq!(tx.input.len(), 1);
-        assert_eq!(tx_prev_outpoints, vec![confirmed_op_3]);
-        assert_eq!(tx.output.len(), 1);
+            .collect();
+        prev_outpoints.sort();
+        let mut expected_outpoints = vec![op_b, confirmed_op];
+        expected_outpoints.sort();
+        assert_eq!(prev_outpoints, expected_outpoints);
 
-        // Can't create a transaction that spends an immature coinbase deposit.
-        let imma_op = bitcoin::OutPoint::from_str(
-            "4753a1d74c0af8dd0a0f3b763c14faf3bd9ed03cbdf33337a074fb0e9f6c7810:0",
-        )
-        .unwrap();
+        // Unless it is frozen.
+        control.freeze_coins(&[confirmed_op]).unwrap();
+        assert!(matches!(
+            control.cpfp_bump(&[op_b], 50),
+            Ok(CreateSpendResult::InsufficientFunds { .. }),
+        ));
+
+        ms.shutdown();
+    }
+
+    #[test]
+    fn spending_policy() {
+        let dummy_tx = bitcoin::Transaction {
+            version: TxVersion::TWO,
+            lock_time: absolute::LockTime::Blocks(absolute::Height::ZERO),
+            input: vec![],
+            output: vec![],
+        };
+        let dummy_op = bitcoin::OutPoint::new(dummy_tx.compute_txid(), 0);
+        let ms = DummyLiana::new(DummyBitcoind::new(), DummyDatabase::new());
+        let mut control = ms.control().clone();
+        control.config.spending_policy = Some(SpendingPolicy {
+            max_tx_amount_sat: Some(50_000),
+            require_labels: true,
+            min_confirmations: Some(1),
+            broadcast_delay_hours: Some(1),
+            ..SpendingPolicy::default()
+        });
+        let mut db_conn = control.db().lock().unwrap().connection();
+        db_conn.new_txs(&[dummy_tx]);
         db_conn.new_unspent_coins(&[Coin {
-            outpoint: imma_op,
-            is_immature: true,
+            outpoint: dummy_op,
+            is_immature: false,
             block_info: None,
-            amount: bitcoin::Amount::from_sat(COIN_VALUE),
+            amount: bitcoin::Amount::from_sat(100_000),
             derivation_index: bip32::ChildNumber::from(13),
             is_change: false,
             spend_txid: None,
             spend_block: None,
-            is_from_self: false,
+            is_from_self: true,
+            is_frozen: false,
         }]);
+        let dummy_addr =
+            bitcoin::Address::from_str("bc1qnsexk3gnuyayu92fc3tczvc7k62u22a22ua2kv").unwrap();
+
+        // The payment must be labelled.
+        let mut destinations = vec![Destination::new(dummy_addr.clone(), 10_000)];
+        assert!(matches!(
+            control.create_spend(&destinations, &[dummy_op], 1, None),
+            Err(CommandError::SpendingPolicy(
+                SpendingPolicyViolation::MissingLabel(OutPoint { vout: 0, .. })
+            ))
+        ));
+
+        // The coin must be confirmed. It is not even selected automatically before.
+        destinations[0].label = Some("payment".to_string());
         assert_eq!(
-            control.create_spend(&destinations, &[imma_op], 1_001, None),
-            Err(CommandError::ImmatureCoinbase(imma_op))
+            control.create_spend(&destinations, &[dummy_op], 1, None),
+            Err(CommandError::SpendingPolicy(
+                SpendingPolicyViolation::InputConfirmations {
+                    outpoint: dummy_op,
+                    confirmations: 0,
+                    min: 1,
+                }
+            ))
+        );
+        assert!(matches!(
+            control.create_spend(&destinations, &[], 1, None),
+            Ok(CreateSpendResult::InsufficientFunds { .. }),
+        ));
+        db_conn.update_tip(&BlockChainTip {
+            hash: bitcoin::BlockHash::from_str(
+                "000000007bc154e0fa7ea32218a72fe2c1bb9f86cf8c9ebf9a715ed27fdb229a",
+            )
+            .unwrap(),
+            height: 100,
+        });
+        db_conn.confirm_coins(&[(dummy_op, 100, 1_700_000_000)]);
+
+        // The amount sent out of the wallet, fees included, is limited.
+        destinations[0].amount = 50_000;
+        assert!(matches!(
+            control.create_spend(&destinations, &[dummy_op], 1, None),
+            Err(CommandError::SpendingPolicy(
+                SpendingPolicyViolation::TxAmount { .. }
+            ))
+        ));
+
+        // A spend abiding by the policy may only be broadcast an hour after it was stored.
+        destinations[0].amount = 10_000;
+        let psbt = if let CreateSpendResult::Success { psbt, .. } =
+            control.create_spend(&destinations, &[], 1, None).unwrap()
+        {
+            psbt
+        } else {
+            panic!("expect successful spend creation")
+        };
+        let txid = psbt.unsigned_tx.compute_txid();
+
+        // Inputs which are not our coins, such as the sender's in a payjoin we receive, don't
+        // count towards the value sent out of the wallet.
+        let mut payjoin_psbt = psbt.clone();
+        payjoin_psbt.unsigned_tx.input.push(bitcoin::TxIn {
+            previous_output: bitcoin::OutPoint::new(txid, 0),
+            sequence: psbt.unsigned_tx.input[0].sequence,
+            ..bitcoin::TxIn::default()
+        });
+        payjoin_psbt.inputs.push(PsbtIn {
+            witness_utxo: Some(bitcoin::TxOut {
+                value: bitcoin::Amount::ONE_BTC,
+                script_pubkey: dummy_addr.assume_checked_ref().script_pubkey(),
+            }),
+            ..PsbtIn::default()
+        });
+        let no_labels = HashSet::new();
+        assert_eq!(
+            control
+                .policy_spend(&mut db_conn, &payjoin_psbt, &no_labels)
+                .unwrap()
+                .amount,
+            control
+                .policy_spend(&mut db_conn, &psbt, &no_labels)
+                .unwrap()
+                .amount
+        );
+
+        assert_eq!(
+            control.check_spend(&txid),
+            Err(CommandError::UnknownSpend(txid))
+        );
+        control.update_spend(psbt).unwrap();
+        assert_eq!(
+            control.check_spend(&txid).unwrap().violations,
+            vec!["The spend may only be broadcast in 60 minutes.".to_string()]
+        );
+        assert_eq!(
+            control.broadcast_spend(&txid),
+            Err(CommandError::SpendingPolicy(
+                SpendingPolicyViolation::BroadcastDelay(60 * 60)
+            ))
         );
 
+        // Without a policy there is nothing to check.
+        control.config.spending_policy = None;
+        assert!(control.check_spend(&txid).unwrap().violations.is_empty());
+
         ms.shutdown();
     }
 
@@ -2567,6 +5224,7 @@ mod tests {
                 spend_txid: None,
                 spend_block: None,
                 is_from_self: false,
+                is_frozen: false,
             },
             Coin {
                 outpoint: dummy_op_b,
@@ -2578,6 +5236,7 @@ mod tests {
                 spend_txid: None,
                 spend_block: None,
                 is_from_self: false,
+                is_frozen: false,
             },
         ]);
 
@@ -2588,21 +5247,12 @@ mod tests {
             bitcoin::Address::from_str("bc1q39srgatmkp6k2ne3l52yhkjprdvunvspqydmkx").unwrap();
         let dummy_value_a = 50_000;
         let dummy_value_b = 60_000;
-        let destinations_a: HashMap<bitcoin::Address<address::NetworkUnchecked>, u64> =
-            [(dummy_addr_a.clone(), dummy_value_a)]
-                .iter()
-                .cloned()
-                .collect();
-        let destinations_b: HashMap<bitcoin::Address<address::NetworkUnchecked>, u64> =
-            [(dummy_addr_b.clone(), dummy_value_b)]
-                .iter()
-                .cloned()
-                .collect();
-        let destinations_c: HashMap<bitcoin::Address<address::NetworkUnchecked>, u64> =
-            [(dummy_addr_a, dummy_value_a), (dummy_addr_b, dummy_value_b)]
-                .iter()
-                .cloned()
-                .collect();
+        let destinations_a = vec![Destination::new(dummy_addr_a.clone(), dummy_value_a)];
+        let destinations_b = vec![Destination::new(dummy_addr_b.clone(), dummy_value_b)];
+        let destinations_c = vec![
+            Destination::new(dummy_addr_a, dummy_value_a),
+            Destination::new(dummy_addr_b, dummy_value_b),
+        ];
         let mut psbt_a = if let CreateSpendResult::Success { psbt, .. } = control
             .create_spend(&destinations_a, &[dummy_op_a], 1, None)
             .unwrap()
@@ -2726,6 +5376,7 @@ mod tests {
                 time: 184500,
             }),
             is_from_self: false,
+            is_frozen: false,
         }]);
         // The coin is spent so we cannot RBF.
         assert_eq!(
@@ -2838,6 +5489,7 @@ mod tests {
                 amount: bitcoin::Amount::from_sat(100_000_000),
                 spend_txid: Some(spend_tx.compute_txid()),
                 is_from_self: false,
+                is_frozen: false,
             },
             // Deposit 2
             Coin {
@@ -2853,6 +5505,7 @@ mod tests {
                 amount: bitcoin::Amount::from_sat(2000),
                 spend_txid: None,
                 is_from_self: false,
+                is_frozen: false,
             },
             // This coin is a change output.
             Coin {
@@ -2865,6 +5518,7 @@ mod tests {
                 amount: bitcoin::Amount::from_sat(100_000_000 - 4000 - 1000),
                 spend_txid: None,
                 is_from_self: false,
+                is_frozen: false,
             },
             // Deposit 3
             Coin {
@@ -2880,6 +5534,7 @@ mod tests {
                 amount: bitcoin::Amount::from_sat(3000),
                 spend_txid: None,
                 is_from_self: false,
+                is_frozen: false,
             },
         ]);
 
@@ -3101,6 +5756,7 @@ mod tests {
                     spend_txid: None,
                     spend_block: None,
                     is_from_self: false,
+                    is_frozen: false,
                 }]);
             }
         }
@@ -3183,6 +5839,7 @@ mod tests {
             spend_txid: None,
             spend_block: None,
             is_from_self: false,
+            is_frozen: false,
         };
         db_conn.new_unspent_coins(&[dummy_coin]);
         // Recovery not available for unconfirmed coins.
@@ -3291,6 +5948,7 @@ mod tests {
             spend_txid: None,
             spend_block: None,
             is_from_self: false,
+            is_frozen: false,
         };
         db_conn.new_unspent_coins(&[dummy_coin_2]);
         db_conn.confirm_coins(&[(dummy_op_2, 92, 200_000)]);
@@ -3361,4 +6019,345 @@ mod tests {
 
         ms.shutdown();
     }
+
+    #[test]
+    fn create_absolute_recovery() {
+        let dummy_tx = bitcoin::Transaction {
+            version: TxVersion::TWO,
+            lock_time: absolute::LockTime::Blocks(absolute::Height::ZERO),
+            input: vec![],
+            output: vec![],
+        };
+        let dummy_txid = dummy_tx.compute_txid();
+        let dummy_op = bitcoin::OutPoint::new(dummy_txid, 0);
+        let ms =
+            DummyLiana::new_absolute_timelock(DummyBitcoind::new(), DummyDatabase::new(), 10, 50);
+        let control = &ms.control();
+        let mut db_conn = control.db().lock().unwrap().connection();
+        db_conn.new_txs(&[dummy_tx]);
+        let dummy_addr =
+            bitcoin::Address::from_str("bc1qnsexk3gnuyayu92fc3tczvc7k62u22a22ua2kv").unwrap();
+
+        // Feerate cannot be less than 1.
+        assert_eq!(
+            control.create_absolute_recovery(dummy_addr.clone(), &[], 0, 50),
+            Err(CommandError::InvalidFeerate(0))
+        );
+        // We have no coins to create recovery.
+        assert_eq!(
+            control.create_absolute_recovery(dummy_addr.clone(), &[], 1, 50),
+            Err(CommandError::RecoveryNotAvailable),
+        );
+
+        // Add an unconfirmed coin. It can't be recovered.
+        let dummy_coin = Coin {
+            outpoint: dummy_op,
+            is_immature: false,
+            block_info: None,
+            amount: bitcoin::Amount::from_sat(100_000),
+            derivation_index: bip32::ChildNumber::from(13),
+            is_change: false,
+            spend_txid: None,
+            spend_block: None,
+            is_from_self: false,
+            is_frozen: false,
+        };
+        db_conn.new_unspent_coins(&[dummy_coin]);
+        assert_eq!(
+            control.create_absolute_recovery(dummy_addr.clone(), &[], 1, 50),
+            Err(CommandError::RecoveryNotAvailable),
+        );
+        assert_eq!(
+            control.create_absolute_recovery(dummy_addr.clone(), &[dummy_op], 1, 50),
+            Err(CommandError::OutpointNotRecoverable(dummy_op, 50)),
+        );
+
+        // Confirm the coin at the tip. It isn't available through the relative timelocked path
+        // but it is through the absolute timelocked one, since the tip (100) is past its height.
+        db_conn.confirm_coins(&[(dummy_op, 100, 100_000)]);
+        assert_eq!(
+            control.create_recovery(dummy_addr.clone(), &[], 1, None),
+            Err(CommandError::RecoveryNotAvailable),
+        );
+        let psbt = control
+            .create_absolute_recovery(dummy_addr.clone(), &[], 1, 50)
+            .unwrap()
+            .psbt;
+        assert_eq!(
+            psbt.unsigned_tx.lock_time,
+            absolute::LockTime::from_consensus(50)
+        );
+        assert_eq!(psbt.unsigned_tx.input.len(), 1);
+        assert_eq!(
+            psbt.unsigned_tx.input[0].sequence,
+            Sequence::ENABLE_RBF_NO_LOCKTIME
+        );
+        assert_eq!(psbt.unsigned_tx.output.len(), 1);
+        assert_eq!(
+            psbt.unsigned_tx.output[0].script_pubkey,
+            dummy_addr.assume_checked_ref().script_pubkey()
+        );
+        // The same when specifying the coin explicitly.
+        let psbt_op = control
+            .create_absolute_recovery(dummy_addr.clone(), &[dummy_op], 1, 50)
+            .unwrap()
+            .psbt;
+        assert_eq!(psbt.unsigned_tx, psbt_op.unsigned_tx);
+        // The fee accounts for the satisfaction of the recovery path, not the primary one.
+        let desc = &control.config.main_descriptor;
+        assert!(
+            psbt.unsigned_tx.output[0].value
+                < Amount::from_sat(100_000)
+                    - Amount::from_sat(desc.unsigned_tx_max_vbytes(&psbt.unsigned_tx, true))
+        );
+
+        // The absolute timelock isn't reached at the next block.
+        assert_eq!(
+            control.create_absolute_recovery(dummy_addr.clone(), &[], 1, 102),
+            Err(CommandError::RecoveryNotAvailable),
+        );
+        assert_eq!(
+            control.create_absolute_recovery(dummy_addr.clone(), &[dummy_op], 1, 102),
+            Err(CommandError::OutpointNotRecoverable(dummy_op, 102)),
+        );
+        // Nor is a time-based one which the median time past of the tip hasn't reached yet.
+        assert_eq!(
+            control.create_absolute_recovery(dummy_addr.clone(), &[], 1, 1_893_456_000),
+            Err(CommandError::RecoveryNotAvailable),
+        );
+
+        ms.shutdown();
+    }
+
+    #[test]
+    fn create_time_recovery() {
+        let dummy_txs: Vec<_> = (0..3)
+            .map(|i| bitcoin::Transaction {
+                version: TxVersion::TWO,
+                lock_time: absolute::LockTime::from_height(i).unwrap(),
+                input: vec![],
+                output: vec![],
+            })
+            .collect();
+        let ops: Vec<_> = dummy_txs
+            .iter()
+            .map(|tx| bitcoin::OutPoint::new(tx.compute_txid(), 0))
+            .collect();
+        // A time-based recovery path available 10 * 512 = 5120 seconds after confirmation. The
+        // dummy backend has a block every 600 seconds and its tip at height 100.
+        let ms = DummyLiana::new_time_timelock(DummyBitcoind::new(), DummyDatabase::new(), 10, 10);
+        let control = &ms.control();
+        let mut db_conn = control.db().lock().unwrap().connection();
+        db_conn.new_txs(&dummy_txs);
+        let dummy_addr =
+            bitcoin::Address::from_str("bc1qnsexk3gnuyayu92fc3tczvc7k62u22a22ua2kv").unwrap();
+
+        // Feerate cannot be less than 1.
+        assert_eq!(
+            control.create_time_recovery(dummy_addr.clone(), &[], 0, 10),
+            Err(CommandError::InvalidFeerate(0))
+        );
+        // We have no coins to create recovery.
+        assert_eq!(
+            control.create_time_recovery(dummy_addr.clone(), &[], 1, 10),
+            Err(CommandError::RecoveryNotAvailable),
+        );
+
+        // Add three coins. Confirm the first one at height 95 and the second one at height 90.
+        let coins: Vec<_> = ops
+            .iter()
+            .enumerate()
+            .map(|(i, op)| Coin {
+                outpoint: *op,
+                is_immature: false,
+                block_info: None,
+                amount: bitcoin::Amount::from_sat(100_000),
+                derivation_index: bip32::ChildNumber::from(i as u32),
+                is_change: false,
+                spend_txid: None,
+                spend_block: None,
+                is_from_self: false,
+                is_frozen: false,
+            })
+            .collect();
+        db_conn.new_unspent_coins(&coins);
+        db_conn.confirm_coins(&[(ops[0], 95, 1_000), (ops[1], 90, 1_000)]);
+
+        // The time elapsed since their confirmation is counted from the median time past of the
+        // block preceding their confirmation block.
+        let listed = control.list_coins(&[], &[]).coins;
+        let elapsed = |op: &bitcoin::OutPoint| {
+            listed
+                .iter()
+                .find(|c| c.outpoint == *op)
+                .unwrap()
+                .time_since_confirmation
+        };
+        assert_eq!(elapsed(&ops[0]), Some(6 * 600));
+        assert_eq!(elapsed(&ops[1]), Some(11 * 600));
+        assert_eq!(elapsed(&ops[2]), None);
+
+        // Only the second coin is available through the time-based path.
+        let psbt = control
+            .create_time_recovery(dummy_addr.clone(), &[], 1, 10)
+            .unwrap()
+            .psbt;
+        assert_eq!(psbt.unsigned_tx.input.len(), 1);
+        assert_eq!(psbt.unsigned_tx.input[0].previous_output, ops[1]);
+        assert_eq!(
+            psbt.unsigned_tx.input[0].sequence,
+            Sequence::from_512_second_intervals(10)
+        );
+        assert_eq!(
+            psbt.unsigned_tx.output[0].script_pubkey,
+            dummy_addr.assume_checked_ref().script_pubkey()
+        );
+        let desc = &control.config.main_descriptor;
+        assert!(desc
+            .partial_spend_info(&psbt)
+            .unwrap()
+            .time_recovery_paths()
+            .contains_key(&10));
+        assert_eq!(
+            control.create_time_recovery(dummy_addr.clone(), &[ops[0]], 1, 10),
+            Err(CommandError::OutpointNotRecoverable(ops[0], 10)),
+        );
+        assert_eq!(
+            control.create_time_recovery(dummy_addr.clone(), &[ops[2]], 1, 10),
+            Err(CommandError::OutpointNotRecoverable(ops[2], 10)),
+        );
+        // The second coin is also available through the block-based path, whose nSequence
+        // doesn't unlock the time-based one.
+        let psbt = control
+            .create_recovery(dummy_addr.clone(), &[], 1, Some(10))
+            .unwrap()
+            .psbt;
+        assert_eq!(psbt.unsigned_tx.input.len(), 1);
+        let spend_info = desc.partial_spend_info(&psbt).unwrap();
+        assert!(spend_info.time_recovery_paths().is_empty());
+        assert!(spend_info.recovery_paths().contains_key(&10));
+        // A path longer than the time elapsed since any coin confirmation isn't available.
+        assert_eq!(
+            control.create_time_recovery(dummy_addr, &[], 1, 13),
+            Err(CommandError::RecoveryNotAvailable),
+        );
+
+        ms.shutdown();
+    }
+
+    #[test]
+    fn import_labels_bip329() {
+        let ms = DummyLiana::new(DummyBitcoind::new(), DummyDatabase::new());
+        let control = &ms.control();
+
+        let txid =
+            Txid::from_str("f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd")
+                .unwrap();
+        let addr = bitcoin::Address::from_str(
+            "bc1q9ksrc647hx8zp2cewl8p5f487dgux3777yees8rjcx46t4daqzzqt7yga8",
+        )
+        .unwrap()
+        .assume_checked();
+        let outpoint = OutPoint::new(txid, 1);
+        control.update_labels(&HashMap::from([(
+            LabelItem::Txid(txid),
+            Some("existing".to_string()),
+        )]));
+
+        let records = [
+            r#"{"type":"tx","ref":"f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd","label":"imported tx"}"#,
+            r#"{"type":"addr","ref":"bc1q9ksrc647hx8zp2cewl8p5f487dgux3777yees8rjcx46t4daqzzqt7yga8","label":"first"}"#,
+            "",
+            r#"{"type":"output","ref":"f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd:1","label":"coin","spendable":true}"#,
+            r#"{"type":"input","ref":"f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd:0","label":"input"}"#,
+            r#"{"type":"pubkey","ref":"0283409659355b6d1cc3c32decd5d561abaac86c37a353b52895a5e6c196d6f448","label":"key"}"#,
+            r#"{"type":"addr","ref":"tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx","label":"testnet"}"#,
+            r#"{"type":"tx","ref":"f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd"}"#,
+            r#"{"type":"unknown","ref":"whatever","label":"label"}"#,
+            "not json",
+            r#"{"type":"addr","ref":"bc1q9ksrc647hx8zp2cewl8p5f487dgux3777yees8rjcx46t4daqzzqt7yga8","label":"second"}"#,
+        ]
+        .join("\n");
+        let skipped =
+            |line: usize, type_: Option<&str>, ref_: Option<&str>, reason| SkippedBip329Record {
+                line,
+                type_: type_.map(String::from),
+                ref_: ref_.map(String::from),
+                reason,
+            };
+        let expected_skipped = vec![
+            skipped(
+                5,
+                Some("input"),
+                Some("f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd:0"),
+                SkippedBip329Reason::Unsupported,
+            ),
+            skipped(
+                6,
+                Some("pubkey"),
+                Some("0283409659355b6d1cc3c32decd5d561abaac86c37a353b52895a5e6c196d6f448"),
+                SkippedBip329Reason::Unsupported,
+            ),
+            skipped(
+                7,
+                Some("addr"),
+                Some("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx"),
+                SkippedBip329Reason::WrongNetwork,
+            ),
+            skipped(
+                8,
+                Some("tx"),
+                Some("f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd"),
+                SkippedBip329Reason::MissingLabel,
+            ),
+            skipped(
+                9,
+                Some("unknown"),
+                Some("whatever"),
+                SkippedBip329Reason::Invalid,
+            ),
+            skipped(10, None, None, SkippedBip329Reason::Invalid),
+        ];
+        let items = HashSet::from([
+            LabelItem::Txid(txid),
+            LabelItem::Address(addr.clone()),
+            LabelItem::OutPoint(outpoint),
+        ]);
+
+        // By default the existing label is kept. The last record for an item wins.
+        let mut merge_skipped = expected_skipped.clone();
+        merge_skipped.insert(
+            0,
+            skipped(
+                1,
+                Some("tx"),
+                Some("f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd"),
+                SkippedBip329Reason::Conflict,
+            ),
+        );
+        assert_eq!(
+            control.import_labels_bip329(&records, LabelsConflictPolicy::Merge),
+            ImportLabelsBip329Result {
+                imported: 2,
+                skipped: merge_skipped,
+            }
+        );
+        let labels = control.get_labels(&items).labels;
+        assert_eq!(labels.get(&txid.to_string()).unwrap(), "existing");
+        assert_eq!(labels.get(&addr.to_string()).unwrap(), "second");
+        assert_eq!(labels.get(&outpoint.to_string()).unwrap(), "coin");
+
+        // Now overwrite the existing labels.
+        assert_eq!(
+            control.import_labels_bip329(&records, LabelsConflictPolicy::Overwrite),
+            ImportLabelsBip329Result {
+                imported: 3,
+                skipped: expected_skipped,
+            }
+        );
+        let labels = control.get_labels(&items).labels;
+        assert_eq!(labels.get(&txid.to_string()).unwrap(), "imported tx");
+
+        ms.shutdown();
+    }
 }
@@ -1,8 +1,11 @@
 use liana::descriptors::LianaDescriptor;
 
-use std::{fmt, net::SocketAddr, path::PathBuf, str::FromStr, time::Duration};
+use std::{convert::TryFrom, fmt, net::SocketAddr, path::PathBuf, str::FromStr, time::Duration};
 
-use crate::datadir::DataDirectory;
+use crate::{
+    commands::MAX_GAP_LIMIT, datadir::DataDirectory, notifications::NotifyHook,
+    signing::SigningPolicy, spending_policy::SpendingPolicy,
+};
 use miniscript::bitcoin::Network;
 
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
@@ -81,6 +84,14 @@ fn default_poll_interval() -> Duration {
     Duration::from_secs(30)
 }
 
+fn default_gap_limit() -> u32 {
+    200
+}
+
+fn is_default_gap_limit(gap_limit: &u32) -> bool {
+    *gap_limit == default_gap_limit()
+}
+
 /// Bitcoin backend config.
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum BitcoinBackend {
@@ -90,6 +101,12 @@ pub enum BitcoinBackend {
     /// Settings specific to Electrum as the Bitcoin interface.
     #[serde(rename = "electrum_config")]
     Electrum(ElectrumConfig),
+    /// Settings specific to an Esplora server as the Bitcoin interface.
+    #[serde(rename = "esplora_config")]
+    Esplora(EsploraConfig),
+    /// Settings specific to compact block filters as the Bitcoin interface.
+    #[serde(rename = "cbf_config")]
+    Cbf(CbfConfig),
 }
 
 /// RPC authentication options.
@@ -139,6 +156,22 @@ fn default_validate_domain() -> bool {
     true
 }
 
+/// Everything we need to know for talking to an Esplora server serenely.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct EsploraConfig {
+    /// The base URL of the Esplora REST API, for instance "https://mempool.space/api".
+    pub addr: String,
+}
+
+/// Everything we need to know for syncing using compact block filters.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CbfConfig {
+    /// The "host:port" of the peers to get the block filters from, in order of preference. They
+    /// must serve compact block filters (for instance bitcoind with `-peerblockfilters`). The
+    /// filter headers of the first reachable one are checked against those of the others.
+    pub peers: Vec<String>,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct BitcoinConfig {
     /// The network we are operating on, one of "bitcoin", "testnet", "testnet4", "regtest", "signet"
@@ -152,6 +185,106 @@ pub struct BitcoinConfig {
     pub poll_interval_secs: Duration,
 }
 
+/// A user allowed to query the JSONRPC server over HTTP.
+#[derive(Clone, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "HttpRpcUserEntry", into = "HttpRpcUserEntry")]
+pub struct HttpRpcUser {
+    pub user: String,
+    pub password: String,
+    /// The methods this user is allowed to call. All of them if not set.
+    pub allowed_methods: Option<Vec<String>>,
+}
+
+impl HttpRpcUser {
+    /// Whether this user is allowed to call this JSONRPC method.
+    pub fn is_allowed(&self, method: &str) -> bool {
+        self.allowed_methods
+            .as_ref()
+            .map(|methods| methods.iter().any(|m| m == method))
+            .unwrap_or(true)
+    }
+}
+
+impl fmt::Debug for HttpRpcUser {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpRpcUser")
+            .field("user", &self.user)
+            .field("password", &"REDACTED")
+            .field("allowed_methods", &self.allowed_methods)
+            .finish()
+    }
+}
+
+/// How a user of the HTTP JSONRPC server is written in the configuration file.
+#[derive(Deserialize, Serialize)]
+struct HttpRpcUserEntry {
+    /// "USER:PASSWORD" for authentication.
+    auth: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    allowed_methods: Option<Vec<String>>,
+}
+
+impl TryFrom<HttpRpcUserEntry> for HttpRpcUser {
+    type Error = &'static str;
+
+    fn try_from(entry: HttpRpcUserEntry) -> Result<Self, Self::Error> {
+        let (user, password) = entry
+            .auth
+            .split_once(':')
+            .ok_or("`auth` must be 'user:password'")?;
+        Ok(HttpRpcUser {
+            user: user.to_string(),
+            password: password.to_string(),
+            allowed_methods: entry.allowed_methods,
+        })
+    }
+}
+
+impl From<HttpRpcUser> for HttpRpcUserEntry {
+    fn from(user: HttpRpcUser) -> Self {
+        HttpRpcUserEntry {
+            auth: format!("{}:{}", user.user, user.password),
+            allowed_methods: user.allowed_methods,
+        }
+    }
+}
+
+/// Settings for the JSONRPC server over HTTP.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct HttpRpcConfig {
+    /// The IP:port to listen on.
+    pub addr: SocketAddr,
+    /// Whether to receive payjoins (BIP78) from anyone at the `/payjoin` path of the server.
+    /// Requires the bitcoind backend.
+    #[serde(default)]
+    pub payjoin: bool,
+    /// The users allowed to query the server, in addition to the owner of the cookie file.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub users: Vec<HttpRpcUser>,
+}
+
+/// Settings for signing spends with the hot signers whose mnemonics are stored in the data
+/// directory.
+#[derive(Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct SigningConfig {
+    /// The password to decrypt the encrypted mnemonics with. Only the mnemonics stored in
+    /// plaintext are loaded if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// The rules a spend must follow for us to sign it.
+    #[serde(default)]
+    pub policy: SigningPolicy,
+}
+
+impl fmt::Debug for SigningConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SigningConfig")
+            .field("password", &self.password.as_ref().map(|_| "REDACTED"))
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
 /// Static informations we require to operate
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Config {
@@ -172,8 +305,29 @@ pub struct Config {
         serialize_with = "serialize_to_string"
     )]
     pub main_descriptor: LianaDescriptor,
+    /// The number of consecutive unused addresses after which we stop looking for transactions
+    /// on each keychain.
+    #[serde(
+        default = "default_gap_limit",
+        skip_serializing_if = "is_default_gap_limit"
+    )]
+    pub gap_limit: u32,
     /// Settings for the Bitcoin interface
     pub bitcoin_config: BitcoinConfig,
+    /// Settings for the JSONRPC server over HTTP. It is not started if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_rpc_config: Option<HttpRpcConfig>,
+    /// Settings for signing spends with the hot signers stored in the data directory. We never
+    /// sign if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signing_config: Option<SigningConfig>,
+    /// Rules the spends must follow to be created and broadcast, on top of the sanity checks
+    /// performed on every spend.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spending_policy: Option<SpendingPolicy>,
+    /// Commands to run upon the events affecting the wallet.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notify_hooks: Vec<NotifyHook>,
     /// Settings specific to the Bitcoin backend.
     #[serde(flatten)]
     pub bitcoin_backend: Option<BitcoinBackend>,
@@ -190,6 +344,11 @@ impl Config {
         Self {
             bitcoin_config,
             bitcoin_backend,
+            gap_limit: default_gap_limit(),
+            http_rpc_config: None,
+            signing_config: None,
+            spending_policy: None,
+            notify_hooks: Vec::new(),
             log_level,
             main_descriptor,
             data_directory: Some(data_directory.path().to_path_buf()),
@@ -197,6 +356,30 @@ impl Config {
         }
     }
 
+    /// The configuration of a wallet hosted along the main one. It uses the same Bitcoin backend,
+    /// gap limit and spending policy but its own descriptor and data directory. The JSONRPC server,
+    /// the hot signers and the notification hooks are the daemon's, they are not set for the
+    /// wallet.
+    pub fn wallet_config(
+        &self,
+        main_descriptor: LianaDescriptor,
+        data_directory: &DataDirectory,
+    ) -> Config {
+        Config {
+            bitcoin_config: self.bitcoin_config.clone(),
+            bitcoin_backend: self.bitcoin_backend.clone(),
+            gap_limit: self.gap_limit,
+            http_rpc_config: None,
+            signing_config: None,
+            spending_policy: self.spending_policy.clone(),
+            notify_hooks: Vec::new(),
+            log_level: self.log_level,
+            main_descriptor,
+            data_directory: Some(data_directory.path().to_path_buf()),
+            data_dir: None,
+        }
+    }
+
     pub fn data_directory(&self) -> Option<DataDirectory> {
         if self.data_directory.is_some() {
             self.data_directory.clone().map(DataDirectory::new)
@@ -302,6 +485,14 @@ impl Config {
 
     /// Make sure the settings are sane.
     pub fn check(&self) -> Result<(), ConfigError> {
+        // Too large a gap limit would have us derive and watch too many addresses.
+        if !(1..=MAX_GAP_LIMIT).contains(&self.gap_limit) {
+            return Err(ConfigError::Unexpected(format!(
+                "Invalid gap limit: {}. Must be between 1 and {}.",
+                self.gap_limit, MAX_GAP_LIMIT
+            )));
+        }
+
         // Check the network of the xpubs in the descriptors
         let expected_network = match self.bitcoin_config.network {
             Network::Bitcoin => Network::Bitcoin,
@@ -314,6 +505,36 @@ impl Config {
             )));
         }
 
+        // The destinations allowed by the signing policy must be for our network.
+        if let Some(signing_config) = &self.signing_config {
+            signing_config
+                .policy
+                .check_network(self.bitcoin_config.network)
+                .map_err(ConfigError::Unexpected)?;
+        }
+        if let Some(spending_policy) = &self.spending_policy {
+            spending_policy
+                .check(self.bitcoin_config.network)
+                .map_err(ConfigError::Unexpected)?;
+        }
+
+        // The Original PSBT of a payjoin is checked using bitcoind's mempool.
+        let receives_payjoins = self
+            .http_rpc_config
+            .as_ref()
+            .map(|http_config| http_config.payjoin)
+            .unwrap_or(false);
+        if receives_payjoins
+            && !matches!(
+                self.bitcoin_backend,
+                None | Some(BitcoinBackend::Bitcoind(..))
+            )
+        {
+            return Err(ConfigError::Unexpected(
+                "Receiving payjoins requires the bitcoind backend.".to_string(),
+            ));
+        }
+
         // TODO: check the semantics of the main descriptor
 
         Ok(())
@@ -325,6 +546,7 @@ mod tests {
     use std::path::PathBuf;
 
     use super::*;
+    use crate::{notifications::EventKind, signing::SpendingPath, spending_policy::WindowLimit};
 
     // Test the format of the configuration file
     #[test]
@@ -365,6 +587,45 @@ mod tests {
             assert_eq!(toml_str, serialized);
         }
 
+        // A valid, round-tripping, config with a custom gap limit.
+        {
+            let toml_str = r#"
+            data_dir = '/home/wizardsardine/custom/folder/'
+            log_level = 'TRACE'
+            main_descriptor = 'wsh(andor(pk([aabbccdd]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),older(10000),pk([aabbccdd]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))#dw4ulnrs'
+            gap_limit = 1000
+
+            [bitcoin_config]
+            network = 'bitcoin'
+            poll_interval_secs = 18
+
+            [bitcoind_config]
+            cookie_path = '/home/user/.bitcoin/.cookie'
+            addr = '127.0.0.1:8332'
+            "#.trim_start().replace("            ", "");
+            let parsed = toml::from_str::<Config>(&toml_str).expect("Deserializing toml_str");
+            assert_eq!(parsed.gap_limit, 1000);
+            let serialized = toml::to_string_pretty(&parsed).expect("Serializing to toml");
+            assert_eq!(toml_str, serialized);
+
+            // The gap limit must be within bounds.
+            for gap_limit in ["0", "100001"] {
+                let toml_str =
+                    toml_str.replace("gap_limit = 1000", &format!("gap_limit = {}", gap_limit));
+                let parsed = toml::from_str::<Config>(&toml_str).expect("Deserializing toml_str");
+                assert!(parsed
+                    .check()
+                    .unwrap_err()
+                    .to_string()
+                    .contains("Invalid gap limit"));
+            }
+
+            // The gap limit defaults to 200.
+            let toml_str = toml_str.replace("gap_limit = 1000\n", "");
+            let parsed = toml::from_str::<Config>(&toml_str).expect("Deserializing toml_str");
+            assert_eq!(parsed.gap_limit, 200);
+        }
+
         // A valid, round-tripping, config for a Taproot descriptor.
         {
             let toml_str = r#"
@@ -405,6 +666,222 @@ mod tests {
             assert_eq!(toml_str, serialized);
         }
 
+        // A valid, round-tripping, config with a JSONRPC server over HTTP
+        {
+            let toml_str = r#"
+            data_dir = '/home/wizardsardine/custom/folder/'
+            log_level = 'TRACE'
+            main_descriptor = 'wsh(andor(pk([aabbccdd]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),older(10000),pk([aabbccdd]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))#dw4ulnrs'
+
+            [bitcoin_config]
+            network = 'bitcoin'
+            poll_interval_secs = 18
+
+            [http_rpc_config]
+            addr = '127.0.0.1:9332'
+            payjoin = true
+
+            [[http_rpc_config.users]]
+            auth = 'admin:pass:word'
+
+            [[http_rpc_config.users]]
+            auth = 'reader:password'
+            allowed_methods = [
+                'getinfo',
+                'listcoins',
+            ]
+
+            [bitcoind_config]
+            cookie_path = '/home/user/.bitcoin/.cookie'
+            addr = '127.0.0.1:8332'
+            "#.trim_start().replace("            ", "");
+            let parsed = toml::from_str::<Config>(&toml_str).expect("Deserializing toml_str");
+            let http_config = parsed.http_rpc_config.clone().unwrap();
+            assert_eq!(http_config.users[0].user, "admin");
+            assert_eq!(http_config.users[0].password, "pass:word");
+            assert!(http_config.users[0].is_allowed("broadcastspend"));
+            assert_eq!(http_config.users[1].user, "reader");
+            assert!(http_config.users[1].is_allowed("listcoins"));
+            assert!(!http_config.users[1].is_allowed("broadcastspend"));
+            assert!(http_config.payjoin);
+            let serialized = toml::to_string_pretty(&parsed).expect("Serializing to toml");
+            assert_eq!(toml_str, serialized);
+        }
+
+        // Notification commands
+        {
+            let toml_str = r#"
+            main_descriptor = "wsh(andor(pk([aabbccdd]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),older(10000),pk([aabbccdd]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))#dw4ulnrs"
+
+            [bitcoin_config]
+            network = "bitcoin"
+
+            [bitcoind_config]
+            cookie_path = "/home/user/.bitcoin/.cookie"
+            addr = "127.0.0.1:8332"
+
+            [[notify_hooks]]
+            command = "echo %e %s >> /tmp/events.log"
+
+            [[notify_hooks]]
+            command = "notify-send %e"
+            events = ["coin_received", "reorg"]
+            "#;
+            let parsed = toml::from_str::<Config>(toml_str).expect("Deserializing toml_str");
+            assert_eq!(
+                parsed.notify_hooks,
+                vec![
+                    NotifyHook {
+                        command: "echo %e %s >> /tmp/events.log".to_string(),
+                        events: None,
+                    },
+                    NotifyHook {
+                        command: "notify-send %e".to_string(),
+                        events: Some(vec![EventKind::CoinReceived, EventKind::Reorg]),
+                    },
+                ]
+            );
+
+            // An unknown kind of event
+            let toml_str = toml_str.replace("\"reorg\"", "\"new_block\"");
+            toml::from_str::<Config>(&toml_str).expect_err("Deserializing an invalid toml_str");
+        }
+
+        // Signing with the hot signers
+        {
+            let toml_str = r#"
+            main_descriptor = "wsh(andor(pk([aabbccdd]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),older(10000),pk([aabbccdd]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))#dw4ulnrs"
+
+            [bitcoin_config]
+            network = "bitcoin"
+
+            [bitcoind_config]
+            cookie_path = "/home/user/.bitcoin/.cookie"
+            addr = "127.0.0.1:8332"
+
+            [signing_config]
+            password = "hunter2"
+
+            [signing_config.policy]
+            max_tx_amount_sat = 1000000
+            max_daily_amount_sat = 5000000
+            allowed_destinations = ["bc1qvklensptw5lk7d470ds60pcpsr0psdpgyvwepv"]
+            allowed_paths = ["primary", "recovery"]
+            "#;
+            let parsed = toml::from_str::<Config>(toml_str).expect("Deserializing toml_str");
+            let signing_config = parsed.signing_config.clone().unwrap();
+            assert_eq!(signing_config.password.as_deref(), Some("hunter2"));
+            assert_eq!(signing_config.policy.max_tx_amount_sat, Some(1_000_000));
+            assert_eq!(signing_config.policy.max_daily_amount_sat, Some(5_000_000));
+            assert_eq!(
+                signing_config.policy.allowed_paths,
+                vec![SpendingPath::Primary, SpendingPath::Recovery]
+            );
+            assert!(!format!("{:?}", signing_config).contains("hunter2"));
+            let serialized = toml::to_string_pretty(&parsed).expect("Serializing to toml");
+            let reparsed = toml::from_str::<Config>(&serialized).expect("Deserializing toml_str");
+            assert_eq!(reparsed.signing_config, Some(signing_config));
+
+            // Only the primary path is allowed by default.
+            let toml_str = toml_str
+                .replace("allowed_paths = [\"primary\", \"recovery\"]", "")
+                .replace("password = \"hunter2\"", "");
+            let parsed = toml::from_str::<Config>(&toml_str).expect("Deserializing toml_str");
+            let signing_config = parsed.signing_config.unwrap();
+            assert_eq!(signing_config.password, None);
+            assert_eq!(
+                signing_config.policy.allowed_paths,
+                vec![SpendingPath::Primary]
+            );
+
+            // The allowed destinations must be for our network.
+            let toml_str = toml_str.replace("network = \"bitcoin\"", "network = \"testnet\"");
+            let parsed = toml::from_str::<Config>(&toml_str).expect("Deserializing toml_str");
+            assert!(parsed
+                .check()
+                .unwrap_err()
+                .to_string()
+                .contains("is not an address for network testnet"));
+
+            // An unknown spending path
+            let toml_str = toml_str.replace(
+                "max_tx_amount_sat = 1000000",
+                "allowed_paths = [\"primary\", \"emergency\"]",
+            );
+            toml::from_str::<Config>(&toml_str).expect_err("Deserializing an invalid toml_str");
+        }
+
+        // Spending policy
+        {
+            let toml_str = r#"
+            main_descriptor = "wsh(andor(pk([aabbccdd]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),older(10000),pk([aabbccdd]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))#dw4ulnrs"
+
+            [bitcoin_config]
+            network = "bitcoin"
+
+            [bitcoind_config]
+            cookie_path = "/home/user/.bitcoin/.cookie"
+            addr = "127.0.0.1:8332"
+
+            [spending_policy]
+            max_tx_amount_sat = 1000000
+            denied_destinations = ["bc1qvklensptw5lk7d470ds60pcpsr0psdpgyvwepv"]
+            require_labels = true
+            min_confirmations = 6
+            broadcast_delay_hours = 24
+
+            [[spending_policy.window_limits]]
+            max_amount_sat = 5000000
+            window_hours = 24
+            "#;
+            let parsed = toml::from_str::<Config>(toml_str).expect("Deserializing toml_str");
+            let spending_policy = parsed.spending_policy.clone().unwrap();
+            assert_eq!(spending_policy.max_tx_amount_sat, Some(1_000_000));
+            assert_eq!(spending_policy.allowed_destinations, None);
+            assert_eq!(spending_policy.denied_destinations.len(), 1);
+            assert!(spending_policy.require_labels);
+            assert_eq!(spending_policy.min_confirmations, Some(6));
+            assert_eq!(spending_policy.broadcast_delay_hours, Some(24));
+            assert_eq!(
+                spending_policy.window_limits,
+                vec![WindowLimit {
+                    max_amount_sat: 5_000_000,
+                    window_hours: 24
+                }]
+            );
+            let serialized = toml::to_string_pretty(&parsed).expect("Serializing to toml");
+            let reparsed = toml::from_str::<Config>(&serialized).expect("Deserializing toml_str");
+            assert_eq!(reparsed.spending_policy, Some(spending_policy));
+
+            // The destinations must be for our network.
+            let toml_str = toml_str.replace("network = \"bitcoin\"", "network = \"testnet\"");
+            let parsed = toml::from_str::<Config>(&toml_str).expect("Deserializing toml_str");
+            assert!(parsed
+                .check()
+                .unwrap_err()
+                .to_string()
+                .contains("is not an address for network testnet"));
+        }
+
+        // Missing colon in the `auth` of a JSONRPC server user
+        let toml_str = r#"
+            main_descriptor = "wsh(andor(pk([aabbccdd]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),older(10000),pk([aabbccdd]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))#dw4ulnrs"
+
+            [bitcoin_config]
+            network = "bitcoin"
+
+            [http_rpc_config]
+            addr = "127.0.0.1:9332"
+
+            [[http_rpc_config.users]]
+            auth = "readerpassword"
+        "#;
+        let config_err =
+            toml::from_str::<Config>(toml_str).expect_err("Deserializing an invalid toml_str");
+        assert!(config_err
+            .to_string()
+            .contains("`auth` must be 'user:password'"));
+
         // Invalid desc checksum
         let toml_str = r#"
             log_level = "trace"
@@ -552,6 +1029,90 @@ mod tests {
         assert_eq!(parsed, expected,);
     }
 
+    // Test the format of the `esplora_config` section
+    #[test]
+    fn toml_esplora_config() {
+        let toml_str = r#"
+            addr = 'http://127.0.0.1:3002'
+            "#
+        .trim_start()
+        .replace("            ", "");
+        let parsed = toml::from_str::<EsploraConfig>(&toml_str).expect("Deserializing toml_str");
+        let serialized = toml::to_string_pretty(&parsed).expect("Serializing to toml");
+        assert_eq!(toml_str, serialized);
+        let expected = EsploraConfig {
+            addr: "http://127.0.0.1:3002".into(),
+        };
+        assert_eq!(parsed, expected);
+    }
+
+    // Test the format of the `cbf_config` section
+    #[test]
+    fn toml_cbf_config() {
+        let toml_str = r#"
+            peers = ['127.0.0.1:18444', 'node.example.com:8333']
+            "#
+        .trim_start()
+        .replace("            ", "");
+        let parsed = toml::from_str::<CbfConfig>(&toml_str).expect("Deserializing toml_str");
+        let expected = CbfConfig {
+            peers: vec!["127.0.0.1:18444".into(), "node.example.com:8333".into()],
+        };
+        assert_eq!(parsed, expected);
+
+        // At least the list of peers must be given.
+        toml::from_str::<CbfConfig>("").expect_err("Deserializing an invalid toml_str");
+    }
+
+    // Only the applicable parts of the configuration are used for an additional wallet.
+    #[test]
+    fn wallet_config() {
+        let toml_str = r#"
+            main_descriptor = "wsh(andor(pk([aabbccdd]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),older(10000),pk([aabbccdd]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))#dw4ulnrs"
+            gap_limit = 50
+
+            [bitcoin_config]
+            network = "bitcoin"
+
+            [bitcoind_config]
+            cookie_path = "/home/user/.bitcoin/.cookie"
+            addr = "127.0.0.1:8332"
+
+            [http_rpc_config]
+            addr = "127.0.0.1:9332"
+
+            [[http_rpc_config.users]]
+            auth = "admin:password"
+
+            [signing_config.policy]
+            max_tx_amount_sat = 1000000
+
+            [spending_policy]
+            max_tx_amount_sat = 1000000
+
+            [[notify_hooks]]
+            command = "notify-send %e"
+            "#;
+        let parsed = toml::from_str::<Config>(toml_str).expect("Deserializing toml_str");
+        let descriptor = LianaDescriptor::from_str("wsh(or_d(pk([aabbccdd]tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*),and_v(v:pkh([aabbccdd]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*),older(100))))").unwrap();
+        let data_dir = DataDirectory::new(PathBuf::from("/tmp/wallets/savings"));
+        let wallet_config = parsed.wallet_config(descriptor.clone(), &data_dir);
+        assert_eq!(wallet_config.main_descriptor, descriptor);
+        assert_eq!(
+            wallet_config.data_directory().unwrap().path(),
+            data_dir.path()
+        );
+        assert!(matches!(
+            wallet_config.bitcoin_backend,
+            Some(BitcoinBackend::Bitcoind(_))
+        ));
+        assert_eq!(wallet_config.gap_limit, 50);
+        assert_eq!(wallet_config.spending_policy, parsed.spending_policy);
+        assert!(wallet_config.http_rpc_config.is_none());
+        assert!(wallet_config.signing_config.is_none());
+        assert!(wallet_config.notify_hooks.is_empty());
+    }
+
     #[test]
     fn config_directory() {
         let filepath = config_file_path().expect("Getting config file path");
@@ -7,7 +7,7 @@ pub mod sqlite;
 use crate::{
     bitcoin::BlockChainTip,
     database::sqlite::{
-        schema::{DbBlockInfo, DbCoin, DbTip},
+        schema::{DbBlockInfo, DbCoin, DbTip, DbWatchonlyCoin},
         SqliteConn, SqliteDb,
     },
 };
@@ -21,7 +21,10 @@ use std::{
 };
 
 use bip329::Labels;
-use miniscript::bitcoin::{self, bip32, psbt::Psbt, secp256k1, Address, Network, OutPoint, Txid};
+use miniscript::{
+    bitcoin::{self, bip32, psbt::Psbt, secp256k1, Address, Network, OutPoint, Txid},
+    descriptor::{Descriptor, DescriptorPublicKey},
+};
 
 /// Information about the wallet.
 ///
@@ -144,6 +147,12 @@ pub trait DatabaseConnection {
     /// Mark a set of coins as not being spent anymore.
     fn unspend_coins(&mut self, outpoints: &[bitcoin::OutPoint]);
 
+    /// Mark a set of coins as frozen, so they don't get selected automatically for spending.
+    fn freeze_coins(&mut self, outpoints: &[bitcoin::OutPoint]);
+
+    /// Mark a set of coins as not being frozen anymore.
+    fn unfreeze_coins(&mut self, outpoints: &[bitcoin::OutPoint]);
+
     /// Mark a set of coins as spent by a specified txid at a specified block time.
     fn confirm_spend(&mut self, outpoints: &[(bitcoin::OutPoint, bitcoin::Txid, i32, u32)]);
 
@@ -194,6 +203,52 @@ pub trait DatabaseConnection {
 
     /// Dump all labels
     fn get_labels_bip329(&mut self, offset: u32, limit: u32) -> Labels;
+
+    /// Get the additional watch-only descriptors, along with the timestamp from which we looked
+    /// for their coins.
+    fn watchonly_descriptors(&mut self) -> Vec<(Descriptor<DescriptorPublicKey>, u32)>;
+
+    /// Store a new watch-only descriptor. It must not already be in database.
+    fn new_watchonly_descriptor(
+        &mut self,
+        descriptor: &Descriptor<DescriptorPublicKey>,
+        timestamp: u32,
+    );
+
+    /// Get the unspent coins of the watch-only descriptors, as of the last poll.
+    fn watchonly_coins(&mut self) -> Vec<WatchonlyCoin>;
+
+    /// Replace the unspent coins of the watch-only descriptors.
+    fn set_watchonly_coins(&mut self, coins: &[WatchonlyCoin]);
+
+    /// Get the spends signed by our hot signers since the given timestamp, along with the value
+    /// they send out of the wallet.
+    fn signed_spends_since(&mut self, timestamp: u32) -> Vec<(bitcoin::Txid, bitcoin::Amount)>;
+
+    /// Record a spend signed by our hot signers. A spend which was already recorded keeps the
+    /// highest value it was signed for and the date at which it was last signed.
+    fn record_signed_spend(
+        &mut self,
+        txid: &bitcoin::Txid,
+        amount: bitcoin::Amount,
+        timestamp: u32,
+    );
+
+    /// Get the spends broadcast since the given timestamp while a spending policy was set, along
+    /// with the value they send out of the wallet and the date at which they were broadcast.
+    fn broadcast_spends_since(
+        &mut self,
+        timestamp: u32,
+    ) -> Vec<(bitcoin::Txid, bitcoin::Amount, u32)>;
+
+    /// Record a spend broadcast while a spending policy was set. A spend which was already
+    /// recorded is left as is.
+    fn record_broadcast_spend(
+        &mut self,
+        txid: &bitcoin::Txid,
+        amount: bitcoin::Amount,
+        timestamp: u32,
+    );
 }
 
 impl DatabaseConnection for SqliteConn {
@@ -314,6 +369,14 @@ impl DatabaseConnection for SqliteConn {
         self.unspend_coins(outpoints)
     }
 
+    fn freeze_coins(&mut self, outpoints: &[bitcoin::OutPoint]) {
+        self.set_coins_frozen(outpoints, true)
+    }
+
+    fn unfreeze_coins(&mut self, outpoints: &[bitcoin::OutPoint]) {
+        self.set_coins_frozen(outpoints, false)
+    }
+
     fn confirm_spend<'a>(&mut self, outpoints: &[(bitcoin::OutPoint, bitcoin::Txid, i32, u32)]) {
         self.confirm_spend(outpoints)
     }
@@ -416,6 +479,61 @@ impl DatabaseConnection for SqliteConn {
             })
             .collect()
     }
+
+    fn watchonly_descriptors(&mut self) -> Vec<(Descriptor<DescriptorPublicKey>, u32)> {
+        self.db_watchonly_descriptors()
+            .into_iter()
+            .map(|db_desc| (db_desc.descriptor, db_desc.timestamp))
+            .collect()
+    }
+
+    fn new_watchonly_descriptor(
+        &mut self,
+        descriptor: &Descriptor<DescriptorPublicKey>,
+        timestamp: u32,
+    ) {
+        self.new_watchonly_descriptor(descriptor, timestamp)
+    }
+
+    fn watchonly_coins(&mut self) -> Vec<WatchonlyCoin> {
+        self.db_watchonly_coins()
+            .into_iter()
+            .map(WatchonlyCoin::from)
+            .collect()
+    }
+
+    fn set_watchonly_coins(&mut self, coins: &[WatchonlyCoin]) {
+        self.set_watchonly_coins(coins)
+    }
+
+    fn signed_spends_since(&mut self, timestamp: u32) -> Vec<(bitcoin::Txid, bitcoin::Amount)> {
+        self.signed_spends_since(timestamp)
+    }
+
+    fn record_signed_spend(
+        &mut self,
+        txid: &bitcoin::Txid,
+        amount: bitcoin::Amount,
+        timestamp: u32,
+    ) {
+        self.record_signed_spend(txid, amount, timestamp)
+    }
+
+    fn broadcast_spends_since(
+        &mut self,
+        timestamp: u32,
+    ) -> Vec<(bitcoin::Txid, bitcoin::Amount, u32)> {
+        self.broadcast_spends_since(timestamp)
+    }
+
+    fn record_broadcast_spend(
+        &mut self,
+        txid: &bitcoin::Txid,
+        amount: bitcoin::Amount,
+        timestamp: u32,
+    ) {
+        self.record_broadcast_spend(txid, amount, timestamp)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -444,6 +562,7 @@ pub struct Coin {
     pub spend_txid: Option<bitcoin::Txid>,
     pub spend_block: Option<BlockInfo>,
     pub is_from_self: bool,
+    pub is_frozen: bool,
 }
 
 impl std::convert::From<DbCoin> for Coin {
@@ -458,6 +577,7 @@ impl std::convert::From<DbCoin> for Coin {
             spend_txid,
             spend_block,
             is_from_self,
+            is_frozen,
             ..
         } = db_coin;
         Coin {
@@ -470,6 +590,7 @@ impl std::convert::From<DbCoin> for Coin {
             spend_txid,
             spend_block: spend_block.map(BlockInfo::from),
             is_from_self,
+            is_frozen,
         }
     }
 }
@@ -484,6 +605,47 @@ impl Coin {
     }
 }
 
+/// An unspent coin of one of the additional watch-only descriptors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchonlyCoin {
+    /// The watch-only descriptor this coin pays to.
+    pub descriptor: Descriptor<DescriptorPublicKey>,
+    pub outpoint: bitcoin::OutPoint,
+    pub amount: bitcoin::Amount,
+    pub address: bitcoin::Address,
+    pub derivation_index: bip32::ChildNumber,
+    /// Height of the block the coin was confirmed in, if any.
+    pub block_height: Option<i32>,
+}
+
+impl std::convert::From<DbWatchonlyCoin> for WatchonlyCoin {
+    fn from(db_coin: DbWatchonlyCoin) -> WatchonlyCoin {
+        let DbWatchonlyCoin {
+            descriptor,
+            outpoint,
+            amount,
+            address,
+            derivation_index,
+            block_height,
+            ..
+        } = db_coin;
+        WatchonlyCoin {
+            descriptor,
+            outpoint,
+            amount,
+            address: address.assume_checked(),
+            derivation_index,
+            block_height,
+        }
+    }
+}
+
+impl WatchonlyCoin {
+    pub fn is_confirmed(&self) -> bool {
+        self.block_height.is_some()
+    }
+}
+
 /// Possible (mutually exclusive) status of a coin.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum CoinStatus {
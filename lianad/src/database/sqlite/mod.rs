@@ -16,14 +16,14 @@ use crate::{
         sqlite::{
             schema::{
                 DbAddress, DbCoin, DbLabel, DbLabelledKind, DbSpendTransaction, DbTip, DbWallet,
-                DbWalletTransaction, SCHEMA,
+                DbWalletTransaction, DbWatchonlyCoin, DbWatchonlyDescriptor, SCHEMA,
             },
             utils::{
-                create_fresh_db, curr_timestamp, db_exec, db_query, db_tx_query, db_version,
-                maybe_apply_migration, LOOK_AHEAD_LIMIT,
+                create_fresh_db, curr_timestamp, db_exec, db_query, db_query_row, db_tx_query,
+                db_version, insert_wallet, maybe_apply_migration, LOOK_AHEAD_LIMIT,
             },
         },
-        Coin, CoinStatus, LabelItem,
+        Coin, CoinStatus, LabelItem, WatchonlyCoin,
     },
 };
 use liana::descriptors::LianaDescriptor;
@@ -33,17 +33,21 @@ use std::{
     collections::{HashMap, HashSet},
     convert::TryInto,
     fmt, io, path,
+    str::FromStr,
 };
 
-use miniscript::bitcoin::{
-    self, bip32,
-    consensus::encode,
-    hashes::{sha256, Hash},
-    psbt::Psbt,
-    secp256k1,
+use miniscript::{
+    bitcoin::{
+        self, bip32,
+        consensus::encode,
+        hashes::{sha256, Hash},
+        psbt::Psbt,
+        secp256k1,
+    },
+    descriptor::{Descriptor, DescriptorPublicKey},
 };
 
-const DB_VERSION: i64 = 8;
+const DB_VERSION: i64 = 13;
 
 /// Last database version for which Bitcoin transactions were not stored in database. In practice
 /// this meant we relied on the bitcoind watchonly wallet to store them for us.
@@ -136,14 +140,21 @@ impl FreshDbOptions {
     }
 }
 
+// The main wallet is the one the database was created with. The id of its row is always 1.
+const MAIN_WALLET_ID: i64 = 1;
+
+/// The interface to a wallet stored in an SQLite database. A database stores a main wallet and
+/// possibly other wallets, each identified by a name.
 #[derive(Debug, Clone)]
 pub struct SqliteDb {
     db_path: path::PathBuf,
+    gap_limit: u32,
+    wallet_id: i64,
 }
 
 impl SqliteDb {
     /// Instantiate an SQLite database either from an existing database file or by creating a fresh
-    /// one.
+    /// one. The returned interface is to the main wallet of the database.
     /// NOTE: don't forget to apply any migration with `maybe_apply_migration` if necessary.
     pub fn new(
         db_path: path::PathBuf,
@@ -161,7 +172,27 @@ impl SqliteDb {
 
         log::info!("Checking if the database needs upgrading.");
 
-        Ok(SqliteDb { db_path })
+        Ok(SqliteDb {
+            db_path,
+            gap_limit: LOOK_AHEAD_LIMIT,
+            wallet_id: MAIN_WALLET_ID,
+        })
+    }
+
+    // The interface to another wallet stored in the same database.
+    fn for_wallet(&self, wallet_id: i64) -> SqliteDb {
+        SqliteDb {
+            db_path: self.db_path.clone(),
+            gap_limit: self.gap_limit,
+            wallet_id,
+        }
+    }
+
+    /// Set the number of addresses past the last used derivation index to be recorded in the
+    /// address->deriv_index mapping. Defaults to 200.
+    pub fn with_gap_limit(mut self, gap_limit: u32) -> SqliteDb {
+        self.gap_limit = gap_limit;
+        self
     }
 
     /// If the database version is older than expected, migrate it to the current version. If
@@ -178,7 +209,99 @@ impl SqliteDb {
     pub fn connection(&self) -> Result<SqliteConn, SqliteDbError> {
         let conn = rusqlite::Connection::open(&self.db_path)?;
         conn.busy_timeout(std::time::Duration::from_secs(60))?;
-        Ok(SqliteConn { conn })
+        Ok(SqliteConn {
+            conn,
+            gap_limit: self.gap_limit,
+            wallet_id: self.wallet_id,
+        })
+    }
+
+    /// Create a new wallet with this name and descriptor in the database, for the same network as
+    /// the main wallet. Returns the interface to the new wallet.
+    pub fn create_wallet(
+        &self,
+        name: &str,
+        main_descriptor: &LianaDescriptor,
+        secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+    ) -> Result<SqliteDb, SqliteDbError> {
+        let mut conn = self.for_wallet(MAIN_WALLET_ID).connection()?;
+        let network = conn.db_tip().network;
+        let mut wallet_id = MAIN_WALLET_ID;
+        db_exec(&mut conn.conn, |db_tx| {
+            wallet_id = insert_wallet(db_tx, Some(name), network, main_descriptor, secp)?;
+            Ok(())
+        })?;
+        log::info!("Created wallet '{}' in database.", name);
+
+        Ok(self.for_wallet(wallet_id))
+    }
+
+    /// Copy the main wallet of the database at the given path into this database, under the given
+    /// name. The other database must be up to date and for the same network. Returns the interface
+    /// to the new wallet.
+    pub fn import_wallet(
+        &self,
+        name: &str,
+        db_path: &path::Path,
+    ) -> Result<SqliteDb, SqliteDbError> {
+        if !db_path.exists() {
+            return Err(SqliteDbError::FileNotFound(db_path.to_path_buf()));
+        }
+        let mut conn = self.for_wallet(MAIN_WALLET_ID).connection()?;
+        let network = conn.db_tip().network;
+        conn.conn.execute(
+            "ATTACH DATABASE ?1 AS other",
+            rusqlite::params![db_path.to_string_lossy()],
+        )?;
+        let res = import_wallet(&mut conn.conn, name, network);
+        conn.conn
+            .execute("DETACH DATABASE other", rusqlite::params![])?;
+        let wallet_id = res?;
+        log::info!(
+            "Imported wallet '{}' from database at '{}'.",
+            name,
+            db_path.display()
+        );
+
+        Ok(self.for_wallet(wallet_id))
+    }
+
+    /// Get the interface to the wallet with this name, if it is stored in the database.
+    pub fn wallet(&self, name: &str) -> Result<Option<SqliteDb>, SqliteDbError> {
+        let mut conn = self.connection()?;
+        let wallet_id: Option<i64> = db_query(
+            &mut conn.conn,
+            "SELECT id FROM wallets WHERE name = ?1",
+            rusqlite::params![name],
+            |row| row.get(0),
+        )?
+        .pop();
+        Ok(wallet_id.map(|id| self.for_wallet(id)))
+    }
+
+    /// Get the names of the wallets stored in the database besides the main one.
+    pub fn wallet_names(&self) -> Result<Vec<String>, SqliteDbError> {
+        let mut conn = self.connection()?;
+        Ok(db_query(
+            &mut conn.conn,
+            "SELECT name FROM wallets WHERE name IS NOT NULL ORDER BY id",
+            rusqlite::params![],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Get the main descriptor of the wallet stored in this database. Contrary to `db_wallet`,
+    /// this may be used before applying the migrations.
+    pub fn main_descriptor(&self) -> Result<LianaDescriptor, SqliteDbError> {
+        let mut conn = self.connection()?;
+        let desc_str: String = db_query_row(
+            &mut conn.conn,
+            "SELECT main_descriptor FROM wallets WHERE id = ?1",
+            rusqlite::params![self.wallet_id],
+            |row| row.get(0),
+        )?;
+        Ok(LianaDescriptor::from_str(&desc_str)
+            .expect("Insane database: can't parse deposit descriptor"))
     }
 
     /// Perform startup sanity checks.
@@ -213,11 +336,156 @@ impl SqliteDb {
     }
 }
 
-// We only support single wallet. The id of the wallet row is always 1.
-const WALLET_ID: i64 = 1;
+// Copy the main wallet of the database attached as "other" under the given name. Returns the id of
+// the new wallet.
+fn import_wallet(
+    conn: &mut rusqlite::Connection,
+    name: &str,
+    network: bitcoin::Network,
+) -> Result<i64, SqliteDbError> {
+    let version: i64 = db_query_row(
+        conn,
+        "SELECT version FROM other.version",
+        rusqlite::params![],
+        |row| row.get(0),
+    )?;
+    if version != DB_VERSION {
+        return Err(SqliteDbError::UnsupportedVersion(version));
+    }
+    let other_network: String = db_query_row(
+        conn,
+        "SELECT network FROM other.tip WHERE wallet_id = ?1",
+        rusqlite::params![MAIN_WALLET_ID],
+        |row| row.get(0),
+    )?;
+    let other_network = bitcoin::Network::from_str(&other_network)
+        .expect("Insane database: can't parse network string");
+    if other_network != network {
+        return Err(SqliteDbError::InvalidNetwork(other_network));
+    }
+
+    let mut wallet_id = MAIN_WALLET_ID;
+    db_exec(conn, |db_tx| {
+        db_tx.execute(
+            "INSERT INTO wallets (timestamp, main_descriptor, deposit_derivation_index, \
+                 change_derivation_index, rescan_timestamp, last_poll_timestamp, name) \
+             SELECT timestamp, main_descriptor, deposit_derivation_index, \
+                 change_derivation_index, rescan_timestamp, last_poll_timestamp, ?1 \
+             FROM other.wallets WHERE id = ?2",
+            rusqlite::params![name, MAIN_WALLET_ID],
+        )?;
+        wallet_id = db_tx.last_insert_rowid();
+        let params = rusqlite::params![wallet_id, MAIN_WALLET_ID];
+
+        db_tx.execute(
+            "INSERT INTO tip (wallet_id, network, blockheight, blockhash) \
+             SELECT ?1, network, blockheight, blockhash FROM other.tip WHERE wallet_id = ?2",
+            params,
+        )?;
+        db_tx.execute(
+            "INSERT INTO addresses (wallet_id, receive_address, change_address, derivation_index) \
+             SELECT ?1, receive_address, change_address, derivation_index \
+             FROM other.addresses WHERE wallet_id = ?2",
+            params,
+        )?;
+        // The transactions are shared by all the wallets.
+        db_tx.execute(
+            "INSERT INTO transactions (txid, tx, num_inputs, num_outputs, is_coinbase) \
+             SELECT txid, tx, num_inputs, num_outputs, is_coinbase FROM other.transactions \
+             WHERE true ON CONFLICT DO NOTHING",
+            rusqlite::params![],
+        )?;
+        db_tx.execute(
+            "INSERT INTO coins (wallet_id, blockheight, blocktime, txid, vout, amount_sat, \
+                 derivation_index, is_change, spend_txid, spend_block_height, spend_block_time, \
+                 is_immature, is_from_self, is_frozen) \
+             SELECT ?1, blockheight, blocktime, txid, vout, amount_sat, derivation_index, \
+                 is_change, spend_txid, spend_block_height, spend_block_time, is_immature, \
+                 is_from_self, is_frozen \
+             FROM other.coins WHERE wallet_id = ?2",
+            params,
+        )?;
+        db_tx.execute(
+            "INSERT INTO spend_transactions (wallet_id, psbt, txid, updated_at) \
+             SELECT ?1, psbt, txid, updated_at FROM other.spend_transactions WHERE wallet_id = ?2",
+            params,
+        )?;
+        db_tx.execute(
+            "INSERT INTO labels (wallet_id, item_kind, item, value) \
+             SELECT ?1, item_kind, item, value FROM other.labels WHERE wallet_id = ?2",
+            params,
+        )?;
+        db_tx.execute(
+            "INSERT INTO watchonly_descriptors (wallet_id, descriptor, timestamp) \
+             SELECT ?1, descriptor, timestamp FROM other.watchonly_descriptors WHERE wallet_id = ?2",
+            params,
+        )?;
+        db_tx.execute(
+            "INSERT INTO watchonly_coins (descriptor_id, txid, vout, amount_sat, address, \
+                 derivation_index, blockheight) \
+             SELECT d.id, c.txid, c.vout, c.amount_sat, c.address, c.derivation_index, \
+                 c.blockheight \
+             FROM other.watchonly_coins AS c \
+             INNER JOIN other.watchonly_descriptors AS od ON c.descriptor_id = od.id \
+             INNER JOIN watchonly_descriptors AS d \
+                 ON d.descriptor = od.descriptor AND d.wallet_id = ?1 \
+             WHERE od.wallet_id = ?2",
+            params,
+        )?;
+        db_tx.execute(
+            "INSERT INTO signed_spends (wallet_id, txid, amount_sat, signed_at) \
+             SELECT ?1, txid, amount_sat, signed_at FROM other.signed_spends WHERE wallet_id = ?2",
+            params,
+        )?;
+        db_tx.execute(
+            "INSERT INTO broadcast_spends (wallet_id, txid, amount_sat, broadcast_at) \
+             SELECT ?1, txid, amount_sat, broadcast_at FROM other.broadcast_spends \
+             WHERE wallet_id = ?2",
+            params,
+        )?;
+        Ok(())
+    })?;
+
+    Ok(wallet_id)
+}
 
 pub struct SqliteConn {
     conn: rusqlite::Connection,
+    gap_limit: u32,
+    // The wallet all the queries are made for.
+    wallet_id: i64,
+}
+
+// Populate the address->deriv_index mapping with the entries for all the derivation indexes up to
+// `gap_limit` past `highest_index` which are not in there yet.
+fn fill_addresses_mapping(
+    db_tx: &rusqlite::Transaction,
+    wallet_id: i64,
+    main_descriptor: &LianaDescriptor,
+    network: bitcoin::Network,
+    highest_index: u32,
+    gap_limit: u32,
+    secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+) -> rusqlite::Result<()> {
+    let last_mapped_index: Option<u32> = db_tx.query_row(
+        "SELECT MAX(derivation_index) FROM addresses WHERE wallet_id = ?1",
+        rusqlite::params![wallet_id],
+        |row| row.get(0),
+    )?;
+    let first_index = last_mapped_index.map(|i| i + 1).unwrap_or(0);
+
+    let receive_desc = main_descriptor.receive_descriptor();
+    let change_desc = main_descriptor.change_descriptor();
+    for index in first_index..highest_index.saturating_add(gap_limit) {
+        let receive_addr = receive_desc.derive(index.into(), secp).address(network);
+        let change_addr = change_desc.derive(index.into(), secp).address(network);
+        db_tx.execute(
+            "INSERT INTO addresses (wallet_id, receive_address, change_address, derivation_index) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![wallet_id, receive_addr.to_string(), change_addr.to_string(), index],
+        )?;
+    }
+
+    Ok(())
 }
 
 impl SqliteConn {
@@ -229,8 +497,8 @@ impl SqliteConn {
     pub fn db_tip(&mut self) -> DbTip {
         db_query(
             &mut self.conn,
-            "SELECT * FROM tip",
-            rusqlite::params![],
+            "SELECT * FROM tip WHERE wallet_id = ?1",
+            rusqlite::params![self.wallet_id],
             |row| row.try_into(),
         )
         .expect("Db must not fail")
@@ -242,8 +510,8 @@ impl SqliteConn {
     pub fn db_wallet(&mut self) -> DbWallet {
         db_query(
             &mut self.conn,
-            "SELECT * FROM wallets",
-            rusqlite::params![],
+            "SELECT * FROM wallets WHERE id = ?1",
+            rusqlite::params![self.wallet_id],
             |row| row.try_into(),
         )
         .expect("Db must not fail")
@@ -253,11 +521,12 @@ impl SqliteConn {
 
     /// Update the network tip.
     pub fn update_tip(&mut self, tip: &BlockChainTip) {
+        let wallet_id = self.wallet_id;
         db_exec(&mut self.conn, |db_tx| {
             db_tx
                 .execute(
-                    "UPDATE tip SET blockheight = (?1), blockhash = (?2)",
-                    rusqlite::params![tip.height, tip.hash[..].to_vec()],
+                    "UPDATE tip SET blockheight = (?1), blockhash = (?2) WHERE wallet_id = (?3)",
+                    rusqlite::params![tip.height, tip.hash[..].to_vec(), wallet_id],
                 )
                 .map(|_| ())
         })
@@ -275,19 +544,25 @@ impl SqliteConn {
         secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
     ) {
         let network = self.db_tip().network;
+        let gap_limit = self.gap_limit;
+        let wallet_id = self.wallet_id;
 
         db_exec(&mut self.conn, |db_tx| {
-            let db_wallet: DbWallet =
-                db_tx_query(db_tx, "SELECT * FROM wallets", rusqlite::params![], |row| {
-                    row.try_into()
-                })?
-                .pop()
-                .expect("There is always a row in the wallet table");
+            let db_wallet: DbWallet = db_tx_query(
+                db_tx,
+                "SELECT * FROM wallets WHERE id = ?1",
+                rusqlite::params![wallet_id],
+                |row| row.try_into(),
+            )?
+            .pop()
+            .expect("There is always a row in the wallet table");
 
             // Make sure we don't set a lower derivation index. This can happen since the
             // derivation is set outside the atomic transaction. So there may be a race between say
             // the Bitcoin poller thread and the JSONRPC commands thread.
-            if (change && index <= db_wallet.change_derivation_index) || (!change && index <= db_wallet.deposit_derivation_index) {
+            if (change && index <= db_wallet.change_derivation_index)
+                || (!change && index <= db_wallet.deposit_derivation_index)
+            {
                 // It was already set at a higher index.
                 return Ok(());
             }
@@ -296,37 +571,34 @@ impl SqliteConn {
             let index_u32: u32 = index.into();
             if change {
                 db_tx.execute(
-                    "UPDATE wallets SET change_derivation_index = (?1)",
-                    rusqlite::params![index_u32],
+                    "UPDATE wallets SET change_derivation_index = (?1) WHERE id = (?2)",
+                    rusqlite::params![index_u32, wallet_id],
                 )?;
             } else {
                 db_tx.execute(
-                    "UPDATE wallets SET deposit_derivation_index = (?1)",
-                    rusqlite::params![index_u32],
+                    "UPDATE wallets SET deposit_derivation_index = (?1) WHERE id = (?2)",
+                    rusqlite::params![index_u32, wallet_id],
                 )?;
             }
 
             // Now if this new index is higher than the highest of our current derivation indexes,
-            // populate the addresses mapping for derivation indexes between our previous "gap
-            // limit index" and the new one.
-            let curr_highest_index = cmp::max(
+            // populate the addresses mapping for derivation indexes up to the new "gap limit
+            // index".
+            let curr_highest_index: u32 = cmp::max(
                 db_wallet.deposit_derivation_index,
                 db_wallet.change_derivation_index,
-            ).into();
+            )
+            .into();
             if index_u32 > curr_highest_index {
-                let receive_desc = db_wallet.main_descriptor.receive_descriptor();
-                let change_desc = db_wallet.main_descriptor.change_descriptor();
-
-                for index in curr_highest_index + 1..=index_u32 {
-                    let la_index = index + LOOK_AHEAD_LIMIT - 1;
-                    let receive_addr = receive_desc.derive(la_index.into(), secp).address(network);
-                    let change_addr = change_desc.derive(la_index.into(), secp).address(network);
-                    db_tx.execute(
-                        "INSERT INTO addresses (receive_address, change_address, derivation_index) VALUES (?1, ?2, ?3)",
-                        rusqlite::params![receive_addr.to_string(), change_addr.to_string(), la_index],
-                    )?;
-                }
-
+                fill_addresses_mapping(
+                    db_tx,
+                    wallet_id,
+                    &db_wallet.main_descriptor,
+                    network,
+                    index_u32,
+                    gap_limit,
+                    secp,
+                )?;
             }
 
             Ok(())
@@ -334,13 +606,47 @@ impl SqliteConn {
         .expect("Database must be available")
     }
 
+    /// Make sure the address->deriv_index mapping contains the entries for all the derivation
+    /// indexes up to the gap limit past our highest derivation index. This is necessary if the gap
+    /// limit was increased since the mapping was populated.
+    pub fn fill_addresses_mapping(&mut self, secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>) {
+        let network = self.db_tip().network;
+        let gap_limit = self.gap_limit;
+        let wallet_id = self.wallet_id;
+
+        db_exec(&mut self.conn, |db_tx| {
+            let db_wallet: DbWallet = db_tx_query(
+                db_tx,
+                "SELECT * FROM wallets WHERE id = ?1",
+                rusqlite::params![wallet_id],
+                |row| row.try_into(),
+            )?
+            .pop()
+            .expect("There is always a row in the wallet table");
+            let highest_index = cmp::max(
+                db_wallet.deposit_derivation_index,
+                db_wallet.change_derivation_index,
+            );
+            fill_addresses_mapping(
+                db_tx,
+                wallet_id,
+                &db_wallet.main_descriptor,
+                network,
+                highest_index.into(),
+                gap_limit,
+                secp,
+            )
+        })
+        .expect("Database must be available")
+    }
+
     pub fn set_wallet_rescan_timestamp(&mut self, timestamp: u32) {
+        let wallet_id = self.wallet_id;
         db_exec(&mut self.conn, |db_tx| {
-            // NOTE: this will need to be updated if we ever implement multi-wallet support
             db_tx
                 .execute(
-                    "UPDATE wallets SET rescan_timestamp = (?1)",
-                    rusqlite::params![timestamp],
+                    "UPDATE wallets SET rescan_timestamp = (?1) WHERE id = (?2)",
+                    rusqlite::params![timestamp, wallet_id],
                 )
                 .map(|_| ())
         })
@@ -359,12 +665,12 @@ impl SqliteConn {
             db_wallet.timestamp,
         );
 
+        let wallet_id = self.wallet_id;
         db_exec(&mut self.conn, |db_tx| {
-            // NOTE: this will need to be updated if we ever implement multi-wallet support
             db_tx
                 .execute(
-                    "UPDATE wallets SET timestamp = (?1), rescan_timestamp = NULL",
-                    rusqlite::params![new_timestamp],
+                    "UPDATE wallets SET timestamp = (?1), rescan_timestamp = NULL WHERE id = (?2)",
+                    rusqlite::params![new_timestamp, wallet_id],
                 )
                 .map(|_| ())
         })
@@ -374,11 +680,12 @@ impl SqliteConn {
     // Sqlite supports i64 integers so we use u32 for the timestamp.
     /// Set the last poll timestamp, where `timestamp` is seconds since UNIX epoch.
     pub fn set_wallet_last_poll_timestamp(&mut self, timestamp: u32) -> Result<(), SqliteDbError> {
+        let wallet_id = self.wallet_id;
         db_exec(&mut self.conn, |db_tx| {
             db_tx
                 .execute(
                     "UPDATE wallets SET last_poll_timestamp = (?1) WHERE id = (?2)",
-                    rusqlite::params![timestamp, WALLET_ID],
+                    rusqlite::params![timestamp, wallet_id],
                 )
                 .map(|_| ())
         })
@@ -432,16 +739,15 @@ impl SqliteConn {
         } else {
             String::new()
         };
-        let where_clause = if !status_condition.is_empty() && !op_condition.is_empty() {
-            format!(" WHERE ({}) AND ({})", status_condition, op_condition)
-        } else if status_condition.is_empty() && !op_condition.is_empty() {
-            format!(" WHERE {}", op_condition)
-        } else if !status_condition.is_empty() && op_condition.is_empty() {
-            format!(" WHERE {}", status_condition)
-        } else {
-            String::new()
-        };
-        let query = format!("SELECT * FROM coins{}", where_clause);
+        let mut query = format!("SELECT * FROM coins WHERE wallet_id = {}", self.wallet_id);
+        if !status_condition.is_empty() {
+            query += &format!(" AND ({})", status_condition);
+        }
+        if !op_condition.is_empty() {
+            query += &format!(" AND ({})", op_condition);
+        }
+        // Keep the insertion order, which the per-wallet unique index would otherwise change.
+        query += " ORDER BY id";
         db_query(&mut self.conn, &query, rusqlite::params![], |row| {
             row.try_into()
         })
@@ -457,6 +763,7 @@ impl SqliteConn {
     /// Store new, unconfirmed and unspent, coins.
     /// Will panic if given a coin that is already in DB.
     pub fn new_unspent_coins<'a>(&mut self, coins: impl IntoIterator<Item = &'a Coin>) {
+        let wallet_id = self.wallet_id;
         db_exec(&mut self.conn, |db_tx| {
             for coin in coins {
                 let deriv_index: u32 = coin.derivation_index.into();
@@ -464,7 +771,7 @@ impl SqliteConn {
                     "INSERT INTO coins (wallet_id, txid, vout, amount_sat, derivation_index, is_change, is_immature) \
                          VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
                     rusqlite::params![
-                        WALLET_ID,
+                        wallet_id,
                         coin.outpoint.txid[..].to_vec(),
                         coin.outpoint.vout,
                         coin.amount.to_sat(),
@@ -481,11 +788,12 @@ impl SqliteConn {
 
     /// Remove a set of coins from the database.
     pub fn remove_coins(&mut self, outpoints: &[bitcoin::OutPoint]) {
+        let wallet_id = self.wallet_id;
         db_exec(&mut self.conn, |db_tx| {
             for outpoint in outpoints {
                 db_tx.execute(
-                    "DELETE FROM coins WHERE txid = ?1 AND vout = ?2",
-                    rusqlite::params![outpoint.txid[..].to_vec(), outpoint.vout,],
+                    "DELETE FROM coins WHERE wallet_id = ?1 AND txid = ?2 AND vout = ?3",
+                    rusqlite::params![wallet_id, outpoint.txid[..].to_vec(), outpoint.vout,],
                 )?;
             }
 
@@ -502,11 +810,12 @@ impl SqliteConn {
         &mut self,
         outpoints: impl IntoIterator<Item = &'a (bitcoin::OutPoint, i32, u32)>,
     ) {
+        let wallet_id = self.wallet_id;
         db_exec(&mut self.conn, |db_tx| {
             for (outpoint, height, time) in outpoints {
                 db_tx.execute(
-                    "UPDATE coins SET blockheight = ?1, blocktime = ?2, is_immature = 0 WHERE txid = ?3 AND vout = ?4",
-                    rusqlite::params![height, time, outpoint.txid[..].to_vec(), outpoint.vout,],
+                    "UPDATE coins SET blockheight = ?1, blocktime = ?2, is_immature = 0 WHERE wallet_id = ?3 AND txid = ?4 AND vout = ?5",
+                    rusqlite::params![height, time, wallet_id, outpoint.txid[..].to_vec(), outpoint.vout,],
                 )?;
             }
 
@@ -520,12 +829,14 @@ impl SqliteConn {
         &mut self,
         outpoints: impl IntoIterator<Item = &'a (bitcoin::OutPoint, bitcoin::Txid)>,
     ) {
+        let wallet_id = self.wallet_id;
         db_exec(&mut self.conn, |db_tx| {
             for (outpoint, spend_txid) in outpoints {
                 db_tx.execute(
-                    "UPDATE coins SET spend_txid = ?1 WHERE txid = ?2 AND vout = ?3",
+                    "UPDATE coins SET spend_txid = ?1 WHERE wallet_id = ?2 AND txid = ?3 AND vout = ?4",
                     rusqlite::params![
                         spend_txid[..].to_vec(),
+                        wallet_id,
                         outpoint.txid[..].to_vec(),
                         outpoint.vout,
                     ],
@@ -542,11 +853,13 @@ impl SqliteConn {
         &mut self,
         outpoints: impl IntoIterator<Item = &'a bitcoin::OutPoint>,
     ) {
+        let wallet_id = self.wallet_id;
         db_exec(&mut self.conn, |db_tx| {
             for outpoint in outpoints {
                 db_tx.execute(
-                    "UPDATE coins SET spend_txid = NULL, spend_block_height = NULL, spend_block_time = NULL WHERE txid = ?1 AND vout = ?2",
+                    "UPDATE coins SET spend_txid = NULL, spend_block_height = NULL, spend_block_time = NULL WHERE wallet_id = ?1 AND txid = ?2 AND vout = ?3",
                     rusqlite::params![
+                        wallet_id,
                         outpoint.txid[..].to_vec(),
                         outpoint.vout,
                     ],
@@ -558,20 +871,42 @@ impl SqliteConn {
         .expect("Database must be available")
     }
 
+    /// Set whether a set of coins is frozen, that is excluded from automatic coin selection.
+    pub fn set_coins_frozen<'a>(
+        &mut self,
+        outpoints: impl IntoIterator<Item = &'a bitcoin::OutPoint>,
+        is_frozen: bool,
+    ) {
+        let wallet_id = self.wallet_id;
+        db_exec(&mut self.conn, |db_tx| {
+            for outpoint in outpoints {
+                db_tx.execute(
+                    "UPDATE coins SET is_frozen = ?1 WHERE wallet_id = ?2 AND txid = ?3 AND vout = ?4",
+                    rusqlite::params![is_frozen, wallet_id, outpoint.txid[..].to_vec(), outpoint.vout,],
+                )?;
+            }
+
+            Ok(())
+        })
+        .expect("Database must be available")
+    }
+
     /// Mark the Spend transaction of a given set of coins as being confirmed at a given
     /// block.
     pub fn confirm_spend<'a>(
         &mut self,
         outpoints: impl IntoIterator<Item = &'a (bitcoin::OutPoint, bitcoin::Txid, i32, u32)>,
     ) {
+        let wallet_id = self.wallet_id;
         db_exec(&mut self.conn, |db_tx| {
             for (outpoint, spend_txid, height, time) in outpoints {
                 db_tx.execute(
-                    "UPDATE coins SET spend_txid = ?1, spend_block_height = ?2, spend_block_time = ?3 WHERE txid = ?4 AND vout = ?5",
+                    "UPDATE coins SET spend_txid = ?1, spend_block_height = ?2, spend_block_time = ?3 WHERE wallet_id = ?4 AND txid = ?5 AND vout = ?6",
                     rusqlite::params![
                         spend_txid[..].to_vec(),
                         height,
                         time,
+                        wallet_id,
                         outpoint.txid[..].to_vec(),
                         outpoint.vout,
                     ],
@@ -586,8 +921,8 @@ impl SqliteConn {
     pub fn db_address(&mut self, address: &bitcoin::Address) -> Option<DbAddress> {
         db_query(
             &mut self.conn,
-            "SELECT * FROM addresses WHERE receive_address = ?1 OR change_address = ?1",
-            rusqlite::params![address.to_string()],
+            "SELECT * FROM addresses WHERE wallet_id = ?1 AND (receive_address = ?2 OR change_address = ?2)",
+            rusqlite::params![self.wallet_id, address.to_string()],
             |row| row.try_into(),
         )
         .expect("Db must not fail")
@@ -601,8 +936,8 @@ impl SqliteConn {
     pub fn db_spend(&mut self, txid: &bitcoin::Txid) -> Option<DbSpendTransaction> {
         db_query(
             &mut self.conn,
-            "SELECT * FROM spend_transactions WHERE txid = ?1",
-            rusqlite::params![txid[..].to_vec()],
+            "SELECT * FROM spend_transactions WHERE wallet_id = ?1 AND txid = ?2",
+            rusqlite::params![self.wallet_id, txid[..].to_vec()],
             |row| row.try_into(),
         )
         .expect("Db must not fail")
@@ -612,12 +947,13 @@ impl SqliteConn {
     /// Insert a new Spend transaction or replace an existing one.
     pub fn store_spend(&mut self, psbt: &Psbt) {
         let txid = &psbt.unsigned_tx.compute_txid()[..].to_vec();
+        let wallet_id = self.wallet_id;
 
         db_exec(&mut self.conn, |db_tx| {
             db_tx.execute(
-                "INSERT into spend_transactions (psbt, txid, updated_at) VALUES (?1, ?2, ?3) \
+                "INSERT into spend_transactions (wallet_id, psbt, txid, updated_at) VALUES (?1, ?2, ?3, ?4) \
                  ON CONFLICT DO UPDATE SET psbt=excluded.psbt",
-                rusqlite::params![psbt.serialize(), txid, curr_timestamp()],
+                rusqlite::params![wallet_id, psbt.serialize(), txid, curr_timestamp()],
             )?;
             Ok(())
         })
@@ -627,14 +963,15 @@ impl SqliteConn {
     pub fn list_spend(&mut self) -> Vec<DbSpendTransaction> {
         db_query(
             &mut self.conn,
-            "SELECT * FROM spend_transactions",
-            rusqlite::params![],
+            "SELECT * FROM spend_transactions WHERE wallet_id = ?1",
+            rusqlite::params![self.wallet_id],
             |row| row.try_into(),
         )
         .expect("Db must not fail")
     }
 
     pub fn update_labels(&mut self, items: &HashMap<LabelItem, Option<String>>) {
+        let wallet_id = self.wallet_id;
         db_exec(&mut self.conn, |db_tx| {
             for (labelled, kind, value) in items
                 .iter()
@@ -649,12 +986,12 @@ impl SqliteConn {
                     db_tx.execute(
                         "INSERT INTO labels (wallet_id, item, item_kind, value) VALUES (?1, ?2, ?3, ?4) \
                         ON CONFLICT DO UPDATE SET value=excluded.value",
-                        rusqlite::params![WALLET_ID, labelled, kind as i64, value],
+                        rusqlite::params![wallet_id, labelled, kind as i64, value],
                     )?;
                 } else {
                     db_tx.execute(
                         "DELETE FROM labels WHERE wallet_id = ?1 AND item = ?2",
-                        rusqlite::params![WALLET_ID, labelled],
+                        rusqlite::params![wallet_id, labelled],
                     )?;
                 }
             }
@@ -665,7 +1002,8 @@ impl SqliteConn {
 
     pub fn db_labels(&mut self, items: &HashSet<LabelItem>) -> Vec<DbLabel> {
         let query = format!(
-            "SELECT * FROM labels where item in ({})",
+            "SELECT * FROM labels WHERE wallet_id = {} AND item in ({})",
+            self.wallet_id,
             items
                 .iter()
                 .map(|a| format!("'{}'", a))
@@ -682,9 +1020,10 @@ impl SqliteConn {
         db_query(
             &mut self.conn,
             "SELECT * FROM labels \
+                WHERE wallet_id = ?1 \
                 ORDER BY id \
-                LIMIT ?1 OFFSET ?2",
-            rusqlite::params![limit, offset],
+                LIMIT ?2 OFFSET ?3",
+            rusqlite::params![self.wallet_id, limit, offset],
             |row| row.try_into(),
         )
         .expect("Db must not fail")
@@ -698,20 +1037,22 @@ impl SqliteConn {
             "SELECT DISTINCT(txid) FROM ( \
                 SELECT * from ( \
                     SELECT txid, blocktime AS date FROM coins \
-                    WHERE blocktime >= (?1) \
+                    WHERE wallet_id = (?4) \
+                    AND blocktime >= (?1) \
                     AND blocktime <= (?2) \
                     ORDER BY blocktime \
                 ) \
                 UNION \
                 SELECT * FROM (
                     SELECT spend_txid AS txid, spend_block_time AS date FROM coins \
-                    WHERE spend_block_time >= (?1) \
+                    WHERE wallet_id = (?4) \
+                    AND spend_block_time >= (?1) \
                     AND spend_block_time <= (?2) \
                     ORDER BY spend_block_time \
                 ) \
                 ORDER BY date DESC LIMIT (?3) \
             )",
-            rusqlite::params![start, end, limit],
+            rusqlite::params![start, end, limit, self.wallet_id],
             |row| {
                 let txid: Vec<u8> = row.get(0)?;
                 let txid: bitcoin::Txid =
@@ -744,12 +1085,14 @@ impl SqliteConn {
             "
             SELECT txid AS id
             FROM coins
+            WHERE wallet_id = ?1
             UNION
             SELECT spend_txid AS id
             FROM coins
-            WHERE spend_txid IS NOT NULL
+            WHERE wallet_id = ?1
+            AND spend_txid IS NOT NULL
             ",
-            rusqlite::params![],
+            rusqlite::params![self.wallet_id],
             |row| {
                 let txid: Vec<u8> = row.get(0)?;
                 let txid: bitcoin::Txid =
@@ -792,6 +1135,7 @@ impl SqliteConn {
     /// for all unconfirmed coins should be set to false before this
     /// method is called.
     pub fn update_coins_from_self(&mut self, prev_tip_height: i32) -> Result<(), rusqlite::Error> {
+        let wallet_id = self.wallet_id;
         db_exec(&mut self.conn, |db_tx| {
             // Given the requirement for unconfirmed coins that all ancestors
             // be from self, we perform the update in a loop until no further
@@ -808,8 +1152,8 @@ impl SqliteConn {
             let max_iterations = {
                 let num_unconfirmed: u64 = db_tx.query_row(
                     "SELECT COUNT(*) FROM coins
-                    WHERE blockheight IS NULL AND is_from_self = 0",
-                    [],
+                    WHERE wallet_id = ?1 AND blockheight IS NULL AND is_from_self = 0",
+                    [wallet_id],
                     |row| row.get(0),
                 )?;
                 // Add 1 for the confirmed coins, which will all
@@ -841,7 +1185,8 @@ impl SqliteConn {
                                     END
                                 ) AS cnt
                             FROM coins
-                            WHERE spend_txid IS NOT NULL
+                            WHERE wallet_id = ?2
+                            AND spend_txid IS NOT NULL
                             -- We only need to consider spend transactions that are
                             -- unconfirmed or confirmed after `prev_tip_height
                             -- as only these transactions will affect the coins that
@@ -850,11 +1195,12 @@ impl SqliteConn {
                             GROUP BY spend_txid
                         ) spends
                         ON t.txid = spends.spend_txid AND t.num_inputs = spends.cnt
-                    WHERE coins.txid = t.txid
+                    WHERE coins.wallet_id = ?2
+                    AND coins.txid = t.txid
                     AND (coins.blockheight IS NULL OR coins.blockheight > ?1)
                     AND coins.is_from_self = 0
                     ",
-                    [prev_tip_height],
+                    [prev_tip_height.into(), wallet_id],
                 )?;
                 if updated == 0 {
                     log::debug!("Finished updating is_from_self in {} iterations.", i + 1);
@@ -873,6 +1219,7 @@ impl SqliteConn {
         &mut self,
         txids: &[bitcoin::Txid],
     ) -> Vec<DbWalletTransaction> {
+        let wallet_id = self.wallet_id;
         // The UNION will remove duplicates.
         // We assume that a transaction's block info is the same in every coins row
         // it appears in.
@@ -882,11 +1229,11 @@ impl SqliteConn {
             INNER JOIN ( \
                 SELECT txid, blockheight, blocktime \
                 FROM coins \
-                WHERE wallet_id = {WALLET_ID} \
+                WHERE wallet_id = {wallet_id} \
                 UNION \
                 SELECT spend_txid, spend_block_height, spend_block_time \
                 FROM coins \
-                WHERE wallet_id = {WALLET_ID} \
+                WHERE wallet_id = {wallet_id} \
                 AND spend_txid IS NOT NULL \
             ) c ON t.txid = c.txid \
             WHERE t.txid in ({})",
@@ -914,10 +1261,174 @@ impl SqliteConn {
     }
 
     pub fn delete_spend(&mut self, txid: &bitcoin::Txid) {
+        let wallet_id = self.wallet_id;
+        db_exec(&mut self.conn, |db_tx| {
+            db_tx.execute(
+                "DELETE FROM spend_transactions WHERE wallet_id = ?1 AND txid = ?2",
+                rusqlite::params![wallet_id, txid[..].to_vec()],
+            )?;
+            Ok(())
+        })
+        .expect("Db must not fail");
+    }
+
+    /// Get all the additional watch-only descriptors.
+    pub fn db_watchonly_descriptors(&mut self) -> Vec<DbWatchonlyDescriptor> {
+        db_query(
+            &mut self.conn,
+            "SELECT id, descriptor, timestamp FROM watchonly_descriptors \
+             WHERE wallet_id = ?1 ORDER BY id",
+            rusqlite::params![self.wallet_id],
+            |row| row.try_into(),
+        )
+        .expect("Db must not fail")
+    }
+
+    /// Store a new watch-only descriptor, to look for coins from the given timestamp.
+    pub fn new_watchonly_descriptor(
+        &mut self,
+        descriptor: &Descriptor<DescriptorPublicKey>,
+        timestamp: u32,
+    ) {
+        let wallet_id = self.wallet_id;
+        db_exec(&mut self.conn, |db_tx| {
+            db_tx.execute(
+                "INSERT INTO watchonly_descriptors (wallet_id, descriptor, timestamp) \
+                 VALUES (?1, ?2, ?3)",
+                rusqlite::params![wallet_id, descriptor.to_string(), timestamp],
+            )?;
+            Ok(())
+        })
+        .expect("Db must not fail");
+    }
+
+    /// Get the coins of all the watch-only descriptors.
+    pub fn db_watchonly_coins(&mut self) -> Vec<DbWatchonlyCoin> {
+        db_query(
+            &mut self.conn,
+            "SELECT c.id, d.descriptor, c.txid, c.vout, c.amount_sat, c.address, \
+                    c.derivation_index, c.blockheight \
+             FROM watchonly_coins AS c \
+             INNER JOIN watchonly_descriptors AS d ON c.descriptor_id = d.id \
+             WHERE d.wallet_id = ?1 \
+             ORDER BY c.id",
+            rusqlite::params![self.wallet_id],
+            |row| row.try_into(),
+        )
+        .expect("Db must not fail")
+    }
+
+    /// Replace the coins of the watch-only descriptors. The descriptor of each coin must already
+    /// be stored in database.
+    pub fn set_watchonly_coins<'a>(&mut self, coins: impl IntoIterator<Item = &'a WatchonlyCoin>) {
+        let wallet_id = self.wallet_id;
+        db_exec(&mut self.conn, |db_tx| {
+            db_tx.execute(
+                "DELETE FROM watchonly_coins WHERE descriptor_id IN \
+                 (SELECT id FROM watchonly_descriptors WHERE wallet_id = ?1)",
+                rusqlite::params![wallet_id],
+            )?;
+            for coin in coins {
+                let deriv_index: u32 = coin.derivation_index.into();
+                db_tx.execute(
+                    "INSERT INTO watchonly_coins (descriptor_id, txid, vout, amount_sat, address, derivation_index, blockheight) \
+                     VALUES ((SELECT id FROM watchonly_descriptors WHERE wallet_id = ?8 AND descriptor = ?1), ?2, ?3, ?4, ?5, ?6, ?7)",
+                    rusqlite::params![
+                        coin.descriptor.to_string(),
+                        coin.outpoint.txid[..].to_vec(),
+                        coin.outpoint.vout,
+                        coin.amount.to_sat(),
+                        coin.address.to_string(),
+                        deriv_index,
+                        coin.block_height,
+                        wallet_id,
+                    ],
+                )?;
+            }
+            Ok(())
+        })
+        .expect("Db must not fail");
+    }
+
+    /// Get the spends signed by our hot signers since the given timestamp, along with the value
+    /// they send out of the wallet.
+    pub fn signed_spends_since(&mut self, timestamp: u32) -> Vec<(bitcoin::Txid, bitcoin::Amount)> {
+        db_query(
+            &mut self.conn,
+            "SELECT txid, amount_sat FROM signed_spends \
+             WHERE wallet_id = ?1 AND signed_at >= ?2 ORDER BY id",
+            rusqlite::params![self.wallet_id, timestamp],
+            |row| {
+                let txid: Vec<u8> = row.get(0)?;
+                let txid: bitcoin::Txid =
+                    encode::deserialize(&txid).expect("We only store valid txids");
+                let amount = row.get(1)?;
+                Ok((txid, bitcoin::Amount::from_sat(amount)))
+            },
+        )
+        .expect("Db must not fail")
+    }
+
+    /// Record a spend signed by our hot signers. A spend which was already recorded keeps the
+    /// highest value it was signed for and the date at which it was last signed.
+    pub fn record_signed_spend(
+        &mut self,
+        txid: &bitcoin::Txid,
+        amount: bitcoin::Amount,
+        timestamp: u32,
+    ) {
+        let wallet_id = self.wallet_id;
         db_exec(&mut self.conn, |db_tx| {
             db_tx.execute(
-                "DELETE FROM spend_transactions WHERE txid = ?1",
-                rusqlite::params![txid[..].to_vec()],
+                "INSERT INTO signed_spends (wallet_id, txid, amount_sat, signed_at) \
+                 VALUES (?1, ?2, ?3, ?4) \
+                 ON CONFLICT (wallet_id, txid) DO UPDATE SET \
+                 amount_sat = MAX(amount_sat, excluded.amount_sat), \
+                 signed_at = MAX(signed_at, excluded.signed_at)",
+                rusqlite::params![wallet_id, txid[..].to_vec(), amount.to_sat(), timestamp],
+            )?;
+            Ok(())
+        })
+        .expect("Db must not fail");
+    }
+
+    /// Get the spends broadcast since the given timestamp while a spending policy was set, along
+    /// with the value they send out of the wallet and the date at which they were broadcast.
+    pub fn broadcast_spends_since(
+        &mut self,
+        timestamp: u32,
+    ) -> Vec<(bitcoin::Txid, bitcoin::Amount, u32)> {
+        db_query(
+            &mut self.conn,
+            "SELECT txid, amount_sat, broadcast_at FROM broadcast_spends \
+             WHERE wallet_id = ?1 AND broadcast_at >= ?2 ORDER BY id",
+            rusqlite::params![self.wallet_id, timestamp],
+            |row| {
+                let txid: Vec<u8> = row.get(0)?;
+                let txid: bitcoin::Txid =
+                    encode::deserialize(&txid).expect("We only store valid txids");
+                let amount = row.get(1)?;
+                let broadcast_at = row.get(2)?;
+                Ok((txid, bitcoin::Amount::from_sat(amount), broadcast_at))
+            },
+        )
+        .expect("Db must not fail")
+    }
+
+    /// Record a spend broadcast while a spending policy was set. A spend which was already
+    /// recorded keeps the value and date at which it was first broadcast.
+    pub fn record_broadcast_spend(
+        &mut self,
+        txid: &bitcoin::Txid,
+        amount: bitcoin::Amount,
+        timestamp: u32,
+    ) {
+        let wallet_id = self.wallet_id;
+        db_exec(&mut self.conn, |db_tx| {
+            db_tx.execute(
+                "INSERT OR IGNORE INTO broadcast_spends \
+                 (wallet_id, txid, amount_sat, broadcast_at) VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![wallet_id, txid[..].to_vec(), amount.to_sat(), timestamp],
             )?;
             Ok(())
         })
@@ -940,24 +1451,25 @@ impl SqliteConn {
     /// This will have to be updated if we are to add new fields based on block data
     /// in the database eventually.
     pub fn rollback_tip(&mut self, new_tip: &BlockChainTip) {
+        let wallet_id = self.wallet_id;
         db_exec(&mut self.conn, |db_tx| {
             db_tx.execute(
-                "UPDATE coins SET blockheight = NULL, blocktime = NULL, spend_block_height = NULL, spend_block_time = NULL WHERE blockheight > ?1",
-                rusqlite::params![new_tip.height],
+                "UPDATE coins SET blockheight = NULL, blocktime = NULL, spend_block_height = NULL, spend_block_time = NULL WHERE wallet_id = ?1 AND blockheight > ?2",
+                rusqlite::params![wallet_id, new_tip.height],
             )?;
             db_tx.execute(
-                "UPDATE coins SET spend_block_height = NULL, spend_block_time = NULL WHERE spend_block_height > ?1",
-                rusqlite::params![new_tip.height],
+                "UPDATE coins SET spend_block_height = NULL, spend_block_time = NULL WHERE wallet_id = ?1 AND spend_block_height > ?2",
+                rusqlite::params![wallet_id, new_tip.height],
             )?;
             // This statement must be run after updating `blockheight` above so that it includes coins
             // that become unconfirmed following the rollback.
             db_tx.execute(
-                "UPDATE coins SET is_from_self = 0 WHERE blockheight IS NULL",
-                rusqlite::params![],
+                "UPDATE coins SET is_from_self = 0 WHERE wallet_id = ?1 AND blockheight IS NULL",
+                rusqlite::params![wallet_id],
             )?;
             db_tx.execute(
-                "UPDATE tip SET blockheight = (?1), blockhash = (?2)",
-                rusqlite::params![new_tip.height, new_tip.hash[..].to_vec()],
+                "UPDATE tip SET blockheight = (?1), blockhash = (?2) WHERE wallet_id = (?3)",
+                rusqlite::params![new_tip.height, new_tip.hash[..].to_vec(), wallet_id],
             )?;
             Ok(())
         })
@@ -1165,50 +1677,229 @@ CREATE TABLE wallets (
     rescan_timestamp INTEGER
 );
 
-/* Our (U)TxOs.
- *
- * The 'spend_block_height' and 'spend_block.time' are only present if the spending
- * transaction for this coin exists and was confirmed.
+/* Our (U)TxOs.
+ *
+ * The 'spend_block_height' and 'spend_block.time' are only present if the spending
+ * transaction for this coin exists and was confirmed.
+ *
+ * The 'is_immature' field is for coinbase deposits that are not yet buried under 100
+ * blocks. Note coinbase deposits can't technically be unconfirmed but we keep them
+ * as such until they become mature.
+ */
+CREATE TABLE coins (
+    id INTEGER PRIMARY KEY NOT NULL,
+    wallet_id INTEGER NOT NULL,
+    blockheight INTEGER,
+    blocktime INTEGER,
+    txid BLOB NOT NULL,
+    vout INTEGER NOT NULL,
+    amount_sat INTEGER NOT NULL,
+    derivation_index INTEGER NOT NULL,
+    is_change BOOLEAN NOT NULL CHECK (is_change IN (0,1)),
+    spend_txid BLOB,
+    spend_block_height INTEGER,
+    spend_block_time INTEGER,
+    is_immature BOOLEAN NOT NULL CHECK (is_immature IN (0,1)),
+    UNIQUE (txid, vout),
+    FOREIGN KEY (wallet_id) REFERENCES wallets (id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT
+);
+
+/* A mapping from descriptor address to derivation index. Necessary until
+ * we can get the derivation index from the parent descriptor from bitcoind.
+ */
+CREATE TABLE addresses (
+    receive_address TEXT NOT NULL UNIQUE,
+    change_address TEXT NOT NULL UNIQUE,
+    derivation_index INTEGER NOT NULL UNIQUE
+);
+
+/* Transactions we created that spend some of our coins. */
+CREATE TABLE spend_transactions (
+    id INTEGER PRIMARY KEY NOT NULL,
+    psbt BLOB UNIQUE NOT NULL,
+    txid BLOB UNIQUE NOT NULL,
+    updated_at INTEGER
+);
+
+/* Labels applied on addresses (0), outpoints (1), txids (2) */
+CREATE TABLE labels (
+    id INTEGER PRIMARY KEY NOT NULL,
+    wallet_id INTEGER NOT NULL,
+    item_kind INTEGER NOT NULL CHECK (item_kind IN (0,1,2)),
+    item TEXT UNIQUE NOT NULL,
+    value TEXT NOT NULL
+);
+";
+
+    const V12_SCHEMA: &str = "\
+CREATE TABLE version (
+    version INTEGER NOT NULL
+);
+
+/* About the Bitcoin network. */
+CREATE TABLE tip (
+    network TEXT NOT NULL,
+    blockheight INTEGER,
+    blockhash BLOB
+);
+
+/* This stores metadata about our wallet. We only support single wallet for
+ * now (and the foreseeable future).
+ *
+ * The 'timestamp' field is the creation date of the wallet. We guarantee to have seen all
+ * information related to our descriptor(s) that occurred after this date.
+ * The optional 'rescan_timestamp' field is a the timestamp we need to rescan the chain
+ * for events related to our descriptor(s) from.
+ */
+CREATE TABLE wallets (
+    id INTEGER PRIMARY KEY NOT NULL,
+    timestamp INTEGER NOT NULL,
+    main_descriptor TEXT NOT NULL,
+    deposit_derivation_index INTEGER NOT NULL,
+    change_derivation_index INTEGER NOT NULL,
+    rescan_timestamp INTEGER,
+    last_poll_timestamp INTEGER
+);
+
+/* Our (U)TxOs.
+ *
+ * The 'spend_block_height' and 'spend_block.time' are only present if the spending
+ * transaction for this coin exists and was confirmed.
+ *
+ * The 'is_immature' field is for coinbase deposits that are not yet buried under 100
+ * blocks. Note coinbase deposits can't technically be unconfirmed but we keep them
+ * as such until they become mature.
+ *
+ * The `is_from_self` field indicates if the coin is the output of a transaction whose
+ * inputs are all from the same wallet as the coin. For an unconfirmed coin, this also
+ * means that all unconfirmed ancestors, if any, are from self.
+ *
+ * The `is_frozen` field is set by the user for coins which must not be selected
+ * automatically when creating a transaction.
+ */
+CREATE TABLE coins (
+    id INTEGER PRIMARY KEY NOT NULL,
+    wallet_id INTEGER NOT NULL,
+    blockheight INTEGER,
+    blocktime INTEGER,
+    txid BLOB NOT NULL,
+    vout INTEGER NOT NULL,
+    amount_sat INTEGER NOT NULL,
+    derivation_index INTEGER NOT NULL,
+    is_change BOOLEAN NOT NULL CHECK (is_change IN (0,1)),
+    spend_txid BLOB,
+    spend_block_height INTEGER,
+    spend_block_time INTEGER,
+    is_immature BOOLEAN NOT NULL CHECK (is_immature IN (0,1)),
+    is_from_self BOOLEAN NOT NULL DEFAULT 0 CHECK (is_from_self IN (0,1)),
+    is_frozen BOOLEAN NOT NULL DEFAULT 0 CHECK (is_frozen IN (0,1)),
+    UNIQUE (txid, vout),
+    FOREIGN KEY (wallet_id) REFERENCES wallets (id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT,
+    FOREIGN KEY (txid) REFERENCES transactions (txid)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT,
+    FOREIGN KEY (spend_txid) REFERENCES transactions (txid)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT
+);
+
+/* A mapping from descriptor address to derivation index. Necessary until
+ * we can get the derivation index from the parent descriptor from bitcoind.
+ */
+CREATE TABLE addresses (
+    receive_address TEXT NOT NULL UNIQUE,
+    change_address TEXT NOT NULL UNIQUE,
+    derivation_index INTEGER NOT NULL UNIQUE
+);
+
+/* Transactions for all wallets. */
+CREATE TABLE transactions (
+    id INTEGER PRIMARY KEY NOT NULL,
+    txid BLOB UNIQUE NOT NULL,
+    tx BLOB UNIQUE NOT NULL,
+    num_inputs INTEGER CHECK (num_inputs IS NULL OR num_inputs > 0),
+    num_outputs INTEGER CHECK (num_outputs IS NULL OR num_outputs > 0),
+    is_coinbase BOOLEAN NOT NULL DEFAULT 0 CHECK (is_coinbase IN (0,1))
+);
+
+/* Transactions we created that spend some of our coins. */
+CREATE TABLE spend_transactions (
+    id INTEGER PRIMARY KEY NOT NULL,
+    psbt BLOB UNIQUE NOT NULL,
+    txid BLOB UNIQUE NOT NULL,
+    updated_at INTEGER
+);
+
+/* Additional watch-only descriptors, which need not be Liana descriptors, whose coins we
+ * track alongside the main descriptor's. For instance those of a former wallet whose funds
+ * are to be swept into this one.
+ *
+ * The 'timestamp' field is the date from which we looked for coins of this descriptor.
+ */
+CREATE TABLE watchonly_descriptors (
+    id INTEGER PRIMARY KEY NOT NULL,
+    wallet_id INTEGER NOT NULL,
+    descriptor TEXT UNIQUE NOT NULL,
+    timestamp INTEGER NOT NULL,
+    FOREIGN KEY (wallet_id) REFERENCES wallets (id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT
+);
+
+/* The unspent coins of the watch-only descriptors, as of the last poll.
  *
- * The 'is_immature' field is for coinbase deposits that are not yet buried under 100
- * blocks. Note coinbase deposits can't technically be unconfirmed but we keep them
- * as such until they become mature.
+ * The 'blockheight' field is only present if the transaction creating the coin was confirmed.
  */
-CREATE TABLE coins (
+CREATE TABLE watchonly_coins (
     id INTEGER PRIMARY KEY NOT NULL,
-    wallet_id INTEGER NOT NULL,
-    blockheight INTEGER,
-    blocktime INTEGER,
+    descriptor_id INTEGER NOT NULL,
     txid BLOB NOT NULL,
     vout INTEGER NOT NULL,
     amount_sat INTEGER NOT NULL,
+    address TEXT NOT NULL,
     derivation_index INTEGER NOT NULL,
-    is_change BOOLEAN NOT NULL CHECK (is_change IN (0,1)),
-    spend_txid BLOB,
-    spend_block_height INTEGER,
-    spend_block_time INTEGER,
-    is_immature BOOLEAN NOT NULL CHECK (is_immature IN (0,1)),
+    blockheight INTEGER,
     UNIQUE (txid, vout),
-    FOREIGN KEY (wallet_id) REFERENCES wallets (id)
+    FOREIGN KEY (descriptor_id) REFERENCES watchonly_descriptors (id)
         ON UPDATE RESTRICT
         ON DELETE RESTRICT
 );
 
-/* A mapping from descriptor address to derivation index. Necessary until
- * we can get the derivation index from the parent descriptor from bitcoind.
+/* The spends signed by our hot signers, along with the value they send out of the wallet
+ * (fees included). Used to enforce the daily limit of the signing policy.
+ *
+ * The 'signed_at' field is the date at which the spend was first signed.
  */
-CREATE TABLE addresses (
-    receive_address TEXT NOT NULL UNIQUE,
-    change_address TEXT NOT NULL UNIQUE,
-    derivation_index INTEGER NOT NULL UNIQUE
+CREATE TABLE signed_spends (
+    id INTEGER PRIMARY KEY NOT NULL,
+    wallet_id INTEGER NOT NULL,
+    txid BLOB UNIQUE NOT NULL,
+    amount_sat INTEGER NOT NULL,
+    signed_at INTEGER NOT NULL,
+    FOREIGN KEY (wallet_id) REFERENCES wallets (id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT
 );
 
-/* Transactions we created that spend some of our coins. */
-CREATE TABLE spend_transactions (
+/* The spends broadcast by the daemon while a spending policy was set, along with the value
+ * they send out of the wallet (fees included). Used to enforce the rolling window limits of
+ * the spending policy.
+ *
+ * The 'broadcast_at' field is the date at which the spend was first broadcast.
+ */
+CREATE TABLE broadcast_spends (
     id INTEGER PRIMARY KEY NOT NULL,
-    psbt BLOB UNIQUE NOT NULL,
+    wallet_id INTEGER NOT NULL,
     txid BLOB UNIQUE NOT NULL,
-    updated_at INTEGER
+    amount_sat INTEGER NOT NULL,
+    broadcast_at INTEGER NOT NULL,
+    FOREIGN KEY (wallet_id) REFERENCES wallets (id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT
 );
 
 /* Labels applied on addresses (0), outpoints (1), txids (2) */
@@ -1250,6 +1941,59 @@ CREATE TABLE labels (
         (tmp_dir, options, secp, db)
     }
 
+    // Create a database with a schema previous to version 13, in which the tip and the addresses
+    // mapping were not per wallet.
+    fn create_legacy_db(
+        db_path: &path::Path,
+        options: FreshDbOptions,
+        secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+    ) {
+        utils::create_db_file(db_path).unwrap();
+        let mut query = String::new();
+        for index in 0..LOOK_AHEAD_LIMIT {
+            let receive_address = options
+                .main_descriptor
+                .receive_descriptor()
+                .derive(index.into(), secp)
+                .address(options.bitcoind_network);
+            let change_address = options
+                .main_descriptor
+                .change_descriptor()
+                .derive(index.into(), secp)
+                .address(options.bitcoind_network);
+            query += &format!(
+                "INSERT INTO addresses (receive_address, change_address, derivation_index) VALUES (\"{}\", \"{}\", {});\n",
+                receive_address, change_address, index
+            );
+        }
+
+        let mut conn = rusqlite::Connection::open(db_path).unwrap();
+        db_exec(&mut conn, |tx| {
+            tx.execute_batch(options.schema)?;
+            tx.execute(
+                "INSERT INTO version (version) VALUES (?1)",
+                rusqlite::params![options.version],
+            )?;
+            tx.execute(
+                "INSERT INTO tip (network, blockheight, blockhash) VALUES (?1, NULL, NULL)",
+                rusqlite::params![options.bitcoind_network.to_string()],
+            )?;
+            tx.execute(
+                "INSERT INTO wallets (timestamp, main_descriptor, deposit_derivation_index, change_derivation_index) \
+                     VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![
+                    curr_timestamp(),
+                    options.main_descriptor.to_string(),
+                    0,
+                    0
+                ],
+            )?;
+            tx.execute_batch(&query)?;
+            Ok(())
+        })
+        .unwrap();
+    }
+
     // All values required to store a coin in the V3 schema DB (including `id` column).
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub struct DbCoinV3 {
@@ -1453,6 +2197,7 @@ CREATE TABLE labels (
                 spend_txid: None,
                 spend_block: None,
                 is_from_self: false,
+                is_frozen: false,
             };
             conn.new_unspent_coins(&[coin_a]);
             // We can query by status and/or outpoint.
@@ -1500,6 +2245,7 @@ CREATE TABLE labels (
                 spend_txid: None,
                 spend_block: None,
                 is_from_self: false,
+                is_frozen: false,
             };
             conn.new_unspent_coins(&[coin_b]);
             // Both coins are unconfirmed.
@@ -1635,6 +2381,7 @@ CREATE TABLE labels (
                 spend_txid: None,
                 spend_block: None,
                 is_from_self: false,
+                is_frozen: false,
             };
             let outpoint_d = bitcoin::OutPoint::new(txs.get(4).unwrap().compute_txid(), 43);
             let coin_d = Coin {
@@ -1647,6 +2394,7 @@ CREATE TABLE labels (
                 spend_txid: None,
                 spend_block: None,
                 is_from_self: false,
+                is_frozen: false,
             };
             conn.new_unspent_coins(&[coin_c, coin_d]);
 
@@ -1762,6 +2510,7 @@ CREATE TABLE labels (
                 spend_txid: None,
                 spend_block: None,
                 is_from_self: false,
+                is_frozen: false,
             };
             conn.new_unspent_coins(&[coin_a]);
             assert_eq!(conn.coins(&[], &[])[0].outpoint, coin_a.outpoint);
@@ -1805,6 +2554,7 @@ CREATE TABLE labels (
                 spend_txid: None,
                 spend_block: None,
                 is_from_self: false,
+                is_frozen: false,
             };
             conn.new_unspent_coins(&[coin_b]);
             let outpoints: HashSet<bitcoin::OutPoint> = conn
@@ -1934,6 +2684,7 @@ CREATE TABLE labels (
                 spend_txid: None,
                 spend_block: None,
                 is_from_self: false,
+                is_frozen: false,
             };
             conn.new_unspent_coins(&[coin_imma]);
             let outpoints: HashSet<bitcoin::OutPoint> = conn
@@ -1950,6 +2701,19 @@ CREATE TABLE labels (
             conn.confirm_coins(&[(coin_imma.outpoint, height, time)]);
             let coin = conn.db_coins(&[coin_imma.outpoint]).pop().unwrap();
             assert!(!coin.is_immature);
+
+            // Coins aren't frozen by default. We can freeze and unfreeze them.
+            assert!(conn.coins(&[], &[]).iter().all(|c| !c.is_frozen));
+            conn.set_coins_frozen(&[coin_a.outpoint, coin_imma.outpoint], true);
+            let coins = conn.coins(&[], &[]);
+            assert!(coins
+                .iter()
+                .all(|c| c.is_frozen == (c.outpoint != coin_b.outpoint)));
+            conn.set_coins_frozen(&[coin_a.outpoint], false);
+            let coins = conn.coins(&[], &[]);
+            assert!(coins
+                .iter()
+                .all(|c| c.is_frozen == (c.outpoint == coin_imma.outpoint)));
         }
 
         fs::remove_dir_all(tmp_dir).unwrap();
@@ -2063,6 +2827,54 @@ CREATE TABLE labels (
         fs::remove_dir_all(tmp_dir).unwrap();
     }
 
+    #[test]
+    fn sqlite_gap_limit() {
+        let (tmp_dir, options, secp, db) = dummy_db();
+        let receive_addr = |index: u32| {
+            options
+                .main_descriptor
+                .receive_descriptor()
+                .derive(index.into(), &secp)
+                .address(options.bitcoind_network)
+        };
+
+        {
+            // The database was created with the default gap limit.
+            let mut conn = db.clone().with_gap_limit(300).connection().unwrap();
+            assert!(conn.db_address(&receive_addr(199)).is_some());
+            assert!(conn.db_address(&receive_addr(200)).is_none());
+
+            // After increasing it, the mapping is filled up to the new gap limit.
+            conn.fill_addresses_mapping(&secp);
+            let db_addr = conn.db_address(&receive_addr(299)).unwrap();
+            assert_eq!(db_addr.derivation_index, 299.into());
+            assert!(conn.db_address(&receive_addr(300)).is_none());
+
+            // Filling it again is a no-op.
+            conn.fill_addresses_mapping(&secp);
+            assert!(conn.db_address(&receive_addr(300)).is_none());
+
+            // Setting a derivation index extends the mapping by the new gap limit.
+            conn.set_derivation_index(10.into(), false, &secp);
+            let db_addr = conn.db_address(&receive_addr(309)).unwrap();
+            assert_eq!(db_addr.derivation_index, 309.into());
+            assert!(conn.db_address(&receive_addr(310)).is_none());
+        }
+
+        {
+            // Decreasing the gap limit doesn't remove any entry.
+            let mut conn = db.with_gap_limit(20).connection().unwrap();
+            conn.fill_addresses_mapping(&secp);
+            assert!(conn.db_address(&receive_addr(309)).is_some());
+            conn.set_derivation_index(400.into(), false, &secp);
+            let db_addr = conn.db_address(&receive_addr(419)).unwrap();
+            assert_eq!(db_addr.derivation_index, 419.into());
+            assert!(conn.db_address(&receive_addr(420)).is_none());
+        }
+
+        fs::remove_dir_all(tmp_dir).unwrap();
+    }
+
     #[test]
     fn sqlite_tip_rollback() {
         let (tmp_dir, _, _, db) = dummy_db();
@@ -2106,6 +2918,7 @@ CREATE TABLE labels (
                     spend_txid: None,
                     spend_block: None,
                     is_from_self: false,
+                    is_frozen: false,
                 },
                 Coin {
                     outpoint: bitcoin::OutPoint::new(txs.get(1).unwrap().compute_txid(), 2),
@@ -2120,6 +2933,7 @@ CREATE TABLE labels (
                     spend_txid: None,
                     spend_block: None,
                     is_from_self: false,
+                    is_frozen: false,
                 },
                 Coin {
                     outpoint: bitcoin::OutPoint::new(txs.get(2).unwrap().compute_txid(), 3),
@@ -2137,6 +2951,7 @@ CREATE TABLE labels (
                         time: 1_231_678,
                     }),
                     is_from_self: false,
+                    is_frozen: false,
                 },
                 Coin {
                     outpoint: bitcoin::OutPoint::new(txs.get(4).unwrap().compute_txid(), 4),
@@ -2151,6 +2966,7 @@ CREATE TABLE labels (
                     spend_txid: None,
                     spend_block: None,
                     is_from_self: false,
+                    is_frozen: false,
                 },
                 Coin {
                     outpoint: bitcoin::OutPoint::new(txs.get(5).unwrap().compute_txid(), 5),
@@ -2168,6 +2984,7 @@ CREATE TABLE labels (
                         time: 1_201_678,
                     }),
                     is_from_self: false,
+                    is_frozen: false,
                 },
             ];
             conn.new_unspent_coins(&coins);
@@ -2286,6 +3103,175 @@ CREATE TABLE labels (
         fs::remove_dir_all(tmp_dir).unwrap();
     }
 
+    #[test]
+    fn sqlite_watchonly() {
+        let (tmp_dir, _, secp, db) = dummy_db();
+
+        {
+            let mut conn = db.connection().unwrap();
+
+            // At first there is no watch-only descriptor nor coin.
+            assert!(conn.db_watchonly_descriptors().is_empty());
+            assert!(conn.db_watchonly_coins().is_empty());
+
+            // Store two watch-only descriptors, they are returned in insertion order.
+            let desc_a = Descriptor::<DescriptorPublicKey>::from_str("wpkh(tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*)").unwrap();
+            let desc_b = Descriptor::<DescriptorPublicKey>::from_str("wpkh(tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/0/*)").unwrap();
+            conn.new_watchonly_descriptor(&desc_a, 1_000);
+            conn.new_watchonly_descriptor(&desc_b, 2_000);
+            let descs = conn.db_watchonly_descriptors();
+            assert_eq!(descs.len(), 2);
+            assert_eq!((&descs[0].descriptor, descs[0].timestamp), (&desc_a, 1_000));
+            assert_eq!((&descs[1].descriptor, descs[1].timestamp), (&desc_b, 2_000));
+
+            // Set some coins for each of them.
+            let coin = |desc: &Descriptor<DescriptorPublicKey>,
+                        index: u32,
+                        vout: u32,
+                        block_height: Option<i32>| {
+                let address = desc
+                    .clone()
+                    .into_single_descriptors()
+                    .unwrap()
+                    .remove(0)
+                    .at_derivation_index(index)
+                    .unwrap()
+                    .derived_descriptor(&secp)
+                    .unwrap()
+                    .address(bitcoin::Network::Bitcoin)
+                    .unwrap();
+                WatchonlyCoin {
+                    descriptor: desc.clone(),
+                    outpoint: bitcoin::OutPoint::from_str(&format!(
+                        "6f0dc85a369b44458eba3a6b0db9ab6a1f0e2a3ea3e0b1a4f6c3e0c3d7a4f33e:{}",
+                        vout
+                    ))
+                    .unwrap(),
+                    amount: bitcoin::Amount::from_sat(10_000 + vout as u64),
+                    address,
+                    derivation_index: index.into(),
+                    block_height,
+                }
+            };
+            let coins = vec![
+                coin(&desc_a, 0, 0, Some(101)),
+                coin(&desc_b, 3, 1, None),
+                coin(&desc_a, 12, 2, None),
+            ];
+            conn.set_watchonly_coins(&coins);
+            let db_coins: Vec<_> = conn
+                .db_watchonly_coins()
+                .into_iter()
+                .map(WatchonlyCoin::from)
+                .collect();
+            assert_eq!(db_coins, coins);
+
+            // Setting the coins again replaces the existing ones.
+            conn.set_watchonly_coins(&coins[1..]);
+            let db_coins: Vec<_> = conn
+                .db_watchonly_coins()
+                .into_iter()
+                .map(WatchonlyCoin::from)
+                .collect();
+            assert_eq!(db_coins, coins[1..]);
+            conn.set_watchonly_coins(&[]);
+            assert!(conn.db_watchonly_coins().is_empty());
+        }
+
+        fs::remove_dir_all(tmp_dir).unwrap();
+    }
+
+    #[test]
+    fn sqlite_signed_spends() {
+        let (tmp_dir, _, _, db) = dummy_db();
+
+        {
+            let mut conn = db.connection().unwrap();
+            assert!(conn.signed_spends_since(0).is_empty());
+
+            let txid_a = bitcoin::Txid::from_str(
+                "6f0dc85a369b44458eba3a6b0db9ab6a1f0e2a3ea3e0b1a4f6c3e0c3d7a4f33e",
+            )
+            .unwrap();
+            let txid_b = bitcoin::Txid::from_str(
+                "0c62a990d20d54429e70859292e82374ba6b1b951a3ab60f26bb65fee5724ff7",
+            )
+            .unwrap();
+            conn.record_signed_spend(&txid_a, bitcoin::Amount::from_sat(10_000), 1_000);
+            conn.record_signed_spend(&txid_b, bitcoin::Amount::from_sat(20_000), 2_000);
+            assert_eq!(
+                conn.signed_spends_since(0),
+                vec![
+                    (txid_a, bitcoin::Amount::from_sat(10_000)),
+                    (txid_b, bitcoin::Amount::from_sat(20_000))
+                ]
+            );
+            assert_eq!(
+                conn.signed_spends_since(1_001),
+                vec![(txid_b, bitcoin::Amount::from_sat(20_000))]
+            );
+
+            // Signing a spend again records the highest value it was signed for, as of the last
+            // time it was signed.
+            conn.record_signed_spend(&txid_a, bitcoin::Amount::from_sat(15_000), 3_000);
+            assert_eq!(
+                conn.signed_spends_since(1_001),
+                vec![
+                    (txid_a, bitcoin::Amount::from_sat(15_000)),
+                    (txid_b, bitcoin::Amount::from_sat(20_000))
+                ]
+            );
+            conn.record_signed_spend(&txid_a, bitcoin::Amount::from_sat(5_000), 4_000);
+            assert_eq!(
+                conn.signed_spends_since(3_001),
+                vec![(txid_a, bitcoin::Amount::from_sat(15_000))]
+            );
+        }
+
+        fs::remove_dir_all(tmp_dir).unwrap();
+    }
+
+    #[test]
+    fn sqlite_broadcast_spends() {
+        let (tmp_dir, _, _, db) = dummy_db();
+
+        {
+            let mut conn = db.connection().unwrap();
+            assert!(conn.broadcast_spends_since(0).is_empty());
+
+            let txid_a = bitcoin::Txid::from_str(
+                "6f0dc85a369b44458eba3a6b0db9ab6a1f0e2a3ea3e0b1a4f6c3e0c3d7a4f33e",
+            )
+            .unwrap();
+            let txid_b = bitcoin::Txid::from_str(
+                "0c62a990d20d54429e70859292e82374ba6b1b951a3ab60f26bb65fee5724ff7",
+            )
+            .unwrap();
+            conn.record_broadcast_spend(&txid_a, bitcoin::Amount::from_sat(10_000), 1_000);
+            conn.record_broadcast_spend(&txid_b, bitcoin::Amount::from_sat(20_000), 2_000);
+            assert_eq!(
+                conn.broadcast_spends_since(0),
+                vec![
+                    (txid_a, bitcoin::Amount::from_sat(10_000), 1_000),
+                    (txid_b, bitcoin::Amount::from_sat(20_000), 2_000)
+                ]
+            );
+            assert_eq!(
+                conn.broadcast_spends_since(1_001),
+                vec![(txid_b, bitcoin::Amount::from_sat(20_000), 2_000)]
+            );
+
+            // Broadcasting a spend again does not record it anew.
+            conn.record_broadcast_spend(&txid_a, bitcoin::Amount::from_sat(15_000), 3_000);
+            assert_eq!(
+                conn.broadcast_spends_since(1_001),
+                vec![(txid_b, bitcoin::Amount::from_sat(20_000), 2_000)]
+            );
+        }
+
+        fs::remove_dir_all(tmp_dir).unwrap();
+    }
+
     #[test]
     fn sqlite_list_txids() {
         let (tmp_dir, _, _, db) = dummy_db();
@@ -2314,6 +3300,7 @@ CREATE TABLE labels (
                     spend_txid: None,
                     spend_block: None,
                     is_from_self: false,
+                    is_frozen: false,
                 },
                 Coin {
                     outpoint: bitcoin::OutPoint::new(txs.get(1).unwrap().compute_txid(), 2),
@@ -2328,6 +3315,7 @@ CREATE TABLE labels (
                     spend_txid: None,
                     spend_block: None,
                     is_from_self: false,
+                    is_frozen: false,
                 },
                 Coin {
                     outpoint: bitcoin::OutPoint::new(txs.get(2).unwrap().compute_txid(), 3),
@@ -2345,6 +3333,7 @@ CREATE TABLE labels (
                         time: 1_123_000,
                     }),
                     is_from_self: false,
+                    is_frozen: false,
                 },
                 Coin {
                     outpoint: bitcoin::OutPoint::new(txs.get(4).unwrap().compute_txid(), 4),
@@ -2359,6 +3348,7 @@ CREATE TABLE labels (
                     spend_txid: None,
                     spend_block: None,
                     is_from_self: false,
+                    is_frozen: false,
                 },
                 Coin {
                     outpoint: bitcoin::OutPoint::new(txs.get(5).unwrap().compute_txid(), 5),
@@ -2376,6 +3366,7 @@ CREATE TABLE labels (
                         time: 1_126_000,
                     }),
                     is_from_self: false,
+                    is_frozen: false,
                 },
             ];
             conn.new_unspent_coins(&coins);
@@ -2438,6 +3429,7 @@ CREATE TABLE labels (
                     spend_txid: None,
                     spend_block: None,
                     is_from_self: false,
+                    is_frozen: false,
                 },
                 Coin {
                     outpoint: bitcoin::OutPoint::new(txs.get(1).unwrap().compute_txid(), 2),
@@ -2452,6 +3444,7 @@ CREATE TABLE labels (
                     spend_txid: None,
                     spend_block: None,
                     is_from_self: false,
+                    is_frozen: false,
                 },
                 Coin {
                     outpoint: bitcoin::OutPoint::new(txs.get(2).unwrap().compute_txid(), 3),
@@ -2469,6 +3462,7 @@ CREATE TABLE labels (
                         time: 1_123_000,
                     }),
                     is_from_self: false,
+                    is_frozen: false,
                 },
                 Coin {
                     outpoint: bitcoin::OutPoint::new(txs.get(4).unwrap().compute_txid(), 4),
@@ -2483,6 +3477,7 @@ CREATE TABLE labels (
                     spend_txid: None,
                     spend_block: None,
                     is_from_self: false,
+                    is_frozen: false,
                 },
                 Coin {
                     outpoint: bitcoin::OutPoint::new(txs.get(5).unwrap().compute_txid(), 5),
@@ -2500,6 +3495,7 @@ CREATE TABLE labels (
                         time: 1_126_000,
                     }),
                     is_from_self: false,
+                    is_frozen: false,
                 },
             ];
             conn.new_unspent_coins(&coins);
@@ -2622,6 +3618,7 @@ CREATE TABLE labels (
                         None
                     },
                     is_from_self: false,
+                    is_frozen: false,
                 })
                 .collect();
 
@@ -2778,6 +3775,7 @@ CREATE TABLE labels (
                 spend_txid: None,
                 spend_block: None,
                 is_from_self: false,
+                is_frozen: false,
             };
             let coin_tx_b: Coin = Coin {
                 outpoint: bitcoin::OutPoint::new(tx_b.compute_txid(), 0),
@@ -2789,6 +3787,7 @@ CREATE TABLE labels (
                 spend_txid: None,
                 spend_block: None,
                 is_from_self: false,
+                is_frozen: false,
             };
             conn.new_txs(&[tx_a, tx_b]);
             conn.new_unspent_coins(&[coin_tx_a, coin_tx_b]);
@@ -2812,6 +3811,7 @@ CREATE TABLE labels (
                 spend_txid: None,
                 spend_block: None,
                 is_from_self: false,
+                is_frozen: false,
             };
             conn.new_txs(&[tx_c.clone()]);
             conn.spend_coins(&[(coin_tx_a.outpoint, tx_c.compute_txid())]);
@@ -2836,6 +3836,7 @@ CREATE TABLE labels (
                 spend_txid: None,
                 spend_block: None,
                 is_from_self: false,
+                is_frozen: false,
             };
             conn.new_txs(&[tx_d.clone()]);
             conn.spend_coins(&[(coin_tx_c.outpoint, tx_d.compute_txid())]);
@@ -2859,6 +3860,7 @@ CREATE TABLE labels (
                 spend_txid: None,
                 spend_block: None,
                 is_from_self: false,
+                is_frozen: false,
             };
             conn.new_txs(&[tx_e.clone()]);
             conn.spend_coins(&[
@@ -2884,6 +3886,7 @@ CREATE TABLE labels (
                 spend_txid: None,
                 spend_block: None,
                 is_from_self: false,
+                is_frozen: false,
             };
             conn.new_txs(&[tx_f.clone()]);
             conn.spend_coins(&[(coin_tx_e.outpoint, tx_f.compute_txid())]);
@@ -3079,7 +4082,7 @@ CREATE TABLE labels (
     }
 
     #[test]
-    fn v0_to_v8_migration() {
+    fn v0_to_v13_migration() {
         let secp = secp256k1::Secp256k1::verification_only();
 
         // Create a database with version 0, using the old schema.
@@ -3091,7 +4094,7 @@ CREATE TABLE labels (
         let mut options = dummy_options();
         options.schema = V0_SCHEMA;
         options.version = 0;
-        create_fresh_db(&db_path, options, &secp).unwrap();
+        create_legacy_db(&db_path, options, &secp);
 
         // Two PSBTs we'll insert in the DB before and after the migration. Note they are random
         // PSBTs taken from the descriptor unit tests, it doesn't matter.
@@ -3143,7 +4146,7 @@ CREATE TABLE labels (
                         "INSERT INTO coins (wallet_id, txid, vout, amount_sat, derivation_index, is_change) \
                              VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
                         rusqlite::params![
-                            WALLET_ID,
+                            MAIN_WALLET_ID,
                             outpoint.txid[..].to_vec(),
                             outpoint.vout,
                             amount.to_sat(),
@@ -3185,7 +4188,7 @@ CREATE TABLE labels (
         {
             let mut conn = db.connection().unwrap();
             let version = conn.db_version();
-            assert_eq!(version, 8);
+            assert_eq!(version, 13);
         }
         // We should now be able to insert another PSBT, to query both, and the first PSBT must
         // have no associated timestamp.
@@ -3226,6 +4229,7 @@ CREATE TABLE labels (
                 spend_txid: None,
                 spend_block: None,
                 is_from_self: false,
+                is_frozen: false,
             }]);
             let coins = conn.coins(&[], &[]);
             assert_eq!(coins.len(), 3);
@@ -3260,7 +4264,7 @@ CREATE TABLE labels (
     }
 
     #[test]
-    fn v3_to_v8_migration() {
+    fn v3_to_v13_migration() {
         let secp = secp256k1::Secp256k1::verification_only();
 
         // Create a database with version 3, using the old schema.
@@ -3272,7 +4276,7 @@ CREATE TABLE labels (
         let mut options = dummy_options();
         options.schema = V3_SCHEMA;
         options.version = 3;
-        create_fresh_db(&db_path, options, &secp).unwrap();
+        create_legacy_db(&db_path, options, &secp);
 
         {
             let db = SqliteDb::new(db_path.clone(), None, &secp).unwrap();
@@ -3302,7 +4306,7 @@ CREATE TABLE labels (
                 bitcoin::OutPoint::new(bitcoin_txs.get(4).unwrap().compute_txid(), 4633);
             let coin_a = DbCoinV3 {
                 id: 1,
-                wallet_id: WALLET_ID,
+                wallet_id: MAIN_WALLET_ID,
                 outpoint: bitcoin::OutPoint::new(bitcoin_txs.first().unwrap().compute_txid(), 1),
                 is_immature: false,
                 amount: bitcoin::Amount::from_sat(1231001),
@@ -3320,7 +4324,7 @@ CREATE TABLE labels (
             };
             let coin_b = DbCoinV3 {
                 id: 2,
-                wallet_id: WALLET_ID,
+                wallet_id: MAIN_WALLET_ID,
                 outpoint: bitcoin::OutPoint::new(bitcoin_txs.get(1).unwrap().compute_txid(), 19234),
                 is_immature: false,
                 amount: bitcoin::Amount::from_sat(23145),
@@ -3335,7 +4339,7 @@ CREATE TABLE labels (
             };
             let coin_c = DbCoinV3 {
                 id: 3,
-                wallet_id: WALLET_ID,
+                wallet_id: MAIN_WALLET_ID,
                 outpoint: bitcoin::OutPoint::new(bitcoin_txs.get(2).unwrap().compute_txid(), 932),
                 is_immature: false,
                 amount: bitcoin::Amount::from_sat(354764),
@@ -3350,7 +4354,7 @@ CREATE TABLE labels (
             };
             let coin_d = DbCoinV3 {
                 id: 4,
-                wallet_id: WALLET_ID,
+                wallet_id: MAIN_WALLET_ID,
                 outpoint: coin_d_outpoint,
                 is_immature: false,
                 amount: bitcoin::Amount::from_sat(23200),
@@ -3362,7 +4366,7 @@ CREATE TABLE labels (
             };
             let coin_e = DbCoinV3 {
                 id: 5,
-                wallet_id: WALLET_ID,
+                wallet_id: MAIN_WALLET_ID,
                 outpoint: coin_e_outpoint,
                 is_immature: false,
                 amount: bitcoin::Amount::from_sat(675000),
@@ -3374,7 +4378,7 @@ CREATE TABLE labels (
             };
             let coin_imma_a = DbCoinV3 {
                 id: 6,
-                wallet_id: WALLET_ID,
+                wallet_id: MAIN_WALLET_ID,
                 outpoint: bitcoin::OutPoint::new(bitcoin_txs.get(5).unwrap().compute_txid(), 5),
                 is_immature: true,
                 amount: bitcoin::Amount::from_sat(4564347),
@@ -3389,7 +4393,7 @@ CREATE TABLE labels (
             };
             let coin_imma_b = DbCoinV3 {
                 id: 7,
-                wallet_id: WALLET_ID,
+                wallet_id: MAIN_WALLET_ID,
                 outpoint: bitcoin::OutPoint::new(bitcoin_txs.get(6).unwrap().compute_txid(), 19234),
                 is_immature: true,
                 amount: bitcoin::Amount::from_sat(731453),
@@ -3412,10 +4416,10 @@ CREATE TABLE labels (
 
             // Migrate the DB.
             maybe_apply_migration(&db_path, &bitcoin_txs).unwrap();
-            assert_eq!(conn.db_version(), 8);
+            assert_eq!(conn.db_version(), 13);
             // Migrating twice will be a no-op. No need to pass `bitcoin_txs` second time.
             maybe_apply_migration(&db_path, &[]).unwrap();
-            assert!(conn.db_version() == 8);
+            assert!(conn.db_version() == 13);
 
             // Compare the `DbCoin`s with the expected values.
             let coins_post = conn.coins(&[], &[]);
@@ -3497,7 +4501,7 @@ CREATE TABLE labels (
             .enumerate()
             .map(|(i, tx)| DbCoinV3 {
                 id: i.try_into().unwrap(),
-                wallet_id: WALLET_ID,
+                wallet_id: MAIN_WALLET_ID,
                 outpoint: bitcoin::OutPoint {
                     txid: tx.compute_txid(),
                     vout: i as u32,
@@ -3527,8 +4531,9 @@ CREATE TABLE labels (
             })
             .collect();
 
+        create_legacy_db(&db_path, options, &secp);
         {
-            let db = SqliteDb::new(db_path.clone(), Some(options), &secp).unwrap();
+            let db = SqliteDb::new(db_path.clone(), None, &secp).unwrap();
             let mut conn = db.connection().unwrap();
 
             // Insert all these coins into database.
@@ -3570,4 +4575,348 @@ CREATE TABLE labels (
 
         fs::remove_dir_all(tmp_dir).unwrap();
     }
+
+    #[test]
+    fn v12_to_v13_migration() {
+        let secp = secp256k1::Secp256k1::verification_only();
+
+        // Create a database with version 12, using the old schema.
+        let tmp_dir = tmp_dir();
+        fs::create_dir_all(&tmp_dir).unwrap();
+        let db_path: path::PathBuf = [tmp_dir.as_path(), path::Path::new("lianad_v12.sqlite3")]
+            .iter()
+            .collect();
+        let mut options = dummy_options();
+        options.schema = V12_SCHEMA;
+        options.version = 12;
+        let main_descriptor = options.main_descriptor.clone();
+        create_legacy_db(&db_path, options, &secp);
+
+        // Populate all the tables of the main wallet.
+        let tx = bitcoin::Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::from_height(1).unwrap(),
+            input: vec![bitcoin::TxIn::default()], // a single input
+            output: vec![bitcoin::TxOut::minimal_non_dust(ScriptBuf::default())], // a single output,
+        };
+        let txid = tx.compute_txid();
+        let psbt = Psbt::from_unsigned_tx(tx.clone()).unwrap();
+        let watchonly_desc = Descriptor::<DescriptorPublicKey>::from_str("wpkh(tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/<0;1>/*)").unwrap();
+        let watchonly_address = watchonly_desc
+            .clone()
+            .into_single_descriptors()
+            .unwrap()
+            .remove(0)
+            .at_derivation_index(0)
+            .unwrap()
+            .address(bitcoin::Network::Bitcoin)
+            .unwrap();
+        {
+            let mut conn = rusqlite::Connection::open(&db_path).unwrap();
+            db_exec(&mut conn, |db_tx| {
+                db_tx.execute(
+                    "INSERT INTO transactions (txid, tx, num_inputs, num_outputs, is_coinbase) \
+                     VALUES (?1, ?2, 1, 1, 0)",
+                    rusqlite::params![txid[..].to_vec(), bitcoin::consensus::serialize(&tx)],
+                )?;
+                db_tx.execute(
+                    "INSERT INTO coins (wallet_id, txid, vout, amount_sat, derivation_index, is_change, is_immature) \
+                     VALUES (?1, ?2, 0, 10000, 3, 0, 0)",
+                    rusqlite::params![MAIN_WALLET_ID, txid[..].to_vec()],
+                )?;
+                db_tx.execute(
+                    "INSERT INTO spend_transactions (psbt, txid, updated_at) VALUES (?1, ?2, 42)",
+                    rusqlite::params![psbt.serialize(), txid[..].to_vec()],
+                )?;
+                db_tx.execute(
+                    "INSERT INTO labels (wallet_id, item_kind, item, value) VALUES (?1, 2, ?2, 'hello')",
+                    rusqlite::params![MAIN_WALLET_ID, txid.to_string()],
+                )?;
+                db_tx.execute(
+                    "INSERT INTO watchonly_descriptors (wallet_id, descriptor, timestamp) VALUES (?1, ?2, 1000)",
+                    rusqlite::params![MAIN_WALLET_ID, watchonly_desc.to_string()],
+                )?;
+                db_tx.execute(
+                    "INSERT INTO watchonly_coins (descriptor_id, txid, vout, amount_sat, address, derivation_index) \
+                     VALUES (1, ?1, 0, 5000, ?2, 0)",
+                    rusqlite::params![txid[..].to_vec(), watchonly_address.to_string()],
+                )?;
+                db_tx.execute(
+                    "INSERT INTO signed_spends (wallet_id, txid, amount_sat, signed_at) VALUES (?1, ?2, 7000, 100)",
+                    rusqlite::params![MAIN_WALLET_ID, txid[..].to_vec()],
+                )?;
+                db_tx.execute(
+                    "INSERT INTO broadcast_spends (wallet_id, txid, amount_sat, broadcast_at) VALUES (?1, ?2, 7000, 100)",
+                    rusqlite::params![MAIN_WALLET_ID, txid[..].to_vec()],
+                )?;
+                Ok(())
+            })
+            .unwrap();
+        }
+
+        // Migrate the DB. Migrating twice is a no-op.
+        maybe_apply_migration(&db_path, &[]).unwrap();
+        maybe_apply_migration(&db_path, &[]).unwrap();
+        let db = SqliteDb::new(db_path, None, &secp).unwrap();
+        db.sanity_check(bitcoin::Network::Bitcoin, &main_descriptor)
+            .unwrap();
+
+        // All the data was kept for the main wallet.
+        {
+            let mut conn = db.connection().unwrap();
+            assert_eq!(conn.db_version(), 13);
+            assert_eq!(conn.db_tip().network, bitcoin::Network::Bitcoin);
+            assert_eq!(conn.db_wallet().main_descriptor, main_descriptor);
+            let receive_address = main_descriptor
+                .receive_descriptor()
+                .derive(0.into(), &secp)
+                .address(bitcoin::Network::Bitcoin);
+            assert_eq!(
+                conn.db_address(&receive_address).unwrap().derivation_index,
+                0.into()
+            );
+            let coins = conn.coins(&[], &[]);
+            assert_eq!(coins.len(), 1);
+            assert_eq!(coins[0].outpoint, bitcoin::OutPoint::new(txid, 0));
+            let spends = conn.list_spend();
+            assert_eq!(spends.len(), 1);
+            assert_eq!((&spends[0].psbt, spends[0].updated_at), (&psbt, Some(42)));
+            let item = LabelItem::from_str(&txid.to_string(), bitcoin::Network::Bitcoin).unwrap();
+            let labels = conn.db_labels(&HashSet::from([item]));
+            assert_eq!(labels[0].value, "hello");
+            assert_eq!(conn.db_watchonly_descriptors().len(), 1);
+            let watchonly_coins = conn.db_watchonly_coins();
+            assert_eq!(watchonly_coins.len(), 1);
+            assert_eq!(watchonly_coins[0].descriptor, watchonly_desc);
+            assert_eq!(
+                conn.signed_spends_since(0),
+                vec![(txid, bitcoin::Amount::from_sat(7000))]
+            );
+            assert_eq!(
+                conn.broadcast_spends_since(0),
+                vec![(txid, bitcoin::Amount::from_sat(7000), 100)]
+            );
+        }
+
+        // We can now store other wallets in the database.
+        assert!(db.wallet_names().unwrap().is_empty());
+        db.create_wallet("other", &main_descriptor, &secp).unwrap();
+        assert_eq!(db.wallet_names().unwrap(), vec!["other".to_string()]);
+
+        fs::remove_dir_all(tmp_dir).unwrap();
+    }
+
+    #[test]
+    fn sqlite_multiple_wallets() {
+        let (tmp_dir, options, secp, db) = dummy_db();
+        let other_desc_str = "wsh(andor(pk([aabbccdd]tpubDExU4YLJkyQ9RRbVScQq2brFxWWha7WmAUByPWyaWYwmcTv3Shx8aHp6mVwuE5n4TeM4z5DTWGf2YhNPmXtfvyr8cUDVvA3txdrFnFgNdF7/<0;1>/*),older(10000),pk([aabbccdd]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))";
+        let other_desc = LianaDescriptor::from_str(other_desc_str).unwrap();
+
+        // At first only the main wallet is stored.
+        assert!(db.wallet_names().unwrap().is_empty());
+        assert!(db.wallet("other").unwrap().is_none());
+
+        // Create another wallet. It can be retrieved by name, but not created twice.
+        let other = db.create_wallet("other", &other_desc, &secp).unwrap();
+        assert_eq!(db.wallet_names().unwrap(), vec!["other".to_string()]);
+        assert!(db.wallet("other").unwrap().is_some());
+        assert!(db.create_wallet("other", &other_desc, &secp).is_err());
+        assert_eq!(db.main_descriptor().unwrap(), options.main_descriptor);
+        assert_eq!(other.main_descriptor().unwrap(), other_desc);
+        other
+            .sanity_check(bitcoin::Network::Bitcoin, &other_desc)
+            .unwrap();
+
+        let mut main_conn = db.connection().unwrap();
+        let mut other_conn = other.connection().unwrap();
+
+        // Each wallet has its own tip.
+        let tip = BlockChainTip {
+            height: 100,
+            hash: BlockHash::from_str(
+                "000000000000000000016f3ee4dbf1a0b4ee2e29abf68d1b21f8b3bdc35e3cd3",
+            )
+            .unwrap(),
+        };
+        main_conn.update_tip(&tip);
+        assert_eq!(main_conn.db_tip().block_height, Some(100));
+        assert_eq!(other_conn.db_tip().block_height, None);
+
+        // Each wallet has its own addresses.
+        let main_address = options
+            .main_descriptor
+            .receive_descriptor()
+            .derive(0.into(), &secp)
+            .address(bitcoin::Network::Bitcoin);
+        let other_address = other_desc
+            .receive_descriptor()
+            .derive(0.into(), &secp)
+            .address(bitcoin::Network::Bitcoin);
+        assert!(main_conn.db_address(&main_address).is_some());
+        assert!(main_conn.db_address(&other_address).is_none());
+        assert!(other_conn.db_address(&main_address).is_none());
+        assert!(other_conn.db_address(&other_address).is_some());
+        main_conn.set_derivation_index(10.into(), false, &secp);
+        assert_eq!(main_conn.db_wallet().deposit_derivation_index, 10.into());
+        assert_eq!(other_conn.db_wallet().deposit_derivation_index, 0.into());
+
+        // The same coin may be stored for both wallets, and is only updated for one.
+        let tx = bitcoin::Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::from_height(1).unwrap(),
+            input: vec![bitcoin::TxIn::default()], // a single input
+            output: vec![bitcoin::TxOut::minimal_non_dust(ScriptBuf::default())], // a single output,
+        };
+        let outpoint = bitcoin::OutPoint::new(tx.compute_txid(), 0);
+        let coin = Coin {
+            outpoint,
+            is_immature: false,
+            block_info: None,
+            amount: bitcoin::Amount::from_sat(98765),
+            derivation_index: bip32::ChildNumber::from_normal_idx(10).unwrap(),
+            is_change: false,
+            spend_txid: None,
+            spend_block: None,
+            is_from_self: false,
+            is_frozen: false,
+        };
+        main_conn.new_txs(&[tx.clone()]);
+        main_conn.new_unspent_coins(&[coin]);
+        assert_eq!(main_conn.coins(&[], &[]).len(), 1);
+        assert!(other_conn.coins(&[], &[]).is_empty());
+        other_conn.new_txs(&[tx.clone()]);
+        other_conn.new_unspent_coins(&[coin]);
+        other_conn.set_coins_frozen(&[outpoint], true);
+        assert!(!main_conn.coins(&[], &[])[0].is_frozen);
+        assert!(other_conn.coins(&[], &[])[0].is_frozen);
+        assert_eq!(
+            main_conn
+                .list_wallet_transactions(&[tx.compute_txid()])
+                .len(),
+            1
+        );
+        other_conn.remove_coins(&[outpoint]);
+        assert_eq!(main_conn.coins(&[], &[]).len(), 1);
+        assert!(other_conn.coins(&[], &[]).is_empty());
+
+        // The same item may be labelled differently by each wallet.
+        let item = LabelItem::from(outpoint);
+        main_conn.update_labels(&HashMap::from([(item.clone(), Some("main".to_string()))]));
+        other_conn.update_labels(&HashMap::from([(item.clone(), Some("other".to_string()))]));
+        let items = HashSet::from([item]);
+        assert_eq!(main_conn.db_labels(&items)[0].value, "main");
+        assert_eq!(other_conn.db_labels(&items)[0].value, "other");
+
+        // The same Spend transaction may be stored by both wallets and deleted for one.
+        let psbt = Psbt::from_unsigned_tx(tx.clone()).unwrap();
+        main_conn.store_spend(&psbt);
+        assert!(other_conn.list_spend().is_empty());
+        other_conn.store_spend(&psbt);
+        other_conn.delete_spend(&tx.compute_txid());
+        assert_eq!(main_conn.list_spend().len(), 1);
+        assert!(other_conn.list_spend().is_empty());
+
+        // Signed and broadcast Spend transactions are recorded per wallet.
+        main_conn.record_signed_spend(&tx.compute_txid(), bitcoin::Amount::from_sat(1_000), 10);
+        main_conn.record_broadcast_spend(&tx.compute_txid(), bitcoin::Amount::from_sat(1_000), 10);
+        assert_eq!(main_conn.signed_spends_since(0).len(), 1);
+        assert!(other_conn.signed_spends_since(0).is_empty());
+        assert_eq!(main_conn.broadcast_spends_since(0).len(), 1);
+        assert!(other_conn.broadcast_spends_since(0).is_empty());
+
+        fs::remove_dir_all(tmp_dir).unwrap();
+    }
+
+    #[test]
+    fn sqlite_import_wallet() {
+        let (tmp_dir, _, secp, db) = dummy_db();
+        let other_desc_str = "wsh(andor(pk([aabbccdd]tpubDExU4YLJkyQ9RRbVScQq2brFxWWha7WmAUByPWyaWYwmcTv3Shx8aHp6mVwuE5n4TeM4z5DTWGf2YhNPmXtfvyr8cUDVvA3txdrFnFgNdF7/<0;1>/*),older(10000),pk([aabbccdd]tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/<0;1>/*)))";
+        let other_desc = LianaDescriptor::from_str(other_desc_str).unwrap();
+
+        // Create the database of another wallet, on its own, and populate it.
+        let other_path: path::PathBuf = [tmp_dir.as_path(), path::Path::new("other.sqlite3")]
+            .iter()
+            .collect();
+        let other_db = SqliteDb::new(
+            other_path.clone(),
+            Some(FreshDbOptions::new(
+                bitcoin::Network::Bitcoin,
+                other_desc.clone(),
+            )),
+            &secp,
+        )
+        .unwrap();
+        let tx = bitcoin::Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::from_height(1).unwrap(),
+            input: vec![bitcoin::TxIn::default()], // a single input
+            output: vec![bitcoin::TxOut::minimal_non_dust(ScriptBuf::default())], // a single output,
+        };
+        let outpoint = bitcoin::OutPoint::new(tx.compute_txid(), 0);
+        let psbt = Psbt::from_unsigned_tx(tx.clone()).unwrap();
+        {
+            let mut conn = other_db.connection().unwrap();
+            conn.set_derivation_index(5.into(), true, &secp);
+            conn.new_txs(&[tx.clone()]);
+            conn.new_unspent_coins(&[Coin {
+                outpoint,
+                is_immature: false,
+                block_info: None,
+                amount: bitcoin::Amount::from_sat(98765),
+                derivation_index: bip32::ChildNumber::from_normal_idx(10).unwrap(),
+                is_change: false,
+                spend_txid: None,
+                spend_block: None,
+                is_from_self: false,
+                is_frozen: false,
+            }]);
+            conn.update_labels(&HashMap::from([(
+                LabelItem::from(outpoint),
+                Some("imported".to_string()),
+            )]));
+            conn.store_spend(&psbt);
+        }
+
+        // Import it into the main database. All its data was copied.
+        let imported = db.import_wallet("imported", &other_path).unwrap();
+        assert_eq!(db.wallet_names().unwrap(), vec!["imported".to_string()]);
+        imported
+            .sanity_check(bitcoin::Network::Bitcoin, &other_desc)
+            .unwrap();
+        {
+            let mut conn = imported.connection().unwrap();
+            assert_eq!(conn.db_wallet().change_derivation_index, 5.into());
+            let coins = conn.coins(&[], &[]);
+            assert_eq!(coins.len(), 1);
+            assert_eq!(coins[0].outpoint, outpoint);
+            let items = HashSet::from([LabelItem::from(outpoint)]);
+            assert_eq!(conn.db_labels(&items)[0].value, "imported");
+            assert_eq!(conn.list_spend()[0].psbt, psbt);
+            let other_address = other_desc
+                .receive_descriptor()
+                .derive(0.into(), &secp)
+                .address(bitcoin::Network::Bitcoin);
+            assert!(conn.db_address(&other_address).is_some());
+        }
+        // The main wallet is left untouched.
+        assert!(db.connection().unwrap().coins(&[], &[]).is_empty());
+
+        // It can't be imported twice under the same name.
+        assert!(db.import_wallet("imported", &other_path).is_err());
+
+        // A database for another network can't be imported.
+        let testnet_path: path::PathBuf = [tmp_dir.as_path(), path::Path::new("testnet.sqlite3")]
+            .iter()
+            .collect();
+        SqliteDb::new(
+            testnet_path.clone(),
+            Some(FreshDbOptions::new(bitcoin::Network::Testnet, other_desc)),
+            &secp,
+        )
+        .unwrap();
+        assert!(db.import_wallet("testnet", &testnet_path).is_err());
+        assert!(db.wallet("testnet").unwrap().is_none());
+
+        fs::remove_dir_all(tmp_dir).unwrap();
+    }
 }
@@ -3,13 +3,16 @@ use liana::descriptors::LianaDescriptor;
 
 use std::{convert::TryFrom, str::FromStr};
 
-use miniscript::bitcoin::{
-    self,
-    address::{self, NetworkUnchecked},
-    bip32,
-    consensus::encode,
-    psbt::Psbt,
-    Address, OutPoint, Txid,
+use miniscript::{
+    bitcoin::{
+        self,
+        address::{self, NetworkUnchecked},
+        bip32,
+        consensus::encode,
+        psbt::Psbt,
+        Address, OutPoint, Txid,
+    },
+    descriptor::{Descriptor, DescriptorPublicKey},
 };
 
 // Due to limitations of Sqlite's ALTER TABLE command and in order not to recreate
@@ -22,15 +25,9 @@ CREATE TABLE version (
     version INTEGER NOT NULL
 );
 
-/* About the Bitcoin network. */
-CREATE TABLE tip (
-    network TEXT NOT NULL,
-    blockheight INTEGER,
-    blockhash BLOB
-);
-
-/* This stores metadata about our wallet. We only support single wallet for
- * now (and the foreseeable future).
+/* This stores metadata about our wallets. The main wallet is the one the database was
+ * created with, it always has id 1 and no name. The other wallets hosted by the daemon
+ * are identified by their name.
  *
  * The 'timestamp' field is the creation date of the wallet. We guarantee to have seen all
  * information related to our descriptor(s) that occurred after this date.
@@ -44,7 +41,20 @@ CREATE TABLE wallets (
     deposit_derivation_index INTEGER NOT NULL,
     change_derivation_index INTEGER NOT NULL,
     rescan_timestamp INTEGER,
-    last_poll_timestamp INTEGER
+    last_poll_timestamp INTEGER,
+    name TEXT
+);
+CREATE UNIQUE INDEX wallets_name ON wallets (name);
+
+/* About the Bitcoin network, as last seen by each wallet. */
+CREATE TABLE tip (
+    wallet_id INTEGER UNIQUE NOT NULL,
+    network TEXT NOT NULL,
+    blockheight INTEGER,
+    blockhash BLOB,
+    FOREIGN KEY (wallet_id) REFERENCES wallets (id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT
 );
 
 /* Our (U)TxOs.
@@ -59,6 +69,9 @@ CREATE TABLE wallets (
  * The `is_from_self` field indicates if the coin is the output of a transaction whose
  * inputs are all from the same wallet as the coin. For an unconfirmed coin, this also
  * means that all unconfirmed ancestors, if any, are from self.
+ *
+ * The `is_frozen` field is set by the user for coins which must not be selected
+ * automatically when creating a transaction.
  */
 CREATE TABLE coins (
     id INTEGER PRIMARY KEY NOT NULL,
@@ -75,7 +88,8 @@ CREATE TABLE coins (
     spend_block_time INTEGER,
     is_immature BOOLEAN NOT NULL CHECK (is_immature IN (0,1)),
     is_from_self BOOLEAN NOT NULL DEFAULT 0 CHECK (is_from_self IN (0,1)),
-    UNIQUE (txid, vout),
+    is_frozen BOOLEAN NOT NULL DEFAULT 0 CHECK (is_frozen IN (0,1)),
+    UNIQUE (wallet_id, txid, vout),
     FOREIGN KEY (wallet_id) REFERENCES wallets (id)
         ON UPDATE RESTRICT
         ON DELETE RESTRICT,
@@ -91,9 +105,16 @@ CREATE TABLE coins (
  * we can get the derivation index from the parent descriptor from bitcoind.
  */
 CREATE TABLE addresses (
-    receive_address TEXT NOT NULL UNIQUE,
-    change_address TEXT NOT NULL UNIQUE,
-    derivation_index INTEGER NOT NULL UNIQUE
+    wallet_id INTEGER NOT NULL,
+    receive_address TEXT NOT NULL,
+    change_address TEXT NOT NULL,
+    derivation_index INTEGER NOT NULL,
+    UNIQUE (wallet_id, receive_address),
+    UNIQUE (wallet_id, change_address),
+    UNIQUE (wallet_id, derivation_index),
+    FOREIGN KEY (wallet_id) REFERENCES wallets (id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT
 );
 
 /* Transactions for all wallets. */
@@ -109,9 +130,86 @@ CREATE TABLE transactions (
 /* Transactions we created that spend some of our coins. */
 CREATE TABLE spend_transactions (
     id INTEGER PRIMARY KEY NOT NULL,
-    psbt BLOB UNIQUE NOT NULL,
-    txid BLOB UNIQUE NOT NULL,
-    updated_at INTEGER
+    wallet_id INTEGER NOT NULL,
+    psbt BLOB NOT NULL,
+    txid BLOB NOT NULL,
+    updated_at INTEGER,
+    UNIQUE (wallet_id, psbt),
+    UNIQUE (wallet_id, txid),
+    FOREIGN KEY (wallet_id) REFERENCES wallets (id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT
+);
+
+/* Additional watch-only descriptors, which need not be Liana descriptors, whose coins we
+ * track alongside the main descriptor's. For instance those of a former wallet whose funds
+ * are to be swept into this one.
+ *
+ * The 'timestamp' field is the date from which we looked for coins of this descriptor.
+ */
+CREATE TABLE watchonly_descriptors (
+    id INTEGER PRIMARY KEY NOT NULL,
+    wallet_id INTEGER NOT NULL,
+    descriptor TEXT NOT NULL,
+    timestamp INTEGER NOT NULL,
+    UNIQUE (wallet_id, descriptor),
+    FOREIGN KEY (wallet_id) REFERENCES wallets (id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT
+);
+
+/* The unspent coins of the watch-only descriptors, as of the last poll.
+ *
+ * The 'blockheight' field is only present if the transaction creating the coin was confirmed.
+ */
+CREATE TABLE watchonly_coins (
+    id INTEGER PRIMARY KEY NOT NULL,
+    descriptor_id INTEGER NOT NULL,
+    txid BLOB NOT NULL,
+    vout INTEGER NOT NULL,
+    amount_sat INTEGER NOT NULL,
+    address TEXT NOT NULL,
+    derivation_index INTEGER NOT NULL,
+    blockheight INTEGER,
+    UNIQUE (descriptor_id, txid, vout),
+    FOREIGN KEY (descriptor_id) REFERENCES watchonly_descriptors (id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT
+);
+
+/* The spends signed by our hot signers, along with the value they send out of the wallet
+ * (fees included). Used to enforce the daily limit of the signing policy.
+ *
+ * The 'signed_at' field is the date at which the spend was first signed.
+ */
+CREATE TABLE signed_spends (
+    id INTEGER PRIMARY KEY NOT NULL,
+    wallet_id INTEGER NOT NULL,
+    txid BLOB NOT NULL,
+    amount_sat INTEGER NOT NULL,
+    signed_at INTEGER NOT NULL,
+    UNIQUE (wallet_id, txid),
+    FOREIGN KEY (wallet_id) REFERENCES wallets (id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT
+);
+
+/* The spends broadcast by the daemon while a spending policy was set, along with the value
+ * they send out of the wallet (fees included). Used to enforce the rolling window limits of
+ * the spending policy.
+ *
+ * The 'broadcast_at' field is the date at which the spend was first broadcast.
+ */
+CREATE TABLE broadcast_spends (
+    id INTEGER PRIMARY KEY NOT NULL,
+    wallet_id INTEGER NOT NULL,
+    txid BLOB NOT NULL,
+    amount_sat INTEGER NOT NULL,
+    broadcast_at INTEGER NOT NULL,
+    UNIQUE (wallet_id, txid),
+    FOREIGN KEY (wallet_id) REFERENCES wallets (id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT
 );
 
 /* Labels applied on addresses (0), outpoints (1), txids (2) */
@@ -119,8 +217,12 @@ CREATE TABLE labels (
     id INTEGER PRIMARY KEY NOT NULL,
     wallet_id INTEGER NOT NULL,
     item_kind INTEGER NOT NULL CHECK (item_kind IN (0,1,2)),
-    item TEXT UNIQUE NOT NULL,
-    value TEXT NOT NULL
+    item TEXT NOT NULL,
+    value TEXT NOT NULL,
+    UNIQUE (wallet_id, item),
+    FOREIGN KEY (wallet_id) REFERENCES wallets (id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT
 );
 ";
 
@@ -136,12 +238,12 @@ impl TryFrom<&rusqlite::Row<'_>> for DbTip {
     type Error = rusqlite::Error;
 
     fn try_from(row: &rusqlite::Row) -> Result<Self, Self::Error> {
-        let network: String = row.get(0)?;
+        let network: String = row.get(1)?;
         let network = bitcoin::Network::from_str(&network)
             .expect("Insane database: can't parse network string");
 
-        let block_height: Option<i32> = row.get(1)?;
-        let block_hash: Option<Vec<u8>> = row.get(2)?;
+        let block_height: Option<i32> = row.get(2)?;
+        let block_hash: Option<Vec<u8>> = row.get(3)?;
         let block_hash: Option<bitcoin::BlockHash> = block_hash
             .map(|h| encode::deserialize(&h).expect("Insane database: can't parse network string"));
 
@@ -222,6 +324,8 @@ pub struct DbCoin {
     /// be from self, as otherwise they will depend on an unconfirmed
     /// external transaction.
     pub is_from_self: bool,
+    /// Whether the user asked for this coin not to be selected automatically.
+    pub is_frozen: bool,
 }
 
 impl TryFrom<&rusqlite::Row<'_>> for DbCoin {
@@ -262,6 +366,7 @@ impl TryFrom<&rusqlite::Row<'_>> for DbCoin {
 
         let is_immature: bool = row.get(12)?;
         let is_from_self: bool = row.get(13)?;
+        let is_frozen: bool = row.get(14)?;
 
         Ok(DbCoin {
             id,
@@ -275,6 +380,7 @@ impl TryFrom<&rusqlite::Row<'_>> for DbCoin {
             spend_txid,
             spend_block,
             is_from_self,
+            is_frozen,
         })
     }
 }
@@ -290,15 +396,15 @@ impl TryFrom<&rusqlite::Row<'_>> for DbAddress {
     type Error = rusqlite::Error;
 
     fn try_from(row: &rusqlite::Row) -> Result<Self, Self::Error> {
-        let receive_address: String = row.get(0)?;
+        let receive_address: String = row.get(1)?;
         let receive_address =
             bitcoin::Address::from_str(&receive_address).expect("We only store valid addresses");
 
-        let change_address: String = row.get(1)?;
+        let change_address: String = row.get(2)?;
         let change_address =
             bitcoin::Address::from_str(&change_address).expect("We only store valid addresses");
 
-        let derivation_index: u32 = row.get(2)?;
+        let derivation_index: u32 = row.get(3)?;
         let derivation_index = bip32::ChildNumber::from(derivation_index);
         assert!(derivation_index.is_normal());
 
@@ -325,14 +431,14 @@ impl TryFrom<&rusqlite::Row<'_>> for DbSpendTransaction {
     fn try_from(row: &rusqlite::Row) -> Result<Self, Self::Error> {
         let id: i64 = row.get(0)?;
 
-        let psbt: Vec<u8> = row.get(1)?;
+        let psbt: Vec<u8> = row.get(2)?;
         let psbt = Psbt::deserialize(&psbt).expect("We only store valid PSBTs");
 
-        let txid: Vec<u8> = row.get(2)?;
+        let txid: Vec<u8> = row.get(3)?;
         let txid: bitcoin::Txid = encode::deserialize(&txid).expect("We only store valid txids");
         assert_eq!(txid, psbt.unsigned_tx.compute_txid());
 
-        let updated_at = row.get(3)?;
+        let updated_at = row.get(4)?;
 
         Ok(DbSpendTransaction {
             id,
@@ -428,6 +534,78 @@ impl TryFrom<&rusqlite::Row<'_>> for DbLabel {
     }
 }
 
+/// A row in the "watchonly_descriptors" table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbWatchonlyDescriptor {
+    pub id: i64,
+    pub descriptor: Descriptor<DescriptorPublicKey>,
+    pub timestamp: u32,
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for DbWatchonlyDescriptor {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row) -> Result<Self, Self::Error> {
+        let id = row.get(0)?;
+        let descriptor: String = row.get(1)?;
+        let descriptor = Descriptor::<DescriptorPublicKey>::from_str(&descriptor)
+            .expect("Insane database: can't parse watch-only descriptor");
+        let timestamp = row.get(2)?;
+
+        Ok(DbWatchonlyDescriptor {
+            id,
+            descriptor,
+            timestamp,
+        })
+    }
+}
+
+/// A row in the "watchonly_coins" table, along with the descriptor of the coin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbWatchonlyCoin {
+    pub id: i64,
+    pub descriptor: Descriptor<DescriptorPublicKey>,
+    pub outpoint: bitcoin::OutPoint,
+    pub amount: bitcoin::Amount,
+    pub address: bitcoin::Address<address::NetworkUnchecked>,
+    pub derivation_index: bip32::ChildNumber,
+    pub block_height: Option<i32>,
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for DbWatchonlyCoin {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row) -> Result<Self, Self::Error> {
+        let id = row.get(0)?;
+        let descriptor: String = row.get(1)?;
+        let descriptor = Descriptor::<DescriptorPublicKey>::from_str(&descriptor)
+            .expect("Insane database: can't parse watch-only descriptor");
+
+        let txid: Vec<u8> = row.get(2)?;
+        let txid: bitcoin::Txid = encode::deserialize(&txid).expect("We only store valid txids");
+        let vout = row.get(3)?;
+        let outpoint = bitcoin::OutPoint { txid, vout };
+
+        let amount = row.get(4)?;
+        let amount = bitcoin::Amount::from_sat(amount);
+        let address: String = row.get(5)?;
+        let address = bitcoin::Address::from_str(&address).expect("We only store valid addresses");
+        let der_idx: u32 = row.get(6)?;
+        let derivation_index = bip32::ChildNumber::from(der_idx);
+        let block_height = row.get(7)?;
+
+        Ok(DbWatchonlyCoin {
+            id,
+            descriptor,
+            outpoint,
+            amount,
+            address,
+            derivation_index,
+            block_height,
+        })
+    }
+}
+
 /// A transaction together with its block info.
 #[derive(Clone, Debug, PartialEq)]
 pub struct DbWalletTransaction {
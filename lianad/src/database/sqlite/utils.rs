@@ -1,4 +1,5 @@
 use crate::database::sqlite::{FreshDbOptions, SqliteDbError, DB_VERSION};
+use liana::descriptors::LianaDescriptor;
 
 use std::{convert::TryInto, fs, path, time};
 
@@ -97,35 +98,56 @@ pub fn create_db_file(db_path: &path::Path) -> Result<(), std::io::Error> {
     };
 }
 
-/// Create a fresh Liana database with the given schema.
-pub fn create_fresh_db(
-    db_path: &path::Path,
-    options: FreshDbOptions,
+/// Insert a new wallet in database, along with its tip and the first entries of its address to
+/// derivation index mapping. Returns the id of the new wallet.
+pub fn insert_wallet(
+    db_tx: &rusqlite::Transaction,
+    name: Option<&str>,
+    network: bitcoin::Network,
+    main_descriptor: &LianaDescriptor,
     secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
-) -> Result<(), SqliteDbError> {
-    create_db_file(db_path)?;
-
-    let timestamp = curr_timestamp();
-
-    // Fill the initial addresses. On a fresh database, the deposit_derivation_index is
-    // necessarily 0.
+) -> rusqlite::Result<i64> {
+    db_tx.execute(
+        "INSERT INTO wallets (timestamp, main_descriptor, deposit_derivation_index, change_derivation_index, name) \
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![curr_timestamp(), main_descriptor.to_string(), 0, 0, name],
+    )?;
+    let wallet_id = db_tx.last_insert_rowid();
+    db_tx.execute(
+        "INSERT INTO tip (wallet_id, network, blockheight, blockhash) VALUES (?1, ?2, NULL, NULL)",
+        rusqlite::params![wallet_id, network.to_string()],
+    )?;
+
+    // Fill the initial addresses. On a fresh wallet, the deposit_derivation_index is necessarily
+    // 0.
     let mut query = String::with_capacity(100 * LOOK_AHEAD_LIMIT as usize);
     for index in 0..LOOK_AHEAD_LIMIT {
-        let receive_address = options
-            .main_descriptor
+        let receive_address = main_descriptor
             .receive_descriptor()
             .derive(index.into(), secp)
-            .address(options.bitcoind_network);
-        let change_address = options
-            .main_descriptor
+            .address(network);
+        let change_address = main_descriptor
             .change_descriptor()
             .derive(index.into(), secp)
-            .address(options.bitcoind_network);
+            .address(network);
         query += &format!(
-            "INSERT INTO addresses (receive_address, change_address, derivation_index) VALUES (\"{}\", \"{}\", {});\n",
-            receive_address, change_address, index
+            "INSERT INTO addresses (wallet_id, receive_address, change_address, derivation_index) VALUES ({}, \"{}\", \"{}\", {});\n",
+            wallet_id, receive_address, change_address, index
         );
     }
+    db_tx.execute_batch(&query)?;
+
+    Ok(wallet_id)
+}
+
+/// Create a fresh Liana database with the given schema. The wallet of the given descriptor is
+/// its main wallet.
+pub fn create_fresh_db(
+    db_path: &path::Path,
+    options: FreshDbOptions,
+    secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+) -> Result<(), SqliteDbError> {
+    create_db_file(db_path)?;
 
     let mut conn = rusqlite::Connection::open(db_path)?;
     db_exec(&mut conn, |tx| {
@@ -134,16 +156,13 @@ pub fn create_fresh_db(
             "INSERT INTO version (version) VALUES (?1)",
             rusqlite::params![options.version],
         )?;
-        tx.execute(
-            "INSERT INTO tip (network, blockheight, blockhash) VALUES (?1, NULL, NULL)",
-            rusqlite::params![options.bitcoind_network.to_string()],
+        insert_wallet(
+            tx,
+            None,
+            options.bitcoind_network,
+            &options.main_descriptor,
+            secp,
         )?;
-        tx.execute(
-            "INSERT INTO wallets (timestamp, main_descriptor, deposit_derivation_index, change_derivation_index) \
-                     VALUES (?1, ?2, ?3, ?4)",
-            rusqlite::params![timestamp, options.main_descriptor.to_string(), 0, 0],
-        )?;
-        tx.execute_batch(&query)?;
 
         Ok(())
     })?;
@@ -482,6 +501,283 @@ fn migrate_v7_to_v8(conn: &mut rusqlite::Connection) -> Result<(), SqliteDbError
     Ok(())
 }
 
+fn migrate_v8_to_v9(conn: &mut rusqlite::Connection) -> Result<(), SqliteDbError> {
+    db_exec(conn, |db_tx| {
+        db_tx.execute_batch(
+            "
+            ALTER TABLE coins ADD COLUMN is_frozen BOOLEAN NOT NULL DEFAULT 0 CHECK (is_frozen IN (0,1));
+
+            UPDATE version SET version = 9;
+            ",
+        )?;
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+fn migrate_v9_to_v10(conn: &mut rusqlite::Connection) -> Result<(), SqliteDbError> {
+    db_exec(conn, |db_tx| {
+        db_tx.execute_batch(
+            "
+            CREATE TABLE watchonly_descriptors (
+                id INTEGER PRIMARY KEY NOT NULL,
+                wallet_id INTEGER NOT NULL,
+                descriptor TEXT UNIQUE NOT NULL,
+                timestamp INTEGER NOT NULL,
+                FOREIGN KEY (wallet_id) REFERENCES wallets (id)
+                    ON UPDATE RESTRICT
+                    ON DELETE RESTRICT
+            );
+
+            CREATE TABLE watchonly_coins (
+                id INTEGER PRIMARY KEY NOT NULL,
+                descriptor_id INTEGER NOT NULL,
+                txid BLOB NOT NULL,
+                vout INTEGER NOT NULL,
+                amount_sat INTEGER NOT NULL,
+                address TEXT NOT NULL,
+                derivation_index INTEGER NOT NULL,
+                blockheight INTEGER,
+                UNIQUE (txid, vout),
+                FOREIGN KEY (descriptor_id) REFERENCES watchonly_descriptors (id)
+                    ON UPDATE RESTRICT
+                    ON DELETE RESTRICT
+            );
+
+            UPDATE version SET version = 10;
+            ",
+        )?;
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+fn migrate_v10_to_v11(conn: &mut rusqlite::Connection) -> Result<(), SqliteDbError> {
+    db_exec(conn, |db_tx| {
+        db_tx.execute_batch(
+            "
+            CREATE TABLE signed_spends (
+                id INTEGER PRIMARY KEY NOT NULL,
+                wallet_id INTEGER NOT NULL,
+                txid BLOB UNIQUE NOT NULL,
+                amount_sat INTEGER NOT NULL,
+                signed_at INTEGER NOT NULL,
+                FOREIGN KEY (wallet_id) REFERENCES wallets (id)
+                    ON UPDATE RESTRICT
+                    ON DELETE RESTRICT
+            );
+
+            UPDATE version SET version = 11;
+            ",
+        )?;
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+fn migrate_v11_to_v12(conn: &mut rusqlite::Connection) -> Result<(), SqliteDbError> {
+    db_exec(conn, |db_tx| {
+        db_tx.execute_batch(
+            "
+            CREATE TABLE broadcast_spends (
+                id INTEGER PRIMARY KEY NOT NULL,
+                wallet_id INTEGER NOT NULL,
+                txid BLOB UNIQUE NOT NULL,
+                amount_sat INTEGER NOT NULL,
+                broadcast_at INTEGER NOT NULL,
+                FOREIGN KEY (wallet_id) REFERENCES wallets (id)
+                    ON UPDATE RESTRICT
+                    ON DELETE RESTRICT
+            );
+
+            UPDATE version SET version = 12;
+            ",
+        )?;
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+// To host several wallets in a single database, the tip, addresses and Spend transactions tables
+// are now per wallet and the constraints of all tables are per wallet. Wallets other than the main
+// one are identified by a name.
+fn migrate_v12_to_v13(conn: &mut rusqlite::Connection) -> Result<(), SqliteDbError> {
+    db_exec(conn, |db_tx| {
+        db_tx.execute_batch(
+            "
+            ALTER TABLE wallets ADD COLUMN name TEXT;
+            CREATE UNIQUE INDEX wallets_name ON wallets (name);
+
+            CREATE TABLE tip_new (
+                wallet_id INTEGER UNIQUE NOT NULL,
+                network TEXT NOT NULL,
+                blockheight INTEGER,
+                blockhash BLOB,
+                FOREIGN KEY (wallet_id) REFERENCES wallets (id)
+                    ON UPDATE RESTRICT
+                    ON DELETE RESTRICT
+            );
+            INSERT INTO tip_new SELECT 1, network, blockheight, blockhash FROM tip;
+            DROP TABLE tip;
+            ALTER TABLE tip_new RENAME TO tip;
+
+            CREATE TABLE coins_new (
+                id INTEGER PRIMARY KEY NOT NULL,
+                wallet_id INTEGER NOT NULL,
+                blockheight INTEGER,
+                blocktime INTEGER,
+                txid BLOB NOT NULL,
+                vout INTEGER NOT NULL,
+                amount_sat INTEGER NOT NULL,
+                derivation_index INTEGER NOT NULL,
+                is_change BOOLEAN NOT NULL CHECK (is_change IN (0,1)),
+                spend_txid BLOB,
+                spend_block_height INTEGER,
+                spend_block_time INTEGER,
+                is_immature BOOLEAN NOT NULL CHECK (is_immature IN (0,1)),
+                is_from_self BOOLEAN NOT NULL DEFAULT 0 CHECK (is_from_self IN (0,1)),
+                is_frozen BOOLEAN NOT NULL DEFAULT 0 CHECK (is_frozen IN (0,1)),
+                UNIQUE (wallet_id, txid, vout),
+                FOREIGN KEY (wallet_id) REFERENCES wallets (id)
+                    ON UPDATE RESTRICT
+                    ON DELETE RESTRICT,
+                FOREIGN KEY (txid) REFERENCES transactions (txid)
+                    ON UPDATE RESTRICT
+                    ON DELETE RESTRICT,
+                FOREIGN KEY (spend_txid) REFERENCES transactions (txid)
+                    ON UPDATE RESTRICT
+                    ON DELETE RESTRICT
+            );
+            INSERT INTO coins_new SELECT * FROM coins;
+            DROP TABLE coins;
+            ALTER TABLE coins_new RENAME TO coins;
+
+            CREATE TABLE addresses_new (
+                wallet_id INTEGER NOT NULL,
+                receive_address TEXT NOT NULL,
+                change_address TEXT NOT NULL,
+                derivation_index INTEGER NOT NULL,
+                UNIQUE (wallet_id, receive_address),
+                UNIQUE (wallet_id, change_address),
+                UNIQUE (wallet_id, derivation_index),
+                FOREIGN KEY (wallet_id) REFERENCES wallets (id)
+                    ON UPDATE RESTRICT
+                    ON DELETE RESTRICT
+            );
+            INSERT INTO addresses_new SELECT 1, receive_address, change_address, derivation_index FROM addresses;
+            DROP TABLE addresses;
+            ALTER TABLE addresses_new RENAME TO addresses;
+
+            CREATE TABLE spend_transactions_new (
+                id INTEGER PRIMARY KEY NOT NULL,
+                wallet_id INTEGER NOT NULL,
+                psbt BLOB NOT NULL,
+                txid BLOB NOT NULL,
+                updated_at INTEGER,
+                UNIQUE (wallet_id, psbt),
+                UNIQUE (wallet_id, txid),
+                FOREIGN KEY (wallet_id) REFERENCES wallets (id)
+                    ON UPDATE RESTRICT
+                    ON DELETE RESTRICT
+            );
+            INSERT INTO spend_transactions_new SELECT id, 1, psbt, txid, updated_at FROM spend_transactions;
+            DROP TABLE spend_transactions;
+            ALTER TABLE spend_transactions_new RENAME TO spend_transactions;
+
+            /* The watch-only coins reference the watch-only descriptors, set them aside while
+             * the descriptors table is recreated. */
+            CREATE TABLE watchonly_coins_old AS SELECT * FROM watchonly_coins;
+            DROP TABLE watchonly_coins;
+
+            CREATE TABLE watchonly_descriptors_new (
+                id INTEGER PRIMARY KEY NOT NULL,
+                wallet_id INTEGER NOT NULL,
+                descriptor TEXT NOT NULL,
+                timestamp INTEGER NOT NULL,
+                UNIQUE (wallet_id, descriptor),
+                FOREIGN KEY (wallet_id) REFERENCES wallets (id)
+                    ON UPDATE RESTRICT
+                    ON DELETE RESTRICT
+            );
+            INSERT INTO watchonly_descriptors_new SELECT * FROM watchonly_descriptors;
+            DROP TABLE watchonly_descriptors;
+            ALTER TABLE watchonly_descriptors_new RENAME TO watchonly_descriptors;
+
+            CREATE TABLE watchonly_coins (
+                id INTEGER PRIMARY KEY NOT NULL,
+                descriptor_id INTEGER NOT NULL,
+                txid BLOB NOT NULL,
+                vout INTEGER NOT NULL,
+                amount_sat INTEGER NOT NULL,
+                address TEXT NOT NULL,
+                derivation_index INTEGER NOT NULL,
+                blockheight INTEGER,
+                UNIQUE (descriptor_id, txid, vout),
+                FOREIGN KEY (descriptor_id) REFERENCES watchonly_descriptors (id)
+                    ON UPDATE RESTRICT
+                    ON DELETE RESTRICT
+            );
+            INSERT INTO watchonly_coins SELECT * FROM watchonly_coins_old;
+            DROP TABLE watchonly_coins_old;
+
+            CREATE TABLE signed_spends_new (
+                id INTEGER PRIMARY KEY NOT NULL,
+                wallet_id INTEGER NOT NULL,
+                txid BLOB NOT NULL,
+                amount_sat INTEGER NOT NULL,
+                signed_at INTEGER NOT NULL,
+                UNIQUE (wallet_id, txid),
+                FOREIGN KEY (wallet_id) REFERENCES wallets (id)
+                    ON UPDATE RESTRICT
+                    ON DELETE RESTRICT
+            );
+            INSERT INTO signed_spends_new SELECT * FROM signed_spends;
+            DROP TABLE signed_spends;
+            ALTER TABLE signed_spends_new RENAME TO signed_spends;
+
+            CREATE TABLE broadcast_spends_new (
+                id INTEGER PRIMARY KEY NOT NULL,
+                wallet_id INTEGER NOT NULL,
+                txid BLOB NOT NULL,
+                amount_sat INTEGER NOT NULL,
+                broadcast_at INTEGER NOT NULL,
+                UNIQUE (wallet_id, txid),
+                FOREIGN KEY (wallet_id) REFERENCES wallets (id)
+                    ON UPDATE RESTRICT
+                    ON DELETE RESTRICT
+            );
+            INSERT INTO broadcast_spends_new SELECT * FROM broadcast_spends;
+            DROP TABLE broadcast_spends;
+            ALTER TABLE broadcast_spends_new RENAME TO broadcast_spends;
+
+            CREATE TABLE labels_new (
+                id INTEGER PRIMARY KEY NOT NULL,
+                wallet_id INTEGER NOT NULL,
+                item_kind INTEGER NOT NULL CHECK (item_kind IN (0,1,2)),
+                item TEXT NOT NULL,
+                value TEXT NOT NULL,
+                UNIQUE (wallet_id, item),
+                FOREIGN KEY (wallet_id) REFERENCES wallets (id)
+                    ON UPDATE RESTRICT
+                    ON DELETE RESTRICT
+            );
+            INSERT INTO labels_new SELECT * FROM labels;
+            DROP TABLE labels;
+            ALTER TABLE labels_new RENAME TO labels;
+
+            UPDATE version SET version = 13;
+            ",
+        )?;
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
 /// Check the database version and if necessary apply the migrations to upgrade it to the current
 /// one. The `bitcoin_txs` parameter is here for the migration from versions 4 and earlier, which
 /// did not store the Bitcoin transactions in database, to versions 5 and later, which do. For a
@@ -545,6 +841,31 @@ pub fn maybe_apply_migration(
                 migrate_v7_to_v8(&mut conn)?;
                 log::warn!("Migration from database version 7 to version 8 successful.");
             }
+            8 => {
+                log::warn!("Upgrading database from version 8 to version 9.");
+                migrate_v8_to_v9(&mut conn)?;
+                log::warn!("Migration from database version 8 to version 9 successful.");
+            }
+            9 => {
+                log::warn!("Upgrading database from version 9 to version 10.");
+                migrate_v9_to_v10(&mut conn)?;
+                log::warn!("Migration from database version 9 to version 10 successful.");
+            }
+            10 => {
+                log::warn!("Upgrading database from version 10 to version 11.");
+                migrate_v10_to_v11(&mut conn)?;
+                log::warn!("Migration from database version 10 to version 11 successful.");
+            }
+            11 => {
+                log::warn!("Upgrading database from version 11 to version 12.");
+                migrate_v11_to_v12(&mut conn)?;
+                log::warn!("Migration from database version 11 to version 12 successful.");
+            }
+            12 => {
+                log::warn!("Upgrading database from version 12 to version 13.");
+                migrate_v12_to_v13(&mut conn)?;
+                log::warn!("Migration from database version 12 to version 13 successful.");
+            }
             _ => return Err(SqliteDbError::UnsupportedVersion(version)),
         }
     }
@@ -44,4 +44,29 @@ impl DataDirectory {
         dir.push("lianad_rpc");
         dir
     }
+    pub fn lianad_rpc_cookie_path(&self) -> PathBuf {
+        let mut dir = self.0.clone();
+        dir.push("lianad_rpc_cookie");
+        dir
+    }
+    /// The file storing the block headers when syncing using compact block filters.
+    pub fn cbf_headers_path(&self) -> PathBuf {
+        let mut dir = self.0.clone();
+        dir.push("cbf_headers");
+        dir
+    }
+    /// The directory containing the data directories of the wallets hosted along the main one.
+    pub fn wallets_path(&self) -> PathBuf {
+        let mut dir = self.0.clone();
+        dir.push("wallets");
+        dir
+    }
+    /// The data directory of the wallet with this name, hosted along the main one. It only holds
+    /// the state of the wallet's Bitcoin backend, the wallet itself is stored in the database of
+    /// the main wallet.
+    pub fn wallet_directory(&self, name: &str) -> DataDirectory {
+        let mut dir = self.wallets_path();
+        dir.push(name);
+        DataDirectory(dir)
+    }
 }
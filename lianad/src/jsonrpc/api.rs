@@ -1,32 +1,76 @@
 use crate::{
-    commands::{CoinStatus, LabelItem},
+    accounting::CostBasisMethod,
+    commands::{
+        CoinStatus, Destination, FeeEstimateMode, LabelItem, LabelsConflictPolicy, MAX_LABEL_LEN,
+    },
     jsonrpc::rpc::{Error, Params, Request, Response},
+    notifications::{EventKind, Subscription},
+    payments,
+    wallets::Wallets,
     DaemonControl,
 };
 
+use liana::descriptors;
+
 use std::{
     collections::{HashMap, HashSet},
     convert::TryInto,
     str::FromStr,
 };
 
-use miniscript::bitcoin::{self, psbt::Psbt, Txid};
+use miniscript::{
+    bitcoin::{self, bip32, psbt::Psbt, Txid},
+    descriptor::{Descriptor, DescriptorPublicKey},
+};
+
+// Parse a destination from an entry of the 'destinations' array. It may be either a BIP21 URI or
+// an object with an address, an amount and an optional label.
+fn destination_from_entry(entry: &serde_json::Value) -> Result<Destination, Error> {
+    let destination = if let Some(uri) = entry.as_str() {
+        payments::parse_bip21(uri).map_err(|e| e.to_string())
+    } else {
+        serde_json::from_value::<Destination>(entry.clone()).map_err(|e| e.to_string())
+    }
+    .map_err(|e| {
+        Error::invalid_params(format!("Invalid entry in 'destinations' parameter: {}.", e))
+    })?;
+    if destination
+        .label
+        .as_ref()
+        .map(|label| label.len() > MAX_LABEL_LEN)
+        .unwrap_or(false)
+    {
+        return Err(Error::invalid_params(format!(
+            "Invalid label length in 'destinations' parameter: must be less or equal than {} characters",
+            MAX_LABEL_LEN
+        )));
+    }
+    Ok(destination)
+}
 
 fn create_spend(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
     let destinations = params
         .get(0, "destinations")
-        .ok_or_else(|| Error::invalid_params("Missing 'destinations' parameter."))?
-        .as_object()
-        .and_then(|obj| {
-            obj.into_iter()
-                .map(|(k, v)| {
-                    let addr = bitcoin::Address::from_str(k).ok()?;
-                    let amount: u64 = v.as_i64()?.try_into().ok()?;
-                    Some((addr, amount))
-                })
-                .collect::<Option<HashMap<bitcoin::Address<bitcoin::address::NetworkUnchecked>, u64>>>()
-        })
-        .ok_or_else(|| Error::invalid_params("Invalid 'destinations' parameter."))?;
+        .ok_or_else(|| Error::invalid_params("Missing 'destinations' parameter."))?;
+    // The destinations may be given as a mapping from address to amount, or as an ordered list.
+    let destinations = if let Some(arr) = destinations.as_array() {
+        arr.iter()
+            .map(destination_from_entry)
+            .collect::<Result<Vec<Destination>, Error>>()?
+    } else {
+        destinations
+            .as_object()
+            .and_then(|obj| {
+                obj.into_iter()
+                    .map(|(k, v)| {
+                        let addr = bitcoin::Address::from_str(k).ok()?;
+                        let amount: u64 = v.as_i64()?.try_into().ok()?;
+                        Some(Destination::new(addr, amount))
+                    })
+                    .collect::<Option<Vec<Destination>>>()
+            })
+            .ok_or_else(|| Error::invalid_params("Invalid 'destinations' parameter."))?
+    };
     let outpoints = params
         .get(1, "outpoints")
         .ok_or_else(|| Error::invalid_params("Missing 'outpoints' parameter."))?
@@ -41,13 +85,18 @@ fn create_spend(control: &DaemonControl, params: Params) -> Result<serde_json::V
                 .collect::<Option<Vec<bitcoin::OutPoint>>>()
         })
         .ok_or_else(|| Error::invalid_params("Invalid 'outpoints' parameter."))?;
-    let feerate: u64 = params
+    // The feerate may be omitted (or `null`) if a confirmation target is given instead.
+    let feerate: Option<u64> = params
         .get(2, "feerate")
-        .ok_or_else(|| Error::invalid_params("Missing 'feerate' parameter."))?
-        .as_u64()
-        .ok_or_else(|| Error::invalid_params("Invalid 'feerate' parameter."))?;
+        .filter(|f| !f.is_null())
+        .map(|f| {
+            f.as_u64()
+                .ok_or_else(|| Error::invalid_params("Invalid 'feerate' parameter."))
+        })
+        .transpose()?;
     let change_address: Option<bitcoin::Address<bitcoin::address::NetworkUnchecked>> = params
         .get(3, "change_address")
+        .filter(|addr| !addr.is_null())
         .map(|addr| {
             let addr_str = addr.as_str().ok_or_else(|| {
                 Error::invalid_params("Invalid 'change_address' parameter: must be a string.")
@@ -57,11 +106,63 @@ fn create_spend(control: &DaemonControl, params: Params) -> Result<serde_json::V
             })
         })
         .transpose()?;
+    let conf_target: Option<u16> = params
+        .get(4, "conf_target")
+        .map(|target| {
+            target
+                .as_u64()
+                .and_then(|t| t.try_into().ok())
+                .ok_or_else(|| Error::invalid_params("Invalid 'conf_target' parameter."))
+        })
+        .transpose()?;
+    let feerate = match (feerate, conf_target) {
+        (Some(feerate), None) => feerate,
+        (None, Some(conf_target)) => {
+            control
+                .estimate_fee(conf_target, FeeEstimateMode::Economical)?
+                .feerate
+        }
+        (Some(_), Some(_)) => {
+            return Err(Error::invalid_params(
+                "Only one of 'feerate' and 'conf_target' must be provided.",
+            ));
+        }
+        (None, None) => {
+            return Err(Error::invalid_params(
+                "Missing 'feerate' or 'conf_target' parameter.",
+            ));
+        }
+    };
 
     let res = control.create_spend(&destinations, &outpoints, feerate, change_address)?;
     Ok(serde_json::json!(&res))
 }
 
+fn estimate_fee(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
+    let conf_target: u16 = params
+        .get(0, "conf_target")
+        .ok_or_else(|| Error::invalid_params("Missing 'conf_target' parameter."))?
+        .as_u64()
+        .and_then(|t| t.try_into().ok())
+        .ok_or_else(|| Error::invalid_params("Invalid 'conf_target' parameter."))?;
+    let mode = params
+        .get(1, "mode")
+        .map(|mode| {
+            mode.as_str()
+                .and_then(FeeEstimateMode::from_arg)
+                .ok_or_else(|| {
+                    Error::invalid_params(format!(
+                        "Invalid value {} for 'mode' parameter: must be 'conservative' or 'economical'.",
+                        mode
+                    ))
+                })
+        })
+        .transpose()?
+        .unwrap_or(FeeEstimateMode::Conservative);
+
+    Ok(serde_json::json!(control.estimate_fee(conf_target, mode)?))
+}
+
 fn update_spend(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
     let psbt: Psbt = params
         .get(0, "psbt")
@@ -98,6 +199,36 @@ fn broadcast_spend(control: &DaemonControl, params: Params) -> Result<serde_json
     Ok(serde_json::json!({}))
 }
 
+fn check_spend(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
+    let txid = params
+        .get(0, "txid")
+        .ok_or_else(|| Error::invalid_params("Missing 'txid' parameter."))?
+        .as_str()
+        .and_then(|s| bitcoin::Txid::from_str(s).ok())
+        .ok_or_else(|| Error::invalid_params("Invalid 'txid' parameter."))?;
+    let res = control.check_spend(&txid)?;
+
+    Ok(serde_json::json!(&res))
+}
+
+fn sign_spend(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
+    let txid = params
+        .get(0, "txid")
+        .ok_or_else(|| Error::invalid_params("Missing 'txid' parameter."))?
+        .as_str()
+        .and_then(|s| bitcoin::Txid::from_str(s).ok())
+        .ok_or_else(|| Error::invalid_params("Invalid 'txid' parameter."))?;
+    let fingerprint = params
+        .get(1, "fingerprint")
+        .ok_or_else(|| Error::invalid_params("Missing 'fingerprint' parameter."))?
+        .as_str()
+        .and_then(|s| bip32::Fingerprint::from_str(s).ok())
+        .ok_or_else(|| Error::invalid_params("Invalid 'fingerprint' parameter."))?;
+    let res = control.sign_spend(&txid, fingerprint)?;
+
+    Ok(serde_json::json!(res))
+}
+
 fn rbf_psbt(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
     let txid = params
         .get(0, "txid")
@@ -123,6 +254,33 @@ fn rbf_psbt(control: &DaemonControl, params: Params) -> Result<serde_json::Value
     Ok(serde_json::json!(&res))
 }
 
+fn cpfp_bump(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
+    let outpoints = outpoints_param(&params)?;
+    let feerate_vb = params
+        .get(1, "feerate")
+        .ok_or_else(|| Error::invalid_params("Missing 'feerate' parameter."))?
+        .as_u64()
+        .ok_or_else(|| Error::invalid_params("Invalid 'feerate' parameter."))?;
+    let res = control.cpfp_bump(&outpoints, feerate_vb)?;
+    Ok(serde_json::json!(&res))
+}
+
+fn send_payjoin(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
+    let txid = params
+        .get(0, "txid")
+        .ok_or_else(|| Error::invalid_params("Missing 'txid' parameter."))?
+        .as_str()
+        .and_then(|s| bitcoin::Txid::from_str(s).ok())
+        .ok_or_else(|| Error::invalid_params("Invalid 'txid' parameter."))?;
+    let bip21 = params
+        .get(1, "bip21")
+        .ok_or_else(|| Error::invalid_params("Missing 'bip21' parameter."))?
+        .as_str()
+        .ok_or_else(|| Error::invalid_params("Invalid 'bip21' parameter."))?;
+    let res = control.send_payjoin(&txid, bip21)?;
+    Ok(serde_json::json!(&res))
+}
+
 fn list_coins(control: &DaemonControl, params: Option<Params>) -> Result<serde_json::Value, Error> {
     let statuses_arg = params
         .as_ref()
@@ -168,10 +326,56 @@ fn list_coins(control: &DaemonControl, params: Option<Params>) -> Result<serde_j
     } else {
         Vec::new()
     };
-    let res = control.list_coins(&statuses, &outpoints);
+    let frozen = params
+        .as_ref()
+        .and_then(|p| p.get(2, "frozen"))
+        .filter(|frozen| !frozen.is_null())
+        .map(|frozen| {
+            frozen
+                .as_bool()
+                .ok_or_else(|| Error::invalid_params("Invalid 'frozen' parameter."))
+        })
+        .transpose()?;
+    let mut res = control.list_coins(&statuses, &outpoints);
+    if let Some(frozen) = frozen {
+        res.coins.retain(|coin| coin.is_frozen == frozen);
+        // Watch-only coins are never frozen.
+        if frozen {
+            res.watchonly_coins.clear();
+        }
+    }
     Ok(serde_json::json!(&res))
 }
 
+fn outpoints_param(params: &Params) -> Result<Vec<bitcoin::OutPoint>, Error> {
+    params
+        .get(0, "outpoints")
+        .ok_or_else(|| Error::invalid_params("Missing 'outpoints' parameter."))?
+        .as_array()
+        .and_then(|arr| {
+            arr.iter()
+                .map(|entry| {
+                    entry
+                        .as_str()
+                        .and_then(|e| bitcoin::OutPoint::from_str(e).ok())
+                })
+                .collect::<Option<Vec<bitcoin::OutPoint>>>()
+        })
+        .ok_or_else(|| Error::invalid_params("Invalid 'outpoints' parameter."))
+}
+
+fn freeze_coins(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
+    let outpoints = outpoints_param(&params)?;
+    control.freeze_coins(&outpoints)?;
+    Ok(serde_json::json!({}))
+}
+
+fn unfreeze_coins(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
+    let outpoints = outpoints_param(&params)?;
+    control.unfreeze_coins(&outpoints)?;
+    Ok(serde_json::json!({}))
+}
+
 fn get_opt_u32<Q>(params: &Option<Params>, index: usize, name: &Q) -> Result<Option<u32>, Error>
 where
     String: std::borrow::Borrow<Q>,
@@ -287,6 +491,25 @@ fn update_deriv_indexes(
     ))
 }
 
+fn discover_addresses(
+    control: &mut DaemonControl,
+    params: Option<Params>,
+) -> Result<serde_json::Value, Error> {
+    let gap_limit = params
+        .as_ref()
+        .and_then(|p| p.get(0, "gap_limit"))
+        .filter(|gap_limit| !gap_limit.is_null())
+        .map(|gap_limit| {
+            gap_limit
+                .as_u64()
+                .and_then(|g| g.try_into().ok())
+                .ok_or_else(|| Error::invalid_params("Invalid 'gap_limit' parameter."))
+        })
+        .transpose()?;
+
+    Ok(serde_json::json!(control.discover_addresses(gap_limit)?))
+}
+
 fn list_confirmed(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
     let start: u32 = params
         .get(0, "start")
@@ -314,6 +537,25 @@ fn list_confirmed(control: &DaemonControl, params: Params) -> Result<serde_json:
     ))
 }
 
+fn accounting_report(
+    control: &DaemonControl,
+    params: Option<Params>,
+) -> Result<serde_json::Value, Error> {
+    let method = params
+        .as_ref()
+        .and_then(|p| p.get(0, "method"))
+        .map(|method| {
+            method
+                .as_str()
+                .and_then(|m| CostBasisMethod::from_str(m).ok())
+                .ok_or_else(|| Error::invalid_params("Invalid 'method' parameter."))
+        })
+        .transpose()?
+        .unwrap_or_default();
+
+    Ok(serde_json::json!(&control.accounting_report(method)))
+}
+
 fn list_spendtxs(
     control: &DaemonControl,
     params: Option<Params>,
@@ -366,6 +608,74 @@ fn start_rescan(control: &mut DaemonControl, params: Params) -> Result<serde_jso
     Ok(serde_json::json!({}))
 }
 
+fn export_descriptor(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
+    let format = params
+        .get(0, "format")
+        .ok_or_else(|| Error::invalid_params("Missing 'format' parameter."))?
+        .as_str()
+        .and_then(|s| descriptors::ExportFormat::from_str(s).ok())
+        .ok_or_else(|| Error::invalid_params("Invalid 'format' parameter."))?;
+    let name = match params.get(1, "name") {
+        Some(name) => name
+            .as_str()
+            .ok_or_else(|| Error::invalid_params("Invalid 'name' parameter."))?,
+        None => "Liana",
+    };
+    let res = control.export_descriptor(format, name)?;
+
+    Ok(serde_json::json!(res))
+}
+
+fn import_watchonly_descriptor(
+    control: &mut DaemonControl,
+    params: Params,
+) -> Result<serde_json::Value, Error> {
+    let descriptor = params
+        .get(0, "descriptor")
+        .ok_or_else(|| Error::invalid_params("Missing 'descriptor' parameter."))?
+        .as_str()
+        .and_then(|s| Descriptor::<DescriptorPublicKey>::from_str(s).ok())
+        .ok_or_else(|| Error::invalid_params("Invalid 'descriptor' parameter."))?;
+    let timestamp: u32 = params
+        .get(1, "timestamp")
+        .ok_or_else(|| Error::invalid_params("Missing 'timestamp' parameter."))?
+        .as_u64()
+        .and_then(|t| t.try_into().ok())
+        .ok_or_else(|| Error::invalid_params("Invalid 'timestamp' parameter."))?;
+    control.import_watchonly_descriptor(descriptor, timestamp)?;
+
+    Ok(serde_json::json!({}))
+}
+
+fn sweep_watchonly(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
+    let feerate: u64 = params
+        .get(0, "feerate")
+        .ok_or_else(|| Error::invalid_params("Missing 'feerate' parameter."))?
+        .as_u64()
+        .ok_or_else(|| Error::invalid_params("Invalid 'feerate' parameter."))?;
+    let outpoints = params
+        .get(1, "outpoints")
+        .map(|param| {
+            param
+                .as_array()
+                .and_then(|arr| {
+                    arr.iter()
+                        .map(|entry| {
+                            entry
+                                .as_str()
+                                .and_then(|e| bitcoin::OutPoint::from_str(e).ok())
+                        })
+                        .collect::<Option<Vec<_>>>()
+                })
+                .ok_or_else(|| Error::invalid_params("Invalid 'outpoints' parameter."))
+        })
+        .transpose()?
+        .unwrap_or_default(); // missing is same as empty array
+
+    let res = control.sweep_watchonly(&outpoints, feerate)?;
+    Ok(serde_json::json!(&res))
+}
+
 fn create_recovery(control: &DaemonControl, params: Params) -> Result<serde_json::Value, Error> {
     let address = params
         .get(0, "address")
@@ -404,8 +714,96 @@ fn create_recovery(control: &DaemonControl, params: Params) -> Result<serde_json
         })
         .transpose()?
         .unwrap_or_default(); // missing is same as empty array
+    let absolute_timelock: Option<u32> = params
+        .get(4, "absolute_timelock")
+        .map(|tl| {
+            tl.as_u64()
+                .and_then(|tl| tl.try_into().ok())
+                .ok_or_else(|| Error::invalid_params("Invalid 'absolute_timelock' parameter."))
+        })
+        .transpose()?;
+    let time_timelock: Option<u16> = params
+        .get(5, "time_timelock")
+        .map(|tl| {
+            tl.as_u64()
+                .and_then(|tl| tl.try_into().ok())
+                .ok_or_else(|| Error::invalid_params("Invalid 'time_timelock' parameter."))
+        })
+        .transpose()?;
+    if [
+        timelock.is_some(),
+        absolute_timelock.is_some(),
+        time_timelock.is_some(),
+    ]
+    .iter()
+    .filter(|is_some| **is_some)
+    .count()
+        > 1
+    {
+        return Err(Error::invalid_params(
+            "Only one of 'timelock', 'absolute_timelock' and 'time_timelock' may be specified.",
+        ));
+    }
 
-    let res = control.create_recovery(address, &outpoints, feerate, timelock)?;
+    let res = if let Some(absolute_timelock) = absolute_timelock {
+        control.create_absolute_recovery(address, &outpoints, feerate, absolute_timelock)?
+    } else if let Some(time_timelock) = time_timelock {
+        control.create_time_recovery(address, &outpoints, feerate, time_timelock)?
+    } else {
+        control.create_recovery(address, &outpoints, feerate, timelock)?
+    };
+    Ok(serde_json::json!(&res))
+}
+
+fn create_proof_of_reserves(
+    control: &DaemonControl,
+    params: Params,
+) -> Result<serde_json::Value, Error> {
+    let message = params
+        .get(0, "message")
+        .ok_or_else(|| Error::invalid_params("Missing 'message' parameter."))?
+        .as_str()
+        .ok_or_else(|| Error::invalid_params("Invalid 'message' parameter."))?;
+    let outpoints = params
+        .get(1, "outpoints")
+        .map(|param| {
+            param
+                .as_array()
+                .and_then(|arr| {
+                    arr.iter()
+                        .map(|entry| {
+                            entry
+                                .as_str()
+                                .and_then(|e| bitcoin::OutPoint::from_str(e).ok())
+                        })
+                        .collect::<Option<Vec<_>>>()
+                })
+                .ok_or_else(|| Error::invalid_params("Invalid 'outpoints' parameter."))
+        })
+        .transpose()?
+        .unwrap_or_default(); // missing is same as empty array
+
+    let res = control.create_proof_of_reserves(message, &outpoints)?;
+    Ok(serde_json::json!(&res))
+}
+
+fn verify_proof_of_reserves(
+    control: &DaemonControl,
+    params: Params,
+) -> Result<serde_json::Value, Error> {
+    let psbt: Psbt = params
+        .get(0, "psbt")
+        .ok_or_else(|| Error::invalid_params("Missing 'psbt' parameter."))?
+        .as_str()
+        .and_then(|s| Psbt::from_str(s).ok())
+        .ok_or_else(|| Error::invalid_params("Invalid 'psbt' parameter."))?;
+    let message = params
+        .get(1, "message")
+        .ok_or_else(|| Error::invalid_params("Missing 'message' parameter."))?
+        .as_str()
+        .ok_or_else(|| Error::invalid_params("Invalid 'message' parameter."))?;
+
+    let res = control.verify_proof_of_reserves(&psbt, message)?;
     Ok(serde_json::json!(&res))
 }
 
@@ -420,10 +818,10 @@ fn update_labels(control: &DaemonControl, params: Params) -> Result<serde_json::
     {
         let value = value.as_str().map(|s| s.to_string());
         if let Some(value) = &value {
-            if value.len() > 100 {
+            if value.len() > MAX_LABEL_LEN {
                 return Err(Error::invalid_params(format!(
-                    "Invalid 'labels.{}' value length: must be less or equal than 100 characters",
-                    item
+                    "Invalid 'labels.{}' value length: must be less or equal than {} characters",
+                    item, MAX_LABEL_LEN
                 )));
             }
         }
@@ -486,15 +884,183 @@ fn get_labels_bip329(control: &DaemonControl, params: Params) -> Result<serde_js
     Ok(serde_json::json!(control.get_labels_bip329(offset, limit)))
 }
 
+fn import_labels_bip329(
+    control: &DaemonControl,
+    params: Params,
+) -> Result<serde_json::Value, Error> {
+    let records = params
+        .get(0, "records")
+        .ok_or_else(|| Error::invalid_params("Missing 'records' parameter."))?
+        .as_str()
+        .ok_or_else(|| Error::invalid_params("Invalid 'records' parameter."))?;
+    let conflict_policy = params
+        .get(1, "conflict")
+        .map(|conflict| {
+            conflict
+                .as_str()
+                .and_then(LabelsConflictPolicy::from_arg)
+                .ok_or_else(|| {
+                    Error::invalid_params(format!(
+                        "Invalid value {} for 'conflict' parameter: must be 'merge' or 'overwrite'.",
+                        conflict
+                    ))
+                })
+        })
+        .transpose()?
+        .unwrap_or(LabelsConflictPolicy::Merge);
+    Ok(serde_json::json!(
+        control.import_labels_bip329(records, conflict_policy)
+    ))
+}
+
+/// Get a subscription to the events requested with this `subscribe` command. Since the events are
+/// streamed on the connection, this command is not treated by `handle_request`.
+pub fn subscribe(control: &DaemonControl, params: Option<Params>) -> Result<Subscription, Error> {
+    let kinds = params
+        .as_ref()
+        .and_then(|params| params.get(0, "events"))
+        .map(|events| {
+            events
+                .as_array()
+                .ok_or_else(|| Error::invalid_params("Invalid 'events' parameter."))?
+                .iter()
+                .map(|event| {
+                    event.as_str().and_then(EventKind::from_arg).ok_or_else(|| {
+                        Error::invalid_params(format!(
+                            "Invalid value {} in 'events' parameter.",
+                            event
+                        ))
+                    })
+                })
+                .collect::<Result<HashSet<EventKind>, Error>>()
+        })
+        .transpose()?;
+    Ok(control.subscribe(kinds))
+}
+
+fn wallet_name(params: &Params) -> Result<String, Error> {
+    params
+        .get(0, "name")
+        .ok_or_else(|| Error::invalid_params("Missing 'name' parameter."))?
+        .as_str()
+        .map(|name| name.to_string())
+        .ok_or_else(|| Error::invalid_params("Invalid 'name' parameter."))
+}
+
+fn create_wallet(wallets: &Wallets, params: Params) -> Result<serde_json::Value, Error> {
+    let name = wallet_name(&params)?;
+    let descriptor = params
+        .get(1, "descriptor")
+        .ok_or_else(|| Error::invalid_params("Missing 'descriptor' parameter."))?
+        .as_str()
+        .and_then(|s| descriptors::LianaDescriptor::from_str(s).ok())
+        .ok_or_else(|| Error::invalid_params("Invalid 'descriptor' parameter."))?;
+    wallets.create_wallet(name, descriptor)?;
+
+    Ok(serde_json::json!({}))
+}
+
+fn load_wallet(wallets: &Wallets, params: Params) -> Result<serde_json::Value, Error> {
+    wallets.load_wallet(wallet_name(&params)?)?;
+    Ok(serde_json::json!({}))
+}
+
+fn unload_wallet(wallets: &Wallets, params: Params) -> Result<serde_json::Value, Error> {
+    wallets.unload_wallet(wallet_name(&params)?)?;
+    Ok(serde_json::json!({}))
+}
+
+/// Get the wallet a request is for. It is selected either by the path the request was sent to
+/// or by its 'wallet' parameter, and defaults to the main wallet. The 'wallet' parameter is
+/// removed from the request parameters as it isn't one of the command's.
+pub fn request_wallet(
+    wallets: &Wallets,
+    path_wallet: Option<&str>,
+    params: &mut Option<Params>,
+) -> Result<DaemonControl, Error> {
+    let param_wallet = match params {
+        Some(Params::Map(map)) => map
+            .remove("wallet")
+            .map(|wallet| {
+                wallet
+                    .as_str()
+                    .map(|name| name.to_string())
+                    .ok_or_else(|| Error::invalid_params("Invalid 'wallet' parameter."))
+            })
+            .transpose()?,
+        _ => None,
+    };
+    let name = match (path_wallet, param_wallet.as_deref()) {
+        (Some(path_wallet), Some(param_wallet)) if path_wallet != param_wallet => {
+            return Err(Error::invalid_params(
+                "The 'wallet' parameter does not match the wallet of the request path.",
+            ));
+        }
+        (Some(name), _) | (None, Some(name)) => Some(name),
+        (None, None) => None,
+    };
+
+    Ok(wallets.get(name)?)
+}
+
+/// Handle an incoming JSONRPC2 request to the daemon. The wallet management commands are handled
+/// directly, others are handled by the wallet the request is for.
+pub fn handle_wallets_request(
+    wallets: &Wallets,
+    path_wallet: Option<&str>,
+    mut req: Request,
+) -> Result<Response, Error> {
+    let result = match req.method.as_str() {
+        "createwallet" => {
+            let params = req.params.ok_or_else(|| {
+                Error::invalid_params("Missing 'name' and 'descriptor' parameters.")
+            })?;
+            create_wallet(wallets, params)?
+        }
+        "listwallets" => serde_json::json!(wallets.list_wallets()),
+        "loadwallet" => {
+            let params = req
+                .params
+                .ok_or_else(|| Error::invalid_params("Missing 'name' parameter."))?;
+            load_wallet(wallets, params)?
+        }
+        "unloadwallet" => {
+            let params = req
+                .params
+                .ok_or_else(|| Error::invalid_params("Missing 'name' parameter."))?;
+            unload_wallet(wallets, params)?
+        }
+        _ => {
+            let mut control = request_wallet(wallets, path_wallet, &mut req.params)?;
+            return handle_request(&mut control, req);
+        }
+    };
+
+    Ok(Response::success(req.id, result))
+}
+
 /// Handle an incoming JSONRPC2 request.
 pub fn handle_request(control: &mut DaemonControl, req: Request) -> Result<Response, Error> {
     let result = match req.method.as_str() {
+        "accountingreport" => accounting_report(control, req.params)?,
         "broadcastspend" => {
             let params = req
                 .params
                 .ok_or_else(|| Error::invalid_params("Missing 'txid' parameter."))?;
             broadcast_spend(control, params)?
         }
+        "checkspend" => {
+            let params = req
+                .params
+                .ok_or_else(|| Error::invalid_params("Missing 'txid' parameter."))?;
+            check_spend(control, params)?
+        }
+        "createproofofreserves" => {
+            let params = req
+                .params
+                .ok_or_else(|| Error::invalid_params("Missing 'message' parameter."))?;
+            create_proof_of_reserves(control, params)?
+        }
         "createrecovery" => {
             let params = req.params.ok_or_else(|| {
                 Error::invalid_params("Missing 'address' and 'feerate' parameters.")
@@ -509,6 +1075,13 @@ pub fn handle_request(control: &mut DaemonControl, req: Request) -> Result<Respo
             })?;
             create_spend(control, params)?
         }
+        "discoveraddresses" => discover_addresses(control, req.params)?,
+        "estimatefee" => {
+            let params = req
+                .params
+                .ok_or_else(|| Error::invalid_params("Missing 'conf_target' parameter."))?;
+            estimate_fee(control, params)?
+        }
         "delspendtx" => {
             let params = req
                 .params
@@ -521,7 +1094,37 @@ pub fn handle_request(control: &mut DaemonControl, req: Request) -> Result<Respo
             })?;
             rbf_psbt(control, params)?
         }
+        "cpfpbump" => {
+            let params = req.params.ok_or_else(|| {
+                Error::invalid_params("Missing 'outpoints' and 'feerate' parameters.")
+            })?;
+            cpfp_bump(control, params)?
+        }
+        "sendpayjoin" => {
+            let params = req
+                .params
+                .ok_or_else(|| Error::invalid_params("Missing 'txid' and 'bip21' parameters."))?;
+            send_payjoin(control, params)?
+        }
+        "freezecoins" => {
+            let params = req
+                .params
+                .ok_or_else(|| Error::invalid_params("Missing 'outpoints' parameter."))?;
+            freeze_coins(control, params)?
+        }
         "getinfo" => serde_json::json!(&control.get_info()),
+        "exportdescriptor" => {
+            let params = req
+                .params
+                .ok_or_else(|| Error::invalid_params("Missing 'format' parameter."))?;
+            export_descriptor(control, params)?
+        }
+        "importwatchonlydescriptor" => {
+            let params = req.params.ok_or_else(|| {
+                Error::invalid_params("Missing 'descriptor' and 'timestamp' parameters.")
+            })?;
+            import_watchonly_descriptor(control, params)?
+        }
         "getnewaddress" => serde_json::json!(&control.get_new_address()),
         "updatederivationindexes" => {
             let params = req.params.ok_or_else(|| {
@@ -562,6 +1165,12 @@ pub fn handle_request(control: &mut DaemonControl, req: Request) -> Result<Respo
             })?;
             list_transactions(control, params)?
         }
+        "signspend" => {
+            let params = req.params.ok_or_else(|| {
+                Error::invalid_params("Missing 'txid' and 'fingerprint' parameters.")
+            })?;
+            sign_spend(control, params)?
+        }
         "startrescan" => {
             let params = req
                 .params
@@ -569,6 +1178,18 @@ pub fn handle_request(control: &mut DaemonControl, req: Request) -> Result<Respo
             start_rescan(control, params)?
         }
         "stop" => serde_json::json!({}),
+        "sweepwatchonly" => {
+            let params = req
+                .params
+                .ok_or_else(|| Error::invalid_params("Missing 'feerate' parameter."))?;
+            sweep_watchonly(control, params)?
+        }
+        "unfreezecoins" => {
+            let params = req
+                .params
+                .ok_or_else(|| Error::invalid_params("Missing 'outpoints' parameter."))?;
+            unfreeze_coins(control, params)?
+        }
         "updatespend" => {
             let params = req
                 .params
@@ -593,6 +1214,18 @@ pub fn handle_request(control: &mut DaemonControl, req: Request) -> Result<Respo
                 .ok_or_else(|| Error::invalid_params("Missing 'offset' and 'limit' parameters."))?;
             get_labels_bip329(control, params)?
         }
+        "importlabelsbip329" => {
+            let params = req
+                .params
+                .ok_or_else(|| Error::invalid_params("Missing 'records' parameter."))?;
+            import_labels_bip329(control, params)?
+        }
+        "verifyproofofreserves" => {
+            let params = req
+                .params
+                .ok_or_else(|| Error::invalid_params("Missing 'psbt' and 'message' parameters."))?;
+            verify_proof_of_reserves(control, params)?
+        }
         _ => {
             return Err(Error::method_not_found());
         }
@@ -1,5 +1,3 @@
-#[cfg(unix)]
 mod api;
-#[cfg(unix)]
 pub mod rpc;
 pub mod server;
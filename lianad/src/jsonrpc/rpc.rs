@@ -1,4 +1,4 @@
-use crate::commands;
+use crate::{commands, payjoin};
 
 use std::{error, fmt};
 
@@ -51,6 +51,27 @@ pub struct Request {
 /// A failure to broadcast a transaction to the P2P network.
 const BROADCAST_ERROR: i64 = 1_000;
 
+/// The Bitcoin backend could not provide a feerate estimate.
+const FEE_ESTIMATION_ERROR: i64 = 1_001;
+
+/// The client is not allowed to call this method.
+const METHOD_NOT_ALLOWED_ERROR: i64 = 1_002;
+
+/// A failure to make a payjoin with the receiver of a payment.
+const PAYJOIN_ERROR: i64 = 1_003;
+
+/// The wallet the request is for is not loaded.
+const WALLET_NOT_FOUND_ERROR: i64 = 1_004;
+
+/// The spend does not abide by the signing policy.
+const SIGNING_POLICY_ERROR: i64 = 1_005;
+
+/// The spend does not abide by the spending policy.
+const SPENDING_POLICY_ERROR: i64 = 1_006;
+
+/// The proof of reserves is invalid.
+const INVALID_PROOF_OF_RESERVES_ERROR: i64 = 1_007;
+
 /// JSONRPC2 error codes. See https://www.jsonrpc.org/specification#error_object.
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum ErrorCode {
@@ -128,6 +149,13 @@ impl Error {
         Error::new(ErrorCode::MethodNotFound, "Method not found")
     }
 
+    pub fn method_not_allowed(method: &str) -> Error {
+        Error::new(
+            ErrorCode::ServerError(METHOD_NOT_ALLOWED_ERROR),
+            format!("Method not allowed: {}", method),
+        )
+    }
+
     pub fn invalid_params(message: impl Into<String>) -> Error {
         Error::new(
             ErrorCode::InvalidParams,
@@ -157,22 +185,72 @@ impl From<commands::CommandError> for Error {
             | commands::CommandError::SpendCreation(..)
             | commands::CommandError::InsufficientFunds(..)
             | commands::CommandError::UnknownSpend(..)
+            | commands::CommandError::MusigSessionConflict(..)
             | commands::CommandError::SpendFinalization(..)
             | commands::CommandError::InsaneRescanTimestamp(..)
             | commands::CommandError::AlreadyRescanning
+            | commands::CommandError::InvalidGapLimit(..)
+            | commands::CommandError::AlreadyDiscoveringAddresses
             | commands::CommandError::InvalidDerivationIndex
             | commands::CommandError::RbfError(..)
+            | commands::CommandError::CpfpError(..)
             | commands::CommandError::EmptyFilterList
             | commands::CommandError::RecoveryNotAvailable
+            | commands::CommandError::InvalidConfTarget(..)
+            | commands::CommandError::InvalidWatchonlyDescriptor(..)
+            | commands::CommandError::NoWatchonlyCoins
+            | commands::CommandError::DescriptorExport(..)
+            | commands::CommandError::WalletAlreadyExists(..)
+            | commands::CommandError::WalletAlreadyLoaded(..)
+            | commands::CommandError::InvalidWalletName(..)
+            | commands::CommandError::MainWalletUnload
+            | commands::CommandError::SigningDisabled
+            | commands::CommandError::UnknownSigner(..)
+            | commands::CommandError::NoReserves
             | commands::CommandError::OutpointNotRecoverable(..) => {
                 Error::new(ErrorCode::InvalidParams, e.to_string())
             }
-            commands::CommandError::RescanTrigger(..) => {
+            commands::CommandError::RescanTrigger(..)
+            | commands::CommandError::WalletSetup(..)
+            | commands::CommandError::AddressDiscovery(..)
+            | commands::CommandError::Signing(..)
+            | commands::CommandError::UnspentOutputs(..) => {
                 Error::new(ErrorCode::InternalError, e.to_string())
             }
+            commands::CommandError::WalletNotFound(..) => Error::new(
+                ErrorCode::ServerError(WALLET_NOT_FOUND_ERROR),
+                e.to_string(),
+            ),
+            commands::CommandError::SigningPolicy(..) => {
+                Error::new(ErrorCode::ServerError(SIGNING_POLICY_ERROR), e.to_string())
+            }
+            commands::CommandError::SpendingPolicy(..) => {
+                Error::new(ErrorCode::ServerError(SPENDING_POLICY_ERROR), e.to_string())
+            }
+            commands::CommandError::InvalidProofOfReserves(..) => Error::new(
+                ErrorCode::ServerError(INVALID_PROOF_OF_RESERVES_ERROR),
+                e.to_string(),
+            ),
             commands::CommandError::TxBroadcast(_) => {
                 Error::new(ErrorCode::ServerError(BROADCAST_ERROR), e.to_string())
             }
+            commands::CommandError::FeeEstimationUnavailable(..) => {
+                Error::new(ErrorCode::ServerError(FEE_ESTIMATION_ERROR), e.to_string())
+            }
+            commands::CommandError::Payjoin(ref payjoin_err) => match payjoin_err {
+                payjoin::SenderError::PaymentRequest(..)
+                | payjoin::SenderError::NoEndpoint
+                | payjoin::SenderError::InsecureEndpoint(..)
+                | payjoin::SenderError::NoPaymentOutput => {
+                    Error::new(ErrorCode::InvalidParams, e.to_string())
+                }
+                payjoin::SenderError::Http(..)
+                | payjoin::SenderError::Receiver(..)
+                | payjoin::SenderError::InvalidResponse(..)
+                | payjoin::SenderError::InvalidProposal(..) => {
+                    Error::new(ErrorCode::ServerError(PAYJOIN_ERROR), e.to_string())
+                }
+            },
         }
     }
 }
@@ -211,3 +289,25 @@ impl Response {
         Response::new(id, None, Some(error))
     }
 }
+
+/// JSONRPC2 notification, a request without identifier which does not expect a response. See
+/// https://www.jsonrpc.org/specification#notification.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Notification {
+    /// Version. Must be "2.0".
+    jsonrpc: String,
+    /// Notification name.
+    method: String,
+    /// Notification parameters.
+    params: serde_json::Value,
+}
+
+impl Notification {
+    pub fn new(method: impl Into<String>, params: serde_json::Value) -> Notification {
+        Notification {
+            jsonrpc: "2.0".to_string(),
+            method: method.into(),
+            params,
+        }
+    }
+}
@@ -0,0 +1,835 @@
+//! JSONRPC2 server over HTTP
+//!
+//! This module implements a minimal HTTP/1.1 server for receiving JSONRPC2 requests over TCP.
+//! Clients must authenticate using HTTP basic authentication, either with the credentials from
+//! the cookie file we create at startup or with those of a user from the configuration. Users
+//! may be restricted to a subset of the JSONRPC methods.
+//!
+//! Requests are for the main wallet unless they are sent to the `/wallet/<name>` path of another
+//! wallet hosted by the daemon.
+//!
+//! If enabled in the configuration, it also receives payjoins (BIP78) for the main wallet at the
+//! `/payjoin` path. Payjoin senders don't authenticate.
+
+use crate::{
+    config::HttpRpcUser,
+    jsonrpc::{
+        api,
+        rpc::{Error, Request, Response},
+    },
+    payjoin,
+    wallets::Wallets,
+    DaemonControl,
+};
+
+use std::{
+    fs,
+    io::{self, BufRead, Read, Write},
+    net, path,
+    sync::{self, atomic},
+    thread, time,
+};
+
+use miniscript::bitcoin::{
+    base64::{self, Engine},
+    hashes::hex::DisplayHex,
+};
+
+// Maximum number of concurrent authenticated RPC connections we may treat.
+const MAX_CONNECTIONS: usize = 16;
+
+// Maximum number of connections we may hold before their first request was authenticated.
+const MAX_PENDING_CONNECTIONS: usize = 64;
+
+// How long a client may take to send the headers of its first request, before it's authenticated.
+const PENDING_READ_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+
+// Maximum size of the request line and headers of an HTTP request.
+const MAX_HEADERS_SIZE: usize = 16 * 1024;
+
+// Maximum size of the body of an HTTP request. Some requests may contain large PSBTs.
+const MAX_BODY_SIZE: usize = 32 * 1024 * 1024;
+
+// How long to wait for a client to send a request before closing the connection.
+const READ_TIMEOUT: time::Duration = time::Duration::from_secs(30);
+
+// The path at which we receive payjoins.
+const PAYJOIN_PATH: &str = "/payjoin";
+
+// Maximum number of payjoin requests we may treat at once. Each of them may wait for our input
+// to be signed for up to PAYJOIN_SIGNATURE_TIMEOUT.
+const MAX_PAYJOIN_REQUESTS: usize = 2;
+
+// Maximum size of the body of a payjoin request, an Original PSBT.
+const MAX_PAYJOIN_BODY_SIZE: usize = 1024 * 1024;
+
+// The prefix of the path at which requests for a given wallet are received.
+const WALLET_PATH_PREFIX: &str = "/wallet/";
+
+// How long to wait for our input to a payjoin proposal to be signed. Senders usually give up
+// after a minute.
+const PAYJOIN_SIGNATURE_TIMEOUT: time::Duration = time::Duration::from_secs(50);
+
+// The content type of all our responses but payjoin proposals.
+const JSON: &str = "application/json";
+
+/// The user name of the credentials stored in the cookie file.
+pub const COOKIE_USER: &str = "__cookie__";
+
+/// An HTTP request, stripped from the parts we don't care about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HttpRequest {
+    method: String,
+    /// The path and query of the request.
+    target: String,
+    authorization: Option<String>,
+    keep_alive: bool,
+    content_length: usize,
+    /// Empty until read by `read_body`.
+    body: Vec<u8>,
+}
+
+impl HttpRequest {
+    fn path(&self) -> &str {
+        self.target.split('?').next().unwrap_or_default()
+    }
+
+    fn query(&self) -> &str {
+        self.target
+            .split_once('?')
+            .map(|(_, query)| query)
+            .unwrap_or_default()
+    }
+
+    /// The wallet selected by the path of the request, if any.
+    fn wallet(&self) -> Option<&str> {
+        self.path()
+            .strip_prefix(WALLET_PATH_PREFIX)
+            .map(|name| name.trim_end_matches('/'))
+    }
+}
+
+// A counting semaphore, used to limit the number of connections we treat at once.
+struct Semaphore {
+    permits: sync::Mutex<usize>,
+    released: sync::Condvar,
+}
+
+// A permit acquired from a semaphore, given back when dropped.
+struct Permit(sync::Arc<Semaphore>);
+
+impl Semaphore {
+    fn new(permits: usize) -> sync::Arc<Self> {
+        sync::Arc::new(Self {
+            permits: sync::Mutex::new(permits),
+            released: sync::Condvar::new(),
+        })
+    }
+
+    // Get a permit if one is available right away.
+    fn try_acquire(self: &sync::Arc<Self>) -> Option<Permit> {
+        let mut permits = self.permits.lock().expect("Must not be poisoned");
+        if *permits == 0 {
+            return None;
+        }
+        *permits -= 1;
+        Some(Permit(self.clone()))
+    }
+
+    // Wait until a permit is available. Returns `None` if we were told to shut down meanwhile.
+    fn acquire(self: &sync::Arc<Self>, shutdown: &atomic::AtomicBool) -> Option<Permit> {
+        let mut permits = self.permits.lock().expect("Must not be poisoned");
+        while *permits == 0 {
+            if shutdown.load(atomic::Ordering::Relaxed) {
+                return None;
+            }
+            permits = self
+                .released
+                .wait_timeout(permits, time::Duration::from_millis(100))
+                .expect("Must not be poisoned")
+                .0;
+        }
+        *permits -= 1;
+        Some(Permit(self.clone()))
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        *self.0.permits.lock().expect("Must not be poisoned") += 1;
+        self.0.released.notify_one();
+    }
+}
+
+// A TCP stream whose reads may have to complete before a deadline, as opposed to only before
+// a timeout between two reads. This prevents clients from holding a connection by sending
+// their request one byte at a time.
+struct TimedStream<'a> {
+    stream: &'a net::TcpStream,
+    deadline: Option<time::Instant>,
+}
+
+impl Read for TimedStream<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let timeout = match self.deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(time::Instant::now());
+                if remaining.is_zero() {
+                    return Err(io::ErrorKind::TimedOut.into());
+                }
+                remaining.min(READ_TIMEOUT)
+            }
+            None => READ_TIMEOUT,
+        };
+        self.stream.set_read_timeout(Some(timeout))?;
+        self.stream.read(buf)
+    }
+}
+
+fn invalid_data(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+// Read a line terminated by CRLF, without the line terminator. Returns `None` if the connection
+// was closed before anything was read.
+fn read_line(stream: &mut dyn BufRead, read_size: &mut usize) -> Result<Option<String>, io::Error> {
+    let mut line = Vec::new();
+    let max_size = (MAX_HEADERS_SIZE - *read_size) as u64;
+    let read = Read::take(&mut *stream, max_size).read_until(b'\n', &mut line)?;
+    if read == 0 {
+        return Ok(None);
+    }
+    *read_size += read;
+    if line.last() != Some(&b'\n') {
+        return Err(invalid_data("HTTP request headers too large or truncated"));
+    }
+    line.pop();
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+
+    String::from_utf8(line)
+        .map(Some)
+        .map_err(|_| invalid_data("Non UTF-8 HTTP request headers"))
+}
+
+// Read the request line and headers of an HTTP request from the stream. Returns `None` if the
+// connection was closed. The body must then be read using `read_body`.
+fn read_request_head(stream: &mut dyn BufRead) -> Result<Option<HttpRequest>, io::Error> {
+    let mut read_size = 0;
+
+    let request_line = match read_line(stream, &mut read_size)? {
+        Some(line) => line,
+        None => return Ok(None),
+    };
+    let mut request_line = request_line.split(' ');
+    let (method, target, version) = match (
+        request_line.next(),
+        request_line.next(),
+        request_line.next(),
+    ) {
+        (Some(method), Some(target), Some(version)) => {
+            (method.to_string(), target.to_string(), version)
+        }
+        _ => return Err(invalid_data("Invalid HTTP request line")),
+    };
+    // Connections are persistent by default since HTTP/1.1.
+    let mut keep_alive = version == "HTTP/1.1";
+
+    let mut authorization = None;
+    let mut content_length = None;
+    loop {
+        let line = read_line(stream, &mut read_size)?
+            .ok_or_else(|| invalid_data("Connection closed while reading HTTP headers"))?;
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| invalid_data("Invalid HTTP header"))?;
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "authorization" => authorization = Some(value.to_string()),
+            "content-length" => {
+                let length = value
+                    .parse::<usize>()
+                    .map_err(|_| invalid_data("Invalid Content-Length header"))?;
+                if length > MAX_BODY_SIZE {
+                    return Err(invalid_data("HTTP request body too large"));
+                }
+                content_length = Some(length);
+            }
+            "connection" => {
+                if value.eq_ignore_ascii_case("close") {
+                    keep_alive = false;
+                } else if value.eq_ignore_ascii_case("keep-alive") {
+                    keep_alive = true;
+                }
+            }
+            "transfer-encoding" => {
+                return Err(invalid_data("Unsupported Transfer-Encoding header"));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(Some(HttpRequest {
+        method,
+        target,
+        authorization,
+        keep_alive,
+        content_length: content_length.unwrap_or(0),
+        body: Vec::new(),
+    }))
+}
+
+// Read the body of this request from the stream, of the size announced in its headers.
+fn read_body(stream: &mut dyn BufRead, http_req: &mut HttpRequest) -> Result<(), io::Error> {
+    let mut body = vec![0; http_req.content_length];
+    stream.read_exact(&mut body)?;
+    http_req.body = body;
+    Ok(())
+}
+
+fn write_response(
+    stream: &mut dyn Write,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+    keep_alive: bool,
+) -> Result<(), io::Error> {
+    let mut headers = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    if status.starts_with("401") {
+        headers.push_str("WWW-Authenticate: Basic realm=\"jsonrpc\"\r\n");
+    }
+    if !keep_alive {
+        headers.push_str("Connection: close\r\n");
+    }
+    headers.push_str("\r\n");
+
+    stream.write_all(headers.as_bytes())?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+// Compare two byte strings in a time which does not depend on the position of the first
+// differing byte, to not leak information about the credentials to a client.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// Get the user corresponding to the credentials in this value of an Authorization header.
+fn authenticate<'a>(users: &'a [HttpRpcUser], authorization: &str) -> Option<&'a HttpRpcUser> {
+    let (scheme, credentials) = authorization.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let credentials = base64::engine::general_purpose::STANDARD
+        .decode(credentials.trim())
+        .ok()?;
+
+    // Go through all users regardless of whether we found a match already.
+    users.iter().fold(None, |found, user| {
+        let expected = format!("{}:{}", user.user, user.password);
+        if constant_time_eq(expected.as_bytes(), &credentials) {
+            Some(user)
+        } else {
+            found
+        }
+    })
+}
+
+// Create a payjoin proposal for the Original PSBT in this request and wait for our input to be
+// signed. Returns the body of the response.
+fn payjoin_response(
+    control: &DaemonControl,
+    http_req: &HttpRequest,
+    shutdown: &atomic::AtomicBool,
+) -> Result<Vec<u8>, payjoin::ReceiverError> {
+    let original = std::str::from_utf8(&http_req.body).map_err(|_| {
+        payjoin::ReceiverError::OriginalRejected("the PSBT is not valid base64.".to_string())
+    })?;
+    let txid = control.create_payjoin_proposal(original, http_req.query())?;
+    log::info!(
+        "Created payjoin proposal '{}'. Waiting for our input to be signed.",
+        txid
+    );
+
+    let start = time::Instant::now();
+    while start.elapsed() < PAYJOIN_SIGNATURE_TIMEOUT && !shutdown.load(atomic::Ordering::Relaxed) {
+        if let Some(psbt) = control.finalize_payjoin_proposal(&txid) {
+            log::info!("Sending payjoin proposal '{}'.", txid);
+            return Ok(psbt.to_string().into_bytes());
+        }
+        thread::sleep(time::Duration::from_secs(1));
+    }
+
+    log::info!(
+        "Payjoin proposal '{}' was not signed in time. Deleting it.",
+        txid
+    );
+    control.abandon_payjoin_proposal(&txid);
+    Err(payjoin::ReceiverError::Unavailable)
+}
+
+// Close the connection after failing to read a request from it.
+fn read_failure(writer: &mut dyn Write, e: io::Error) -> Result<(), io::Error> {
+    match e.kind() {
+        io::ErrorKind::InvalidData => {
+            log::debug!("Invalid HTTP request: '{}'", e);
+            write_response(writer, "400 Bad Request", JSON, &[], false)
+        }
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {
+            log::trace!("Closing idle HTTP connection.");
+            Ok(())
+        }
+        _ => Err(e),
+    }
+}
+
+// Treat a payjoin request, and close the connection. The body of the request is yet to be read.
+fn payjoin_handler(
+    control: &DaemonControl,
+    reader: &mut dyn BufRead,
+    mut writer: &net::TcpStream,
+    mut http_req: HttpRequest,
+    payjoins: &sync::Arc<Semaphore>,
+    shutdown: &atomic::AtomicBool,
+) -> Result<(), io::Error> {
+    if http_req.content_length > MAX_PAYJOIN_BODY_SIZE {
+        log::debug!("Payjoin request body too large.");
+        return write_response(&mut writer, "400 Bad Request", JSON, &[], false);
+    }
+    if let Err(e) = read_body(reader, &mut http_req) {
+        return read_failure(&mut writer, e);
+    }
+
+    let res = match payjoins.try_acquire() {
+        Some(_permit) => payjoin_response(control, &http_req, shutdown),
+        None => {
+            log::info!("Too many payjoin requests being treated.");
+            Err(payjoin::ReceiverError::Unavailable)
+        }
+    };
+    let (status, content_type, body) = match res {
+        Ok(body) => ("200 OK", "text/plain", body),
+        Err(e) => {
+            log::info!("Rejecting payjoin request: '{}'", e);
+            ("400 Bad Request", JSON, serde_json::to_vec(&e.to_json())?)
+        }
+    };
+    if let Err(e) = write_response(&mut writer, status, content_type, &body, false) {
+        log::error!("Error writing response: '{}'", e);
+    }
+    Ok(())
+}
+
+// Handle all requests from this connection. Until a request from the connection was
+// authenticated it holds a permit for pending connections, then one for authenticated
+// connections. Payjoins are only received if a limit for their requests is given.
+fn connection_handler(
+    wallets: Wallets,
+    stream: net::TcpStream,
+    users: &[HttpRpcUser],
+    payjoins: Option<&sync::Arc<Semaphore>>,
+    pending_permit: Permit,
+    connections: &sync::Arc<Semaphore>,
+    shutdown: sync::Arc<atomic::AtomicBool>,
+) -> Result<(), io::Error> {
+    stream.set_nonblocking(false)?;
+    let mut reader = io::BufReader::new(TimedStream {
+        stream: &stream,
+        deadline: None,
+    });
+    let mut writer = &stream;
+    let mut _permit = pending_permit;
+    let mut authenticated = false;
+
+    while !shutdown.load(atomic::Ordering::Relaxed) {
+        // Don't let unauthenticated clients hold a connection for long.
+        reader.get_mut().deadline =
+            (!authenticated).then(|| time::Instant::now() + PENDING_READ_TIMEOUT);
+        let mut http_req = match read_request_head(&mut reader) {
+            Ok(Some(req)) => req,
+            Ok(None) => {
+                // Connection closed.
+                return Ok(());
+            }
+            Err(e) => return read_failure(&mut writer, e),
+        };
+        let keep_alive = http_req.keep_alive;
+
+        if http_req.method != "POST" {
+            write_response(&mut writer, "405 Method Not Allowed", JSON, &[], keep_alive)?;
+            if !keep_alive {
+                return Ok(());
+            }
+            continue;
+        }
+
+        if let Some(payjoins) = payjoins.filter(|_| http_req.path() == PAYJOIN_PATH) {
+            return payjoin_handler(
+                wallets.main(),
+                &mut reader,
+                writer,
+                http_req,
+                payjoins,
+                &shutdown,
+            );
+        }
+
+        let user = match http_req
+            .authorization
+            .as_deref()
+            .and_then(|auth| authenticate(users, auth))
+        {
+            Some(user) => user,
+            None => {
+                log::warn!("Rejecting JSONRPC request with invalid credentials.");
+                // Slow down brute-forcing attempts.
+                thread::sleep(time::Duration::from_millis(250));
+                return write_response(&mut writer, "401 Unauthorized", JSON, &[], false);
+            }
+        };
+
+        // Only authenticated connections count against the connections limit. Give back the
+        // pending permit before waiting for a slot to free up.
+        if !authenticated {
+            drop(_permit);
+            _permit = match connections.acquire(&shutdown) {
+                Some(permit) => permit,
+                None => return Ok(()),
+            };
+            authenticated = true;
+            reader.get_mut().deadline = None;
+        }
+
+        if let Err(e) = read_body(&mut reader, &mut http_req) {
+            return read_failure(&mut writer, e);
+        }
+        let req: Request = match serde_json::from_slice(&http_req.body) {
+            Ok(req) => req,
+            Err(e) => {
+                log::debug!("Invalid JSONRPC request: '{}'", e);
+                write_response(&mut writer, "400 Bad Request", JSON, &[], keep_alive)?;
+                if !keep_alive {
+                    return Ok(());
+                }
+                continue;
+            }
+        };
+
+        let req_id = req.id.clone();
+        let (status, response) = if !user.is_allowed(&req.method) {
+            log::warn!(
+                "User '{}' is not allowed to call '{}'.",
+                user.user,
+                req.method
+            );
+            (
+                "403 Forbidden",
+                Response::error(req_id, Error::method_not_allowed(&req.method)),
+            )
+        } else {
+            if req.method == "stop" {
+                shutdown.store(true, atomic::Ordering::Relaxed);
+                log::info!("Stopping the liana daemon.");
+            }
+
+            log::trace!("JSONRPC request: {:?}", serde_json::to_string(&req));
+            let response = api::handle_wallets_request(&wallets, http_req.wallet(), req)
+                .unwrap_or_else(|e| Response::error(req_id, e));
+            ("200 OK", response)
+        };
+        log::trace!("JSONRPC response: {:?}", serde_json::to_string(&response));
+        let body = serde_json::to_vec(&response)?;
+        if let Err(e) = write_response(&mut writer, status, JSON, &body, keep_alive) {
+            log::error!("Error writing response: '{}'", e);
+            return Ok(());
+        }
+
+        if !keep_alive {
+            return Ok(());
+        }
+    }
+
+    Ok(())
+}
+
+/// The main event loop. Wait for connections, and treat requests sent through them.
+pub fn rpcserver_loop(
+    listener: net::TcpListener,
+    users: Vec<HttpRpcUser>,
+    payjoin: bool,
+    wallets: Wallets,
+    shutdown: sync::Arc<atomic::AtomicBool>,
+) -> Result<(), io::Error> {
+    // Same as for the Unix Domain Socket, treat each connection in its own thread.
+    let connections = Semaphore::new(MAX_CONNECTIONS);
+    let pending_connections = Semaphore::new(MAX_PENDING_CONNECTIONS);
+    let payjoins = payjoin.then(|| Semaphore::new(MAX_PAYJOIN_REQUESTS));
+    let users: sync::Arc<[HttpRpcUser]> = sync::Arc::from(users);
+    let mut handler_id: u64 = 0;
+
+    listener.set_nonblocking(true)?;
+    while !shutdown.load(atomic::Ordering::Relaxed) {
+        let (connection, peer_addr) = match listener.accept() {
+            Ok(c) => c,
+            Err(_) => {
+                thread::sleep(time::Duration::from_millis(100));
+                continue;
+            }
+        };
+        log::trace!("New JSONRPC connection from {}", peer_addr);
+
+        let pending_permit = match pending_connections.try_acquire() {
+            Some(permit) => permit,
+            None => {
+                log::warn!(
+                    "Too many unauthenticated JSONRPC connections. Dropping connection from {}.",
+                    peer_addr
+                );
+                continue;
+            }
+        };
+
+        handler_id += 1;
+        thread::Builder::new()
+            .name(format!("liana-jsonrpc-http-{}", handler_id))
+            .spawn({
+                let wallets = wallets.clone();
+                let users = users.clone();
+                let connections = connections.clone();
+                let payjoins = payjoins.clone();
+                let shutdown = shutdown.clone();
+
+                move || {
+                    if let Err(e) = connection_handler(
+                        wallets,
+                        connection,
+                        &users,
+                        payjoins.as_ref(),
+                        pending_permit,
+                        &connections,
+                        shutdown,
+                    ) {
+                        log::error!("Error while handling connection {}: '{}'", handler_id, e);
+                    } else {
+                        log::trace!("Connection {} terminated without error.", handler_id);
+                    }
+                }
+            })?;
+    }
+
+    Ok(())
+}
+
+// Write the cookie file readable only by the user, replacing any leftover from a previous run.
+fn write_cookie_file(cookie_path: &path::Path, content: &str) -> Result<(), io::Error> {
+    if cookie_path.exists() {
+        log::debug!("Removing leftover rpc cookie file.");
+        fs::remove_file(cookie_path)?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(cookie_path)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()
+}
+
+/// Bind to the TCP socket at `addr` and create a cookie file at `cookie_path` with fresh
+/// credentials granting access to all methods. Returns the listener and the credentials.
+pub fn rpcserver_setup(
+    addr: &net::SocketAddr,
+    cookie_path: &path::Path,
+) -> Result<(net::TcpListener, HttpRpcUser), io::Error> {
+    log::debug!("Binding JSONRPC HTTP server at {}", addr);
+    let listener = net::TcpListener::bind(addr)?;
+
+    let password = liana::random::random_bytes()
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?[..]
+        .to_lower_hex_string();
+    let cookie = HttpRpcUser {
+        user: COOKIE_USER.to_string(),
+        password,
+        allowed_methods: None,
+    };
+    write_cookie_file(cookie_path, &format!("{}:{}", cookie.user, cookie.password))?;
+
+    Ok((listener, cookie))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(user: &str, password: &str, allowed_methods: Option<&[&str]>) -> HttpRpcUser {
+        HttpRpcUser {
+            user: user.to_string(),
+            password: password.to_string(),
+            allowed_methods: allowed_methods
+                .map(|methods| methods.iter().map(|m| m.to_string()).collect()),
+        }
+    }
+
+    fn read_request(stream: &mut dyn BufRead) -> Result<Option<HttpRequest>, io::Error> {
+        let mut req = match read_request_head(stream)? {
+            Some(req) => req,
+            None => return Ok(None),
+        };
+        read_body(stream, &mut req)?;
+        Ok(Some(req))
+    }
+
+    fn basic_auth(credentials: &str) -> String {
+        format!(
+            "Basic {}",
+            base64::engine::general_purpose::STANDARD.encode(credentials)
+        )
+    }
+
+    #[test]
+    fn request_read() {
+        // A simple request with a body.
+        let body = r#"{"jsonrpc": "2.0", "id": 0, "method": "getinfo"}"#;
+        let raw_req = format!(
+            "POST / HTTP/1.1\r\nHost: 127.0.0.1\r\nAuthorization: Basic dXNlcjpwYXNz\r\ncontent-length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let mut stream = io::Cursor::new(raw_req.into_bytes());
+        let req = read_request(&mut stream).unwrap().unwrap();
+        assert_eq!(
+            req,
+            HttpRequest {
+                method: "POST".to_string(),
+                target: "/".to_string(),
+                authorization: Some("Basic dXNlcjpwYXNz".to_string()),
+                keep_alive: true,
+                content_length: body.len(),
+                body: body.as_bytes().to_vec(),
+            }
+        );
+        // The connection was then closed.
+        assert!(read_request(&mut stream).unwrap().is_none());
+
+        // Multiple requests on the same connection, one of them closing it.
+        let raw_reqs = format!(
+            "POST / HTTP/1.1\r\nContent-Length: 2\r\n\r\n{{}}POST / HTTP/1.1\nConnection: close\nContent-Length: {}\n\n{}",
+            body.len(),
+            body
+        );
+        let mut stream = io::Cursor::new(raw_reqs.into_bytes());
+        let req = read_request(&mut stream).unwrap().unwrap();
+        assert_eq!(req.body, b"{}");
+        assert!(req.keep_alive);
+        let req = read_request(&mut stream).unwrap().unwrap();
+        assert_eq!(req.body, body.as_bytes());
+        assert!(!req.keep_alive);
+
+        // The query is separated from the path.
+        let mut stream = io::Cursor::new(
+            b"POST /payjoin?v=1&minfeerate=2 HTTP/1.1\r\nContent-Length: 0\r\n\r\n".to_vec(),
+        );
+        let req = read_request(&mut stream).unwrap().unwrap();
+        assert_eq!(req.path(), "/payjoin");
+        assert_eq!(req.query(), "v=1&minfeerate=2");
+        let mut stream = io::Cursor::new(b"POST /payjoin HTTP/1.1\r\n\r\n".to_vec());
+        let req = read_request(&mut stream).unwrap().unwrap();
+        assert_eq!(req.path(), "/payjoin");
+        assert_eq!(req.query(), "");
+        assert_eq!(req.wallet(), None);
+
+        // The wallet may be selected by the path.
+        let mut stream =
+            io::Cursor::new(b"POST /wallet/savings HTTP/1.1\r\nContent-Length: 0\r\n\r\n".to_vec());
+        let req = read_request(&mut stream).unwrap().unwrap();
+        assert_eq!(req.wallet(), Some("savings"));
+
+        // HTTP/1.0 connections aren't persistent by default.
+        let mut stream = io::Cursor::new(b"POST / HTTP/1.0\r\n\r\n".to_vec());
+        let req = read_request(&mut stream).unwrap().unwrap();
+        assert!(!req.keep_alive);
+        assert!(req.body.is_empty());
+
+        // Invalid requests.
+        for raw_req in [
+            "POST /\r\n\r\n".to_string(),
+            "POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\n{}".to_string(),
+            "POST / HTTP/1.1\r\nContent-Length: ten\r\n\r\n".to_string(),
+            format!(
+                "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+                MAX_BODY_SIZE + 1
+            ),
+            format!(
+                "POST / HTTP/1.1\r\nX-Pad: {}\r\n\r\n",
+                "a".repeat(MAX_HEADERS_SIZE)
+            ),
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n".to_string(),
+        ] {
+            let mut stream = io::Cursor::new(raw_req.into_bytes());
+            assert!(read_request(&mut stream).is_err());
+        }
+    }
+
+    #[test]
+    fn connections_limit() {
+        let shutdown = atomic::AtomicBool::new(false);
+        let sem = Semaphore::new(2);
+        let permit_a = sem.try_acquire().unwrap();
+        let _permit_b = sem.acquire(&shutdown).unwrap();
+        assert!(sem.try_acquire().is_none());
+
+        // A permit is given back when dropped, and waiters are woken up.
+        let waiter = thread::spawn({
+            let sem = sem.clone();
+            move || sem.acquire(&atomic::AtomicBool::new(false))
+        });
+        thread::sleep(time::Duration::from_millis(50));
+        drop(permit_a);
+        let _permit_c = waiter.join().unwrap().unwrap();
+        assert!(sem.try_acquire().is_none());
+
+        // Waiters give up when told to shut down.
+        shutdown.store(true, atomic::Ordering::Relaxed);
+        assert!(sem.acquire(&shutdown).is_none());
+    }
+
+    #[test]
+    fn request_auth() {
+        let users = vec![
+            user(COOKIE_USER, "cookiepass", None),
+            user("reader", "pass:word", Some(&["getinfo", "listcoins"])),
+        ];
+
+        let cookie = authenticate(&users, &basic_auth("__cookie__:cookiepass")).unwrap();
+        assert_eq!(cookie.user, COOKIE_USER);
+        assert!(cookie.is_allowed("broadcastspend"));
+
+        // The password may contain a colon.
+        let reader = authenticate(&users, &basic_auth("reader:pass:word")).unwrap();
+        assert_eq!(reader.user, "reader");
+        assert!(reader.is_allowed("getinfo"));
+        assert!(!reader.is_allowed("broadcastspend"));
+        assert!(!reader.is_allowed("delspendtx"));
+
+        // Invalid credentials.
+        assert!(authenticate(&users, &basic_auth("reader:pass")).is_none());
+        assert!(authenticate(&users, &basic_auth("reader:cookiepass")).is_none());
+        assert!(authenticate(&users, &basic_auth("__cookie__:cookiepass ")).is_none());
+        assert!(authenticate(&users, "Basic not_base64").is_none());
+        assert!(authenticate(&users, "Bearer cookiepass").is_none());
+        assert!(authenticate(&[], &basic_auth("__cookie__:cookiepass")).is_none());
+    }
+}
@@ -1,31 +1,85 @@
+mod http;
 #[cfg(unix)]
 mod unix;
 
 use std::{
-    io, path,
+    fs, io,
     sync::{atomic::AtomicBool, Arc},
+    thread,
 };
 
-use crate::DaemonControl;
+use crate::{datadir::DataDirectory, wallets::Wallets};
+
+// Start the JSONRPC server over HTTP in its own thread, if it is enabled in the configuration.
+fn start_http_server(
+    data_dir: &DataDirectory,
+    wallets: &Wallets,
+    shutdown: &Arc<AtomicBool>,
+) -> Result<Option<thread::JoinHandle<Result<(), io::Error>>>, io::Error> {
+    let http_config = match wallets.main().config.http_rpc_config.clone() {
+        Some(config) => config,
+        None => return Ok(None),
+    };
+    let cookie_path = data_dir.lianad_rpc_cookie_path();
+    let (listener, cookie) = http::rpcserver_setup(&http_config.addr, &cookie_path)?;
+    let payjoin = http_config.payjoin;
+    let addr = http_config.addr;
+    let mut users = http_config.users;
+    users.push(cookie);
+
+    thread::Builder::new()
+        .name("liana-jsonrpc-http".to_string())
+        .spawn({
+            let wallets = wallets.clone();
+            let shutdown = shutdown.clone();
+            move || {
+                log::info!("JSONRPC HTTP server started on {}.", addr);
+                if payjoin {
+                    log::info!("Receiving payjoins at {}/payjoin.", addr);
+                }
+                let res = http::rpcserver_loop(listener, users, payjoin, wallets, shutdown);
+                if let Err(e) = fs::remove_file(&cookie_path) {
+                    log::error!("Error removing the JSONRPC cookie file: '{}'", e);
+                }
+                log::info!("JSONRPC HTTP server stopped.");
+                res
+            }
+        })
+        .map(Some)
+}
 
 #[cfg(unix)]
 pub fn run(
-    socket_path: &path::Path,
-    daemon_control: DaemonControl,
+    data_dir: &DataDirectory,
+    wallets: Wallets,
     shutdown: Arc<AtomicBool>,
 ) -> Result<(), io::Error> {
-    let listener = unix::rpcserver_setup(socket_path)?;
+    let listener = unix::rpcserver_setup(&data_dir.lianad_rpc_socket_path())?;
+    let http_handle = start_http_server(data_dir, &wallets, &shutdown)?;
     log::info!("JSONRPC server started.");
-    let res = unix::rpcserver_loop(listener, daemon_control, shutdown);
+    let res = unix::rpcserver_loop(listener, wallets, shutdown.clone());
     log::info!("JSONRPC server stopped.");
+
+    // Make sure to stop the HTTP server too if we stopped because of an error.
+    if let Some(handle) = http_handle {
+        shutdown.store(true, std::sync::atomic::Ordering::Relaxed);
+        handle
+            .join()
+            .expect("JSONRPC HTTP server thread must not panic")?;
+    }
     res
 }
 
 #[cfg(windows)]
 pub fn run(
-    _socket_path: &path::Path,
-    _daemon_control: DaemonControl,
-    _shutdown: Arc<AtomicBool>,
+    data_dir: &DataDirectory,
+    wallets: Wallets,
+    shutdown: Arc<AtomicBool>,
 ) -> Result<(), io::Error> {
-    todo!("Implement a json rpc server over Named pipe");
+    match start_http_server(data_dir, &wallets, &shutdown)? {
+        Some(handle) => handle
+            .join()
+            .expect("JSONRPC HTTP server thread must not panic"),
+        None => todo!("Implement a json rpc server over Named pipe"),
+    }
 }
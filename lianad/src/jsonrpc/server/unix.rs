@@ -6,13 +6,15 @@
 use crate::{
     jsonrpc::{
         api,
-        rpc::{Request, Response},
+        rpc::{Notification, Request, Response},
     },
-    DaemonControl,
+    notifications::Subscription,
+    wallets::Wallets,
 };
 
 use std::{
-    fs, io,
+    fs,
+    io::{self, Read, Write},
     os::unix::{fs::PermissionsExt, net},
     path,
     sync::{self, atomic},
@@ -82,9 +84,63 @@ fn read_command(
     }
 }
 
+// Whether the client closed this connection. Any data it sent is discarded.
+fn is_closed(stream: &mut net::UnixStream) -> Result<bool, io::Error> {
+    let mut buf = [0; 512];
+    stream.set_read_timeout(Some(time::Duration::from_millis(1)))?;
+    let res = match stream.read(&mut buf) {
+        Ok(0) => Ok(true),
+        Ok(_) => Ok(false),
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ) =>
+        {
+            Ok(false)
+        }
+        Err(e) => Err(e),
+    };
+    stream.set_read_timeout(None)?;
+    res
+}
+
+// Stream the events of this subscription to the client as JSONRPC notifications, one per line,
+// until either the client closes the connection or we shut down.
+fn events_stream(
+    subscription: Subscription,
+    mut stream: net::UnixStream,
+    shutdown: sync::Arc<atomic::AtomicBool>,
+) -> Result<(), io::Error> {
+    while !shutdown.load(atomic::Ordering::Relaxed) {
+        match subscription.next_event(time::Duration::from_millis(100)) {
+            Ok(Some(event)) => {
+                let notification = Notification::new("event", serde_json::to_value(&event)?);
+                log::trace!(
+                    "JSONRPC notification: {:?}",
+                    serde_json::to_string(&notification)
+                );
+                serde_json::to_writer(&stream, &notification)?;
+                stream.write_all(b"\n")?;
+            }
+            Ok(None) => {
+                if is_closed(&mut stream)? {
+                    return Ok(());
+                }
+            }
+            Err(_) => {
+                log::warn!("Subscription terminated, closing the connection.");
+                return Ok(());
+            }
+        }
+    }
+
+    Ok(())
+}
+
 // Handle all messages from this connection.
 fn connection_handler(
-    mut control: DaemonControl,
+    wallets: Wallets,
     mut stream: net::UnixStream,
     shutdown: sync::Arc<atomic::AtomicBool>,
 ) -> Result<(), io::Error> {
@@ -93,7 +149,7 @@ fn connection_handler(
     let mut cursor = 0;
 
     while !shutdown.load(atomic::Ordering::Relaxed) {
-        let req = match read_command(&mut stream, &mut buf, &mut end, &mut cursor)? {
+        let mut req = match read_command(&mut stream, &mut buf, &mut end, &mut cursor)? {
             Some(req) => req,
             None => {
                 // Connection closed.
@@ -107,9 +163,28 @@ fn connection_handler(
             log::info!("Stopping the liana daemon.");
         }
 
+        // Once subscribed, the connection is only used to stream the events.
+        if &req.method == "subscribe" {
+            log::trace!("JSONRPC request: {:?}", serde_json::to_string(&req));
+            let subscription = api::request_wallet(&wallets, None, &mut req.params)
+                .and_then(|control| api::subscribe(&control, req.params));
+            match subscription {
+                Ok(subscription) => {
+                    let response = Response::success(req_id, serde_json::json!({}));
+                    serde_json::to_writer(&stream, &response)?;
+                    stream.write_all(b"\n")?;
+                    return events_stream(subscription, stream, shutdown);
+                }
+                Err(e) => {
+                    serde_json::to_writer(&stream, &Response::error(req_id, e))?;
+                    continue;
+                }
+            }
+        }
+
         log::trace!("JSONRPC request: {:?}", serde_json::to_string(&req));
-        let response =
-            api::handle_request(&mut control, req).unwrap_or_else(|e| Response::error(req_id, e));
+        let response = api::handle_wallets_request(&wallets, None, req)
+            .unwrap_or_else(|e| Response::error(req_id, e));
         log::trace!("JSONRPC response: {:?}", serde_json::to_string(&response));
         if let Err(e) = serde_json::to_writer(&stream, &response) {
             log::error!("Error writing response: '{}'", e);
@@ -120,11 +195,10 @@ fn connection_handler(
     Ok(())
 }
 
-// FIXME: have a decent way to share the DaemonControl between connections. Maybe make it Clone?
 /// The main event loop. Wait for connections, and treat requests sent through them.
 pub fn rpcserver_loop(
     listener: net::UnixListener,
-    daemon_control: DaemonControl,
+    wallets: Wallets,
     shutdown: sync::Arc<atomic::AtomicBool>,
 ) -> Result<(), io::Error> {
     // Keep it simple. We don't need great performances so just treat each connection in
@@ -151,12 +225,12 @@ pub fn rpcserver_loop(
         thread::Builder::new()
             .name(format!("liana-jsonrpc-{}", handler_id))
             .spawn({
-                let control = daemon_control.clone();
+                let wallets = wallets.clone();
                 let counter = connections_counter.clone();
                 let shutdown = shutdown.clone();
 
                 move || {
-                    if let Err(e) = connection_handler(control, connection, shutdown) {
+                    if let Err(e) = connection_handler(wallets, connection, shutdown) {
                         log::error!("Error while handling connection {}: '{}'", handler_id, e);
                     } else {
                         log::trace!("Connection {} terminated without error.", handler_id);
@@ -1,21 +1,31 @@
+pub mod accounting;
 mod bitcoin;
 pub mod commands;
 pub mod config;
 mod database;
 pub mod datadir;
 mod jsonrpc;
+pub mod notifications;
+pub mod payjoin;
+pub mod payments;
+pub mod proof_of_reserves;
+pub mod signing;
+pub mod spending_policy;
 #[cfg(test)]
 mod testutils;
+pub mod wallets;
 
 pub use bdk_electrum::electrum_client;
 pub use bip329;
-use bitcoin::electrum;
+use bitcoin::{cbf, electrum};
 use datadir::DataDirectory;
 pub use miniscript;
 
 pub use crate::bitcoin::{
-    d::{BitcoinD, BitcoindError, WalletError},
+    cbf::{Cbf, CbfError},
+    d::{BitcoinD, BitcoindConnection, BitcoindError, WalletError},
     electrum::{Electrum, ElectrumError},
+    esplora::{client::Client as EsploraClient, Esplora, EsploraError},
 };
 
 use crate::jsonrpc::server;
@@ -24,17 +34,19 @@ use crate::{
     config::Config,
     database::{
         sqlite::{FreshDbOptions, SqliteDb, SqliteDbError, MAX_DB_VERSION_NO_TX_DB},
-        DatabaseInterface,
+        DatabaseConnection, DatabaseInterface, LabelItem,
     },
 };
 
 use std::{
+    collections::{HashMap, VecDeque},
     error, fmt, io, path,
     sync::{self, mpsc},
     thread,
 };
 
-use miniscript::bitcoin::{constants::ChainHash, hashes::Hash, secp256k1, BlockHash};
+use liana::signer::SignerError;
+use miniscript::bitcoin::{constants::ChainHash, hashes::Hash, secp256k1, BlockHash, Txid};
 
 #[cfg(not(test))]
 use std::panic;
@@ -91,11 +103,16 @@ pub enum StartupError {
     DatadirCreation(path::PathBuf, io::Error),
     MissingBitcoindConfig,
     MissingElectrumConfig,
+    MissingEsploraConfig,
+    MissingCbfConfig,
     MissingBitcoinBackendConfig,
     DbMigrateBitcoinTxs(&'static str),
     Database(SqliteDbError),
     Bitcoind(BitcoindError),
     Electrum(ElectrumError),
+    Esplora(EsploraError),
+    Cbf(CbfError),
+    Signers(SignerError),
     #[cfg(windows)]
     NoWatchonlyInDatadir,
 }
@@ -120,6 +137,14 @@ impl fmt::Display for StartupError {
                 f,
                 "Our Bitcoin interface is Electrum but we have no 'electrum_config' entry in the configuration."
             ),
+            Self::MissingEsploraConfig => write!(
+                f,
+                "Our Bitcoin interface is Esplora but we have no 'esplora_config' entry in the configuration."
+            ),
+            Self::MissingCbfConfig => write!(
+                f,
+                "Our Bitcoin interface is compact block filters but we have no 'cbf_config' entry in the configuration."
+            ),
             Self::MissingBitcoinBackendConfig => write!(
                 f,
                 "No Bitcoin backend entry in the configuration."
@@ -131,6 +156,13 @@ impl fmt::Display for StartupError {
             Self::Database(e) => write!(f, "Error initializing database: '{}'.", e),
             Self::Bitcoind(e) => write!(f, "Error setting up bitcoind interface: '{}'.", e),
             Self::Electrum(e) => write!(f, "Error setting up Electrum interface: '{}'.", e),
+            Self::Esplora(e) => write!(f, "Error setting up Esplora interface: '{}'.", e),
+            Self::Cbf(e) => write!(
+                f,
+                "Error setting up compact block filters interface: '{}'.",
+                e
+            ),
+            Self::Signers(e) => write!(f, "Error loading the hot signers: '{}'.", e),
             #[cfg(windows)]
             Self::NoWatchonlyInDatadir => {
                 write!(
@@ -187,7 +219,7 @@ fn setup_sqlite(
 
     // If opening an existing wallet whose database does not yet store the wallet transactions,
     // query them from the Bitcoin backend before proceeding to the migration.
-    let sqlite = SqliteDb::new(db_path, options, secp)?;
+    let sqlite = SqliteDb::new(db_path, options, secp)?.with_gap_limit(config.gap_limit);
     if !fresh_data_dir {
         let mut conn = sqlite.connection()?;
         let wallet_txs = if conn.db_version() <= MAX_DB_VERSION_NO_TX_DB {
@@ -209,6 +241,8 @@ fn setup_sqlite(
     }
 
     sqlite.sanity_check(config.bitcoin_config.network, &config.main_descriptor)?;
+    // The gap limit may have been increased since the last startup.
+    sqlite.connection()?.fill_addresses_mapping(secp);
     log::info!("Database initialized and checked.");
 
     Ok(sqlite)
@@ -220,6 +254,7 @@ fn setup_bitcoind(
     config: &Config,
     data_dir: &DataDirectory,
     fresh_data_dir: bool,
+    connection: Option<&BackendConnection>,
 ) -> Result<BitcoinD, StartupError> {
     let wo_path: path::PathBuf = data_dir.lianad_watchonly_wallet_path();
     let wo_path_str = wo_path.to_str().expect("Must be valid unicode").to_string();
@@ -239,7 +274,12 @@ fn setup_bitcoind(
         Some(config::BitcoinBackend::Bitcoind(bitcoind_config)) => bitcoind_config,
         _ => Err(StartupError::MissingBitcoindConfig)?,
     };
-    let bitcoind = BitcoinD::new(bitcoind_config, wo_path_str)?;
+    let bitcoind = match connection {
+        Some(BackendConnection::Bitcoind(connection)) => {
+            BitcoinD::with_connection(connection, wo_path_str)?
+        }
+        _ => BitcoinD::new(bitcoind_config, wo_path_str)?,
+    };
     bitcoind.node_sanity_checks(
         config.bitcoin_config.network,
         config.main_descriptor.is_taproot(),
@@ -262,21 +302,11 @@ fn setup_bitcoind(
     Ok(bitcoind)
 }
 
-// Create an Electrum interface from a client and BDK-based wallet, and do some sanity checks.
-// If all went well, returns the interface to Electrum.
-fn setup_electrum(
+// Create a BDK-based wallet and populate it with the data from our database.
+fn bdk_wallet_from_db(
     config: &Config,
-    db: sync::Arc<sync::Mutex<dyn DatabaseInterface>>,
-) -> Result<Electrum, StartupError> {
-    let electrum_config = match config.bitcoin_backend.as_ref() {
-        Some(config::BitcoinBackend::Electrum(electrum_config)) => electrum_config,
-        _ => Err(StartupError::MissingElectrumConfig)?,
-    };
-    // First create the client to communicate with the Electrum server.
-    let client = electrum::client::Client::new(electrum_config)
-        .map_err(|e| StartupError::Electrum(ElectrumError::Client(e)))?;
-    // Then create the BDK-based wallet and populate it with DB data.
-    let mut db_conn = db.connection();
+    db_conn: &mut dyn DatabaseConnection,
+) -> electrum::wallet::BdkWallet {
     let tip = db_conn.chain_tip();
     let coins: Vec<_> = db_conn
         .coins(&[], &[])
@@ -306,27 +336,227 @@ fn setup_electrum(
         .map(|(tx, _, _)| tx)
         .collect();
     let (receive_index, change_index) = (db_conn.receive_index(), db_conn.change_index());
-    let genesis_hash = {
-        let chain_hash = ChainHash::using_genesis_block(config.bitcoin_config.network);
-        BlockHash::from_byte_array(*chain_hash.as_bytes())
-    };
-    let bdk_wallet = electrum::wallet::BdkWallet::new(
+    electrum::wallet::BdkWallet::new(
         &config.main_descriptor,
-        genesis_hash,
+        genesis_hash(config.bitcoin_config.network),
         tip,
         &coins,
         &txs,
         receive_index,
         change_index,
-    );
+    )
+}
+
+fn genesis_hash(network: miniscript::bitcoin::Network) -> BlockHash {
+    let chain_hash = ChainHash::using_genesis_block(network);
+    BlockHash::from_byte_array(*chain_hash.as_bytes())
+}
+
+// Create an Electrum interface from a client and BDK-based wallet, and do some sanity checks.
+// If all went well, returns the interface to Electrum.
+fn setup_electrum(
+    config: &Config,
+    connection: Option<&BackendConnection>,
+    db: sync::Arc<sync::Mutex<dyn DatabaseInterface>>,
+) -> Result<Electrum, StartupError> {
+    let electrum_config = match config.bitcoin_backend.as_ref() {
+        Some(config::BitcoinBackend::Electrum(electrum_config)) => electrum_config,
+        _ => Err(StartupError::MissingElectrumConfig)?,
+    };
+    // First create the client to communicate with the Electrum server, unless we already have one.
+    let client = match connection {
+        Some(BackendConnection::Electrum(client)) => client.clone(),
+        _ => sync::Arc::new(
+            electrum::client::Client::new(electrum_config)
+                .map_err(|e| StartupError::Electrum(ElectrumError::Client(e)))?,
+        ),
+    };
+    // Then create the BDK-based wallet and populate it with DB data.
+    let mut db_conn = db.connection();
+    let bdk_wallet = bdk_wallet_from_db(config, db_conn.as_mut());
     let full_scan = db_conn.rescan_timestamp().is_some();
-    let electrum = Electrum::new(client, bdk_wallet, full_scan).map_err(StartupError::Electrum)?;
+    let stop_gap = config.gap_limit as usize;
+    let electrum =
+        Electrum::new(client, bdk_wallet, full_scan, stop_gap).map_err(StartupError::Electrum)?;
     electrum
-        .sanity_checks(&genesis_hash)
+        .sanity_checks(&genesis_hash(config.bitcoin_config.network))
         .map_err(StartupError::Electrum)?;
     Ok(electrum)
 }
 
+// Create an Esplora interface from a client and BDK-based wallet, and do some sanity checks.
+// If all went well, returns the interface to Esplora.
+fn setup_esplora(
+    config: &Config,
+    connection: Option<&BackendConnection>,
+    db: sync::Arc<sync::Mutex<dyn DatabaseInterface>>,
+) -> Result<Esplora, StartupError> {
+    let esplora_config = match config.bitcoin_backend.as_ref() {
+        Some(config::BitcoinBackend::Esplora(esplora_config)) => esplora_config,
+        _ => Err(StartupError::MissingEsploraConfig)?,
+    };
+    // First create the client to communicate with the Esplora server, unless we already have one.
+    let client = match connection {
+        Some(BackendConnection::Esplora(client)) => client.clone(),
+        _ => sync::Arc::new(
+            EsploraClient::new(esplora_config)
+                .map_err(|e| StartupError::Esplora(EsploraError::Client(e)))?,
+        ),
+    };
+    // Then create the BDK-based wallet and populate it with DB data.
+    let mut db_conn = db.connection();
+    let bdk_wallet = bdk_wallet_from_db(config, db_conn.as_mut());
+    let full_scan = db_conn.rescan_timestamp().is_some();
+    let esplora = Esplora::new(client, bdk_wallet, full_scan, config.gap_limit);
+    esplora
+        .sanity_checks(&genesis_hash(config.bitcoin_config.network))
+        .map_err(StartupError::Esplora)?;
+    Ok(esplora)
+}
+
+// Create an interface syncing using compact block filters from our peers, and a BDK-based wallet
+// populated with DB data.
+fn setup_cbf(
+    config: &Config,
+    data_dir: &DataDirectory,
+    connection: Option<&BackendConnection>,
+    db: sync::Arc<sync::Mutex<dyn DatabaseInterface>>,
+) -> Result<Cbf, StartupError> {
+    let cbf_config = match config.bitcoin_backend.as_ref() {
+        Some(config::BitcoinBackend::Cbf(cbf_config)) => cbf_config,
+        _ => Err(StartupError::MissingCbfConfig)?,
+    };
+    // Connect to our peers and load the chain of headers, unless we already did.
+    let connection = match connection {
+        Some(BackendConnection::Cbf(connection)) => connection.clone(),
+        _ => cbf::Connection::new(
+            cbf_config,
+            config.bitcoin_config.network,
+            &data_dir.cbf_headers_path(),
+        )
+        .map_err(StartupError::Cbf)?,
+    };
+    let mut db_conn = db.connection();
+    let bdk_wallet = bdk_wallet_from_db(config, db_conn.as_mut());
+    // If a rescan was requested, resume it. Otherwise there is no need to scan the blocks from
+    // before the creation of the wallet.
+    let (scan_timestamp, rescanning) = match db_conn.rescan_timestamp() {
+        Some(timestamp) => (timestamp, true),
+        None => (db_conn.timestamp(), false),
+    };
+    Ok(Cbf::new(
+        connection,
+        bdk_wallet,
+        Some(scan_timestamp),
+        rescanning,
+    ))
+}
+
+/// The connection to the Bitcoin backend. It is set up along the main wallet and reused by the
+/// additional wallets hosted by the daemon.
+#[derive(Clone)]
+pub(crate) enum BackendConnection {
+    Bitcoind(BitcoindConnection),
+    Electrum(sync::Arc<electrum::client::Client>),
+    Esplora(sync::Arc<EsploraClient>),
+    Cbf(cbf::Connection),
+}
+
+// Set up the interface to the Bitcoin backend of this configuration, reusing the given connection
+// if any. In the case of bitcoind, the interface must have been set up already as it's needed
+// before the database. Returns the interface along with its connection to the backend.
+#[allow(clippy::type_complexity)]
+fn setup_bitcoin_interface(
+    config: &Config,
+    data_dir: &DataDirectory,
+    connection: Option<&BackendConnection>,
+    bitcoind: Option<BitcoinD>,
+    db: &sync::Arc<sync::Mutex<dyn DatabaseInterface>>,
+) -> Result<
+    (
+        sync::Arc<sync::Mutex<dyn BitcoinInterface>>,
+        BackendConnection,
+    ),
+    StartupError,
+> {
+    Ok(match (bitcoind, &config.bitcoin_backend) {
+        (Some(bitcoind), _) => {
+            let connection = BackendConnection::Bitcoind(bitcoind.connection().clone());
+            (sync::Arc::from(sync::Mutex::from(bitcoind)), connection)
+        }
+        (None, Some(config::BitcoinBackend::Electrum(..))) => {
+            let electrum = setup_electrum(config, connection, db.clone())?;
+            let connection = BackendConnection::Electrum(electrum.client().clone());
+            (sync::Arc::from(sync::Mutex::from(electrum)), connection)
+        }
+        (None, Some(config::BitcoinBackend::Esplora(..))) => {
+            let esplora = setup_esplora(config, connection, db.clone())?;
+            let connection = BackendConnection::Esplora(esplora.client().clone());
+            (sync::Arc::from(sync::Mutex::from(esplora)), connection)
+        }
+        (None, Some(config::BitcoinBackend::Cbf(..))) => {
+            let cbf = setup_cbf(config, data_dir, connection, db.clone())?;
+            let connection = BackendConnection::Cbf(cbf.connection().clone());
+            (sync::Arc::from(sync::Mutex::from(cbf)), connection)
+        }
+        (None, _) => Err(StartupError::MissingBitcoinBackendConfig)?,
+    })
+}
+
+// Set up a wallet hosted along the main one. It is stored in the database of the main wallet and
+// created there if it isn't yet. Its data directory only holds the state of its Bitcoin backend, if
+// any, and is created if it doesn't exist yet. Its interface to the Bitcoin backend reuses the
+// connection of the main wallet if any.
+#[allow(clippy::type_complexity)]
+fn setup_wallet(
+    config: &Config,
+    name: &str,
+    main_db: &SqliteDb,
+    connection: Option<&BackendConnection>,
+    secp: &secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+) -> Result<
+    (
+        sync::Arc<sync::Mutex<dyn BitcoinInterface>>,
+        sync::Arc<sync::Mutex<dyn DatabaseInterface>>,
+    ),
+    StartupError,
+> {
+    let data_dir = config
+        .data_directory()
+        .ok_or(StartupError::DefaultDataDirNotFound)?;
+    if !data_dir.exists() {
+        data_dir
+            .init()
+            .map_err(|e| StartupError::DatadirCreation(data_dir.path().to_path_buf(), e))?;
+        log::info!(
+            "Created a new wallet data directory at '{}'",
+            data_dir.path().to_string_lossy()
+        );
+    }
+    let wallet_db = main_db.wallet(name)?;
+    let fresh_wallet = wallet_db.is_none();
+
+    // Set up the watchonly wallet first, so as not to store a wallet we can't poll in database.
+    let bitcoind = if let Some(config::BitcoinBackend::Bitcoind(_)) = &config.bitcoin_backend {
+        Some(setup_bitcoind(config, &data_dir, fresh_wallet, connection)?)
+    } else {
+        None
+    };
+    let sqlite = match wallet_db {
+        Some(db) => db,
+        None => main_db.create_wallet(name, &config.main_descriptor, secp)?,
+    }
+    .with_gap_limit(config.gap_limit);
+    sqlite.sanity_check(config.bitcoin_config.network, &config.main_descriptor)?;
+    // The gap limit may have been increased since the wallet was last loaded.
+    sqlite.connection()?.fill_addresses_mapping(secp);
+    let db: sync::Arc<sync::Mutex<dyn DatabaseInterface>> =
+        sync::Arc::from(sync::Mutex::from(sqlite));
+    let (bit, _) = setup_bitcoin_interface(config, &data_dir, connection, bitcoind, &db)?;
+
+    Ok((bit, db))
+}
+
 #[derive(Clone)]
 pub struct DaemonControl {
     config: Config,
@@ -335,6 +565,16 @@ pub struct DaemonControl {
     // FIXME: Should we require Sync on DatabaseInterface rather than using a Mutex?
     db: sync::Arc<sync::Mutex<dyn DatabaseInterface>>,
     secp: secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+    notifier: notifications::Notifier,
+    // The progress of an ongoing address discovery, shared between the copies of this handle.
+    address_discovery: sync::Arc<sync::Mutex<Option<commands::AddressDiscoveryProgress>>>,
+    // The hot signers to sign spends with, if signing is enabled. Shared by all the wallets.
+    signers: Option<sync::Arc<signing::Signers>>,
+    // The coins we contributed to payjoins, by txid of the Original PSBT.
+    payjoin_contributions: sync::Arc<sync::Mutex<HashMap<Txid, commands::PayjoinContribution>>>,
+    // The labels of the outputs of the latest spends we created, set once the spend is stored.
+    #[allow(clippy::type_complexity)]
+    pending_labels: sync::Arc<sync::Mutex<VecDeque<(Txid, HashMap<LabelItem, Option<String>>)>>>,
 }
 
 impl DaemonControl {
@@ -344,6 +584,8 @@ impl DaemonControl {
         poller_sender: mpsc::SyncSender<poller::PollerMessage>,
         db: sync::Arc<sync::Mutex<dyn DatabaseInterface>>,
         secp: secp256k1::Secp256k1<secp256k1::VerifyOnly>,
+        notifier: notifications::Notifier,
+        signers: Option<sync::Arc<signing::Signers>>,
     ) -> DaemonControl {
         DaemonControl {
             config,
@@ -351,6 +593,12 @@ impl DaemonControl {
             poller_sender,
             db,
             secp,
+            notifier,
+            address_discovery: sync::Arc::new(sync::Mutex::new(None)),
+            signers,
+            payjoin_contributions: sync::Arc::new(sync::Mutex::new(HashMap::new())),
+            #[allow(clippy::type_complexity)]
+            pending_labels: sync::Arc::new(sync::Mutex::new(VecDeque::new())),
         }
     }
 
@@ -362,12 +610,13 @@ impl DaemonControl {
 }
 
 /// The handle to a Liana daemon. It might either be the handle for a daemon which exposes a
-/// JSONRPC server or one which exposes its API through a `DaemonControl`.
+/// JSONRPC server or one which exposes its API through the `DaemonControl` of each of the wallets
+/// it hosts.
 pub enum DaemonHandle {
     Controller {
         poller_sender: mpsc::SyncSender<poller::PollerMessage>,
         poller_handle: thread::JoinHandle<()>,
-        control: DaemonControl,
+        wallets: wallets::Wallets,
     },
     Server {
         poller_sender: mpsc::SyncSender<poller::PollerMessage>,
@@ -382,7 +631,7 @@ impl DaemonHandle {
     /// method to check for internal errors. To shut down the daemon use the `stop` method.
     ///
     /// The `with_rpc_server` controls whether we should start a JSONRPC server to receive queries
-    /// or instead return a `DaemonControl` object for a caller to access the daemon's API.
+    /// or instead return the `Wallets` hosted by the daemon for a caller to access their API.
     ///
     /// You may specify a custom Bitcoin interface through the `bitcoin` parameter. If `None`, the
     /// default Bitcoin interface (`bitcoind` JSONRPC) will be used.
@@ -415,7 +664,7 @@ impl DaemonHandle {
         // migration when setting up SQLite below.
         let bitcoind = if bitcoin.is_none() {
             if let Some(config::BitcoinBackend::Bitcoind(_)) = &config.bitcoin_backend {
-                Some(setup_bitcoind(&config, &data_dir, fresh_data_dir)?)
+                Some(setup_bitcoind(&config, &data_dir, fresh_data_dir, None)?)
             } else {
                 None
             }
@@ -423,35 +672,65 @@ impl DaemonHandle {
             None
         };
 
-        // Then set up the database backend.
-        let db = match db {
-            Some(db) => sync::Arc::from(sync::Mutex::from(db)),
-            None => sync::Arc::from(sync::Mutex::from(setup_sqlite(
-                &config,
-                &data_dir,
-                fresh_data_dir,
-                &secp,
-                &bitcoind,
-            )?)) as sync::Arc<sync::Mutex<dyn DatabaseInterface>>,
+        // Then set up the database backend. Keep the SQLite database, if used, to store the
+        // additional wallets.
+        let (db, sqlite) = match db {
+            Some(db) => (
+                sync::Arc::from(sync::Mutex::from(db))
+                    as sync::Arc<sync::Mutex<dyn DatabaseInterface>>,
+                None,
+            ),
+            None => {
+                let sqlite = setup_sqlite(&config, &data_dir, fresh_data_dir, &secp, &bitcoind)?;
+                (
+                    sync::Arc::from(sync::Mutex::from(sqlite.clone()))
+                        as sync::Arc<sync::Mutex<dyn DatabaseInterface>>,
+                    Some(sqlite),
+                )
+            }
         };
 
-        // Finally set up the Bitcoin backend.
-        let bit = match (bitcoin, &config.bitcoin_backend) {
-            (Some(bit), _) => sync::Arc::from(sync::Mutex::from(bit)),
-            (None, Some(config::BitcoinBackend::Bitcoind(..))) => sync::Arc::from(
-                sync::Mutex::from(bitcoind.expect("bitcoind must have been set already")),
-            )
-                as sync::Arc<sync::Mutex<dyn BitcoinInterface>>,
-            (None, Some(config::BitcoinBackend::Electrum(..))) => {
-                sync::Arc::from(sync::Mutex::from(setup_electrum(&config, db.clone())?))
+        // Finally set up the Bitcoin backend. Keep its connection for the additional wallets.
+        let (bit, connection) = match bitcoin {
+            Some(bit) => (
+                sync::Arc::from(sync::Mutex::from(bit))
+                    as sync::Arc<sync::Mutex<dyn BitcoinInterface>>,
+                None,
+            ),
+            None => {
+                let (bit, connection) =
+                    setup_bitcoin_interface(&config, &data_dir, None, bitcoind, &db)?;
+                (bit, Some(connection))
             }
-            (None, None) => Err(StartupError::MissingBitcoinBackendConfig)?,
         };
 
+        // Load the hot signers if we are to sign spends.
+        let signers = config
+            .signing_config
+            .as_ref()
+            .map(|signing_config| {
+                signing::Signers::load(
+                    signing_config,
+                    &data_dir,
+                    config.bitcoin_config.network,
+                    db.clone(),
+                )
+                .map(sync::Arc::new)
+            })
+            .transpose()
+            .map_err(StartupError::Signers)?;
+
         // Start the poller thread. Keep the thread handle to be able to check if it crashed. Store
-        // an atomic to be able to stop it.
-        let mut bitcoin_poller =
-            poller::Poller::new(bit.clone(), db.clone(), config.main_descriptor.clone());
+        // an atomic to be able to stop it. The poller notifies the events it detects to the
+        // commands configured by the user and to the subscribers of the API.
+        let notifier = notifications::Notifier::new(config.notify_hooks.clone());
+        let mut bitcoin_poller = poller::Poller::new(
+            bit.clone(),
+            db.clone(),
+            config.main_descriptor.clone(),
+            notifier.clone(),
+        );
+        let polled_wallets = bitcoin_poller.wallets();
         let (poller_sender, poller_receiver) = mpsc::sync_channel(0);
         let poller_handle = thread::Builder::new()
             .name("Bitcoin Network poller".to_string())
@@ -467,16 +746,30 @@ impl DaemonHandle {
 
         // Create the API the external world will use to talk to us, either directly through the Rust
         // structure or through the JSONRPC server we may setup below.
-        let control = DaemonControl::new(config, bit, poller_sender.clone(), db, secp);
+        let control = DaemonControl::new(
+            config,
+            bit,
+            poller_sender.clone(),
+            db,
+            secp,
+            notifier,
+            signers,
+        );
+
+        // We may also host additional wallets.
+        let wallets = wallets::Wallets::new(control, sqlite, polled_wallets, connection);
 
         if with_rpc_server {
+            // Through the JSONRPC server, load those which were created in a previous run.
+            wallets.load_all();
+
             let rpcserver_shutdown = sync::Arc::from(sync::atomic::AtomicBool::from(false));
             let rpcserver_handle = thread::Builder::new()
                 .name("Bitcoin Network poller".to_string())
                 .spawn({
                     let shutdown = rpcserver_shutdown.clone();
                     move || {
-                        server::run(&data_dir.lianad_rpc_socket_path(), control, shutdown)?;
+                        server::run(&data_dir, wallets, shutdown)?;
                         Ok(())
                     }
                 })
@@ -493,7 +786,7 @@ impl DaemonHandle {
         Ok(DaemonHandle::Controller {
             poller_sender,
             poller_handle,
-            control,
+            wallets,
         })
     }
 
@@ -0,0 +1,453 @@
+//! # Liana notifications
+//!
+//! Let the outside world know about the events affecting the wallet as they are detected by the
+//! Bitcoin poller. This is done by running the commands configured by the user and by streaming
+//! the events to the clients which subscribed to them.
+
+use std::{
+    collections::HashSet,
+    process,
+    sync::{self, mpsc},
+    thread,
+};
+
+use miniscript::bitcoin;
+use serde::{Deserialize, Serialize};
+
+/// How many events may be pending for a subscriber before we give up on it.
+const SUBSCRIPTION_BUFFER_SIZE: usize = 1_000;
+
+/// How many events may be waiting for the command of a hook to be run before we drop new ones.
+const HOOK_QUEUE_SIZE: usize = 100;
+
+/// The kind of an [`Event`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    CoinReceived,
+    CoinConfirmed,
+    CoinSpent,
+    SpendConfirmed,
+    TipChanged,
+    Reorg,
+    RescanFinished,
+}
+
+impl EventKind {
+    pub fn from_arg(s: &str) -> Option<EventKind> {
+        match s {
+            "coin_received" => Some(EventKind::CoinReceived),
+            "coin_confirmed" => Some(EventKind::CoinConfirmed),
+            "coin_spent" => Some(EventKind::CoinSpent),
+            "spend_confirmed" => Some(EventKind::SpendConfirmed),
+            "tip_changed" => Some(EventKind::TipChanged),
+            "reorg" => Some(EventKind::Reorg),
+            "rescan_finished" => Some(EventKind::RescanFinished),
+            _ => None,
+        }
+    }
+
+    /// Converts an `EventKind` to its equivalent argument name as used in the `subscribe`
+    /// RPC command and in the configuration.
+    pub fn to_arg(&self) -> &'static str {
+        match self {
+            EventKind::CoinReceived => "coin_received",
+            EventKind::CoinConfirmed => "coin_confirmed",
+            EventKind::CoinSpent => "coin_spent",
+            EventKind::SpendConfirmed => "spend_confirmed",
+            EventKind::TipChanged => "tip_changed",
+            EventKind::Reorg => "reorg",
+            EventKind::RescanFinished => "rescan_finished",
+        }
+    }
+}
+
+/// An event affecting the wallet.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// A new coin was received. It may or may not be confirmed yet.
+    CoinReceived {
+        outpoint: bitcoin::OutPoint,
+        amount: bitcoin::Amount,
+        address: bitcoin::Address,
+        derivation_index: u32,
+        is_change: bool,
+    },
+    /// A coin's transaction was confirmed.
+    CoinConfirmed {
+        outpoint: bitcoin::OutPoint,
+        height: i32,
+        time: u32,
+    },
+    /// A transaction spending a coin was detected. It may be replaced later on.
+    CoinSpent {
+        outpoint: bitcoin::OutPoint,
+        spend_txid: bitcoin::Txid,
+    },
+    /// The transaction spending a coin was confirmed.
+    SpendConfirmed {
+        outpoint: bitcoin::OutPoint,
+        spend_txid: bitcoin::Txid,
+        height: i32,
+        time: u32,
+    },
+    /// We processed a new best block.
+    TipChanged {
+        height: i32,
+        hash: bitcoin::BlockHash,
+    },
+    /// The block chain was reorganized. We rolled back our state to this common ancestor of the
+    /// former and the new best chains.
+    Reorg {
+        height: i32,
+        hash: bitcoin::BlockHash,
+    },
+    /// A rescan of the block chain completed. We rolled back our state to the last block before
+    /// the rescan timestamp in order to process the past transactions.
+    RescanFinished { timestamp: u32 },
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::CoinReceived { .. } => EventKind::CoinReceived,
+            Event::CoinConfirmed { .. } => EventKind::CoinConfirmed,
+            Event::CoinSpent { .. } => EventKind::CoinSpent,
+            Event::SpendConfirmed { .. } => EventKind::SpendConfirmed,
+            Event::TipChanged { .. } => EventKind::TipChanged,
+            Event::Reorg { .. } => EventKind::Reorg,
+            Event::RescanFinished { .. } => EventKind::RescanFinished,
+        }
+    }
+
+    /// What the event is about: the outpoint of the coin, the hash of the block or the
+    /// timestamp of the rescan.
+    pub fn subject(&self) -> String {
+        match self {
+            Event::CoinReceived { outpoint, .. }
+            | Event::CoinConfirmed { outpoint, .. }
+            | Event::CoinSpent { outpoint, .. }
+            | Event::SpendConfirmed { outpoint, .. } => outpoint.to_string(),
+            Event::TipChanged { hash, .. } | Event::Reorg { hash, .. } => hash.to_string(),
+            Event::RescanFinished { timestamp } => timestamp.to_string(),
+        }
+    }
+}
+
+/// A command to run upon some events.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct NotifyHook {
+    /// The command to run. `%e` is replaced by the kind of the event and `%s` by its subject
+    /// (see [`Event::subject`]). The whole event is passed as JSON in the `LIANA_EVENT`
+    /// environment variable.
+    pub command: String,
+    /// The kinds of events to run the command upon. All of them if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<EventKind>>,
+}
+
+impl NotifyHook {
+    fn is_interested(&self, kind: EventKind) -> bool {
+        self.events
+            .as_ref()
+            .map(|events| events.contains(&kind))
+            .unwrap_or(true)
+    }
+
+    // Run the command for this event and wait for it to exit.
+    fn run(&self, event: &Event) {
+        let command = self
+            .command
+            .replace("%e", event.kind().to_arg())
+            .replace("%s", &event.subject());
+        let event_json = serde_json::to_string(event).expect("Serialization can't fail");
+
+        #[cfg(not(windows))]
+        let mut cmd = process::Command::new("sh");
+        #[cfg(not(windows))]
+        cmd.arg("-c");
+        #[cfg(windows)]
+        let mut cmd = process::Command::new("cmd");
+        #[cfg(windows)]
+        cmd.arg("/C");
+
+        log::debug!("Running notification command '{}'", command);
+        match cmd.arg(&command).env("LIANA_EVENT", event_json).status() {
+            Ok(status) if !status.success() => {
+                log::warn!("Notification command '{}' exited with {}.", command, status);
+            }
+            Err(e) => {
+                log::error!("Error running notification command '{}': {}", command, e);
+            }
+            _ => {}
+        }
+    }
+}
+
+// A hook along with the queue of the events to run its command upon. The command is run by a
+// dedicated thread for one event after the other, which stops once the queue is dropped.
+struct HookWorker {
+    hook: NotifyHook,
+    sender: mpsc::SyncSender<Event>,
+}
+
+impl HookWorker {
+    fn start(hook: NotifyHook) -> Result<HookWorker, std::io::Error> {
+        let (sender, receiver) = mpsc::sync_channel::<Event>(HOOK_QUEUE_SIZE);
+        thread::Builder::new()
+            .name("liana-notify-hook".to_string())
+            .spawn({
+                let hook = hook.clone();
+                move || {
+                    for event in receiver {
+                        hook.run(&event);
+                    }
+                }
+            })?;
+        Ok(HookWorker { hook, sender })
+    }
+
+    // Queue this event for the command to be run upon. It is dropped if the command can't keep
+    // up with the events.
+    fn queue(&self, event: &Event) {
+        match self.sender.try_send(event.clone()) {
+            Ok(()) => {}
+            Err(mpsc::TrySendError::Full(_)) => log::warn!(
+                "Too many events pending for notification command '{}'. Dropping {:?}.",
+                self.hook.command,
+                event
+            ),
+            Err(mpsc::TrySendError::Disconnected(_)) => log::error!(
+                "Notification command '{}' is not being run anymore. Dropping {:?}.",
+                self.hook.command,
+                event
+            ),
+        }
+    }
+}
+
+/// A stream of events, as returned by [`Notifier::subscribe`].
+pub struct Subscription {
+    receiver: mpsc::Receiver<Event>,
+}
+
+impl Subscription {
+    /// Wait for the next event. Returns `None` if no event happened within `timeout`, and an
+    /// error if the subscription was terminated.
+    pub fn next_event(
+        &self,
+        timeout: std::time::Duration,
+    ) -> Result<Option<Event>, mpsc::RecvTimeoutError> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(event) => Ok(Some(event)),
+            Err(mpsc::RecvTimeoutError::Timeout) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+struct Subscriber {
+    sender: mpsc::SyncSender<Event>,
+    kinds: Option<HashSet<EventKind>>,
+}
+
+/// Dispatch the events to the configured commands and the subscribers.
+#[derive(Clone)]
+pub struct Notifier {
+    hooks: sync::Arc<[HookWorker]>,
+    subscribers: sync::Arc<sync::Mutex<Vec<Subscriber>>>,
+}
+
+impl Notifier {
+    pub fn new(hooks: Vec<NotifyHook>) -> Notifier {
+        let hooks: Vec<_> = hooks
+            .into_iter()
+            .filter_map(|hook| {
+                HookWorker::start(hook)
+                    .map_err(|e| log::error!("Error spawning notification command thread: {}", e))
+                    .ok()
+            })
+            .collect();
+        Notifier {
+            hooks: hooks.into(),
+            subscribers: sync::Arc::new(sync::Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Get notified of the events of the given kinds, or of all events if `None`.
+    pub fn subscribe(&self, kinds: Option<HashSet<EventKind>>) -> Subscription {
+        let (sender, receiver) = mpsc::sync_channel(SUBSCRIPTION_BUFFER_SIZE);
+        self.subscribers
+            .lock()
+            .expect("Notifier lock poisoned")
+            .push(Subscriber { sender, kinds });
+        Subscription { receiver }
+    }
+
+    /// Let the world know about these events.
+    pub fn notify(&self, events: &[Event]) {
+        for event in events {
+            log::debug!("New event: {:?}", event);
+            let kind = event.kind();
+
+            for worker in self
+                .hooks
+                .iter()
+                .filter(|worker| worker.hook.is_interested(kind))
+            {
+                worker.queue(event);
+            }
+
+            // Forget about the subscribers which went away or which don't keep up.
+            self.subscribers
+                .lock()
+                .expect("Notifier lock poisoned")
+                .retain(|sub| {
+                    if sub
+                        .kinds
+                        .as_ref()
+                        .is_some_and(|kinds| !kinds.contains(&kind))
+                    {
+                        return true;
+                    }
+                    match sub.sender.try_send(event.clone()) {
+                        Ok(()) => true,
+                        Err(mpsc::TrySendError::Full(_)) => {
+                            log::warn!("Dropping subscriber which does not process events.");
+                            false
+                        }
+                        Err(mpsc::TrySendError::Disconnected(_)) => false,
+                    }
+                });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{str::FromStr, time::Duration};
+
+    fn tip_changed(height: i32) -> Event {
+        Event::TipChanged {
+            height,
+            hash: bitcoin::BlockHash::from_str(
+                "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+            )
+            .unwrap(),
+        }
+    }
+
+    fn coin_spent() -> Event {
+        let txid = bitcoin::Txid::from_str(
+            "f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd",
+        )
+        .unwrap();
+        Event::CoinSpent {
+            outpoint: bitcoin::OutPoint::new(txid, 0),
+            spend_txid: txid,
+        }
+    }
+
+    #[test]
+    fn event_serialization() {
+        assert_eq!(
+            serde_json::to_value(tip_changed(1)).unwrap(),
+            serde_json::json!({
+                "type": "tip_changed",
+                "height": 1,
+                "hash": "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(coin_spent()).unwrap(),
+            serde_json::json!({
+                "type": "coin_spent",
+                "outpoint": "f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd:0",
+                "spend_txid": "f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd",
+            })
+        );
+        assert_eq!(
+            coin_spent().subject(),
+            "f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd:0"
+        );
+
+        for kind in [
+            EventKind::CoinReceived,
+            EventKind::CoinConfirmed,
+            EventKind::CoinSpent,
+            EventKind::SpendConfirmed,
+            EventKind::TipChanged,
+            EventKind::Reorg,
+            EventKind::RescanFinished,
+        ] {
+            assert_eq!(EventKind::from_arg(kind.to_arg()), Some(kind));
+            assert_eq!(
+                serde_json::to_value(kind).unwrap(),
+                serde_json::json!(kind.to_arg())
+            );
+        }
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn hooks() {
+        let path = std::env::temp_dir().join(format!("liana-notify-hook-{}", process::id()));
+        let notifier = Notifier::new(vec![NotifyHook {
+            command: format!("echo \"$LIANA_EVENT\" >> {}", path.display()),
+            events: Some(vec![EventKind::TipChanged]),
+        }]);
+
+        // The command is run for the events it is interested in, one after the other.
+        notifier.notify(&[tip_changed(1), coin_spent(), tip_changed(2)]);
+        let expected: String = [tip_changed(1), tip_changed(2)]
+            .iter()
+            .map(|event| serde_json::to_string(event).unwrap() + "\n")
+            .collect();
+        let mut i = 0;
+        while std::fs::read_to_string(&path).unwrap_or_default() != expected {
+            assert!(i < 100, "The command was not run for the expected events.");
+            thread::sleep(Duration::from_millis(100));
+            i += 1;
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn subscriptions() {
+        let notifier = Notifier::new(Vec::new());
+        let timeout = Duration::from_millis(10);
+
+        // Subscribers get the events they are interested in, in order.
+        let all = notifier.subscribe(None);
+        let tips = notifier.subscribe(Some(HashSet::from([EventKind::TipChanged])));
+        notifier.notify(&[tip_changed(1), coin_spent(), tip_changed(2)]);
+        assert_eq!(all.next_event(timeout), Ok(Some(tip_changed(1))));
+        assert_eq!(all.next_event(timeout), Ok(Some(coin_spent())));
+        assert_eq!(all.next_event(timeout), Ok(Some(tip_changed(2))));
+        assert_eq!(all.next_event(timeout), Ok(None));
+        assert_eq!(tips.next_event(timeout), Ok(Some(tip_changed(1))));
+        assert_eq!(tips.next_event(timeout), Ok(Some(tip_changed(2))));
+        assert_eq!(tips.next_event(timeout), Ok(None));
+
+        // The subscribers which went away are forgotten.
+        drop(all);
+        notifier.notify(&[tip_changed(3)]);
+        assert_eq!(notifier.subscribers.lock().unwrap().len(), 1);
+
+        // So are those which don't keep up.
+        let events: Vec<_> = (0..=SUBSCRIPTION_BUFFER_SIZE as i32)
+            .map(tip_changed)
+            .collect();
+        notifier.notify(&events);
+        assert!(notifier.subscribers.lock().unwrap().is_empty());
+        for _ in 0..SUBSCRIPTION_BUFFER_SIZE {
+            assert!(matches!(tips.next_event(timeout), Ok(Some(_))));
+        }
+        assert_eq!(
+            tips.next_event(timeout),
+            Err(mpsc::RecvTimeoutError::Disconnected)
+        );
+    }
+}
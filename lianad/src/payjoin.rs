@@ -0,0 +1,855 @@
+//! # Payjoin
+//!
+//! Both sides of the payjoin protocol (BIP78), by which the receiver of a payment contributes
+//! inputs to the transaction paying it. As a sender we send a signed Original PSBT to the
+//! endpoint of the receiver and check the payjoin proposal it answers with before it gets signed
+//! again. As a receiver we add one of our coins to the Original PSBT sent to us.
+
+use std::{collections::HashSet, error, fmt, str::FromStr};
+
+use miniscript::bitcoin::{
+    self,
+    psbt::{Input as PsbtIn, Output as PsbtOut, Psbt},
+    Amount, OutPoint,
+};
+
+/// The version of the protocol we implement.
+const PAYJOIN_VERSION: u32 = 1;
+
+/// How long to wait for the receiver to answer, in seconds.
+const SENDER_TIMEOUT_SECS: u64 = 60;
+
+/// The minimum value the sender's output may be left with after paying for our input.
+const DUST_OUTPUT_SATS: u64 = liana::spend::DUST_OUTPUT_SATS;
+
+/// An error when sending a payment using payjoin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SenderError {
+    PaymentRequest(String),
+    NoEndpoint,
+    InsecureEndpoint(String),
+    NoPaymentOutput,
+    Http(String),
+    /// The receiver returned an error, with its error code and message.
+    Receiver(String, String),
+    InvalidResponse(String),
+    InvalidProposal(String),
+}
+
+impl fmt::Display for SenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::PaymentRequest(e) => write!(f, "Invalid payment request: {}", e),
+            Self::NoEndpoint => write!(f, "The payment request has no payjoin endpoint"),
+            Self::InsecureEndpoint(endpoint) => write!(
+                f,
+                "Payjoin endpoint '{}' must use HTTPS, or plain HTTP to the local machine",
+                endpoint
+            ),
+            Self::NoPaymentOutput => write!(
+                f,
+                "The transaction does not contain the payment to the payjoin receiver"
+            ),
+            Self::Http(e) => write!(f, "Error when contacting the payjoin receiver: {}", e),
+            Self::Receiver(code, message) => {
+                write!(f, "The payjoin receiver returned an error '{}'", code)?;
+                if !message.is_empty() {
+                    write!(f, ": {}", message)?;
+                }
+                Ok(())
+            }
+            Self::InvalidResponse(e) => write!(f, "Invalid response from the receiver: {}", e),
+            Self::InvalidProposal(e) => write!(f, "Invalid payjoin proposal: {}", e),
+        }
+    }
+}
+
+impl error::Error for SenderError {}
+
+/// An error to return to a payjoin sender.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReceiverError {
+    /// We can't contribute to the transaction. We don't tell why, to not leak information about
+    /// our wallet.
+    Unavailable,
+    /// We added our input but we couldn't bump the fee of the proposal.
+    NotEnoughMoney,
+    VersionUnsupported,
+    OriginalRejected(String),
+}
+
+impl ReceiverError {
+    /// The error code of this error, as specified by BIP78.
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            Self::Unavailable => "unavailable",
+            Self::NotEnoughMoney => "not-enough-money",
+            Self::VersionUnsupported => "version-unsupported",
+            Self::OriginalRejected(..) => "original-psbt-rejected",
+        }
+    }
+
+    /// The JSON body of the response to the sender for this error.
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut json = serde_json::json!({
+            "errorCode": self.error_code(),
+            "message": self.to_string(),
+        });
+        if let Self::VersionUnsupported = self {
+            json["supported"] = serde_json::json!([PAYJOIN_VERSION]);
+        }
+        json
+    }
+}
+
+impl fmt::Display for ReceiverError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unavailable => write!(f, "The payjoin endpoint is not available for now."),
+            Self::NotEnoughMoney => write!(
+                f,
+                "The receiver added some inputs but could not bump the fee of the payjoin proposal."
+            ),
+            Self::VersionUnsupported => {
+                write!(f, "This version of payjoin is not supported.")
+            }
+            Self::OriginalRejected(e) => write!(f, "The Original PSBT was rejected: {}", e),
+        }
+    }
+}
+
+impl error::Error for ReceiverError {}
+
+/// The parameters sent along with the Original PSBT, which restrict how the receiver may
+/// modify it.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PayjoinParams {
+    /// The index of the output the receiver may decrease to have the sender pay for the fee of
+    /// its inputs. Usually the change output of the sender.
+    pub additional_fee_output_index: Option<usize>,
+    /// By how much the receiver may decrease this output.
+    pub max_additional_fee_contribution: Amount,
+    /// Whether the receiver is forbidden to replace the output paying it.
+    pub disable_output_substitution: bool,
+    /// The minimum feerate of the payjoin proposal, in sats/vb.
+    pub min_feerate_vb: Option<u64>,
+}
+
+impl PayjoinParams {
+    /// The query string of a request to the endpoint of a receiver with these parameters.
+    pub fn to_query(&self) -> String {
+        let mut query = format!("v={}", PAYJOIN_VERSION);
+        if let Some(index) = self.additional_fee_output_index {
+            query.push_str(&format!(
+                "&additionalfeeoutputindex={}&maxadditionalfeecontribution={}",
+                index,
+                self.max_additional_fee_contribution.to_sat()
+            ));
+        }
+        if self.disable_output_substitution {
+            query.push_str("&disableoutputsubstitution=true");
+        }
+        if let Some(feerate) = self.min_feerate_vb {
+            query.push_str(&format!("&minfeerate={}", feerate));
+        }
+        query
+    }
+
+    /// Parse the parameters from the query string of a request to our endpoint. Unknown
+    /// parameters are ignored.
+    pub fn from_query(query: &str) -> Result<Self, ReceiverError> {
+        let mut params = PayjoinParams::default();
+        let (mut fee_output_index, mut max_contribution) = (None, None);
+        for param in query.split('&').filter(|param| !param.is_empty()) {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            let invalid =
+                || ReceiverError::OriginalRejected(format!("Invalid parameter '{}'", key));
+            match key {
+                "v" => {
+                    if value.parse::<u32>().map_err(|_| invalid())? != PAYJOIN_VERSION {
+                        return Err(ReceiverError::VersionUnsupported);
+                    }
+                }
+                "additionalfeeoutputindex" => {
+                    fee_output_index = Some(value.parse::<usize>().map_err(|_| invalid())?)
+                }
+                "maxadditionalfeecontribution" => {
+                    max_contribution = Some(value.parse::<u64>().map_err(|_| invalid())?)
+                }
+                "disableoutputsubstitution" => {
+                    params.disable_output_substitution =
+                        value.parse::<bool>().map_err(|_| invalid())?
+                }
+                "minfeerate" => {
+                    let feerate = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|feerate| feerate.is_finite() && *feerate >= 0.0)
+                        .ok_or_else(invalid)?;
+                    params.min_feerate_vb = Some(feerate.ceil() as u64);
+                }
+                _ => {}
+            }
+        }
+
+        // The sender may only contribute to the fee if it tells us both from which output and up
+        // to how much.
+        if let (Some(index), Some(contribution)) = (fee_output_index, max_contribution) {
+            params.additional_fee_output_index = Some(index);
+            params.max_additional_fee_contribution = Amount::from_sat(contribution);
+        }
+        Ok(params)
+    }
+}
+
+fn is_finalized(psbt_in: &PsbtIn) -> bool {
+    psbt_in.final_script_sig.is_some() || psbt_in.final_script_witness.is_some()
+}
+
+// Plain HTTP is only allowed to the local machine, for instance to a local proxy or for testing.
+fn check_endpoint(endpoint: &str) -> Result<(), SenderError> {
+    let lowercase = endpoint.to_ascii_lowercase();
+    if lowercase.starts_with("https://") {
+        return Ok(());
+    }
+    if let Some(rest) = lowercase.strip_prefix("http://") {
+        let authority = rest.split(['/', '?']).next().unwrap_or_default();
+        let host = if authority.starts_with('[') {
+            authority
+                .split_once(']')
+                .map(|(host, _)| &host[1..])
+                .unwrap_or(authority)
+        } else {
+            authority
+                .split_once(':')
+                .map(|(host, _)| host)
+                .unwrap_or(authority)
+        };
+        if matches!(host, "localhost" | "127.0.0.1" | "::1") {
+            return Ok(());
+        }
+    }
+    Err(SenderError::InsecureEndpoint(endpoint.to_string()))
+}
+
+/// Send the (finalized) Original PSBT to the endpoint of the receiver and get its payjoin
+/// proposal.
+pub fn send_original(
+    endpoint: &str,
+    original: &Psbt,
+    params: &PayjoinParams,
+) -> Result<Psbt, SenderError> {
+    check_endpoint(endpoint)?;
+    let separator = if endpoint.contains('?') { '&' } else { '?' };
+    let url = format!("{}{}{}", endpoint, separator, params.to_query());
+
+    log::debug!("Sending Original PSBT to payjoin endpoint '{}'.", endpoint);
+    let response = minreq::post(url)
+        .with_header("Content-Type", "text/plain")
+        .with_body(original.to_string())
+        .with_timeout(SENDER_TIMEOUT_SECS)
+        .send()
+        .map_err(|e| SenderError::Http(e.to_string()))?;
+    let body = response
+        .as_str()
+        .map_err(|e| SenderError::InvalidResponse(e.to_string()))?;
+
+    if response.status_code != 200 {
+        let error = serde_json::from_str::<serde_json::Value>(body)
+            .ok()
+            .and_then(|json| {
+                let code = json.get("errorCode")?.as_str()?.to_string();
+                let message = json
+                    .get("message")
+                    .and_then(|message| message.as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                Some(SenderError::Receiver(code, message))
+            });
+        return Err(error.unwrap_or_else(|| {
+            SenderError::Http(format!(
+                "Unexpected response status {} {}",
+                response.status_code, response.reason_phrase
+            ))
+        }));
+    }
+
+    Psbt::from_str(body.trim()).map_err(|e| SenderError::InvalidResponse(e.to_string()))
+}
+
+/// An Original PSBT we sent to a payjoin receiver.
+#[derive(Debug, Clone)]
+pub struct Original {
+    /// The PSBT, before finalization.
+    pub psbt: Psbt,
+    /// The index of the output paying the receiver.
+    pub payment_index: usize,
+    /// The feerate of the finalized transaction, in sats/vb.
+    pub feerate_vb: u64,
+    /// The size of one of our inputs once satisfied, in vbytes.
+    pub input_vbytes: u64,
+    /// The parameters sent along with it.
+    pub params: PayjoinParams,
+}
+
+impl Original {
+    /// Check the payjoin proposal of the receiver as specified by BIP78. `is_ours` tells whether
+    /// a coin belongs to our wallet. On success, returns the proposal with the information needed
+    /// to sign our inputs restored from the Original PSBT.
+    pub fn check_proposal(
+        &self,
+        mut proposal: Psbt,
+        mut is_ours: impl FnMut(&OutPoint) -> bool,
+    ) -> Result<Psbt, SenderError> {
+        let invalid = |msg: &str| Err(SenderError::InvalidProposal(msg.to_string()));
+        let (original_tx, tx) = (&self.psbt.unsigned_tx, &proposal.unsigned_tx);
+        if tx.version != original_tx.version || tx.lock_time != original_tx.lock_time {
+            return invalid("the transaction version or locktime changed.");
+        }
+        if proposal.inputs.len() != tx.input.len() || proposal.outputs.len() != tx.output.len() {
+            return invalid("the PSBT is inconsistent with its transaction.");
+        }
+        let sequence = original_tx.input.first().map(|txin| txin.sequence);
+
+        // Our inputs must be left untouched and stripped from all their data, the receiver's ones
+        // must be signed.
+        let mut our_inputs = HashSet::with_capacity(original_tx.input.len());
+        let mut receiver_inputs = 0;
+        for (txin, psbt_in) in tx.input.iter().zip(proposal.inputs.iter_mut()) {
+            if !psbt_in.bip32_derivation.is_empty()
+                || !psbt_in.tap_key_origins.is_empty()
+                || !psbt_in.partial_sigs.is_empty()
+                || !psbt_in.tap_script_sigs.is_empty()
+                || psbt_in.tap_key_sig.is_some()
+            {
+                return invalid("an input contains key paths or partial signatures.");
+            }
+            match original_tx
+                .input
+                .iter()
+                .position(|orig_txin| orig_txin.previous_output == txin.previous_output)
+            {
+                Some(index) => {
+                    if !our_inputs.insert(index) {
+                        return invalid("one of our inputs is duplicated.");
+                    }
+                    if txin.sequence != original_tx.input[index].sequence {
+                        return invalid("the sequence of one of our inputs changed.");
+                    }
+                    if is_finalized(psbt_in)
+                        || psbt_in.witness_utxo.is_some()
+                        || psbt_in.non_witness_utxo.is_some()
+                    {
+                        return invalid("one of our inputs was not cleared.");
+                    }
+                    let mut restored = self.psbt.inputs[index].clone();
+                    restored.partial_sigs.clear();
+                    restored.tap_script_sigs.clear();
+                    restored.tap_key_sig = None;
+                    restored.final_script_sig = None;
+                    restored.final_script_witness = None;
+                    *psbt_in = restored;
+                }
+                None => {
+                    if is_ours(&txin.previous_output) {
+                        return invalid("the receiver added one of our coins.");
+                    }
+                    if !is_finalized(psbt_in) {
+                        return invalid("an input of the receiver is not finalized.");
+                    }
+                    if psbt_in.witness_utxo.is_none() && psbt_in.non_witness_utxo.is_none() {
+                        return invalid("an input of the receiver misses its previous output.");
+                    }
+                    if Some(txin.sequence) != sequence {
+                        return invalid("an input of the receiver has a different sequence.");
+                    }
+                    receiver_inputs += 1;
+                }
+            }
+        }
+        if our_inputs.len() != original_tx.input.len() {
+            return invalid("some of our inputs are missing.");
+        }
+        if receiver_inputs == 0 {
+            return invalid("the receiver did not contribute any input.");
+        }
+
+        // All the outputs of the Original PSBT must still be there. Only the one allowed by the
+        // parameters may decrease, to contribute to the fee.
+        let mut original_outputs = vec![false; original_tx.output.len()];
+        let mut contribution = Amount::ZERO;
+        for (txout, psbt_out) in tx.output.iter().zip(proposal.outputs.iter_mut()) {
+            if !psbt_out.bip32_derivation.is_empty() || !psbt_out.tap_key_origins.is_empty() {
+                return invalid("an output contains key paths.");
+            }
+            let index = match original_tx.output.iter().enumerate().position(|(i, orig)| {
+                !original_outputs[i] && orig.script_pubkey == txout.script_pubkey
+            }) {
+                Some(index) => index,
+                // The receiver may add outputs, paid for by its own inputs.
+                None => continue,
+            };
+            original_outputs[index] = true;
+            let original_value = original_tx.output[index].value;
+            if txout.value < original_value {
+                if Some(index) != self.params.additional_fee_output_index {
+                    return invalid("the value of an output decreased.");
+                }
+                contribution = original_value - txout.value;
+            }
+            *psbt_out = self.psbt.outputs[index].clone();
+        }
+        if original_outputs.iter().any(|found| !found) {
+            return invalid("some outputs of the Original PSBT are missing.");
+        }
+
+        // We may only pay for the additional fee incurred by the inputs of the receiver.
+        let (original_fee, fee) = match (self.psbt.fee(), proposal.fee()) {
+            (Ok(original_fee), Ok(fee)) => (original_fee, fee),
+            _ => return invalid("could not compute the fee of the transaction."),
+        };
+        if fee < original_fee {
+            return invalid("the fee decreased.");
+        }
+        if contribution > self.params.max_additional_fee_contribution {
+            return invalid("our contribution to the fee is too large.");
+        }
+        if contribution > fee - original_fee
+            || contribution.to_sat()
+                > self
+                    .feerate_vb
+                    .saturating_mul(self.input_vbytes)
+                    .saturating_mul(receiver_inputs)
+        {
+            return invalid(
+                "our contribution to the fee pays for more than the receiver's inputs.",
+            );
+        }
+
+        Ok(proposal)
+    }
+}
+
+/// Check an Original PSBT sent to us by a payjoin sender. Returns the fee it pays.
+pub fn check_original(original: &Psbt, params: &PayjoinParams) -> Result<Amount, ReceiverError> {
+    let rejected = |msg: &str| Err(ReceiverError::OriginalRejected(msg.to_string()));
+    let tx = &original.unsigned_tx;
+    if original.inputs.len() != tx.input.len() || original.outputs.len() != tx.output.len() {
+        return rejected("the PSBT is inconsistent with its transaction.");
+    }
+    if tx.input.is_empty() {
+        return rejected("the transaction has no input.");
+    }
+    if original.inputs.iter().any(|psbt_in| !is_finalized(psbt_in)) {
+        return rejected("all the inputs must be finalized.");
+    }
+    if let Some(index) = params.additional_fee_output_index {
+        if index >= tx.output.len() {
+            return rejected("the additional fee output index is out of bounds.");
+        }
+    }
+    original
+        .fee()
+        .map_err(|e| ReceiverError::OriginalRejected(e.to_string()))
+}
+
+/// Create a payjoin proposal from this (checked) Original PSBT by inserting our input, spending
+/// `outpoint` with `psbt_in` information, at `position`. `input_vbytes` is the size of our input
+/// once satisfied. The value of our input is added to the output at `output_index` which pays
+/// us, minus the part of the fee for our input the sender does not pay for. Once signed, the
+/// proposal must be stripped using [`strip_proposal`] before being sent back.
+pub fn contribute(
+    original: &Psbt,
+    params: &PayjoinParams,
+    output_index: usize,
+    outpoint: OutPoint,
+    psbt_in: PsbtIn,
+    input_vbytes: u64,
+    position: usize,
+) -> Result<Psbt, ReceiverError> {
+    let original_fee = check_original(original, params)?.to_sat();
+    let amount = psbt_in
+        .witness_utxo
+        .as_ref()
+        .map(|txo| txo.value.to_sat())
+        .expect("We always set the previous output of our input.");
+    let vsize = original.clone().extract_tx_unchecked_fee_rate().vsize() as u64;
+
+    // We need to at least keep the feerate of the Original PSBT and to reach the minimum feerate
+    // set by the sender, if any.
+    let original_feerate_vb = original_fee / vsize;
+    let fee_needed = std::cmp::max(
+        original_feerate_vb.saturating_mul(input_vbytes),
+        params
+            .min_feerate_vb
+            .unwrap_or(0)
+            .saturating_mul(vsize + input_vbytes)
+            .saturating_sub(original_fee),
+    );
+
+    // The sender may pay for part of it, the rest is deduced from what we receive.
+    let mut proposal = original.clone();
+    let mut sender_contribution = 0;
+    if let Some(index) = params
+        .additional_fee_output_index
+        .filter(|index| *index != output_index)
+    {
+        let fee_output = &mut proposal.unsigned_tx.output[index];
+        sender_contribution = fee_needed
+            .min(params.max_additional_fee_contribution.to_sat())
+            .min(fee_output.value.to_sat().saturating_sub(DUST_OUTPUT_SATS));
+        fee_output.value -= Amount::from_sat(sender_contribution);
+    }
+    let our_fee = fee_needed - sender_contribution;
+    if amount <= our_fee {
+        return Err(ReceiverError::NotEnoughMoney);
+    }
+    proposal.unsigned_tx.output[output_index].value += Amount::from_sat(amount - our_fee);
+
+    // The sender's inputs are kept for now, as signing ours may need their previous outputs.
+    let sequence = proposal.unsigned_tx.input[0].sequence;
+    for psbt_out in proposal.outputs.iter_mut() {
+        *psbt_out = PsbtOut::default();
+    }
+    proposal.xpub.clear();
+    let position = position.min(proposal.inputs.len());
+    proposal.unsigned_tx.input.insert(
+        position,
+        bitcoin::TxIn {
+            previous_output: outpoint,
+            sequence,
+            ..bitcoin::TxIn::default()
+        },
+    );
+    proposal.inputs.insert(position, psbt_in);
+
+    Ok(proposal)
+}
+
+/// Strip the proposal from all the data the sender must not receive back: the data of its own
+/// inputs and of all the outputs. Only our input, at `our_index`, is left untouched.
+pub fn strip_proposal(proposal: &mut Psbt, our_index: usize) {
+    for (index, psbt_in) in proposal.inputs.iter_mut().enumerate() {
+        if index != our_index {
+            *psbt_in = PsbtIn::default();
+        }
+    }
+    for psbt_out in proposal.outputs.iter_mut() {
+        *psbt_out = PsbtOut::default();
+    }
+    proposal.xpub.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use miniscript::bitcoin::{
+        absolute, hashes::Hash, transaction, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid,
+        WScriptHash, Witness,
+    };
+
+    fn spk(n: u8) -> ScriptBuf {
+        ScriptBuf::new_p2wsh(&WScriptHash::from_byte_array([n; 32]))
+    }
+
+    fn outpoint(n: u8) -> OutPoint {
+        OutPoint::new(Txid::from_byte_array([n; 32]), 0)
+    }
+
+    fn witness() -> Witness {
+        Witness::from_slice(&[vec![0; 72], vec![1; 72], vec![2; 100]])
+    }
+
+    fn prev_out(n: u8, value: u64) -> PsbtIn {
+        PsbtIn {
+            witness_utxo: Some(TxOut {
+                value: Amount::from_sat(value),
+                script_pubkey: spk(n),
+            }),
+            ..PsbtIn::default()
+        }
+    }
+
+    // An Original PSBT spending a 100_000 sats coin to pay 50_000 sats to the receiver and 49_000
+    // sats to our change, before finalization.
+    fn original() -> Original {
+        let tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: outpoint(1),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                ..TxIn::default()
+            }],
+            output: vec![
+                TxOut {
+                    value: Amount::from_sat(50_000),
+                    script_pubkey: spk(2),
+                },
+                TxOut {
+                    value: Amount::from_sat(49_000),
+                    script_pubkey: spk(3),
+                },
+            ],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+        psbt.inputs[0] = prev_out(1, 100_000);
+        Original {
+            psbt,
+            payment_index: 0,
+            feerate_vb: 5,
+            input_vbytes: 150,
+            params: PayjoinParams {
+                additional_fee_output_index: Some(1),
+                max_additional_fee_contribution: Amount::from_sat(500),
+                disable_output_substitution: true,
+                min_feerate_vb: Some(5),
+            },
+        }
+    }
+
+    fn finalized(original: &Original) -> Psbt {
+        let mut psbt = original.psbt.clone();
+        psbt.inputs[0].final_script_witness = Some(witness());
+        psbt
+    }
+
+    // The proposal of the receiver, once it signed its input.
+    fn proposal(original: &Original) -> Psbt {
+        let mut proposal = contribute(
+            &finalized(original),
+            &original.params,
+            0,
+            outpoint(4),
+            prev_out(4, 30_000),
+            150,
+            1,
+        )
+        .unwrap();
+        proposal.inputs[1].final_script_witness = Some(witness());
+        strip_proposal(&mut proposal, 1);
+        proposal
+    }
+
+    #[test]
+    fn params_query() {
+        let params = original().params;
+        let query = params.to_query();
+        assert_eq!(
+            query,
+            "v=1&additionalfeeoutputindex=1&maxadditionalfeecontribution=500&disableoutputsubstitution=true&minfeerate=5"
+        );
+        assert_eq!(PayjoinParams::from_query(&query).unwrap(), params);
+
+        // Unknown parameters are ignored, the fee contribution needs both parameters.
+        let params =
+            PayjoinParams::from_query("v=1&additionalfeeoutputindex=1&minfeerate=1.5&foo=bar")
+                .unwrap();
+        assert_eq!(
+            params,
+            PayjoinParams {
+                min_feerate_vb: Some(2),
+                ..PayjoinParams::default()
+            }
+        );
+
+        assert_eq!(
+            PayjoinParams::from_query("v=2"),
+            Err(ReceiverError::VersionUnsupported)
+        );
+        assert!(matches!(
+            PayjoinParams::from_query("v=1&minfeerate=-1"),
+            Err(ReceiverError::OriginalRejected(..))
+        ));
+        assert!(matches!(
+            PayjoinParams::from_query("maxadditionalfeecontribution=a"),
+            Err(ReceiverError::OriginalRejected(..))
+        ));
+    }
+
+    #[test]
+    fn endpoint() {
+        for endpoint in [
+            "https://example.com/payjoin",
+            "HTTPS://example.com",
+            "http://localhost:3000/pj?id=1",
+            "http://127.0.0.1/pj",
+            "http://[::1]:8080",
+        ] {
+            check_endpoint(endpoint).unwrap();
+        }
+        for endpoint in [
+            "http://example.com/payjoin",
+            "http://localhost.example.com",
+            "http://exampleonionaddress.onion",
+            "ftp://127.0.0.1",
+        ] {
+            assert!(matches!(
+                check_endpoint(endpoint),
+                Err(SenderError::InsecureEndpoint(..))
+            ));
+        }
+    }
+
+    #[test]
+    fn receiver_contribution() {
+        let original = original();
+
+        // The Original PSBT must be finalized.
+        assert!(matches!(
+            check_original(&original.psbt, &original.params),
+            Err(ReceiverError::OriginalRejected(..))
+        ));
+        let finalized = finalized(&original);
+        assert_eq!(
+            check_original(&finalized, &original.params),
+            Ok(Amount::from_sat(1_000))
+        );
+        let mut params = original.params.clone();
+        params.additional_fee_output_index = Some(2);
+        assert!(matches!(
+            check_original(&finalized, &params),
+            Err(ReceiverError::OriginalRejected(..))
+        ));
+
+        // The sender pays for our input up to its maximum contribution, we pay for the rest.
+        let proposal = proposal(&original);
+        let tx = &proposal.unsigned_tx;
+        assert_eq!(
+            tx.input
+                .iter()
+                .map(|i| i.previous_output)
+                .collect::<Vec<_>>(),
+            vec![outpoint(1), outpoint(4)]
+        );
+        assert_eq!(tx.input[1].sequence, Sequence::ENABLE_RBF_NO_LOCKTIME);
+        assert_eq!(tx.output[1].value, Amount::from_sat(49_000 - 500));
+        let vsize = finalized.clone().extract_tx_unchecked_fee_rate().vsize() as u64;
+        let fee_needed = 1_000 / vsize * 150;
+        assert!(fee_needed > 500);
+        assert_eq!(
+            tx.output[0].value,
+            Amount::from_sat(50_000 + 30_000 - (fee_needed - 500))
+        );
+        assert_eq!(proposal.inputs[0], PsbtIn::default());
+        assert!(proposal.outputs.iter().all(|o| *o == PsbtOut::default()));
+
+        // Before being stripped, the proposal keeps the data of the sender's inputs.
+        let proposal = contribute(
+            &finalized,
+            &original.params,
+            0,
+            outpoint(4),
+            prev_out(4, 30_000),
+            150,
+            1,
+        )
+        .unwrap();
+        assert_eq!(proposal.inputs[0], finalized.inputs[0]);
+        assert_eq!(proposal.inputs[1], prev_out(4, 30_000));
+
+        // If the sender doesn't contribute, we pay for all of it.
+        let mut params = original.params.clone();
+        params.additional_fee_output_index = None;
+        let proposal = contribute(
+            &finalized,
+            &params,
+            0,
+            outpoint(4),
+            prev_out(4, 30_000),
+            150,
+            0,
+        )
+        .unwrap();
+        assert_eq!(proposal.unsigned_tx.input[0].previous_output, outpoint(4));
+        assert_eq!(
+            proposal.unsigned_tx.output[1].value,
+            Amount::from_sat(49_000)
+        );
+        assert_eq!(
+            proposal.unsigned_tx.output[0].value,
+            Amount::from_sat(50_000 + 30_000 - fee_needed)
+        );
+
+        // Contributing an input not even worth its fee would only decrease the payment.
+        assert_eq!(
+            contribute(
+                &finalized,
+                &params,
+                0,
+                outpoint(4),
+                prev_out(4, 100),
+                150,
+                0
+            ),
+            Err(ReceiverError::NotEnoughMoney)
+        );
+    }
+
+    #[test]
+    fn sender_checks() {
+        let original = original();
+        let is_ours = |op: &OutPoint| *op == outpoint(1);
+
+        // A valid proposal. The data of our input and outputs is restored.
+        let proposal = proposal(&original);
+        let checked = original.check_proposal(proposal.clone(), is_ours).unwrap();
+        assert_eq!(checked.inputs[0], original.psbt.inputs[0]);
+        assert_eq!(checked.inputs[1], proposal.inputs[1]);
+        assert_eq!(checked.unsigned_tx, proposal.unsigned_tx);
+
+        let check = |proposal: Psbt| original.check_proposal(proposal, is_ours);
+        let assert_invalid = |proposal: Psbt| {
+            assert!(matches!(
+                check(proposal),
+                Err(SenderError::InvalidProposal(..))
+            ))
+        };
+
+        // The receiver must not touch our inputs.
+        let mut invalid = proposal.clone();
+        invalid.inputs[0].final_script_witness = Some(witness());
+        assert_invalid(invalid);
+        let mut invalid = proposal.clone();
+        invalid.inputs[0].witness_utxo = original.psbt.inputs[0].witness_utxo.clone();
+        assert_invalid(invalid);
+        let mut invalid = proposal.clone();
+        invalid.unsigned_tx.input[0].sequence = Sequence::MAX;
+        assert_invalid(invalid);
+        let mut invalid = proposal.clone();
+        invalid.unsigned_tx.input.remove(0);
+        invalid.inputs.remove(0);
+        assert_invalid(invalid);
+
+        // Its own inputs must be signed, and not ours.
+        let mut invalid = proposal.clone();
+        invalid.inputs[1].final_script_witness = None;
+        assert_invalid(invalid);
+        assert!(matches!(
+            original.check_proposal(proposal.clone(), |_| true),
+            Err(SenderError::InvalidProposal(..))
+        ));
+        let mut invalid = proposal.clone();
+        invalid.unsigned_tx.input.remove(1);
+        invalid.inputs.remove(1);
+        assert_invalid(invalid);
+
+        // It must not decrease the payment, nor take more than allowed from our change.
+        let mut invalid = proposal.clone();
+        invalid.unsigned_tx.output[0].value = Amount::from_sat(49_999);
+        assert_invalid(invalid);
+        let mut invalid = proposal.clone();
+        invalid.unsigned_tx.output[1].value -= Amount::from_sat(1);
+        assert_invalid(invalid);
+        let mut invalid = proposal.clone();
+        invalid.unsigned_tx.output.remove(1);
+        invalid.outputs.remove(1);
+        assert_invalid(invalid);
+
+        // Nor make us pay for the fee of something else than its inputs.
+        let mut invalid = proposal.clone();
+        invalid.unsigned_tx.output[0].value += Amount::from_sat(700);
+        assert_invalid(invalid);
+    }
+}
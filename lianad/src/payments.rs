@@ -0,0 +1,340 @@
+//! # Batches of payments
+//!
+//! Read the payments to be made by a Spend transaction from BIP21 URIs or from CSV files, as an
+//! ordered list of [`Destination`]s.
+
+use crate::commands::Destination;
+
+use std::{error, fmt, str::FromStr};
+
+use miniscript::bitcoin::{self, Denomination};
+
+const BIP21_SCHEME: &str = "bitcoin:";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaymentsError {
+    InvalidUri(String),
+    UnknownRequiredParameter(String),
+    InvalidAddress(String),
+    InvalidAmount(String),
+    MissingAmount,
+    InvalidRecord,
+    /// An error on this line of a CSV file.
+    Line(usize, Box<PaymentsError>),
+}
+
+impl fmt::Display for PaymentsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidUri(uri) => write!(f, "Invalid BIP21 URI: '{}'", uri),
+            Self::UnknownRequiredParameter(param) => {
+                write!(f, "Unsupported required parameter '{}' in BIP21 URI", param)
+            }
+            Self::InvalidAddress(addr) => write!(f, "Invalid address: '{}'", addr),
+            Self::InvalidAmount(amount) => write!(f, "Invalid amount: '{}'", amount),
+            Self::MissingAmount => write!(f, "Missing payment amount"),
+            Self::InvalidRecord => write!(
+                f,
+                "Invalid record: must be 'address,amount[,label]' or a BIP21 URI"
+            ),
+            Self::Line(line, e) => write!(f, "Line {}: {}", line, e),
+        }
+    }
+}
+
+impl error::Error for PaymentsError {}
+
+fn parse_address(
+    addr: &str,
+) -> Result<bitcoin::Address<bitcoin::address::NetworkUnchecked>, PaymentsError> {
+    bitcoin::Address::from_str(addr).map_err(|_| PaymentsError::InvalidAddress(addr.to_string()))
+}
+
+// Amounts are always expressed in bitcoins, as in BIP21 URIs.
+fn parse_amount(amount: &str) -> Result<u64, PaymentsError> {
+    bitcoin::Amount::from_str_in(amount, Denomination::Bitcoin)
+        .map(|amount| amount.to_sat())
+        .map_err(|_| PaymentsError::InvalidAmount(amount.to_string()))
+}
+
+// Decode the percent-encoded characters of a URI component. Returns `None` if the encoding is
+// invalid or if the decoded bytes aren't valid UTF-8.
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Whether this string looks like a BIP21 URI.
+pub fn is_bip21_uri(s: &str) -> bool {
+    s.trim()
+        .get(..BIP21_SCHEME.len())
+        .map(|scheme| scheme.eq_ignore_ascii_case(BIP21_SCHEME))
+        .unwrap_or(false)
+}
+
+/// Parse a payment from a BIP21 URI, such as `bitcoin:<address>?amount=0.01&label=Rent`. The
+/// amount is mandatory. Other parameters than `amount` and `label` are ignored, unless they are
+/// required (prefixed with `req-`).
+pub fn parse_bip21(uri: &str) -> Result<Destination, PaymentsError> {
+    parse_bip21_payjoin(uri).map(|(destination, _)| destination)
+}
+
+/// Parse a payment from a BIP21 URI as with [`parse_bip21`], along with the payjoin endpoint of
+/// the receiver (its `pj` parameter) if any.
+pub fn parse_bip21_payjoin(uri: &str) -> Result<(Destination, Option<String>), PaymentsError> {
+    let uri = uri.trim();
+    if !is_bip21_uri(uri) {
+        return Err(PaymentsError::InvalidUri(uri.to_string()));
+    }
+    let (addr, query) = uri[BIP21_SCHEME.len()..]
+        .split_once('?')
+        .unwrap_or((&uri[BIP21_SCHEME.len()..], ""));
+    let address = parse_address(addr)?;
+
+    let (mut amount, mut label, mut payjoin_endpoint) = (None, None, None);
+    for param in query.split('&').filter(|param| !param.is_empty()) {
+        let (key, value) = param
+            .split_once('=')
+            .and_then(|(key, value)| Some((key, percent_decode(value)?)))
+            .ok_or_else(|| PaymentsError::InvalidUri(uri.to_string()))?;
+        match key {
+            "amount" => amount = Some(parse_amount(&value)?),
+            "label" => label = Some(value),
+            "pj" => payjoin_endpoint = Some(value),
+            key if key.starts_with("req-") => {
+                return Err(PaymentsError::UnknownRequiredParameter(key.to_string()))
+            }
+            _ => {}
+        }
+    }
+
+    let destination = Destination {
+        address,
+        amount: amount.ok_or(PaymentsError::MissingAmount)?,
+        label: label.filter(|label| !label.is_empty()),
+    };
+    Ok((destination, payjoin_endpoint))
+}
+
+// Split a CSV record into its fields. Fields may be enclosed in double quotes, in which case they
+// may contain commas, and double quotes if they are doubled. Returns `None` if a quote isn't
+// closed.
+fn csv_fields(record: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = record.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    in_quotes = false;
+                }
+            }
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                in_quotes = true;
+            }
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    if in_quotes {
+        return None;
+    }
+    fields.push(field);
+    Some(fields.into_iter().map(|f| f.trim().to_string()).collect())
+}
+
+fn parse_csv_record(record: &str) -> Result<Destination, PaymentsError> {
+    let fields = csv_fields(record).ok_or(PaymentsError::InvalidRecord)?;
+    match fields.as_slice() {
+        [uri] if is_bip21_uri(uri) => parse_bip21(uri),
+        [address, amount] | [address, amount, _] => Ok(Destination {
+            address: parse_address(address)?,
+            amount: parse_amount(amount)?,
+            label: fields.get(2).filter(|label| !label.is_empty()).cloned(),
+        }),
+        _ => Err(PaymentsError::InvalidRecord),
+    }
+}
+
+/// Parse a list of payments from a CSV file. Each line is either an `address,amount,label`
+/// record, with the amount in bitcoins and an optional label, or a BIP21 URI. The first line
+/// is ignored if it is a header (its first field is `address`). Empty lines are ignored.
+pub fn parse_csv(content: &str) -> Result<Vec<Destination>, PaymentsError> {
+    let mut destinations = Vec::new();
+    for (i, record) in content.lines().enumerate() {
+        let record = record.trim();
+        if record.is_empty() {
+            continue;
+        }
+        if destinations.is_empty()
+            && record
+                .split(',')
+                .next()
+                .map(|field| {
+                    field
+                        .trim()
+                        .trim_matches('"')
+                        .eq_ignore_ascii_case("address")
+                })
+                .unwrap_or(false)
+        {
+            continue;
+        }
+        let destination =
+            parse_csv_record(record).map_err(|e| PaymentsError::Line(i + 1, Box::new(e)))?;
+        destinations.push(destination);
+    }
+    Ok(destinations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bip21() {
+        let addr = "bc1qnsexk3gnuyayu92fc3tczvc7k62u22a22ua2kv";
+        let dest = parse_bip21(&format!("bitcoin:{}?amount=0.015&label=Luke%20Jr", addr)).unwrap();
+        assert_eq!(dest.address.assume_checked().to_string(), addr);
+        assert_eq!(dest.amount, 1_500_000);
+        assert_eq!(dest.label.as_deref(), Some("Luke Jr"));
+
+        // The scheme is case insensitive, other parameters are ignored.
+        let dest = parse_bip21(&format!(
+            "BITCOIN:{}?message=Donation&amount=1&somethingyoudontunderstand=50",
+            addr
+        ))
+        .unwrap();
+        assert_eq!(dest.amount, 100_000_000);
+        assert_eq!(dest.label, None);
+
+        // Unless they are required.
+        assert_eq!(
+            parse_bip21(&format!("bitcoin:{}?amount=1&req-somethingelse=x", addr)),
+            Err(PaymentsError::UnknownRequiredParameter(
+                "req-somethingelse".to_string()
+            ))
+        );
+
+        // We need an amount, and a valid one.
+        assert_eq!(
+            parse_bip21(&format!("bitcoin:{}?label=Luke-Jr", addr)),
+            Err(PaymentsError::MissingAmount)
+        );
+        assert_eq!(
+            parse_bip21(&format!("bitcoin:{}?amount=1,5", addr)),
+            Err(PaymentsError::InvalidAmount("1,5".to_string()))
+        );
+        assert_eq!(
+            parse_bip21(&format!("bitcoin:{}?amount=0.000000001", addr)),
+            Err(PaymentsError::InvalidAmount("0.000000001".to_string()))
+        );
+
+        // Invalid URIs.
+        assert!(matches!(
+            parse_bip21(&format!("litecoin:{}?amount=1", addr)),
+            Err(PaymentsError::InvalidUri(_))
+        ));
+        assert!(matches!(
+            parse_bip21("bitcoin:notanaddress?amount=1"),
+            Err(PaymentsError::InvalidAddress(_))
+        ));
+        assert!(matches!(
+            parse_bip21(&format!("bitcoin:{}?amount=1&label=%E", addr)),
+            Err(PaymentsError::InvalidUri(_))
+        ));
+
+        // The payjoin endpoint is percent-decoded.
+        let (dest, endpoint) = parse_bip21_payjoin(&format!(
+            "bitcoin:{}?amount=0.01&pj=https%3A%2F%2Fexample.com%2Fpj%3Fid%3D1&pjos=0",
+            addr
+        ))
+        .unwrap();
+        assert_eq!(dest.amount, 1_000_000);
+        assert_eq!(endpoint.as_deref(), Some("https://example.com/pj?id=1"));
+        let (_, endpoint) = parse_bip21_payjoin(&format!("bitcoin:{}?amount=0.01", addr)).unwrap();
+        assert_eq!(endpoint, None);
+    }
+
+    #[test]
+    fn csv() {
+        let addr_a = "bc1qnsexk3gnuyayu92fc3tczvc7k62u22a22ua2kv";
+        let addr_b = "bc1q39srgatmkp6k2ne3l52yhkjprdvunvspqydmkx";
+        let csv = format!(
+            "address,amount,label\n\
+             {a},0.001,Salary\n\
+             \n\
+             {b},0.002\n\
+             {a}, 0.003 ,\"Rent, \"\"March\"\"\"\n\
+             bitcoin:{b}?amount=0.004&label=Tip\n",
+            a = addr_a,
+            b = addr_b
+        );
+        let dests = parse_csv(&csv).unwrap();
+        let summary: Vec<_> = dests
+            .iter()
+            .map(|d| {
+                (
+                    d.address.clone().assume_checked().to_string(),
+                    d.amount,
+                    d.label.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (addr_a.to_string(), 100_000, Some("Salary".to_string())),
+                (addr_b.to_string(), 200_000, None),
+                (
+                    addr_a.to_string(),
+                    300_000,
+                    Some("Rent, \"March\"".to_string())
+                ),
+                (addr_b.to_string(), 400_000, Some("Tip".to_string())),
+            ]
+        );
+
+        // The errors are reported along with their line.
+        let csv = format!("{},0.001\n{},abc\n", addr_a, addr_b);
+        assert_eq!(
+            parse_csv(&csv),
+            Err(PaymentsError::Line(
+                2,
+                Box::new(PaymentsError::InvalidAmount("abc".to_string()))
+            ))
+        );
+        let csv = format!("{},0.001,label,extra\n", addr_a);
+        assert_eq!(
+            parse_csv(&csv),
+            Err(PaymentsError::Line(
+                1,
+                Box::new(PaymentsError::InvalidRecord)
+            ))
+        );
+        let csv = format!("{},0.001,\"label\n", addr_a);
+        assert_eq!(
+            parse_csv(&csv),
+            Err(PaymentsError::Line(
+                1,
+                Box::new(PaymentsError::InvalidRecord)
+            ))
+        );
+    }
+}
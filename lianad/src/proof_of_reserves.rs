@@ -0,0 +1,432 @@
+//! # Proof of reserves
+//!
+//! Prove the control of coins without moving them, as specified in BIP127. A proof is a
+//! transaction spending the coins along with a "commitment" input, which commits to a message
+//! (typically chosen by the auditor) by spending an output that cannot exist. The transaction can
+//! therefore never be broadcast, but its signatures for the coins can be checked like those of any
+//! other transaction.
+
+use crate::database::Coin;
+
+use liana::{descriptors::LianaDescriptor, spend::TxGetter};
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+};
+
+use miniscript::{
+    bitcoin::{
+        self,
+        hashes::{hash160, sha256d, Hash},
+        opcodes,
+        psbt::{Input as PsbtIn, Output as PsbtOut, Psbt},
+        script, secp256k1,
+        sighash::{EcdsaSighashType, TapSighashType},
+    },
+    psbt::PsbtExt,
+};
+
+/// The tag prepended to the message before hashing it into the commitment outpoint.
+const COMMITMENT_TAG: &str = "Proof-of-Reserves: ";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProofError {
+    /// The first input of the proof does not commit to the expected message.
+    Commitment,
+    /// The proof does not spend any coin besides the commitment input.
+    NoCoin,
+    /// The same coin is spent more than once.
+    DuplicateCoin(bitcoin::OutPoint),
+    /// The proof must have a single output, paying to an unspendable Script.
+    Output,
+    /// The PSBT input for this coin does not tell the output it spends.
+    MissingPrevout(bitcoin::OutPoint),
+    /// This coin is not part of the UTxO set.
+    UnknownCoin(bitcoin::OutPoint),
+    /// A signature for this coin does not commit to the whole proof transaction.
+    SighashType(bitcoin::OutPoint),
+    /// The signatures for this coin are missing or invalid.
+    Signature(bitcoin::OutPoint, String),
+}
+
+impl fmt::Display for ProofError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Commitment => write!(f, "The proof does not commit to this message."),
+            Self::NoCoin => write!(f, "The proof does not spend any coin."),
+            Self::DuplicateCoin(op) => write!(f, "Coin at '{}' is spent more than once.", op),
+            Self::Output => write!(
+                f,
+                "The proof must have a single output, paying to an unspendable Script."
+            ),
+            Self::MissingPrevout(op) => {
+                write!(f, "Missing the spent output for coin at '{}'.", op)
+            }
+            Self::UnknownCoin(op) => write!(f, "Coin at '{}' is not unspent.", op),
+            Self::SighashType(op) => write!(
+                f,
+                "A signature for coin at '{}' does not commit to the whole proof.",
+                op
+            ),
+            Self::Signature(op, e) => write!(f, "Invalid signatures for coin at '{}': {}", op, e),
+        }
+    }
+}
+
+impl std::error::Error for ProofError {}
+
+/// The outpoint spent by the commitment input of a proof for this message. Its txid is the hash
+/// of the message, so it cannot exist.
+pub fn commitment_outpoint(message: &str) -> bitcoin::OutPoint {
+    let hash = sha256d::Hash::hash(format!("{}{}", COMMITMENT_TAG, message).as_bytes());
+    bitcoin::OutPoint::new(bitcoin::Txid::from_raw_hash(hash), 0)
+}
+
+// The output we pretend the commitment input spends. It is anyone-can-spend so the commitment
+// input needs no signature.
+fn commitment_prevout() -> bitcoin::TxOut {
+    bitcoin::TxOut {
+        value: bitcoin::Amount::ZERO,
+        script_pubkey: script::Builder::new()
+            .push_opcode(opcodes::OP_TRUE)
+            .into_script(),
+    }
+}
+
+// The PSBT input for the commitment, final already since it needs no signature.
+fn commitment_psbt_in() -> PsbtIn {
+    PsbtIn {
+        witness_utxo: Some(commitment_prevout()),
+        final_script_sig: Some(bitcoin::ScriptBuf::new()),
+        final_script_witness: Some(bitcoin::Witness::new()),
+        ..PsbtIn::default()
+    }
+}
+
+/// The Script a proof pays to. It is a P2PKH for a hash nobody knows the preimage of, so it could
+/// not be spent even if the proof was a valid transaction.
+pub fn unspendable_script_pubkey() -> bitcoin::ScriptBuf {
+    let hash = hash160::Hash::hash(&[0]);
+    bitcoin::ScriptBuf::new_p2pkh(&bitcoin::PubkeyHash::from_raw_hash(hash))
+}
+
+/// Create a proof of reserves for these coins of ours, committing to the given message. The PSBT
+/// must then be signed for each of the coins, like a spend.
+pub fn create_proof(
+    secp: &secp256k1::Secp256k1<impl secp256k1::Verification>,
+    main_descriptor: &LianaDescriptor,
+    tx_getter: &mut impl TxGetter,
+    message: &str,
+    coins: &[Coin],
+) -> Psbt {
+    let mut tx = bitcoin::Transaction {
+        version: bitcoin::transaction::Version::TWO,
+        lock_time: bitcoin::absolute::LockTime::ZERO,
+        input: vec![bitcoin::TxIn {
+            previous_output: commitment_outpoint(message),
+            sequence: bitcoin::Sequence::ENABLE_RBF_NO_LOCKTIME,
+            ..bitcoin::TxIn::default()
+        }],
+        output: Vec::with_capacity(1),
+    };
+    let mut psbt_ins = Vec::with_capacity(coins.len() + 1);
+    psbt_ins.push(commitment_psbt_in());
+
+    // Populate the inputs for our coins as we would for a spend, so signers can sign them.
+    for coin in coins {
+        tx.input.push(bitcoin::TxIn {
+            previous_output: coin.outpoint,
+            sequence: bitcoin::Sequence::ENABLE_RBF_NO_LOCKTIME,
+            ..bitcoin::TxIn::default()
+        });
+        let desc = if coin.is_change {
+            main_descriptor.change_descriptor()
+        } else {
+            main_descriptor.receive_descriptor()
+        };
+        let coin_desc = desc.derive(coin.derivation_index, secp);
+        let mut psbt_in = PsbtIn::default();
+        coin_desc.update_psbt_in(&mut psbt_in);
+        main_descriptor.update_musig_psbt_in(&mut psbt_in);
+        psbt_in.witness_utxo = Some(bitcoin::TxOut {
+            value: coin.amount,
+            script_pubkey: coin_desc.script_pubkey(),
+        });
+        if !main_descriptor.is_taproot() {
+            psbt_in.non_witness_utxo = tx_getter.get_tx(&coin.outpoint.txid);
+        }
+        psbt_ins.push(psbt_in);
+    }
+
+    tx.output.push(bitcoin::TxOut {
+        value: coins.iter().map(|coin| coin.amount).sum(),
+        script_pubkey: unspendable_script_pubkey(),
+    });
+    Psbt {
+        unsigned_tx: tx,
+        version: 0,
+        xpub: BTreeMap::new(),
+        proprietary: BTreeMap::new(),
+        unknown: BTreeMap::new(),
+        inputs: psbt_ins,
+        outputs: vec![PsbtOut::default()],
+    }
+}
+
+/// Check the structure of a proof of reserves for this message and get the coins it claims the
+/// control of, along with the Script each pays to according to the PSBT.
+pub fn proof_coins(
+    psbt: &Psbt,
+    message: &str,
+) -> Result<Vec<(bitcoin::OutPoint, bitcoin::ScriptBuf)>, ProofError> {
+    let tx = &psbt.unsigned_tx;
+    if tx.input.first().map(|txin| txin.previous_output) != Some(commitment_outpoint(message)) {
+        return Err(ProofError::Commitment);
+    }
+    if tx.input.len() < 2 {
+        return Err(ProofError::NoCoin);
+    }
+    if tx.output.len() != 1 || tx.output[0].script_pubkey != unspendable_script_pubkey() {
+        return Err(ProofError::Output);
+    }
+
+    let mut seen = HashSet::with_capacity(tx.input.len());
+    tx.input
+        .iter()
+        .zip(psbt.inputs.iter())
+        .skip(1)
+        .map(|(txin, psbt_in)| {
+            let op = txin.previous_output;
+            if !seen.insert(op) {
+                return Err(ProofError::DuplicateCoin(op));
+            }
+            let spk = psbt_in
+                .witness_utxo
+                .as_ref()
+                .or_else(|| {
+                    psbt_in
+                        .non_witness_utxo
+                        .as_ref()
+                        .and_then(|prev_tx| prev_tx.output.get(op.vout as usize))
+                })
+                .map(|txo| txo.script_pubkey.clone())
+                .ok_or(ProofError::MissingPrevout(op))?;
+            Ok((op, spk))
+        })
+        .collect()
+}
+
+/// Verify a signed proof of reserves for this message against the unspent outputs at the coins it
+/// spends, as queried from the UTxO set. Returns the proven coins along with their value.
+///
+/// The coins' signatures are checked from the partial signatures in the PSBT, as they would be
+/// before finalizing a spend.
+pub fn verify_proof(
+    secp: &secp256k1::Secp256k1<impl secp256k1::Verification>,
+    psbt: &Psbt,
+    message: &str,
+    utxos: &HashMap<bitcoin::OutPoint, bitcoin::TxOut>,
+) -> Result<Vec<(bitcoin::OutPoint, bitcoin::Amount)>, ProofError> {
+    let coins = proof_coins(psbt, message)?;
+    let mut psbt = psbt.clone();
+    psbt.inputs[0] = commitment_psbt_in();
+
+    // Only trust the UTxO set regarding the outputs spent by the proof. Signing for a different
+    // value or Script than the actual one would make the signature invalid.
+    for (index, (op, _)) in coins.iter().enumerate().map(|(i, c)| (i + 1, c)) {
+        let txo = utxos.get(op).ok_or(ProofError::UnknownCoin(*op))?;
+        let psbt_in = &mut psbt.inputs[index];
+        psbt_in.witness_utxo = Some(txo.clone());
+        psbt_in.non_witness_utxo = None;
+        psbt_in.final_script_sig = None;
+        psbt_in.final_script_witness = None;
+
+        // A signature which does not commit to all the inputs and outputs could be reused from
+        // another transaction, one not committing to the message.
+        let ecdsa_all = psbt_in
+            .partial_sigs
+            .values()
+            .all(|sig| sig.sighash_type == EcdsaSighashType::All);
+        let schnorr_all = psbt_in
+            .tap_key_sig
+            .iter()
+            .chain(psbt_in.tap_script_sigs.values())
+            .all(|sig| {
+                sig.sighash_type == TapSighashType::Default
+                    || sig.sighash_type == TapSighashType::All
+            });
+        if !ecdsa_all || !schnorr_all {
+            return Err(ProofError::SighashType(*op));
+        }
+    }
+
+    // Finalizing an input checks its satisfaction, signatures included, against the output it
+    // spends.
+    for (index, (op, _)) in coins.iter().enumerate().map(|(i, c)| (i + 1, c)) {
+        psbt.finalize_inp_mut(secp, index)
+            .map_err(|e| ProofError::Signature(*op, e.to_string()))?;
+    }
+
+    Ok(coins
+        .into_iter()
+        .map(|(op, _)| (op, utxos[&op].value))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use liana::signer::HotSigner;
+    use miniscript::bitcoin::bip32;
+    use std::str::FromStr;
+
+    struct NoTxGetter;
+
+    impl TxGetter for NoTxGetter {
+        fn get_tx(&mut self, _: &bitcoin::Txid) -> Option<bitcoin::Transaction> {
+            None
+        }
+    }
+
+    fn coin(outpoint: &str, amount: u64, index: u32) -> Coin {
+        Coin {
+            outpoint: bitcoin::OutPoint::from_str(outpoint).unwrap(),
+            is_immature: false,
+            block_info: None,
+            amount: bitcoin::Amount::from_sat(amount),
+            derivation_index: index.into(),
+            is_change: false,
+            spend_txid: None,
+            spend_block: None,
+            is_from_self: false,
+            is_frozen: false,
+        }
+    }
+
+    #[test]
+    fn proof_of_reserves() {
+        let secp = secp256k1::Secp256k1::new();
+        let network = bitcoin::Network::Bitcoin;
+
+        // A wallet whose primary path is a single hot signer.
+        let (signer, recov_signer) = (
+            HotSigner::generate(network).unwrap(),
+            HotSigner::generate(network).unwrap(),
+        );
+        let origin = bip32::DerivationPath::from_str("m/48'/0'/0'/2'").unwrap();
+        let desc = LianaDescriptor::from_str(&format!(
+            "wsh(or_d(pk([{}/48'/0'/0'/2']{}/<0;1>/*),and_v(v:pkh([{}/48'/0'/0'/2']{}/<0;1>/*),older(10))))",
+            signer.fingerprint(&secp),
+            signer.xpub_at(&origin, &secp),
+            recov_signer.fingerprint(&secp),
+            recov_signer.xpub_at(&origin, &secp),
+        ))
+        .unwrap();
+        let coins = [
+            coin(
+                "4613e078e4cdbb0fce1bc6e44b028f0e11621a134a1605efdc456c32d155c922:19",
+                19_000,
+                0,
+            ),
+            coin(
+                "5613e078e4cdbb0fce1bc6e44b028f0e11621a134a1605efdc456c32d155c922:2",
+                1_000_000,
+                7,
+            ),
+        ];
+        let utxos: HashMap<_, _> = coins
+            .iter()
+            .map(|c| {
+                let spk = desc
+                    .receive_descriptor()
+                    .derive(c.derivation_index, &secp)
+                    .script_pubkey();
+                let txo = bitcoin::TxOut {
+                    value: c.amount,
+                    script_pubkey: spk,
+                };
+                (c.outpoint, txo)
+            })
+            .collect();
+
+        // The proof spends the commitment input and the coins to a single unspendable output.
+        let message = "Reserves of Liana Inc. for the 2026 audit";
+        let psbt = create_proof(&secp, &desc, &mut NoTxGetter, message, &coins);
+        let tx = &psbt.unsigned_tx;
+        assert_eq!(tx.input.len(), 3);
+        assert_eq!(tx.input[0].previous_output, commitment_outpoint(message));
+        assert_eq!(tx.output.len(), 1);
+        assert_eq!(tx.output[0].value, bitcoin::Amount::from_sat(1_019_000));
+        assert!(tx.output[0].script_pubkey.is_p2pkh());
+        assert_eq!(
+            proof_coins(&psbt, message).unwrap(),
+            coins
+                .iter()
+                .map(|c| (c.outpoint, utxos[&c.outpoint].script_pubkey.clone()))
+                .collect::<Vec<_>>()
+        );
+
+        // It must be signed to be valid.
+        assert!(matches!(
+            verify_proof(&secp, &psbt, message, &utxos),
+            Err(ProofError::Signature(..))
+        ));
+        let signed_psbt = signer.sign_psbt(psbt.clone(), &secp).unwrap();
+        let proven = verify_proof(&secp, &signed_psbt, message, &utxos).unwrap();
+        assert_eq!(
+            proven,
+            coins
+                .iter()
+                .map(|c| (c.outpoint, c.amount))
+                .collect::<Vec<_>>()
+        );
+
+        // It does not prove anything for another message.
+        assert_eq!(
+            verify_proof(&secp, &signed_psbt, "Another message", &utxos),
+            Err(ProofError::Commitment)
+        );
+
+        // All coins must still be unspent, for the value they were signed for.
+        let mut spent_utxos = utxos.clone();
+        spent_utxos.remove(&coins[1].outpoint);
+        assert_eq!(
+            verify_proof(&secp, &signed_psbt, message, &spent_utxos),
+            Err(ProofError::UnknownCoin(coins[1].outpoint))
+        );
+        let mut other_utxos = utxos.clone();
+        other_utxos.get_mut(&coins[0].outpoint).unwrap().value = bitcoin::Amount::from_sat(20_000);
+        assert!(matches!(
+            verify_proof(&secp, &signed_psbt, message, &other_utxos),
+            Err(ProofError::Signature(op, _)) if op == coins[0].outpoint
+        ));
+
+        // The same coin may not be counted twice.
+        let mut dup_psbt = signed_psbt.clone();
+        let dup_txin = dup_psbt.unsigned_tx.input[1].clone();
+        let dup_psbt_in = dup_psbt.inputs[1].clone();
+        dup_psbt.unsigned_tx.input.push(dup_txin);
+        dup_psbt.inputs.push(dup_psbt_in);
+        assert_eq!(
+            verify_proof(&secp, &dup_psbt, message, &utxos),
+            Err(ProofError::DuplicateCoin(coins[0].outpoint))
+        );
+
+        // The proof must pay to the unspendable Script only.
+        let mut spendable_psbt = signed_psbt.clone();
+        spendable_psbt.unsigned_tx.output[0].script_pubkey =
+            utxos[&coins[0].outpoint].script_pubkey.clone();
+        assert_eq!(
+            verify_proof(&secp, &spendable_psbt, message, &utxos),
+            Err(ProofError::Output)
+        );
+
+        // A proof without any coin is rejected.
+        let empty_psbt = create_proof(&secp, &desc, &mut NoTxGetter, message, &[]);
+        assert_eq!(
+            verify_proof(&secp, &empty_psbt, message, &utxos),
+            Err(ProofError::NoCoin)
+        );
+    }
+}
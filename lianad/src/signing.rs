@@ -0,0 +1,468 @@
+//! # Liana signing
+//!
+//! The daemon may sign the spends stored in its database with the hot signers whose mnemonics are
+//! stored in its data directory. This is meant for a daemon acting as an automated cosigner. It is
+//! opt-in through the `signing_config` section of the configuration and a spend is only ever
+//! signed if it abides by the signing policy set there.
+
+use crate::{config::SigningConfig, database::DatabaseInterface, datadir::DataDirectory};
+
+use liana::{
+    descriptors::LianaDescriptor,
+    signer::{HotSigner, SignerError, MNEMONICS_FOLDER_NAME},
+};
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, sync,
+};
+
+use miniscript::bitcoin::{
+    self, address::NetworkUnchecked, bip32::Fingerprint, psbt::Psbt, secp256k1,
+};
+use serde::{Deserialize, Serialize};
+
+/// The period over which the amount sent by the signed spends is limited, in seconds.
+pub const DAILY_LIMIT_PERIOD: u32 = 24 * 60 * 60;
+
+/// A way of spending the coins of the wallet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpendingPath {
+    /// The primary path, available at any time.
+    Primary,
+    /// Any of the timelocked recovery paths.
+    Recovery,
+}
+
+impl fmt::Display for SpendingPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Primary => write!(f, "primary"),
+            Self::Recovery => write!(f, "recovery"),
+        }
+    }
+}
+
+fn default_allowed_paths() -> Vec<SpendingPath> {
+    vec![SpendingPath::Primary]
+}
+
+fn is_default_allowed_paths(paths: &[SpendingPath]) -> bool {
+    paths == default_allowed_paths().as_slice()
+}
+
+/// The rules a spend must follow for the daemon to sign it. All of them are checked.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SigningPolicy {
+    /// The maximum value a single spend may send out of the wallet, fees included, in satoshis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tx_amount_sat: Option<u64>,
+    /// The maximum value the spends signed over the last 24 hours may send out of the wallet,
+    /// fees included, in satoshis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_daily_amount_sat: Option<u64>,
+    /// The addresses a spend may send coins to, besides the wallet's own. Any if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_destinations: Option<Vec<bitcoin::Address<NetworkUnchecked>>>,
+    /// The spending paths a spend may use. Only the primary path if not set.
+    #[serde(
+        default = "default_allowed_paths",
+        skip_serializing_if = "is_default_allowed_paths"
+    )]
+    pub allowed_paths: Vec<SpendingPath>,
+}
+
+impl Default for SigningPolicy {
+    fn default() -> Self {
+        SigningPolicy {
+            max_tx_amount_sat: None,
+            max_daily_amount_sat: None,
+            allowed_destinations: None,
+            allowed_paths: default_allowed_paths(),
+        }
+    }
+}
+
+/// The reason why a spend was refused to be signed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyViolation {
+    /// The spend could not be analyzed.
+    InsaneSpend(String),
+    /// The spend may be made through a spending path which is not allowed.
+    SpendingPath(SpendingPath),
+    /// The spend sends coins to an address which is not allowed.
+    Destination(bitcoin::ScriptBuf),
+    TxAmount {
+        amount: bitcoin::Amount,
+        max: bitcoin::Amount,
+    },
+    DailyAmount {
+        amount: bitcoin::Amount,
+        already_signed: bitcoin::Amount,
+        max: bitcoin::Amount,
+    },
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InsaneSpend(e) => write!(f, "Cannot analyze the spend: {}", e),
+            Self::SpendingPath(path) => {
+                write!(f, "Spending through the {} path is not allowed.", path)
+            }
+            Self::Destination(spk) => {
+                write!(f, "Sending to script '{}' is not allowed.", spk.to_hex_string())
+            }
+            Self::TxAmount { amount, max } => write!(
+                f,
+                "The spend sends {} out of the wallet, more than the maximum of {} per transaction.",
+                amount, max
+            ),
+            Self::DailyAmount {
+                amount,
+                already_signed,
+                max,
+            } => write!(
+                f,
+                "The spend sends {} out of the wallet while {} were already signed over the last \
+                 24 hours, more than the maximum of {} per day.",
+                amount, already_signed, max
+            ),
+        }
+    }
+}
+
+/// What a spend does with the coins of the wallet, as far as the signing policy is concerned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpendSummary {
+    /// The value the spend sends out of the wallet, including the fees.
+    pub amount: bitcoin::Amount,
+    /// The scripts of the outputs which do not pay back to the wallet.
+    pub destinations: Vec<bitcoin::ScriptBuf>,
+    /// The spending paths through which the spend may be made.
+    pub paths: HashSet<SpendingPath>,
+}
+
+impl SpendSummary {
+    /// Analyze a spend of the coins of this descriptor. `our_coins` are the inputs of the spend
+    /// which are coins of the wallet along with their value as stored in our database, the others
+    /// (such as those of the other party in a payjoin) are not accounted for. Errors if the spend
+    /// cannot be analyzed, or if the PSBT gives another value for one of our coins.
+    pub fn new(
+        descriptor: &LianaDescriptor,
+        psbt: &Psbt,
+        our_coins: &HashMap<bitcoin::OutPoint, bitcoin::Amount>,
+        secp: &secp256k1::Secp256k1<impl secp256k1::Verification>,
+    ) -> Result<SpendSummary, String> {
+        let spend_info = descriptor
+            .partial_spend_info(psbt)
+            .map_err(|e| e.to_string())?;
+        let mut paths = HashSet::new();
+        paths.insert(SpendingPath::Primary);
+        if !spend_info.recovery_paths().is_empty()
+            || !spend_info.time_recovery_paths().is_empty()
+            || !spend_info.absolute_recovery_paths().is_empty()
+        {
+            paths.insert(SpendingPath::Recovery);
+        }
+
+        // The value of our coins is taken from our database. The one in the PSBT is provided by
+        // whoever created it, and it's not committed to by the signatures of all inputs.
+        let mut in_value = bitcoin::Amount::ZERO;
+        for (psbt_in, txin) in psbt.inputs.iter().zip(psbt.unsigned_tx.input.iter()) {
+            let outpoint = &txin.previous_output;
+            if let Some(value) = our_coins.get(outpoint) {
+                if psbt_in
+                    .witness_utxo
+                    .as_ref()
+                    .is_some_and(|txo| txo.value != *value)
+                {
+                    return Err(format!(
+                        "the previous output value for coin '{}' doesn't match ours.",
+                        outpoint
+                    ));
+                }
+                in_value += *value;
+            }
+        }
+        let change_indexes: HashSet<usize> = descriptor
+            .change_indexes(psbt, secp)
+            .into_iter()
+            .map(|change| change.index())
+            .collect();
+        let mut change_value = bitcoin::Amount::ZERO;
+        let mut destinations = Vec::new();
+        for (i, txo) in psbt.unsigned_tx.output.iter().enumerate() {
+            if change_indexes.contains(&i) {
+                change_value += txo.value;
+            } else {
+                destinations.push(txo.script_pubkey.clone());
+            }
+        }
+        // The wallet may receive more than it spends, for instance in a payjoin we receive.
+        let amount = in_value
+            .checked_sub(change_value)
+            .unwrap_or(bitcoin::Amount::ZERO);
+
+        Ok(SpendSummary {
+            amount,
+            destinations,
+            paths,
+        })
+    }
+}
+
+impl SigningPolicy {
+    /// Check a spend against this policy, given the value sent out of the wallet by the other
+    /// spends signed over the last 24 hours.
+    pub fn check(
+        &self,
+        spend: &SpendSummary,
+        already_signed: bitcoin::Amount,
+    ) -> Result<(), PolicyViolation> {
+        // A spend through the recovery path could also be made through the primary path, but
+        // not the other way around.
+        if spend.paths.contains(&SpendingPath::Recovery)
+            && !self.allowed_paths.contains(&SpendingPath::Recovery)
+        {
+            return Err(PolicyViolation::SpendingPath(SpendingPath::Recovery));
+        }
+        if !spend.paths.contains(&SpendingPath::Recovery)
+            && !self.allowed_paths.contains(&SpendingPath::Primary)
+        {
+            return Err(PolicyViolation::SpendingPath(SpendingPath::Primary));
+        }
+
+        if let Some(allowed) = &self.allowed_destinations {
+            let allowed: HashSet<_> = allowed
+                .iter()
+                .map(|addr| addr.assume_checked_ref().script_pubkey())
+                .collect();
+            if let Some(spk) = spend
+                .destinations
+                .iter()
+                .find(|spk| !allowed.contains(*spk))
+            {
+                return Err(PolicyViolation::Destination(spk.clone()));
+            }
+        }
+
+        if let Some(max) = self.max_tx_amount_sat.map(bitcoin::Amount::from_sat) {
+            if spend.amount > max {
+                return Err(PolicyViolation::TxAmount {
+                    amount: spend.amount,
+                    max,
+                });
+            }
+        }
+        if let Some(max) = self.max_daily_amount_sat.map(bitcoin::Amount::from_sat) {
+            if spend.amount + already_signed > max {
+                return Err(PolicyViolation::DailyAmount {
+                    amount: spend.amount,
+                    already_signed,
+                    max,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the destinations of this policy are addresses for the given network.
+    pub fn check_network(&self, network: bitcoin::Network) -> Result<(), String> {
+        for addr in self.allowed_destinations.iter().flatten() {
+            if !addr.is_valid_for_network(network) {
+                return Err(format!(
+                    "Allowed destination '{}' is not an address for network {}.",
+                    addr.assume_checked_ref(),
+                    network
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The hot signers the daemon signs with, along with the policy a spend must abide by. They are
+/// shared by all the wallets of the daemon.
+pub struct Signers {
+    signers: Vec<HotSigner>,
+    policy: SigningPolicy,
+    secp: secp256k1::Secp256k1<secp256k1::All>,
+    // The database of the main wallet, in which the spends signed for all the wallets are
+    // recorded so the daily limit applies to them all.
+    signed_spends_db: sync::Mutex<sync::Arc<sync::Mutex<dyn DatabaseInterface>>>,
+}
+
+impl Signers {
+    /// Load the hot signers from the mnemonics folder of the data directory. The encrypted
+    /// mnemonics are only loaded if they can be decrypted with the configured password. The
+    /// signed spends are recorded in `signed_spends_db`.
+    pub fn load(
+        config: &SigningConfig,
+        data_dir: &DataDirectory,
+        network: bitcoin::Network,
+        signed_spends_db: sync::Arc<sync::Mutex<dyn DatabaseInterface>>,
+    ) -> Result<Signers, SignerError> {
+        let folder = data_dir.path().join(MNEMONICS_FOLDER_NAME);
+        let signers = HotSigner::from_folder(&folder, network, config.password.as_deref())?;
+        let signers = Signers {
+            signers,
+            policy: config.policy.clone(),
+            secp: secp256k1::Secp256k1::new(),
+            signed_spends_db: sync::Mutex::new(signed_spends_db),
+        };
+        log::info!(
+            "Loaded hot signers with fingerprints: {:?}",
+            signers.fingerprints()
+        );
+        Ok(signers)
+    }
+
+    pub fn fingerprints(&self) -> Vec<Fingerprint> {
+        self.signers
+            .iter()
+            .map(|signer| signer.fingerprint(&self.secp))
+            .collect()
+    }
+
+    pub fn policy(&self) -> &SigningPolicy {
+        &self.policy
+    }
+
+    /// Get the database in which the spends signed for all the wallets are recorded. It must be
+    /// held from checking a spend against the daily limit until it is recorded, for concurrent
+    /// spends not to exceed the limit together.
+    pub fn signed_spends_db(
+        &self,
+    ) -> sync::MutexGuard<'_, sync::Arc<sync::Mutex<dyn DatabaseInterface>>> {
+        self.signed_spends_db
+            .lock()
+            .expect("Signed spends lock poisoned")
+    }
+
+    /// Sign this PSBT with the hot signer of the given fingerprint.
+    ///
+    /// **This does not check the signing policy.**
+    pub fn sign(&self, fingerprint: Fingerprint, psbt: Psbt) -> Option<Result<Psbt, SignerError>> {
+        self.signers
+            .iter()
+            .find(|signer| signer.fingerprint(&self.secp) == fingerprint)
+            .map(|signer| signer.sign_psbt(psbt, &self.secp))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn summary(amount: u64, destinations: &[&str], recovery: bool) -> SpendSummary {
+        let mut paths = HashSet::new();
+        paths.insert(SpendingPath::Primary);
+        if recovery {
+            paths.insert(SpendingPath::Recovery);
+        }
+        SpendSummary {
+            amount: bitcoin::Amount::from_sat(amount),
+            destinations: destinations
+                .iter()
+                .map(|addr| {
+                    bitcoin::Address::from_str(addr)
+                        .unwrap()
+                        .assume_checked()
+                        .script_pubkey()
+                })
+                .collect(),
+            paths,
+        }
+    }
+
+    #[test]
+    fn signing_policy() {
+        let addr_a = "bc1q39srgatmkp6k2ne3l52yhkjprdvunvspqydmkx";
+        let addr_b = "bc1qnsexk3gnuyayu92fc3tczvc7k62u22a22ua2kv";
+
+        // By default there is no limit but on the spending path.
+        let policy = SigningPolicy::default();
+        policy
+            .check(
+                &summary(2_100_000_000_000_000, &[addr_a, addr_b], false),
+                bitcoin::Amount::MAX_MONEY,
+            )
+            .unwrap();
+        assert_eq!(
+            policy.check(&summary(1_000, &[addr_a], true), bitcoin::Amount::ZERO),
+            Err(PolicyViolation::SpendingPath(SpendingPath::Recovery))
+        );
+
+        // Only the recovery path may be allowed.
+        let policy = SigningPolicy {
+            allowed_paths: vec![SpendingPath::Recovery],
+            ..SigningPolicy::default()
+        };
+        policy
+            .check(&summary(1_000, &[addr_a], true), bitcoin::Amount::ZERO)
+            .unwrap();
+        assert_eq!(
+            policy.check(&summary(1_000, &[addr_a], false), bitcoin::Amount::ZERO),
+            Err(PolicyViolation::SpendingPath(SpendingPath::Primary))
+        );
+
+        // Limit the destinations and the amounts.
+        let policy = SigningPolicy {
+            max_tx_amount_sat: Some(100_000),
+            max_daily_amount_sat: Some(150_000),
+            allowed_destinations: Some(vec![bitcoin::Address::from_str(addr_a).unwrap()]),
+            ..SigningPolicy::default()
+        };
+        policy
+            .check(&summary(100_000, &[addr_a], false), bitcoin::Amount::ZERO)
+            .unwrap();
+        // A self-send has no destination.
+        policy
+            .check(&summary(1_000, &[], false), bitcoin::Amount::ZERO)
+            .unwrap();
+        assert_eq!(
+            policy.check(
+                &summary(1_000, &[addr_a, addr_b], false),
+                bitcoin::Amount::ZERO
+            ),
+            Err(PolicyViolation::Destination(
+                bitcoin::Address::from_str(addr_b)
+                    .unwrap()
+                    .assume_checked()
+                    .script_pubkey()
+            ))
+        );
+        assert_eq!(
+            policy.check(&summary(100_001, &[addr_a], false), bitcoin::Amount::ZERO),
+            Err(PolicyViolation::TxAmount {
+                amount: bitcoin::Amount::from_sat(100_001),
+                max: bitcoin::Amount::from_sat(100_000),
+            })
+        );
+        policy
+            .check(
+                &summary(50_000, &[addr_a], false),
+                bitcoin::Amount::from_sat(100_000),
+            )
+            .unwrap();
+        assert_eq!(
+            policy.check(
+                &summary(50_001, &[addr_a], false),
+                bitcoin::Amount::from_sat(100_000)
+            ),
+            Err(PolicyViolation::DailyAmount {
+                amount: bitcoin::Amount::from_sat(50_001),
+                already_signed: bitcoin::Amount::from_sat(100_000),
+                max: bitcoin::Amount::from_sat(150_000),
+            })
+        );
+
+        // The destinations must be for the network we are running on.
+        policy.check_network(bitcoin::Network::Bitcoin).unwrap();
+        policy.check_network(bitcoin::Network::Testnet).unwrap_err();
+    }
+}
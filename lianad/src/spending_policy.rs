@@ -0,0 +1,469 @@
+//! # Liana spending policy
+//!
+//! Guardrails on the spends created and broadcast by the daemon, on top of the sanity checks
+//! performed on every spend. It is opt-in through the `spending_policy` section of the
+//! configuration. A spend is checked against it when it is created and before it is broadcast.
+
+use std::{collections::HashSet, fmt};
+
+use miniscript::bitcoin::{self, address::NetworkUnchecked};
+use serde::{Deserialize, Serialize};
+
+const SECONDS_PER_HOUR: u32 = 60 * 60;
+
+/// A maximum value the spends broadcast over a rolling window of time may send out of the wallet.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct WindowLimit {
+    /// The maximum value, fees included, in satoshis.
+    pub max_amount_sat: u64,
+    /// The duration of the window, in hours.
+    pub window_hours: u32,
+}
+
+/// The rules the spends created and broadcast by the daemon must follow. All of them are checked.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub struct SpendingPolicy {
+    /// The maximum value a single spend may send out of the wallet, fees included, in satoshis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tx_amount_sat: Option<u64>,
+    /// The addresses a spend may send coins to, besides the wallet's own. Any if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_destinations: Option<Vec<bitcoin::Address<NetworkUnchecked>>>,
+    /// The addresses a spend may never send coins to.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub denied_destinations: Vec<bitcoin::Address<NetworkUnchecked>>,
+    /// Whether the outputs which do not pay back to the wallet must be labelled.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub require_labels: bool,
+    /// The number of confirmations the coins must have to be spent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_confirmations: Option<u32>,
+    /// The number of hours to wait after a spend was stored before it may be broadcast.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub broadcast_delay_hours: Option<u32>,
+    /// The maximum values the spends broadcast over rolling windows may send out of the wallet.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub window_limits: Vec<WindowLimit>,
+}
+
+/// The reason why a spend does not abide by the spending policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpendingPolicyViolation {
+    /// The spend could not be analyzed.
+    InsaneSpend(String),
+    TxAmount {
+        amount: bitcoin::Amount,
+        max: bitcoin::Amount,
+    },
+    WindowAmount {
+        amount: bitcoin::Amount,
+        already_broadcast: bitcoin::Amount,
+        max: bitcoin::Amount,
+        window_hours: u32,
+    },
+    /// The spend sends coins to an address which is not among the allowed destinations.
+    NotAllowedDestination(bitcoin::ScriptBuf),
+    /// The spend sends coins to an address which is among the denied destinations.
+    DeniedDestination(bitcoin::ScriptBuf),
+    /// This output of the spend does not pay back to the wallet and is not labelled.
+    MissingLabel(bitcoin::OutPoint),
+    InputConfirmations {
+        outpoint: bitcoin::OutPoint,
+        confirmations: u32,
+        min: u32,
+    },
+    /// The spend may only be broadcast in this number of seconds.
+    BroadcastDelay(u32),
+}
+
+impl fmt::Display for SpendingPolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InsaneSpend(e) => write!(f, "Cannot analyze the spend: {}", e),
+            Self::TxAmount { amount, max } => write!(
+                f,
+                "The spend sends {} out of the wallet, more than the maximum of {} per transaction.",
+                amount, max
+            ),
+            Self::WindowAmount {
+                amount,
+                already_broadcast,
+                max,
+                window_hours,
+            } => write!(
+                f,
+                "The spend sends {} out of the wallet while {} were already broadcast over the last \
+                 {} hours, more than the maximum of {}.",
+                amount, already_broadcast, window_hours, max
+            ),
+            Self::NotAllowedDestination(spk) => write!(
+                f,
+                "Sending to script '{}' is not allowed.",
+                spk.to_hex_string()
+            ),
+            Self::DeniedDestination(spk) => {
+                write!(f, "Sending to script '{}' is denied.", spk.to_hex_string())
+            }
+            Self::MissingLabel(outpoint) => write!(f, "Output '{}' must be labelled.", outpoint),
+            Self::InputConfirmations {
+                outpoint,
+                confirmations,
+                min,
+            } => write!(
+                f,
+                "Coin '{}' has {} confirmations, less than the minimum of {}.",
+                outpoint, confirmations, min
+            ),
+            Self::BroadcastDelay(secs) => write!(
+                f,
+                "The spend may only be broadcast in {} minutes.",
+                (secs + 59) / 60
+            ),
+        }
+    }
+}
+
+/// An output of a spend which does not pay back to the wallet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DestinationOutput {
+    pub outpoint: bitcoin::OutPoint,
+    pub script_pubkey: bitcoin::ScriptBuf,
+    /// Whether either the output or its address is labelled.
+    pub is_labelled: bool,
+}
+
+/// What a spend does, as far as the spending policy is concerned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicySpend {
+    pub txid: bitcoin::Txid,
+    /// The value the spend sends out of the wallet, including the fees.
+    pub amount: bitcoin::Amount,
+    pub destinations: Vec<DestinationOutput>,
+    /// The coins spent, along with their number of confirmations.
+    pub inputs: Vec<(bitcoin::OutPoint, u32)>,
+}
+
+impl SpendingPolicy {
+    /// The duration of the longest window limit, in seconds. Spends broadcast before that may be
+    /// disregarded.
+    pub fn longest_window(&self) -> u32 {
+        self.window_limits
+            .iter()
+            .map(|limit| limit.window_hours.saturating_mul(SECONDS_PER_HOUR))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Check a spend against all the rules of this policy but the broadcast delay, given the
+    /// spends broadcast so far along with the value they sent out of the wallet and the date at
+    /// which they were broadcast.
+    pub fn violations(
+        &self,
+        spend: &PolicySpend,
+        broadcast_spends: &[(bitcoin::Txid, bitcoin::Amount, u32)],
+        now: u32,
+    ) -> Vec<SpendingPolicyViolation> {
+        let mut violations = Vec::new();
+
+        if let Some(max) = self.max_tx_amount_sat.map(bitcoin::Amount::from_sat) {
+            if spend.amount > max {
+                violations.push(SpendingPolicyViolation::TxAmount {
+                    amount: spend.amount,
+                    max,
+                });
+            }
+        }
+        // A spend which was already broadcast does not count twice.
+        for limit in &self.window_limits {
+            let start = now.saturating_sub(limit.window_hours.saturating_mul(SECONDS_PER_HOUR));
+            let already_broadcast = broadcast_spends
+                .iter()
+                .filter(|(txid, _, broadcast_at)| *txid != spend.txid && *broadcast_at >= start)
+                .map(|(_, amount, _)| *amount)
+                .sum();
+            let max = bitcoin::Amount::from_sat(limit.max_amount_sat);
+            if spend.amount + already_broadcast > max {
+                violations.push(SpendingPolicyViolation::WindowAmount {
+                    amount: spend.amount,
+                    already_broadcast,
+                    max,
+                    window_hours: limit.window_hours,
+                });
+            }
+        }
+
+        let allowed: Option<HashSet<_>> = self.allowed_destinations.as_ref().map(|allowed| {
+            allowed
+                .iter()
+                .map(|addr| addr.assume_checked_ref().script_pubkey())
+                .collect()
+        });
+        let denied: HashSet<_> = self
+            .denied_destinations
+            .iter()
+            .map(|addr| addr.assume_checked_ref().script_pubkey())
+            .collect();
+        for destination in &spend.destinations {
+            if denied.contains(&destination.script_pubkey) {
+                violations.push(SpendingPolicyViolation::DeniedDestination(
+                    destination.script_pubkey.clone(),
+                ));
+            } else if let Some(allowed) = &allowed {
+                if !allowed.contains(&destination.script_pubkey) {
+                    violations.push(SpendingPolicyViolation::NotAllowedDestination(
+                        destination.script_pubkey.clone(),
+                    ));
+                }
+            }
+            if self.require_labels && !destination.is_labelled {
+                violations.push(SpendingPolicyViolation::MissingLabel(destination.outpoint));
+            }
+        }
+
+        if let Some(min) = self.min_confirmations {
+            for (outpoint, confirmations) in &spend.inputs {
+                if *confirmations < min {
+                    violations.push(SpendingPolicyViolation::InputConfirmations {
+                        outpoint: *outpoint,
+                        confirmations: *confirmations,
+                        min,
+                    });
+                }
+            }
+        }
+
+        violations
+    }
+
+    /// Check a spend stored at the given date may be broadcast now. A spend stored before we
+    /// recorded this date is held back as if it was stored now. It may be deleted and stored
+    /// again to start the delay.
+    pub fn broadcast_delay_violation(
+        &self,
+        stored_at: Option<u32>,
+        now: u32,
+    ) -> Option<SpendingPolicyViolation> {
+        let delay = self.broadcast_delay_hours?.saturating_mul(SECONDS_PER_HOUR);
+        let elapsed = now.saturating_sub(stored_at.unwrap_or(now));
+        if elapsed < delay {
+            Some(SpendingPolicyViolation::BroadcastDelay(delay - elapsed))
+        } else {
+            None
+        }
+    }
+
+    /// Check the rules of this policy are consistent, and their addresses for the given network.
+    pub fn check(&self, network: bitcoin::Network) -> Result<(), String> {
+        for addr in self
+            .allowed_destinations
+            .iter()
+            .flatten()
+            .chain(self.denied_destinations.iter())
+        {
+            if !addr.is_valid_for_network(network) {
+                return Err(format!(
+                    "Spending policy destination '{}' is not an address for network {}.",
+                    addr.assume_checked_ref(),
+                    network
+                ));
+            }
+        }
+        if self
+            .window_limits
+            .iter()
+            .any(|limit| limit.window_hours == 0)
+        {
+            return Err(
+                "Spending policy window limits must be at least one hour long.".to_string(),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use miniscript::bitcoin::hashes::Hash;
+    use std::str::FromStr;
+
+    fn spk(addr: &str) -> bitcoin::ScriptBuf {
+        bitcoin::Address::from_str(addr)
+            .unwrap()
+            .assume_checked()
+            .script_pubkey()
+    }
+
+    fn spend(amount: u64, destinations: &[(&str, bool)], inputs: &[u32]) -> PolicySpend {
+        let txid = bitcoin::Txid::from_str(
+            "6f0dc85a369b44458eba3a6b0db9ab6a1f0e2a3ea3e0b1a4f6c3e0c3d7a4f33e",
+        )
+        .unwrap();
+        PolicySpend {
+            txid,
+            amount: bitcoin::Amount::from_sat(amount),
+            destinations: destinations
+                .iter()
+                .enumerate()
+                .map(|(vout, (addr, is_labelled))| DestinationOutput {
+                    outpoint: bitcoin::OutPoint::new(txid, vout as u32),
+                    script_pubkey: spk(addr),
+                    is_labelled: *is_labelled,
+                })
+                .collect(),
+            inputs: inputs
+                .iter()
+                .enumerate()
+                .map(|(vout, confs)| {
+                    (
+                        bitcoin::OutPoint::new(bitcoin::Txid::all_zeros(), vout as u32),
+                        *confs,
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn spending_policy() {
+        let addr_a = "bc1q39srgatmkp6k2ne3l52yhkjprdvunvspqydmkx";
+        let addr_b = "bc1qnsexk3gnuyayu92fc3tczvc7k62u22a22ua2kv";
+        let now = 1_700_000_000;
+
+        // By default there is no rule.
+        let policy = SpendingPolicy::default();
+        assert!(policy
+            .violations(
+                &spend(2_100_000_000_000_000, &[(addr_a, false)], &[0]),
+                &[],
+                now
+            )
+            .is_empty());
+        assert_eq!(policy.broadcast_delay_violation(Some(now), now), None);
+        assert_eq!(policy.longest_window(), 0);
+
+        // All the violations are reported.
+        let policy = SpendingPolicy {
+            max_tx_amount_sat: Some(100_000),
+            allowed_destinations: Some(vec![bitcoin::Address::from_str(addr_a).unwrap()]),
+            require_labels: true,
+            min_confirmations: Some(6),
+            ..SpendingPolicy::default()
+        };
+        assert!(policy
+            .violations(&spend(100_000, &[(addr_a, true)], &[6, 10]), &[], now)
+            .is_empty());
+        let violating = spend(100_001, &[(addr_a, false), (addr_b, true)], &[6, 5]);
+        assert_eq!(
+            policy.violations(&violating, &[], now),
+            vec![
+                SpendingPolicyViolation::TxAmount {
+                    amount: bitcoin::Amount::from_sat(100_001),
+                    max: bitcoin::Amount::from_sat(100_000),
+                },
+                SpendingPolicyViolation::MissingLabel(violating.destinations[0].outpoint),
+                SpendingPolicyViolation::NotAllowedDestination(spk(addr_b)),
+                SpendingPolicyViolation::InputConfirmations {
+                    outpoint: violating.inputs[1].0,
+                    confirmations: 5,
+                    min: 6,
+                },
+            ]
+        );
+
+        // Denied destinations.
+        let policy = SpendingPolicy {
+            denied_destinations: vec![bitcoin::Address::from_str(addr_b).unwrap()],
+            ..SpendingPolicy::default()
+        };
+        assert!(policy
+            .violations(&spend(1_000, &[(addr_a, false)], &[]), &[], now)
+            .is_empty());
+        assert_eq!(
+            policy.violations(&spend(1_000, &[(addr_b, false)], &[]), &[], now),
+            vec![SpendingPolicyViolation::DeniedDestination(spk(addr_b))]
+        );
+
+        // Rolling windows only account for the other spends broadcast within them.
+        let policy = SpendingPolicy {
+            window_limits: vec![
+                WindowLimit {
+                    max_amount_sat: 100_000,
+                    window_hours: 1,
+                },
+                WindowLimit {
+                    max_amount_sat: 150_000,
+                    window_hours: 24,
+                },
+            ],
+            ..SpendingPolicy::default()
+        };
+        assert_eq!(policy.longest_window(), 24 * 60 * 60);
+        let other_txid = bitcoin::Txid::from_str(
+            "0c62a990d20d54429e70859292e82374ba6b1b951a3ab60f26bb65fee5724ff7",
+        )
+        .unwrap();
+        let candidate = spend(50_000, &[(addr_a, false)], &[]);
+        let broadcast_spends = [
+            (
+                other_txid,
+                bitcoin::Amount::from_sat(90_000),
+                now - 2 * 60 * 60,
+            ),
+            (candidate.txid, bitcoin::Amount::from_sat(50_000), now - 60),
+        ];
+        assert!(policy
+            .violations(&candidate, &broadcast_spends, now)
+            .is_empty());
+        let candidate = spend(60_001, &[(addr_a, false)], &[]);
+        assert_eq!(
+            policy.violations(&candidate, &broadcast_spends, now),
+            vec![SpendingPolicyViolation::WindowAmount {
+                amount: bitcoin::Amount::from_sat(60_001),
+                already_broadcast: bitcoin::Amount::from_sat(90_000),
+                max: bitcoin::Amount::from_sat(150_000),
+                window_hours: 24,
+            }]
+        );
+        assert!(policy
+            .violations(&candidate, &broadcast_spends, now + 24 * 60 * 60)
+            .is_empty());
+
+        // The broadcast delay starts when the spend is stored.
+        let policy = SpendingPolicy {
+            broadcast_delay_hours: Some(2),
+            ..SpendingPolicy::default()
+        };
+        assert_eq!(
+            policy.broadcast_delay_violation(Some(now - 60 * 60), now),
+            Some(SpendingPolicyViolation::BroadcastDelay(60 * 60))
+        );
+        assert_eq!(
+            policy.broadcast_delay_violation(Some(now - 2 * 60 * 60), now),
+            None
+        );
+        assert_eq!(
+            policy.broadcast_delay_violation(None, now),
+            Some(SpendingPolicyViolation::BroadcastDelay(2 * 60 * 60))
+        );
+
+        // Addresses must be for our network and windows not empty.
+        let policy = SpendingPolicy {
+            denied_destinations: vec![bitcoin::Address::from_str(addr_b).unwrap()],
+            ..SpendingPolicy::default()
+        };
+        policy.check(bitcoin::Network::Bitcoin).unwrap();
+        assert!(policy
+            .check(bitcoin::Network::Testnet)
+            .unwrap_err()
+            .contains("is not an address for network testnet"));
+        let policy = SpendingPolicy {
+            window_limits: vec![WindowLimit {
+                max_amount_sat: 100_000,
+                window_hours: 0,
+            }],
+            ..SpendingPolicy::default()
+        };
+        policy.check(bitcoin::Network::Bitcoin).unwrap_err();
+    }
+}
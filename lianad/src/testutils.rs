@@ -1,8 +1,12 @@
 use crate::{
-    bitcoin::{BitcoinInterface, Block, BlockChainTip, MempoolEntry, SyncProgress, UTxO},
+    bitcoin::{
+        BitcoinInterface, Block, BlockChainTip, FeeEstimate, FeeEstimateMode, MempoolEntry,
+        SyncProgress, UTxO, WatchonlyUTxO,
+    },
     config::{BitcoinConfig, Config},
     database::{
         BlockInfo, Coin, CoinStatus, DatabaseConnection, DatabaseInterface, LabelItem, Wallet,
+        WatchonlyCoin,
     },
     datadir::DataDirectory,
     DaemonControl, DaemonHandle,
@@ -25,6 +29,10 @@ use miniscript::{
 
 pub struct DummyBitcoind {
     pub txs: HashMap<Txid, (Transaction, Option<Block>)>,
+    pub mempool_entries: HashMap<Txid, MempoolEntry>,
+    pub used_spks: HashSet<bitcoin::ScriptBuf>,
+    pub used_spks_max_index: Option<u32>,
+    pub utxos: HashMap<bitcoin::OutPoint, bitcoin::TxOut>,
 }
 
 impl DummyBitcoind {}
@@ -33,6 +41,10 @@ impl DummyBitcoind {
     pub fn new() -> Self {
         Self {
             txs: HashMap::new(),
+            mempool_entries: HashMap::new(),
+            used_spks: HashSet::new(),
+            used_spks_max_index: None,
+            utxos: HashMap::new(),
         }
     }
 }
@@ -113,22 +125,37 @@ impl BitcoinInterface for DummyBitcoind {
         todo!()
     }
 
-    fn start_rescan(&mut self, _: &descriptors::LianaDescriptor, _: u32) -> Result<(), String> {
+    fn test_mempool_accept(&self, _: &bitcoin::Transaction) -> Result<Option<String>, String> {
         todo!()
     }
 
+    fn start_rescan(&mut self, _: &descriptors::LianaDescriptor, _: u32) -> Result<(), String> {
+        // Rescans complete immediately.
+        Ok(())
+    }
+
     fn rescan_progress(&self) -> Option<f64> {
         None
     }
 
     fn block_before_date(&self, _: u32) -> Option<BlockChainTip> {
-        todo!()
+        Some(self.genesis_block())
     }
 
     fn tip_time(&self) -> Option<u32> {
         None
     }
 
+    fn tip_median_time_past(&self) -> Option<u32> {
+        self.median_time_past(self.chain_tip().height)
+    }
+
+    fn median_time_past(&self, height: i32) -> Option<u32> {
+        // Pretend a block was mined every 10 minutes since the genesis block.
+        let height: u32 = height.try_into().ok()?;
+        Some(self.genesis_block_timestamp() + height * 600)
+    }
+
     fn wallet_transaction(
         &self,
         txid: &bitcoin::Txid,
@@ -140,8 +167,64 @@ impl BitcoinInterface for DummyBitcoind {
         Vec::new()
     }
 
-    fn mempool_entry(&self, _: &bitcoin::Txid) -> Option<MempoolEntry> {
-        None
+    fn mempool_entry(&self, txid: &bitcoin::Txid) -> Option<MempoolEntry> {
+        self.mempool_entries.get(txid).cloned()
+    }
+
+    fn estimate_fee(&self, conf_target: u16, mode: FeeEstimateMode) -> Option<FeeEstimate> {
+        // Pretend we only have data for targets of 2 blocks or more.
+        let blocks = conf_target.max(2);
+        let feerate_vb = match mode {
+            FeeEstimateMode::Conservative => 20,
+            FeeEstimateMode::Economical => 10,
+        };
+        Some(FeeEstimate { feerate_vb, blocks })
+    }
+
+    fn import_watchonly_descriptor(
+        &mut self,
+        _: &descriptor::Descriptor<descriptor::DescriptorPublicKey>,
+        _: u32,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn watchonly_coins(&self, spks: &[bitcoin::ScriptBuf]) -> Vec<WatchonlyUTxO> {
+        self.utxos
+            .iter()
+            .filter(|(_, txo)| spks.contains(&txo.script_pubkey))
+            .map(|(op, txo)| WatchonlyUTxO {
+                outpoint: *op,
+                amount: txo.value,
+                script_pubkey: txo.script_pubkey.clone(),
+                block_height: None,
+            })
+            .collect()
+    }
+
+    fn used_spks(
+        &self,
+        spks: &[bitcoin::ScriptBuf],
+    ) -> Result<HashSet<bitcoin::ScriptBuf>, String> {
+        Ok(spks
+            .iter()
+            .filter(|spk| self.used_spks.contains(*spk))
+            .cloned()
+            .collect())
+    }
+
+    fn used_spks_max_index(&self, _: &descriptors::LianaDescriptor) -> Option<u32> {
+        self.used_spks_max_index
+    }
+
+    fn unspent_outputs(
+        &self,
+        outputs: &[(bitcoin::OutPoint, bitcoin::ScriptBuf)],
+    ) -> Result<HashMap<bitcoin::OutPoint, bitcoin::TxOut>, String> {
+        Ok(outputs
+            .iter()
+            .filter_map(|(op, _)| self.utxos.get(op).map(|txo| (*op, txo.clone())))
+            .collect())
     }
 }
 
@@ -156,6 +239,10 @@ struct DummyDbState {
     timestamp: u32,
     rescan_timestamp: Option<u32>,
     last_poll_timestamp: Option<u32>,
+    watchonly_descriptors: Vec<(descriptor::Descriptor<descriptor::DescriptorPublicKey>, u32)>,
+    watchonly_coins: Vec<WatchonlyCoin>,
+    signed_spends: Vec<(bitcoin::Txid, bitcoin::Amount, u32)>,
+    broadcast_spends: Vec<(bitcoin::Txid, bitcoin::Amount, u32)>,
 }
 
 pub struct DummyDatabase {
@@ -191,6 +278,10 @@ impl DummyDatabase {
                 timestamp: now,
                 rescan_timestamp: None,
                 last_poll_timestamp: None,
+                watchonly_descriptors: Vec::new(),
+                watchonly_coins: Vec::new(),
+                signed_spends: Vec::new(),
+                broadcast_spends: Vec::new(),
             })),
         }
     }
@@ -347,6 +438,20 @@ impl DatabaseConnection for DummyDatabase {
         }
     }
 
+    fn freeze_coins(&mut self, outpoints: &[bitcoin::OutPoint]) {
+        for op in outpoints {
+            let mut db = self.db.write().unwrap();
+            db.coins.get_mut(op).unwrap().is_frozen = true;
+        }
+    }
+
+    fn unfreeze_coins(&mut self, outpoints: &[bitcoin::OutPoint]) {
+        for op in outpoints {
+            let mut db = self.db.write().unwrap();
+            db.coins.get_mut(op).unwrap().is_frozen = false;
+        }
+    }
+
     fn confirm_spend<'a>(&mut self, outpoints: &[(bitcoin::OutPoint, bitcoin::Txid, i32, u32)]) {
         for (op, spend_txid, height, time) in outpoints {
             let mut db = self.db.write().unwrap();
@@ -416,20 +521,20 @@ impl DatabaseConnection for DummyDatabase {
         self.db.write().unwrap().spend_txs.remove(txid);
     }
 
-    fn rollback_tip(&mut self, _: &BlockChainTip) {
-        todo!()
+    fn rollback_tip(&mut self, tip: &BlockChainTip) {
+        self.db.write().unwrap().curr_tip = Some(*tip);
     }
 
     fn rescan_timestamp(&mut self) -> Option<u32> {
         self.db.read().unwrap().rescan_timestamp
     }
 
-    fn set_rescan(&mut self, _: u32) {
-        todo!()
+    fn set_rescan(&mut self, timestamp: u32) {
+        self.db.write().unwrap().rescan_timestamp = Some(timestamp);
     }
 
     fn complete_rescan(&mut self) {
-        todo!()
+        self.db.write().unwrap().rescan_timestamp = None;
     }
 
     fn last_poll_timestamp(&mut self) -> Option<u32> {
@@ -550,6 +655,84 @@ impl DatabaseConnection for DummyDatabase {
     fn get_labels_bip329(&mut self, _offset: u32, _limit: u32) -> bip329::Labels {
         todo!()
     }
+
+    fn watchonly_descriptors(
+        &mut self,
+    ) -> Vec<(descriptor::Descriptor<descriptor::DescriptorPublicKey>, u32)> {
+        self.db.read().unwrap().watchonly_descriptors.clone()
+    }
+
+    fn new_watchonly_descriptor(
+        &mut self,
+        desc: &descriptor::Descriptor<descriptor::DescriptorPublicKey>,
+        timestamp: u32,
+    ) {
+        self.db
+            .write()
+            .unwrap()
+            .watchonly_descriptors
+            .push((desc.clone(), timestamp));
+    }
+
+    fn watchonly_coins(&mut self) -> Vec<WatchonlyCoin> {
+        self.db.read().unwrap().watchonly_coins.clone()
+    }
+
+    fn set_watchonly_coins(&mut self, coins: &[WatchonlyCoin]) {
+        self.db.write().unwrap().watchonly_coins = coins.to_vec();
+    }
+
+    fn signed_spends_since(&mut self, timestamp: u32) -> Vec<(bitcoin::Txid, bitcoin::Amount)> {
+        self.db
+            .read()
+            .unwrap()
+            .signed_spends
+            .iter()
+            .filter(|(_, _, signed_at)| *signed_at >= timestamp)
+            .map(|(txid, amount, _)| (*txid, *amount))
+            .collect()
+    }
+
+    fn record_signed_spend(
+        &mut self,
+        txid: &bitcoin::Txid,
+        amount: bitcoin::Amount,
+        timestamp: u32,
+    ) {
+        let mut db = self.db.write().unwrap();
+        if let Some(spend) = db.signed_spends.iter_mut().find(|(t, _, _)| t == txid) {
+            spend.1 = spend.1.max(amount);
+            spend.2 = spend.2.max(timestamp);
+        } else {
+            db.signed_spends.push((*txid, amount, timestamp));
+        }
+    }
+
+    fn broadcast_spends_since(
+        &mut self,
+        timestamp: u32,
+    ) -> Vec<(bitcoin::Txid, bitcoin::Amount, u32)> {
+        self.db
+            .read()
+            .unwrap()
+            .broadcast_spends
+            .iter()
+            .filter(|(_, _, broadcast_at)| *broadcast_at >= timestamp)
+            .cloned()
+            .collect()
+    }
+
+    fn record_broadcast_spend(
+        &mut self,
+        txid: &bitcoin::Txid,
+        amount: bitcoin::Amount,
+        timestamp: u32,
+    ) {
+        let mut db = self.db.write().unwrap();
+        if !db.broadcast_spends.iter().any(|(t, _, _)| t == txid) {
+            db.broadcast_spends.push((*txid, amount, timestamp));
+        }
+    }
 }
 
 pub struct DummyLiana {
@@ -583,6 +766,8 @@ impl DummyLiana {
         database: impl DatabaseInterface + 'static,
         rpc_server: bool,
         timelock: u16,
+        absolute_timelock: Option<u32>,
+        time_timelock: Option<u16>,
     ) -> DummyLiana {
         let tmp_dir = tmp_dir();
         fs::create_dir_all(&tmp_dir).unwrap();
@@ -601,11 +786,30 @@ impl DummyLiana {
 
         let owner_key = descriptors::PathInfo::Single(descriptor::DescriptorPublicKey::from_str("[aabbccdd]xpub68JJTXc1MWK8KLW4HGLXZBJknja7kDUJuFHnM424LbziEXsfkh1WQCiEjjHw4zLqSUm4rvhgyGkkuRowE9tCJSgt3TQB5J3SKAbZ2SdcKST/<0;1>/*").unwrap());
         let heir_key = descriptors::PathInfo::Single(descriptor::DescriptorPublicKey::from_str("[aabbccdd]xpub68JJTXc1MWK8PEQozKsRatrUHXKFNkD1Cb1BuQU9Xr5moCv87anqGyXLyUd4KpnDyZgo3gz4aN1r3NiaoweFW8UutBsBbgKHzaD5HkTkifK/<0;1>/*").unwrap());
-        let policy = descriptors::LianaPolicy::new_legacy(
+        let mut policy = descriptors::LianaPolicy::new_legacy(
             owner_key,
             [(timelock, heir_key)].iter().cloned().collect(),
         )
         .unwrap();
+        if let Some(absolute_timelock) = absolute_timelock {
+            let abs_heir_key = descriptors::PathInfo::Single(descriptor::DescriptorPublicKey::from_str("[aabbccdd]xpub6Bw79HbNSeS2xXw1sngPE3ehnk1U3iSPCgLYzC9LpN8m9nDuaKLZvkg8QXxL5pDmEmQtYscmUD8B9MkAAZbh6vxPzNXMaLfGQ9Sb3z85qhR/<0;1>/*").unwrap());
+            policy = policy
+                .with_absolute_recovery_paths(
+                    [(absolute_timelock, abs_heir_key)]
+                        .iter()
+                        .cloned()
+                        .collect(),
+                )
+                .unwrap();
+        }
+        if let Some(time_timelock) = time_timelock {
+            let time_heir_key = descriptors::PathInfo::Single(descriptor::DescriptorPublicKey::from_str("[aabbccdd]xpub67zuTXF9Ln4731avKTBSawoVVNRuMfmRvkL7kLUaLBRqma9ZqdHBJg9qx8cPUm3oNQMiXT4TmGovXNoQPuwg17RFcVJ8YrnbcooN7pxVJqC/<0;1>/*").unwrap());
+            policy = policy
+                .with_time_recovery_paths(
+                    [(time_timelock, time_heir_key)].iter().cloned().collect(),
+                )
+                .unwrap();
+        }
         let desc = descriptors::LianaDescriptor::new(policy);
         let config = Config::new(
             bitcoin_config,
@@ -626,7 +830,7 @@ impl DummyLiana {
         bitcoin_interface: impl BitcoinInterface + 'static,
         database: impl DatabaseInterface + 'static,
     ) -> DummyLiana {
-        Self::_new(bitcoin_interface, database, false, 10_000)
+        Self::_new(bitcoin_interface, database, false, 10_000, None, None)
     }
 
     /// Creates a new DummyLiana interface with the specified recovery path timelock.
@@ -635,7 +839,43 @@ impl DummyLiana {
         database: impl DatabaseInterface + 'static,
         timelock: u16,
     ) -> DummyLiana {
-        Self::_new(bitcoin_interface, database, false, timelock)
+        Self::_new(bitcoin_interface, database, false, timelock, None, None)
+    }
+
+    /// Creates a new DummyLiana interface with the specified recovery path timelock and an
+    /// additional recovery path behind the specified absolute timelock.
+    pub fn new_absolute_timelock(
+        bitcoin_interface: impl BitcoinInterface + 'static,
+        database: impl DatabaseInterface + 'static,
+        timelock: u16,
+        absolute_timelock: u32,
+    ) -> DummyLiana {
+        Self::_new(
+            bitcoin_interface,
+            database,
+            false,
+            timelock,
+            Some(absolute_timelock),
+            None,
+        )
+    }
+
+    /// Creates a new DummyLiana interface with the specified recovery path timelock and an
+    /// additional recovery path behind the specified time-based relative timelock.
+    pub fn new_time_timelock(
+        bitcoin_interface: impl BitcoinInterface + 'static,
+        database: impl DatabaseInterface + 'static,
+        timelock: u16,
+        time_timelock: u16,
+    ) -> DummyLiana {
+        Self::_new(
+            bitcoin_interface,
+            database,
+            false,
+            timelock,
+            None,
+            Some(time_timelock),
+        )
     }
 
     /// Creates a new DummyLiana interface which also spins up an RPC server.
@@ -643,12 +883,12 @@ impl DummyLiana {
         bitcoin_interface: impl BitcoinInterface + 'static,
         database: impl DatabaseInterface + 'static,
     ) -> DummyLiana {
-        Self::_new(bitcoin_interface, database, true, 10_000)
+        Self::_new(bitcoin_interface, database, true, 10_000, None, None)
     }
 
     pub fn control(&self) -> &DaemonControl {
         match self.handle {
-            DaemonHandle::Controller { ref control, .. } => control,
+            DaemonHandle::Controller { ref wallets, .. } => wallets.main(),
             DaemonHandle::Server { .. } => unreachable!(),
         }
     }
@@ -0,0 +1,330 @@
+//! # Liana wallets
+//!
+//! A single daemon may host several wallets. The main wallet is the one of the configuration and
+//! is always loaded. Additional wallets are created, loaded and unloaded through the API. They are
+//! all stored in the database of the main wallet, each under its own name. An additional wallet
+//! also has a data directory, under the `wallets/` folder of the daemon's data directory by
+//! default, for the state of its Bitcoin backend (such as its watchonly wallet). They all reuse the
+//! connection of the main wallet to the Bitcoin backend and are kept up to date by the daemon's
+//! Bitcoin poller.
+//!
+//! Only parts of the configuration apply to the additional wallets: the Bitcoin backend, the gap
+//! limit and the spending policy (see [`crate::config::Config::wallet_config`]). The hot signers are
+//! those of the daemon. The JSONRPC server and the notification hooks are those of the main wallet,
+//! the events of the other wallets may be subscribed to through the API.
+
+use crate::{
+    bitcoin::poller::{PolledWallet, PolledWallets},
+    commands::CommandError,
+    database::sqlite::SqliteDb,
+    datadir::DataDirectory,
+    notifications::Notifier,
+    setup_wallet, BackendConnection, DaemonControl,
+};
+
+use liana::descriptors::LianaDescriptor;
+
+use std::{collections::BTreeMap, path, sync};
+
+use serde::{Deserialize, Serialize};
+
+/// The name under which the main wallet is listed and may be selected.
+pub const MAIN_WALLET: &str = "main";
+
+/// The maximum length of a wallet name.
+const MAX_WALLET_NAME_LEN: usize = 64;
+
+// A wallet name is also the name of its default data directory. Restrict it to characters which
+// are safe to use in a path on all platforms, as well as in a URL.
+fn is_valid_wallet_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_WALLET_NAME_LEN
+        && name != MAIN_WALLET
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// The wallets hosted by this daemon.
+#[derive(Clone)]
+pub struct Wallets {
+    main: DaemonControl,
+    // The database of the main wallet, which stores the additional wallets. Not set if the main
+    // wallet uses a custom database interface, in which case no other wallet may be hosted.
+    db: Option<SqliteDb>,
+    loaded: sync::Arc<sync::Mutex<BTreeMap<String, DaemonControl>>>,
+    polled: PolledWallets,
+    // The connection of the main wallet to the Bitcoin backend. Not set if the main wallet uses a
+    // custom interface, in which case each wallet connects to the backend on its own.
+    connection: Option<BackendConnection>,
+}
+
+impl Wallets {
+    pub(crate) fn new(
+        main: DaemonControl,
+        db: Option<SqliteDb>,
+        polled: PolledWallets,
+        connection: Option<BackendConnection>,
+    ) -> Wallets {
+        Wallets {
+            main,
+            db,
+            loaded: sync::Arc::new(sync::Mutex::new(BTreeMap::new())),
+            polled,
+            connection,
+        }
+    }
+
+    /// The main wallet of this daemon.
+    pub fn main(&self) -> &DaemonControl {
+        &self.main
+    }
+
+    /// Get the wallet with this name, or the main wallet if no name is given.
+    pub fn get(&self, name: Option<&str>) -> Result<DaemonControl, CommandError> {
+        match name {
+            None | Some(MAIN_WALLET) => Ok(self.main.clone()),
+            Some(name) => self
+                .loaded
+                .lock()
+                .expect("Wallets lock poisoned")
+                .get(name)
+                .cloned()
+                .ok_or_else(|| CommandError::WalletNotFound(name.to_string())),
+        }
+    }
+
+    fn db(&self) -> Result<&SqliteDb, CommandError> {
+        self.db.as_ref().ok_or_else(|| {
+            CommandError::WalletSetup(
+                "additional wallets can only be stored in an SQLite database".to_string(),
+            )
+        })
+    }
+
+    // The default data directory of the wallet with this name.
+    fn data_directory(&self, name: &str) -> DataDirectory {
+        self.main
+            .config
+            .data_directory()
+            .expect("The daemon's data directory was set at startup.")
+            .wallet_directory(name)
+    }
+
+    // Set up the wallet with this name and descriptor, and start polling it. The wallet is created
+    // in database if it isn't stored there yet.
+    fn load(
+        &self,
+        loaded: &mut BTreeMap<String, DaemonControl>,
+        name: &str,
+        descriptor: LianaDescriptor,
+        data_dir: &DataDirectory,
+    ) -> Result<(), CommandError> {
+        let config = self.main.config.wallet_config(descriptor, data_dir);
+        config
+            .check()
+            .map_err(|e| CommandError::WalletSetup(e.to_string()))?;
+        let (bit, db) = setup_wallet(
+            &config,
+            name,
+            self.db()?,
+            self.connection.as_ref(),
+            &self.main.secp,
+        )
+        .map_err(|e| CommandError::WalletSetup(e.to_string()))?;
+
+        // Each wallet has its own notifier so the events may be subscribed to per wallet.
+        let notifier = Notifier::new(config.notify_hooks.clone());
+        let polled_wallet = PolledWallet::new(
+            bit.clone(),
+            db.clone(),
+            &config.main_descriptor,
+            notifier.clone(),
+        );
+        let control = DaemonControl::new(
+            config,
+            bit,
+            self.main.poller_sender.clone(),
+            db,
+            self.main.secp.clone(),
+            notifier,
+            self.main.signers.clone(),
+        );
+        self.polled
+            .lock()
+            .expect("Polled wallets lock poisoned")
+            .insert(name.to_string(), polled_wallet);
+        loaded.insert(name.to_string(), control);
+        log::info!("Loaded wallet '{}'.", name);
+
+        Ok(())
+    }
+
+    /// Load all the wallets stored in the database of the daemon, using their default data
+    /// directory. A wallet which fails to load is skipped.
+    pub(crate) fn load_all(&self) {
+        let names = match self.db.as_ref().map(|db| db.wallet_names()) {
+            Some(Ok(names)) => names,
+            Some(Err(e)) => {
+                log::error!("Error listing the wallets stored in database: '{}'", e);
+                return;
+            }
+            None => return,
+        };
+        for name in names {
+            if let Err(e) = self.load_wallet(name.clone()) {
+                log::error!("Error loading wallet '{}': '{}'", name, e);
+            }
+        }
+    }
+
+    /// Create a new wallet with this name and descriptor, and load it.
+    pub fn create_wallet(
+        &self,
+        name: String,
+        descriptor: LianaDescriptor,
+    ) -> Result<(), CommandError> {
+        if !is_valid_wallet_name(&name) {
+            return Err(CommandError::InvalidWalletName(name));
+        }
+        // Hold the lock for the whole setup so the same wallet can't be set up twice at once.
+        let mut loaded = self.loaded.lock().expect("Wallets lock poisoned");
+        let data_dir = self.data_directory(&name);
+        if loaded.contains_key(&name)
+            || self
+                .db()?
+                .wallet(&name)
+                .map_err(|e| CommandError::WalletSetup(e.to_string()))?
+                .is_some()
+            || data_dir.exists()
+        {
+            return Err(CommandError::WalletAlreadyExists(name));
+        }
+
+        self.load(&mut loaded, &name, descriptor, &data_dir)
+    }
+
+    /// Load an existing wallet stored in the database of the daemon, using its default data
+    /// directory.
+    pub fn load_wallet(&self, name: String) -> Result<(), CommandError> {
+        let data_dir = self.data_directory(&name);
+        self.load_wallet_from(name, &data_dir)
+    }
+
+    /// Load an existing wallet stored in the database of the daemon, using the given data
+    /// directory for the state of its Bitcoin backend.
+    pub fn load_wallet_from(
+        &self,
+        name: String,
+        data_dir: &DataDirectory,
+    ) -> Result<(), CommandError> {
+        if !is_valid_wallet_name(&name) {
+            return Err(CommandError::InvalidWalletName(name));
+        }
+        let mut loaded = self.loaded.lock().expect("Wallets lock poisoned");
+        if loaded.contains_key(&name) {
+            return Err(CommandError::WalletAlreadyLoaded(name));
+        }
+        let descriptor = self
+            .db()?
+            .wallet(&name)
+            .and_then(|db| db.map(|db| db.main_descriptor()).transpose())
+            .map_err(|e| CommandError::WalletSetup(e.to_string()))?
+            .ok_or_else(|| CommandError::WalletNotFound(name.clone()))?;
+        self.load(&mut loaded, &name, descriptor, data_dir)
+    }
+
+    /// Copy the main wallet of another (up to date) database into the database of the daemon under
+    /// this name, and load it using the given data directory for the state of its Bitcoin backend.
+    /// This is used to host a wallet which so far ran in its own daemon, in which case the data
+    /// directory is the one it used.
+    pub fn import_wallet(
+        &self,
+        name: String,
+        db_path: &path::Path,
+        data_dir: &DataDirectory,
+    ) -> Result<(), CommandError> {
+        if !is_valid_wallet_name(&name) {
+            return Err(CommandError::InvalidWalletName(name));
+        }
+        let mut loaded = self.loaded.lock().expect("Wallets lock poisoned");
+        let db = self.db()?;
+        if loaded.contains_key(&name)
+            || db
+                .wallet(&name)
+                .map_err(|e| CommandError::WalletSetup(e.to_string()))?
+                .is_some()
+        {
+            return Err(CommandError::WalletAlreadyExists(name));
+        }
+        let descriptor = db
+            .import_wallet(&name, db_path)
+            .and_then(|db| db.main_descriptor())
+            .map_err(|e| CommandError::WalletSetup(e.to_string()))?;
+        self.load(&mut loaded, &name, descriptor, data_dir)
+    }
+
+    /// Stop polling this wallet and remove it from the loaded wallets. It is kept in database and
+    /// may be loaded again later.
+    pub fn unload_wallet(&self, name: String) -> Result<(), CommandError> {
+        if name == MAIN_WALLET {
+            return Err(CommandError::MainWalletUnload);
+        }
+        let mut loaded = self.loaded.lock().expect("Wallets lock poisoned");
+        if loaded.remove(&name).is_none() {
+            return Err(CommandError::WalletNotFound(name));
+        }
+        self.polled
+            .lock()
+            .expect("Polled wallets lock poisoned")
+            .remove(&name);
+        log::info!("Unloaded wallet '{}'.", name);
+
+        Ok(())
+    }
+
+    /// List the loaded wallets, starting with the main one.
+    pub fn list_wallets(&self) -> ListWalletsResult {
+        let main = WalletEntry {
+            name: MAIN_WALLET.to_string(),
+            descriptor: self.main.config.main_descriptor.clone(),
+        };
+        let loaded = self.loaded.lock().expect("Wallets lock poisoned");
+        let wallets = std::iter::once(main)
+            .chain(loaded.iter().map(|(name, control)| WalletEntry {
+                name: name.clone(),
+                descriptor: control.config.main_descriptor.clone(),
+            }))
+            .collect();
+        ListWalletsResult { wallets }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalletEntry {
+    pub name: String,
+    pub descriptor: LianaDescriptor,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListWalletsResult {
+    pub wallets: Vec<WalletEntry>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wallet_name() {
+        assert!(is_valid_wallet_name("savings"));
+        assert!(is_valid_wallet_name("Company_Treasury-2"));
+        assert!(!is_valid_wallet_name(""));
+        assert!(!is_valid_wallet_name(MAIN_WALLET));
+        assert!(!is_valid_wallet_name("../main"));
+        assert!(!is_valid_wallet_name("my wallet"));
+        assert!(!is_valid_wallet_name("wallet/1"));
+        assert!(!is_valid_wallet_name(&"a".repeat(MAX_WALLET_NAME_LEN + 1)));
+        assert!(is_valid_wallet_name(&"a".repeat(MAX_WALLET_NAME_LEN)));
+    }
+}
@@ -3,7 +3,9 @@ from bip32.utils import _pubkey_to_fingerprint
 from bip380.descriptors import Descriptor
 from concurrent import futures
 from test_framework.bitcoind import Bitcoind
+from test_framework.cbf import Cbf
 from test_framework.electrs import Electrs
+from test_framework.esplora import Esplora
 from test_framework.lianad import Lianad
 from test_framework.signer import SingleSigner, MultiSigner
 from test_framework.utils import (
@@ -137,6 +139,20 @@ def bitcoin_backend(directory, bitcoind):
         electrs.startup()
         yield electrs
         electrs.cleanup()
+    elif BITCOIN_BACKEND_TYPE is BitcoinBackendType.Cbf:
+        cbf = Cbf(bitcoind)
+        yield cbf
+        cbf.cleanup()
+    elif BITCOIN_BACKEND_TYPE is BitcoinBackendType.Esplora:
+        esplora = Esplora(
+            esplora_dir=os.path.join(directory, "esplora"),
+            bitcoind_dir=bitcoind.bitcoin_dir,
+            bitcoind_rpcport=bitcoind.rpcport,
+            bitcoind_p2pport=bitcoind.p2pport,
+        )
+        esplora.startup()
+        yield esplora
+        esplora.cleanup()
     else:
         raise NotImplementedError
 
@@ -202,6 +218,61 @@ def lianad(bitcoin_backend, directory):
     lianad.cleanup()
 
 
+# The BIP39 test vector mnemonic, used for the hot signer loaded by lianad.
+HOT_SIGNER_MNEMONIC = " ".join(["abandon"] * 11 + ["about"])
+
+
+@pytest.fixture
+def lianad_hot_signer(bitcoin_backend, directory):
+    """A lianad whose primary key is a hot signer it can sign with, under a signing policy
+    allowing at most 0.01 BTC to be sent out of the wallet per transaction."""
+    datadir = os.path.join(directory, "lianad")
+    os.makedirs(datadir, exist_ok=True)
+
+    # Use the key derived from the mnemonic as primary key, for the spends to be signed
+    # both by the daemon and the test suite.
+    signer = SingleSigner(is_taproot=USE_TAPROOT)
+    seed = hashlib.pbkdf2_hmac(
+        "sha512", HOT_SIGNER_MNEMONIC.encode(), b"mnemonic", 2048
+    )
+    signer.primary_hd = BIP32.from_seed(seed, network="test")
+    prim_fingerprint = xpub_fingerprint(signer.primary_hd)
+    main_desc = Descriptor.from_str(
+        single_key_desc(
+            prim_fingerprint,
+            signer.primary_hd.get_xpub(),
+            xpub_fingerprint(signer.recovery_hd),
+            signer.recovery_hd.get_xpub(),
+            10,
+            is_taproot=USE_TAPROOT,
+        )
+    )
+
+    lianad = Lianad(
+        datadir,
+        signer,
+        main_desc,
+        bitcoin_backend,
+    )
+    mnemonics_dir = os.path.join(datadir, "regtest", "mnemonics")
+    os.makedirs(mnemonics_dir, exist_ok=True)
+    mnemonic_path = os.path.join(mnemonics_dir, f"mnemonic-{prim_fingerprint}.txt")
+    with open(mnemonic_path, "w") as f:
+        f.write(HOT_SIGNER_MNEMONIC)
+    with open(lianad.conf_file, "a") as f:
+        f.write("[signing_config.policy]\n")
+        f.write("max_tx_amount_sat = 1000000\n")
+
+    try:
+        lianad.start()
+        yield lianad
+    except Exception:
+        lianad.cleanup()
+        raise
+
+    lianad.cleanup()
+
+
 # This can currently only be used with Taproot if no signing is required.
 @pytest.fixture
 def lianad_with_deriv_paths(bitcoin_backend, directory):
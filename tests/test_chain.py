@@ -560,3 +560,71 @@ def test_spend_replacement(lianad, bitcoind):
     assert (
         lianad.rpc.listcoins([], [first_outpoints[0]])["coins"][0]["spend_info"] is None
     )
+
+
+def test_cbf_backend(directory, bitcoind):
+    """Test syncing using the compact block filters served by bitcoind, whatever the
+    backend used by the rest of the test suite."""
+    datadir = os.path.join(directory, "lianad_cbf")
+    os.makedirs(datadir, exist_ok=True)
+    signer = SingleSigner(is_taproot=USE_TAPROOT)
+    main_desc = Descriptor.from_str(
+        single_key_desc(
+            xpub_fingerprint(signer.primary_hd),
+            signer.primary_hd.get_xpub(),
+            xpub_fingerprint(signer.recovery_hd),
+            signer.recovery_hd.get_xpub(),
+            10,
+            is_taproot=USE_TAPROOT,
+        )
+    )
+    lianad = Lianad(datadir, signer, main_desc, Cbf(bitcoind))
+    lianad.start()
+
+    try:
+        wait_for(
+            lambda: lianad.rpc.getinfo()["block_height"]
+            == bitcoind.rpc.getblockcount()
+        )
+
+        # We only see a deposit once it's confirmed, as we don't have a mempool.
+        addr = lianad.rpc.getnewaddress()["address"]
+        txid = bitcoind.rpc.sendtoaddress(addr, 0.01)
+        bitcoind.generate_block(1, wait_for_mempool=txid)
+        wait_for(lambda: len(lianad.rpc.listcoins()["coins"]) == 1)
+        coin = lianad.rpc.listcoins()["coins"][0]
+        assert coin["block_height"] == bitcoind.rpc.getblockcount()
+
+        # Spending it broadcasts the transaction to bitcoind over P2P. We immediately
+        # see our coin as being spent.
+        destinations = {bitcoind.rpc.getnewaddress(): 500_000}
+        res = lianad.rpc.createspend(destinations, [coin["outpoint"]], 2)
+        spend_txid = sign_and_broadcast_psbt(lianad, PSBT.from_base64(res["psbt"]))
+        wait_for(lambda: spend_txid in bitcoind.rpc.getrawmempool())
+        spend_info = get_coin(lianad, coin["outpoint"])["spend_info"]
+        assert spend_info["txid"] == spend_txid and spend_info["height"] is None
+
+        # The spend gets confirmed. The change output is detected.
+        bitcoind.generate_block(1, wait_for_mempool=spend_txid)
+        spend_height = bitcoind.rpc.getblockcount()
+        wait_for(
+            lambda: get_coin(lianad, coin["outpoint"])["spend_info"]["height"]
+            == spend_height
+        )
+        wait_for(lambda: len(lianad.rpc.listcoins()["coins"]) == 2)
+
+        # A reorg is detected and the spend is confirmed again in the new chain.
+        bitcoind.simple_reorg(spend_height, shift=1)
+        lianad.wait_for_logs(
+            ["Block chain reorganization detected.", "Tip was rolled back."]
+        )
+        wait_for(
+            lambda: get_coin(lianad, coin["outpoint"])["spend_info"]["height"]
+            == spend_height + 1
+        )
+        wait_for(
+            lambda: lianad.rpc.getinfo()["block_height"]
+            == bitcoind.rpc.getblockcount()
+        )
+    finally:
+        lianad.cleanup()
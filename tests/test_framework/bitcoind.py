@@ -78,6 +78,9 @@ class Bitcoind(BitcoinBackend):
             # See https://github.com/bitcoin/bitcoin/blob/fa05ee0517d58b600f0ccad4c02c0734a23707d6/src/net.cpp#L1961.
             # h/t pythcoiner :)
             "peertimeout": 2 * 24 * 60 * 60,  # 2 days
+            # Serve compact block filters to lianad when it uses them as Bitcoin backend.
+            "blockfilterindex": 1,
+            "peerblockfilters": 1,
         }
         self.conf_file = os.path.join(bitcoin_dir, "bitcoin.conf")
         with open(self.conf_file, "w") as f:
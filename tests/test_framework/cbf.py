@@ -0,0 +1,25 @@
+from test_framework.utils import BitcoinBackend
+
+
+class Cbf(BitcoinBackend):
+    """Sync lianad using the compact block filters served by bitcoind over P2P.
+
+    There is no additional process to run: lianad connects directly to bitcoind.
+    """
+
+    def __init__(self, bitcoind):
+        self.bitcoind = bitcoind
+
+    def startup(self):
+        pass
+
+    def stop(self):
+        pass
+
+    def cleanup(self):
+        pass
+
+    def append_to_lianad_conf(self, conf_file):
+        with open(conf_file, "a") as f:
+            f.write("[cbf_config]\n")
+            f.write(f"peers = ['127.0.0.1:{self.bitcoind.p2pport}']\n")
@@ -0,0 +1,80 @@
+import logging
+import os
+
+from ephemeral_port_reserve import reserve
+from test_framework.utils import BitcoinBackend, TailableProc, ESPLORA_PATH, TIMEOUT
+
+
+class Esplora(BitcoinBackend):
+    """The Blockstream fork of electrs, which serves the Esplora REST API."""
+
+    def __init__(
+        self,
+        bitcoind_dir,
+        bitcoind_rpcport,
+        bitcoind_p2pport,
+        esplora_dir,
+        httpport=None,
+    ):
+        TailableProc.__init__(self, esplora_dir, verbose=False)
+
+        if httpport is None:
+            httpport = reserve()
+
+        # The Electrum and monitoring servers can't be deactivated. Configure their ports so
+        # they don't conflict with other instances when running tests in parallel.
+        electrum_port = reserve()
+        monitoring_port = reserve()
+
+        self.esplora_dir = esplora_dir
+        self.httpport = httpport
+
+        if not os.path.exists(esplora_dir):
+            os.makedirs(esplora_dir)
+
+        self.cmd_line = [
+            ESPLORA_PATH,
+            "-vvvv",
+            "--network",
+            "regtest",
+            "--daemon-dir",
+            bitcoind_dir,
+            "--daemon-rpc-addr",
+            f"127.0.0.1:{bitcoind_rpcport}",
+            "--db-dir",
+            esplora_dir,
+            "--http-addr",
+            f"127.0.0.1:{self.httpport}",
+            "--electrum-rpc-addr",
+            f"127.0.0.1:{electrum_port}",
+            "--monitoring-addr",
+            f"127.0.0.1:{monitoring_port}",
+            "--jsonrpc-import",
+        ]
+
+    def start(self):
+        TailableProc.start(self)
+        self.wait_for_log("REST server running on", timeout=TIMEOUT)
+        logging.info("Esplora started")
+
+    def startup(self):
+        try:
+            self.start()
+        except Exception:
+            self.stop()
+            raise
+
+    def stop(self):
+        return TailableProc.stop(self)
+
+    def cleanup(self):
+        try:
+            self.stop()
+        except Exception:
+            self.proc.kill()
+        self.proc.wait()
+
+    def append_to_lianad_conf(self, conf_file):
+        with open(conf_file, "a") as f:
+            f.write("[esplora_config]\n")
+            f.write(f"addr = 'http://127.0.0.1:{self.httpport}'\n")
@@ -27,6 +27,8 @@ LIANAD_PATH = os.getenv("LIANAD_PATH", DEFAULT_MS_PATH)
 class BitcoinBackendType(str, enum.Enum):
     Bitcoind = "bitcoind"
     Electrs = "electrs"
+    Cbf = "cbf"
+    Esplora = "esplora"
 
 
 DEFAULT_BITCOIN_BACKEND_TYPE = "bitcoind"
@@ -37,6 +39,8 @@ DEFAULT_BITCOIND_PATH = "bitcoind"
 BITCOIND_PATH = os.getenv("BITCOIND_PATH", DEFAULT_BITCOIND_PATH)
 DEFAULT_ELECTRS_PATH = "electrs"
 ELECTRS_PATH = os.getenv("ELECTRS_PATH", DEFAULT_ELECTRS_PATH)
+DEFAULT_ESPLORA_PATH = "electrs-esplora"
+ESPLORA_PATH = os.getenv("ESPLORA_PATH", DEFAULT_ESPLORA_PATH)
 OLD_LIANAD_PATH = os.getenv("OLD_LIANAD_PATH", None)
 IS_NOT_BITCOIND_24 = bool(int(os.getenv("IS_NOT_BITCOIND_24", True)))
 USE_TAPROOT = bool(
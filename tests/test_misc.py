@@ -1,11 +1,18 @@
+import base64
+import json
 import logging
+import os
 import pytest
 import shutil
 import time
+import urllib.error
+import urllib.request
+
+from ephemeral_port_reserve import reserve
 
 from fixtures import *
 from test_framework.authproxy import JSONRPCException
-from test_framework.serializations import PSBT
+from test_framework.serializations import COutPoint, CTxIn, PSBT, PSBTMap
 from test_framework.utils import (
     BitcoinBackendType,
     BITCOIN_BACKEND_TYPE,
@@ -19,6 +26,7 @@ from test_framework.utils import (
     USE_TAPROOT,
 )
 
+from http.server import BaseHTTPRequestHandler, HTTPServer
 from threading import Thread
 
 
@@ -387,3 +395,221 @@ def test_retry_on_workqueue_exceeded(lianad, bitcoind, executor):
     # We should have retried the request to bitcoind, which should now succeed along with the call.
     # This just checks the response we get is sane, nothing particular with this field.
     assert "block_height" in f_liana.result(TIMEOUT)
+
+
+def http_rpc(port, auth, method, params=None):
+    """Call the lianad JSONRPC server over HTTP using the given 'user:password' credentials."""
+    request = urllib.request.Request(
+        f"http://127.0.0.1:{port}",
+        data=json.dumps(
+            {"jsonrpc": "2.0", "id": 0, "method": method, "params": params or []}
+        ).encode(),
+        headers={
+            "Authorization": f"Basic {base64.b64encode(auth.encode()).decode()}",
+            "Content-Type": "application/json",
+        },
+    )
+    with urllib.request.urlopen(request) as response:
+        return json.loads(response.read())
+
+
+def test_http_rpc(lianad, bitcoind):
+    """Test the JSONRPC server over HTTP, its authentication and the per-user allow-lists."""
+    port = reserve()
+    lianad.stop()
+    with open(lianad.conf_file, "a") as f:
+        f.write("[http_rpc_config]\n")
+        f.write(f"addr = '127.0.0.1:{port}'\n")
+        f.write("[[http_rpc_config.users]]\n")
+        f.write("auth = 'admin:admin_pass'\n")
+        f.write("[[http_rpc_config.users]]\n")
+        f.write("auth = 'reader:reader_pass'\n")
+        f.write("allowed_methods = ['getinfo', 'listcoins']\n")
+    lianad.start()
+
+    # A cookie file was created, its credentials grant access to all methods.
+    cookie_path = os.path.join(lianad.datadir, "regtest", "lianad_rpc_cookie")
+    with open(cookie_path) as f:
+        cookie = f.read()
+    assert cookie.startswith("__cookie__:")
+    info = http_rpc(port, cookie, "getinfo")["result"]
+    assert info["descriptors"] == lianad.rpc.getinfo()["descriptors"]
+    assert "address" in http_rpc(port, cookie, "getnewaddress")["result"]
+
+    # So do the ones of a user without an allow-list.
+    assert "result" in http_rpc(port, "admin:admin_pass", "getnewaddress")
+
+    # A user with an allow-list can only call these methods.
+    assert "result" in http_rpc(port, "reader:reader_pass", "getinfo")
+    assert http_rpc(port, "reader:reader_pass", "listcoins")["result"] == {"coins": []}
+    for method, params in [
+        ("getnewaddress", []),
+        ("delspendtx", ["00" * 32]),
+        ("broadcastspend", ["00" * 32]),
+    ]:
+        with pytest.raises(urllib.error.HTTPError) as e:
+            http_rpc(port, "reader:reader_pass", method, params)
+        assert e.value.code == 403
+        error = json.loads(e.value.read())["error"]
+        assert error["message"] == f"Method not allowed: {method}"
+
+    # Invalid credentials are rejected.
+    for auth in ["reader:admin_pass", "unknown:reader_pass", "__cookie__:"]:
+        with pytest.raises(urllib.error.HTTPError) as e:
+            http_rpc(port, auth, "getinfo")
+        assert e.value.code == 401
+
+    # The cookie file is removed when the daemon stops.
+    lianad.stop()
+    assert not os.path.exists(cookie_path)
+    lianad.start()
+
+
+def test_payjoin(lianad, bitcoind, executor):
+    """Test receiving a payjoin from bitcoind through the HTTP server."""
+    port = reserve()
+    lianad.stop()
+    with open(lianad.conf_file, "a") as f:
+        f.write("[http_rpc_config]\n")
+        f.write(f"addr = '127.0.0.1:{port}'\n")
+        f.write("payjoin = true\n")
+    lianad.start()
+
+    # Get a coin to contribute to the payjoin.
+    txid = bitcoind.rpc.sendtoaddress(lianad.rpc.getnewaddress()["address"], 0.02)
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(lambda: len(lianad.rpc.listcoins(["confirmed"])["coins"]) == 1)
+    coin = lianad.rpc.listcoins(["confirmed"])["coins"][0]
+
+    # Have bitcoind create and sign an Original PSBT paying to one of our addresses.
+    addr = lianad.rpc.getnewaddress()["address"]
+    res = bitcoind.rpc.walletcreatefundedpsbt([], [{addr: 0.01}])
+    original = bitcoind.rpc.walletprocesspsbt(res["psbt"])["psbt"]
+
+    def post_original():
+        request = urllib.request.Request(
+            f"http://127.0.0.1:{port}/payjoin?v=1&minfeerate=1",
+            data=original.encode(),
+            headers={"Content-Type": "text/plain"},
+        )
+        with urllib.request.urlopen(request) as response:
+            return response.read().decode()
+
+    # The proposal is stored as a Spend transaction, and is sent back once we signed it.
+    f_proposal = executor.submit(post_original)
+    wait_for(lambda: len(lianad.rpc.listspendtxs()["spend_txs"]) == 1)
+    proposal = PSBT.from_base64(lianad.rpc.listspendtxs()["spend_txs"][0]["psbt"])
+    assert coin["outpoint"] in [
+        f"{txin.prevout.hash:064x}:{txin.prevout.n}" for txin in proposal.tx.vin
+    ]
+
+    # The same Original sent again while its proposal is pending is rejected right away.
+    with pytest.raises(urllib.error.HTTPError) as e:
+        post_original()
+    assert json.loads(e.value.read())["errorCode"] == "unavailable"
+
+    signed_proposal = lianad.signer.sign_psbt(proposal)
+    lianad.rpc.updatespend(signed_proposal.to_base64())
+    proposal = f_proposal.result(TIMEOUT)
+
+    # bitcoind signs its inputs again and broadcasts the payjoin. It spends our coin,
+    # and the payment now includes its value minus the fee for our input.
+    res = bitcoind.rpc.walletprocesspsbt(proposal)
+    payjoin_txid = bitcoind.rpc.sendrawtransaction(
+        bitcoind.rpc.finalizepsbt(res["psbt"])["hex"]
+    )
+    bitcoind.generate_block(1, wait_for_mempool=payjoin_txid)
+    wait_for(
+        lambda: lianad.rpc.listcoins([], [coin["outpoint"]])["coins"][0]["spend_info"]
+        is not None
+    )
+    payment = next(
+        c
+        for c in lianad.rpc.listcoins(["confirmed"])["coins"]
+        if c["outpoint"].startswith(payjoin_txid)
+    )
+    assert int(0.01 * COIN) < payment["amount"] < int(0.03 * COIN)
+
+
+def test_payjoin_send(lianad, bitcoind):
+    """Test sending a payjoin to a stand-in receiver backed by bitcoind's wallet."""
+    txid = bitcoind.rpc.sendtoaddress(lianad.rpc.getnewaddress()["address"], 0.02)
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(lambda: len(lianad.rpc.listcoins(["confirmed"])["coins"]) == 1)
+    coin = lianad.rpc.listcoins(["confirmed"])["coins"][0]
+
+    # Create and sign a Spend transaction paying a payment request from bitcoind.
+    dest = bitcoind.rpc.getnewaddress()
+    dest_spk = bytes.fromhex(bitcoind.rpc.getaddressinfo(dest)["scriptPubKey"])
+    res = lianad.rpc.createspend({dest: 1_000_000}, [], 2)
+    spend_psbt = lianad.signer.sign_psbt(PSBT.from_base64(res["psbt"]))
+    lianad.rpc.updatespend(spend_psbt.to_base64())
+    spend_txid = spend_psbt.tx.txid().hex()
+
+    # A payjoin can only be sent to a payment request with a secure endpoint, and for a
+    # Spend transaction which pays it.
+    with pytest.raises(RpcError, match="has no payjoin endpoint"):
+        lianad.rpc.sendpayjoin(spend_txid, f"bitcoin:{dest}?amount=0.01")
+    with pytest.raises(RpcError, match="must use HTTPS"):
+        lianad.rpc.sendpayjoin(
+            spend_txid, f"bitcoin:{dest}?amount=0.01&pj=http://example.com/pj"
+        )
+    other_dest = bitcoind.rpc.getnewaddress()
+    with pytest.raises(RpcError, match="does not contain the payment"):
+        lianad.rpc.sendpayjoin(
+            spend_txid, f"bitcoin:{other_dest}?amount=0.01&pj=https://example.com/pj"
+        )
+
+    # The stand-in receiver adds one of bitcoind's coins to the Original PSBT, pays
+    # the fee for it out of the payment and signs it.
+    receiver_coin = bitcoind.rpc.listunspent()[0]
+    queries = []
+
+    class PayjoinReceiver(BaseHTTPRequestHandler):
+        def do_POST(self):
+            queries.append(self.path)
+            body = self.rfile.read(int(self.headers["Content-Length"]))
+            proposal = PSBT.from_base64(body.decode())
+            tx = proposal.tx
+            prevout = COutPoint(int(receiver_coin["txid"], 16), receiver_coin["vout"])
+            tx.vin.append(CTxIn(prevout, nSequence=tx.vin[0].nSequence))
+            payment = next(txo for txo in tx.vout if txo.scriptPubKey == dest_spk)
+            payment.nValue += int(receiver_coin["amount"] * COIN) - 1_000
+            proposal.g.map[0] = tx.serialize_without_witness()
+            proposal.i.append(PSBTMap())
+            proposal.make_blank()
+            res = bitcoind.rpc.walletprocesspsbt(
+                proposal.to_base64(), True, "ALL", False
+            )
+            self.send_response(200)
+            self.send_header("Content-Type", "text/plain")
+            self.end_headers()
+            self.wfile.write(res["psbt"].encode())
+
+        def log_message(self, *args):
+            pass
+
+    port = reserve()
+    server = HTTPServer(("127.0.0.1", port), PayjoinReceiver)
+    Thread(target=server.serve_forever, daemon=True).start()
+    bip21 = f"bitcoin:{dest}?amount=0.01&pj=http://127.0.0.1:{port}/pj"
+    try:
+        res = lianad.rpc.sendpayjoin(spend_txid, bip21)
+    finally:
+        server.shutdown()
+    assert queries[0].startswith("/pj?v=1&additionalfeeoutputindex=")
+    assert "disableoutputsubstitution=true" in queries[0]
+
+    # The checked proposal is stored as a new Spend, which we sign again and broadcast.
+    payjoin_psbt = PSBT.from_base64(res["psbt"])
+    assert len(payjoin_psbt.tx.vin) == len(spend_psbt.tx.vin) + 1
+    assert len(lianad.rpc.listspendtxs()["spend_txs"]) == 2
+    signed_psbt = lianad.signer.sign_psbt(payjoin_psbt)
+    lianad.rpc.updatespend(signed_psbt.to_base64())
+    payjoin_txid = signed_psbt.tx.txid().hex()
+    lianad.rpc.broadcastspend(payjoin_txid)
+    bitcoind.generate_block(1, wait_for_mempool=payjoin_txid)
+    wait_for(
+        lambda: lianad.rpc.listcoins([], [coin["outpoint"]])["coins"][0]["spend_info"]
+        is not None
+    )
@@ -1,23 +1,31 @@
+import json
 import pytest
 import random
 import re
 import time
 
 from fixtures import *
+from test_framework.bitcoind import BitcoindRpcInterface
 from test_framework.serializations import (
+    CTxOut,
     PSBT,
     PSBT_IN_PARTIAL_SIG,
     PSBT_IN_NON_WITNESS_UTXO,
+    PSBT_IN_WITNESS_UTXO,
+    from_binary,
 )
 from test_framework.utils import (
     wait_for,
     COIN,
     RpcError,
     get_txid,
+    BITCOIN_BACKEND_TYPE,
+    BitcoinBackendType,
     spend_coins,
     sign_and_broadcast,
     sign_and_broadcast_psbt,
     USE_TAPROOT,
+    UnixSocket,
 )
 
 MAX_DERIV = 2**31 - 1
@@ -42,6 +50,37 @@ def test_getinfo(lianad):
     assert res["change_index"] == 0
 
 
+def test_export_descriptor(lianad):
+    desc = lianad.rpc.getinfo()["descriptors"]["main"]
+
+    res = lianad.rpc.exportdescriptor("coldcard", "Savings")
+    assert res["format"] == "coldcard"
+    assert "# Name: Savings" in res["content"]
+    assert desc in res["content"]
+
+    # The name defaults to "Liana".
+    res = lianad.rpc.exportdescriptor("bip388")
+    policy = json.loads(res["content"])
+    assert policy["name"] == "Liana"
+    assert policy["descriptor_template"].count("@0/") >= 1
+    assert all(key in desc for key in policy["keys_info"])
+
+    res = lianad.rpc.exportdescriptor("specter", "Savings")
+    specter = json.loads(res["content"])
+    assert specter["label"] == "Savings"
+    assert "<0;1>" not in specter["descriptor"]
+
+    res = lianad.rpc.exportdescriptor("bsms")
+    lines = res["content"].splitlines()
+    assert lines[0] == "BSMS 1.0"
+    assert lines[1] == desc
+    addr = lianad.rpc.listaddresses(0, 1)["addresses"][0]["receive"]
+    assert lines[3] == addr
+
+    with pytest.raises(RpcError, match="Invalid 'format' parameter."):
+        lianad.rpc.exportdescriptor("electrum")
+
+
 def test_update_derivation_indexes(lianad):
     info = lianad.rpc.getinfo()
     assert info["receive_index"] == 0
@@ -149,6 +188,46 @@ def test_update_derivation_indexes(lianad):
     assert ret["change"] == last_change
 
 
+@pytest.mark.skipif(
+    BITCOIN_BACKEND_TYPE is BitcoinBackendType.Cbf,
+    reason="Address discovery isn't supported with compact block filters.",
+)
+def test_discover_addresses(lianad, bitcoind):
+    # Nothing was used past our indexes.
+    res = lianad.rpc.discoveraddresses()
+    assert res == {"receive": 0, "change": 0, "rescan": False}
+    with pytest.raises(RpcError, match="Invalid gap limit"):
+        lianad.rpc.discoveraddresses(0)
+    if BITCOIN_BACKEND_TYPE is BitcoinBackendType.Bitcoind:
+        # bitcoind only tracks the addresses within the range of the imported descriptors.
+        with pytest.raises(RpcError, match="only tracks the addresses up to"):
+            lianad.rpc.discoveraddresses(2_000)
+
+    # Receive a coin on an address far past the gap limit, as if it had been handed out by
+    # another instance of this wallet.
+    addr = lianad.rpc.listaddresses(250, 1)["addresses"][0]["receive"]
+    txid = bitcoind.rpc.sendtoaddress(addr, 0.1)
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(
+        lambda: lianad.rpc.getinfo()["block_height"] == bitcoind.rpc.getblockcount()
+    )
+    assert len(lianad.rpc.listcoins()["coins"]) == 0
+
+    # It's not found with a gap limit too small, but it is with a larger one.
+    res = lianad.rpc.discoveraddresses(100)
+    assert res == {"receive": 0, "change": 0, "rescan": False}
+    res = lianad.rpc.discoveraddresses(gap_limit=300)
+    assert res["receive"] == 250 and res["change"] == 0 and res["rescan"]
+    assert lianad.rpc.getinfo()["receive_index"] == 250
+    assert "address_discovery" not in lianad.rpc.getinfo()
+
+    # The rescan finds the coin.
+    wait_for(lambda: lianad.rpc.getinfo()["rescan_progress"] is None)
+    wait_for(lambda: len(lianad.rpc.listcoins()["coins"]) == 1)
+    assert lianad.rpc.listcoins()["coins"][0]["outpoint"][:64] == txid
+    assert lianad.rpc.getnewaddress()["derivation_index"] == 251
+
+
 def test_getaddress(lianad):
     res = lianad.rpc.getnewaddress()
     assert "address" in res
@@ -795,6 +874,135 @@ def test_create_spend(lianad, bitcoind):
         lianad.rpc.createspend(destinations, [imma_coin["outpoint"]], 1)
 
 
+def test_create_spend_batch(lianad, bitcoind):
+    """Create a Spend from an ordered list of payments, possibly with labels."""
+    addr = lianad.rpc.getnewaddress()["address"]
+    txid = bitcoind.rpc.sendtoaddress(addr, 0.1)
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(lambda: len(lianad.rpc.listcoins()["coins"]) == 1)
+
+    # Pay twice to the same address, once using a BIP21 URI.
+    dest_addr = bitcoind.rpc.getnewaddress()
+    other_addr = bitcoind.rpc.getnewaddress()
+    destinations = [
+        {"address": dest_addr, "amount": 200_000, "label": "Invoice 1"},
+        {"address": other_addr, "amount": 300_000},
+        f"bitcoin:{dest_addr}?amount=0.004&label=Invoice%202",
+    ]
+    res = lianad.rpc.createspend(destinations, [], 2)
+    spend_psbt = PSBT.from_base64(res["psbt"])
+    vout = spend_psbt.tx.vout
+    assert [o.nValue for o in vout[:3]] == [200_000, 300_000, 400_000]
+    assert vout[0].scriptPubKey == vout[2].scriptPubKey != vout[1].scriptPubKey
+
+    # The outputs are labelled once the Spend is stored.
+    spend_txid = spend_psbt.tx.txid().hex()
+    outpoints = [f"{spend_txid}:{i}" for i in range(3)]
+    assert lianad.rpc.getlabels(outpoints)["labels"] == {}
+    lianad.rpc.updatespend(res["psbt"])
+    assert lianad.rpc.getlabels(outpoints)["labels"] == {
+        outpoints[0]: "Invoice 1",
+        outpoints[2]: "Invoice 2",
+    }
+
+    # Invalid destinations are rejected.
+    for dest in (
+        f"bitcoin:{dest_addr}?label=No%20amount",
+        f"bitcoin:{dest_addr}?amount=0.01&req-pop=liana",
+        {"address": dest_addr},
+        {"address": dest_addr, "amount": 200_000, "label": "x" * 101},
+    ):
+        with pytest.raises(RpcError, match="'destinations' parameter"):
+            lianad.rpc.createspend([dest], [], 2)
+
+
+def test_freeze_coins(lianad, bitcoind):
+    """Frozen coins are not selected automatically, but can be spent explicitly."""
+    addr = lianad.rpc.getnewaddress()["address"]
+    txids = [bitcoind.rpc.sendtoaddress(addr, amount) for amount in (0.01, 0.02)]
+    bitcoind.generate_block(1, wait_for_mempool=txids)
+    wait_for(lambda: len(lianad.rpc.listcoins(["confirmed"])["coins"]) == 2)
+    coins = lianad.rpc.listcoins()["coins"]
+    assert all(not c["is_frozen"] for c in coins)
+    big_coin = next(c for c in coins if c["amount"] == 2_000_000)
+    small_coin = next(c for c in coins if c["amount"] == 1_000_000)
+
+    # Unknown coins can't be frozen.
+    unknown_op = f"{'00' * 32}:0"
+    with pytest.raises(RpcError, match=f"Unknown outpoint '{unknown_op}'"):
+        lianad.rpc.freezecoins([big_coin["outpoint"], unknown_op])
+    with pytest.raises(RpcError, match="Invalid 'outpoints' parameter."):
+        lianad.rpc.freezecoins(["not_an_outpoint"])
+
+    # Freeze the biggest coin. It is reported as such and can be filtered on.
+    lianad.rpc.freezecoins([big_coin["outpoint"]])
+    frozen = lianad.rpc.listcoins([], [], True)["coins"]
+    assert [c["outpoint"] for c in frozen] == [big_coin["outpoint"]]
+    assert frozen[0]["is_frozen"]
+    not_frozen = lianad.rpc.listcoins([], [], False)["coins"]
+    assert [c["outpoint"] for c in not_frozen] == [small_coin["outpoint"]]
+
+    # Coin selection won't use it, even if it's needed.
+    destinations = {bitcoind.rpc.getnewaddress(): 1_500_000}
+    assert "missing" in lianad.rpc.createspend(destinations, [], 2)
+    destinations = {bitcoind.rpc.getnewaddress(): 500_000}
+    res = lianad.rpc.createspend(destinations, [], 2)
+    spend_psbt = PSBT.from_base64(res["psbt"])
+    assert len(spend_psbt.tx.vin) == 1
+    assert (
+        f"{spend_psbt.tx.vin[0].prevout.hash:064x}:{spend_psbt.tx.vin[0].prevout.n}"
+        == small_coin["outpoint"]
+    )
+
+    # It can still be spent explicitly, with a warning.
+    res = lianad.rpc.createspend(destinations, [big_coin["outpoint"]], 2)
+    assert any(f"Coin {big_coin['outpoint']} is frozen" in w for w in res["warnings"])
+
+    # Once unfrozen it is available again to coin selection.
+    lianad.rpc.unfreezecoins([big_coin["outpoint"]])
+    assert all(not c["is_frozen"] for c in lianad.rpc.listcoins()["coins"])
+    destinations = {bitcoind.rpc.getnewaddress(): 1_500_000}
+    assert "psbt" in lianad.rpc.createspend(destinations, [], 2)
+
+
+def test_estimatefee(lianad, bitcoind):
+    # The confirmation target must be within bounds.
+    for conf_target in (0, 1_009):
+        with pytest.raises(RpcError, match="Invalid confirmation target"):
+            lianad.rpc.estimatefee(conf_target)
+    with pytest.raises(RpcError, match="Invalid value .* for 'mode' parameter"):
+        lianad.rpc.estimatefee(2, "fast")
+
+    # Get a coin to spend.
+    addr = lianad.rpc.getnewaddress()["address"]
+    txid = bitcoind.rpc.sendtoaddress(addr, 0.01)
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(lambda: len(lianad.rpc.listcoins()["coins"]) == 1)
+    outpoints = [c["outpoint"] for c in lianad.rpc.listcoins()["coins"]]
+    destinations = {bitcoind.rpc.getnewaddress(): 200_000}
+
+    # Exactly one of the feerate and the confirmation target must be given.
+    with pytest.raises(RpcError, match="Only one of 'feerate' and 'conf_target'"):
+        lianad.rpc.createspend(destinations, outpoints, 2, None, 6)
+    with pytest.raises(RpcError, match="Missing 'feerate' or 'conf_target'"):
+        lianad.rpc.createspend(destinations, outpoints, None)
+
+    if BITCOIN_BACKEND_TYPE in (BitcoinBackendType.Bitcoind, BitcoinBackendType.Cbf):
+        # There isn't enough data on regtest for bitcoind to give an estimate, and our peers
+        # don't tell us anything about the fee market when using compact block filters.
+        with pytest.raises(RpcError, match="No feerate estimate available"):
+            lianad.rpc.estimatefee(6)
+        with pytest.raises(RpcError, match="No feerate estimate available"):
+            lianad.rpc.createspend(destinations, outpoints, None, None, 6)
+    else:
+        # Electrs falls back to the minimum relay fee.
+        res = lianad.rpc.estimatefee(6, "economical")
+        assert res["feerate"] >= 1
+        assert res["blocks"] == 6
+        res = lianad.rpc.createspend(destinations, outpoints, None, None, 6)
+        assert "psbt" in res
+
+
 def test_list_spend(lianad, bitcoind):
     # Start by creating two conflicting Spend PSBTs. The first one will have a change
     # output but not the second one.
@@ -964,6 +1172,141 @@ def test_broadcast_spend(lianad, bitcoind):
     lianad.rpc.broadcastspend(txid)
 
 
+def test_sign_spend_disabled(lianad):
+    # Signing must be enabled in the configuration.
+    with pytest.raises(RpcError, match="Signing is not enabled.*"):
+        lianad.rpc.signspend("00" * 32, "aabbccdd")
+
+
+def test_sign_spend(lianad_hot_signer, bitcoind):
+    lianad = lianad_hot_signer
+    addr = lianad.rpc.getnewaddress()["address"]
+    txid = bitcoind.rpc.sendtoaddress(addr, 0.05)
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(lambda: len(lianad.rpc.listcoins()["coins"]) == 1)
+    outpoints = [c["outpoint"] for c in lianad.rpc.listcoins()["coins"]]
+    fingerprint = xpub_fingerprint(lianad.signer.primary_hd)
+
+    # We can only sign a stored Spend, with a signer the daemon knows about.
+    res = lianad.rpc.createspend({bitcoind.rpc.getnewaddress(): 500_000}, outpoints, 2)
+    txid = PSBT.from_base64(res["psbt"]).tx.txid().hex()
+    with pytest.raises(RpcError, match="Unknown spend transaction.*"):
+        lianad.rpc.signspend(txid, fingerprint)
+    lianad.rpc.updatespend(res["psbt"])
+    with pytest.raises(RpcError, match="No hot signer with fingerprint 'aabbccdd'."):
+        lianad.rpc.signspend(txid, "aabbccdd")
+
+    # A spend over the maximum amount per transaction is refused.
+    big_res = lianad.rpc.createspend(
+        {bitcoind.rpc.getnewaddress(): 2_000_000}, outpoints, 2
+    )
+    big_txid = PSBT.from_base64(big_res["psbt"]).tx.txid().hex()
+    lianad.rpc.updatespend(big_res["psbt"])
+    with pytest.raises(RpcError, match="Refusing to sign: .*per transaction."):
+        lianad.rpc.signspend(big_txid, fingerprint)
+    lianad.rpc.delspendtx(big_txid)
+
+    # The value of our coins is taken from our database, a PSBT lying about it is refused.
+    lying_res = lianad.rpc.createspend(
+        {bitcoind.rpc.getnewaddress(): 400_000}, outpoints, 2
+    )
+    lying_psbt = PSBT.from_base64(lying_res["psbt"])
+    lying_txid = lying_psbt.tx.txid().hex()
+    prev_txo = from_binary(CTxOut, lying_psbt.i[0].map[PSBT_IN_WITNESS_UTXO])
+    prev_txo.nValue = 1_000
+    lying_psbt.i[0].map[PSBT_IN_WITNESS_UTXO] = prev_txo.serialize()
+    lianad.rpc.updatespend(lying_psbt.to_base64())
+    with pytest.raises(
+        RpcError, match="Refusing to sign: .*previous output value for coin .*"
+    ):
+        lianad.rpc.signspend(lying_txid, fingerprint)
+    lianad.rpc.delspendtx(lying_txid)
+
+    # The spend within the policy gets signed, and the signed PSBT stored.
+    signed_psbt = PSBT.from_base64(lianad.rpc.signspend(txid, fingerprint)["psbt"])
+    assert signed_psbt.tx.txid().hex() == txid
+    spend = lianad.rpc.listspendtxs()["spend_txs"][0]
+    assert spend["psbt"] == signed_psbt.to_base64()
+
+    # It is signed by the primary key and can be broadcast.
+    lianad.rpc.broadcastspend(txid)
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+
+
+@pytest.mark.skipif(
+    BITCOIN_BACKEND_TYPE is BitcoinBackendType.Cbf,
+    reason="Querying the UTXO set isn't supported with compact block filters.",
+)
+def test_proof_of_reserves(lianad, bitcoind):
+    message = "Proof of reserves for the 2026 audit"
+    with pytest.raises(RpcError, match="No unspent coin to prove the control of."):
+        lianad.rpc.createproofofreserves(message)
+
+    # Receive two coins.
+    for amount in (0.1, 0.2):
+        addr = lianad.rpc.getnewaddress()["address"]
+        txid = bitcoind.rpc.sendtoaddress(addr, amount)
+        bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(lambda: len(lianad.rpc.listcoins(["confirmed"])["coins"]) == 2)
+    coins = lianad.rpc.listcoins()["coins"]
+
+    # The proof spends all the confirmed coins along with the commitment input, and can't be
+    # broadcast.
+    psbt = PSBT.from_base64(lianad.rpc.createproofofreserves(message)["psbt"])
+    assert len(psbt.tx.vin) == 3
+    assert len(psbt.tx.vout) == 1
+    assert psbt.tx.vout[0].nValue == 30_000_000
+    with pytest.raises(RpcError, match="Invalid signatures for coin at .*"):
+        lianad.rpc.verifyproofofreserves(psbt.to_base64(), message)
+    signed_psbt = lianad.signer.sign_psbt(psbt)
+
+    # Once signed, it proves the control of the coins for this message only.
+    res = lianad.rpc.verifyproofofreserves(signed_psbt.to_base64(), message)
+    assert res["amount"] == 30_000_000
+    assert sorted(c["outpoint"] for c in res["coins"]) == sorted(
+        c["outpoint"] for c in coins
+    )
+    with pytest.raises(RpcError, match="The proof does not commit to this message."):
+        lianad.rpc.verifyproofofreserves(signed_psbt.to_base64(), "Another message")
+
+    # A proof may be restricted to some of the coins.
+    outpoint = coins[0]["outpoint"]
+    psbt = PSBT.from_base64(
+        lianad.rpc.createproofofreserves(message, [outpoint])["psbt"]
+    )
+    assert len(psbt.tx.vin) == 2
+    signed_psbt = lianad.signer.sign_psbt(psbt)
+    res = lianad.rpc.verifyproofofreserves(signed_psbt.to_base64(), message)
+    assert [c["outpoint"] for c in res["coins"]] == [outpoint]
+
+    # Once the coin is spent, the proof isn't valid anymore.
+    spend_coins(lianad, bitcoind, [coins[0]])
+    with pytest.raises(RpcError, match=f"Coin at '{outpoint}' is not unspent."):
+        lianad.rpc.verifyproofofreserves(signed_psbt.to_base64(), message)
+
+
+@pytest.mark.skipif(
+    BITCOIN_BACKEND_TYPE is not BitcoinBackendType.Cbf,
+    reason="Only compact block filters can't look up other Scripts than the wallet's.",
+)
+def test_cbf_unsupported_queries(lianad, bitcoind):
+    with pytest.raises(RpcError, match="Looking for used addresses is not supported"):
+        lianad.rpc.discoveraddresses()
+    # The address discovery isn't reported as ongoing.
+    assert "address_discovery" not in lianad.rpc.getinfo()
+
+    # Create a proof of reserves for a coin of ours. It can't be verified.
+    addr = lianad.rpc.getnewaddress()["address"]
+    txid = bitcoind.rpc.sendtoaddress(addr, 0.01)
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(lambda: len(lianad.rpc.listcoins()["coins"]) == 1)
+    message = "Proof of reserves"
+    res = lianad.rpc.createproofofreserves(message)
+    psbt = lianad.signer.sign_psbt(PSBT.from_base64(res["psbt"]))
+    with pytest.raises(RpcError, match="Querying the UTxO set is not supported"):
+        lianad.rpc.verifyproofofreserves(psbt.to_base64(), message)
+
+
 # Use a descriptor that includes hardened derivation paths so that we can check
 # there is no problem regarding the use of `h` and `'`.
 def test_start_rescan_does_not_error(lianad_with_deriv_paths, bitcoind):
@@ -1239,6 +1582,48 @@ def test_listtransactions(lianad, bitcoind):
     assert bit_txids == txids
 
 
+def test_accounting_report(lianad, bitcoind):
+    assert lianad.rpc.accountingreport() == {"method": "fifo", "entries": []}
+    with pytest.raises(RpcError, match="Invalid 'method' parameter"):
+        lianad.rpc.accountingreport("hifo")
+
+    # Receive 1BTC then 2BTC, and spend the latter entirely.
+    receive_txids = []
+    for amount in (1, 2):
+        addr = lianad.rpc.getnewaddress()["address"]
+        txid = bitcoind.rpc.sendtoaddress(addr, amount)
+        bitcoind.generate_block(1, wait_for_mempool=txid)
+        receive_txids.append(txid)
+    wait_for(lambda: len(lianad.rpc.listcoins(["confirmed"])["coins"]) == 2)
+    coin = next(
+        c for c in lianad.rpc.listcoins()["coins"] if c["amount"] == 2 * COIN
+    )
+    spend_txid = get_txid(spend_coins(lianad, bitcoind, [coin]))
+    bitcoind.generate_block(1, wait_for_mempool=spend_txid)
+    wait_for(lambda: len(lianad.rpc.listcoins(["spent"])["coins"]) == 1)
+
+    # With FIFO the spend disposes of the first coin received, with LIFO of the last one.
+    report = lianad.rpc.accountingreport()
+    assert report == lianad.rpc.accountingreport("fifo")
+    entries = report["entries"]
+    assert [e["txid"] for e in entries] == receive_txids + [spend_txid]
+    assert [e["kind"] for e in entries] == ["receive", "receive", "send"]
+    assert [e["amount"] for e in entries[:2]] == [COIN, 2 * COIN]
+    spend = entries[2]
+    disposed = -spend["amount"]
+    assert spend["fee"] > 0 and spend["unmatched"] == 0
+    assert [(l["txid"], l["amount"]) for l in spend["lots"]] == [
+        (receive_txids[0], COIN),
+        (receive_txids[1], disposed - COIN),
+    ]
+    # The daemon doesn't know about fiat prices.
+    assert all("price" not in e and "gain" not in e for e in entries)
+    spend = lianad.rpc.accountingreport(method="lifo")["entries"][2]
+    assert [(l["txid"], l["amount"]) for l in spend["lots"]] == [
+        (receive_txids[1], disposed)
+    ]
+
+
 def test_create_recovery(lianad, bitcoind):
     """Test the sweep of coins that are available through the timelocked path."""
     # Generate blocks in order to test locktime set correctly.
@@ -1354,6 +1739,94 @@ def test_create_recovery(lianad, bitcoind):
     sign_and_broadcast(lianad, bitcoind, reco_psbt, recovery=True)
 
 
+def test_watchonly_descriptors(lianad, bitcoind):
+    """Test importing an additional watch-only descriptor and sweeping its coins."""
+    # Create a separate bitcoind wallet whose descriptor we'll watch from lianad.
+    bitcoind.node_rpc.createwallet("watchonly-source")
+    source_wallet = BitcoindRpcInterface(
+        bitcoind.bitcoin_dir, "regtest", bitcoind.rpcport, wallet="watchonly-source"
+    )
+    watchonly_desc = next(
+        d["desc"]
+        for d in source_wallet.listdescriptors()["descriptors"]
+        if d["desc"].startswith("wpkh(") and not d["internal"]
+    )
+
+    # We can't import the main descriptor, nor a descriptor with an insane timestamp.
+    tip_timestamp = bitcoind.rpc.getblockheader(bitcoind.rpc.getbestblockhash())["time"]
+    main_desc = lianad.rpc.getinfo()["descriptors"]["main"]
+    with pytest.raises(RpcError, match="This is the main descriptor."):
+        lianad.rpc.importwatchonlydescriptor(main_desc, tip_timestamp - 1)
+    with pytest.raises(RpcError, match="Insane timestamp.*"):
+        lianad.rpc.importwatchonlydescriptor(watchonly_desc, tip_timestamp + 60 * 60)
+
+    # Import it and check it's reported.
+    lianad.rpc.importwatchonlydescriptor(watchonly_desc, tip_timestamp - 1)
+    wait_for(lambda: lianad.rpc.getinfo()["rescan_progress"] is None)
+    assert len(lianad.rpc.getinfo()["descriptors"]["watchonly"]) == 1
+    with pytest.raises(RpcError, match="This descriptor is already imported."):
+        lianad.rpc.importwatchonlydescriptor(watchonly_desc, tip_timestamp - 1)
+
+    # There is nothing to sweep yet.
+    with pytest.raises(RpcError, match="No watch-only coin to sweep."):
+        lianad.rpc.sweepwatchonly(2)
+
+    # Receive a couple of coins on the watch-only descriptor. They are not part of
+    # the wallet's coins.
+    destinations = {
+        source_wallet.getnewaddress(): 0.1,
+        source_wallet.getnewaddress(): 0.2,
+    }
+    txid = bitcoind.rpc.sendmany("", destinations)
+    wait_for(lambda: len(lianad.rpc.listcoins()["watchonly_coins"]) == 2)
+    assert all(
+        c["block_height"] is None for c in lianad.rpc.listcoins()["watchonly_coins"]
+    )
+    assert len(lianad.rpc.listcoins()["coins"]) == 0
+    # Unconfirmed coins are only swept if explicitly specified.
+    with pytest.raises(RpcError, match="No watch-only coin to sweep."):
+        lianad.rpc.sweepwatchonly(2)
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(
+        lambda: all(
+            c["block_height"] is not None
+            for c in lianad.rpc.listcoins()["watchonly_coins"]
+        )
+    )
+    watchonly_coins = lianad.rpc.listcoins()["watchonly_coins"]
+    assert sorted(c["amount"] for c in watchonly_coins) == [
+        int(0.1 * COIN),
+        int(0.2 * COIN),
+    ]
+    watchonly_descs = lianad.rpc.getinfo()["descriptors"]["watchonly"]
+    assert all(c["descriptor"] == watchonly_descs[0] for c in watchonly_coins)
+    assert len(lianad.rpc.listcoins(["confirmed"])["watchonly_coins"]) == 2
+
+    # We can sweep a single coin by specifying it.
+    res = lianad.rpc.sweepwatchonly(2, [watchonly_coins[0]["outpoint"]])
+    psbt = PSBT.from_base64(res["psbt"])
+    assert len(psbt.tx.vin) == 1
+    assert len(psbt.tx.vout) == 1
+
+    # Sweep all of them to the wallet, signing with the source wallet.
+    res = lianad.rpc.sweepwatchonly(2)
+    psbt = PSBT.from_base64(res["psbt"])
+    assert len(psbt.tx.vin) == 2
+    assert len(psbt.tx.vout) == 1
+    assert int(0.2999 * COIN) < int(psbt.tx.vout[0].nValue) < int(0.3 * COIN)
+    signed_psbt = source_wallet.walletprocesspsbt(res["psbt"])["psbt"]
+    tx_hex = bitcoind.rpc.finalizepsbt(signed_psbt)["hex"]
+    txid = bitcoind.rpc.sendrawtransaction(tx_hex)
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+
+    # The swept coins are now a regular coin of the wallet.
+    wait_for(lambda: len(lianad.rpc.listcoins()["watchonly_coins"]) == 0)
+    wait_for(lambda: len(lianad.rpc.listcoins()["coins"]) == 1)
+    coin = lianad.rpc.listcoins()["coins"][0]
+    assert coin["outpoint"] == f"{txid}:0"
+    assert coin["block_height"] is not None
+
+
 def test_labels(lianad, bitcoind):
     """Test the creation and updating of labels."""
     # We can set a label for an address.
@@ -1551,6 +2024,55 @@ def test_labels_bip329(lianad, bitcoind):
         assert not label_found(name, batch3)
 
 
+def test_import_labels_bip329(lianad, bitcoind):
+    addr = lianad.rpc.getnewaddress()["address"]
+    txid = bitcoind.rpc.sendtoaddress(addr, 1)
+    wait_for(lambda: len(lianad.rpc.listcoins()["coins"]) == 1)
+    outpoint = lianad.rpc.listcoins()["coins"][0]["outpoint"]
+    lianad.rpc.updatelabels({addr: "existing"})
+
+    records = [
+        {"type": "tx", "ref": txid, "label": "tx"},
+        {"type": "addr", "ref": addr, "label": "addr"},
+        {"type": "output", "ref": outpoint, "label": "coin", "spendable": True},
+        {
+            "type": "xpub",
+            "ref": "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8",
+            "label": "xpub",
+        },
+        {"type": "tx", "ref": txid},
+    ]
+    jsonl = "\n".join(json.dumps(r) for r in records) + "\nnot json\n"
+
+    # By default, the existing labels are kept.
+    res = lianad.rpc.importlabelsbip329(jsonl)
+    assert res["imported"] == 2
+    assert [(r["line"], r["type"], r["reason"]) for r in res["skipped"]] == [
+        (2, "addr", "conflict"),
+        (4, "xpub", "unsupported"),
+        (5, "tx", "missing_label"),
+        (6, None, "invalid"),
+    ]
+    labels = lianad.rpc.getlabels([txid, addr, outpoint])["labels"]
+    assert labels == {txid: "tx", addr: "existing", outpoint: "coin"}
+
+    # They can be overwritten instead.
+    res = lianad.rpc.importlabelsbip329(jsonl, "overwrite")
+    assert res["imported"] == 3
+    assert len(res["skipped"]) == 3
+    assert lianad.rpc.getlabels([addr])["labels"] == {addr: "addr"}
+
+    # The exported labels can be imported back.
+    exported = lianad.rpc.getlabelsbip329(0, 100)["labels"]
+    res = lianad.rpc.importlabelsbip329(
+        "\n".join(json.dumps(l) for l in exported), "overwrite"
+    )
+    assert res == {"imported": 3, "skipped": []}
+
+    with pytest.raises(RpcError, match="Invalid value .* for 'conflict' parameter"):
+        lianad.rpc.importlabelsbip329(jsonl, "replace")
+
+
 def test_rbfpsbt_bump_fee(lianad, bitcoind):
     """Test the use of RBF to bump the fee of a transaction."""
 
@@ -1960,3 +2482,122 @@ def test_rbfpsbt_cancel(lianad, bitcoind):
             for c in lianad.rpc.listcoins([], [rbf_1_outpoint])["coins"]
         )
     )
+
+
+def test_subscribe(lianad, bitcoind):
+    """Test the stream of events on the RPC socket."""
+    # Only some events may be subscribed to.
+    with pytest.raises(RpcError, match="Invalid value .* in 'events' parameter."):
+        lianad.rpc.subscribe(["coin_received", "new_block"])
+
+    sock = UnixSocket(lianad.rpc.socket_path)
+    stream = sock.sock.makefile("rb")
+    req = {
+        "jsonrpc": "2.0",
+        "id": 0,
+        "method": "subscribe",
+        "params": [["coin_received", "coin_confirmed", "tip_changed"]],
+    }
+    sock.sendall(json.dumps(req).encode() + b"\n")
+    assert json.loads(stream.readline()) == {"jsonrpc": "2.0", "id": 0, "result": {}}
+
+    def next_event():
+        notification = json.loads(stream.readline())
+        assert notification["jsonrpc"] == "2.0" and notification["method"] == "event"
+        return notification["params"]
+
+    # We get notified of a new coin.
+    addr = lianad.rpc.getnewaddress()["address"]
+    txid = bitcoind.rpc.sendtoaddress(addr, 0.01)
+    event = next_event()
+    assert event["type"] == "coin_received"
+    assert event["outpoint"].startswith(txid)
+    assert event["address"] == addr
+    assert event["amount"] == 1_000_000
+    assert event["is_change"] is False
+
+    # Then of its confirmation, along with the new tip.
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    event = next_event()
+    assert event["type"] == "coin_confirmed"
+    assert event["outpoint"].startswith(txid)
+    assert event["height"] == bitcoind.rpc.getblockcount()
+    event = next_event()
+    assert event == {
+        "type": "tip_changed",
+        "height": bitcoind.rpc.getblockcount(),
+        "hash": bitcoind.rpc.getbestblockhash(),
+    }
+
+    # The subscription does not prevent other clients from using the RPC interface.
+    coins = lianad.rpc.listcoins()["coins"]
+    assert any(c["outpoint"].startswith(txid) for c in coins)
+    sock.close()
+
+
+def test_multiple_wallets(lianad, bitcoind):
+    """Create, use, unload and load an additional wallet."""
+    signer = SingleSigner(is_taproot=USE_TAPROOT)
+    desc = single_key_desc(
+        xpub_fingerprint(signer.primary_hd),
+        signer.primary_hd.get_xpub(),
+        xpub_fingerprint(signer.recovery_hd),
+        signer.recovery_hd.get_xpub(),
+        10,
+        is_taproot=USE_TAPROOT,
+    )
+    desc = str(Descriptor.from_str(desc))
+
+    # At first only the main wallet is loaded.
+    main_desc = lianad.rpc.getinfo()["descriptors"]["main"]
+    assert lianad.rpc.listwallets()["wallets"] == [
+        {"name": "main", "descriptor": main_desc}
+    ]
+
+    # Create an additional wallet. It can be selected by name, and the main wallet either by
+    # name or by default.
+    lianad.rpc.createwallet("savings", desc)
+    savings_desc = lianad.rpc.getinfo(wallet="savings")["descriptors"]["main"]
+    assert savings_desc != main_desc
+    assert lianad.rpc.getinfo(wallet="main")["descriptors"]["main"] == main_desc
+    assert lianad.rpc.listwallets()["wallets"] == [
+        {"name": "main", "descriptor": main_desc},
+        {"name": "savings", "descriptor": savings_desc},
+    ]
+
+    # The coins of each wallet are tracked separately.
+    addr = lianad.rpc.getnewaddress(wallet="savings")["address"]
+    txid = bitcoind.rpc.sendtoaddress(addr, 0.01)
+    wait_for(lambda: len(lianad.rpc.listcoins(wallet="savings")["coins"]) == 1)
+    assert lianad.rpc.listcoins(wallet="savings")["coins"][0]["outpoint"].startswith(
+        txid
+    )
+    assert len(lianad.rpc.listcoins()["coins"]) == 0
+
+    # Invalid requests are rejected.
+    with pytest.raises(RpcError, match="Wallet 'unknown' not found."):
+        lianad.rpc.getinfo(wallet="unknown")
+    with pytest.raises(RpcError, match="Wallet 'savings' already exists."):
+        lianad.rpc.createwallet("savings", desc)
+    with pytest.raises(RpcError, match="Invalid wallet name"):
+        lianad.rpc.createwallet("../savings", desc)
+    with pytest.raises(RpcError, match="The main wallet can't be unloaded."):
+        lianad.rpc.unloadwallet("main")
+
+    # An unloaded wallet can't be used until it is loaded again.
+    lianad.rpc.unloadwallet("savings")
+    assert len(lianad.rpc.listwallets()["wallets"]) == 1
+    with pytest.raises(RpcError, match="Wallet 'savings' not found."):
+        lianad.rpc.listcoins(wallet="savings")
+    with pytest.raises(RpcError, match="Wallet 'savings' not found."):
+        lianad.rpc.unloadwallet("savings")
+    lianad.rpc.loadwallet("savings")
+    with pytest.raises(RpcError, match="Wallet 'savings' is already loaded."):
+        lianad.rpc.loadwallet("savings")
+    assert len(lianad.rpc.listcoins(wallet="savings")["coins"]) == 1
+
+    # The additional wallets are loaded again upon restart.
+    lianad.stop()
+    lianad.start()
+    assert len(lianad.rpc.listwallets()["wallets"]) == 2
+    assert len(lianad.rpc.listcoins(wallet="savings")["coins"]) == 1
@@ -607,6 +607,54 @@ def test_sweep(lianad, bitcoind):
     assert balance == int((0.2 + 0.1 + 0.3) * COIN)
 
 
+def test_cpfpbump(lianad, bitcoind):
+    """We can speed up the confirmation of a payment to us by spending it at a higher feerate."""
+    # Receive a coin paying the minimum feerate.
+    addr = lianad.rpc.getnewaddress()["address"]
+    deposit = bitcoind.rpc.sendtoaddress(
+        addr, 0.01, "", "", False, True, None, "unset", False, 1
+    )
+    wait_for(lambda: len(lianad.rpc.listcoins(["unconfirmed"])["coins"]) == 1)
+    outpoint = lianad.rpc.listcoins(["unconfirmed"])["coins"][0]["outpoint"]
+
+    # The deposit already pays for 1 sat/vb.
+    with pytest.raises(RpcError, match="already pay 1 sats/vb"):
+        lianad.rpc.cpfpbump([outpoint], 1)
+
+    # Create a child paying for the deposit to reach the target feerate.
+    feerate = 10
+    res = lianad.rpc.cpfpbump([outpoint], feerate)
+    anc_vsize = bitcoind.rpc.getmempoolentry(deposit)["ancestorsize"]
+    anc_fees = int(bitcoind.rpc.getmempoolentry(deposit)["fees"]["ancestor"] * COIN)
+    additional_fee = additional_fees(anc_vsize, anc_fees, feerate)
+    assert res["warnings"] == [
+        "CPFP: an unconfirmed input was selected. The current transaction fee "
+        f"was increased by {additional_fee} sats to make the average feerate of "
+        "both the input and current transaction equal to the selected feerate."
+    ]
+    child_psbt = PSBT.from_base64(res["psbt"])
+    assert len(child_psbt.tx.vin) == 1 and len(child_psbt.o) == 1
+    child_txid = sign_and_broadcast_psbt(lianad, child_psbt)
+    child_entry = bitcoind.rpc.getmempoolentry(child_txid)
+    assert (
+        child_entry["fees"]["ancestor"] * COIN // child_entry["ancestorsize"]
+        == feerate
+    )
+
+    # The coin can't be bumped anymore once spent, or once confirmed.
+    wait_for(
+        lambda: lianad.rpc.listcoins([], [outpoint])["coins"][0]["spend_info"]
+        is not None
+    )
+    with pytest.raises(RpcError, match="is already spent"):
+        lianad.rpc.cpfpbump([outpoint], 20)
+    bitcoind.generate_block(1, wait_for_mempool=child_txid)
+    wait_for(lambda: len(lianad.rpc.listcoins(["confirmed"])["coins"]) == 1)
+    change_outpoint = lianad.rpc.listcoins(["confirmed"])["coins"][0]["outpoint"]
+    with pytest.raises(RpcError, match="is already confirmed"):
+        lianad.rpc.cpfpbump([change_outpoint], 20)
+
+
 @pytest.mark.parametrize("feerate", [1, 2])
 @pytest.mark.skipif(not USE_TAPROOT, reason="This tests a Taproot-specific bug.")
 def test_tr_multisig_2_of_2_feerate_is_met(feerate, lianad_multisig_2_of_2, bitcoind):
@@ -643,3 +691,45 @@ def test_tr_multisig_2_of_2_feerate_is_met(feerate, lianad_multisig_2_of_2, bitc
     spend_weight = res["weight"]
     assert spend_weight == 646
     assert spend_fee == math.ceil(646.0 / 4.0) * feerate
+
+
+def test_spending_policy(lianad, bitcoind):
+    """Test the spends created and broadcast by lianad must abide by its spending policy."""
+    lianad.stop()
+    with open(lianad.conf_file, "a") as f:
+        f.write("[spending_policy]\n")
+        f.write("require_labels = true\n")
+        f.write("min_confirmations = 1\n")
+        f.write("[[spending_policy.window_limits]]\n")
+        f.write("max_amount_sat = 1000000\n")
+        f.write("window_hours = 24\n")
+    lianad.start()
+
+    # Unconfirmed coins may not be spent.
+    destinations = {
+        lianad.rpc.getnewaddress()["address"]: 0.05,
+        lianad.rpc.getnewaddress()["address"]: 0.05,
+    }
+    txid = bitcoind.rpc.sendmany("", destinations)
+    wait_for(lambda: len(lianad.rpc.listcoins(["unconfirmed"])["coins"]) == 2)
+    outpoint = lianad.rpc.listcoins()["coins"][0]["outpoint"]
+    dest_addr = bitcoind.rpc.getnewaddress()
+    with pytest.raises(RpcError, match=".*has 0 confirmations.*"):
+        lianad.rpc.createspend({dest_addr: 600_000}, [outpoint], 2)
+    bitcoind.generate_block(1, wait_for_mempool=txid)
+    wait_for(lambda: len(lianad.rpc.listcoins(["confirmed"])["coins"]) == 2)
+
+    # The payments must be labelled.
+    with pytest.raises(RpcError, match=".*must be labelled.*"):
+        lianad.rpc.createspend({dest_addr: 600_000}, [], 2)
+    destinations = [{"address": dest_addr, "amount": 600_000, "label": "Invoice 1"}]
+    res = lianad.rpc.createspend(destinations, [], 2)
+    psbt = PSBT.from_base64(res["psbt"])
+    lianad.rpc.updatespend(res["psbt"])
+    assert lianad.rpc.checkspend(psbt.tx.txid().hex())["violations"] == []
+    sign_and_broadcast_psbt(lianad, psbt)
+
+    # The spends broadcast count towards the limit over the last 24 hours.
+    destinations[0]["label"] = "Invoice 2"
+    with pytest.raises(RpcError, match=".*already broadcast over the last 24 hours.*"):
+        lianad.rpc.createspend(destinations, [], 2)